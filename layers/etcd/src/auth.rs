@@ -0,0 +1,936 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use prost::Message;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sekas_client::{WriteBatchRequest, WriteBuilder};
+use tokio::sync::Mutex;
+use tonic::{Request, Response, Status};
+
+use crate::etcd::authpb::{self, permission};
+use crate::etcd::v3::{auth_server, *};
+use crate::kv::{response_header, scan_range, to_revision};
+use crate::store::Store;
+
+type Result<T> = std::result::Result<T, Status>;
+
+/// The metadata key carrying the token of etcd requests.
+const TOKEN_KEY: &str = "token";
+/// The name of the root user and the root role.
+const ROOT: &str = "root";
+
+/// The key of the auth enabled flag in the auth collection.
+const ENABLED_KEY: &[u8] = b"enabled";
+/// The prefix of users, which are keyed by the user name.
+const USER_PREFIX: &[u8] = b"user/";
+/// The prefix of roles, which are keyed by the role name.
+const ROLE_PREFIX: &[u8] = b"role/";
+
+/// The interval to reload the users and roles, so that the changes from other
+/// proxies are observed.
+const AUTH_CACHE_TTL: Duration = Duration::from_secs(1);
+/// The TTL of tokens, it is extended once the token is used, like the simple
+/// token of etcd.
+const TOKEN_TTL: Duration = Duration::from_secs(300);
+const TOKEN_LEN: usize = 16;
+const SALT_LEN: usize = 16;
+
+/// The marker attached to the extensions of requests by the sekas server if
+/// the requests are issued with the token of a sekas superuser, they are
+/// treated as the etcd root user.
+#[derive(Debug, Clone, Copy)]
+pub struct Superuser;
+
+/// The permissions of etcd are mapped onto the sekas permission model, in
+/// which a higher level implies the lower ones. So the write-only permission
+/// of etcd also grants read, as the write permission on a sekas database does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Permission {
+    None,
+    Read,
+    Write,
+}
+
+impl From<permission::Type> for Permission {
+    fn from(perm_type: permission::Type) -> Self {
+        match perm_type {
+            permission::Type::Read => Permission::Read,
+            permission::Type::Write | permission::Type::Readwrite => Permission::Write,
+        }
+    }
+}
+
+/// The permission granted on the keys in `[start, end)`, the `end` is `None`
+/// if the range is unbounded.
+#[derive(Debug, Clone)]
+pub(crate) struct KeyPermission {
+    start: Vec<u8>,
+    end: Option<Vec<u8>>,
+    permission: Permission,
+}
+
+impl KeyPermission {
+    fn new(perm: &authpb::Permission) -> Self {
+        let (start, end) = scan_range(&perm.key, &perm.range_end);
+        KeyPermission { start, end, permission: perm.perm_type().into() }
+    }
+
+    fn covers(&self, start: &[u8], end: Option<&[u8]>) -> bool {
+        self.start.as_slice() <= start
+            && match (self.end.as_deref(), end) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(limit), Some(end)) => end <= limit,
+            }
+    }
+}
+
+/// Who issues an etcd request.
+pub(crate) enum Identity {
+    /// The authentication is disabled, all requests are allowed.
+    Anyone,
+    Root,
+    User {
+        name: String,
+        roles: Vec<String>,
+        permissions: Vec<KeyPermission>,
+    },
+}
+
+impl Identity {
+    /// Check whether the permission on the keys in range is granted. A range is
+    /// allowed only if it is covered by a single permission of the roles.
+    pub(crate) fn check_range(
+        &self,
+        key: &[u8],
+        range_end: &[u8],
+        required: Permission,
+    ) -> Result<()> {
+        let Identity::User { permissions, .. } = self else {
+            return Ok(());
+        };
+        let (start, end) = scan_range(key, range_end);
+        let granted = permissions
+            .iter()
+            .filter(|perm| perm.covers(&start, end.as_deref()))
+            .map(|perm| perm.permission)
+            .max()
+            .unwrap_or(Permission::None);
+        if granted < required {
+            return Err(permission_denied());
+        }
+        Ok(())
+    }
+
+    /// Check the permissions of the compared keys and the ops of txn.
+    pub(crate) fn check_txn(&self, request: &TxnRequest) -> Result<()> {
+        use request_op::Request as Op;
+
+        for compare in &request.compare {
+            self.check_range(&compare.key, &compare.range_end, Permission::Read)?;
+        }
+        for op in request.success.iter().chain(&request.failure) {
+            match &op.request {
+                Some(Op::RequestRange(req)) => {
+                    self.check_range(&req.key, &req.range_end, Permission::Read)?
+                }
+                Some(Op::RequestPut(req)) => self.check_range(&req.key, &[], Permission::Write)?,
+                Some(Op::RequestDeleteRange(req)) => {
+                    self.check_range(&req.key, &req.range_end, Permission::Write)?
+                }
+                Some(Op::RequestTxn(req)) => self.check_txn(req)?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the request is issued by the root, it is required to
+    /// manage the users and roles.
+    pub(crate) fn check_admin(&self) -> Result<()> {
+        match self {
+            Identity::User { .. } => Err(permission_denied()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The end user the requests to sekas are forwarded for, so that the etcd
+    /// users are limited by the client quotas of sekas. They are named apart
+    /// from the sekas users.
+    pub(crate) fn end_user(&self) -> Option<String> {
+        match self {
+            Identity::User { name, .. } => Some(format!("etcd/{name}")),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_user(&self) -> bool {
+        matches!(self, Identity::User { .. })
+    }
+
+    fn is_named(&self, user: &str) -> bool {
+        matches!(self, Identity::User { name, .. } if name == user)
+    }
+
+    fn has_role(&self, role: &str) -> bool {
+        matches!(self, Identity::User { roles, .. } if roles.iter().any(|r| r == role))
+    }
+}
+
+/// The users and roles, with the versions of their records.
+#[derive(Default)]
+struct AuthState {
+    enabled: bool,
+    enabled_version: Option<u64>,
+    users: BTreeMap<String, (authpb::User, u64)>,
+    roles: BTreeMap<String, (authpb::Role, u64)>,
+    /// The latest version of the records.
+    revision: u64,
+}
+
+impl AuthState {
+    fn identity(&self, name: &str) -> Option<Identity> {
+        let (user, _) = self.users.get(name)?;
+        if user.roles.iter().any(|role| role == ROOT) {
+            return Some(Identity::Root);
+        }
+        let permissions = user
+            .roles
+            .iter()
+            .filter_map(|role| self.roles.get(role))
+            .flat_map(|(role, _)| role.key_permission.iter().map(KeyPermission::new))
+            .collect();
+        Some(Identity::User { name: name.to_owned(), roles: user.roles.clone(), permissions })
+    }
+
+    fn user(&self, name: &str) -> Result<&authpb::User> {
+        self.users.get(name).map(|(user, _)| user).ok_or_else(user_not_found)
+    }
+
+    fn role(&self, name: &str) -> Result<&authpb::Role> {
+        self.roles.get(name).map(|(role, _)| role).ok_or_else(role_not_found)
+    }
+
+    fn header(&self) -> Option<ResponseHeader> {
+        response_header(to_revision(self.revision))
+    }
+}
+
+/// The changes of the users and roles.
+enum Change {
+    Enable(bool),
+    PutUser(authpb::User),
+    DeleteUser(String),
+    PutRole(authpb::Role),
+    DeleteRole(String),
+}
+
+/// The users and roles cached by this proxy, and the tokens issued by it.
+#[derive(Default)]
+pub(crate) struct AuthCache {
+    state: Mutex<Option<(Instant, Arc<AuthState>)>>,
+    /// The user name and the deadline of tokens.
+    tokens: std::sync::Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl AuthCache {
+    fn issue_token(&self, name: &str) -> String {
+        let token: String =
+            rand::thread_rng().sample_iter(&Alphanumeric).take(TOKEN_LEN).map(char::from).collect();
+        let now = Instant::now();
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|_, (_, deadline)| now < *deadline);
+        tokens.insert(token.clone(), (name.to_owned(), now + TOKEN_TTL));
+        token
+    }
+
+    fn verify_token(&self, token: &str) -> Option<String> {
+        let now = Instant::now();
+        let mut tokens = self.tokens.lock().unwrap();
+        let (name, deadline) = tokens.get(token).cloned()?;
+        if deadline <= now {
+            tokens.remove(token);
+            return None;
+        }
+        tokens.insert(token.to_owned(), (name.clone(), now + TOKEN_TTL));
+        Some(name)
+    }
+
+    fn revoke_tokens(&self, name: &str) {
+        self.tokens.lock().unwrap().retain(|_, (user, _)| user != name);
+    }
+
+    async fn invalidate(&self) {
+        *self.state.lock().await = None;
+    }
+}
+
+/// The etcd auth service. The users and roles are stored in a system
+/// collection, and the range permissions of roles are mapped onto the sekas
+/// permission model, see [`Permission`].
+///
+/// The tokens are issued and verified by each proxy, the clients have to
+/// authenticate again once the token is rejected by another proxy. If the
+/// authentication of sekas is enabled, the etcd authentication is always
+/// enabled, and the requests issued by the sekas superusers are treated as
+/// the etcd root user.
+pub struct Auth {
+    store: Arc<Store>,
+}
+
+impl Auth {
+    pub(crate) fn new(store: Arc<Store>) -> Self {
+        Auth { store }
+    }
+
+    async fn admin_state<T>(&self, request: &Request<T>) -> Result<Arc<AuthState>> {
+        identify(&self.store, request).await?.check_admin()?;
+        cached_state(&self.store).await
+    }
+
+    fn is_enabled(&self, state: &AuthState) -> bool {
+        state.enabled || self.store.auth_required()
+    }
+}
+
+#[tonic::async_trait]
+impl auth_server::Auth for Auth {
+    /// AuthEnable enables authentication.
+    async fn auth_enable(
+        &self,
+        request: Request<AuthEnableRequest>,
+    ) -> Result<Response<AuthEnableResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let revision = update(&self.store, |state| {
+            let Some((root, _)) = state.users.get(ROOT) else {
+                return Err(Status::failed_precondition("etcdserver: root user does not exist"));
+            };
+            if !root.roles.iter().any(|role| role == ROOT) {
+                return Err(Status::failed_precondition(
+                    "etcdserver: root user does not have root role",
+                ));
+            }
+            Ok(if state.enabled { vec![] } else { vec![Change::Enable(true)] })
+        })
+        .await?;
+        Ok(Response::new(AuthEnableResponse { header: response_header(revision) }))
+    }
+
+    /// AuthDisable disables authentication.
+    async fn auth_disable(
+        &self,
+        request: Request<AuthDisableRequest>,
+    ) -> Result<Response<AuthDisableResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        if self.store.auth_required() {
+            return Err(Status::failed_precondition(
+                "etcdserver: authentication is required by the sekas cluster",
+            ));
+        }
+        let revision = update(&self.store, |state| {
+            Ok(if state.enabled { vec![Change::Enable(false)] } else { vec![] })
+        })
+        .await?;
+        Ok(Response::new(AuthDisableResponse { header: response_header(revision) }))
+    }
+
+    /// AuthStatus displays authentication status.
+    async fn auth_status(
+        &self,
+        _request: Request<AuthStatusRequest>,
+    ) -> Result<Response<AuthStatusResponse>> {
+        let state = cached_state(&self.store).await?;
+        Ok(Response::new(AuthStatusResponse {
+            header: state.header(),
+            enabled: self.is_enabled(&state),
+            auth_revision: state.revision,
+        }))
+    }
+
+    /// Authenticate processes an authenticate request.
+    async fn authenticate(
+        &self,
+        request: Request<AuthenticateRequest>,
+    ) -> Result<Response<AuthenticateResponse>> {
+        let request = request.into_inner();
+        let state = cached_state(&self.store).await?;
+        if !self.is_enabled(&state) {
+            return Err(Status::failed_precondition("etcdserver: authentication is not enabled"));
+        }
+        let authenticated = state
+            .users
+            .get(&request.name)
+            .map(|(user, _)| {
+                let no_password =
+                    user.options.as_ref().map(|opts| opts.no_password).unwrap_or_default();
+                !no_password && verify_password(&request.password, &user.password)
+            })
+            .unwrap_or_default();
+        if !authenticated {
+            return Err(Status::invalid_argument(
+                "etcdserver: authentication failed, invalid user ID or password",
+            ));
+        }
+        let token = self.store.auth_cache().issue_token(&request.name);
+        Ok(Response::new(AuthenticateResponse { header: state.header(), token }))
+    }
+
+    /// UserAdd adds a new user. User name cannot be empty.
+    async fn user_add(
+        &self,
+        request: Request<AuthUserAddRequest>,
+    ) -> Result<Response<AuthUserAddResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        if request.name.is_empty() {
+            return Err(Status::invalid_argument("etcdserver: user name is empty"));
+        }
+        let no_password = request.options.as_ref().map(|opts| opts.no_password).unwrap_or_default();
+        let password = new_password(&request.password, &request.hashed_password, no_password)?;
+        let revision = update(&self.store, |state| {
+            if state.users.contains_key(&request.name) {
+                return Err(Status::failed_precondition("etcdserver: user name already exists"));
+            }
+            Ok(vec![Change::PutUser(authpb::User {
+                name: request.name.clone().into_bytes(),
+                password: password.clone(),
+                roles: vec![],
+                options: request.options.clone(),
+            })])
+        })
+        .await?;
+        Ok(Response::new(AuthUserAddResponse { header: response_header(revision) }))
+    }
+
+    /// UserGet gets detailed user information.
+    async fn user_get(
+        &self,
+        request: Request<AuthUserGetRequest>,
+    ) -> Result<Response<AuthUserGetResponse>> {
+        let identity = identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        if !identity.is_named(&request.name) {
+            identity.check_admin()?;
+        }
+        let state = cached_state(&self.store).await?;
+        let user = state.user(&request.name)?;
+        Ok(Response::new(AuthUserGetResponse { header: state.header(), roles: user.roles.clone() }))
+    }
+
+    /// UserList gets a list of all users.
+    async fn user_list(
+        &self,
+        request: Request<AuthUserListRequest>,
+    ) -> Result<Response<AuthUserListResponse>> {
+        let state = self.admin_state(&request).await?;
+        let users = state.users.keys().cloned().collect();
+        Ok(Response::new(AuthUserListResponse { header: state.header(), users }))
+    }
+
+    /// UserDelete deletes a specified user.
+    async fn user_delete(
+        &self,
+        request: Request<AuthUserDeleteRequest>,
+    ) -> Result<Response<AuthUserDeleteResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let revision = update(&self.store, |state| {
+            if self.is_enabled(state) && request.name == ROOT {
+                return Err(invalid_auth_management());
+            }
+            state.user(&request.name)?;
+            Ok(vec![Change::DeleteUser(request.name.clone())])
+        })
+        .await?;
+        self.store.auth_cache().revoke_tokens(&request.name);
+        Ok(Response::new(AuthUserDeleteResponse { header: response_header(revision) }))
+    }
+
+    /// UserChangePassword changes the password of a specified user.
+    async fn user_change_password(
+        &self,
+        request: Request<AuthUserChangePasswordRequest>,
+    ) -> Result<Response<AuthUserChangePasswordResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let password = new_password(&request.password, &request.hashed_password, false)?;
+        let revision = update(&self.store, |state| {
+            let mut user = state.user(&request.name)?.clone();
+            user.password = password.clone();
+            Ok(vec![Change::PutUser(user)])
+        })
+        .await?;
+        self.store.auth_cache().revoke_tokens(&request.name);
+        Ok(Response::new(AuthUserChangePasswordResponse { header: response_header(revision) }))
+    }
+
+    /// UserGrant grants a role to a specified user.
+    async fn user_grant_role(
+        &self,
+        request: Request<AuthUserGrantRoleRequest>,
+    ) -> Result<Response<AuthUserGrantRoleResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let revision = update(&self.store, |state| {
+            let mut user = state.user(&request.user)?.clone();
+            if request.role != ROOT {
+                state.role(&request.role)?;
+            }
+            if user.roles.contains(&request.role) {
+                return Ok(vec![]);
+            }
+            user.roles.push(request.role.clone());
+            user.roles.sort_unstable();
+            Ok(vec![Change::PutUser(user)])
+        })
+        .await?;
+        Ok(Response::new(AuthUserGrantRoleResponse { header: response_header(revision) }))
+    }
+
+    /// UserRevokeRole revokes a role of specified user.
+    async fn user_revoke_role(
+        &self,
+        request: Request<AuthUserRevokeRoleRequest>,
+    ) -> Result<Response<AuthUserRevokeRoleResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let revision = update(&self.store, |state| {
+            if self.is_enabled(state) && request.name == ROOT && request.role == ROOT {
+                return Err(invalid_auth_management());
+            }
+            let mut user = state.user(&request.name)?.clone();
+            if !user.roles.contains(&request.role) {
+                return Err(Status::failed_precondition(
+                    "etcdserver: role is not granted to the user",
+                ));
+            }
+            user.roles.retain(|role| role != &request.role);
+            Ok(vec![Change::PutUser(user)])
+        })
+        .await?;
+        Ok(Response::new(AuthUserRevokeRoleResponse { header: response_header(revision) }))
+    }
+
+    /// RoleAdd adds a new role. Role name cannot be empty.
+    async fn role_add(
+        &self,
+        request: Request<AuthRoleAddRequest>,
+    ) -> Result<Response<AuthRoleAddResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        if request.name.is_empty() {
+            return Err(Status::invalid_argument("etcdserver: role name is empty"));
+        }
+        let revision = update(&self.store, |state| {
+            if state.roles.contains_key(&request.name) {
+                return Err(Status::failed_precondition("etcdserver: role name already exists"));
+            }
+            Ok(vec![Change::PutRole(authpb::Role {
+                name: request.name.clone().into_bytes(),
+                key_permission: vec![],
+            })])
+        })
+        .await?;
+        Ok(Response::new(AuthRoleAddResponse { header: response_header(revision) }))
+    }
+
+    /// RoleGet gets detailed role information.
+    async fn role_get(
+        &self,
+        request: Request<AuthRoleGetRequest>,
+    ) -> Result<Response<AuthRoleGetResponse>> {
+        let identity = identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        if !identity.has_role(&request.role) {
+            identity.check_admin()?;
+        }
+        let state = cached_state(&self.store).await?;
+        let role = state.role(&request.role)?;
+        Ok(Response::new(AuthRoleGetResponse {
+            header: state.header(),
+            perm: role.key_permission.clone(),
+        }))
+    }
+
+    /// RoleList gets lists of all roles.
+    async fn role_list(
+        &self,
+        request: Request<AuthRoleListRequest>,
+    ) -> Result<Response<AuthRoleListResponse>> {
+        let state = self.admin_state(&request).await?;
+        let roles = state.roles.keys().cloned().collect();
+        Ok(Response::new(AuthRoleListResponse { header: state.header(), roles }))
+    }
+
+    /// RoleDelete deletes a specified role, it is also revoked from the users.
+    async fn role_delete(
+        &self,
+        request: Request<AuthRoleDeleteRequest>,
+    ) -> Result<Response<AuthRoleDeleteResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let revision = update(&self.store, |state| {
+            if self.is_enabled(state) && request.role == ROOT {
+                return Err(invalid_auth_management());
+            }
+            state.role(&request.role)?;
+            let mut changes = vec![Change::DeleteRole(request.role.clone())];
+            for (user, _) in state.users.values() {
+                if user.roles.contains(&request.role) {
+                    let mut user = user.clone();
+                    user.roles.retain(|role| role != &request.role);
+                    changes.push(Change::PutUser(user));
+                }
+            }
+            Ok(changes)
+        })
+        .await?;
+        Ok(Response::new(AuthRoleDeleteResponse { header: response_header(revision) }))
+    }
+
+    /// RoleGrantPermission grants a permission of a specified key or range to
+    /// a specified role.
+    async fn role_grant_permission(
+        &self,
+        request: Request<AuthRoleGrantPermissionRequest>,
+    ) -> Result<Response<AuthRoleGrantPermissionResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let perm = request
+            .perm
+            .ok_or_else(|| Status::invalid_argument("etcdserver: permission is empty"))?;
+        let revision = update(&self.store, |state| {
+            let mut role = state.role(&request.name)?.clone();
+            role.key_permission.retain(|p| p.key != perm.key || p.range_end != perm.range_end);
+            role.key_permission.push(perm.clone());
+            role.key_permission.sort_by(|a, b| a.key.cmp(&b.key));
+            Ok(vec![Change::PutRole(role)])
+        })
+        .await?;
+        Ok(Response::new(AuthRoleGrantPermissionResponse { header: response_header(revision) }))
+    }
+
+    /// RoleRevokePermission revokes a key or range permission of a specified
+    /// role.
+    async fn role_revoke_permission(
+        &self,
+        request: Request<AuthRoleRevokePermissionRequest>,
+    ) -> Result<Response<AuthRoleRevokePermissionResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let revision = update(&self.store, |state| {
+            let mut role = state.role(&request.role)?.clone();
+            let num_perms = role.key_permission.len();
+            role.key_permission
+                .retain(|p| p.key != request.key || p.range_end != request.range_end);
+            if role.key_permission.len() == num_perms {
+                return Err(Status::failed_precondition(
+                    "etcdserver: permission is not granted to the role",
+                ));
+            }
+            Ok(vec![Change::PutRole(role)])
+        })
+        .await?;
+        Ok(Response::new(AuthRoleRevokePermissionResponse { header: response_header(revision) }))
+    }
+}
+
+/// Identify who issues the request by the token in metadata.
+pub(crate) async fn identify<T>(store: &Store, request: &Request<T>) -> Result<Identity> {
+    if request.extensions().get::<Superuser>().is_some() {
+        return Ok(Identity::Root);
+    }
+    let state = cached_state(store).await?;
+    if !state.enabled && !store.auth_required() {
+        return Ok(Identity::Anyone);
+    }
+    let token = request
+        .metadata()
+        .get(TOKEN_KEY)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| Status::invalid_argument("etcdserver: user name is empty"))?;
+    let invalid_token = || Status::unauthenticated("etcdserver: invalid auth token");
+    let name = store.auth_cache().verify_token(token).ok_or_else(invalid_token)?;
+    state.identity(&name).ok_or_else(invalid_token)
+}
+
+async fn cached_state(store: &Store) -> Result<Arc<AuthState>> {
+    let mut cached = store.auth_cache().state.lock().await;
+    if let Some((loaded_at, state)) = cached.as_ref() {
+        if loaded_at.elapsed() < AUTH_CACHE_TTL {
+            return Ok(state.clone());
+        }
+    }
+    let state = Arc::new(load_state(store).await?);
+    *cached = Some((Instant::now(), state.clone()));
+    Ok(state)
+}
+
+async fn load_state(store: &Store) -> Result<AuthState> {
+    let (database, _) = store.target().await?;
+    let auth_collection_id = store.auth_collection_id().await?;
+    let (_, value_sets) =
+        database.scan_raw_values(auth_collection_id, vec![], None, 0, None).await?;
+    let corrupted = |err: prost::DecodeError| {
+        Status::internal(format!("etcdserver: corrupted auth record: {err}"))
+    };
+    let mut state = AuthState::default();
+    for value_set in value_sets {
+        let Some(value) = value_set.values.into_iter().next() else { continue };
+        // The record is deleted.
+        let Some(content) = value.content else { continue };
+        state.revision = state.revision.max(value.version);
+        let key = value_set.user_key;
+        if key == ENABLED_KEY {
+            state.enabled = content == [1];
+            state.enabled_version = Some(value.version);
+        } else if let Some(name) = key.strip_prefix(USER_PREFIX) {
+            let user = authpb::User::decode(content.as_slice()).map_err(corrupted)?;
+            state.users.insert(String::from_utf8_lossy(name).into_owned(), (user, value.version));
+        } else if let Some(name) = key.strip_prefix(ROLE_PREFIX) {
+            let role = authpb::Role::decode(content.as_slice()).map_err(corrupted)?;
+            state.roles.insert(String::from_utf8_lossy(name).into_owned(), (role, value.version));
+        }
+    }
+    Ok(state)
+}
+
+/// Apply the changes made on the latest users and roles, the changes are made
+/// again if the records are modified concurrently. Return the revision of the
+/// changes.
+async fn update<F>(store: &Store, make_changes: F) -> Result<i64>
+where
+    F: Fn(&AuthState) -> Result<Vec<Change>>,
+{
+    let (database, _) = store.target().await?;
+    let auth_collection_id = store.auth_collection_id().await?;
+    loop {
+        let state = load_state(store).await?;
+        let changes = make_changes(&state)?;
+        if changes.is_empty() {
+            return Ok(to_revision(state.revision));
+        }
+        let mut batch = WriteBatchRequest::default();
+        for change in changes {
+            let (key, version, value) = match change {
+                Change::Enable(enabled) => {
+                    (ENABLED_KEY.to_vec(), state.enabled_version, Some(vec![enabled as u8]))
+                }
+                Change::PutUser(user) => {
+                    let name = String::from_utf8_lossy(&user.name).into_owned();
+                    let version = state.users.get(&name).map(|(_, v)| *v);
+                    (record_key(USER_PREFIX, &name), version, Some(user.encode_to_vec()))
+                }
+                Change::DeleteUser(name) => {
+                    let version = state.users.get(&name).map(|(_, v)| *v);
+                    (record_key(USER_PREFIX, &name), version, None)
+                }
+                Change::PutRole(role) => {
+                    let name = String::from_utf8_lossy(&role.name).into_owned();
+                    let version = state.roles.get(&name).map(|(_, v)| *v);
+                    (record_key(ROLE_PREFIX, &name), version, Some(role.encode_to_vec()))
+                }
+                Change::DeleteRole(name) => {
+                    let version = state.roles.get(&name).map(|(_, v)| *v);
+                    (record_key(ROLE_PREFIX, &name), version, None)
+                }
+            };
+            let builder = match version {
+                Some(version) => WriteBuilder::new(key).expect_version(version),
+                None => WriteBuilder::new(key).expect_not_exists(),
+            };
+            batch = match value {
+                Some(value) => batch.add_put(auth_collection_id, builder.ensure_put(value)),
+                None => batch.add_delete(auth_collection_id, builder.ensure_delete()),
+            };
+        }
+        match database.write_batch(batch).await {
+            Ok(resp) => {
+                store.auth_cache().invalidate().await;
+                return Ok(to_revision(resp.version));
+            }
+            // Modified by other proxies concurrently, try again.
+            Err(sekas_client::Error::CasFailed(..)) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn record_key(prefix: &[u8], name: &str) -> Vec<u8> {
+    let mut key = prefix.to_owned();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// Return the digest of the new password. The hashed passwords are computed
+/// by the etcd clients with bcrypt, which is not supported.
+fn new_password(password: &str, hashed_password: &str, no_password: bool) -> Result<Vec<u8>> {
+    if !hashed_password.is_empty() {
+        return Err(Status::invalid_argument("etcdserver: hashed password is not supported"));
+    }
+    if no_password {
+        return Ok(vec![]);
+    }
+    if password.is_empty() {
+        return Err(Status::invalid_argument("etcdserver: password is empty"));
+    }
+    let salt: [u8; SALT_LEN] = rand::random();
+    Ok(password_digest(password, &salt))
+}
+
+/// The salted digest of the password, the salt is prepended to the digest.
+fn password_digest(password: &str, salt: &[u8]) -> Vec<u8> {
+    let mut hasher = openssl::sha::Sha256::new();
+    hasher.update(salt);
+    hasher.update(password.as_bytes());
+    let mut digest = salt.to_owned();
+    digest.extend_from_slice(&hasher.finish());
+    digest
+}
+
+/// Compare in constant time, so that the digests are not leaked by timing.
+fn verify_password(password: &str, digest: &[u8]) -> bool {
+    if digest.len() < SALT_LEN {
+        return false;
+    }
+    let expect = password_digest(password, &digest[..SALT_LEN]);
+    expect.len() == digest.len() && openssl::memcmp::eq(&expect, digest)
+}
+
+fn permission_denied() -> Status {
+    Status::permission_denied("etcdserver: permission denied")
+}
+
+fn user_not_found() -> Status {
+    Status::failed_precondition("etcdserver: user name not found")
+}
+
+fn role_not_found() -> Status {
+    Status::failed_precondition("etcdserver: role name not found")
+}
+
+fn invalid_auth_management() -> Status {
+    Status::failed_precondition("etcdserver: invalid auth management")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perm(key: &[u8], range_end: &[u8], perm_type: permission::Type) -> authpb::Permission {
+        authpb::Permission {
+            perm_type: perm_type as i32,
+            key: key.to_owned(),
+            range_end: range_end.to_owned(),
+        }
+    }
+
+    #[test]
+    fn password_verification() {
+        let digest = new_password("secret", "", false).unwrap();
+        assert!(verify_password("secret", &digest));
+        assert!(!verify_password("other", &digest));
+        assert_ne!(digest, new_password("secret", "", false).unwrap());
+        assert!(!verify_password("", &[]));
+
+        assert!(new_password("", "", false).is_err());
+        assert!(new_password("", "", true).unwrap().is_empty());
+        assert!(new_password("secret", "$2a$10$hash", false).is_err());
+    }
+
+    #[test]
+    fn range_permissions() {
+        let mut state = AuthState::default();
+        let role = authpb::Role {
+            name: b"kube".to_vec(),
+            key_permission: vec![
+                perm(b"/registry/", b"/registry0", permission::Type::Readwrite),
+                perm(b"/config", b"", permission::Type::Read),
+            ],
+        };
+        state.roles.insert("kube".to_owned(), (role, 1));
+        let user = authpb::User {
+            name: b"alice".to_vec(),
+            roles: vec!["kube".to_owned()],
+            ..Default::default()
+        };
+        state.users.insert("alice".to_owned(), (user, 1));
+        let root = authpb::User {
+            name: b"root".to_vec(),
+            roles: vec![ROOT.to_owned()],
+            ..Default::default()
+        };
+        state.users.insert("root".to_owned(), (root, 1));
+
+        let alice = state.identity("alice").unwrap();
+        assert!(alice.check_range(b"/registry/pods/a", b"", Permission::Write).is_ok());
+        assert!(alice
+            .check_range(b"/registry/pods/", b"/registry/pods0", Permission::Read)
+            .is_ok());
+        assert!(alice.check_range(b"/registry/", b"\0", Permission::Read).is_err());
+        assert!(alice.check_range(b"/config", b"", Permission::Read).is_ok());
+        assert!(alice.check_range(b"/config", b"", Permission::Write).is_err());
+        assert!(alice.check_range(b"/other", b"", Permission::Read).is_err());
+        assert!(alice.check_admin().is_err());
+        assert!(alice.has_role("kube"));
+
+        let root = state.identity("root").unwrap();
+        assert!(root.check_range(b"/other", b"\0", Permission::Write).is_ok());
+        assert!(root.check_admin().is_ok());
+        assert!(state.identity("bob").is_none());
+    }
+
+    #[test]
+    fn write_permission_implies_read() {
+        let write = KeyPermission::new(&perm(b"a", b"", permission::Type::Write));
+        assert_eq!(write.permission, Permission::Write);
+        assert!(write.covers(b"a", Some(b"a\0")));
+        assert!(!write.covers(b"a", None));
+
+        let all = KeyPermission::new(&perm(b"\0", b"\0", permission::Type::Read));
+        assert!(all.covers(b"", None));
+        assert!(all.covers(b"z", Some(b"zz")));
+    }
+
+    #[test]
+    fn txn_permissions() {
+        let read_only = Identity::User {
+            name: "alice".to_owned(),
+            roles: vec![],
+            permissions: vec![KeyPermission::new(&perm(b"a", b"b", permission::Type::Read))],
+        };
+        let range = RequestOp {
+            request: Some(request_op::Request::RequestRange(RangeRequest {
+                key: b"a1".to_vec(),
+                ..Default::default()
+            })),
+        };
+        let put = RequestOp {
+            request: Some(request_op::Request::RequestPut(PutRequest {
+                key: b"a1".to_vec(),
+                ..Default::default()
+            })),
+        };
+        let txn = TxnRequest { success: vec![range.clone()], ..Default::default() };
+        assert!(read_only.check_txn(&txn).is_ok());
+        let txn = TxnRequest { success: vec![range], failure: vec![put], ..Default::default() };
+        assert!(read_only.check_txn(&txn).is_err());
+        assert!(Identity::Anyone.check_txn(&txn).is_ok());
+    }
+}
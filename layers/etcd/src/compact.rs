@@ -0,0 +1,155 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+use sekas_client::{WriteBatchRequest, WriteBuilder};
+use tonic::Status;
+
+use crate::kv::{read_range, to_revision};
+use crate::store::Store;
+
+type Result<T> = std::result::Result<T, Status>;
+
+/// The key of the compact revision in the meta collection.
+const COMPACT_REVISION_KEY: &[u8] = b"compact_revision";
+
+/// The TTL of the GC lease which holds the versions after compact revision.
+const GC_LEASE_TTL: Duration = Duration::from_secs(60);
+/// The interval to renew the GC lease.
+const GC_LEASE_RENEW_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Advance the compact revision, the versions before it are allowed to be
+/// collected by the MVCC GC. Return the current revision.
+pub(crate) async fn compact(store: &Store, revision: i64) -> Result<i64> {
+    let (database, collection_id) = store.target().await?;
+    let meta_collection_id = store.meta_collection_id().await?;
+    loop {
+        let (read_version, _) = read_range(database, *collection_id, &[], &[], None, 0).await?;
+        let current_revision = to_revision(read_version);
+        if revision > current_revision {
+            return Err(future_revision_error());
+        }
+        let key = COMPACT_REVISION_KEY.to_vec();
+        let value = database.get_raw_value(meta_collection_id, key.clone()).await?;
+        let compacted = value.as_ref().and_then(|v| v.content.as_deref()).and_then(decode_revision);
+        let min_gc_timestamp = to_revision(database.min_gc_timestamp(*collection_id).await?);
+        if revision <= compacted.unwrap_or_default().max(min_gc_timestamp) {
+            return Err(compacted_error());
+        }
+
+        let builder = match (value, compacted) {
+            (Some(value), Some(_)) => WriteBuilder::new(key).expect_version(value.version),
+            _ => WriteBuilder::new(key).expect_not_exists(),
+        };
+        let put = builder.ensure_put(revision.to_be_bytes().to_vec());
+        let batch = WriteBatchRequest::default().add_put(meta_collection_id, put);
+        match database.write_batch(batch).await {
+            Ok(_) => {}
+            // Compacted by other proxies concurrently, try again.
+            Err(sekas_client::Error::CasFailed(..)) => continue,
+            Err(err) => return Err(err.into()),
+        }
+        if let Err(err) = hold_history(store).await {
+            warn!("hold etcd history after compact revision {revision}: {err}");
+        }
+        return Ok(current_revision);
+    }
+}
+
+/// Return the compact revision, the versions before it might be collected by
+/// the MVCC GC.
+pub(crate) async fn compact_revision(store: &Store) -> Result<i64> {
+    let (database, collection_id) = store.target().await?;
+    let meta_collection_id = store.meta_collection_id().await?;
+    let compacted = database
+        .get(meta_collection_id, COMPACT_REVISION_KEY.to_vec())
+        .await?
+        .as_deref()
+        .and_then(decode_revision)
+        .unwrap_or_default();
+    let min_gc_timestamp = to_revision(database.min_gc_timestamp(*collection_id).await?);
+    Ok(compacted.max(min_gc_timestamp))
+}
+
+/// Check that the versions at `revision` could be read, the revision must be
+/// neither compacted nor in the future. The latest revision is read if
+/// `revision` is not positive, so it is always valid.
+pub(crate) async fn check_read_revision(store: &Store, revision: i64) -> Result<()> {
+    if revision <= 0 {
+        return Ok(());
+    }
+    if revision < compact_revision(store).await? {
+        return Err(compacted_error());
+    }
+    let (database, collection_id) = store.target().await?;
+    let (read_version, _) = read_range(database, *collection_id, &[], &[], None, 0).await?;
+    if revision > to_revision(read_version) {
+        return Err(future_revision_error());
+    }
+    Ok(())
+}
+
+/// Hold the GC lease periodically, so the versions after the compact revision
+/// are retained, like the event history of etcd.
+pub(crate) async fn hold_history_periodically(store: Arc<Store>) {
+    let mut interval = tokio::time::interval(GC_LEASE_RENEW_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = hold_history(&store).await {
+            warn!("hold etcd history: {err}");
+        }
+    }
+}
+
+/// Renew the GC lease, or move it to the latest compact revision.
+async fn hold_history(store: &Store) -> Result<()> {
+    let (database, collection_id) = store.target().await?;
+    let revision = compact_revision(store).await? as u64;
+    let mut gc_lease = store.gc_lease().lock().await;
+    match *gc_lease {
+        Some((lease_id, timestamp)) if timestamp >= revision => {
+            let result = database.renew_gc_lease(lease_id, *collection_id, timestamp, GC_LEASE_TTL);
+            if let Err(err) = result.await {
+                // The lease is lost, acquire a new one next time.
+                *gc_lease = None;
+                return Err(err.into());
+            }
+        }
+        _ => {
+            let (lease_id, _) =
+                database.hold_gc_lease(*collection_id, revision, GC_LEASE_TTL).await?;
+            if let Some((prev_lease_id, _)) = gc_lease.replace((lease_id, revision)) {
+                database.release_gc_lease(prev_lease_id).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+pub(crate) fn compacted_error() -> Status {
+    Status::out_of_range("etcdserver: mvcc: required revision has been compacted")
+}
+
+#[inline]
+pub(crate) fn future_revision_error() -> Status {
+    Status::out_of_range("etcdserver: mvcc: required revision is a future revision")
+}
+
+fn decode_revision(buf: &[u8]) -> Option<i64> {
+    Some(i64::from_be_bytes(buf.try_into().ok()?))
+}
@@ -11,26 +11,111 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use sekas_api::server::v1::{PutType, Value, ValueSet};
+use sekas_client::{Database, WriteBatchRequest, WriteBuilder};
 use tonic::{Request, Response, Status};
 
+use crate::auth::{identify, Permission};
+use crate::compact::{check_read_revision, compact, hold_history_periodically};
+use crate::etcd::v3::event::EventType;
+use crate::etcd::v3::range_request::{SortOrder, SortTarget};
 use crate::etcd::v3::{kv_server, *};
+use crate::lease::{attach_lease, resolve_leases};
+use crate::store::Store;
 
 type Result<T> = std::result::Result<T, Status>;
 
-pub struct Kv {}
+/// The length of the header stored before each etcd value, which consists of
+/// the create revision, zero if the key is created by the put, and the version
+/// of the key.
+const VALUE_HEADER_LEN: usize = 16;
+
+/// The key in the meta collection which is written along with every created
+/// key, so that the txns reading ranges are able to detect the keys inserted
+/// into these ranges concurrently.
+const INSERT_GUARD_KEY: &[u8] = b"insert_guard";
+
+/// The etcd KV service, the revisions of etcd are mapped to the MVCC versions
+/// of sekas. The `create_revision` and `version` of a key are stored along
+/// with its value, so the key values are read from the latest versions.
+pub struct Kv {
+    store: Arc<Store>,
+}
+
+impl Kv {
+    /// Create the KV service, and spawn a task to hold the event history.
+    pub(crate) fn new(store: Arc<Store>) -> Self {
+        tokio::spawn(hold_history_periodically(store.clone()));
+        Kv { store }
+    }
+}
 
 #[tonic::async_trait]
 impl kv_server::Kv for Kv {
     /// Range gets the keys in the range from the key-value store.
-    async fn range(&self, _request: Request<RangeRequest>) -> Result<Response<RangeResponse>> {
-        todo!()
+    async fn range(&self, request: Request<RangeRequest>) -> Result<Response<RangeResponse>> {
+        let identity = identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        identity.check_range(&request.key, &request.range_end, Permission::Read)?;
+        let (database, collection_id) = self.store.target().await?;
+        let database = &database.with_end_user(identity.end_user());
+        check_read_revision(&self.store, request.revision).await?;
+        let read_version = if request.revision > 0 { Some(request.revision as u64) } else { None };
+        let (read_version, _, mut resp) =
+            serve_range(&self.store, database, *collection_id, &request, read_version).await?;
+        resp.header = response_header(to_revision(read_version));
+        Ok(Response::new(resp))
     }
 
     /// Put puts the given key into the key-value store.
     /// A put request increments the revision of the key-value store
     /// and generates one event in the event history.
-    async fn put(&self, _request: Request<PutRequest>) -> Result<Response<PutResponse>> {
-        todo!()
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutResponse>> {
+        let identity = identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        identity.check_range(&request.key, &[], Permission::Write)?;
+        let (database, collection_id) = self.store.target().await?;
+        let database = &database.with_end_user(identity.end_user());
+        let meta_collection_id = self.store.meta_collection_id().await?;
+        loop {
+            let (read_version, value_sets) =
+                read_range(database, *collection_id, &request.key, &[], None, 0).await?;
+            let current = value_sets.into_iter().next();
+            let latest_version = current.as_ref().and_then(|v| v.values.first()).map(|v| v.version);
+            let mut prev_kv = current.and_then(to_key_value);
+            resolve_leases(&self.store, prev_kv.as_mut(), Some(read_version), false).await?;
+            let value = match (&prev_kv, request.ignore_value) {
+                (_, false) => request.value.clone(),
+                (Some(prev_kv), true) => prev_kv.value.clone(),
+                (None, true) => return Err(Status::invalid_argument("etcdserver: key not found")),
+            };
+            let put = expect_latest_version(request.key.clone(), latest_version)
+                .ensure_put(encode_value(prev_kv.as_ref(), &value));
+            let mut batch = WriteBatchRequest::default().add_put(*collection_id, put);
+            if prev_kv.is_none() {
+                let guard = WriteBuilder::new(INSERT_GUARD_KEY.to_vec()).ensure_put(vec![]);
+                batch.puts.push((meta_collection_id, guard));
+            }
+            if request.lease != 0 {
+                attach_lease(&self.store, request.lease, &request.key, &mut batch).await?;
+            }
+            let revision = match database.write_batch(batch).await {
+                Ok(resp) => to_revision(resp.version),
+                // The key or the lease is modified concurrently, try again.
+                Err(sekas_client::Error::CasFailed(..)) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let event =
+                put_event(request.key.clone(), value, request.lease, prev_kv.clone(), revision);
+            self.store.publish(revision, vec![event]);
+            return Ok(Response::new(PutResponse {
+                header: response_header(revision),
+                prev_kv: if request.prev_kv { prev_kv } else { None },
+            }));
+        }
     }
 
     /// DeleteRange deletes the given range from the key-value store.
@@ -38,16 +123,774 @@ impl kv_server::Kv for Kv {
     /// and generates a delete event in the event history for every deleted key.
     async fn delete_range(
         &self,
-        _request: Request<DeleteRangeRequest>,
+        request: Request<DeleteRangeRequest>,
     ) -> Result<Response<DeleteRangeResponse>> {
-        todo!()
+        let identity = identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        identity.check_range(&request.key, &request.range_end, Permission::Write)?;
+        let (database, collection_id) = self.store.target().await?;
+        let database = &database.with_end_user(identity.end_user());
+        loop {
+            let (read_version, value_sets) =
+                read_range(database, *collection_id, &request.key, &request.range_end, None, 0)
+                    .await?;
+            let mut batch = WriteBatchRequest::default();
+            let mut prev_kvs = Vec::with_capacity(value_sets.len());
+            for value_set in value_sets {
+                let latest_version = value_set.values.first().map(|v| v.version);
+                let Some(prev_kv) = to_key_value(value_set) else { continue };
+                let delete =
+                    expect_latest_version(prev_kv.key.clone(), latest_version).ensure_delete();
+                batch = batch.add_delete(*collection_id, delete);
+                prev_kvs.push(prev_kv);
+            }
+            if prev_kvs.is_empty() {
+                return Ok(Response::new(DeleteRangeResponse {
+                    header: response_header(to_revision(read_version)),
+                    ..Default::default()
+                }));
+            }
+            resolve_leases(&self.store, &mut prev_kvs, Some(read_version), false).await?;
+
+            let revision = match database.write_batch(batch).await {
+                Ok(resp) => to_revision(resp.version),
+                // The keys are modified concurrently, try again.
+                Err(sekas_client::Error::CasFailed(..)) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let events =
+                prev_kvs.iter().map(|prev_kv| delete_event(prev_kv.clone(), revision)).collect();
+            self.store.publish(revision, events);
+            return Ok(Response::new(DeleteRangeResponse {
+                header: response_header(revision),
+                deleted: prev_kvs.len() as i64,
+                prev_kvs: if request.prev_kv { prev_kvs } else { vec![] },
+            }));
+        }
     }
 
     /// Txn processes multiple requests in a single transaction.
     /// A txn request increments the revision of the key-value store
     /// and generates events with the same revision for every completed request.
     /// It is not allowed to modify the same key several times within one txn.
-    async fn txn(&self, _request: Request<TxnRequest>) -> Result<Response<TxnResponse>> {
-        todo!()
+    ///
+    /// The compared keys and the written keys are validated when committing
+    /// the writes, the txn is retried if any of them is modified concurrently.
+    /// The ranges compared or read are also validated against the keys inserted
+    /// concurrently, by the insert guard. The range operations read the
+    /// snapshot before the txn.
+    async fn txn(&self, request: Request<TxnRequest>) -> Result<Response<TxnResponse>> {
+        let identity = identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        identity.check_txn(&request)?;
+        let (database, collection_id) = self.store.target().await?;
+        let database = &database.with_end_user(identity.end_user());
+        let meta_collection_id = self.store.meta_collection_id().await?;
+        loop {
+            let mut txn = Txn::new(&self.store, database, *collection_id, meta_collection_id);
+            let mut succeeded = true;
+            for compare in &request.compare {
+                if !txn.compare(compare).await? {
+                    succeeded = false;
+                    break;
+                }
+            }
+            let ops = if succeeded { &request.success } else { &request.failure };
+            let mut responses = Vec::with_capacity(ops.len());
+            for op in ops {
+                responses.push(txn.execute(op).await?);
+            }
+            let Some(revision) = txn.commit().await? else {
+                // The keys are modified concurrently, try again.
+                continue;
+            };
+            for resp in &mut responses {
+                match &mut resp.response {
+                    Some(response_op::Response::ResponseRange(resp)) => {
+                        resp.header = response_header(revision);
+                    }
+                    Some(response_op::Response::ResponsePut(resp)) => {
+                        resp.header = response_header(revision);
+                    }
+                    Some(response_op::Response::ResponseDeleteRange(resp)) => {
+                        resp.header = response_header(revision);
+                    }
+                    _ => {}
+                }
+            }
+            return Ok(Response::new(TxnResponse {
+                header: response_header(revision),
+                succeeded,
+                responses,
+            }));
+        }
+    }
+
+    /// Compact compacts the event history in the etcd key-value store. The
+    /// key-value store should be periodically compacted or the event history
+    /// will continue to grow indefinitely.
+    ///
+    /// The versions before the compact revision are left to the MVCC GC, and
+    /// the versions after it are held by a GC lease.
+    async fn compact(
+        &self,
+        request: Request<CompactionRequest>,
+    ) -> Result<Response<CompactionResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let revision = compact(&self.store, request.revision).await?;
+        Ok(Response::new(CompactionResponse { header: response_header(revision) }))
+    }
+}
+
+/// Convert the etcd range into the scan range `[start, end)`. An empty
+/// `range_end` means the single `key`, and `\0` means all keys greater than or
+/// equal to `key`.
+pub(crate) fn scan_range(key: &[u8], range_end: &[u8]) -> (Vec<u8>, Option<Vec<u8>>) {
+    match range_end {
+        [] => {
+            let mut end = key.to_owned();
+            end.push(0);
+            (key.to_owned(), Some(end))
+        }
+        [0] if key == [0] => (vec![], None),
+        [0] => (key.to_owned(), None),
+        _ => (key.to_owned(), Some(range_end.to_owned())),
+    }
+}
+
+/// The state of a txn, the reads share a snapshot and the writes are committed
+/// in a single sekas write batch.
+struct Txn<'a> {
+    store: &'a Store,
+    database: &'a Database,
+    collection_id: u64,
+    meta_collection_id: u64,
+    read_version: Option<u64>,
+    /// The latest versions of the read keys, `None` if the key has no versions.
+    reads: BTreeMap<Vec<u8>, Option<u64>>,
+    /// The latest version of the insert guard, it is read once any range is
+    /// read, and the inner `None` means the guard has no versions.
+    insert_guard: Option<Option<u64>>,
+    batch: WriteBatchRequest,
+    /// The written keys, it is not allowed to modify a key several times.
+    writes: HashSet<Vec<u8>>,
+    /// The keys put with their values, leases and previous key values, and the
+    /// previous key values deleted.
+    puts: Vec<(Vec<u8>, Vec<u8>, i64, Option<KeyValue>)>,
+    deletes: Vec<KeyValue>,
+}
+
+impl<'a> Txn<'a> {
+    fn new(
+        store: &'a Store,
+        database: &'a Database,
+        collection_id: u64,
+        meta_collection_id: u64,
+    ) -> Self {
+        Txn {
+            store,
+            database,
+            collection_id,
+            meta_collection_id,
+            read_version: None,
+            reads: BTreeMap::default(),
+            insert_guard: None,
+            batch: WriteBatchRequest::default(),
+            writes: HashSet::default(),
+            puts: Vec::default(),
+            deletes: Vec::default(),
+        }
+    }
+
+    /// Read the keys in range at the snapshot of txn.
+    async fn read(&mut self, key: &[u8], range_end: &[u8]) -> Result<Vec<ValueSet>> {
+        let (read_version, value_sets) =
+            read_range(self.database, self.collection_id, key, range_end, self.read_version, 0)
+                .await?;
+        self.read_version = Some(read_version);
+        for value_set in &value_sets {
+            let latest_version = value_set.values.first().map(|v| v.version);
+            self.reads.insert(value_set.user_key.clone(), latest_version);
+        }
+        if range_end.is_empty() && value_sets.is_empty() {
+            self.reads.insert(key.to_owned(), None);
+        }
+        if !range_end.is_empty() {
+            self.guard_inserts().await?;
+        }
+        Ok(value_sets)
+    }
+
+    /// Read the latest version of the insert guard at the snapshot of txn, the
+    /// only keys recorded for a range are the existing ones, so the keys
+    /// inserted into it are detected by the guard when committing.
+    async fn guard_inserts(&mut self) -> Result<()> {
+        if self.insert_guard.is_some() {
+            return Ok(());
+        }
+        let (_, value_sets) = read_range(
+            self.database,
+            self.meta_collection_id,
+            INSERT_GUARD_KEY,
+            &[],
+            self.read_version,
+            0,
+        )
+        .await?;
+        let latest_version = value_sets.first().and_then(|v| v.values.first()).map(|v| v.version);
+        self.insert_guard = Some(latest_version);
+        Ok(())
+    }
+
+    /// Evaluate the compare against the latest versions of the keys in range,
+    /// all keys must satisfy the compare.
+    async fn compare(&mut self, compare: &Compare) -> Result<bool> {
+        let value_sets = self.read(&compare.key, &compare.range_end).await?;
+        let mut kvs = value_sets.into_iter().filter_map(to_key_value).collect::<Vec<_>>();
+        if compare.target() == compare::CompareTarget::Lease {
+            resolve_leases(self.store, &mut kvs, self.read_version, false).await?;
+        }
+        if kvs.is_empty() {
+            return Ok(compare_key_value(compare, None));
+        }
+        Ok(kvs.iter().all(|kv| compare_key_value(compare, Some(kv))))
+    }
+
+    async fn execute(&mut self, op: &RequestOp) -> Result<ResponseOp> {
+        use request_op::Request as Op;
+        use response_op::Response as OpResponse;
+
+        let response = match &op.request {
+            Some(Op::RequestRange(request)) if request.revision > 0 => {
+                check_read_revision(self.store, request.revision).await?;
+                let read_version = Some(request.revision as u64);
+                let (_, _, resp) = serve_range(
+                    self.store,
+                    self.database,
+                    self.collection_id,
+                    request,
+                    read_version,
+                )
+                .await?;
+                OpResponse::ResponseRange(resp)
+            }
+            Some(Op::RequestRange(request)) => {
+                let (read_version, reads, resp) = serve_range(
+                    self.store,
+                    self.database,
+                    self.collection_id,
+                    request,
+                    self.read_version,
+                )
+                .await?;
+                self.read_version = Some(read_version);
+                for (key, latest_version) in reads {
+                    self.reads.insert(key, Some(latest_version));
+                }
+                // The absent keys are not recorded even if the range is a single key.
+                self.guard_inserts().await?;
+                OpResponse::ResponseRange(resp)
+            }
+            Some(Op::RequestPut(request)) => {
+                let current = self.read(&request.key, &[]).await?.into_iter().next();
+                let latest_version = self.reads.get(&request.key).cloned().flatten();
+                let mut prev_kv = current.and_then(to_key_value);
+                resolve_leases(self.store, prev_kv.as_mut(), self.read_version, false).await?;
+                let value = match (&prev_kv, request.ignore_value) {
+                    (_, false) => request.value.clone(),
+                    (Some(prev_kv), true) => prev_kv.value.clone(),
+                    (None, true) => {
+                        return Err(Status::invalid_argument("etcdserver: key not found"))
+                    }
+                };
+                self.add_write(&request.key)?;
+                let put = expect_latest_version(request.key.clone(), latest_version)
+                    .ensure_put(encode_value(prev_kv.as_ref(), &value));
+                self.batch.puts.push((self.collection_id, put));
+                if request.lease != 0 {
+                    attach_lease(self.store, request.lease, &request.key, &mut self.batch).await?;
+                }
+                self.puts.push((request.key.clone(), value, request.lease, prev_kv.clone()));
+                OpResponse::ResponsePut(PutResponse {
+                    header: None,
+                    prev_kv: if request.prev_kv { prev_kv } else { None },
+                })
+            }
+            Some(Op::RequestDeleteRange(request)) => {
+                let value_sets = self.read(&request.key, &request.range_end).await?;
+                let mut prev_kvs = Vec::with_capacity(value_sets.len());
+                for value_set in value_sets {
+                    let latest_version = value_set.values.first().map(|v| v.version);
+                    let Some(prev_kv) = to_key_value(value_set) else { continue };
+                    self.add_write(&prev_kv.key)?;
+                    let delete =
+                        expect_latest_version(prev_kv.key.clone(), latest_version).ensure_delete();
+                    self.batch.deletes.push((self.collection_id, delete));
+                    prev_kvs.push(prev_kv);
+                }
+                resolve_leases(self.store, &mut prev_kvs, self.read_version, false).await?;
+                self.deletes.extend(prev_kvs.iter().cloned());
+                OpResponse::ResponseDeleteRange(DeleteRangeResponse {
+                    header: None,
+                    deleted: prev_kvs.len() as i64,
+                    prev_kvs: if request.prev_kv { prev_kvs } else { vec![] },
+                })
+            }
+            Some(Op::RequestTxn(_)) => {
+                return Err(Status::unimplemented("nested txn is not supported"));
+            }
+            None => return Err(Status::invalid_argument("request of txn op is required")),
+        };
+        Ok(ResponseOp { response: Some(response) })
+    }
+
+    fn add_write(&mut self, key: &[u8]) -> Result<()> {
+        if !self.writes.insert(key.to_owned()) {
+            return Err(Status::invalid_argument("etcdserver: duplicate key given in txn request"));
+        }
+        Ok(())
+    }
+
+    /// Commit the writes and publish the events, return the revision of txn.
+    /// `None` is returned if any of the read keys are modified concurrently.
+    async fn commit(self) -> Result<Option<i64>> {
+        let Txn {
+            store,
+            database,
+            collection_id,
+            meta_collection_id,
+            read_version,
+            reads,
+            insert_guard,
+            mut batch,
+            writes,
+            puts,
+            deletes,
+        } = self;
+        if puts.is_empty() && deletes.is_empty() {
+            // A read only txn.
+            return Ok(Some(read_version.map(to_revision).unwrap_or_default()));
+        }
+
+        // Validate the keys only read, by the nop writes with conditions.
+        for (key, latest_version) in reads {
+            if !writes.contains(&key) {
+                let mut put = expect_latest_version(key, latest_version).ensure_put(vec![]);
+                put.put_type = PutType::Nop.into();
+                batch.puts.push((collection_id, put));
+            }
+        }
+        // Bump the insert guard if any key is created, and validate that no keys
+        // are inserted into the ranges read.
+        let inserted = puts.iter().any(|(_, _, _, prev_kv)| prev_kv.is_none());
+        if inserted || insert_guard.is_some() {
+            let builder = match insert_guard {
+                Some(latest_version) => {
+                    expect_latest_version(INSERT_GUARD_KEY.to_vec(), latest_version)
+                }
+                None => WriteBuilder::new(INSERT_GUARD_KEY.to_vec()),
+            };
+            let mut put = builder.ensure_put(vec![]);
+            if !inserted {
+                put.put_type = PutType::Nop.into();
+            }
+            batch.puts.push((meta_collection_id, put));
+        }
+        let revision = match database.write_batch(batch).await {
+            Ok(resp) => to_revision(resp.version),
+            Err(sekas_client::Error::CasFailed(..)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut events = Vec::with_capacity(puts.len() + deletes.len());
+        for (key, value, lease, prev_kv) in puts {
+            events.push(put_event(key, value, lease, prev_kv, revision));
+        }
+        for prev_kv in deletes {
+            events.push(delete_event(prev_kv, revision));
+        }
+        store.publish(revision, events);
+        Ok(Some(revision))
+    }
+}
+
+/// Read the latest versions of the keys in the etcd range at `read_version`,
+/// the deleted keys are skipped. At most `limit` keys are read if it is not
+/// zero, and the latest revision is read if `read_version` is `None`.
+pub(crate) async fn read_range(
+    database: &Database,
+    collection_id: u64,
+    key: &[u8],
+    range_end: &[u8],
+    read_version: Option<u64>,
+    limit: usize,
+) -> Result<(u64, Vec<ValueSet>)> {
+    let (start_key, end_key) = scan_range(key, range_end);
+    Ok(database.scan_values(collection_id, start_key, end_key, limit, read_version).await?)
+}
+
+/// Read all MVCC versions of the keys in the etcd range at `read_version`,
+/// including the tombstones. At most `limit` keys are read if it is not zero.
+pub(crate) async fn read_history(
+    database: &Database,
+    collection_id: u64,
+    key: &[u8],
+    range_end: &[u8],
+    read_version: Option<u64>,
+    limit: usize,
+) -> Result<(u64, Vec<ValueSet>)> {
+    let (start_key, end_key) = scan_range(key, range_end);
+    Ok(database.scan_raw_values(collection_id, start_key, end_key, limit, read_version).await?)
+}
+
+/// Serve the range request at `read_version`. Like etcd, the limit is pushed
+/// down to the scan if the keys are returned in the order of scan and not
+/// filtered, and the `count` is the number of all keys in range. Returns the
+/// read version, the keys read with their latest versions, and the response
+/// without header.
+async fn serve_range(
+    store: &Store,
+    database: &Database,
+    collection_id: u64,
+    request: &RangeRequest,
+    read_version: Option<u64>,
+) -> Result<(u64, Vec<(Vec<u8>, u64)>, RangeResponse)> {
+    let (key, range_end) = (&request.key, &request.range_end);
+    if request.count_only {
+        let (start_key, end_key) = scan_range(key, range_end);
+        let (read_version, count) =
+            database.count_keys(collection_id, start_key, end_key, read_version).await?;
+        return Ok((
+            read_version,
+            vec![],
+            RangeResponse { count: count as i64, ..Default::default() },
+        ));
+    }
+
+    let limit = scan_limit(request);
+    let (read_version, value_sets) =
+        read_range(database, collection_id, key, range_end, read_version, limit).await?;
+    let count = if limit == 0 || value_sets.len() < limit {
+        value_sets.len()
+    } else {
+        let (start_key, end_key) = scan_range(key, range_end);
+        database.count_keys(collection_id, start_key, end_key, Some(read_version)).await?.1
+    };
+    let reads = value_sets
+        .iter()
+        .filter_map(|v| Some((v.user_key.clone(), v.values.first()?.version)))
+        .collect();
+    let mut resp = range_response(request, value_sets);
+    resp.count = count as i64;
+    resolve_leases(store, &mut resp.kvs, Some(read_version), false).await?;
+    Ok((read_version, reads, resp))
+}
+
+/// The number of keys to scan for the range request, zero means all keys in
+/// range. One more key is scanned to tell whether there are more keys.
+fn scan_limit(request: &RangeRequest) -> usize {
+    let in_scan_order =
+        request.sort_target() == SortTarget::Key && request.sort_order() != SortOrder::Descend;
+    let filtered = request.min_mod_revision != 0
+        || request.max_mod_revision != 0
+        || request.min_create_revision != 0
+        || request.max_create_revision != 0;
+    if request.limit > 0 && in_scan_order && !filtered {
+        request.limit as usize + 1
+    } else {
+        0
+    }
+}
+
+/// Read the MVCC versions of the keys in the etcd range changed since
+/// `min_version` at the latest version, the versions before `min_version` are
+/// returned back to the latest tombstone.
+pub(crate) async fn read_changes(
+    database: &Database,
+    collection_id: u64,
+    key: &[u8],
+    range_end: &[u8],
+    min_version: u64,
+) -> Result<(u64, Vec<ValueSet>)> {
+    let (start_key, end_key) = scan_range(key, range_end);
+    Ok(database.scan_changed_values(collection_id, start_key, end_key, None, min_version).await?)
+}
+
+/// Build the range response without header and count from the latest
+/// versions of keys.
+fn range_response(request: &RangeRequest, value_sets: Vec<ValueSet>) -> RangeResponse {
+    let mut kvs = value_sets
+        .into_iter()
+        .filter_map(to_key_value)
+        .filter(|kv| in_revision_range(kv, request))
+        .collect::<Vec<_>>();
+    sort_key_values(&mut kvs, request.sort_order(), request.sort_target());
+    let more = request.limit > 0 && kvs.len() > request.limit as usize;
+    if request.limit > 0 {
+        kvs.truncate(request.limit as usize);
+    }
+    if request.keys_only {
+        kvs.iter_mut().for_each(|kv| kv.value.clear());
+    }
+    RangeResponse { header: None, kvs, more, count: 0 }
+}
+
+/// Compare the key value with the target, the missing key is treated as zero
+/// versions and revisions, except the value target which never matches.
+fn compare_key_value(compare: &Compare, kv: Option<&KeyValue>) -> bool {
+    use compare::{CompareResult, CompareTarget, TargetUnion};
+
+    let ordering = match (compare.target(), &compare.target_union) {
+        (CompareTarget::Version, Some(TargetUnion::Version(v))) => {
+            kv.map(|kv| kv.version).unwrap_or_default().cmp(v)
+        }
+        (CompareTarget::Create, Some(TargetUnion::CreateRevision(v))) => {
+            kv.map(|kv| kv.create_revision).unwrap_or_default().cmp(v)
+        }
+        (CompareTarget::Mod, Some(TargetUnion::ModRevision(v))) => {
+            kv.map(|kv| kv.mod_revision).unwrap_or_default().cmp(v)
+        }
+        (CompareTarget::Value, Some(TargetUnion::Value(v))) => match kv {
+            Some(kv) => kv.value.cmp(v),
+            None => return false,
+        },
+        (CompareTarget::Lease, Some(TargetUnion::Lease(v))) => {
+            kv.map(|kv| kv.lease).unwrap_or_default().cmp(v)
+        }
+        _ => return false,
+    };
+    match compare.result() {
+        CompareResult::Equal => ordering.is_eq(),
+        CompareResult::Greater => ordering.is_gt(),
+        CompareResult::Less => ordering.is_lt(),
+        CompareResult::NotEqual => ordering.is_ne(),
+    }
+}
+
+fn put_event(
+    key: Vec<u8>,
+    value: Vec<u8>,
+    lease: i64,
+    prev_kv: Option<KeyValue>,
+    revision: i64,
+) -> Event {
+    let kv = KeyValue {
+        key,
+        create_revision: prev_kv.as_ref().map(|kv| kv.create_revision).unwrap_or(revision),
+        mod_revision: revision,
+        version: prev_kv.as_ref().map(|kv| kv.version).unwrap_or_default() + 1,
+        value,
+        lease,
+    };
+    Event { r#type: EventType::Put.into(), kv: Some(kv), prev_kv }
+}
+
+pub(crate) fn delete_event(prev_kv: KeyValue, revision: i64) -> Event {
+    let kv = KeyValue { key: prev_kv.key.clone(), mod_revision: revision, ..Default::default() };
+    Event { r#type: EventType::Delete.into(), kv: Some(kv), prev_kv: Some(prev_kv) }
+}
+
+/// Build a write which expects that the key is not modified since the latest
+/// version read, so that the previous key value is consistent with the write.
+fn expect_latest_version(key: Vec<u8>, latest_version: Option<u64>) -> WriteBuilder {
+    let builder = WriteBuilder::new(key);
+    match latest_version {
+        Some(version) => builder.expect_version_le(version),
+        None => builder.expect_not_exists(),
+    }
+}
+
+/// Build the etcd key value from the latest version of a key, `None` is
+/// returned if the key is deleted.
+pub(crate) fn to_key_value(value_set: ValueSet) -> Option<KeyValue> {
+    let latest = value_set.values.into_iter().next()?;
+    decode_key_value(value_set.user_key, latest)
+}
+
+/// Build the etcd key value from a version of the key, `None` is returned if
+/// it is a tombstone or not written by etcd. The lease is left zero, see
+/// [`resolve_leases`].
+pub(crate) fn decode_key_value(key: Vec<u8>, value: Value) -> Option<KeyValue> {
+    let content = value.content?;
+    let create_revision = i64::from_be_bytes(content.get(..8)?.try_into().ok()?);
+    let version = i64::from_be_bytes(content.get(8..VALUE_HEADER_LEN)?.try_into().ok()?);
+    let mod_revision = to_revision(value.version);
+    Some(KeyValue {
+        key,
+        create_revision: if create_revision == 0 { mod_revision } else { create_revision },
+        mod_revision,
+        version,
+        value: content[VALUE_HEADER_LEN..].to_vec(),
+        lease: 0,
+    })
+}
+
+/// Encode the etcd value along with the create revision and the version of
+/// key, which are derived from the previous key value.
+pub(crate) fn encode_value(prev_kv: Option<&KeyValue>, value: &[u8]) -> Vec<u8> {
+    let create_revision = prev_kv.map(|kv| kv.create_revision).unwrap_or_default();
+    let version = prev_kv.map(|kv| kv.version).unwrap_or_default() + 1;
+    let mut buf = Vec::with_capacity(VALUE_HEADER_LEN + value.len());
+    buf.extend_from_slice(&create_revision.to_be_bytes());
+    buf.extend_from_slice(&version.to_be_bytes());
+    buf.extend_from_slice(value);
+    buf
+}
+
+fn in_revision_range(kv: &KeyValue, request: &RangeRequest) -> bool {
+    (request.min_mod_revision == 0 || kv.mod_revision >= request.min_mod_revision)
+        && (request.max_mod_revision == 0 || kv.mod_revision <= request.max_mod_revision)
+        && (request.min_create_revision == 0 || kv.create_revision >= request.min_create_revision)
+        && (request.max_create_revision == 0 || kv.create_revision <= request.max_create_revision)
+}
+
+/// Sort the key values, which are scanned in ascending order of key.
+fn sort_key_values(kvs: &mut [KeyValue], order: SortOrder, target: SortTarget) {
+    let order = match (order, target) {
+        // Same as etcd, sort in ascending order by default if the target is not key.
+        (SortOrder::None, SortTarget::Key) => return,
+        (SortOrder::None, _) => SortOrder::Ascend,
+        (order, _) => order,
+    };
+    let cmp = |a: &KeyValue, b: &KeyValue| match target {
+        SortTarget::Key => a.key.cmp(&b.key),
+        SortTarget::Version => a.version.cmp(&b.version),
+        SortTarget::Create => a.create_revision.cmp(&b.create_revision),
+        SortTarget::Mod => a.mod_revision.cmp(&b.mod_revision),
+        SortTarget::Value => a.value.cmp(&b.value),
+    };
+    if order == SortOrder::Descend {
+        kvs.sort_by(|a, b| cmp(b, a));
+    } else {
+        kvs.sort_by(cmp);
+    }
+}
+
+#[inline]
+pub(crate) fn response_header(revision: i64) -> Option<ResponseHeader> {
+    Some(ResponseHeader { revision, ..Default::default() })
+}
+
+#[inline]
+pub(crate) fn to_revision(version: u64) -> i64 {
+    version.min(i64::MAX as u64) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(content: Option<&[u8]>, version: u64) -> Value {
+        Value { content: content.map(ToOwned::to_owned), version, ..Default::default() }
+    }
+
+    #[test]
+    fn etcd_range_to_scan_range() {
+        assert_eq!(scan_range(b"a", b""), (b"a".to_vec(), Some(b"a\0".to_vec())));
+        assert_eq!(scan_range(b"a", b"\0"), (b"a".to_vec(), None));
+        assert_eq!(scan_range(b"\0", b"\0"), (vec![], None));
+        assert_eq!(scan_range(b"a", b"c"), (b"a".to_vec(), Some(b"c".to_vec())));
+    }
+
+    #[test]
+    fn key_value_revisions() {
+        let v1 = encode_value(None, b"v1");
+        let value_set = ValueSet { user_key: b"a".to_vec(), values: vec![value(Some(&v1), 20)] };
+        let prev_kv = to_key_value(value_set).unwrap();
+        assert_eq!(prev_kv.value, b"v1");
+        assert_eq!(prev_kv.mod_revision, 20);
+        assert_eq!(prev_kv.create_revision, 20);
+        assert_eq!(prev_kv.version, 1);
+
+        let v2 = encode_value(Some(&prev_kv), b"v2");
+        let value_set = ValueSet {
+            user_key: b"a".to_vec(),
+            values: vec![value(Some(&v2), 30), value(Some(&v1), 20)],
+        };
+        let kv = to_key_value(value_set).unwrap();
+        assert_eq!(kv.value, b"v2");
+        assert_eq!(kv.mod_revision, 30);
+        assert_eq!(kv.create_revision, 20);
+        assert_eq!(kv.version, 2);
+
+        let value_set = ValueSet {
+            user_key: b"a".to_vec(),
+            values: vec![value(None, 40), value(Some(&v2), 30)],
+        };
+        assert!(to_key_value(value_set).is_none());
+
+        // The values not written by etcd are ignored.
+        let value_set = ValueSet { user_key: b"a".to_vec(), values: vec![value(Some(b"v"), 20)] };
+        assert!(to_key_value(value_set).is_none());
+    }
+
+    #[test]
+    fn expect_not_modified() {
+        use sekas_api::server::v1::WriteConditionType;
+
+        let put = expect_latest_version(b"a".to_vec(), Some(10)).ensure_put(vec![]);
+        assert_eq!(put.conditions[0].r#type(), WriteConditionType::ExpectVersionLe);
+        assert_eq!(put.conditions[0].version, 10);
+
+        let delete = expect_latest_version(b"a".to_vec(), None).ensure_delete();
+        assert_eq!(delete.conditions[0].r#type(), WriteConditionType::ExpectNotExists);
+    }
+
+    #[test]
+    fn compare_key_values() {
+        use compare::{CompareResult, CompareTarget, TargetUnion};
+
+        let compare = |result: CompareResult, target: CompareTarget, union: TargetUnion| Compare {
+            result: result.into(),
+            target: target.into(),
+            key: b"a".to_vec(),
+            target_union: Some(union),
+            ..Default::default()
+        };
+        let kv = KeyValue {
+            key: b"a".to_vec(),
+            create_revision: 10,
+            mod_revision: 20,
+            version: 2,
+            value: b"v".to_vec(),
+            lease: 0,
+        };
+        let c = compare(CompareResult::Equal, CompareTarget::Version, TargetUnion::Version(2));
+        assert!(compare_key_value(&c, Some(&kv)));
+        // The missing key has zero version.
+        assert!(!compare_key_value(&c, None));
+        let c =
+            compare(CompareResult::Equal, CompareTarget::Create, TargetUnion::CreateRevision(0));
+        assert!(compare_key_value(&c, None));
+        let c = compare(CompareResult::Greater, CompareTarget::Mod, TargetUnion::ModRevision(10));
+        assert!(compare_key_value(&c, Some(&kv)));
+        let c = compare(CompareResult::Less, CompareTarget::Mod, TargetUnion::ModRevision(10));
+        assert!(!compare_key_value(&c, Some(&kv)));
+        let c = compare(
+            CompareResult::NotEqual,
+            CompareTarget::Value,
+            TargetUnion::Value(b"x".to_vec()),
+        );
+        assert!(compare_key_value(&c, Some(&kv)));
+        // The value compare never matches a missing key.
+        assert!(!compare_key_value(&c, None));
+        // The target union must match the target.
+        let c = compare(CompareResult::Equal, CompareTarget::Value, TargetUnion::Version(2));
+        assert!(!compare_key_value(&c, Some(&kv)));
+    }
+
+    #[test]
+    fn sort_by_target() {
+        let kv = |key: &[u8], mod_revision: i64| KeyValue {
+            key: key.to_owned(),
+            mod_revision,
+            ..Default::default()
+        };
+        let mut kvs = vec![kv(b"a", 3), kv(b"b", 1), kv(b"c", 2)];
+        sort_key_values(&mut kvs, SortOrder::None, SortTarget::Mod);
+        assert_eq!(kvs.iter().map(|kv| kv.mod_revision).collect::<Vec<_>>(), vec![1, 2, 3]);
+        sort_key_values(&mut kvs, SortOrder::Descend, SortTarget::Key);
+        assert_eq!(kvs.iter().map(|kv| kv.key[0]).collect::<Vec<_>>(), b"cba".to_vec());
     }
 }
@@ -11,27 +11,88 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use log::warn;
+use sekas_client::{Database, WriteBatchRequest, WriteBuilder};
+use tokio::sync::mpsc;
 use tonic::{Request, Response, Status, Streaming};
 
+use crate::auth::{identify, Permission};
 use crate::etcd::v3::{lease_server, *};
+use crate::kv::{delete_event, read_range, response_header, to_key_value, to_revision};
+use crate::store::Store;
 
 type Result<T> = std::result::Result<T, Status>;
 
-pub struct LeaseKeepAliveStream {}
+/// The prefix of lease records, which are keyed by the lease id.
+const LEASE_PREFIX: u8 = b'l';
+/// The prefix of attachments, which are keyed by the lease id and the key.
+const ATTACHMENT_PREFIX: u8 = b'k';
+/// The prefix of the lease ids of attachments, which are keyed by the key, so
+/// the lease of a key could be resolved.
+const KEY_LEASE_PREFIX: u8 = b'a';
+
+/// The minimal TTL of leases in seconds.
+const MIN_LEASE_TTL: i64 = 5;
+/// The maximal TTL of leases in seconds.
+const MAX_LEASE_TTL: i64 = 9_000_000_000;
+
+/// The interval to check the expired leases.
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The etcd lease service. The leases are stored in a system collection with
+/// the deadline of wall clock, so any proxy could expire the leases, even
+/// after the proxy granted them is restarted.
+///
+/// A key is attached to a lease by recording the version of the put, the
+/// attachment is invalid once the key is modified by other puts. The lease id
+/// is also recorded by the key along with the put, to resolve the lease of the
+/// key values read.
+pub struct Lease {
+    store: Arc<Store>,
+}
+
+pub struct LeaseKeepAliveStream {
+    receiver: mpsc::UnboundedReceiver<Result<LeaseKeepAliveResponse>>,
+}
+
+/// The lease record, `deadline` is the unix timestamp in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LeaseRecord {
+    ttl: i64,
+    deadline: u64,
+}
+
+/// The key attached to a lease.
+struct Attachment {
+    attachment_key: Vec<u8>,
+    /// The key value and its latest version, `None` if the attachment is
+    /// invalid.
+    key_value: Option<(KeyValue, u64)>,
+}
+
+impl Lease {
+    /// Create the lease service, and spawn a task to expire leases.
+    pub(crate) fn new(store: Arc<Store>) -> Self {
+        tokio::spawn(expire_leases(store.clone()));
+        Lease { store }
+    }
+}
 
 impl futures::Stream for LeaseKeepAliveStream {
     type Item = Result<LeaseKeepAliveResponse>;
 
-    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        todo!()
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
     }
 }
 
-pub struct Lease {}
-
 #[tonic::async_trait]
 impl lease_server::Lease for Lease {
     /// LeaseGrant creates a lease which expires if the server does not receive
@@ -40,18 +101,62 @@ impl lease_server::Lease for Lease {
     /// expired key generates a delete event in the event history.
     async fn lease_grant(
         &self,
-        _request: Request<LeaseGrantRequest>,
+        request: Request<LeaseGrantRequest>,
     ) -> Result<Response<LeaseGrantResponse>> {
-        todo!()
+        identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        if request.ttl > MAX_LEASE_TTL {
+            return Err(Status::out_of_range("etcdserver: too large lease TTL"));
+        }
+        let ttl = request.ttl.max(MIN_LEASE_TTL);
+        let (database, _) = self.store.target().await?;
+        let lease_collection_id = self.store.lease_collection_id().await?;
+        loop {
+            let id = if request.id != 0 { request.id } else { generate_lease_id() };
+            let record = LeaseRecord { ttl, deadline: deadline_after(ttl) };
+            let put =
+                WriteBuilder::new(lease_key(id)).expect_not_exists().ensure_put(record.encode());
+            let batch = WriteBatchRequest::default().add_put(lease_collection_id, put);
+            let revision = match database.write_batch(batch).await {
+                Ok(resp) => to_revision(resp.version),
+                Err(sekas_client::Error::CasFailed(..)) if request.id != 0 => {
+                    return Err(Status::failed_precondition("etcdserver: lease already exists"));
+                }
+                // The generated id is conflicted, try again.
+                Err(sekas_client::Error::CasFailed(..)) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            return Ok(Response::new(LeaseGrantResponse {
+                header: response_header(revision),
+                id,
+                ttl,
+                error: String::default(),
+            }));
+        }
     }
 
     /// LeaseRevoke revokes a lease. All keys attached to the lease will expire
     /// and be deleted.
     async fn lease_revoke(
         &self,
-        _request: Request<LeaseRevokeRequest>,
+        request: Request<LeaseRevokeRequest>,
     ) -> Result<Response<LeaseRevokeResponse>> {
-        todo!()
+        let identity = identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        if identity.is_user() {
+            // Revoking a lease deletes the keys attached to it.
+            for attachment in attachments(&self.store, request.id).await? {
+                if let Some((kv, _)) = attachment.key_value {
+                    identity.check_range(&kv.key, &[], Permission::Write)?;
+                }
+            }
+        }
+        match revoke_lease(&self.store, request.id, None).await? {
+            Some(revision) => {
+                Ok(Response::new(LeaseRevokeResponse { header: response_header(revision) }))
+            }
+            None => Err(lease_not_found()),
+        }
     }
 
     /// Server streaming response type for the LeaseKeepAlive method.
@@ -62,24 +167,399 @@ impl lease_server::Lease for Lease {
     /// from the server to the client.
     async fn lease_keep_alive(
         &self,
-        _request: Request<Streaming<LeaseKeepAliveRequest>>,
+        request: Request<Streaming<LeaseKeepAliveRequest>>,
     ) -> Result<Response<Self::LeaseKeepAliveStream>> {
-        todo!()
+        identify(&self.store, &request).await?;
+        let mut requests = request.into_inner();
+        let store = self.store.clone();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let resp = match requests.message().await {
+                    Ok(Some(request)) => keep_alive(&store, request.id).await,
+                    Ok(None) => break,
+                    Err(status) => Err(status),
+                };
+                let is_err = resp.is_err();
+                if sender.send(resp).is_err() || is_err {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(LeaseKeepAliveStream { receiver }))
     }
 
     /// LeaseTimeToLive retrieves lease information.
     async fn lease_time_to_live(
         &self,
-        _request: Request<LeaseTimeToLiveRequest>,
+        request: Request<LeaseTimeToLiveRequest>,
     ) -> Result<Response<LeaseTimeToLiveResponse>> {
-        todo!()
+        identify(&self.store, &request).await?;
+        let request = request.into_inner();
+        let (database, _) = self.store.target().await?;
+        let lease_collection_id = self.store.lease_collection_id().await?;
+        let Some((_, record)) = read_lease(database, lease_collection_id, request.id).await? else {
+            return Ok(Response::new(LeaseTimeToLiveResponse {
+                id: request.id,
+                ttl: -1,
+                ..Default::default()
+            }));
+        };
+        let keys = if request.keys {
+            attachments(&self.store, request.id)
+                .await?
+                .into_iter()
+                .filter_map(|attachment| attachment.key_value.map(|(kv, _)| kv.key))
+                .collect()
+        } else {
+            vec![]
+        };
+        Ok(Response::new(LeaseTimeToLiveResponse {
+            header: None,
+            id: request.id,
+            ttl: record.remaining_ttl(unix_timestamp_millis()),
+            granted_ttl: record.ttl,
+            keys,
+        }))
     }
 
     /// LeaseLeases lists all existing leases.
     async fn lease_leases(
         &self,
-        _request: Request<LeaseLeasesRequest>,
+        request: Request<LeaseLeasesRequest>,
     ) -> Result<Response<LeaseLeasesResponse>> {
-        todo!()
+        identify(&self.store, &request).await?;
+        let leases =
+            list_leases(&self.store).await?.into_iter().map(|(id, _)| LeaseStatus { id }).collect();
+        Ok(Response::new(LeaseLeasesResponse { header: None, leases }))
+    }
+}
+
+impl LeaseRecord {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(16);
+        buf.extend_from_slice(&self.ttl.to_be_bytes());
+        buf.extend_from_slice(&self.deadline.to_be_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let ttl = i64::from_be_bytes(buf.get(..8)?.try_into().ok()?);
+        let deadline = u64::from_be_bytes(buf.get(8..16)?.try_into().ok()?);
+        Some(LeaseRecord { ttl, deadline })
+    }
+
+    /// The remaining TTL in seconds, rounded up.
+    fn remaining_ttl(&self, now: u64) -> i64 {
+        let remaining = self.deadline.saturating_sub(now);
+        remaining.div_ceil(1000) as i64
+    }
+}
+
+/// Attach the key to the lease in the write batch of the put. The batch fails
+/// with `CasFailed` if the lease is revoked concurrently.
+pub(crate) async fn attach_lease(
+    store: &Store,
+    lease_id: i64,
+    key: &[u8],
+    batch: &mut WriteBatchRequest,
+) -> Result<()> {
+    let (database, _) = store.target().await?;
+    let lease_collection_id = store.lease_collection_id().await?;
+    if read_lease(database, lease_collection_id, lease_id).await?.is_none() {
+        return Err(lease_not_found());
+    }
+    let attach = WriteBuilder::new(attachment_key(lease_id, key)).ensure_put(vec![]);
+    let key_lease =
+        WriteBuilder::new(key_lease_key(key)).ensure_put(lease_id.to_be_bytes().to_vec());
+    let check = WriteBuilder::new(lease_key(lease_id)).expect_exists().ensure_nop();
+    batch.puts.push((lease_collection_id, attach));
+    batch.puts.push((lease_collection_id, key_lease));
+    batch.puts.push((lease_collection_id, check));
+    Ok(())
+}
+
+/// Resolve the leases of the key values read at `read_version` from the
+/// attachments, the lease of a key is valid only if it is recorded by the put
+/// of the key value. The history of the attachments is read if `history` is
+/// true, to resolve the leases of the key values before the latest versions.
+pub(crate) async fn resolve_leases<'a>(
+    store: &Store,
+    kvs: impl IntoIterator<Item = &'a mut KeyValue>,
+    read_version: Option<u64>,
+    history: bool,
+) -> Result<()> {
+    // The key values of the delete events have no create revision.
+    let kvs = kvs.into_iter().filter(|kv| kv.create_revision != 0).collect::<Vec<_>>();
+    let (Some(first), Some(last)) =
+        (kvs.iter().map(|kv| &kv.key).min(), kvs.iter().map(|kv| &kv.key).max())
+    else {
+        return Ok(());
+    };
+    let start_key = key_lease_key(first);
+    let mut end_key = key_lease_key(last);
+    end_key.push(0);
+
+    let (database, _) = store.target().await?;
+    let lease_collection_id = store.lease_collection_id().await?;
+    let end_key = Some(end_key);
+    let (_, value_sets) = if history {
+        database.scan_raw_values(lease_collection_id, start_key, end_key, 0, read_version).await?
+    } else {
+        database.scan_values(lease_collection_id, start_key, end_key, 0, read_version).await?
+    };
+    let key_leases = value_sets
+        .into_iter()
+        .map(|value_set| (value_set.user_key[1..].to_vec(), value_set.values))
+        .collect::<HashMap<_, _>>();
+    for kv in kvs {
+        kv.lease = key_leases
+            .get(&kv.key)
+            .and_then(|values| values.iter().find(|v| to_revision(v.version) == kv.mod_revision))
+            .and_then(|v| Some(i64::from_be_bytes(v.content.as_deref()?.try_into().ok()?)))
+            .unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// Renew the lease, return the TTL of the lease, or zero if the lease is not
+/// found.
+async fn keep_alive(store: &Store, id: i64) -> Result<LeaseKeepAliveResponse> {
+    let (database, _) = store.target().await?;
+    let lease_collection_id = store.lease_collection_id().await?;
+    let Some((_, record)) = read_lease(database, lease_collection_id, id).await? else {
+        return Ok(LeaseKeepAliveResponse { id, ttl: 0, ..Default::default() });
+    };
+    let record = LeaseRecord { ttl: record.ttl, deadline: deadline_after(record.ttl) };
+    let put = WriteBuilder::new(lease_key(id)).expect_exists().ensure_put(record.encode());
+    let batch = WriteBatchRequest::default().add_put(lease_collection_id, put);
+    match database.write_batch(batch).await {
+        Ok(resp) => Ok(LeaseKeepAliveResponse {
+            header: response_header(to_revision(resp.version)),
+            id,
+            ttl: record.ttl,
+        }),
+        // The lease is revoked concurrently.
+        Err(sekas_client::Error::CasFailed(..)) => {
+            Ok(LeaseKeepAliveResponse { id, ttl: 0, ..Default::default() })
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Delete the lease and the keys attached to it, return the revision of the
+/// deletion, or `None` if the lease is not found. If `expired_at` is
+/// specified, the lease is only deleted if it is expired at that time.
+async fn revoke_lease(store: &Store, id: i64, expired_at: Option<u64>) -> Result<Option<i64>> {
+    let (database, collection_id) = store.target().await?;
+    let lease_collection_id = store.lease_collection_id().await?;
+    loop {
+        let Some((version, record)) = read_lease(database, lease_collection_id, id).await? else {
+            return Ok(None);
+        };
+        if expired_at.map(|now| now < record.deadline).unwrap_or_default() {
+            // The lease is renewed.
+            return Ok(None);
+        }
+
+        let delete_lease = WriteBuilder::new(lease_key(id)).expect_version(version).ensure_delete();
+        let mut batch = WriteBatchRequest::default().add_delete(lease_collection_id, delete_lease);
+        let mut prev_kvs = vec![];
+        for attachment in attachments(store, id).await? {
+            let delete = WriteBuilder::new(attachment.attachment_key).ensure_delete();
+            batch.deletes.push((lease_collection_id, delete));
+            if let Some((prev_kv, latest_version)) = attachment.key_value {
+                let delete = WriteBuilder::new(prev_kv.key.clone()).expect_version(latest_version);
+                batch.deletes.push((*collection_id, delete.ensure_delete()));
+                let delete = WriteBuilder::new(key_lease_key(&prev_kv.key)).ensure_delete();
+                batch.deletes.push((lease_collection_id, delete));
+                prev_kvs.push(prev_kv);
+            }
+        }
+        let revision = match database.write_batch(batch).await {
+            Ok(resp) => to_revision(resp.version),
+            // The lease or the attached keys are modified concurrently, try again.
+            Err(sekas_client::Error::CasFailed(..)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if !prev_kvs.is_empty() {
+            let events = prev_kvs.into_iter().map(|kv| delete_event(kv, revision)).collect();
+            store.publish(revision, events);
+        }
+        return Ok(Some(revision));
+    }
+}
+
+/// Read the keys attached to the lease, and validate them with the latest
+/// versions of keys.
+async fn attachments(store: &Store, id: i64) -> Result<Vec<Attachment>> {
+    let (database, collection_id) = store.target().await?;
+    let lease_collection_id = store.lease_collection_id().await?;
+    let prefix = attachment_key(id, &[]);
+    let end = match (id as u64).checked_add(1) {
+        Some(next) => attachment_key(next as i64, &[]),
+        None => vec![ATTACHMENT_PREFIX + 1],
+    };
+    let (_, value_sets) =
+        database.scan_raw_values(lease_collection_id, prefix.clone(), Some(end), 0, None).await?;
+    let mut attachments = Vec::with_capacity(value_sets.len());
+    for value_set in value_sets {
+        let Some(value) = value_set.values.first() else { continue };
+        if value.content.is_none() {
+            continue;
+        }
+        let attached_version = value.version;
+        let key = &value_set.user_key[prefix.len()..];
+        let (_, key_value_sets) = read_range(database, *collection_id, key, &[], None, 0).await?;
+        let key_value = key_value_sets.into_iter().next().and_then(|value_set| {
+            let latest_version = value_set.values.first().map(|v| v.version)?;
+            // The key is modified after attached.
+            if latest_version != attached_version {
+                return None;
+            }
+            let kv = to_key_value(value_set)?;
+            Some((KeyValue { lease: id, ..kv }, latest_version))
+        });
+        attachments.push(Attachment { attachment_key: value_set.user_key, key_value });
+    }
+    Ok(attachments)
+}
+
+/// List the leases and their records.
+async fn list_leases(store: &Store) -> Result<Vec<(i64, LeaseRecord)>> {
+    let (database, _) = store.target().await?;
+    let lease_collection_id = store.lease_collection_id().await?;
+    let (_, value_sets) = database
+        .scan_raw_values(
+            lease_collection_id,
+            vec![LEASE_PREFIX],
+            Some(vec![LEASE_PREFIX + 1]),
+            0,
+            None,
+        )
+        .await?;
+    let leases = value_sets
+        .into_iter()
+        .filter_map(|value_set| {
+            let id = decode_lease_id(&value_set.user_key)?;
+            let content = value_set.values.into_iter().next()?.content?;
+            Some((id, LeaseRecord::decode(&content)?))
+        })
+        .collect();
+    Ok(leases)
+}
+
+async fn read_lease(
+    database: &Database,
+    lease_collection_id: u64,
+    id: i64,
+) -> Result<Option<(u64, LeaseRecord)>> {
+    let value = database.get_raw_value(lease_collection_id, lease_key(id)).await?;
+    Ok(value.and_then(|v| Some((v.version, LeaseRecord::decode(&v.content?)?))))
+}
+
+/// Delete the expired leases and the keys attached to them periodically.
+async fn expire_leases(store: Arc<Store>) {
+    let mut interval = tokio::time::interval(EXPIRE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = unix_timestamp_millis();
+        let leases = match list_leases(&store).await {
+            Ok(leases) => leases,
+            Err(status) => {
+                warn!("list etcd leases: {status}");
+                continue;
+            }
+        };
+        for (id, record) in leases {
+            if now < record.deadline {
+                continue;
+            }
+            if let Err(status) = revoke_lease(&store, id, Some(now)).await {
+                warn!("expire etcd lease {id}: {status}");
+            }
+        }
+    }
+}
+
+fn lease_key(id: i64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(9);
+    key.push(LEASE_PREFIX);
+    key.extend_from_slice(&(id as u64).to_be_bytes());
+    key
+}
+
+fn decode_lease_id(key: &[u8]) -> Option<i64> {
+    match key {
+        [LEASE_PREFIX, id @ ..] => Some(u64::from_be_bytes(id.try_into().ok()?) as i64),
+        _ => None,
+    }
+}
+
+fn attachment_key(id: i64, key: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(9 + key.len());
+    buf.push(ATTACHMENT_PREFIX);
+    buf.extend_from_slice(&(id as u64).to_be_bytes());
+    buf.extend_from_slice(key);
+    buf
+}
+
+fn key_lease_key(key: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(1 + key.len());
+    buf.push(KEY_LEASE_PREFIX);
+    buf.extend_from_slice(key);
+    buf
+}
+
+/// Generate a positive lease id from the wall clock, the conflicts are
+/// resolved by retrying.
+fn generate_lease_id() -> i64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    ((nanos as u64) >> 1).max(1) as i64
+}
+
+fn deadline_after(ttl: i64) -> u64 {
+    unix_timestamp_millis().saturating_add((ttl as u64).saturating_mul(1000))
+}
+
+fn unix_timestamp_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+fn lease_not_found() -> Status {
+    Status::not_found("etcdserver: requested lease not found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lease_record_encoding() {
+        let record = LeaseRecord { ttl: 10, deadline: 123456 };
+        assert_eq!(LeaseRecord::decode(&record.encode()), Some(record));
+        assert_eq!(LeaseRecord::decode(&[0; 8]), None);
+    }
+
+    #[test]
+    fn lease_remaining_ttl() {
+        let record = LeaseRecord { ttl: 10, deadline: 10_000 };
+        assert_eq!(record.remaining_ttl(0), 10);
+        assert_eq!(record.remaining_ttl(9_999), 1);
+        assert_eq!(record.remaining_ttl(10_000), 0);
+        assert_eq!(record.remaining_ttl(20_000), 0);
+    }
+
+    #[test]
+    fn lease_keys_order() {
+        assert_eq!(decode_lease_id(&lease_key(42)), Some(42));
+        assert_eq!(decode_lease_id(&attachment_key(42, b"key")), None);
+        // The attachments of a lease are in range of `[id, id + 1)`.
+        assert!(attachment_key(1, &[]) < attachment_key(1, b"\xff\xff"));
+        assert!(attachment_key(1, b"\xff\xff") < attachment_key(2, &[]));
+        assert!(attachment_key(-1, b"key") > attachment_key(i64::MAX, b"key"));
+        assert!(lease_key(-1) < vec![LEASE_PREFIX + 1]);
+        // The lease ids of attachments are not in the range of attachments.
+        assert!(key_lease_key(b"\xff\xff") < attachment_key(0, &[]));
     }
 }
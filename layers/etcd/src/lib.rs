@@ -12,12 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod auth;
+mod compact;
 mod kv;
 mod lease;
+mod maintenance;
+mod store;
 mod watch;
 
+use std::sync::Arc;
+
+pub use self::auth::{Auth, Superuser};
 pub use self::kv::Kv;
 pub use self::lease::Lease;
+pub use self::maintenance::{ClusterInfo, ClusterStatus, Maintenance};
+pub use self::store::Store;
 pub use self::watch::Watch;
 
 pub mod etcd {
@@ -34,14 +43,25 @@ pub mod etcd {
     }
 }
 
-pub fn make_etcd_kv_service() -> etcd::v3::kv_server::KvServer<Kv> {
-    todo!()
+pub fn make_etcd_kv_service(store: Arc<Store>) -> etcd::v3::kv_server::KvServer<Kv> {
+    etcd::v3::kv_server::KvServer::new(Kv::new(store))
+}
+
+pub fn make_etcd_watch_service(store: Arc<Store>) -> etcd::v3::watch_server::WatchServer<Watch> {
+    etcd::v3::watch_server::WatchServer::new(Watch::new(store))
+}
+
+pub fn make_etcd_lease_service(store: Arc<Store>) -> etcd::v3::lease_server::LeaseServer<Lease> {
+    etcd::v3::lease_server::LeaseServer::new(Lease::new(store))
 }
 
-pub fn make_etcd_watch_service() -> etcd::v3::watch_server::WatchServer<Watch> {
-    todo!()
+pub fn make_etcd_maintenance_service(
+    store: Arc<Store>,
+    cluster: Arc<dyn ClusterInfo>,
+) -> etcd::v3::maintenance_server::MaintenanceServer<Maintenance> {
+    etcd::v3::maintenance_server::MaintenanceServer::new(Maintenance::new(store, cluster))
 }
 
-pub fn make_etcd_lease_service() -> etcd::v3::lease_server::LeaseServer<Lease> {
-    todo!()
+pub fn make_etcd_auth_service(store: Arc<Store>) -> etcd::v3::auth_server::AuthServer<Auth> {
+    etcd::v3::auth_server::AuthServer::new(Auth::new(store))
 }
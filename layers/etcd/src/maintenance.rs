@@ -0,0 +1,245 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+
+use sekas_api::server::v1::ValueSet;
+use tonic::{Request, Response, Status};
+
+use crate::auth::identify;
+use crate::compact::{compact_revision, compacted_error};
+use crate::etcd::v3::alarm_request::AlarmAction;
+use crate::etcd::v3::{maintenance_server, *};
+use crate::kv::{read_history, read_range, response_header, to_revision};
+use crate::store::Store;
+
+type Result<T> = std::result::Result<T, Status>;
+
+/// The version of etcd API served by this layer.
+const ETCD_VERSION: &str = "3.5.0";
+
+/// The number of keys read per page when hashing the keyspace.
+const HASH_KV_PAGE_SIZE: usize = 1024;
+
+/// The status of the sekas cluster, which is reported as the etcd member
+/// status.
+#[derive(Debug, Default, Clone)]
+pub struct ClusterStatus {
+    /// The id of the node serving the request.
+    pub member_id: u64,
+    /// The id of the node which serves the root leader.
+    pub leader: u64,
+    /// The max raft term of groups.
+    pub raft_term: u64,
+    /// The errors of cluster, eg the root is unavailable.
+    pub errors: Vec<String>,
+}
+
+/// Provide the status of the sekas cluster to the maintenance service.
+#[tonic::async_trait]
+pub trait ClusterInfo: Send + Sync + 'static {
+    async fn cluster_status(&self) -> ClusterStatus;
+}
+
+/// The etcd maintenance service, it is used by the health checks of etcd
+/// clients. The storage is maintained by sekas itself, so the defragment is
+/// a no-op and the alarms are only kept in memory.
+pub struct Maintenance {
+    store: Arc<Store>,
+    cluster: Arc<dyn ClusterInfo>,
+    alarms: Mutex<Vec<AlarmMember>>,
+}
+
+impl Maintenance {
+    pub(crate) fn new(store: Arc<Store>, cluster: Arc<dyn ClusterInfo>) -> Self {
+        Maintenance { store, cluster, alarms: Mutex::default() }
+    }
+
+    async fn current_revision(&self) -> Result<i64> {
+        let (database, collection_id) = self.store.target().await?;
+        let (read_version, _) = read_range(database, *collection_id, &[], &[], None, 0).await?;
+        Ok(to_revision(read_version))
+    }
+}
+
+#[tonic::async_trait]
+impl maintenance_server::Maintenance for Maintenance {
+    /// Alarm activates, deactivates, and queries alarms regarding cluster
+    /// health.
+    async fn alarm(&self, request: Request<AlarmRequest>) -> Result<Response<AlarmResponse>> {
+        if request.get_ref().action() != AlarmAction::Get {
+            identify(&self.store, &request).await?.check_admin()?;
+        }
+        let request = request.into_inner();
+        let member = AlarmMember { member_id: request.member_id, alarm: request.alarm };
+        let alarms = {
+            let mut alarms = self.alarms.lock().unwrap();
+            match request.action() {
+                AlarmAction::Get => {}
+                AlarmAction::Activate => {
+                    if !alarms.contains(&member) {
+                        alarms.push(member);
+                    }
+                }
+                AlarmAction::Deactivate => alarms.retain(|alarm| alarm != &member),
+            }
+            alarms.clone()
+        };
+        let revision = self.current_revision().await?;
+        Ok(Response::new(AlarmResponse { header: response_header(revision), alarms }))
+    }
+
+    /// Status gets the status of the member.
+    async fn status(&self, _request: Request<StatusRequest>) -> Result<Response<StatusResponse>> {
+        let status = self.cluster.cluster_status().await;
+        let revision = self.current_revision().await?;
+        let header = ResponseHeader {
+            member_id: status.member_id,
+            revision,
+            raft_term: status.raft_term,
+            ..Default::default()
+        };
+        let mut errors = status.errors;
+        errors.extend(self.alarms.lock().unwrap().iter().map(|alarm| {
+            format!("memberID:{} alarm:{}", alarm.member_id, alarm.alarm().as_str_name())
+        }));
+        Ok(Response::new(StatusResponse {
+            header: Some(header),
+            version: ETCD_VERSION.to_owned(),
+            leader: status.leader,
+            raft_index: revision as u64,
+            raft_term: status.raft_term,
+            raft_applied_index: revision as u64,
+            errors,
+            ..Default::default()
+        }))
+    }
+
+    /// Defragment defragments a member's backend database to recover storage
+    /// space.
+    async fn defragment(
+        &self,
+        request: Request<DefragmentRequest>,
+    ) -> Result<Response<DefragmentResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let revision = self.current_revision().await?;
+        Ok(Response::new(DefragmentResponse { header: response_header(revision) }))
+    }
+
+    /// HashKV computes the hash of all MVCC keys up to a given revision.
+    async fn hash_kv(&self, request: Request<HashKvRequest>) -> Result<Response<HashKvResponse>> {
+        identify(&self.store, &request).await?.check_admin()?;
+        let request = request.into_inner();
+        let compact_revision = compact_revision(&self.store).await?;
+        if request.revision > 0 && request.revision < compact_revision {
+            return Err(compacted_error());
+        }
+        let (database, collection_id) = self.store.target().await?;
+        let mut read_version =
+            if request.revision > 0 { Some(request.revision as u64) } else { None };
+        // The keyspace is hashed page by page, all pages are read at the version of
+        // the first one.
+        let mut hasher = crc32fast::Hasher::new();
+        let mut key = vec![0];
+        loop {
+            let (version, value_sets) =
+                read_history(database, *collection_id, &key, &[0], read_version, HASH_KV_PAGE_SIZE)
+                    .await?;
+            read_version = Some(version);
+            hash_key_values(&mut hasher, &value_sets, compact_revision);
+            match value_sets.last() {
+                Some(value_set) if value_sets.len() >= HASH_KV_PAGE_SIZE => {
+                    key = value_set.user_key.clone();
+                    key.push(0);
+                }
+                _ => break,
+            }
+        }
+        let hash_revision = to_revision(read_version.unwrap_or_default());
+        Ok(Response::new(HashKvResponse {
+            header: response_header(hash_revision),
+            hash: hasher.finalize(),
+            compact_revision,
+            hash_revision,
+        }))
+    }
+}
+
+/// Feed the versions of keys after the compact revision to the hasher.
+fn hash_key_values(hasher: &mut crc32fast::Hasher, value_sets: &[ValueSet], compact_revision: i64) {
+    for value_set in value_sets {
+        for value in &value_set.values {
+            if to_revision(value.version) < compact_revision {
+                continue;
+            }
+            hasher.update(&value_set.user_key);
+            hasher.update(&value.version.to_be_bytes());
+            match &value.content {
+                Some(content) => hasher.update(content),
+                // The tombstone.
+                None => hasher.update(&[0xFF]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::Value;
+
+    use super::*;
+
+    fn value_set(key: &[u8], versions: &[(u64, Option<&[u8]>)]) -> ValueSet {
+        let values = versions
+            .iter()
+            .map(|(version, content)| Value {
+                content: content.map(ToOwned::to_owned),
+                version: *version,
+                ..Default::default()
+            })
+            .collect();
+        ValueSet { user_key: key.to_owned(), values }
+    }
+
+    fn hash(pages: &[&[ValueSet]], compact_revision: i64) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        for page in pages {
+            hash_key_values(&mut hasher, page, compact_revision);
+        }
+        hasher.finalize()
+    }
+
+    #[test]
+    fn hash_ignores_compacted_versions() {
+        let before = vec![value_set(b"a", &[(20, Some(b"a2")), (10, Some(b"a1"))])];
+        let after = vec![value_set(b"a", &[(20, Some(b"a2"))])];
+        assert_eq!(hash(&[&before], 15), hash(&[&after], 15));
+        assert_ne!(hash(&[&before], 0), hash(&[&after], 0));
+
+        let deleted = vec![value_set(b"a", &[(30, None), (20, Some(b"a2"))])];
+        assert_ne!(hash(&[&deleted], 15), hash(&[&after], 15));
+    }
+
+    #[test]
+    fn hash_is_independent_of_pages() {
+        let value_sets = vec![
+            value_set(b"a", &[(20, Some(b"a2")), (10, Some(b"a1"))]),
+            value_set(b"b", &[(30, None), (25, Some(b"b1"))]),
+            value_set(b"c", &[(40, Some(b"c1"))]),
+        ];
+        let whole = hash(&[&value_sets], 0);
+        assert_eq!(whole, hash(&[&value_sets[..1], &value_sets[1..]], 0));
+        assert_eq!(whole, hash(&[&value_sets[..2], &value_sets[2..], &[]], 0));
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sekas_client::{AppError, AppResult, Database, SekasClient};
+use tokio::sync::{broadcast, Mutex, OnceCell};
+
+use crate::auth::AuthCache;
+use crate::etcd::v3::Event;
+
+/// The database to store the etcd keys.
+const DATABASE: &str = "etcd";
+/// The collection to store the etcd keys.
+const COLLECTION: &str = "kv";
+/// The system collection to store the etcd leases.
+const LEASE_COLLECTION: &str = "lease";
+/// The system collection to store the metadata, eg the compact revision.
+const META_COLLECTION: &str = "meta";
+/// The system collection to store the etcd users and roles.
+const AUTH_COLLECTION: &str = "auth";
+/// The number of pending revisions of the event channel. The events only wake
+/// up the change reader of watchers, which reads them from the MVCC versions.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// The sekas collection which stores the etcd keys, it is created on the first
+/// access.
+pub struct Store {
+    client: SekasClient,
+    target: OnceCell<(Database, u64)>,
+    lease_collection_id: OnceCell<u64>,
+    meta_collection_id: OnceCell<u64>,
+    auth_collection_id: OnceCell<u64>,
+    /// The GC lease held by this proxy and the timestamp it holds.
+    gc_lease: Mutex<Option<(u64, u64)>>,
+    /// The events generated by the writes of each revision.
+    events: broadcast::Sender<(i64, Vec<Event>)>,
+    auth_cache: AuthCache,
+    /// Whether the etcd authentication is required regardless of the auth
+    /// enabled flag, eg. the authentication of sekas is enabled.
+    auth_required: bool,
+}
+
+impl Store {
+    pub fn new(client: SekasClient) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Store {
+            client,
+            target: OnceCell::new(),
+            lease_collection_id: OnceCell::new(),
+            meta_collection_id: OnceCell::new(),
+            auth_collection_id: OnceCell::new(),
+            gc_lease: Mutex::new(None),
+            events,
+            auth_cache: AuthCache::default(),
+            auth_required: false,
+        }
+    }
+
+    /// Require the etcd authentication, so the auth can not be disabled.
+    pub fn with_auth_required(mut self, required: bool) -> Self {
+        self.auth_required = required;
+        self
+    }
+
+    /// Publish the events of a revision, to wake up the watchers of this proxy.
+    pub fn publish(&self, revision: i64, events: Vec<Event>) {
+        // It is fine if there is no any watchers.
+        let _ = self.events.send((revision, events));
+    }
+
+    /// Subscribe the events published by this proxy.
+    pub fn subscribe(&self) -> broadcast::Receiver<(i64, Vec<Event>)> {
+        self.events.subscribe()
+    }
+
+    /// Return the database and collection id to store the etcd keys.
+    pub async fn target(&self) -> AppResult<&(Database, u64)> {
+        self.target
+            .get_or_try_init(|| async {
+                let database = match self.client.open_database(DATABASE.to_owned()).await {
+                    Err(AppError::NotFound(_)) => {
+                        match self.client.create_database(DATABASE.to_owned()).await {
+                            Err(AppError::AlreadyExists(_)) => {
+                                self.client.open_database(DATABASE.to_owned()).await
+                            }
+                            result => result,
+                        }
+                    }
+                    result => result,
+                }?;
+                let collection_id = open_or_create_collection(&database, COLLECTION).await?;
+                Ok((database, collection_id))
+            })
+            .await
+    }
+
+    /// Return the id of the collection to store the etcd leases, it is in the
+    /// same database as the etcd keys.
+    pub async fn lease_collection_id(&self) -> AppResult<u64> {
+        let (database, _) = self.target().await?;
+        self.lease_collection_id
+            .get_or_try_init(|| open_or_create_collection(database, LEASE_COLLECTION))
+            .await
+            .cloned()
+    }
+
+    /// Return the id of the collection to store the metadata.
+    pub async fn meta_collection_id(&self) -> AppResult<u64> {
+        let (database, _) = self.target().await?;
+        self.meta_collection_id
+            .get_or_try_init(|| open_or_create_collection(database, META_COLLECTION))
+            .await
+            .cloned()
+    }
+
+    /// Return the id of the collection to store the etcd users and roles.
+    pub async fn auth_collection_id(&self) -> AppResult<u64> {
+        let (database, _) = self.target().await?;
+        self.auth_collection_id
+            .get_or_try_init(|| open_or_create_collection(database, AUTH_COLLECTION))
+            .await
+            .cloned()
+    }
+
+    /// Return the GC lease held by this proxy.
+    pub(crate) fn gc_lease(&self) -> &Mutex<Option<(u64, u64)>> {
+        &self.gc_lease
+    }
+
+    /// Return the users and roles cached by this proxy.
+    pub(crate) fn auth_cache(&self) -> &AuthCache {
+        &self.auth_cache
+    }
+
+    pub(crate) fn auth_required(&self) -> bool {
+        self.auth_required
+    }
+}
+
+async fn open_or_create_collection(database: &Database, name: &str) -> AppResult<u64> {
+    let collection = match database.open_collection(name.to_owned()).await {
+        Err(AppError::NotFound(_)) => match database.create_collection(name.to_owned()).await {
+            Err(AppError::AlreadyExists(_)) => database.open_collection(name.to_owned()).await,
+            result => result,
+        },
+        result => result,
+    }?;
+    Ok(collection.id)
+}
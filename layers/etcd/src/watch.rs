@@ -11,24 +11,101 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-use tonic::{Request, Response, Streaming};
+use log::warn;
+use sekas_api::server::v1::ValueSet;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tonic::{Request, Response, Status, Streaming};
 
+use crate::auth::{identify, Identity, Permission};
+use crate::compact::compact_revision;
+use crate::etcd::v3::event::EventType;
+use crate::etcd::v3::watch_create_request::FilterType;
+use crate::etcd::v3::watch_request::RequestUnion;
 use crate::etcd::v3::{watch_server, *};
+use crate::kv::{decode_key_value, read_changes, response_header, to_revision};
+use crate::lease::resolve_leases;
+use crate::store::Store;
 
 type Result<T> = std::result::Result<T, tonic::Status>;
 
-pub struct Watch {}
+/// The interval to catch up the MVCC versions, so that the writes from other
+/// proxies are observed. The writes of this proxy wake up the change reader
+/// immediately.
+const CATCH_UP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of pending changes of the change feed, the lagged watchers have
+/// to catch up from the MVCC versions.
+const CHANGE_FEED_CAPACITY: usize = 64;
+
+/// The number of pending responses of a watch stream, the watchers wait for
+/// the stream to be drained, and catch up from the MVCC versions at once if
+/// the change feed is lagged.
+const WATCH_STREAM_CAPACITY: usize = 128;
+
+/// The interval of the progress notifications.
+const PROGRESS_NOTIFY_INTERVAL: Duration = Duration::from_secs(10);
 
-pub struct WatchStream {}
+/// The etcd watch service. The events are derived from the MVCC versions of
+/// sekas, so the watchers could resume from any revision which has not been
+/// reclaimed by MVCC gc, the gc safe point is reported as the compact revision.
+///
+/// The changes are read by a change reader of the proxy and fanned out to the
+/// watchers, a watcher only reads the MVCC versions of its range when it is
+/// created or lagged.
+pub struct Watch {
+    store: Arc<Store>,
+    changes: broadcast::Sender<Arc<Changes>>,
+}
+
+pub struct WatchStream {
+    receiver: mpsc::Receiver<Result<WatchResponse>>,
+}
+
+/// The events of all etcd keys in the revisions `(prev_revision, revision]`.
+struct Changes {
+    prev_revision: i64,
+    revision: i64,
+    events: Vec<Event>,
+}
+
+struct WatcherHandle {
+    handle: JoinHandle<()>,
+    /// All events at or before this revision have been sent.
+    progress: Arc<AtomicI64>,
+}
+
+struct Watcher {
+    store: Arc<Store>,
+    changes: broadcast::Receiver<Arc<Changes>>,
+    watch_id: i64,
+    request: WatchCreateRequest,
+    progress: Arc<AtomicI64>,
+    sender: mpsc::Sender<Result<WatchResponse>>,
+}
+
+impl Watch {
+    /// Create the watch service, and spawn the change reader.
+    pub(crate) fn new(store: Arc<Store>) -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_FEED_CAPACITY);
+        tokio::spawn(read_changes_periodically(store.clone(), changes.clone()));
+        Watch { store, changes }
+    }
+}
 
 impl futures::Stream for WatchStream {
     type Item = Result<WatchResponse>;
 
-    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        todo!()
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
     }
 }
 
@@ -45,8 +122,402 @@ impl watch_server::Watch for Watch {
     /// compaction revision.
     async fn watch(
         &self,
-        _request: Request<Streaming<WatchRequest>>,
+        request: Request<Streaming<WatchRequest>>,
     ) -> Result<Response<WatchStream>> {
-        todo!()
+        let identity = identify(&self.store, &request).await?;
+        let (sender, receiver) = mpsc::channel(WATCH_STREAM_CAPACITY);
+        let requests = request.into_inner();
+        let store = self.store.clone();
+        let changes = self.changes.clone();
+        tokio::spawn(serve_watch_requests(store, changes, identity, requests, sender));
+        Ok(Response::new(WatchStream { receiver }))
+    }
+}
+
+/// Read the changes of all etcd keys, when the keys are written by this proxy
+/// or periodically, and publish them to the watchers. The changes are not
+/// read if there is no watchers.
+async fn read_changes_periodically(store: Arc<Store>, changes: broadcast::Sender<Arc<Changes>>) {
+    let mut subscriber = store.subscribe();
+    let mut interval = tokio::time::interval(CATCH_UP_INTERVAL);
+    // The revision is unknown if it is zero.
+    let mut revision = 0;
+    loop {
+        tokio::select! {
+            received = subscriber.recv() => {
+                if let Err(broadcast::error::RecvError::Closed) = received {
+                    return;
+                }
+                // The writes since the last read are read together.
+                while subscriber.try_recv().is_ok() {}
+            }
+            _ = interval.tick() => {}
+        }
+        if changes.receiver_count() == 0 {
+            revision = 0;
+            continue;
+        }
+
+        let from_revision = if revision == 0 { 0 } else { revision + 1 };
+        match read_events(&store, &[0], &[0], from_revision).await {
+            Ok((read_revision, events)) => {
+                let prev_revision = if revision == 0 { read_revision } else { revision };
+                revision = read_revision;
+                let _ = changes.send(Arc::new(Changes { prev_revision, revision, events }));
+            }
+            Err(status) => {
+                warn!("read changes of etcd keys since revision {revision}: {status}");
+            }
+        }
+    }
+}
+
+async fn serve_watch_requests(
+    store: Arc<Store>,
+    changes: broadcast::Sender<Arc<Changes>>,
+    identity: Identity,
+    mut requests: Streaming<WatchRequest>,
+    sender: mpsc::Sender<Result<WatchResponse>>,
+) {
+    let mut watchers: HashMap<i64, WatcherHandle> = HashMap::default();
+    let mut next_watch_id = 0;
+    loop {
+        let request = match requests.message().await {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(status) => {
+                let _ = sender.send(Err(status)).await;
+                break;
+            }
+        };
+        match request.request_union {
+            Some(RequestUnion::CreateRequest(request)) => {
+                let watch_id = if request.watch_id > 0 {
+                    request.watch_id
+                } else {
+                    while watchers.contains_key(&next_watch_id) {
+                        next_watch_id += 1;
+                    }
+                    next_watch_id
+                };
+                if watchers.get(&watch_id).map(|w| !w.handle.is_finished()).unwrap_or_default() {
+                    let _ = sender
+                        .send(Ok(WatchResponse {
+                            watch_id,
+                            created: true,
+                            canceled: true,
+                            cancel_reason: "etcdserver: duplicated watch id".to_owned(),
+                            ..Default::default()
+                        }))
+                        .await;
+                    continue;
+                }
+                if let Err(status) =
+                    identity.check_range(&request.key, &request.range_end, Permission::Read)
+                {
+                    let _ = sender
+                        .send(Ok(WatchResponse {
+                            watch_id,
+                            created: true,
+                            canceled: true,
+                            cancel_reason: status.message().to_owned(),
+                            ..Default::default()
+                        }))
+                        .await;
+                    continue;
+                }
+                let progress = Arc::new(AtomicI64::new(0));
+                let watcher = Watcher {
+                    store: store.clone(),
+                    changes: changes.subscribe(),
+                    watch_id,
+                    request,
+                    progress: progress.clone(),
+                    sender: sender.clone(),
+                };
+                let handle = tokio::spawn(watcher.run());
+                watchers.insert(watch_id, WatcherHandle { handle, progress });
+            }
+            Some(RequestUnion::CancelRequest(request)) => {
+                if let Some(watcher) = watchers.remove(&request.watch_id) {
+                    watcher.handle.abort();
+                    let _ = sender
+                        .send(Ok(WatchResponse {
+                            watch_id: request.watch_id,
+                            canceled: true,
+                            ..Default::default()
+                        }))
+                        .await;
+                }
+            }
+            Some(RequestUnion::ProgressRequest(_)) => {
+                let revision = watchers
+                    .values()
+                    .filter(|w| !w.handle.is_finished())
+                    .map(|w| w.progress.load(Ordering::Acquire))
+                    .min()
+                    .unwrap_or_default();
+                let _ = sender
+                    .send(Ok(WatchResponse {
+                        header: response_header(revision),
+                        watch_id: -1,
+                        ..Default::default()
+                    }))
+                    .await;
+            }
+            None => {}
+        }
+    }
+    for watcher in watchers.into_values() {
+        watcher.handle.abort();
+    }
+}
+
+impl Watcher {
+    async fn run(mut self) {
+        if let Err(status) = self.watch().await {
+            let _ = self
+                .sender
+                .send(Ok(WatchResponse {
+                    watch_id: self.watch_id,
+                    canceled: true,
+                    cancel_reason: status.message().to_owned(),
+                    ..Default::default()
+                }))
+                .await;
+        }
+    }
+
+    async fn watch(&mut self) -> Result<()> {
+        let start_revision = self.request.start_revision;
+        if start_revision > 0 {
+            let compact_revision = compact_revision(&self.store).await?;
+            if start_revision < compact_revision {
+                self.send(WatchResponse { created: true, ..Default::default() }).await?;
+                return self
+                    .send(WatchResponse {
+                        canceled: true,
+                        compact_revision,
+                        cancel_reason: "etcdserver: mvcc: required revision has been compacted"
+                            .to_owned(),
+                        ..Default::default()
+                    })
+                    .await;
+            }
+        }
+
+        // The change feed is subscribed before reading, so no changes are missed.
+        let (mut revision, events) = self.read_events(start_revision).await?;
+        self.send(WatchResponse {
+            header: response_header(revision),
+            created: true,
+            ..Default::default()
+        })
+        .await?;
+        self.send_events(revision, events).await?;
+
+        let mut last_sent = Instant::now();
+        loop {
+            let (read_revision, events) = match self.changes.recv().await {
+                Ok(changes) if changes.revision <= revision => continue,
+                Ok(changes) if changes.prev_revision <= revision => {
+                    let events = changes
+                        .events
+                        .iter()
+                        .filter(|e| {
+                            e.kv.as_ref().map(|kv| kv.mod_revision).unwrap_or_default() > revision
+                        })
+                        .filter(|e| self.is_watched(e))
+                        .cloned()
+                        .collect();
+                    (changes.revision, events)
+                }
+                // Some changes are missed, catch up from the MVCC versions at once.
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                    self.read_events(revision + 1).await?
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            };
+            if !events.is_empty() {
+                last_sent = Instant::now();
+            }
+            self.send_events(read_revision, events).await?;
+            revision = read_revision;
+            if self.request.progress_notify && last_sent.elapsed() >= PROGRESS_NOTIFY_INTERVAL {
+                last_sent = Instant::now();
+                self.send(WatchResponse {
+                    header: response_header(revision),
+                    ..Default::default()
+                })
+                .await?;
+            }
+        }
+    }
+
+    /// Read the events of the watched range since `from_revision` from the
+    /// MVCC versions, return the read revision and the events.
+    async fn read_events(&self, from_revision: i64) -> Result<(i64, Vec<Event>)> {
+        let (key, range_end) = (&self.request.key, &self.request.range_end);
+        let (revision, events) = read_events(&self.store, key, range_end, from_revision).await?;
+        let events = events.into_iter().filter(|event| self.is_watched(event)).collect();
+        Ok((revision, events))
+    }
+
+    async fn send_events(&self, revision: i64, mut events: Vec<Event>) -> Result<()> {
+        self.progress.store(revision, Ordering::Release);
+        if events.is_empty() {
+            return Ok(());
+        }
+        if !self.request.prev_kv {
+            events.iter_mut().for_each(|event| event.prev_kv = None);
+        }
+        self.send(WatchResponse { header: response_header(revision), events, ..Default::default() })
+            .await
+    }
+
+    async fn send(&self, mut resp: WatchResponse) -> Result<()> {
+        resp.watch_id = self.watch_id;
+        self.sender.send(Ok(resp)).await.map_err(|_| Status::cancelled("watch stream is closed"))
+    }
+
+    /// Whether the event is in the watched range and not filtered.
+    fn is_watched(&self, event: &Event) -> bool {
+        let filter = match event.r#type() {
+            EventType::Put => FilterType::Noput,
+            EventType::Delete => FilterType::Nodelete,
+        };
+        let key = event.kv.as_ref().map(|kv| kv.key.as_slice()).unwrap_or_default();
+        in_range(key, &self.request.key, &self.request.range_end)
+            && !self.request.filters().any(|f| f == filter)
+    }
+}
+
+/// Read the events in the etcd range since `from_revision` from the MVCC
+/// versions, return the read revision and the events. Only the read revision
+/// is returned if `from_revision` is zero.
+async fn read_events(
+    store: &Store,
+    key: &[u8],
+    range_end: &[u8],
+    from_revision: i64,
+) -> Result<(i64, Vec<Event>)> {
+    let (database, collection_id) = store.target().await?;
+    // Only the keys changed since `from_revision` are read, no keys are changed
+    // since the max version.
+    let min_version = if from_revision == 0 { u64::MAX } else { from_revision as u64 };
+    let (read_version, value_sets) =
+        read_changes(database, *collection_id, key, range_end, min_version).await?;
+    let revision = to_revision(read_version);
+    if from_revision == 0 {
+        return Ok((revision, vec![]));
+    }
+    let mut events = history_events(value_sets, from_revision);
+    let kvs = events.iter_mut().flat_map(|e| e.kv.iter_mut().chain(e.prev_kv.iter_mut()));
+    resolve_leases(store, kvs, Some(read_version), true).await?;
+    Ok((revision, events))
+}
+
+/// Whether the key is in the etcd range.
+fn in_range(key: &[u8], start: &[u8], range_end: &[u8]) -> bool {
+    match range_end {
+        [] => key == start,
+        [0] => start == [0] || key >= start,
+        _ => start <= key && key < range_end,
+    }
+}
+
+/// Build the events with revision not less than `from_revision` from the MVCC
+/// versions of keys, the events are sorted by revision.
+fn history_events(value_sets: Vec<ValueSet>, from_revision: i64) -> Vec<Event> {
+    let mut events = vec![];
+    for value_set in value_sets {
+        let mut prev_kv: Option<KeyValue> = None;
+        // The values are in desc order of version.
+        for value in value_set.values.into_iter().rev() {
+            let revision = to_revision(value.version);
+            let event = match value.content {
+                Some(_) => {
+                    let Some(kv) = decode_key_value(value_set.user_key.clone(), value) else {
+                        continue;
+                    };
+                    let prev_kv = std::mem::replace(&mut prev_kv, Some(kv.clone()));
+                    Event { r#type: EventType::Put.into(), kv: Some(kv), prev_kv }
+                }
+                None => {
+                    let Some(prev_kv) = prev_kv.take() else { continue };
+                    let kv = KeyValue {
+                        key: value_set.user_key.clone(),
+                        mod_revision: revision,
+                        ..Default::default()
+                    };
+                    Event { r#type: EventType::Delete.into(), kv: Some(kv), prev_kv: Some(prev_kv) }
+                }
+            };
+            if revision >= from_revision {
+                events.push(event);
+            }
+        }
+    }
+    // The events of different keys are interleaved by revision.
+    events.sort_by_key(|event| event.kv.as_ref().map(|kv| kv.mod_revision).unwrap_or_default());
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::Value;
+
+    use super::*;
+    use crate::kv::encode_value;
+
+    fn value(content: Option<&[u8]>, version: u64) -> Value {
+        Value { content: content.map(ToOwned::to_owned), version, ..Default::default() }
+    }
+
+    #[test]
+    fn etcd_range_contains_key() {
+        assert!(in_range(b"a", b"a", b""));
+        assert!(!in_range(b"ab", b"a", b""));
+        assert!(in_range(b"ab", b"a", b"b"));
+        assert!(!in_range(b"b", b"a", b"b"));
+        assert!(in_range(b"z", b"a", b"\0"));
+        assert!(in_range(b"", b"\0", b"\0"));
+    }
+
+    #[test]
+    fn build_history_events() {
+        let a1 = encode_value(None, b"a1");
+        let a2 = encode_value(None, b"a2");
+        let b1 = encode_value(None, b"b1");
+        let b2 = encode_value(
+            Some(&KeyValue { create_revision: 20, version: 1, ..Default::default() }),
+            b"b2",
+        );
+        let value_sets = vec![
+            ValueSet {
+                user_key: b"a".to_vec(),
+                values: vec![value(Some(&a2), 40), value(None, 30), value(Some(&a1), 10)],
+            },
+            ValueSet {
+                user_key: b"b".to_vec(),
+                values: vec![value(Some(&b2), 35), value(Some(&b1), 20)],
+            },
+        ];
+        let events = history_events(value_sets.clone(), 0);
+        let revisions =
+            events.iter().map(|e| e.kv.as_ref().unwrap().mod_revision).collect::<Vec<_>>();
+        assert_eq!(revisions, vec![10, 20, 30, 35, 40]);
+        assert_eq!(events[2].r#type(), EventType::Delete);
+        assert_eq!(events[2].prev_kv.as_ref().unwrap().value, b"a1");
+        // The key is created again after deleted.
+        assert_eq!(events[4].kv.as_ref().unwrap().create_revision, 40);
+        assert_eq!(events[4].kv.as_ref().unwrap().version, 1);
+        assert!(events[4].prev_kv.is_none());
+        // The second version of key b.
+        assert_eq!(events[3].kv.as_ref().unwrap().create_revision, 20);
+        assert_eq!(events[3].kv.as_ref().unwrap().version, 2);
+
+        let events = history_events(value_sets, 31);
+        assert_eq!(events.len(), 2);
     }
 }
@@ -0,0 +1,257 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use sekas_etcd_proxy::etcd::v3::compare::{CompareResult, CompareTarget, TargetUnion};
+use sekas_etcd_proxy::etcd::v3::kv_client::KvClient;
+use sekas_etcd_proxy::etcd::v3::lease_client::LeaseClient;
+use sekas_etcd_proxy::etcd::v3::watch_client::WatchClient;
+use sekas_etcd_proxy::etcd::v3::watch_request::RequestUnion;
+use sekas_etcd_proxy::etcd::v3::*;
+use sekas_etcd_proxy::Store;
+use sekas_runtime::TcpIncoming;
+use sekas_testkit::{ClusterClient, TestContext};
+use tokio::net::TcpListener;
+use tonic::transport::Server;
+
+/// Serve the etcd services of a proxy, return the address.
+async fn serve_etcd_proxy(store: Arc<Store>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::builder()
+        .add_service(sekas_etcd_proxy::make_etcd_kv_service(store.clone()))
+        .add_service(sekas_etcd_proxy::make_etcd_watch_service(store.clone()))
+        .add_service(sekas_etcd_proxy::make_etcd_lease_service(store))
+        .serve_with_incoming(TcpIncoming::from_listener(listener, true));
+    sekas_runtime::spawn(server);
+    format!("http://{addr}")
+}
+
+fn compare_lease(key: &[u8], result: CompareResult, lease: i64) -> Compare {
+    Compare {
+        result: result.into(),
+        target: CompareTarget::Lease.into(),
+        key: key.to_vec(),
+        target_union: Some(TargetUnion::Lease(lease)),
+        ..Default::default()
+    }
+}
+
+#[sekas_macro::test]
+async fn etcd_key_value_with_lease() {
+    let mut ctx = TestContext::new("etcd_key_value_with_lease");
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let store = Arc::new(Store::new(c.app_client().await));
+    let addr = serve_etcd_proxy(store).await;
+    let mut kv = KvClient::connect(addr.clone()).await.unwrap();
+    let mut lease = LeaseClient::connect(addr.clone()).await.unwrap();
+    let mut watch = WatchClient::connect(addr).await.unwrap();
+
+    let grant = LeaseGrantRequest { ttl: 60, ..Default::default() };
+    let id = lease.lease_grant(grant).await.unwrap().into_inner().id;
+    let put =
+        PutRequest { key: b"k1".to_vec(), value: b"v1".to_vec(), lease: id, ..Default::default() };
+    let put_resp = kv.put(put).await.unwrap().into_inner();
+    let revision = put_resp.header.unwrap().revision;
+    let put = PutRequest { key: b"k2".to_vec(), value: b"v2".to_vec(), ..Default::default() };
+    kv.put(put).await.unwrap();
+
+    // Range reports the lease of keys.
+    let range = RangeRequest { key: b"k".to_vec(), range_end: b"l".to_vec(), ..Default::default() };
+    let kvs = kv.range(range).await.unwrap().into_inner().kvs;
+    let leases = kvs.iter().map(|kv| (kv.key.clone(), kv.lease)).collect::<Vec<_>>();
+    assert_eq!(leases, vec![(b"k1".to_vec(), id), (b"k2".to_vec(), 0)]);
+
+    // Watch reports the lease of the history events.
+    let create =
+        WatchCreateRequest { key: b"k1".to_vec(), start_revision: revision, ..Default::default() };
+    let request = WatchRequest { request_union: Some(RequestUnion::CreateRequest(create)) };
+    let requests = futures::stream::iter(vec![request]).chain(futures::stream::pending());
+    let mut responses = watch.watch(requests).await.unwrap().into_inner();
+    let event = loop {
+        let resp = responses.message().await.unwrap().unwrap();
+        if let Some(event) = resp.events.into_iter().next() {
+            break event;
+        }
+    };
+    let event_kv = event.kv.unwrap();
+    assert_eq!(event_kv.mod_revision, revision);
+    assert_eq!(event_kv.lease, id);
+
+    // Compare the lease of keys in txn.
+    let txn = TxnRequest {
+        compare: vec![
+            compare_lease(b"k1", CompareResult::Equal, id),
+            compare_lease(b"k2", CompareResult::Equal, 0),
+        ],
+        ..Default::default()
+    };
+    assert!(kv.txn(txn).await.unwrap().into_inner().succeeded);
+    let txn = TxnRequest {
+        compare: vec![compare_lease(b"k1", CompareResult::NotEqual, id)],
+        ..Default::default()
+    };
+    assert!(!kv.txn(txn).await.unwrap().into_inner().succeeded);
+
+    // The key is detached from the lease by the put without lease.
+    let put = PutRequest {
+        key: b"k1".to_vec(),
+        value: b"v3".to_vec(),
+        prev_kv: true,
+        ..Default::default()
+    };
+    let prev_kv = kv.put(put).await.unwrap().into_inner().prev_kv.unwrap();
+    assert_eq!(prev_kv.lease, id);
+    let range = RangeRequest { key: b"k1".to_vec(), ..Default::default() };
+    let kvs = kv.range(range).await.unwrap().into_inner().kvs;
+    assert_eq!(kvs[0].lease, 0);
+    let txn = TxnRequest {
+        compare: vec![compare_lease(b"k1", CompareResult::Equal, 0)],
+        ..Default::default()
+    };
+    assert!(kv.txn(txn).await.unwrap().into_inner().succeeded);
+}
+
+#[sekas_macro::test]
+async fn etcd_watchers_share_changes() {
+    let mut ctx = TestContext::new("etcd_watchers_share_changes");
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let store = Arc::new(Store::new(c.app_client().await));
+    let addr = serve_etcd_proxy(store).await;
+    let mut kv = KvClient::connect(addr.clone()).await.unwrap();
+    let mut watch = WatchClient::connect(addr).await.unwrap();
+
+    let create = |key: &[u8]| WatchRequest {
+        request_union: Some(RequestUnion::CreateRequest(WatchCreateRequest {
+            key: key.to_vec(),
+            ..Default::default()
+        })),
+    };
+    let requests =
+        futures::stream::iter(vec![create(b"a"), create(b"b")]).chain(futures::stream::pending());
+    let mut responses = watch.watch(requests).await.unwrap().into_inner();
+    let mut num_created = 0;
+    while num_created < 2 {
+        if responses.message().await.unwrap().unwrap().created {
+            num_created += 1;
+        }
+    }
+
+    for key in [b"a", b"b", b"c", b"a"] {
+        let put = PutRequest { key: key.to_vec(), value: b"v".to_vec(), ..Default::default() };
+        kv.put(put).await.unwrap();
+    }
+
+    // Each watcher only receives the events of its key.
+    let mut events: HashMap<i64, Vec<KeyValue>> = HashMap::default();
+    while events.values().map(Vec::len).sum::<usize>() < 3 {
+        let resp = responses.message().await.unwrap().unwrap();
+        let kvs = resp.events.into_iter().map(|event| event.kv.unwrap());
+        events.entry(resp.watch_id).or_default().extend(kvs);
+    }
+    let mut watched = events
+        .into_values()
+        .map(|kvs| kvs.into_iter().map(|kv| (kv.key, kv.version)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    watched.sort();
+    assert_eq!(
+        watched,
+        vec![vec![(b"a".to_vec(), 1), (b"a".to_vec(), 2)], vec![(b"b".to_vec(), 1)]]
+    );
+}
+
+#[sekas_macro::test]
+async fn etcd_txn_guards_range_inserts() {
+    let mut ctx = TestContext::new("etcd_txn_guards_range_inserts");
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let store = Arc::new(Store::new(c.app_client().await));
+    let addr = serve_etcd_proxy(store.clone()).await;
+    let mut kv = KvClient::connect(addr).await.unwrap();
+    let (database, _) = store.target().await.unwrap();
+    let meta_collection_id = store.meta_collection_id().await.unwrap();
+    let guard_version = || async {
+        let value = database.get_raw_value(meta_collection_id, b"insert_guard".to_vec());
+        value.await.unwrap().map(|v| v.version)
+    };
+
+    // The insert guard is bumped by the created keys only.
+    let put = PutRequest { key: b"k1".to_vec(), value: b"v1".to_vec(), ..Default::default() };
+    kv.put(put).await.unwrap();
+    let version = guard_version().await.unwrap();
+    let put = PutRequest { key: b"k1".to_vec(), value: b"v2".to_vec(), ..Default::default() };
+    kv.put(put).await.unwrap();
+    assert_eq!(guard_version().await, Some(version));
+
+    // The txn reading a range validates the guard without bumping it.
+    let range = RangeRequest { key: b"k".to_vec(), range_end: b"l".to_vec(), ..Default::default() };
+    let put = PutRequest { key: b"k1".to_vec(), value: b"v3".to_vec(), ..Default::default() };
+    let txn = TxnRequest {
+        success: vec![
+            RequestOp { request: Some(request_op::Request::RequestRange(range.clone())) },
+            RequestOp { request: Some(request_op::Request::RequestPut(put)) },
+        ],
+        ..Default::default()
+    };
+    assert!(kv.txn(txn).await.unwrap().into_inner().succeeded);
+    assert_eq!(guard_version().await, Some(version));
+
+    // The txn creating a key in the range read bumps the guard.
+    let put = PutRequest { key: b"k2".to_vec(), value: b"v1".to_vec(), ..Default::default() };
+    let txn = TxnRequest {
+        success: vec![
+            RequestOp { request: Some(request_op::Request::RequestRange(range.clone())) },
+            RequestOp { request: Some(request_op::Request::RequestPut(put)) },
+        ],
+        ..Default::default()
+    };
+    assert!(kv.txn(txn).await.unwrap().into_inner().succeeded);
+    assert!(guard_version().await.unwrap() > version);
+    let kvs = kv.range(range).await.unwrap().into_inner().kvs;
+    assert_eq!(kvs.len(), 2);
+}
+
+#[sekas_macro::test]
+async fn etcd_range_rejects_future_revision() {
+    let mut ctx = TestContext::new("etcd_range_rejects_future_revision");
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let store = Arc::new(Store::new(c.app_client().await));
+    let addr = serve_etcd_proxy(store).await;
+    let mut kv = KvClient::connect(addr).await.unwrap();
+
+    let put = PutRequest { key: b"k1".to_vec(), value: b"v1".to_vec(), ..Default::default() };
+    let revision = kv.put(put).await.unwrap().into_inner().header.unwrap().revision;
+
+    let range = RangeRequest { key: b"k1".to_vec(), revision, ..Default::default() };
+    assert_eq!(kv.range(range).await.unwrap().into_inner().kvs.len(), 1);
+
+    let future =
+        RangeRequest { key: b"k1".to_vec(), revision: revision + 1000, ..Default::default() };
+    let status = kv.range(future.clone()).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::OutOfRange);
+    assert!(status.message().contains("future revision"));
+
+    let txn = TxnRequest {
+        success: vec![RequestOp { request: Some(request_op::Request::RequestRange(future)) }],
+        ..Default::default()
+    };
+    let status = kv.txn(txn).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::OutOfRange);
+}
@@ -0,0 +1,155 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A Redis protocol front end, it speaks RESP and maps the commands onto a
+//! sekas collection, so that the Redis clients could be used without changes.
+//!
+//! The supported commands are `GET`, `SET`, `DEL`, `INCR`, `INCRBY`, `DECR`,
+//! `DECRBY`, `MGET`, `SCAN`, `EXPIRE`, `PING`, `ECHO` and `QUIT`. The
+//! counters are stored as decimal strings like Redis, and updated by the
+//! read-modify-write with the version CAS.
+
+mod resp;
+mod session;
+
+use std::io;
+use std::sync::Arc;
+
+use log::{debug, info, warn};
+use sekas_client::{AppError, AppResult, Database, SekasClient};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::OnceCell;
+
+pub use self::resp::Frame;
+use self::session::Session;
+
+#[derive(Debug, Clone)]
+pub struct RedisOptions {
+    /// The database to store the redis keys, it is created if not exists.
+    pub database: String,
+    /// The collection to store the redis keys, it is created if not exists.
+    pub collection: String,
+}
+
+struct Layer {
+    client: SekasClient,
+    opts: RedisOptions,
+    target: OnceCell<(Database, u64)>,
+}
+
+/// Accept the redis connections from the listener and serve them until the
+/// listener is broken.
+pub async fn serve(
+    listener: TcpListener,
+    client: SekasClient,
+    opts: RedisOptions,
+) -> io::Result<()> {
+    info!("redis layer is serving at {}", listener.local_addr()?);
+    let layer = Arc::new(Layer { client, opts, target: OnceCell::new() });
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let layer = layer.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_connection(stream, layer).await {
+                warn!("serve redis connection from {peer}: {err}");
+            }
+            debug!("redis connection from {peer} is closed");
+        });
+    }
+}
+
+async fn serve_connection(mut stream: TcpStream, layer: Arc<Layer>) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut session = Session::new(layer);
+    let mut input = Vec::with_capacity(4096);
+    let mut output = Vec::with_capacity(4096);
+    let mut chunk = vec![0u8; 4096];
+    loop {
+        loop {
+            let (frame, consumed) = match resp::parse(&input) {
+                Ok(Some(parsed)) => parsed,
+                Ok(None) => break,
+                Err(err) => {
+                    // The rest of the input could not be framed, so the connection is closed
+                    // after replying the error, like redis does.
+                    Frame::error(format!("Protocol error: {err}")).encode(&mut output);
+                    stream.write_all(&output).await?;
+                    return Err(err);
+                }
+            };
+            input.drain(..consumed);
+            let args = match frame.into_args() {
+                Ok(args) => args,
+                Err(reply) => {
+                    reply.encode(&mut output);
+                    continue;
+                }
+            };
+            if args.first().map(|cmd| cmd.eq_ignore_ascii_case(b"QUIT")).unwrap_or_default() {
+                Frame::ok().encode(&mut output);
+                stream.write_all(&output).await?;
+                return Ok(());
+            }
+            session.execute(args).await.encode(&mut output);
+        }
+        if !output.is_empty() {
+            stream.write_all(&output).await?;
+            output.clear();
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        input.extend_from_slice(&chunk[..n]);
+    }
+}
+
+impl Layer {
+    /// Return the database and collection id to store the redis keys.
+    async fn target(&self) -> AppResult<&(Database, u64)> {
+        self.target
+            .get_or_try_init(|| async {
+                let database = open_or_create(
+                    || self.client.open_database(self.opts.database.clone()),
+                    || self.client.create_database(self.opts.database.clone()),
+                )
+                .await?;
+                let collection = open_or_create(
+                    || database.open_collection(self.opts.collection.clone()),
+                    || database.create_collection(self.opts.collection.clone()),
+                )
+                .await?;
+                Ok((database, collection.id))
+            })
+            .await
+    }
+}
+
+async fn open_or_create<T, O, C, OF, CF>(open: O, create: C) -> AppResult<T>
+where
+    O: Fn() -> OF,
+    C: FnOnce() -> CF,
+    OF: std::future::Future<Output = AppResult<T>>,
+    CF: std::future::Future<Output = AppResult<T>>,
+{
+    match open().await {
+        Err(AppError::NotFound(_)) => match create().await {
+            // Created by another proxy concurrently.
+            Err(AppError::AlreadyExists(_)) => open().await,
+            result => result,
+        },
+        result => result,
+    }
+}
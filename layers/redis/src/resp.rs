@@ -0,0 +1,276 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io;
+
+/// The max length of a bulk string, the same as the default
+/// `proto-max-bulk-len` of redis.
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// The max number of elements of an array.
+const MAX_ARRAY_LEN: usize = 1024 * 1024;
+
+/// The max length of an inline command or a line of the frame header.
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// The max nesting depth of the arrays.
+const MAX_DEPTH: usize = 8;
+
+/// The frame of redis serialization protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    /// `None` for the null bulk string.
+    Bulk(Option<Vec<u8>>),
+    /// `None` for the null array.
+    Array(Option<Vec<Frame>>),
+}
+
+impl Frame {
+    #[inline]
+    pub fn ok() -> Self {
+        Frame::Simple("OK".to_owned())
+    }
+
+    #[inline]
+    pub fn error(msg: impl std::fmt::Display) -> Self {
+        Frame::Error(format!("ERR {msg}"))
+    }
+
+    #[inline]
+    pub fn bulk(value: impl Into<Vec<u8>>) -> Self {
+        Frame::Bulk(Some(value.into()))
+    }
+
+    /// Convert a request frame into the command arguments, the error reply is
+    /// returned if the frame is not an array of bulk strings.
+    pub fn into_args(self) -> Result<Vec<Vec<u8>>, Frame> {
+        let Frame::Array(Some(frames)) = self else {
+            return Err(Frame::error("Protocol error: expect an array of bulk strings"));
+        };
+        frames
+            .into_iter()
+            .map(|frame| match frame {
+                Frame::Bulk(Some(arg)) => Ok(arg),
+                Frame::Simple(arg) => Ok(arg.into_bytes()),
+                _ => Err(Frame::error("Protocol error: expect bulk strings")),
+            })
+            .collect()
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Frame::Simple(s) => {
+                buf.push(b'+');
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Frame::Error(s) => {
+                buf.push(b'-');
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Frame::Integer(v) => {
+                buf.push(b':');
+                buf.extend_from_slice(v.to_string().as_bytes());
+            }
+            Frame::Bulk(None) => buf.extend_from_slice(b"$-1"),
+            Frame::Bulk(Some(data)) => {
+                buf.push(b'$');
+                buf.extend_from_slice(data.len().to_string().as_bytes());
+                buf.extend_from_slice(b"\r\n");
+                buf.extend_from_slice(data);
+            }
+            Frame::Array(None) => buf.extend_from_slice(b"*-1"),
+            Frame::Array(Some(frames)) => {
+                buf.push(b'*');
+                buf.extend_from_slice(frames.len().to_string().as_bytes());
+                buf.extend_from_slice(b"\r\n");
+                for frame in frames {
+                    frame.encode(buf);
+                }
+                return;
+            }
+        }
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Parse a frame from the buffer, return the frame and the number of consumed
+/// bytes, or `None` if the buffer is incomplete. The inline commands, which
+/// are separated by whitespaces, are accepted too.
+pub fn parse(buf: &[u8]) -> io::Result<Option<(Frame, usize)>> {
+    match buf.first() {
+        None => Ok(None),
+        Some(b'*') => parse_frame(buf, 0),
+        Some(_) => {
+            let Some(end) = find_line_end(buf, 0)? else { return Ok(None) };
+            let args = buf[..end]
+                .split(|c| c.is_ascii_whitespace())
+                .filter(|arg| !arg.is_empty())
+                .map(Frame::bulk)
+                .collect();
+            Ok(Some((Frame::Array(Some(args)), end + 2)))
+        }
+    }
+}
+
+fn parse_frame(buf: &[u8], pos: usize) -> io::Result<Option<(Frame, usize)>> {
+    parse_nested_frame(buf, pos, 0)
+}
+
+fn parse_nested_frame(buf: &[u8], pos: usize, depth: usize) -> io::Result<Option<(Frame, usize)>> {
+    let Some(&kind) = buf.get(pos) else { return Ok(None) };
+    let Some(end) = find_line_end(buf, pos + 1)? else { return Ok(None) };
+    let line = &buf[pos + 1..end];
+    let next = end + 2;
+    match kind {
+        b'+' => Ok(Some((Frame::Simple(to_utf8(line)?), next))),
+        b'-' => Ok(Some((Frame::Error(to_utf8(line)?), next))),
+        b':' => Ok(Some((Frame::Integer(to_integer(line)?), next))),
+        b'$' => {
+            let len = to_integer(line)?;
+            if len < 0 {
+                return Ok(Some((Frame::Bulk(None), next)));
+            }
+            let len = to_length(len, MAX_BULK_LEN, "invalid bulk length")?;
+            let data_end =
+                next.checked_add(len).ok_or_else(|| invalid_data("invalid bulk length"))?;
+            if buf.len() < data_end + 2 {
+                return Ok(None);
+            }
+            if &buf[data_end..data_end + 2] != b"\r\n" {
+                return Err(invalid_data("bulk string is not terminated by CRLF"));
+            }
+            Ok(Some((Frame::bulk(&buf[next..data_end]), data_end + 2)))
+        }
+        b'*' => {
+            let len = to_integer(line)?;
+            if len < 0 {
+                return Ok(Some((Frame::Array(None), next)));
+            }
+            let len = to_length(len, MAX_ARRAY_LEN, "invalid multibulk length")?;
+            if depth >= MAX_DEPTH {
+                return Err(invalid_data("too many nested arrays"));
+            }
+            // The length is sent by the client, so the frames are not preallocated by it.
+            let mut frames = Vec::new();
+            let mut pos = next;
+            for _ in 0..len {
+                let Some((frame, next)) = parse_nested_frame(buf, pos, depth + 1)? else {
+                    return Ok(None);
+                };
+                frames.push(frame);
+                pos = next;
+            }
+            Ok(Some((Frame::Array(Some(frames)), pos)))
+        }
+        _ => Err(invalid_data(format!("unknown frame type {kind}"))),
+    }
+}
+
+/// Find the CRLF terminating the line starting at `pos`, an error is returned
+/// if the line is too long.
+fn find_line_end(buf: &[u8], pos: usize) -> io::Result<Option<usize>> {
+    match find_crlf(buf, pos) {
+        Some(end) if end - pos <= MAX_INLINE_LEN => Ok(Some(end)),
+        None if buf.len().saturating_sub(pos) <= MAX_INLINE_LEN => Ok(None),
+        _ => Err(invalid_data("too big inline request")),
+    }
+}
+
+fn find_crlf(buf: &[u8], pos: usize) -> Option<usize> {
+    buf.get(pos..)?.windows(2).position(|w| w == b"\r\n").map(|offset| pos + offset)
+}
+
+fn to_length(len: i64, max: usize, msg: &'static str) -> io::Result<usize> {
+    usize::try_from(len).ok().filter(|&len| len <= max).ok_or_else(|| invalid_data(msg))
+}
+
+fn to_utf8(line: &[u8]) -> io::Result<String> {
+    String::from_utf8(line.to_owned()).map_err(invalid_data)
+}
+
+fn to_integer(line: &[u8]) -> io::Result<i64> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid_data("invalid integer"))
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_and_parse() {
+        let frames = vec![
+            Frame::ok(),
+            Frame::error("unknown"),
+            Frame::Integer(-10),
+            Frame::Bulk(None),
+            Frame::bulk("value\r\n"),
+            Frame::Array(None),
+            Frame::Array(Some(vec![Frame::bulk("a"), Frame::Integer(1)])),
+        ];
+        for frame in frames {
+            let mut buf = vec![];
+            frame.encode(&mut buf);
+            if matches!(frame, Frame::Array(_)) {
+                assert_eq!(parse(&buf).unwrap(), Some((frame, buf.len())));
+            }
+            let len = buf.len();
+            assert_eq!(parse_frame(&buf, 0).unwrap().unwrap().1, len);
+            // Any prefix is incomplete.
+            assert!(parse_frame(&buf[..len - 1], 0).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn parse_inline_and_pipelined_commands() {
+        let buf = b"PING\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n";
+        let (frame, consumed) = parse(buf).unwrap().unwrap();
+        assert_eq!(frame.into_args().unwrap(), vec![b"PING".to_vec()]);
+        let (frame, rest) = parse(&buf[consumed..]).unwrap().unwrap();
+        assert_eq!(frame.into_args().unwrap(), vec![b"GET".to_vec(), b"k".to_vec()]);
+        assert_eq!(consumed + rest, buf.len());
+
+        assert!(parse(b"*1\r\n$3\r\nGETX\r\n").is_err());
+        assert!(parse(b"*1\r\n:1\r\n").unwrap().unwrap().0.into_args().is_err());
+    }
+
+    #[test]
+    fn parse_untrusted_lengths() {
+        assert!(parse(b"*9999999999\r\n").is_err());
+        assert!(parse(b"*9223372036854775807\r\n").is_err());
+        assert!(parse(b"*1\r\n$9999999999\r\n").is_err());
+        assert!(parse(b"*1\r\n$9223372036854775807\r\n").is_err());
+        // The lengths within the limits are incomplete until the data arrives.
+        assert!(parse(b"*1048576\r\n").unwrap().is_none());
+        assert!(parse(b"*1\r\n$536870912\r\n").unwrap().is_none());
+
+        let nested = b"*1\r\n".repeat(MAX_DEPTH + 1);
+        assert!(parse(&nested).is_err());
+        let inline = vec![b'a'; MAX_INLINE_LEN + 1];
+        assert!(parse(&inline).is_err());
+        assert!(parse(&inline[..MAX_INLINE_LEN]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,433 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sekas_client::{AppError, Database, Error, WriteBatchRequest, WriteBuilder};
+
+use crate::{Frame, Layer};
+
+/// The default number of keys returned by a `SCAN` command.
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// The max number of scan cursors kept by a connection, the oldest cursor is
+/// dropped once it is exceeded.
+const MAX_SCAN_CURSORS: usize = 1024;
+
+/// The max attempts of the read-modify-write commands, the command fails if
+/// the key is still updated concurrently.
+const MAX_CAS_ATTEMPTS: u32 = 10;
+
+/// The backoff before retrying a read-modify-write command, it is doubled on
+/// each attempt up to `MAX_CAS_BACKOFF`.
+const MIN_CAS_BACKOFF: Duration = Duration::from_millis(1);
+const MAX_CAS_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The state of a redis connection.
+pub(crate) struct Session {
+    layer: Arc<Layer>,
+    /// The scan cursors of this connection, the value is the start key of the
+    /// next scan.
+    cursors: BTreeMap<u64, Vec<u8>>,
+    next_cursor: u64,
+}
+
+impl Session {
+    pub fn new(layer: Arc<Layer>) -> Self {
+        Session { layer, cursors: BTreeMap::default(), next_cursor: 1 }
+    }
+
+    /// Execute a command and return the reply.
+    pub async fn execute(&mut self, args: Vec<Vec<u8>>) -> Frame {
+        let Some(cmd) = args.first() else {
+            return Frame::error("empty command");
+        };
+        let cmd = String::from_utf8_lossy(cmd).to_ascii_uppercase();
+        let args = &args[1..];
+        match cmd.as_str() {
+            "PING" => match args {
+                [] => Frame::Simple("PONG".to_owned()),
+                [msg] => Frame::bulk(msg.clone()),
+                _ => wrong_arity(&cmd),
+            },
+            "ECHO" => match args {
+                [msg] => Frame::bulk(msg.clone()),
+                _ => wrong_arity(&cmd),
+            },
+            // Some clients issue `COMMAND` during connection, no command docs are provided.
+            "COMMAND" => Frame::Array(Some(vec![])),
+            "GET" => match args {
+                [key] => self.get(key.clone()).await.unwrap_or_else(|e| e),
+                _ => wrong_arity(&cmd),
+            },
+            "SET" => match args {
+                [key, value, options @ ..] => {
+                    self.set(key.clone(), value.clone(), options).await.unwrap_or_else(|e| e)
+                }
+                _ => wrong_arity(&cmd),
+            },
+            "DEL" if !args.is_empty() => self.del(args).await.unwrap_or_else(|e| e),
+            "MGET" if !args.is_empty() => self.mget(args).await.unwrap_or_else(|e| e),
+            "INCR" | "DECR" => match args {
+                [key] => {
+                    let delta = if cmd == "INCR" { 1 } else { -1 };
+                    self.incr_by(key.clone(), delta).await.unwrap_or_else(|e| e)
+                }
+                _ => wrong_arity(&cmd),
+            },
+            "INCRBY" | "DECRBY" => match args {
+                [key, delta] => match parse_integer(delta) {
+                    Ok(delta) => {
+                        let delta = if cmd == "INCRBY" { delta } else { delta.wrapping_neg() };
+                        self.incr_by(key.clone(), delta).await.unwrap_or_else(|e| e)
+                    }
+                    Err(e) => e,
+                },
+                _ => wrong_arity(&cmd),
+            },
+            "SCAN" => match args {
+                [cursor, options @ ..] => self.scan(cursor, options).await.unwrap_or_else(|e| e),
+                _ => wrong_arity(&cmd),
+            },
+            "EXPIRE" => match args {
+                [key, seconds] => match parse_integer(seconds) {
+                    Ok(seconds) => self.expire(key.clone(), seconds).await.unwrap_or_else(|e| e),
+                    Err(e) => e,
+                },
+                _ => wrong_arity(&cmd),
+            },
+            "DEL" | "MGET" => wrong_arity(&cmd),
+            _ => Frame::error(format!("unknown command '{cmd}'")),
+        }
+    }
+
+    async fn get(&self, key: Vec<u8>) -> Result<Frame, Frame> {
+        let (database, collection_id) = self.target().await?;
+        let value = database.get(*collection_id, key).await.map_err(to_frame)?;
+        Ok(Frame::Bulk(value))
+    }
+
+    async fn set(&self, key: Vec<u8>, value: Vec<u8>, options: &[Vec<u8>]) -> Result<Frame, Frame> {
+        let mut builder = WriteBuilder::new(key);
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            let option = String::from_utf8_lossy(option).to_ascii_uppercase();
+            match option.as_str() {
+                "NX" => builder = builder.expect_not_exists(),
+                "XX" => builder = builder.expect_exists(),
+                "EX" | "PX" => {
+                    let ttl = options.next().ok_or_else(|| Frame::error("syntax error"))?;
+                    let ttl = parse_integer(ttl)?;
+                    if ttl <= 0 {
+                        return Err(Frame::error("invalid expire time in 'set' command"));
+                    }
+                    // The ttl is in seconds, round up the milliseconds.
+                    let ttl = if option == "EX" {
+                        ttl
+                    } else {
+                        ttl.checked_add(999)
+                            .ok_or_else(|| Frame::error("invalid expire time in 'set' command"))?
+                            / 1000
+                    };
+                    builder = builder.with_expire_at(expire_at(ttl as u64));
+                }
+                _ => return Err(Frame::error("syntax error")),
+            }
+        }
+        let put = builder.put(value).map_err(app_error_to_frame)?;
+        let (database, collection_id) = self.target().await?;
+        match database.write_batch(WriteBatchRequest::default().add_put(*collection_id, put)).await
+        {
+            Ok(_) => Ok(Frame::ok()),
+            // The `NX` or `XX` condition is not satisfied.
+            Err(Error::CasFailed(..)) => Ok(Frame::Bulk(None)),
+            Err(err) => Err(to_frame(err)),
+        }
+    }
+
+    async fn del(&self, keys: &[Vec<u8>]) -> Result<Frame, Frame> {
+        let (database, collection_id) = self.target().await?;
+        let mut batch = WriteBatchRequest::default();
+        for key in keys {
+            let delete = WriteBuilder::new(key.clone()).take_prev_value().ensure_delete();
+            batch = batch.add_delete(*collection_id, delete);
+        }
+        let resp = database.write_batch(batch).await.map_err(to_frame)?;
+        let num_deleted = resp
+            .deletes
+            .iter()
+            .filter(|prev_value| {
+                prev_value.as_ref().map(|v| v.content.is_some()).unwrap_or_default()
+            })
+            .count();
+        Ok(Frame::Integer(num_deleted as i64))
+    }
+
+    async fn mget(&self, keys: &[Vec<u8>]) -> Result<Frame, Frame> {
+        let (database, collection_id) = self.target().await?;
+        let values = futures::future::try_join_all(
+            keys.iter().map(|key| database.get(*collection_id, key.clone())),
+        )
+        .await
+        .map_err(to_frame)?;
+        Ok(Frame::Array(Some(values.into_iter().map(Frame::Bulk).collect())))
+    }
+
+    /// Increase the integer value of the key by `delta`.
+    ///
+    /// `PutType::AddI64` is not used, since it stores the integer as big-endian
+    /// bytes and wraps on overflow. Like redis, the integer is stored as a
+    /// decimal string so `GET` and `SET` work on it, and the overflow is
+    /// rejected. Instead the value is read and written with the version CAS,
+    /// which keeps the expiration of the key, and the concurrent updates are
+    /// retried at most `MAX_CAS_ATTEMPTS` times.
+    async fn incr_by(&self, key: Vec<u8>, delta: i64) -> Result<Frame, Frame> {
+        let (database, collection_id) = self.target().await?;
+        let mut attempt = 0;
+        loop {
+            cas_backoff(attempt).await?;
+            attempt += 1;
+            let value =
+                database.get_raw_value(*collection_id, key.clone()).await.map_err(to_frame)?;
+            let builder = WriteBuilder::new(key.clone());
+            let (builder, prev_value) =
+                match value.and_then(|v| Some((v.content?, v.version, v.expire_at))) {
+                    Some((content, version, expire_at)) => {
+                        let mut builder = builder.expect_version(version);
+                        if expire_at != 0 {
+                            builder = builder.with_expire_at(expire_at);
+                        }
+                        (builder, parse_integer(&content)?)
+                    }
+                    None => (builder.expect_not_exists(), 0),
+                };
+            let value = prev_value
+                .checked_add(delta)
+                .ok_or_else(|| Frame::error("increment or decrement would overflow"))?;
+            let put = builder.ensure_put(value.to_string().into_bytes());
+            let batch = WriteBatchRequest::default().add_put(*collection_id, put);
+            match database.write_batch(batch).await {
+                Ok(_) => return Ok(Frame::Integer(value)),
+                // The key is updated concurrently, try again.
+                Err(Error::CasFailed(..)) => continue,
+                Err(err) => return Err(to_frame(err)),
+            }
+        }
+    }
+
+    async fn scan(&mut self, cursor: &[u8], options: &[Vec<u8>]) -> Result<Frame, Frame> {
+        let mut pattern = None;
+        let mut count = DEFAULT_SCAN_COUNT;
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            let value = options.next().ok_or_else(|| Frame::error("syntax error"))?;
+            match String::from_utf8_lossy(option).to_ascii_uppercase().as_str() {
+                "MATCH" => pattern = Some(value.clone()),
+                "COUNT" => match parse_integer(value)? {
+                    count_value if count_value > 0 => count = count_value as usize,
+                    _ => return Err(Frame::error("syntax error")),
+                },
+                _ => return Err(Frame::error("syntax error")),
+            }
+        }
+
+        let cursor = parse_integer(cursor)? as u64;
+        let start_key = if cursor == 0 {
+            vec![]
+        } else {
+            self.cursors.remove(&cursor).ok_or_else(|| Frame::error("invalid cursor"))?
+        };
+        let (database, collection_id) = self.target().await?;
+        let key_values =
+            database.scan(*collection_id, start_key, None, count).await.map_err(to_frame)?;
+        let next_cursor = match key_values.last() {
+            Some((last_key, _)) if key_values.len() >= count => {
+                // The next scan starts from the successor of the last key.
+                let mut next_key = last_key.clone();
+                next_key.push(0);
+                let cursor = self.next_cursor;
+                self.next_cursor += 1;
+                if self.cursors.len() >= MAX_SCAN_CURSORS {
+                    self.cursors.pop_first();
+                }
+                self.cursors.insert(cursor, next_key);
+                cursor
+            }
+            _ => 0,
+        };
+        let keys = key_values
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| pattern.as_ref().map(|p| glob_match(p, key)).unwrap_or(true))
+            .map(Frame::bulk)
+            .collect();
+        Ok(Frame::Array(Some(vec![Frame::bulk(next_cursor.to_string()), Frame::Array(Some(keys))])))
+    }
+
+    async fn expire(&self, key: Vec<u8>, seconds: i64) -> Result<Frame, Frame> {
+        let (database, collection_id) = self.target().await?;
+        let mut attempt = 0;
+        loop {
+            cas_backoff(attempt).await?;
+            attempt += 1;
+            let value =
+                database.get_raw_value(*collection_id, key.clone()).await.map_err(to_frame)?;
+            let Some((content, version)) = value.and_then(|v| Some((v.content?, v.version))) else {
+                return Ok(Frame::Integer(0));
+            };
+            let builder = WriteBuilder::new(key.clone()).expect_version(version);
+            let batch = if seconds <= 0 {
+                WriteBatchRequest::default().add_delete(*collection_id, builder.ensure_delete())
+            } else {
+                let put = builder.with_expire_at(expire_at(seconds as u64)).ensure_put(content);
+                WriteBatchRequest::default().add_put(*collection_id, put)
+            };
+            match database.write_batch(batch).await {
+                Ok(_) => return Ok(Frame::Integer(1)),
+                // The key is updated concurrently, try again.
+                Err(Error::CasFailed(..)) => continue,
+                Err(err) => return Err(to_frame(err)),
+            }
+        }
+    }
+
+    async fn target(&self) -> Result<&(Database, u64), Frame> {
+        self.layer.target().await.map_err(app_error_to_frame)
+    }
+}
+
+/// Wait before the `attempt`-th attempt of a read-modify-write command, fail
+/// once the attempts are exhausted.
+async fn cas_backoff(attempt: u32) -> Result<(), Frame> {
+    if attempt == 0 {
+        return Ok(());
+    }
+    if attempt >= MAX_CAS_ATTEMPTS {
+        return Err(Frame::error("the key is updated concurrently, try again"));
+    }
+    let backoff = MIN_CAS_BACKOFF.saturating_mul(1 << (attempt - 1)).min(MAX_CAS_BACKOFF);
+    tokio::time::sleep(backoff).await;
+    Ok(())
+}
+
+/// Return the unix timestamp in seconds when a value expires after `ttl`
+/// seconds.
+fn expire_at(ttl: u64) -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    now.saturating_add(ttl)
+}
+
+/// Match the key with a glob style pattern, `*`, `?` and `\` are supported.
+///
+/// The pattern is matched by two pointers, on a mismatch the last `*` is
+/// retried to consume one more byte of the key, so the time is bounded by
+/// `O(pattern.len() * key.len())` however many `*` are repeated.
+fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // The pattern position after the last `*` and the key position it is
+    // retried from.
+    let mut backtrack = None;
+    while k < key.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, k));
+                continue;
+            }
+            Some(b'?') => {
+                p += 1;
+                k += 1;
+                continue;
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                if pattern[p + 1] == key[k] {
+                    p += 2;
+                    k += 1;
+                    continue;
+                }
+            }
+            Some(&c) if c == key[k] => {
+                p += 1;
+                k += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match backtrack.as_mut() {
+            Some((star_p, star_k)) => {
+                *star_k += 1;
+                p = *star_p;
+                k = *star_k;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+fn parse_integer(value: &[u8]) -> Result<i64, Frame> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| Frame::error("value is not an integer or out of range"))
+}
+
+fn wrong_arity(cmd: &str) -> Frame {
+    Frame::error(format!("wrong number of arguments for '{}' command", cmd.to_ascii_lowercase()))
+}
+
+fn to_frame(err: Error) -> Frame {
+    Frame::error(err)
+}
+
+fn app_error_to_frame(err: AppError) -> Frame {
+    Frame::error(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_pattern() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"*", b"key"));
+        assert!(glob_match(b"user:*", b"user:1"));
+        assert!(!glob_match(b"user:*", b"order:1"));
+        assert!(glob_match(b"user:?", b"user:1"));
+        assert!(!glob_match(b"user:?", b"user:10"));
+        assert!(glob_match(b"*:1", b"user:1"));
+        assert!(glob_match(b"a\\*", b"a*"));
+        assert!(!glob_match(b"a\\*", b"ab"));
+        assert!(glob_match(b"*a*b*", b"xaybz"));
+        assert!(!glob_match(b"*a*b*", b"xbya"));
+        assert!(glob_match(b"a\\", b"a\\"));
+
+        // The repeated `*` do not take exponential time.
+        let pattern = b"*a".repeat(32);
+        let key = vec![b'a'; 4096];
+        assert!(!glob_match(&[pattern.as_slice(), b"b"].concat(), &key));
+        assert!(glob_match(&pattern, &key));
+    }
+
+    #[test]
+    fn parse_counter() {
+        assert_eq!(parse_integer(b"10").ok(), Some(10));
+        assert_eq!(parse_integer(b"-10").ok(), Some(-10));
+        assert!(parse_integer(&10i64.to_be_bytes()).is_err());
+        assert!(parse_integer(b"abc").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2023 The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use sekas_redis_proxy::RedisOptions;
+use sekas_testkit::{ClusterClient, TestContext};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Send a command and check the reply.
+async fn command(stream: &mut TcpStream, args: &[&str], expect: &str) {
+    let mut req = format!("*{}\r\n", args.len());
+    for arg in args {
+        req.push_str(&format!("${}\r\n{arg}\r\n", arg.len()));
+    }
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut reply = vec![0u8; expect.len()];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&reply), expect, "command {args:?}");
+}
+
+#[sekas_macro::test]
+async fn redis_incr_decimal_values() {
+    let mut ctx = TestContext::new("redis_incr_decimal_values");
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = RedisOptions { database: "redis".to_owned(), collection: "default".to_owned() };
+    let _handle = sekas_runtime::spawn(sekas_redis_proxy::serve(listener, client, opts));
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    command(&mut stream, &["SET", "k", "10"], "+OK\r\n").await;
+    command(&mut stream, &["INCR", "k"], ":11\r\n").await;
+    command(&mut stream, &["GET", "k"], "$2\r\n11\r\n").await;
+    command(&mut stream, &["INCRBY", "k", "-20"], ":-9\r\n").await;
+    command(&mut stream, &["DECRBY", "k", "1"], ":-10\r\n").await;
+    command(&mut stream, &["GET", "k"], "$3\r\n-10\r\n").await;
+
+    // The missing key is treated as zero.
+    command(&mut stream, &["DECR", "missing"], ":-1\r\n").await;
+    command(&mut stream, &["GET", "missing"], "$2\r\n-1\r\n").await;
+
+    // The value is overwritten by `SET`.
+    command(&mut stream, &["SET", "k", "abc"], "+OK\r\n").await;
+    command(&mut stream, &["INCR", "k"], "-ERR value is not an integer or out of range\r\n").await;
+    command(&mut stream, &["GET", "k"], "$3\r\nabc\r\n").await;
+    command(&mut stream, &["SET", "k", "9223372036854775807"], "+OK\r\n").await;
+    command(&mut stream, &["INCR", "k"], "-ERR increment or decrement would overflow\r\n").await;
+}
+
+#[sekas_macro::test]
+async fn redis_keys_expire() {
+    let mut ctx = TestContext::new("redis_keys_expire");
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let opts = RedisOptions { database: "redis".to_owned(), collection: "default".to_owned() };
+    let _handle = sekas_runtime::spawn(sekas_redis_proxy::serve(listener, client, opts));
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    command(&mut stream, &["SET", "ex", "v", "EX", "2"], "+OK\r\n").await;
+    command(&mut stream, &["SET", "px", "v", "PX", "2000"], "+OK\r\n").await;
+    let max = i64::MAX.to_string();
+    let invalid = "-ERR invalid expire time in 'set' command\r\n";
+    command(&mut stream, &["SET", "px", "v", "PX", &max], invalid).await;
+    command(&mut stream, &["SET", "expire", "v"], "+OK\r\n").await;
+    command(&mut stream, &["SET", "persist", "v"], "+OK\r\n").await;
+    command(&mut stream, &["EXPIRE", "expire", "2"], ":1\r\n").await;
+    command(&mut stream, &["EXPIRE", "missing", "1"], ":0\r\n").await;
+    command(&mut stream, &["GET", "ex"], "$1\r\nv\r\n").await;
+
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    command(&mut stream, &["GET", "ex"], "$-1\r\n").await;
+    command(&mut stream, &["GET", "px"], "$-1\r\n").await;
+    command(&mut stream, &["GET", "expire"], "$-1\r\n").await;
+    command(&mut stream, &["GET", "persist"], "$1\r\nv\r\n").await;
+}
@@ -294,6 +294,7 @@ def raft_group_request_panels():
         simple_total("raft transfer leader qps",
                      "raftgroup_transfer_leader_total"),
         simple_total("raft unreachable qps", "raftgroup_unreachable_total"),
+        simple_total("raft busy rejected qps", "raftgroup_busy_rejected_total"),
     )
 
 
@@ -358,12 +359,19 @@ def node_job_panels():
         simple_total("ingest chunk qps", "node_ingest_chunk_total"),
         simple_duration_seconds("pull shard duration",
                                 "node_pull_shard_duration_seconds"),
+        simple_duration_seconds("pull shard throttle duration",
+                                "node_move_shard_throttle_duration_seconds"),
+        simple_total("pull shard backoff qps", "node_move_shard_backoff_total"),
         simple_total("report qps", "node_report_total"),
         simple_duration_seconds("report duration",
                                 "node_report_duration_seconds"),
         simple_total("destory replica qps", "node_destory_replica_total"),
         simple_duration_seconds("destory replica duration",
                                 "node_destory_replica_duration_seconds"),
+        vector_total("resource bytes", "node_resource_bytes_total", "group"),
+        vector_duration_seconds("resource throttle duration",
+                                "node_resource_throttle_duration_seconds",
+                                "group"),
     )
 
 
@@ -478,6 +486,10 @@ def client_group_panels():
             "group_client_group_request_duration_seconds", "type", "type"),
         simple_total("group client retry total",
             "group_client_retry_total"),
+        simple_total("connection health probe total",
+            "conn_manager_health_probe_total"),
+        simple_total("evicted connection total",
+            "conn_manager_evict_connection_total"),
     )
 
 
@@ -22,6 +22,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     tonic_build::configure().compile(
         &[
+            "sekas/gateway/v1/gateway.proto",
             "sekas/server/v1/catalog.proto",
             "sekas/server/v1/error.proto",
             "sekas/server/v1/metadata.proto",
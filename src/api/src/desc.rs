@@ -14,7 +14,7 @@
 
 //! A mod to hold the helper functions of XxxDesc.
 
-use crate::server::v1::{RangePartition, ShardDesc};
+use crate::server::v1::{CompressionCodec, RangePartition, ShardDesc};
 
 impl ShardDesc {
     pub fn whole(shard_id: u64, collection_id: u64) -> Self {
@@ -22,10 +22,26 @@ impl ShardDesc {
             id: shard_id,
             collection_id,
             range: Some(RangePartition { start: vec![], end: vec![] }),
+            clone_source: None,
+            encrypted: false,
+            compression: CompressionCodec::Uncompressed as i32,
+            key_schema: None,
+            constraints: vec![],
+            frozen: false,
         }
     }
 
     pub fn with_range(shard_id: u64, collection_id: u64, start: Vec<u8>, end: Vec<u8>) -> Self {
-        ShardDesc { id: shard_id, collection_id, range: Some(RangePartition { start, end }) }
+        ShardDesc {
+            id: shard_id,
+            collection_id,
+            range: Some(RangePartition { start, end }),
+            clone_source: None,
+            encrypted: false,
+            compression: CompressionCodec::Uncompressed as i32,
+            key_schema: None,
+            constraints: vec![],
+            frozen: false,
+        }
     }
 }
@@ -49,28 +49,33 @@ impl ErrorDetailUnion {
                     | Value::NotLeader(_)
                     | Value::NotMatch(_)
                     | Value::NotRoot(_)
-                    | Value::ServerIsBusy(_),
+                    | Value::ServerIsBusy(_)
+                    | Value::GroupBusy(_),
             )
         )
     }
 }
 
 impl ErrorDetail {
+    /// Return whether the request could be retried, the flag filled by the
+    /// server is respected for the unknown details.
     #[inline]
     pub fn is_retryable(&self) -> bool {
-        self.detail.as_ref().map(ErrorDetailUnion::is_retryable).unwrap_or_default()
+        self.retryable
+            || self.detail.as_ref().map(ErrorDetailUnion::is_retryable).unwrap_or_default()
     }
 }
 
 impl ErrorDetail {
     #[inline]
     pub fn new(value: error_detail_union::Value) -> Self {
-        ErrorDetail { detail: Some(ErrorDetailUnion { value: Some(value) }), ..Default::default() }
+        Self::with_message(value, String::default())
     }
 
     #[inline]
     pub fn with_message(value: error_detail_union::Value, message: String) -> Self {
-        ErrorDetail { detail: Some(ErrorDetailUnion { value: Some(value) }), message }
+        let detail = ErrorDetailUnion { value: Some(value) };
+        ErrorDetail { retryable: detail.is_retryable(), detail: Some(detail), message }
     }
 
     #[inline]
@@ -102,10 +107,21 @@ impl ErrorDetail {
 impl Error {
     #[inline]
     pub fn not_leader(group_id: u64, term: u64, leader: Option<ReplicaDesc>) -> Self {
+        Self::not_leader_with_descriptor(group_id, term, leader, None)
+    }
+
+    #[inline]
+    pub fn not_leader_with_descriptor(
+        group_id: u64,
+        term: u64,
+        leader: Option<ReplicaDesc>,
+        descriptor: Option<GroupDesc>,
+    ) -> Self {
         Self::with_detail_value(error_detail_union::Value::NotLeader(NotLeader {
             group_id,
             term,
             leader,
+            descriptor,
         }))
     }
 
@@ -123,6 +139,20 @@ impl Error {
         Self::with_detail_value(error_detail_union::Value::ServerIsBusy(ServerIsBusy {}))
     }
 
+    #[inline]
+    pub fn group_busy(group_id: u64, retry_after_ms: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::GroupBusy(GroupBusy {
+            group_id,
+            retry_after_ms,
+        }))
+    }
+
+    #[inline]
+    pub fn quota_exceeded(msg: impl Into<String>, retry_after_ms: u64) -> Self {
+        let value = error_detail_union::Value::QuotaExceeded(QuotaExceeded { retry_after_ms });
+        Error { details: vec![ErrorDetail::with_message(value, msg.into())] }
+    }
+
     #[inline]
     pub fn not_match(desc: GroupDesc) -> Self {
         Self::with_detail_value(error_detail_union::Value::NotMatch(EpochNotMatch {
@@ -146,6 +176,33 @@ impl Error {
         }))
     }
 
+    #[inline]
+    pub fn collection_frozen(collection_id: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::CollectionFrozen(CollectionFrozen {
+            collection_id,
+        }))
+    }
+
+    #[inline]
+    pub fn node_incarnation_mismatch(node_id: u64, expected: u64, actual: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::NodeIncarnationMismatch(
+            NodeIncarnationMismatch { node_id, expected, actual },
+        ))
+    }
+
+    #[inline]
+    pub fn node_not_found(node_id: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::NodeNotFound(NodeNotFound { node_id }))
+    }
+
+    #[inline]
+    pub fn node_status_not_match(node_id: u64, status: NodeStatus) -> Self {
+        Self::with_detail_value(error_detail_union::Value::NodeStatusNotMatch(NodeStatusNotMatch {
+            node_id,
+            status: status.into(),
+        }))
+    }
+
     #[inline]
     pub fn status(code: i32, msg: impl Into<String>) -> Self {
         Error { details: vec![ErrorDetail::status(code, msg)] }
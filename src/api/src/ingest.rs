@@ -0,0 +1,124 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The format of key/value files used by bulk ingestion.
+//!
+//! An ingest file is a sequence of records, each record is encoded as:
+//!
+//! ```text
+//! | key len (u32 LE) | key | value len (u32 LE) | value |
+//! ```
+//!
+//! The keys in a file must be strictly increasing.
+
+use crate::server::v1::IngestFile;
+
+/// Build the content of an ingest file.
+#[derive(Default)]
+pub struct IngestFileBuilder {
+    buf: Vec<u8>,
+    last_key: Option<Vec<u8>>,
+}
+
+/// Iterate the records of an ingest file.
+pub struct IngestFileReader<'a> {
+    buf: &'a [u8],
+}
+
+impl IngestFileBuilder {
+    /// Append a record to the file. Return false if the key is not greater
+    /// than the previous one.
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> bool {
+        if self.last_key.as_deref().map(|last| last >= key).unwrap_or_default() {
+            return false;
+        }
+        self.buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        self.buf.extend_from_slice(key);
+        self.buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        self.buf.extend_from_slice(value);
+        self.last_key = Some(key.to_owned());
+        true
+    }
+
+    /// Finish the building, return the content of the file.
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl IngestFile {
+    /// Compute the fingerprint of the file content.
+    pub fn fingerprint(name: String, content: &[u8]) -> Self {
+        IngestFile { name, crc32: crc32fast::hash(content), size: content.len() as u64 }
+    }
+}
+
+impl<'a> IngestFileReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        IngestFileReader { buf }
+    }
+
+    fn read_slice(&mut self) -> Option<&'a [u8]> {
+        if self.buf.len() < 4 {
+            return None;
+        }
+        let (len, rest) = self.buf.split_at(4);
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        if rest.len() < len {
+            return None;
+        }
+        let (data, rest) = rest.split_at(len);
+        self.buf = rest;
+        Some(data)
+    }
+}
+
+impl<'a> Iterator for IngestFileReader<'a> {
+    /// The record, or `Err(())` if the file is corrupted.
+    type Item = Result<(&'a [u8], &'a [u8]), ()>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let record = self.read_slice().and_then(|key| self.read_slice().map(|value| (key, value)));
+        if record.is_none() {
+            // Stop iterating once the file is corrupted.
+            self.buf = &[];
+        }
+        Some(record.ok_or(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ingest_file_build_and_read() {
+        let mut builder = IngestFileBuilder::default();
+        assert!(builder.add(b"a", b"1"));
+        assert!(builder.add(b"b", b""));
+        assert!(!builder.add(b"b", b"2"));
+        assert!(!builder.add(b"a", b"2"));
+        let content = builder.finish();
+
+        let records = IngestFileReader::new(&content).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records, vec![(&b"a"[..], &b"1"[..]), (&b"b"[..], &b""[..])]);
+
+        let records = IngestFileReader::new(&content[..content.len() - 1]).collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert!(records[1].is_err());
+    }
+}
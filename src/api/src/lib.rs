@@ -15,11 +15,22 @@
 
 mod desc;
 mod error;
+mod ingest;
 mod move_shard;
+mod request;
 mod txn;
 mod value;
 mod write;
 
+pub use self::ingest::{IngestFileBuilder, IngestFileReader};
+
+pub mod gateway {
+    pub mod v1 {
+        #![allow(clippy::all)]
+        tonic::include_proto!("sekas.gateway.v1");
+    }
+}
+
 pub mod server {
     pub mod v1 {
         #![allow(clippy::all)]
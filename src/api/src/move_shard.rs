@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use prost::Message;
+
 use super::server::v1::*;
 
 impl MoveShardDesc {
@@ -40,3 +42,41 @@ impl std::fmt::Display for MoveShardDesc {
         )
     }
 }
+
+impl PullShardChunk {
+    /// Build a chunk of the value sets, with the checksum of them.
+    pub fn new(data: Vec<ValueSet>) -> Self {
+        let crc32 = checksum(&data);
+        PullShardChunk { data, crc32 }
+    }
+
+    /// Verify the checksum of the value sets.
+    #[inline]
+    pub fn verify(&self) -> bool {
+        checksum(&self.data) == self.crc32
+    }
+}
+
+fn checksum(data: &[ValueSet]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for value_set in data {
+        hasher.update(&value_set.encode_to_vec());
+    }
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pull_shard_chunk_checksum() {
+        let value_set =
+            ValueSet { user_key: b"a".to_vec(), values: vec![Value::with_value(b"1".to_vec(), 1)] };
+        let mut chunk = PullShardChunk::new(vec![value_set]);
+        assert!(chunk.verify());
+        chunk.data[0].user_key = b"b".to_vec();
+        assert!(!chunk.verify());
+        assert!(PullShardChunk::new(vec![]).verify());
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A mod to hold the helper functions of group requests.
+
+use crate::server::v1::group_request_union::Request;
+
+impl Request {
+    /// Return the name of the request, it could be used as span or metric
+    /// label.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::Get(_) => "get",
+            Request::Scan(_) => "scan",
+            Request::Write(_) => "write",
+            Request::WriteIntent(_) => "write_intent",
+            Request::CommitIntent(_) => "commit_intent",
+            Request::ClearIntent(_) => "clear_intent",
+            Request::CreateShard(_) => "create_shard",
+            Request::TruncateShard(_) => "truncate_shard",
+            Request::FreezeShard(_) => "freeze_shard",
+            Request::SplitShard(_) => "split_shard",
+            Request::ChangeReplicas(_) => "change_replicas",
+            Request::AcceptShard(_) => "accept_shard",
+            Request::Transfer(_) => "transfer",
+            Request::MoveReplicas(_) => "move_replicas",
+            Request::Ingest(_) => "ingest",
+        }
+    }
+
+    /// Return the target shard of the request, if it is a shard request.
+    pub fn shard_id(&self) -> Option<u64> {
+        match self {
+            Request::Get(req) => Some(req.shard_id),
+            Request::Scan(req) => Some(req.shard_id),
+            Request::Write(req) => Some(req.shard_id),
+            Request::WriteIntent(req) => Some(req.shard_id),
+            Request::CommitIntent(req) => Some(req.shard_id),
+            Request::ClearIntent(req) => Some(req.shard_id),
+            Request::Ingest(req) => Some(req.shard_id),
+            Request::AcceptShard(req) => req.shard_desc.as_ref().map(|s| s.id),
+            Request::CreateShard(req) => req.shard.as_ref().map(|s| s.id),
+            Request::TruncateShard(req) => Some(req.shard_id),
+            Request::FreezeShard(req) => Some(req.shard_id),
+            Request::SplitShard(req) => Some(req.shard_id),
+            Request::ChangeReplicas(_) | Request::Transfer(_) | Request::MoveReplicas(_) => None,
+        }
+    }
+
+    /// Return the start version of the txn, if it is a txn request.
+    pub fn txn_version(&self) -> Option<u64> {
+        match self {
+            Request::WriteIntent(req) => Some(req.start_version),
+            Request::CommitIntent(req) => Some(req.start_version),
+            Request::ClearIntent(req) => Some(req.start_version),
+            _ => None,
+        }
+    }
+}
@@ -14,14 +14,51 @@
 
 //! A mod to hold the helper functions of txn related structures.
 
-use crate::server::v1::TxnIntent;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::server::v1::{TxnIntent, Value};
 
 impl TxnIntent {
     pub fn tombstone(start_version: u64) -> Self {
-        TxnIntent { start_version, is_delete: true, value: None }
+        TxnIntent { start_version, is_delete: true, ..Default::default() }
     }
 
     pub fn with_put(start_version: u64, value: Option<Vec<u8>>) -> Self {
-        TxnIntent { start_version, is_delete: false, value }
+        TxnIntent { start_version, is_delete: false, value, ..Default::default() }
+    }
+
+    /// Set the expire time of the value, see `PutRequest::expire_at`.
+    pub fn with_expire_at(mut self, expire_at: u64) -> Self {
+        self.expire_at = expire_at;
+        self
+    }
+
+    /// Set the database owning the txn record, see `TxnIntent::database_id`.
+    pub fn with_database(mut self, database_id: u64) -> Self {
+        self.database_id = database_id;
+        self
+    }
+
+    /// Set the number of chunks of the value, see `PutRequest::num_chunks`.
+    pub fn with_num_chunks(mut self, num_chunks: u32) -> Self {
+        self.num_chunks = num_chunks;
+        self
+    }
+
+    /// Return the value of the intent committed at `commit_version`, the
+    /// expired value is returned as a tombstone.
+    pub fn committed_value(&self, commit_version: u64) -> Value {
+        let value = Value {
+            content: self.value.clone(),
+            version: commit_version,
+            expire_at: self.expire_at,
+            num_chunks: self.num_chunks,
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if self.is_delete || value.is_expired(now) {
+            Value::tombstone(commit_version)
+        } else {
+            value
+        }
     }
 }
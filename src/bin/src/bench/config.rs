@@ -60,12 +60,29 @@ pub struct DataConfig {
 
     pub read: f64,
     pub write: f64,
+    pub scan: f64,
+    pub txn: f64,
     pub value: std::ops::Range<usize>,
+
+    /// The max number of key values returned by a scan.
+    pub scan_limit: usize,
+    /// The number of keys written by a txn.
+    pub txn_size: usize,
 }
 
 impl Default for DataConfig {
     fn default() -> Self {
-        DataConfig { inserted: 10000, limited: 10000, read: 0.5, write: 0.5, value: 10..11 }
+        DataConfig {
+            inserted: 10000,
+            limited: 10000,
+            read: 0.5,
+            write: 0.5,
+            scan: 0.0,
+            txn: 0.0,
+            value: 10..11,
+            scan_limit: 100,
+            txn_size: 4,
+        }
     }
 }
 
@@ -156,6 +156,8 @@ async fn open_database(cfg: &AppConfig) -> Result<Database> {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(200)),
         timeout: Some(Duration::from_millis(500)),
+        app_tag: Some("bench".to_owned()),
+        ..Default::default()
     };
     let client = SekasClient::new(opts, cfg.addrs.clone()).await?;
     let database = match client.open_database(cfg.database.clone()).await {
@@ -186,12 +188,14 @@ fn spawn_reporter(ctx: &Context, cfg: AppConfig) -> WaitGroup {
     let wait_group = WaitGroup::new();
     let cloned_wait_group = wait_group.clone();
     ctx.runtime.spawn(async move {
+        let start_ctx = ReportContext::default();
         let mut ctx = ReportContext::default();
         select! {
             _ = shutdown => {},
             _ = reporter_main(cfg, &mut ctx) => {},
         }
         report::display(&mut ctx);
+        report::display_total(&start_ctx);
         drop(wait_group);
     });
     cloned_wait_group
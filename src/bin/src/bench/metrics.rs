@@ -52,3 +52,5 @@ macro_rules! request_total {
 
 request_total!(put);
 request_total!(get);
+request_total!(scan);
+request_total!(txn);
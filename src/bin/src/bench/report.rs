@@ -18,20 +18,29 @@ use prometheus::proto::Metric;
 
 use super::metrics::*;
 
+/// The name of an operation, and its histograms of success and failure
+/// requests.
+type Operation = (&'static str, &'static prometheus::Histogram, &'static prometheus::Histogram);
+
+fn operations() -> [Operation; 4] {
+    [
+        ("GET", &GET_SUCCESS_REQUEST_DURATION_SECONDS, &GET_FAILURE_REQUEST_DURATION_SECONDS),
+        ("PUT", &PUT_SUCCESS_REQUEST_DURATION_SECONDS, &PUT_FAILURE_REQUEST_DURATION_SECONDS),
+        ("SCAN", &SCAN_SUCCESS_REQUEST_DURATION_SECONDS, &SCAN_FAILURE_REQUEST_DURATION_SECONDS),
+        ("TXN", &TXN_SUCCESS_REQUEST_DURATION_SECONDS, &TXN_FAILURE_REQUEST_DURATION_SECONDS),
+    ]
+}
+
 pub(super) struct ReportContext {
     instant: Instant,
-    get_success: Metric,
-    get_failure: Metric,
-    put_success: Metric,
-    put_failure: Metric,
+    /// The success and failure metrics of each operation, in the order of
+    /// `operations()`.
+    metrics: Vec<(Metric, Metric)>,
 }
 
 struct Summary {
     interval: Duration,
-    get_success: Histogram,
-    get_failure: Histogram,
-    put_success: Histogram,
-    put_failure: Histogram,
+    histograms: Vec<(Histogram, Histogram)>,
 }
 
 struct Histogram {
@@ -93,27 +102,28 @@ impl From<prometheus::proto::Metric> for Histogram {
 impl ReportContext {
     pub fn default() -> Self {
         use prometheus::core::Metric;
-        let get_success = GET_SUCCESS_REQUEST_DURATION_SECONDS.metric();
-        let get_failure = GET_FAILURE_REQUEST_DURATION_SECONDS.metric();
-        let put_success = PUT_SUCCESS_REQUEST_DURATION_SECONDS.metric();
-        let put_failure = PUT_FAILURE_REQUEST_DURATION_SECONDS.metric();
-        ReportContext {
-            instant: Instant::now(),
-            get_success,
-            get_failure,
-            put_success,
-            put_failure,
-        }
+        let metrics = operations()
+            .into_iter()
+            .map(|(_, success, failure)| (success.metric(), failure.metric()))
+            .collect();
+        ReportContext { instant: Instant::now(), metrics }
     }
 }
 
 fn diff(current: &ReportContext, earlier: &ReportContext) -> Summary {
     Summary {
         interval: current.instant.saturating_duration_since(earlier.instant),
-        get_success: Histogram::from(histogram_diff(&current.get_success, &earlier.get_success)),
-        get_failure: Histogram::from(histogram_diff(&current.get_failure, &earlier.get_failure)),
-        put_success: Histogram::from(histogram_diff(&current.put_success, &earlier.put_success)),
-        put_failure: Histogram::from(histogram_diff(&current.put_failure, &earlier.put_failure)),
+        histograms: current
+            .metrics
+            .iter()
+            .zip(earlier.metrics.iter())
+            .map(|((success, failure), (earlier_success, earlier_failure))| {
+                (
+                    Histogram::from(histogram_diff(success, earlier_success)),
+                    Histogram::from(histogram_diff(failure, earlier_failure)),
+                )
+            })
+            .collect(),
     }
 }
 
@@ -156,35 +166,42 @@ fn histogram_diff(
 
 pub(super) fn display(earlier_ctx: &mut ReportContext) {
     let mut current_ctx = ReportContext::default();
-    let summary = diff(&current_ctx, earlier_ctx);
-
-    display_histogram(
-        "GET",
-        &summary.get_success,
-        summary.interval,
-        current_ctx.get_success.get_histogram().get_sample_count(),
-    );
-    display_histogram(
-        "PUT",
-        &summary.put_success,
-        summary.interval,
-        current_ctx.put_success.get_histogram().get_sample_count(),
-    );
-    display_histogram(
-        "GET_ERROR",
-        &summary.get_failure,
-        summary.interval,
-        current_ctx.get_failure.get_histogram().get_sample_count(),
-    );
-    display_histogram(
-        "PUT_ERROR",
-        &summary.put_failure,
-        summary.interval,
-        current_ctx.put_failure.get_histogram().get_sample_count(),
-    );
+    display_summary(&current_ctx, earlier_ctx);
     std::mem::swap(earlier_ctx, &mut current_ctx);
 }
 
+/// Display the throughput and latency of the whole benchmark since `start_ctx`.
+pub(super) fn display_total(start_ctx: &ReportContext) {
+    println!("Summary:");
+    display_summary(&ReportContext::default(), start_ctx);
+}
+
+fn display_summary(current_ctx: &ReportContext, earlier_ctx: &ReportContext) {
+    let summary = diff(current_ctx, earlier_ctx);
+    let names = operations().map(|(name, _, _)| name);
+    for ((name, (success, _)), (metric, _)) in
+        names.iter().zip(summary.histograms.iter()).zip(current_ctx.metrics.iter())
+    {
+        display_histogram(
+            name,
+            success,
+            summary.interval,
+            metric.get_histogram().get_sample_count(),
+        );
+    }
+    for ((name, (_, failure)), (_, metric)) in
+        names.iter().zip(summary.histograms.iter()).zip(current_ctx.metrics.iter())
+    {
+        let name = format!("{name}_ERROR");
+        display_histogram(
+            &name,
+            failure,
+            summary.interval,
+            metric.get_histogram().get_sample_count(),
+        );
+    }
+}
+
 fn display_histogram(name: &str, h: &Histogram, interval: Duration, count: u64) {
     if h.count == 0 {
         return;
@@ -17,7 +17,7 @@ use std::time::Instant;
 
 use log::trace;
 use rand::prelude::*;
-use sekas_client::Database;
+use sekas_client::{Database, WriteBatchRequest, WriteBuilder};
 
 use super::metrics::*;
 use super::AppConfig;
@@ -39,8 +39,21 @@ pub struct Generator {
 
 #[derive(Debug, Clone)]
 pub enum NextOp {
-    Put { key: Vec<u8>, value: Vec<u8> },
-    Get { key: Vec<u8> },
+    Put {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Get {
+        key: Vec<u8>,
+    },
+    Scan {
+        start_key: Vec<u8>,
+        limit: usize,
+    },
+    /// Put the key values in a txn.
+    Txn {
+        kvs: Vec<(Vec<u8>, Vec<u8>)>,
+    },
 }
 
 impl Generator {
@@ -50,10 +63,22 @@ impl Generator {
 
     pub fn next_op(&mut self) -> NextOp {
         let v = self.rng.gen_range(0..100) as f64 / 100.0;
+        let data = &self.cfg.data;
+        let (write, scan, txn) =
+            (data.write, data.write + data.scan, data.write + data.scan + data.txn);
         let key = self.next_key();
-        if v < self.cfg.data.write {
+        if v < write {
             let value = self.next_bytes(self.cfg.data.value.clone());
             NextOp::Put { key, value }
+        } else if v < scan {
+            NextOp::Scan { start_key: key, limit: self.cfg.data.scan_limit }
+        } else if v < txn {
+            let mut kvs = vec![(key, self.next_bytes(self.cfg.data.value.clone()))];
+            for _ in 1..self.cfg.data.txn_size {
+                let key = self.next_key();
+                kvs.push((key, self.next_bytes(self.cfg.data.value.clone())));
+            }
+            NextOp::Txn { kvs }
         } else {
             NextOp::Get { key }
         }
@@ -111,6 +136,12 @@ async fn execute(db: &Database, co: u64, next_op: NextOp) {
         NextOp::Put { key, value } => {
             put(db, co, key, value).await;
         }
+        NextOp::Scan { start_key, limit } => {
+            scan(db, co, start_key, limit).await;
+        }
+        NextOp::Txn { kvs } => {
+            txn(db, co, kvs).await;
+        }
     }
 }
 
@@ -147,6 +178,44 @@ async fn put(db: &Database, co: u64, key: Vec<u8>, value: Vec<u8>) {
     PUT_REQUEST_TOTAL.inc();
 }
 
+async fn scan(db: &Database, co: u64, start_key: Vec<u8>, limit: usize) {
+    trace!("send scan request");
+    let start = Instant::now();
+    match db.scan(co, start_key, None, limit).await {
+        Ok(_) => {
+            SCAN_SUCCESS_REQUEST_TOTAL.inc();
+            SCAN_SUCCESS_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
+        }
+        Err(e) => {
+            tracing::error!("scan request {e:?}");
+            SCAN_FAILURE_REQUEST_TOTAL.inc();
+            SCAN_FAILURE_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
+        }
+    }
+    SCAN_REQUEST_TOTAL.inc();
+}
+
+async fn txn(db: &Database, co: u64, kvs: Vec<(Vec<u8>, Vec<u8>)>) {
+    trace!("send txn request");
+    let puts = kvs
+        .into_iter()
+        .map(|(key, value)| (co, WriteBuilder::new(key).ensure_put(value)))
+        .collect();
+    let start = Instant::now();
+    match db.write_batch(WriteBatchRequest { puts, ..Default::default() }).await {
+        Ok(_) => {
+            TXN_SUCCESS_REQUEST_TOTAL.inc();
+            TXN_SUCCESS_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
+        }
+        Err(e) => {
+            tracing::error!("txn request {e:?}");
+            TXN_FAILURE_REQUEST_TOTAL.inc();
+            TXN_FAILURE_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
+        }
+    }
+    TXN_REQUEST_TOTAL.inc();
+}
+
 #[inline]
 fn saturating_elapsed_seconds(instant: Instant) -> f64 {
     let now = Instant::now();
@@ -65,6 +65,11 @@ struct StartCommand {
     #[clap(long, value_name = "LIMIT")]
     cpu_nums: Option<u32>,
 
+    /// Sets a toml file declaring the initial databases and collections, which
+    /// are created by the root after the cluster is bootstrapped
+    #[clap(long, value_name = "FILE")]
+    bootstrap_spec: Option<String>,
+
     /// Dump config as toml file and exit
     #[clap(long, value_name = "FILE")]
     dump: Option<String>,
@@ -73,13 +78,7 @@ struct StartCommand {
 impl StartCommand {
     fn run(self) -> Result<()> {
         use sekas_runtime::{ExecutorOwner, ShutdownNotifier};
-
-        let filter_layer =
-            EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info")).unwrap();
-        tracing_subscriber::fmt()
-            .with_env_filter(filter_layer)
-            .with_ansi(atty::is(atty::Stream::Stderr))
-            .init();
+        use tracing_subscriber::prelude::*;
 
         let mut config = match load_config(&self) {
             Ok(c) => c,
@@ -87,6 +86,12 @@ impl StartCommand {
                 return Err(Error::InvalidArgument(format!("Config: {e}")));
             }
         };
+        if let Some(filename) = self.bootstrap_spec.as_ref() {
+            let contents = std::fs::read_to_string(filename)?;
+            let spec = toml::from_str(&contents)
+                .map_err(|e| Error::InvalidArgument(format!("Bootstrap spec: {e}")))?;
+            config.root.bootstrap_spec = Some(spec);
+        }
 
         if let Some(filename) = self.dump {
             let contents = toml::to_string(&config).expect("Config is serializable");
@@ -98,16 +103,30 @@ impl StartCommand {
             config.cpu_nums = num_cpus::get() as u32;
         }
 
-        info!("{config:#?}");
-
         let notifier = ShutdownNotifier::new();
         let shutdown = notifier.subscribe();
         let owner = ExecutorOwner::with_config(config.cpu_nums as usize, config.executor.clone());
         let executor = owner.executor();
+
+        // The OTLP exporter is installed within the runtime, it exports spans in
+        // background.
+        let tracer = executor.block_on(async { sekas_server::init_tracer(&config.trace) })?;
+        let filter_layer =
+            EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info")).unwrap();
+        tracing_subscriber::registry()
+            .with(filter_layer)
+            .with(tracing_subscriber::fmt::layer().with_ansi(atty::is(atty::Stream::Stderr)))
+            .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)))
+            .init();
+
+        info!("{config:#?}");
+
         let _handle = executor.spawn(async move {
-            notifier.ctrl_c().await;
+            notifier.terminate().await;
         });
-        sekas_server::run(config, executor, shutdown)
+        let result = sekas_server::run(config, executor, shutdown);
+        sekas_server::shutdown_tracer();
+        result
     }
 }
 
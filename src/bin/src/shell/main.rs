@@ -33,6 +33,11 @@ pub struct Command {
     #[clap(long, default_value = "0.0.0.0:21805")]
     addrs: Vec<String>,
 
+    /// Sets the token to authenticate, it is required if the cluster enables
+    /// the authentication.
+    #[clap(long)]
+    token: Option<String>,
+
     /// Sets the log level.
     #[clap(long)]
     log_level: Option<tracing::Level>,
@@ -54,7 +59,7 @@ impl Command {
 
         let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
         runtime.block_on(async move {
-            editor_main(self.addrs).await;
+            editor_main(self.addrs, self.token).await;
         });
     }
 }
@@ -323,11 +328,11 @@ impl Session {
     }
 }
 
-async fn editor_main(addrs: Vec<String>) {
+async fn editor_main(addrs: Vec<String>, token: Option<String>) {
     use rustyline::history::MemHistory;
     use rustyline::Config;
 
-    let mut session = new_session(addrs).await.expect("new session");
+    let mut session = new_session(addrs, token).await.expect("new session");
     let cfg = Config::builder().build();
     let history = MemHistory::new();
     let mut editor = Editor::<(), _>::with_history(cfg, history).expect("Editor::new");
@@ -372,10 +377,13 @@ fn usage() -> Result<()> {
     Ok(())
 }
 
-async fn new_session(addrs: Vec<String>) -> Result<Session> {
+async fn new_session(addrs: Vec<String>, token: Option<String>) -> Result<Session> {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(200)),
         timeout: Some(Duration::from_millis(500)),
+        app_tag: Some("shell".to_owned()),
+        token,
+        ..Default::default()
     };
     let client = SekasClient::new(opts, addrs).await?;
     Ok(Session {
@@ -16,11 +16,16 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use derivative::Derivative;
+
 use crate::discovery::StaticServiceDiscovery;
-use crate::rpc::{ConnManager, RootClient, Router};
-use crate::{AppError, AppResult, Database};
+use crate::rpc::{ConnManager, ConnPoolOptions, RootClient, Router, TlsOptions, TokenInterceptor};
+use crate::{
+    AppError, AppResult, ClientInstrument, Database, RetryPolicy, RetryState, SystemCollections,
+};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Derivative, Clone, Default)]
+#[derivative(Debug)]
 pub struct ClientOptions {
     /// The duration of connection timeout, an error is issued if establish
     /// connection is not finished after the duration.
@@ -28,6 +33,42 @@ pub struct ClientOptions {
 
     /// The duration of RPC over this client.
     pub timeout: Option<Duration>,
+
+    /// The workload tag of this client. It is attached to requests, so that the
+    /// server could break down metrics, rate limits and slow request logs by
+    /// applications.
+    pub app_tag: Option<String>,
+
+    /// The values larger than this size are split into chunks before writing,
+    /// so that the values exceeding the raft proposal size can be stored. The
+    /// chunking is disabled if it is `None`, the chunked values are always
+    /// reassembled on reads.
+    pub chunk_size: Option<usize>,
+
+    /// The policy to retry the failed operations, it is applied to the
+    /// requests issued to both groups and root.
+    pub retry_policy: RetryPolicy,
+
+    /// The options of the connection pools to endpoints.
+    pub conn_pool: ConnPoolOptions,
+
+    /// The TLS options of the connections to endpoints, the connections are
+    /// in plaintext if it is `None`.
+    pub tls: Option<TlsOptions>,
+
+    /// The tenant of this client, the databases are created, listed and opened
+    /// within the tenant. The default tenant is used if it is `None`.
+    pub tenant: Option<String>,
+
+    /// The token to authenticate this client, it is required if the cluster
+    /// enables the authentication.
+    #[derivative(Debug = "ignore")]
+    pub token: Option<String>,
+
+    /// The hooks to observe the latencies, retries, bytes and router lookups
+    /// of this client.
+    #[derivative(Debug = "ignore")]
+    pub instrument: Option<Arc<dyn ClientInstrument>>,
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +79,9 @@ pub struct Client {
 #[derive(Debug, Clone)]
 struct ClientInner {
     opts: ClientOptions,
+    /// The end user the requests are forwarded for, see
+    /// [`Client::with_end_user`].
+    end_user: Option<String>,
     root_client: RootClient,
     router: Router,
     conn_manager: ConnManager,
@@ -45,16 +89,23 @@ struct ClientInner {
 
 impl Client {
     pub async fn new(opts: ClientOptions, addrs: Vec<String>) -> AppResult<Self> {
-        let conn_manager = if let Some(connect_timeout) = opts.connect_timeout {
-            ConnManager::with_connect_timeout(connect_timeout)
-        } else {
-            ConnManager::new()
-        };
+        let interceptor = TokenInterceptor::new(opts.token.as_deref())?;
+        let conn_manager = ConnManager::with_options(
+            opts.connect_timeout,
+            opts.conn_pool.clone(),
+            opts.tls.clone(),
+            interceptor,
+        );
 
         let discovery = Arc::new(StaticServiceDiscovery::new(addrs.clone()));
-        let root_client = RootClient::new(discovery, conn_manager.clone());
-        let router = Router::new(root_client.clone()).await;
-        Ok(Self { inner: Arc::new(ClientInner { opts, root_client, router, conn_manager }) })
+        let root_client = RootClient::with_retry_policy(
+            discovery,
+            conn_manager.clone(),
+            opts.retry_policy.clone(),
+        );
+        let router = Router::with_instrument(root_client.clone(), opts.instrument.clone()).await;
+        let inner = ClientInner { opts, end_user: None, root_client, router, conn_manager };
+        Ok(Self { inner: Arc::new(inner) })
     }
 
     pub fn build(
@@ -63,21 +114,48 @@ impl Client {
         root_client: RootClient,
         conn_manager: ConnManager,
     ) -> Self {
-        Client { inner: Arc::new(ClientInner { opts, root_client, router, conn_manager }) }
+        let inner = ClientInner { opts, end_user: None, root_client, router, conn_manager };
+        Client { inner: Arc::new(inner) }
+    }
+
+    /// Return a client with the specified workload tag, which shares the
+    /// connections and the router with this client.
+    pub fn with_app_tag(&self, app_tag: Option<String>) -> Self {
+        let inner = &self.inner;
+        let opts = ClientOptions { app_tag, ..inner.opts.clone() };
+        Client { inner: Arc::new(ClientInner { opts, ..(**inner).clone() }) }
+    }
+
+    /// Return a client forwarding the requests for the end user, which shares
+    /// the connections and the router with this client. It is used by the
+    /// proxies, the servers count the requests forwarded with the cluster token
+    /// against the end user by the client quotas.
+    pub fn with_end_user(&self, end_user: Option<String>) -> Self {
+        Client { inner: Arc::new(ClientInner { end_user, ..(*self.inner).clone() }) }
     }
 
     pub async fn create_database(&self, name: String) -> AppResult<Database> {
-        let db_desc = self.inner.root_client.create_database(name).await?;
+        let db_desc = self.inner.root_client.create_database(self.tenant(), name).await?;
+        self.inner.router.apply_database(db_desc.clone());
         Ok(Database::new(self.clone(), db_desc, self.rpc_timeout()))
     }
 
     pub async fn delete_database(&self, name: String) -> AppResult<()> {
-        self.inner.root_client.delete_database(name).await?;
+        self.inner.root_client.delete_database(self.tenant(), name.clone()).await?;
+        if self.inner.opts.tenant.is_none() {
+            self.inner.router.invalidate_database(&name);
+        }
         Ok(())
     }
 
+    #[deprecated(note = "use `list_databases` instead")]
     pub async fn list_database(&self) -> AppResult<Vec<Database>> {
-        let databases = self.inner.root_client.list_database().await?;
+        self.list_databases().await
+    }
+
+    /// List the databases of the tenant of this client.
+    pub async fn list_databases(&self) -> AppResult<Vec<Database>> {
+        let databases = self.inner.root_client.list_database(self.tenant()).await?;
         Ok(databases
             .into_iter()
             .map(|desc| Database::new(self.clone(), desc, self.rpc_timeout()))
@@ -85,12 +163,42 @@ impl Client {
     }
 
     pub async fn open_database(&self, name: String) -> AppResult<Database> {
-        match self.inner.root_client.get_database(name.clone()).await? {
+        // The router only caches the names of the databases of the default tenant,
+        // since the id of the tenant is unknown to the client.
+        if self.inner.opts.tenant.is_none() {
+            if let Some(desc) = self.inner.router.find_database(&name) {
+                return Ok(Database::new(self.clone(), desc, self.rpc_timeout()));
+            }
+        }
+        match self.inner.root_client.get_database(self.tenant(), name.clone()).await? {
             None => Err(AppError::NotFound(format!("database {}", name))),
             Some(desc) => Ok(Database::new(self.clone(), desc, self.rpc_timeout())),
         }
     }
 
+    /// Open the read-only views of the cluster metadata, eg. the nodes and
+    /// groups, see [`SystemCollections`].
+    pub async fn open_system_collections(&self) -> AppResult<SystemCollections> {
+        // The system database belongs to the default tenant.
+        let desc = sekas_schema::system::db::database_desc();
+        let db = Database::new(self.clone(), desc, self.rpc_timeout());
+        Ok(SystemCollections::new(db))
+    }
+
+    /// Reload the cached database and collection descriptors from root. The
+    /// cache is maintained by the watch stream, this is only required if the
+    /// stale descriptors are not acceptable.
+    pub async fn refresh_metadata(&self) -> AppResult<()> {
+        let root_client = &self.inner.root_client;
+        let databases = root_client.list_database(self.tenant()).await?;
+        let mut collections = Vec::new();
+        for desc in &databases {
+            collections.extend(root_client.list_collection(desc.clone()).await?);
+        }
+        self.inner.router.reset_metadata(databases, collections);
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn root_client(&self) -> RootClient {
         self.inner.root_client.clone()
@@ -106,6 +214,43 @@ impl Client {
         self.inner.conn_manager.clone()
     }
 
+    #[inline]
+    fn tenant(&self) -> String {
+        self.inner.opts.tenant.clone().unwrap_or_default()
+    }
+
+    #[inline]
+    pub(crate) fn app_tag(&self) -> &str {
+        self.inner.opts.app_tag.as_deref().unwrap_or_default()
+    }
+
+    #[inline]
+    pub(crate) fn end_user(&self) -> &str {
+        self.inner.end_user.as_deref().unwrap_or_default()
+    }
+
+    #[inline]
+    pub(crate) fn chunk_size(&self) -> Option<usize> {
+        self.inner.opts.chunk_size.filter(|size| *size > 0)
+    }
+
+    #[inline]
+    pub(crate) fn instrument(&self) -> Option<&dyn ClientInstrument> {
+        self.inner.opts.instrument.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn retry_policy(&self) -> &RetryPolicy {
+        &self.inner.opts.retry_policy
+    }
+
+    /// Create a retry state of an operation with the retry policy of this
+    /// client.
+    #[inline]
+    pub(crate) fn retry_state(&self, timeout: Option<Duration>) -> RetryState {
+        RetryState::with_policy(self.inner.opts.retry_policy.clone(), timeout)
+    }
+
     #[inline]
     fn rpc_timeout(&self) -> Option<Duration> {
         self.inner.opts.timeout
@@ -0,0 +1,73 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Split the large values into chunks, so that the values larger than the
+//! proposal size of raft can be stored.
+//!
+//! The chunks of a value are stored in the chunk keys, which are the user key
+//! followed by [`CHUNK_KEY_MARK`] and the chunk index, and the user key holds
+//! a manifest, which is a value flagged with the number of chunks, see
+//! `Value::num_chunks`. The chunk keys are written in the same transaction
+//! with the manifest, and they are reassembled on reads and scans.
+
+/// The mark between the user key and the chunk index, the keys ending with
+/// this mark and a 4 bytes index are reserved for chunks.
+const CHUNK_KEY_MARK: &[u8] = b"\x00\xffsekas-chunk\xff";
+
+/// Return the key of the chunk of the user key.
+pub fn chunk_key(user_key: &[u8], index: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(user_key.len() + CHUNK_KEY_MARK.len() + 4);
+    buf.extend_from_slice(user_key);
+    buf.extend_from_slice(CHUNK_KEY_MARK);
+    buf.extend_from_slice(&index.to_be_bytes());
+    buf
+}
+
+/// Return whether the key is a chunk key.
+pub fn is_chunk_key(key: &[u8]) -> bool {
+    key.len() >= CHUNK_KEY_MARK.len() + 4 && key[..key.len() - 4].ends_with(CHUNK_KEY_MARK)
+}
+
+/// Split the value into chunks of `chunk_size`.
+#[inline]
+pub fn split(value: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    value.chunks(chunk_size).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_keys() {
+        assert!(is_chunk_key(&chunk_key(b"key", 0)));
+        assert!(is_chunk_key(&chunk_key(b"", u32::MAX)));
+        assert!(!is_chunk_key(b"key"));
+        assert!(!is_chunk_key(CHUNK_KEY_MARK));
+
+        // The chunk keys follow the user key.
+        assert!(b"key".as_slice() < chunk_key(b"key", 0).as_slice());
+        assert!(chunk_key(b"key", 0) < chunk_key(b"key", 1));
+        assert!(chunk_key(b"key", 1) < b"key\x01".to_vec());
+    }
+
+    #[test]
+    fn split_value() {
+        let value = (0..1000u32).map(|v| v as u8).collect::<Vec<_>>();
+        let chunks = split(&value, 256);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[3].len(), 1000 - 256 * 3);
+        assert_eq!(chunks.concat(), value);
+    }
+}
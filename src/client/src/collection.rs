@@ -0,0 +1,251 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use sekas_api::server::v1::{CollectionDesc, CompressionCodec, KeySchema};
+
+use crate::{AppResult, Database, Error, Result};
+
+/// Encode and decode the keys or values of a typed [`Collection`].
+pub trait Codec<T>: Send + Sync {
+    fn encode(&self, value: &T) -> Vec<u8>;
+
+    fn decode(&self, bytes: Vec<u8>) -> Result<T>;
+}
+
+/// The codec of raw bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytesCodec;
+
+/// The codec of UTF-8 strings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StringCodec;
+
+/// The codec of `u64`, which is encoded in big endian so that the order of
+/// the encoded keys is consistent with the numbers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct U64Codec;
+
+/// The codec of any serde types, which is encoded in JSON.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+/// The options to create a collection, see
+/// [`Database::create_collection_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct CollectionOptions {
+    pub(crate) encrypted: bool,
+    pub(crate) compression: CompressionCodec,
+    pub(crate) key_schema: Option<KeySchema>,
+    pub(crate) constraints: Vec<String>,
+}
+
+/// A typed wrapper of a collection, the keys and values are encoded by the
+/// user-provided codecs.
+pub struct Collection<K, V> {
+    db: Database,
+    desc: CollectionDesc,
+    key_codec: Arc<dyn Codec<K>>,
+    value_codec: Arc<dyn Codec<V>>,
+    _marker: PhantomData<fn(K, V)>,
+}
+
+impl Codec<Vec<u8>> for BytesCodec {
+    fn encode(&self, value: &Vec<u8>) -> Vec<u8> {
+        value.clone()
+    }
+
+    fn decode(&self, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(bytes)
+    }
+}
+
+impl Codec<String> for StringCodec {
+    fn encode(&self, value: &String) -> Vec<u8> {
+        value.as_bytes().to_vec()
+    }
+
+    fn decode(&self, bytes: Vec<u8>) -> Result<String> {
+        String::from_utf8(bytes).map_err(|err| Error::Internal(Box::new(err)))
+    }
+}
+
+impl Codec<u64> for U64Codec {
+    fn encode(&self, value: &u64) -> Vec<u8> {
+        value.to_be_bytes().to_vec()
+    }
+
+    fn decode(&self, bytes: Vec<u8>) -> Result<u64> {
+        let bytes: [u8; 8] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            Error::Internal(format!("decode u64 from {} bytes", bytes.len()).into())
+        })?;
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> Codec<T> for JsonCodec
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn encode(&self, value: &T) -> Vec<u8> {
+        serde_json::to_vec(value).expect("serialize to JSON")
+    }
+
+    fn decode(&self, bytes: Vec<u8>) -> Result<T> {
+        serde_json::from_slice(&bytes).map_err(|err| Error::Internal(Box::new(err)))
+    }
+}
+
+impl CollectionOptions {
+    /// Encrypt the values at rest, it requires the servers to configure the
+    /// encryption key file.
+    pub fn encrypted(mut self) -> Self {
+        self.encrypted = true;
+        self
+    }
+
+    /// Compress the values by the codec before storing, the reads are
+    /// transparent.
+    pub fn compression(mut self, compression: CompressionCodec) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Validate the keys by the key schema, the writes of keys violating the
+    /// schema are rejected.
+    pub fn key_schema(mut self, key_schema: KeySchema) -> Self {
+        self.key_schema = Some(key_schema);
+        self
+    }
+
+    /// Only place the replicas on the nodes with all labels of the
+    /// constraints, eg `disk=ssd`.
+    pub fn constraints(mut self, constraints: Vec<String>) -> Self {
+        self.constraints = constraints;
+        self
+    }
+}
+
+impl<K, V> Collection<K, V> {
+    pub fn new(
+        db: Database,
+        desc: CollectionDesc,
+        key_codec: impl Codec<K> + 'static,
+        value_codec: impl Codec<V> + 'static,
+    ) -> Self {
+        Collection {
+            db,
+            desc,
+            key_codec: Arc::new(key_codec),
+            value_codec: Arc::new(value_codec),
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn desc(&self) -> &CollectionDesc {
+        &self.desc
+    }
+
+    pub async fn get(&self, key: &K) -> Result<Option<V>> {
+        let key = self.key_codec.encode(key);
+        match self.db.get(self.desc.id, key).await? {
+            Some(value) => Ok(Some(self.value_codec.decode(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn put(&self, key: &K, value: &V) -> AppResult<()> {
+        let key = self.key_codec.encode(key);
+        let value = self.value_codec.encode(value);
+        self.db.put(self.desc.id, key, value).await
+    }
+
+    pub async fn delete(&self, key: &K) -> AppResult<()> {
+        let key = self.key_codec.encode(key);
+        self.db.delete(self.desc.id, key).await
+    }
+
+    /// Delete the key and return its previous value, see
+    /// [`Database::delete_with_prev`] for details.
+    pub async fn delete_with_prev(&self, key: &K) -> AppResult<Option<V>> {
+        let key = self.key_codec.encode(key);
+        match self.db.delete_with_prev(self.desc.id, key).await? {
+            Some(value) => Ok(Some(self.value_codec.decode(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Return whether the key exists, the value is not transferred.
+    pub async fn exists(&self, key: &K) -> Result<bool> {
+        let key = self.key_codec.encode(key);
+        self.db.exists(self.desc.id, key).await
+    }
+
+    /// Scan the key values in range `[start_key, end_key)`, see
+    /// [`Database::scan`] for details.
+    pub async fn scan(
+        &self,
+        start_key: &K,
+        end_key: Option<&K>,
+        limit: usize,
+    ) -> Result<Vec<(K, V)>> {
+        let start_key = self.key_codec.encode(start_key);
+        let end_key = end_key.map(|key| self.key_codec.encode(key));
+        let key_values = self.db.scan(self.desc.id, start_key, end_key, limit).await?;
+        key_values
+            .into_iter()
+            .map(|(key, value)| Ok((self.key_codec.decode(key)?, self.value_codec.decode(value)?)))
+            .collect()
+    }
+}
+
+impl<K, V> Clone for Collection<K, V> {
+    fn clone(&self) -> Self {
+        Collection {
+            db: self.db.clone(),
+            desc: self.desc.clone(),
+            key_codec: self.key_codec.clone(),
+            value_codec: self.value_codec.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn u64_codec_keeps_order() {
+        let codec = U64Codec;
+        let (small, large) = (codec.encode(&255), codec.encode(&256));
+        assert!(small < large);
+        assert_eq!(codec.decode(large).unwrap(), 256);
+        assert!(codec.decode(vec![1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn string_codec() {
+        let codec = StringCodec;
+        let bytes = codec.encode(&"value".to_owned());
+        assert_eq!(bytes, b"value".to_vec());
+        assert_eq!(codec.decode(bytes).unwrap(), "value");
+        assert!(codec.decode(vec![0xff, 0xfe]).is_err());
+    }
+}
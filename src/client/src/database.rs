@@ -11,7 +11,8 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
@@ -21,8 +22,9 @@ use sekas_schema::system::txn::TXN_MAX_VERSION;
 use crate::metrics::*;
 use crate::write_batch::WriteBatchContext;
 use crate::{
-    record_latency, AppError, AppResult, GroupClient, RetryState, SekasClient, WriteBatchRequest,
-    WriteBatchResponse, WriteBuilder,
+    chunk, record_latency, AppError, AppResult, Codec, Collection, CollectionOptions, GroupClient,
+    Operation, RetryState, SekasClient, WriteBatchRequest, WriteBatchResponse, WriteBuilder,
+    WriteCoalescer, WriteCoalescerOptions,
 };
 
 #[derive(Debug, Clone)]
@@ -34,52 +36,239 @@ pub struct Database {
     read_without_version: bool,
 }
 
+/// The options of scanning the values of a collection.
+#[derive(Debug, Default)]
+struct ScanOptions {
+    /// At most `limit` key values are returned if it is not zero.
+    limit: usize,
+    /// The latest version is read if it is `None`.
+    read_version: Option<u64>,
+    /// Include all versions retained by MVCC, including the tombstones.
+    include_raw_data: bool,
+    /// Only scan the keys changed since this version if it is not zero.
+    min_version: u64,
+    /// Only return the keys, the contents of values are left empty.
+    keys_only: bool,
+}
+
 impl Database {
     pub fn new(client: SekasClient, desc: DatabaseDesc, rpc_timeout: Option<Duration>) -> Self {
         let read_without_version = desc.id == sekas_schema::system::db::ID;
         Database { client, desc, rpc_timeout, read_without_version }
     }
 
+    /// Return the handle of this database which forwards the requests for the
+    /// end user, see [`SekasClient::with_end_user`].
+    pub fn with_end_user(&self, end_user: Option<String>) -> Self {
+        Database { client: self.client.with_end_user(end_user), ..self.clone() }
+    }
+
     pub async fn create_collection(&self, name: String) -> AppResult<CollectionDesc> {
-        let desc = self.client.root_client().create_collection(self.desc.clone(), name).await?;
+        self.create_collection_with_options(name, CollectionOptions::default()).await
+    }
+
+    /// Create a collection with the options, eg. the compression and the key
+    /// schema.
+    pub async fn create_collection_with_options(
+        &self,
+        name: String,
+        options: CollectionOptions,
+    ) -> AppResult<CollectionDesc> {
+        let desc =
+            self.client.root_client().create_collection(self.desc.clone(), name, options).await?;
+        self.client.router().apply_collection(desc.clone());
+        Ok(desc)
+    }
+
+    /// Create a new collection which shares the data of the source collection
+    /// at a snapshot, the following writes to either collection are not
+    /// visible to the other one.
+    pub async fn clone_collection(
+        &self,
+        source: String,
+        target: String,
+    ) -> AppResult<CollectionDesc> {
+        let desc =
+            self.client.root_client().clone_collection(self.desc.clone(), source, target).await?;
+        self.client.router().apply_collection(desc.clone());
         Ok(desc)
     }
 
     pub async fn delete_collection(&self, name: String) -> AppResult<()> {
-        self.client.root_client().delete_collection(self.desc.clone(), name).await?;
+        self.client.root_client().delete_collection(self.desc.clone(), name.clone()).await?;
+        self.client.router().invalidate_collection(self.desc.id, &name);
         Ok(())
     }
 
+    /// Open the collection as a typed collection, the keys and values are
+    /// encoded by the codecs.
+    pub async fn open_typed_collection<K, V>(
+        &self,
+        name: String,
+        key_codec: impl Codec<K> + 'static,
+        value_codec: impl Codec<V> + 'static,
+    ) -> AppResult<Collection<K, V>> {
+        let desc = self.open_collection(name).await?;
+        Ok(Collection::new(self.clone(), desc, key_codec, value_codec))
+    }
+
+    #[deprecated(note = "use `list_collections` instead")]
     pub async fn list_collection(&self) -> AppResult<Vec<CollectionDesc>> {
+        self.list_collections().await
+    }
+
+    /// List the collections of this database.
+    pub async fn list_collections(&self) -> AppResult<Vec<CollectionDesc>> {
         let collections = self.client.root_client().list_collection(self.desc.clone()).await?;
         Ok(collections)
     }
 
+    /// Describe the collection along with the layout and the usage of its
+    /// shards, the usage is reported by the group leaders so it might be stale.
+    pub async fn describe_collection(&self, name: String) -> AppResult<CollectionDescription> {
+        let root_client = self.client.root_client();
+        match root_client.describe_collection(self.desc.clone(), name.clone()).await? {
+            None => Err(AppError::NotFound(format!("collection {}", name))),
+            Some(description) => Ok(description),
+        }
+    }
+
     pub async fn open_collection(&self, name: String) -> AppResult<CollectionDesc> {
+        if let Some(desc) = self.client.router().find_collection(self.desc.id, &name) {
+            return Ok(desc);
+        }
         match self.client.root_client().get_collection(self.desc.clone(), name.clone()).await? {
             None => Err(AppError::NotFound(format!("collection {}", name))),
             Some(co_desc) => Ok(co_desc),
         }
     }
 
+    /// Return the min GC timestamp of the collection, the AS-OF reads above
+    /// this timestamp are valid.
+    pub async fn min_gc_timestamp(&self, collection_id: u64) -> AppResult<u64> {
+        let min_gc_timestamp = self.client.root_client().min_gc_timestamp(collection_id).await?;
+        Ok(min_gc_timestamp)
+    }
+
+    /// Hold the GC horizon of the collection below `timestamp` for `ttl`, for a
+    /// long-running read job. Return the lease id and the expired timestamp,
+    /// the lease should be renewed by [`Database::renew_gc_lease`] before it
+    /// expires. It requires a superuser if the authentication is enabled.
+    pub async fn hold_gc_lease(
+        &self,
+        collection_id: u64,
+        timestamp: u64,
+        ttl: Duration,
+    ) -> AppResult<(u64, u64)> {
+        let root_client = self.client.root_client();
+        let lease = root_client.hold_gc_lease(0, collection_id, timestamp, ttl).await?;
+        Ok(lease)
+    }
+
+    /// Renew the GC lease, return the new expired timestamp.
+    pub async fn renew_gc_lease(
+        &self,
+        lease_id: u64,
+        collection_id: u64,
+        timestamp: u64,
+        ttl: Duration,
+    ) -> AppResult<u64> {
+        let root_client = self.client.root_client();
+        let (_, expired_at) =
+            root_client.hold_gc_lease(lease_id, collection_id, timestamp, ttl).await?;
+        Ok(expired_at)
+    }
+
+    pub async fn release_gc_lease(&self, lease_id: u64) -> AppResult<()> {
+        self.client.root_client().release_gc_lease(lease_id).await?;
+        Ok(())
+    }
+
     pub async fn delete(&self, collection_id: u64, key: Vec<u8>) -> AppResult<()> {
         let delete = WriteBuilder::new(key).ensure_delete();
         let batch =
             WriteBatchRequest { deletes: vec![(collection_id, delete)], ..Default::default() };
-        self.write_batch(batch).await?;
+        self.write_batch_with_op(Operation::Delete, batch).await?;
         Ok(())
     }
 
+    /// Delete the key and return its previous value, `None` is returned if the
+    /// key doesn't exist. The chunked previous value is reassembled.
+    pub async fn delete_with_prev(
+        &self,
+        collection_id: u64,
+        key: Vec<u8>,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let delete = WriteBuilder::new(key.clone()).take_prev_value().ensure_delete();
+        let batch =
+            WriteBatchRequest { deletes: vec![(collection_id, delete)], ..Default::default() };
+        let resp = self.write_batch_with_op(Operation::Delete, batch).await?;
+        let Some(prev) = resp.deletes.into_iter().next().flatten() else {
+            return Ok(None);
+        };
+        if prev.num_chunks == 0 {
+            return Ok(prev.content);
+        }
+        // The chunks are deleted along with the key, but they are still visible
+        // before the version of this deletion.
+        let read_version = resp.version - 1;
+        let mut retry_state = self.client.retry_state(self.rpc_timeout);
+        loop {
+            match self
+                .read_chunks(
+                    collection_id,
+                    &key,
+                    prev.num_chunks,
+                    read_version,
+                    &mut HashMap::default(),
+                    &mut retry_state,
+                )
+                .await
+            {
+                Ok(content) => return Ok(Some(content)),
+                Err(err) => retry_state.retry(err).await?,
+            }
+        }
+    }
+
     pub async fn put(&self, collection_id: u64, key: Vec<u8>, value: Vec<u8>) -> AppResult<()> {
         let put = WriteBuilder::new(key).ensure_put(value);
         let batch = WriteBatchRequest { puts: vec![(collection_id, put)], ..Default::default() };
-        self.write_batch(batch).await?;
+        self.write_batch_with_op(Operation::Put, batch).await?;
         Ok(())
     }
 
+    /// Atomically add `delta` to the i64 value of the key and return the new
+    /// value. A missing key is treated as zero.
+    pub async fn incr(&self, collection_id: u64, key: Vec<u8>, delta: i64) -> AppResult<i64> {
+        let put = WriteBuilder::new(key).ensure_add(delta);
+        let batch = WriteBatchRequest { puts: vec![(collection_id, put)], ..Default::default() };
+        let resp = self.write_batch_with_op(Operation::Put, batch).await?;
+        resp.add_results
+            .into_iter()
+            .next()
+            .flatten()
+            .ok_or_else(|| AppError::Internal("the result of add is missing".into()))
+    }
+
     pub async fn write_batch(&self, req: WriteBatchRequest) -> crate::Result<WriteBatchResponse> {
-        let ctx = WriteBatchContext::new(req, self.client.clone(), self.rpc_timeout);
-        ctx.commit().await
+        self.write_batch_with_op(Operation::WriteBatch, req).await
+    }
+
+    async fn write_batch_with_op(
+        &self,
+        op: Operation,
+        req: WriteBatchRequest,
+    ) -> crate::Result<WriteBatchResponse> {
+        let start = Instant::now();
+        let sent_bytes =
+            req.puts.iter().map(|(_, put)| put.key.len() + put.value.len()).sum::<usize>()
+                + req.deletes.iter().map(|(_, delete)| delete.key.len()).sum::<usize>();
+        let ctx = WriteBatchContext::new(req, self.client.clone(), self.rpc_timeout)
+            .with_database(self.desc.id);
+        let result = ctx.commit().await;
+        self.report_operation(op, start, result.is_ok(), sent_bytes, 0);
+        result
     }
 
     pub async fn get(&self, collection_id: u64, key: Vec<u8>) -> crate::Result<Option<Vec<u8>>> {
@@ -95,39 +284,374 @@ impl Database {
         CLIENT_DATABASE_BYTES_TOTAL.rx.inc_by(key.len() as u64);
         CLIENT_DATABASE_REQUEST_TOTAL.get.inc();
         record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.get);
-        let mut retry_state = RetryState::new(self.rpc_timeout);
+        let start = Instant::now();
+        let mut retry_state = self.client.retry_state(self.rpc_timeout);
 
         loop {
             match self.get_inner(collection_id, &key, &mut retry_state).await {
                 Ok(value) => {
-                    CLIENT_DATABASE_BYTES_TOTAL.tx.inc_by(
-                        value
-                            .as_ref()
-                            .map(|v| v.content.as_ref().map(Vec::len).unwrap_or_default())
-                            .unwrap_or_default() as u64,
-                    );
+                    let received_bytes = value
+                        .as_ref()
+                        .map(|v| v.content.as_ref().map(Vec::len).unwrap_or_default())
+                        .unwrap_or_default();
+                    CLIENT_DATABASE_BYTES_TOTAL.tx.inc_by(received_bytes as u64);
+                    self.report_operation(Operation::Get, start, true, key.len(), received_bytes);
                     return Ok(value);
                 }
                 Err(err) => {
-                    retry_state.retry(err).await?;
+                    if let Err(err) = retry_state.retry(err).await {
+                        self.report_operation(Operation::Get, start, false, key.len(), 0);
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return whether the key exists, only the key is read so that the large
+    /// value is not transferred.
+    pub async fn exists(&self, collection_id: u64, key: Vec<u8>) -> crate::Result<bool> {
+        CLIENT_DATABASE_BYTES_TOTAL.rx.inc_by(key.len() as u64);
+        CLIENT_DATABASE_REQUEST_TOTAL.get.inc();
+        record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.get);
+        let start = Instant::now();
+        let mut retry_state = self.client.retry_state(self.rpc_timeout);
+        loop {
+            match self.exists_inner(collection_id, &key, &mut retry_state).await {
+                Ok(exists) => {
+                    self.report_operation(Operation::Get, start, true, key.len(), 0);
+                    return Ok(exists);
+                }
+                Err(err) => {
+                    if let Err(err) = retry_state.retry(err).await {
+                        self.report_operation(Operation::Get, start, false, key.len(), 0);
+                        return Err(err);
+                    }
                 }
             }
         }
     }
 
+    async fn exists_inner(
+        &self,
+        collection_id: u64,
+        user_key: &[u8],
+        retry_state: &mut RetryState,
+    ) -> crate::Result<bool> {
+        let start_version = self.read_version(retry_state).await?;
+        let value = self.get_at(collection_id, user_key, start_version, true, retry_state).await?;
+        Ok(value.map(|v| v.content.is_some()).unwrap_or_default())
+    }
+
     async fn get_inner(
         &self,
         collection_id: u64,
         user_key: &[u8],
         retry_state: &mut RetryState,
     ) -> crate::Result<Option<Value>> {
-        let root_client = self.client.root_client();
-        let start_version = if self.read_without_version {
-            TXN_MAX_VERSION
-        } else {
-            root_client.alloc_txn_id(1, retry_state.timeout()).await?
+        let start_version = self.read_version(retry_state).await?;
+        let Some(mut value) =
+            self.get_at(collection_id, user_key, start_version, false, retry_state).await?
+        else {
+            return Ok(None);
+        };
+        if value.num_chunks != 0 && value.content.is_some() {
+            let content = self
+                .read_chunks(
+                    collection_id,
+                    user_key,
+                    value.num_chunks,
+                    start_version,
+                    &mut HashMap::default(),
+                    retry_state,
+                )
+                .await?;
+            value.content = Some(content);
+        }
+        Ok(Some(value))
+    }
+
+    /// Scan the key values of the collection in range `[start_key, end_key)`,
+    /// the end is unbounded if `end_key` is `None`. At most `limit` key values
+    /// are returned if it is not zero. The chunked values are reassembled.
+    pub async fn scan(
+        &self,
+        collection_id: u64,
+        start_key: Vec<u8>,
+        end_key: Option<Vec<u8>>,
+        limit: usize,
+    ) -> crate::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let start = Instant::now();
+        let sent_bytes = start_key.len() + end_key.as_ref().map(Vec::len).unwrap_or_default();
+        let result = self
+            .scan_inner(
+                collection_id,
+                start_key,
+                end_key,
+                ScanOptions { limit, ..Default::default() },
+            )
+            .await
+            .map(|(_, value_sets)| {
+                value_sets
+                    .into_iter()
+                    .filter_map(|value_set| {
+                        let content = value_set.values.into_iter().next()?.content?;
+                        Some((value_set.user_key, content))
+                    })
+                    .collect::<Vec<_>>()
+            });
+        let received_bytes = result
+            .as_ref()
+            .map(|kvs| kvs.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>())
+            .unwrap_or_default();
+        self.report_operation(Operation::Scan, start, result.is_ok(), sent_bytes, received_bytes);
+        result
+    }
+
+    /// Scan the raw values of the collection in range `[start_key, end_key)`
+    /// at `read_version`, the latest version is read if it is `None`. All
+    /// versions retained by MVCC are returned in desc order, including the
+    /// tombstones, and only the latest value is reassembled from chunks.
+    /// Returns the read version along with the value sets.
+    pub async fn scan_raw_values(
+        &self,
+        collection_id: u64,
+        start_key: Vec<u8>,
+        end_key: Option<Vec<u8>>,
+        limit: usize,
+        read_version: Option<u64>,
+    ) -> crate::Result<(u64, Vec<ValueSet>)> {
+        let opts =
+            ScanOptions { limit, read_version, include_raw_data: true, ..Default::default() };
+        self.scan_inner(collection_id, start_key, end_key, opts).await
+    }
+
+    /// Like [`Database::scan_raw_values`], but only the latest value of each
+    /// key is returned, and the deleted keys are skipped.
+    pub async fn scan_values(
+        &self,
+        collection_id: u64,
+        start_key: Vec<u8>,
+        end_key: Option<Vec<u8>>,
+        limit: usize,
+        read_version: Option<u64>,
+    ) -> crate::Result<(u64, Vec<ValueSet>)> {
+        let opts = ScanOptions { limit, read_version, ..Default::default() };
+        self.scan_inner(collection_id, start_key, end_key, opts).await
+    }
+
+    /// Count the keys of the collection in range `[start_key, end_key)` at
+    /// `read_version`, the values are not transferred. Returns the read
+    /// version along with the number of keys.
+    pub async fn count_keys(
+        &self,
+        collection_id: u64,
+        start_key: Vec<u8>,
+        end_key: Option<Vec<u8>>,
+        read_version: Option<u64>,
+    ) -> crate::Result<(u64, usize)> {
+        let opts = ScanOptions { read_version, keys_only: true, ..Default::default() };
+        let (read_version, value_sets) =
+            self.scan_inner(collection_id, start_key, end_key, opts).await?;
+        Ok((read_version, value_sets.len()))
+    }
+
+    /// Like [`Database::scan_raw_values`], but only scan the keys changed since
+    /// `min_version`. The versions of the changed keys before `min_version` are
+    /// returned back to the latest tombstone, so the history of them could be
+    /// derived.
+    pub async fn scan_changed_values(
+        &self,
+        collection_id: u64,
+        start_key: Vec<u8>,
+        end_key: Option<Vec<u8>>,
+        read_version: Option<u64>,
+        min_version: u64,
+    ) -> crate::Result<(u64, Vec<ValueSet>)> {
+        let opts =
+            ScanOptions { read_version, include_raw_data: true, min_version, ..Default::default() };
+        self.scan_inner(collection_id, start_key, end_key, opts).await
+    }
+
+    async fn scan_inner(
+        &self,
+        collection_id: u64,
+        start_key: Vec<u8>,
+        end_key: Option<Vec<u8>>,
+        opts: ScanOptions,
+    ) -> crate::Result<(u64, Vec<ValueSet>)> {
+        let ScanOptions { limit, read_version, include_raw_data, min_version, keys_only } = opts;
+        let mut retry_state = self.client.retry_state(self.rpc_timeout);
+        let start_version = match read_version {
+            Some(version) => version,
+            None => self.read_version(&mut retry_state).await?,
         };
+        let mut value_sets = Vec::new();
+        let mut cursor = start_key;
+        let mut exclude_cursor = false;
+        loop {
+            let router = self.client.router();
+            let (group, shard) = match router.find_shard(collection_id, &cursor) {
+                Ok(v) => v,
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                    continue;
+                }
+            };
+            let shard_end = sekas_schema::shard::end_key(&shard);
+            let scan_end = match &end_key {
+                Some(end) if shard_end.is_empty() || *end <= shard_end => end.clone(),
+                _ => shard_end.clone(),
+            };
+            let req = Request::Scan(ShardScanRequest {
+                shard_id: shard.id,
+                start_version,
+                limit: if limit == 0 { 0 } else { (limit - value_sets.len()) as u64 },
+                start_key: Some(cursor.clone()),
+                exclude_start_key: exclude_cursor,
+                end_key: if scan_end.is_empty() { None } else { Some(scan_end.clone()) },
+                exclude_end_key: true,
+                include_raw_data,
+                min_version,
+                keys_only,
+                ..Default::default()
+            });
+            let mut client = GroupClient::new(group, self.client.clone());
+            if let Some(duration) = retry_state.timeout() {
+                client.set_timeout(duration);
+            }
+            let resp = match client.request(&req).await {
+                Ok(Response::Scan(resp)) => resp,
+                Ok(_) => {
+                    return Err(crate::Error::Internal(
+                        "invalid response type, Scan is required".into(),
+                    ))
+                }
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                    continue;
+                }
+            };
+
+            let last_key = resp.data.last().map(|value_set| value_set.user_key.clone());
+            // The chunk keys follow their user keys, so the chunks are reassembled from
+            // the same response, only the ones cut off by the limit are read by keys.
+            let (chunk_sets, data): (Vec<_>, Vec<_>) = resp
+                .data
+                .into_iter()
+                .partition(|value_set| chunk::is_chunk_key(&value_set.user_key));
+            let mut scanned_chunks = chunk_sets
+                .into_iter()
+                .filter_map(|value_set| {
+                    let content = value_set.values.into_iter().next()?.content?;
+                    Some((value_set.user_key, content))
+                })
+                .collect::<HashMap<_, _>>();
+            for mut value_set in data {
+                match value_set.values.first_mut() {
+                    Some(Value { content: Some(content), num_chunks, .. }) => {
+                        if *num_chunks != 0 {
+                            *content = self
+                                .read_chunks(
+                                    collection_id,
+                                    &value_set.user_key,
+                                    *num_chunks,
+                                    start_version,
+                                    &mut scanned_chunks,
+                                    &mut retry_state,
+                                )
+                                .await?;
+                        }
+                    }
+                    _ if include_raw_data => {}
+                    _ => continue,
+                }
+                value_sets.push(value_set);
+            }
+            if limit != 0 && value_sets.len() >= limit {
+                value_sets.truncate(limit);
+                break;
+            }
+            if resp.has_more {
+                if let Some(last_key) = last_key {
+                    cursor = last_key;
+                    exclude_cursor = true;
+                }
+                continue;
+            }
+            if scan_end.is_empty() || Some(&scan_end) == end_key.as_ref() {
+                break;
+            }
+            cursor = scan_end;
+            exclude_cursor = false;
+        }
+        Ok((start_version, value_sets))
+    }
+
+    fn report_operation(
+        &self,
+        op: Operation,
+        start: Instant,
+        succeed: bool,
+        sent_bytes: usize,
+        received_bytes: usize,
+    ) {
+        if let Some(instrument) = self.client.instrument() {
+            instrument.on_operation(op, start.elapsed(), succeed);
+            instrument.on_bytes(op, sent_bytes as u64, received_bytes as u64);
+        }
+    }
+
+    async fn read_version(&self, retry_state: &mut RetryState) -> crate::Result<u64> {
+        if self.read_without_version {
+            Ok(TXN_MAX_VERSION)
+        } else {
+            self.client.root_client().alloc_txn_id(1, retry_state.timeout()).await
+        }
+    }
 
+    /// Reassemble the chunks of the user key, the chunks are taken from the
+    /// scanned ones if exist, otherwise they are read at the start version.
+    async fn read_chunks(
+        &self,
+        collection_id: u64,
+        user_key: &[u8],
+        num_chunks: u32,
+        start_version: u64,
+        scanned_chunks: &mut HashMap<Vec<u8>, Vec<u8>>,
+        retry_state: &mut RetryState,
+    ) -> crate::Result<Vec<u8>> {
+        let mut chunks = Vec::with_capacity(num_chunks as usize);
+        for index in 0..num_chunks {
+            let chunk_key = chunk::chunk_key(user_key, index);
+            if let Some(chunk) = scanned_chunks.remove(&chunk_key) {
+                chunks.push(chunk);
+                continue;
+            }
+            let chunk = self
+                .get_at(collection_id, &chunk_key, start_version, false, retry_state)
+                .await?
+                .and_then(|v| v.content)
+                .ok_or_else(|| {
+                    crate::Error::Internal(
+                        format!("the chunk {index} of key {user_key:?} is lost").into(),
+                    )
+                })?;
+            chunks.push(chunk);
+        }
+        Ok(chunks.concat())
+    }
+
+    /// Read the value of the key at the start version, the content of a live
+    /// value is empty if `key_only` is set.
+    async fn get_at(
+        &self,
+        collection_id: u64,
+        user_key: &[u8],
+        start_version: u64,
+        key_only: bool,
+        retry_state: &mut RetryState,
+    ) -> crate::Result<Option<Value>> {
         let router = self.client.router();
         let (group, shard) = router.find_shard(collection_id, user_key)?;
         let mut client = GroupClient::new(group, self.client.clone());
@@ -135,25 +659,34 @@ impl Database {
             shard_id: shard.id,
             start_version,
             user_key: user_key.to_owned(),
+            key_only,
         });
         if let Some(duration) = retry_state.timeout() {
             client.set_timeout(duration);
         }
-        match client.request(&req).await? {
+        let resp = match self.client.retry_policy().hedged_read_delay {
+            Some(delay) => client.hedged_request(&req, delay).await?,
+            None => client.request(&req).await?,
+        };
+        match resp {
             Response::Get(ShardGetResponse { value }) => Ok(value),
             _ => Err(crate::Error::Internal("invalid response type, Get is required".into())),
         }
     }
 
+    /// Create a coalescer, to merge the concurrent writes to the same shard.
+    pub fn write_coalescer(&self, opts: WriteCoalescerOptions) -> WriteCoalescer {
+        WriteCoalescer::new(self.client.clone(), self.clone(), opts)
+    }
+
     /// To issue a batch writes to a shard.
-    #[allow(dead_code)]
     pub(crate) async fn write(
         &self,
-        request: ShardWriteRequest,
+        request: &ShardWriteRequest,
     ) -> crate::Result<ShardWriteResponse> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = self.client.retry_state(self.rpc_timeout);
         loop {
-            match self.write_inner(&request, retry_state.timeout()).await {
+            match self.write_inner(request, retry_state.timeout()).await {
                 Ok(value) => {
                     return Ok(value);
                 }
@@ -14,8 +14,9 @@
 // limitations under the License.
 
 use std::error::Error as StdError;
+use std::time::Duration;
 
-use sekas_api::server::v1::{GroupDesc, ReplicaDesc, RootDesc, Value};
+use sekas_api::server::v1::{GroupDesc, NodeStatus, ReplicaDesc, RootDesc, Value};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub type AppResult<T> = std::result::Result<T, AppError>;
@@ -37,6 +38,11 @@ pub enum AppError {
     #[error("cas condition {1} not satisfied, operation index {0}")]
     CasFailed(u64, u64, Option<Value>),
 
+    /// The collection is read-only, the writes are rejected until it is
+    /// unfrozen.
+    #[error("collection {0} is frozen")]
+    CollectionFrozen(u64),
+
     #[error("network: {0}")]
     Network(tonic::Status),
 
@@ -61,15 +67,43 @@ pub enum Error {
     #[error("{0} is exhausted")]
     ResourceExhausted(String),
 
+    /// The request exceeds the quota of the client, it could be retried after
+    /// the duration. Zero means it is never admitted, eg. it is too large.
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String, Duration),
+
     #[error("cas condition {1} not satisfied, operation index {0}")]
     CasFailed(u64, u64, Option<Value>),
 
+    /// The collection is read-only, the writes are rejected until it is
+    /// unfrozen.
+    #[error("collection {0} is frozen")]
+    CollectionFrozen(u64),
+
+    /// The request is issued by or to a stale incarnation of the node, eg. the
+    /// node is wiped and re-joined the cluster.
+    #[error("node {0} incarnation {2} not match, {1} is expected")]
+    NodeIncarnationMismatch(/* node_id */ u64, /* expected */ u64, /* actual */ u64),
+
+    #[error("node {0} not found")]
+    NodeNotFound(u64),
+
+    /// The current status of the node doesn't allow the operation, eg. the
+    /// node is already cordoned.
+    #[error("node {0} is {1:?}")]
+    NodeStatusNotMatch(/* node_id */ u64, NodeStatus),
+
     #[error("group epoch not match")]
     EpochNotMatch(GroupDesc),
 
     #[error("group {0} not found")]
     GroupNotFound(u64),
 
+    /// The writes of the group are rejected by the admission control, it
+    /// should be retried after the duration.
+    #[error("group {0} is busy")]
+    GroupBusy(u64, Duration),
+
     #[error("not root leader")]
     NotRootLeader(RootDesc, u64, Option<ReplicaDesc>),
 
@@ -80,6 +114,8 @@ pub enum Error {
         // term
         u64,
         Option<ReplicaDesc>,
+        // The latest group descriptor known by the target replica.
+        Option<GroupDesc>,
     ),
 
     /// This indicates that the `GroupClient` has not been able to access the
@@ -106,11 +142,17 @@ impl From<tonic::Status> for Error {
 
         match status.code() {
             Code::Ok => panic!("invalid argument"),
+            Code::NotFound | Code::FailedPrecondition if !status.details().is_empty() => {
+                from_source_or_details(status)
+            }
             Code::InvalidArgument => Error::InvalidArgument(status.message().into()),
             Code::Cancelled if status.message().contains("Timeout expired") => {
                 Error::DeadlineExceeded(status.message().into())
             }
             Code::AlreadyExists => Error::AlreadyExists(status.message().into()),
+            Code::ResourceExhausted if !status.details().is_empty() => {
+                from_source_or_details(status)
+            }
             Code::ResourceExhausted => Error::ResourceExhausted(status.message().into()),
             Code::NotFound => Error::NotFound(status.message().into()),
             Code::Internal => Error::Internal(status.message().into()),
@@ -135,13 +177,30 @@ impl From<sekas_api::server::v1::Error> for Error {
         let msg = detail.message.clone();
         match detail.detail.as_ref().and_then(|u| u.value.clone()) {
             Some(Value::GroupNotFound(v)) => Error::GroupNotFound(v.group_id),
-            Some(Value::NotLeader(v)) => Error::NotLeader(v.group_id, v.term, v.leader),
+            Some(Value::GroupBusy(v)) => {
+                Error::GroupBusy(v.group_id, Duration::from_millis(v.retry_after_ms))
+            }
+            Some(Value::NotLeader(v)) => {
+                Error::NotLeader(v.group_id, v.term, v.leader, v.descriptor)
+            }
             Some(Value::NotRoot(v)) => {
                 Error::NotRootLeader(v.root.unwrap_or_default(), v.term, v.leader)
             }
             Some(Value::NotMatch(v)) => Error::EpochNotMatch(v.descriptor.unwrap_or_default()),
+            Some(Value::QuotaExceeded(v)) => {
+                Error::QuotaExceeded(msg, Duration::from_millis(v.retry_after_ms))
+            }
             Some(Value::StatusCode(v)) => Status::new(v.into(), msg).into(),
             Some(Value::CasFailed(v)) => Error::CasFailed(v.index, v.cond_index, v.prev_value),
+            Some(Value::CollectionFrozen(v)) => Error::CollectionFrozen(v.collection_id),
+            Some(Value::NodeIncarnationMismatch(v)) => {
+                Error::NodeIncarnationMismatch(v.node_id, v.expected, v.actual)
+            }
+            Some(Value::NodeNotFound(v)) => Error::NodeNotFound(v.node_id),
+            Some(Value::NodeStatusNotMatch(v)) => {
+                let status = NodeStatus::from_i32(v.status).unwrap_or_default();
+                Error::NodeStatusNotMatch(v.node_id, status)
+            }
             _ => Status::internal(format!("unknown error detail, msg: {msg}")).into(),
         }
     }
@@ -163,15 +222,25 @@ impl From<Error> for AppError {
             Error::CasFailed(index, cond_index, prev_value) => {
                 AppError::CasFailed(index, cond_index, prev_value)
             }
+            Error::CollectionFrozen(id) => AppError::CollectionFrozen(id),
             Error::Internal(v) => AppError::Internal(v),
 
             Error::Transport(status) => AppError::Network(status),
+            Error::ResourceExhausted(msg) => {
+                AppError::Network(tonic::Status::resource_exhausted(msg))
+            }
+            Error::QuotaExceeded(msg, retry_after) => {
+                AppError::Network(quota_exceeded_status(msg, retry_after))
+            }
+            err @ Error::NodeIncarnationMismatch(..) => AppError::Internal(err.into()),
+            err @ Error::NodeNotFound(_) => AppError::NotFound(err.to_string()),
+            err @ Error::NodeStatusNotMatch(..) => AppError::InvalidArgument(err.to_string()),
             Error::Connect(status) => panic!("do not expose connect error {status:?} to user"),
             Error::Rpc(status) => panic!("unknown error: {status:?}"),
 
             Error::EpochNotMatch(_)
-            | Error::ResourceExhausted(_)
             | Error::GroupNotFound(_)
+            | Error::GroupBusy(..)
             | Error::GroupNotAccessable(_)
             | Error::NotRootLeader(..)
             | Error::NotLeader(..) => unreachable!("convert err {err:?} to `AppError`"),
@@ -188,13 +257,61 @@ impl From<AppError> for tonic::Status {
             AppError::AlreadyExists(msg) => Status::already_exists(msg),
             AppError::InvalidArgument(msg) => Status::invalid_argument(msg),
             AppError::DeadlineExceeded(msg) => Status::deadline_exceeded(msg),
-            AppError::CasFailed(_, _, _) => todo!("not supported"),
+            AppError::CasFailed(index, cond_index, _) => Status::failed_precondition(format!(
+                "the condition {cond_index} of write {index} is not satisfied"
+            )),
+            err @ AppError::CollectionFrozen(_) => Status::failed_precondition(err.to_string()),
             AppError::Network(status) => status, // as proxy
             AppError::Internal(err) => Status::internal(err.to_string()),
         }
     }
 }
 
+/// Convert the error to the status of the public services, the internal
+/// routing errors are exposed as retryable `Unavailable`.
+impl From<Error> for tonic::Status {
+    fn from(err: Error) -> Self {
+        use tonic::Status;
+
+        match err {
+            Error::InvalidArgument(msg) => Status::invalid_argument(msg),
+            Error::DeadlineExceeded(msg) => Status::deadline_exceeded(msg),
+            Error::NotFound(msg) => Status::not_found(msg),
+            Error::AlreadyExists(msg) => Status::already_exists(msg),
+            Error::ResourceExhausted(msg) => Status::resource_exhausted(msg),
+            Error::QuotaExceeded(msg, retry_after) => quota_exceeded_status(msg, retry_after),
+            Error::CasFailed(index, cond_index, _) => Status::failed_precondition(format!(
+                "the condition {cond_index} of write {index} is not satisfied"
+            )),
+            err @ Error::CollectionFrozen(_) => Status::failed_precondition(err.to_string()),
+            err @ (Error::NodeIncarnationMismatch(..) | Error::NodeStatusNotMatch(..)) => {
+                Status::failed_precondition(err.to_string())
+            }
+            err @ Error::NodeNotFound(_) => Status::not_found(err.to_string()),
+            Error::Rpc(status) => status,
+            Error::Internal(err) => Status::internal(err.to_string()),
+            Error::EpochNotMatch(_)
+            | Error::GroupNotFound(_)
+            | Error::GroupBusy(..)
+            | Error::GroupNotAccessable(_)
+            | Error::NotRootLeader(..)
+            | Error::NotLeader(..)
+            | Error::Connect(_)
+            | Error::Transport(_) => Status::unavailable(err.to_string()),
+        }
+    }
+}
+
+/// Build the `ResourceExhausted` status carrying the retry-after hint, so that
+/// it is kept when the status is forwarded by proxies.
+pub fn quota_exceeded_status(msg: String, retry_after: Duration) -> tonic::Status {
+    use prost::Message;
+    use sekas_api::server::v1;
+
+    let details = v1::Error::quota_exceeded(msg.clone(), retry_after.as_millis() as u64);
+    tonic::Status::with_details(tonic::Code::ResourceExhausted, msg, details.encode_to_vec().into())
+}
+
 pub fn find_io_error(status: &tonic::Status) -> Option<&std::io::Error> {
     use tonic::Code;
     if status.code() == Code::Unavailable || status.code() == Code::Unknown {
@@ -222,6 +339,10 @@ pub fn retryable_io_err(err: &std::io::Error) -> bool {
     matches!(err.kind(), ErrorKind::ConnectionRefused)
 }
 
+/// The message of the `Unavailable` status replied by the nodes which are
+/// shutting down gracefully.
+pub const NODE_SHUTTING_DOWN: &str = "node is shutting down";
+
 pub fn retryable_rpc_err(status: &tonic::Status) -> bool {
     use tonic::Code;
     if status.code() == Code::Unavailable
@@ -229,6 +350,9 @@ pub fn retryable_rpc_err(status: &tonic::Status) -> bool {
     {
         // connection timeout.
         true
+    } else if status.code() == Code::Unavailable && status.message() == NODE_SHUTTING_DOWN {
+        // Retry the other replicas.
+        true
     } else {
         let mut cause = status.source();
         while let Some(err) = cause {
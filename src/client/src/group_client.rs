@@ -23,6 +23,7 @@ use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
 use sekas_schema::shard;
 use tonic::{Code, Status};
+use tracing::Instrument;
 
 use crate::metrics::*;
 use crate::rpc::{NodeClient, RequestBatchBuilder, RouterGroupState, RpcTimeout};
@@ -126,21 +127,37 @@ impl GroupClient {
         }
         self.next_access_index = 0;
 
-        let deadline = self.timeout.take().map(|duration| Instant::now() + duration);
+        let policy = self.client.retry_policy();
+        let max_attempts = policy.max_attempts;
+        let deadline =
+            self.timeout.take().or(policy.deadline).map(|duration| Instant::now() + duration);
         let mut index = 0;
+        let mut attempts = 0;
         let group_id = self.group_id;
         while let Some((node_id, client)) = self.recommend_client() {
+            if max_attempts.map(|max| attempts >= max).unwrap_or_default() {
+                break;
+            }
             trace!("group {group_id} issue rpc request with index {index} to node {node_id}");
             index += 1;
             let ctx = InvokeContext { group_id, epoch: self.epoch, node_id, timeout: self.timeout };
             match op(ctx, client).await {
-                Err(status) => self.apply_status(status, &opt)?,
+                // Following the new leader is a redirect rather than a retry, so it is not
+                // counted as an attempt.
+                Err(status) => {
+                    if !self.apply_status(status, &opt)? {
+                        attempts += 1;
+                    }
+                }
                 Ok(s) => return Ok(s),
             };
             if deadline.map(|v| v.elapsed() > Duration::ZERO).unwrap_or_default() {
                 return Err(Error::DeadlineExceeded("issue rpc".to_owned()));
             }
             GROUP_CLIENT_RETRY_TOTAL.inc();
+            if let Some(instrument) = self.client.instrument() {
+                instrument.on_retry(group_id);
+            }
         }
 
         trace!("group {group_id} issue rpc failed, group is not accessable");
@@ -225,7 +242,9 @@ impl GroupClient {
         None
     }
 
-    fn apply_status(&mut self, status: tonic::Status, opt: &InvokeOpt<'_>) -> Result<()> {
+    /// Apply the status of a failed rpc, return whether the request is
+    /// redirected to the new leader, or the error if it is not retryable.
+    fn apply_status(&mut self, status: tonic::Status, opt: &InvokeOpt<'_>) -> Result<bool> {
         match Error::from(status) {
             Error::GroupNotFound(_) => {
                 debug!(
@@ -234,15 +253,20 @@ impl GroupClient {
                     self.access_node_id.unwrap_or_default(),
                 );
                 self.access_node_id = None;
-                Ok(())
+                Ok(false)
             }
-            Error::NotLeader(_, term, leader_desc) => {
+            Error::NotLeader(_, term, leader_desc, group_desc) => {
                 trace!(
                     "group {} not leader, new leader {leader_desc:?}, term {term}",
                     self.group_id
                 );
-                self.apply_not_leader_status(term, leader_desc);
-                Ok(())
+                if let Some(group_desc) = group_desc {
+                    self.client.router().apply_group_hint(group_desc);
+                }
+                if let Some(leader) = leader_desc.clone() {
+                    self.client.router().apply_leader_hint(self.group_id, leader, term);
+                }
+                Ok(self.apply_not_leader_status(term, leader_desc))
             }
             Error::Connect(status) => {
                 debug!(
@@ -252,7 +276,7 @@ impl GroupClient {
                     status.to_string(),
                 );
                 self.access_node_id = None;
-                Ok(())
+                Ok(false)
             }
             Error::Transport(status)
                 if opt.ignore_transport_error
@@ -265,11 +289,18 @@ impl GroupClient {
                     status.to_string(),
                 );
                 self.access_node_id = None;
-                Ok(())
+                Ok(false)
+            }
+            Error::EpochNotMatch(group_desc) => {
+                self.client.router().apply_group_hint(group_desc.clone());
+                self.apply_epoch_not_match_status(group_desc, opt)?;
+                Ok(false)
             }
-            Error::EpochNotMatch(group_desc) => self.apply_epoch_not_match_status(group_desc, opt),
             e => {
-                if !matches!(e, Error::CasFailed(_, _, _)) {
+                if !matches!(
+                    e,
+                    Error::CasFailed(_, _, _) | Error::CollectionFrozen(_) | Error::GroupBusy(..)
+                ) {
                     warn!(
                         "group {} issue rpc to {}: epoch {} with unknown error {e:?}",
                         self.group_id,
@@ -282,7 +313,9 @@ impl GroupClient {
         }
     }
 
-    fn apply_not_leader_status(&mut self, term: u64, leader_desc: Option<ReplicaDesc>) {
+    /// Apply the `NotLeader` status, return whether the request is redirected
+    /// to the new leader.
+    fn apply_not_leader_status(&mut self, term: u64, leader_desc: Option<ReplicaDesc>) -> bool {
         debug!(
             "group {} issue rpc to {}: not leader, new leader {:?} term {term}, local state {:?}",
             self.group_id,
@@ -301,8 +334,10 @@ impl GroupClient {
                 // group descriptor is used). In order to ensure that the leader can be retried
                 // later, the leader needs to be saved to the replicas.
                 move_replica_to_first_element(&mut self.replicas, leader);
+                return true;
             }
         }
+        false
     }
 
     fn apply_epoch_not_match_status(
@@ -344,7 +379,43 @@ impl GroupClient {
 }
 
 impl GroupClient {
+    /// Issue an idempotent read request. If it isn't finished after `delay`, a
+    /// hedged request is issued with the latest routing, and the first
+    /// successful response is returned.
+    pub async fn hedged_request(&mut self, request: &Request, delay: Duration) -> Result<Response> {
+        debug_assert!(is_read_only_request(request));
+        let mut hedged_client = self.clone();
+        let first = self.request(request);
+        tokio::pin!(first);
+        tokio::select! {
+            resp = &mut first => return resp,
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        GROUP_CLIENT_HEDGED_REQUEST_TOTAL.inc();
+        // The leader might be changed or the connection is stalled, so the hedged
+        // request starts from the latest routing.
+        if let Ok(group_state) = hedged_client.client.router().find_group(hedged_client.group_id) {
+            hedged_client.apply_group_state(group_state);
+        }
+        hedged_client.access_node_id = None;
+        let second = hedged_client.request(request);
+        tokio::pin!(second);
+        tokio::select! {
+            resp = &mut first => match resp {
+                Ok(resp) => Ok(resp),
+                Err(_) => second.await,
+            },
+            resp = &mut second => match resp {
+                Ok(resp) => Ok(resp),
+                Err(_) => first.await,
+            },
+        }
+    }
+
     pub async fn request(&mut self, request: &Request) -> Result<Response> {
+        let app_tag = self.client.app_tag().to_owned();
+        let end_user = self.client.end_user().to_owned();
         let op = |ctx: InvokeContext, client: NodeClient| {
             let latency = take_group_request_metrics(request);
             let req = BatchRequest {
@@ -354,6 +425,8 @@ impl GroupClient {
                     epoch: ctx.epoch,
                     request: Some(GroupRequestUnion { request: Some(request.clone()) }),
                 }],
+                app_tag: app_tag.clone(),
+                end_user: end_user.clone(),
             };
             async move {
                 record_latency_opt!(latency);
@@ -370,7 +443,8 @@ impl GroupClient {
             accurate_epoch: false,
             ignore_transport_error: false,
         };
-        self.invoke_with_opt(op, opt).await
+        let span = request_span(self.group_id, request);
+        self.invoke_with_opt(op, opt).instrument(span).await
     }
 
     fn batch_response<T>(mut resps: Vec<T>) -> Result<T, Status> {
@@ -418,6 +492,78 @@ impl GroupClient {
         self.invoke(op).await
     }
 
+    /// Drop the data of the shard and replace it with `new_shard`, which covers
+    /// the same range of the shard.
+    pub async fn truncate_shard(&mut self, shard_id: u64, new_shard: &ShardDesc) -> Result<()> {
+        let op = |ctx: InvokeContext, client: NodeClient| {
+            let new_shard = new_shard.to_owned();
+            let req = RequestBatchBuilder::new(ctx.node_id)
+                .truncate_shard(ctx.group_id, ctx.epoch, shard_id, new_shard)
+                .build();
+            async move {
+                let resp = client
+                    .batch_group_requests(req)
+                    .await
+                    .and_then(Self::batch_response)
+                    .and_then(Self::group_response)?;
+                match resp {
+                    Response::TruncateShard(_) => Ok(()),
+                    _ => Err(Status::internal("invalid response type, TruncateShard is required")),
+                }
+            }
+        };
+        self.invoke(op).await
+    }
+
+    /// Mark the shard as read-only or writable.
+    pub async fn freeze_shard(&mut self, shard_id: u64, frozen: bool) -> Result<()> {
+        let op = |ctx: InvokeContext, client: NodeClient| {
+            let req = RequestBatchBuilder::new(ctx.node_id)
+                .freeze_shard(ctx.group_id, ctx.epoch, shard_id, frozen)
+                .build();
+            async move {
+                let resp = client
+                    .batch_group_requests(req)
+                    .await
+                    .and_then(Self::batch_response)
+                    .and_then(Self::group_response)?;
+                match resp {
+                    Response::FreezeShard(_) => Ok(()),
+                    _ => Err(Status::internal("invalid response type, FreezeShard is required")),
+                }
+            }
+        };
+        self.invoke(op).await
+    }
+
+    /// Split the shard at `split_key`, the new shard covers the range from
+    /// `split_key` to the end of the shard. It is a no-op if the new shard
+    /// already exists.
+    pub async fn split_shard(
+        &mut self,
+        shard_id: u64,
+        split_key: &[u8],
+        new_shard_id: u64,
+    ) -> Result<()> {
+        let op = |ctx: InvokeContext, client: NodeClient| {
+            let req = RequestBatchBuilder::new(ctx.node_id)
+                .split_shard(ctx.group_id, ctx.epoch, shard_id, split_key.to_owned(), new_shard_id)
+                .build();
+            async move {
+                let resp = client
+                    .batch_group_requests(req)
+                    .await
+                    .and_then(Self::batch_response)
+                    .and_then(Self::group_response)?;
+                match resp {
+                    Response::SplitShard(_) => Ok(()),
+                    _ => Err(Status::internal("invalid response type, SplitShard is required")),
+                }
+            }
+        };
+        self.invoke(op).await
+    }
+
     pub async fn transfer_leader(&mut self, dest_replica: u64) -> Result<()> {
         let op = |ctx: InvokeContext, client: NodeClient| {
             let dest_replica = dest_replica.to_owned();
@@ -552,7 +698,7 @@ impl GroupClient {
 // Moving shard related functions, which will be retried at:
 // `sekas-client::migrate_client::MigrateClient`.
 impl GroupClient {
-    pub async fn acquire_shard(&mut self, desc: &MoveShardDesc) -> Result<()> {
+    pub async fn acquire_shard(&mut self, desc: &MoveShardDesc) -> Result<AcquireShardResponse> {
         let op = |_: InvokeContext, client: NodeClient| async move {
             client.acquire_shard(desc.clone()).await
         };
@@ -569,6 +715,25 @@ impl GroupClient {
         self.invoke_with_opt(op, opt).await
     }
 
+    pub async fn pull_shard(
+        &mut self,
+        shard_id: u64,
+        last_key: Vec<u8>,
+        chunk_bytes: u64,
+    ) -> Result<tonic::Streaming<PullShardChunk>> {
+        let op = |ctx: InvokeContext, client: NodeClient| {
+            let req = PullShardRequest {
+                group_id: ctx.group_id,
+                shard_id,
+                last_key: last_key.clone(),
+                chunk_bytes,
+            };
+            async move { client.pull_shard(req).await }
+        };
+        let opt = InvokeOpt { ignore_transport_error: true, ..Default::default() };
+        self.invoke_with_opt(op, opt).await
+    }
+
     pub async fn forward(&mut self, req: &ForwardRequest) -> Result<ForwardResponse> {
         let op = |_: InvokeContext, client: NodeClient| {
             let cloned_req = req.clone();
@@ -579,6 +744,88 @@ impl GroupClient {
     }
 }
 
+// Bulk load related functions.
+impl GroupClient {
+    /// Upload the ingest files to all replicas of the group, and then ingest
+    /// them into the shard. The content of each file should be built by
+    /// [`sekas_api::IngestFileBuilder`]. The files are staged for the shard,
+    /// uploading them requires the write permission of its database.
+    ///
+    /// The leader confirms that all replicas hold the files before ingesting,
+    /// so `InvalidArgument` is returned if the replicas are changed during
+    /// uploading, and the ingestion could be retried.
+    pub async fn ingest(&mut self, shard_id: u64, files: Vec<(String, Vec<u8>)>) -> Result<()> {
+        if self.epoch == 0 {
+            self.initial_group_state()?;
+        } else if let Ok(group_state) = self.client.router().find_group(self.group_id) {
+            // Upload the files to the latest replicas known.
+            if group_state.epoch > self.epoch {
+                self.apply_group_state(group_state);
+            }
+        }
+
+        let node_ids = self.replicas.iter().map(|r| r.node_id).collect::<Vec<_>>();
+        let mut ingest_files = Vec::with_capacity(files.len());
+        for (name, content) in files {
+            for &node_id in &node_ids {
+                let client =
+                    self.fetch_client(node_id).ok_or(Error::GroupNotAccessable(self.group_id))?;
+                upload_ingest_file(&client, self.group_id, shard_id, &name, &content).await?;
+            }
+            ingest_files.push(IngestFile::fingerprint(name, &content));
+        }
+
+        let version = self.client.root_client().alloc_txn_id(1, self.timeout).await?;
+        let req = Request::Ingest(ShardIngestRequest { shard_id, files: ingest_files, version });
+        match self.request(&req).await? {
+            Response::Ingest(_) => Ok(()),
+            _ => Err(Error::Internal("invalid response type, Ingest is required".into())),
+        }
+    }
+}
+
+async fn upload_ingest_file(
+    client: &NodeClient,
+    group_id: u64,
+    shard_id: u64,
+    name: &str,
+    content: &[u8],
+) -> Result<()> {
+    const CHUNK_SIZE: usize = 1 << 20;
+
+    let mut offset = 0;
+    loop {
+        let chunk = &content[offset..std::cmp::min(offset + CHUNK_SIZE, content.len())];
+        let size = client
+            .upload_ingest_file(group_id, shard_id, name.to_owned(), offset as u64, chunk.to_vec())
+            .await?;
+        offset += chunk.len();
+        debug_assert_eq!(size, offset as u64);
+        if offset >= content.len() {
+            return Ok(());
+        }
+    }
+}
+
+/// Build the span of a group request, its context is propagated to the servers
+/// along with the request.
+fn request_span(group_id: u64, request: &Request) -> tracing::Span {
+    let span = tracing::info_span!(
+        "group_client.request",
+        group_id,
+        request = request.name(),
+        shard_id = tracing::field::Empty,
+        txn = tracing::field::Empty,
+    );
+    if let Some(shard_id) = request.shard_id() {
+        span.record("shard_id", shard_id);
+    }
+    if let Some(start_version) = request.txn_version() {
+        span.record("txn", start_version);
+    }
+    span
+}
+
 #[inline]
 fn is_read_only_request(request: &Request) -> bool {
     matches!(request, Request::Get(_) | Request::Scan(_))
@@ -606,6 +853,7 @@ fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
         Request::ClearIntent(req) => {
             is_target_shard_exists(descriptor, req.shard_id, &req.user_key)
         }
+        Request::Ingest(req) => descriptor.shards.iter().any(|s| s.id == req.shard_id),
         _ => false,
     }
 }
@@ -0,0 +1,58 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+/// The operations issued by applications via `Database`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Get,
+    Put,
+    Delete,
+    Scan,
+    WriteBatch,
+}
+
+/// The hooks to observe the client, so that applications could export the
+/// client metrics to their own registry. All hooks are no-op by default, and
+/// they are invoked in the request path, so the implementation should be
+/// cheap.
+pub trait ClientInstrument: Send + Sync {
+    /// An operation is finished.
+    fn on_operation(&self, _op: Operation, _latency: Duration, _succeed: bool) {}
+
+    /// A group request is retried, eg. the target replica is not the leader.
+    fn on_retry(&self, _group_id: u64) {}
+
+    /// The bytes of keys and values sent to and received from the servers by
+    /// an operation.
+    fn on_bytes(&self, _op: Operation, _sent: u64, _received: u64) {}
+
+    /// The router is looked up, `hit` is false if the route is not found in
+    /// the local cache.
+    fn on_router_lookup(&self, _hit: bool) {}
+}
+
+impl Operation {
+    /// Return the name of the operation, it could be used as metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Get => "get",
+            Operation::Put => "put",
+            Operation::Delete => "delete",
+            Operation::Scan => "scan",
+            Operation::WriteBatch => "write_batch",
+        }
+    }
+}
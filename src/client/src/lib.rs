@@ -16,28 +16,44 @@
 pub mod error;
 
 mod app_client;
+mod chunk;
+mod collection;
 mod database;
 mod discovery;
 mod group_client;
+mod instrument;
 mod metrics;
 mod move_shard_client;
 mod retry;
 mod rpc;
 mod shard_client;
+mod system;
 mod txn;
 mod write_batch;
+mod write_coalescer;
 
 pub use sekas_api::server::v1::CollectionDesc;
 use tonic::async_trait;
 
 pub use crate::app_client::{Client as SekasClient, ClientOptions};
+#[cfg(feature = "json")]
+pub use crate::collection::JsonCodec;
+pub use crate::collection::{
+    BytesCodec, Codec, Collection, CollectionOptions, StringCodec, U64Codec,
+};
 pub use crate::database::Database;
 pub use crate::discovery::{ServiceDiscovery, StaticServiceDiscovery};
 pub use crate::error::{AppError, AppResult, Error, Result};
 pub use crate::group_client::GroupClient;
+pub use crate::instrument::{ClientInstrument, Operation};
 pub use crate::move_shard_client::MoveShardClient;
-pub use crate::retry::RetryState;
-pub use crate::rpc::{ConnManager, NodeClient, RootClient, Router, RouterGroupState};
+pub use crate::retry::{RetryPolicy, RetryState, RetryableError};
+pub use crate::rpc::{
+    AuthChannel, ConnManager, ConnPoolOptions, NodeClient, RootClient, Router, RouterGroupState,
+    TlsOptions, TokenInterceptor, AUTHORIZATION_KEY,
+};
 pub use crate::shard_client::ShardClient;
+pub use crate::system::SystemCollections;
 pub use crate::txn::TxnStateTable;
 pub use crate::write_batch::{WriteBatchRequest, WriteBatchResponse, WriteBuilder};
+pub use crate::write_coalescer::{WriteCoalescer, WriteCoalescerOptions, WriteFuture};
@@ -31,8 +31,12 @@ make_static_metric! {
             transfer,
             accept_shard,
             create_shard,
+            truncate_shard,
+            freeze_shard,
+            split_shard,
             move_replicas,
             change_replicas,
+            ingest,
         }
     }
     pub struct GroupRequestDuration: Histogram {
@@ -48,8 +52,12 @@ make_static_metric! {
             transfer,
             accept_shard,
             create_shard,
+            truncate_shard,
+            freeze_shard,
+            split_shard,
             move_replicas,
             change_replicas,
+            ingest,
         }
     }
 }
@@ -74,9 +82,24 @@ lazy_static! {
         .unwrap();
     pub static ref GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS: GroupRequestDuration =
         GroupRequestDuration::from(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS_VEC);
+    pub static ref GROUP_CLIENT_HEDGED_REQUEST_TOTAL: IntCounter = register_int_counter!(
+        "group_client_hedged_request_total",
+        "The total hedged requests issued by group client"
+    )
+    .unwrap();
     pub static ref GROUP_CLIENT_RETRY_TOTAL: IntCounter =
         register_int_counter!("group_client_retry_total", "The total retries of group client",)
             .unwrap();
+    pub static ref CONN_MANAGER_HEALTH_PROBE_TOTAL: IntCounter = register_int_counter!(
+        "conn_manager_health_probe_total",
+        "The total health probes issued by conn manager"
+    )
+    .unwrap();
+    pub static ref CONN_MANAGER_EVICT_CONNECTION_TOTAL: IntCounter = register_int_counter!(
+        "conn_manager_evict_connection_total",
+        "The total broken connections evicted by conn manager"
+    )
+    .unwrap();
 }
 
 pub fn take_group_request_metrics(
@@ -117,6 +140,18 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.create_shard.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.create_shard)
         }
+        Request::TruncateShard(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.truncate_shard.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.truncate_shard)
+        }
+        Request::FreezeShard(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.freeze_shard.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.freeze_shard)
+        }
+        Request::SplitShard(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.split_shard.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.split_shard)
+        }
         Request::ChangeReplicas(_) => {
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.change_replicas.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.change_replicas)
@@ -129,6 +164,10 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.move_replicas.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.move_replicas)
         }
+        Request::Ingest(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.ingest.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.ingest)
+        }
     }
 }
 
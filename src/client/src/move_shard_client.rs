@@ -16,7 +16,6 @@
 use sekas_api::server::v1::*;
 
 use crate::group_client::GroupClient;
-use crate::retry::RetryState;
 use crate::shard_client::ShardClient;
 use crate::{Error, Result, SekasClient};
 
@@ -32,13 +31,13 @@ impl MoveShardClient {
         MoveShardClient { group_id, client }
     }
 
-    pub async fn acquire_shard(&mut self, desc: &MoveShardDesc) -> Result<()> {
-        let mut retry_state = RetryState::new(None);
+    pub async fn acquire_shard(&mut self, desc: &MoveShardDesc) -> Result<AcquireShardResponse> {
+        let mut retry_state = self.client.retry_state(None);
 
         loop {
             let mut client = self.group_client();
             match client.acquire_shard(desc).await {
-                Ok(()) => return Ok(()),
+                Ok(resp) => return Ok(resp),
                 e @ Err(Error::EpochNotMatch(..)) => return e,
                 Err(err) => {
                     retry_state.retry(err).await?;
@@ -48,7 +47,7 @@ impl MoveShardClient {
     }
 
     pub async fn move_out(&mut self, desc: &MoveShardDesc) -> Result<()> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = self.client.retry_state(None);
 
         loop {
             let mut client = self.group_client();
@@ -66,7 +65,7 @@ impl MoveShardClient {
         shard_id: u64,
         last_key: Option<Vec<u8>>,
     ) -> Result<Vec<ValueSet>> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = self.client.retry_state(None);
 
         loop {
             let client = ShardClient::new(self.group_id, shard_id, self.client.clone());
@@ -79,8 +78,30 @@ impl MoveShardClient {
         }
     }
 
+    /// Open a stream to pull the data of the shard from the group leader,
+    /// starting after `last_key`.
+    pub async fn pull_shard_stream(
+        &self,
+        shard_id: u64,
+        last_key: Option<Vec<u8>>,
+        chunk_bytes: u64,
+    ) -> Result<tonic::Streaming<PullShardChunk>> {
+        let mut retry_state = self.client.retry_state(None);
+
+        loop {
+            let mut client = self.group_client();
+            let last_key = last_key.clone().unwrap_or_default();
+            match client.pull_shard(shard_id, last_key, chunk_bytes).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
     pub async fn forward(&mut self, req: &ForwardRequest) -> Result<ForwardResponse> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = self.client.retry_state(None);
 
         loop {
             let mut client = self.group_client();
@@ -17,9 +17,79 @@ use std::time::{Duration, Instant};
 
 use crate::{Error, Result};
 
+/// The classes of errors which could be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryableError {
+    /// The route is stale, eg. `NotFound`, `EpochNotMatch` and
+    /// `GroupNotAccessable`.
+    Routing,
+    /// The group is busy, the backoff hint of the server is respected.
+    Busy,
+    /// The server runs out of resources, eg. the rate limit is exceeded.
+    ResourceExhausted,
+    /// The connection is broken. It is only safe for the idempotent
+    /// operations.
+    Transport,
+}
+
+/// The policy to retry the failed operations of a client.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The max attempts of an operation, including the first one. The
+    /// redirects to the new leaders are not counted. It is only bounded by the
+    /// deadline if it is `None`.
+    pub max_attempts: Option<usize>,
+
+    /// The first backoff interval, it is doubled after each retry.
+    pub initial_backoff: Duration,
+
+    /// The max backoff interval.
+    pub max_backoff: Duration,
+
+    /// The errors to retry.
+    pub retryable_errors: Vec<RetryableError>,
+
+    /// The deadline of an operation including all retries, it takes effect if
+    /// the operation doesn't specify a timeout.
+    pub deadline: Option<Duration>,
+
+    /// Issue a hedged request if an idempotent get isn't finished after the
+    /// delay, and return the first response. It is disabled if it is `None`.
+    pub hedged_read_delay: Option<Duration>,
+}
+
 pub struct RetryState {
     interval_ms: u64,
     deadline: Option<Instant>,
+    attempts: usize,
+    policy: RetryPolicy,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: None,
+            initial_backoff: Duration::from_millis(8),
+            max_backoff: Duration::from_millis(250),
+            retryable_errors: vec![RetryableError::Routing, RetryableError::Busy],
+            deadline: None,
+            hedged_read_delay: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Return whether the error class should be retried.
+    #[inline]
+    pub fn is_retryable(&self, err: RetryableError) -> bool {
+        self.retryable_errors.contains(&err)
+    }
+
+    /// Return the next backoff interval.
+    #[inline]
+    pub fn next_backoff(&self, interval: Duration) -> Duration {
+        std::cmp::min(interval * 2, self.max_backoff)
+    }
 }
 
 impl Default for RetryState {
@@ -30,7 +100,19 @@ impl Default for RetryState {
 
 impl RetryState {
     pub fn new(timeout: Option<Duration>) -> Self {
-        RetryState { interval_ms: 8, deadline: timeout.and_then(|d| Instant::now().checked_add(d)) }
+        RetryState::with_policy(RetryPolicy::default(), timeout)
+    }
+
+    /// Create a retry state with the policy, the `timeout` takes precedence
+    /// over the deadline of the policy.
+    pub fn with_policy(policy: RetryPolicy, timeout: Option<Duration>) -> Self {
+        let deadline = timeout.or(policy.deadline).and_then(|d| Instant::now().checked_add(d));
+        RetryState {
+            interval_ms: policy.initial_backoff.as_millis() as u64,
+            deadline,
+            attempts: 1,
+            policy,
+        }
     }
 
     #[inline]
@@ -39,8 +121,16 @@ impl RetryState {
     }
 
     pub fn is_retryable(&self, err: &Error) -> bool {
-        match err {
-            Error::NotFound(_) | Error::EpochNotMatch(_) | Error::GroupNotAccessable(_) => true,
+        let class = match err {
+            Error::NotFound(_) | Error::EpochNotMatch(_) | Error::GroupNotAccessable(_) => {
+                RetryableError::Routing
+            }
+            Error::GroupBusy(..) => RetryableError::Busy,
+            Error::ResourceExhausted(_) => RetryableError::ResourceExhausted,
+            // The request is never admitted.
+            Error::QuotaExceeded(_, retry_after) if retry_after.is_zero() => return false,
+            Error::QuotaExceeded(..) => RetryableError::ResourceExhausted,
+            Error::Transport(_) => RetryableError::Transport,
             Error::NotLeader(..)
             | Error::GroupNotFound(_)
             | Error::NotRootLeader(..)
@@ -49,20 +139,31 @@ impl RetryState {
             }
             Error::InvalidArgument(_)
             | Error::DeadlineExceeded(_)
-            | Error::ResourceExhausted(_)
             | Error::AlreadyExists(_)
             | Error::CasFailed(_, _, _)
+            | Error::CollectionFrozen(_)
+            | Error::NodeIncarnationMismatch(..)
+            | Error::NodeNotFound(_)
+            | Error::NodeStatusNotMatch(..)
             | Error::Rpc(_)
-            | Error::Transport(_)
-            | Error::Internal(_) => false,
-        }
+            | Error::Internal(_) => return false,
+        };
+        self.policy.is_retryable(class)
     }
 
     pub async fn retry(&mut self, err: Error) -> Result<()> {
         if !self.is_retryable(&err) {
             return Err(err);
         }
+        if self.policy.max_attempts.map(|max| self.attempts >= max).unwrap_or_default() {
+            return Err(err);
+        }
 
+        if let Error::GroupBusy(_, retry_after) | Error::QuotaExceeded(_, retry_after) = err {
+            // Respect the backoff hint of the server.
+            let retry_after_ms = retry_after.as_millis() as u64;
+            self.interval_ms = std::cmp::max(self.interval_ms, retry_after_ms);
+        }
         self.force_retry().await
     }
 
@@ -76,7 +177,73 @@ impl RetryState {
             }
         }
         tokio::time::sleep(interval).await;
-        self.interval_ms = std::cmp::min(self.interval_ms * 2, 250);
+        self.attempts += 1;
+        self.interval_ms =
+            self.policy.next_backoff(Duration::from_millis(self.interval_ms)).as_millis() as u64;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_error_classes() {
+        let state = RetryState::new(None);
+        assert!(state.is_retryable(&Error::EpochNotMatch(Default::default())));
+        assert!(state.is_retryable(&Error::GroupBusy(1, Duration::ZERO)));
+        assert!(!state.is_retryable(&Error::ResourceExhausted("rate limit".into())));
+        assert!(!state.is_retryable(&Error::InvalidArgument("key".into())));
+
+        let policy = RetryPolicy {
+            retryable_errors: vec![RetryableError::ResourceExhausted],
+            ..Default::default()
+        };
+        let state = RetryState::with_policy(policy, None);
+        assert!(!state.is_retryable(&Error::GroupBusy(1, Duration::ZERO)));
+        assert!(state.is_retryable(&Error::ResourceExhausted("rate limit".into())));
+        assert!(state.is_retryable(&Error::QuotaExceeded("rate".into(), Duration::from_millis(1))));
+        assert!(!state.is_retryable(&Error::QuotaExceeded("size".into(), Duration::ZERO)));
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::default();
+        let mut interval = policy.initial_backoff;
+        for _ in 0..16 {
+            interval = policy.next_backoff(interval);
+        }
+        assert_eq!(interval, policy.max_backoff);
+    }
+
+    #[tokio::test]
+    async fn max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: Some(3),
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let mut state = RetryState::with_policy(policy, None);
+        assert!(state.retry(Error::GroupNotAccessable(1)).await.is_ok());
+        assert!(state.retry(Error::GroupNotAccessable(1)).await.is_ok());
+        assert!(matches!(
+            state.retry(Error::GroupNotAccessable(1)).await,
+            Err(Error::GroupNotAccessable(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn policy_deadline() {
+        let policy = RetryPolicy { deadline: Some(Duration::ZERO), ..Default::default() };
+        let mut state = RetryState::with_policy(policy.clone(), None);
+        assert!(matches!(
+            state.retry(Error::GroupNotAccessable(1)).await,
+            Err(Error::DeadlineExceeded(_))
+        ));
+
+        // The timeout of the operation takes precedence.
+        let mut state = RetryState::with_policy(policy, Some(Duration::from_secs(10)));
+        assert!(state.retry(Error::GroupNotAccessable(1)).await.is_ok());
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+
+use crate::{Error, Result};
+
+/// The metadata key carrying the token of requests.
+pub const AUTHORIZATION_KEY: &str = "authorization";
+
+/// The channel attaching the token to requests.
+pub type AuthChannel = InterceptedService<Channel, TokenInterceptor>;
+
+/// Attach the token to the outgoing requests, in the form of `Bearer <token>`.
+#[derive(Debug, Clone, Default)]
+pub struct TokenInterceptor {
+    value: Option<MetadataValue<Ascii>>,
+}
+
+impl TokenInterceptor {
+    pub fn new(token: Option<&str>) -> Result<Self> {
+        let value = token
+            .map(|token| {
+                format!("Bearer {token}").parse::<MetadataValue<Ascii>>().map_err(|_| {
+                    Error::InvalidArgument("the token contains invalid characters".to_owned())
+                })
+            })
+            .transpose()?;
+        Ok(TokenInterceptor { value })
+    }
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(value) = &self.value {
+            request.metadata_mut().insert(AUTHORIZATION_KEY, value.clone());
+        }
+        Ok(request)
+    }
+}
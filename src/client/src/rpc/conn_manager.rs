@@ -16,27 +16,67 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use log::{debug, info, warn};
 use sekas_api::server::v1::root_client::RootClient;
 use tonic::transport::{Channel, Endpoint};
+use tonic::Code;
 
-use super::NodeClient;
+use super::tls::TlsState;
+use super::{AuthChannel, NodeClient, TlsOptions, TokenInterceptor};
+use crate::error::{retryable_rpc_err, transport_err};
+use crate::metrics::*;
 use crate::{Error, Result};
 
+/// The options of the connection pools of `ConnManager`.
+#[derive(Debug, Clone)]
+pub struct ConnPoolOptions {
+    /// The max number of connections to an endpoint, the requests are
+    /// dispatched to the connections in round robin.
+    ///
+    /// Default: 1.
+    pub max_connections: usize,
+
+    /// The interval of probing the health of connections, the broken
+    /// connections are evicted and re-established on demand. The health
+    /// checking is disabled if it is `None`.
+    ///
+    /// Default: None.
+    pub health_check_interval: Option<Duration>,
+
+    /// The timeout of a health probe, the connection is considered broken if
+    /// the probe is not finished after the duration.
+    ///
+    /// Default: 1s.
+    pub health_check_timeout: Duration,
+}
+
 #[derive(Clone, Debug)]
 pub struct ConnManager {
     connect_timeout: Option<Duration>,
+    opts: ConnPoolOptions,
+    tls: Option<Arc<TlsState>>,
+    interceptor: TokenInterceptor,
     core: Arc<Mutex<Core>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct Core {
-    channels: HashMap<String, ChannelInfo>,
+    next_channel_id: u64,
+    pools: HashMap<String, ConnPool>,
+}
+
+/// The connections to an endpoint.
+#[derive(Debug, Default)]
+struct ConnPool {
+    channels: Vec<ChannelInfo>,
+    next_index: usize,
+    access: usize,
 }
 
 #[derive(Debug)]
 struct ChannelInfo {
+    id: u64,
     channel: Channel,
-    access: usize,
 }
 
 impl ConnManager {
@@ -50,63 +90,155 @@ impl ConnManager {
         mgr
     }
 
+    pub fn with_options(
+        connect_timeout: Option<Duration>,
+        opts: ConnPoolOptions,
+        tls: Option<TlsOptions>,
+        interceptor: TokenInterceptor,
+    ) -> Self {
+        let core = Arc::<Mutex<Core>>::default();
+        let cloned_core = core.clone();
+
+        // FIXME
+        // 1. graceful shutdown
+        // 2. spawn in executor.
+        tokio::spawn(async move {
+            recycle_conn_main(cloned_core).await;
+        });
+        if let Some(interval) = opts.health_check_interval {
+            let cloned_core = core.clone();
+            let timeout = opts.health_check_timeout;
+            tokio::spawn(async move {
+                health_check_main(cloned_core, interval, timeout).await;
+            });
+        }
+        let tls = tls.map(|tls| Arc::new(TlsState::new(tls)));
+        if let Some(interval) = tls.as_ref().and_then(|tls| tls.reload_interval()) {
+            let cloned_core = core.clone();
+            let cloned_tls = tls.clone().unwrap();
+            tokio::spawn(async move {
+                tls_reload_main(cloned_core, cloned_tls, interval).await;
+            });
+        }
+        ConnManager { connect_timeout, opts, tls, interceptor, core }
+    }
+
     // TODO(walter) add tags
     pub fn get(&self, addr: String) -> Result<Channel> {
         let mut core = self.core.lock().unwrap();
-        if let Some(info) = core.channels.get_mut(&addr) {
-            info.access += 1;
-            return Ok(info.channel.clone());
+        let Core { next_channel_id, pools } = &mut *core;
+        let pool = pools.entry(addr.clone()).or_default();
+        pool.access += 1;
+        if pool.channels.len() < self.opts.max_connections.max(1) {
+            let channel = connect_lazy(&addr, self.connect_timeout, self.tls.as_deref())?;
+            let id = *next_channel_id;
+            *next_channel_id += 1;
+            pool.channels.push(ChannelInfo { id, channel: channel.clone() });
+            return Ok(channel);
         }
 
-        let channel = match Endpoint::new(format!("http://{}", addr)) {
-            Ok(endpoint) => {
-                if let Some(connect_timeout) = self.connect_timeout {
-                    endpoint.connect_timeout(connect_timeout).connect_lazy()
-                } else {
-                    endpoint.connect_lazy()
-                }
-            }
-            Err(e) => return Err(Error::Internal(Box::new(e))),
-        };
-        let info = ChannelInfo { channel: channel.clone(), access: 1 };
-        core.channels.insert(addr, info);
-        Ok(channel)
+        let index = pool.next_index % pool.channels.len();
+        pool.next_index = index + 1;
+        Ok(pool.channels[index].channel.clone())
+    }
+
+    /// Establish a dedicated connection which is not shared with the pooled
+    /// ones, eg. for the long-lived streams.
+    pub async fn connect(&self, addr: &str) -> Result<Channel> {
+        let endpoint = build_endpoint(addr, self.connect_timeout, self.tls.as_deref())?;
+        endpoint
+            .connect()
+            .await
+            .map_err(|e| Error::Connect(tonic::Status::unavailable(e.to_string())))
     }
 
     #[inline]
     pub fn get_node_client(&self, addr: String) -> Result<NodeClient> {
         let channel = self.get(addr)?;
-        Ok(NodeClient::new(channel))
+        Ok(NodeClient::with_interceptor(channel, self.interceptor.clone()))
+    }
+
+    /// The interceptor attaching the token to the requests.
+    #[inline]
+    pub fn interceptor(&self) -> &TokenInterceptor {
+        &self.interceptor
     }
 
     #[inline]
-    pub fn get_root_client(&self, addr: String) -> Result<RootClient<Channel>> {
+    pub fn get_root_client(&self, addr: String) -> Result<RootClient<AuthChannel>> {
         let channel = self.get(addr)?;
-        Ok(RootClient::new(channel))
+        Ok(RootClient::with_interceptor(channel, self.interceptor.clone()))
+    }
+}
+
+impl Default for ConnPoolOptions {
+    fn default() -> Self {
+        ConnPoolOptions {
+            max_connections: 1,
+            health_check_interval: None,
+            health_check_timeout: Duration::from_secs(1),
+        }
     }
 }
 
 impl Default for ConnManager {
     fn default() -> Self {
-        let core = Arc::new(Mutex::new(Core { channels: HashMap::default() }));
-        let cloned_core = core.clone();
+        ConnManager::with_options(
+            None,
+            ConnPoolOptions::default(),
+            None,
+            TokenInterceptor::default(),
+        )
+    }
+}
 
-        // FIXME
-        // 1. graceful shutdown
-        // 2. spawn in executor.
-        tokio::spawn(async move {
-            recycle_conn_main(cloned_core).await;
-        });
-        ConnManager { core, connect_timeout: None }
+impl Core {
+    /// Evict the specified channel from the pool, return whether it is exists.
+    fn evict(&mut self, addr: &str, channel_id: u64) -> bool {
+        let Some(pool) = self.pools.get_mut(addr) else {
+            return false;
+        };
+        let num_channels = pool.channels.len();
+        pool.channels.retain(|info| info.id != channel_id);
+        num_channels != pool.channels.len()
     }
 }
 
+fn connect_lazy(
+    addr: &str,
+    connect_timeout: Option<Duration>,
+    tls: Option<&TlsState>,
+) -> Result<Channel> {
+    Ok(build_endpoint(addr, connect_timeout, tls)?.connect_lazy())
+}
+
+fn build_endpoint(
+    addr: &str,
+    connect_timeout: Option<Duration>,
+    tls: Option<&TlsState>,
+) -> Result<Endpoint> {
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let mut endpoint = match Endpoint::new(format!("{}://{}", scheme, addr)) {
+        Ok(endpoint) => endpoint,
+        Err(e) => return Err(Error::Internal(Box::new(e))),
+    };
+    if let Some(tls) = tls {
+        endpoint = endpoint
+            .tls_config(tls.client_config()?)
+            .map_err(|e| Error::InvalidArgument(format!("tls config: {e}")))?;
+    }
+    if let Some(connect_timeout) = connect_timeout {
+        endpoint = endpoint.connect_timeout(connect_timeout);
+    }
+    Ok(endpoint)
+}
+
 async fn recycle_conn_main(core: Arc<Mutex<Core>>) {
     let mut interval = tokio::time::interval(Duration::from_secs(60));
     loop {
         interval.tick().await;
         let mut core = core.lock().unwrap();
-        core.channels.retain(|_, v| {
+        core.pools.retain(|_, v| {
             if v.access == 0 {
                 false
             } else {
@@ -116,3 +248,122 @@ async fn recycle_conn_main(core: Arc<Mutex<Core>>) {
         });
     }
 }
+
+/// Reload the certificates once the files are changed, the connections are
+/// re-established on demand with the new certificates.
+async fn tls_reload_main(core: Arc<Mutex<Core>>, tls: Arc<TlsState>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        match tls.reload_if_changed() {
+            Ok(true) => {
+                info!("tls certificates are reloaded, reset all connections");
+                core.lock().unwrap().pools.clear();
+            }
+            Ok(false) => {}
+            Err(err) => warn!("reload tls certificates: {err}"),
+        }
+    }
+}
+
+async fn health_check_main(core: Arc<Mutex<Core>>, interval: Duration, timeout: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        health_check(&core, timeout).await;
+    }
+}
+
+/// Probe all connections, and evict the broken ones.
+async fn health_check(core: &Mutex<Core>, timeout: Duration) {
+    let channels = {
+        let core = core.lock().unwrap();
+        core.pools
+            .iter()
+            .flat_map(|(addr, pool)| {
+                pool.channels.iter().map(|info| (addr.clone(), info.id, info.channel.clone()))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let probes = channels.into_iter().map(|(addr, id, channel)| async move {
+        let healthy = probe(channel, timeout).await;
+        (addr, id, healthy)
+    });
+    for (addr, id, healthy) in futures::future::join_all(probes).await {
+        if healthy {
+            continue;
+        }
+        let mut core = core.lock().unwrap();
+        if core.evict(&addr, id) {
+            warn!("evict broken connection {id} to {addr}");
+            CONN_MANAGER_EVICT_CONNECTION_TOTAL.inc();
+        }
+    }
+}
+
+async fn probe(channel: Channel, timeout: Duration) -> bool {
+    CONN_MANAGER_HEALTH_PROBE_TOTAL.inc();
+    let client = NodeClient::new(channel);
+    match tokio::time::timeout(timeout, client.get_root()).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(status)) => {
+            // Only the errors raised by the connection are considered, the node still
+            // serves the connection if it reply an error.
+            let broken = status.code() == Code::Unavailable
+                || retryable_rpc_err(&status)
+                || transport_err(&status);
+            if broken {
+                debug!("health probe failed: {status}");
+            }
+            !broken
+        }
+        Err(_) => {
+            debug!("health probe timeout after {timeout:?}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn round_robin_connections() {
+        let opts = ConnPoolOptions { max_connections: 2, ..Default::default() };
+        let mgr = ConnManager::with_options(None, opts, None, TokenInterceptor::default());
+        for _ in 0..4 {
+            mgr.get("127.0.0.1:1".to_owned()).unwrap();
+        }
+        let core = mgr.core.lock().unwrap();
+        let pool = core.pools.get("127.0.0.1:1").unwrap();
+        assert_eq!(pool.channels.len(), 2);
+        assert_eq!(pool.access, 4);
+    }
+
+    #[tokio::test]
+    async fn evict_broken_connections() {
+        let opts = ConnPoolOptions { max_connections: 2, ..Default::default() };
+        let mgr = ConnManager::with_options(
+            Some(Duration::from_millis(100)),
+            opts,
+            None,
+            TokenInterceptor::default(),
+        );
+        mgr.get("127.0.0.1:1".to_owned()).unwrap();
+        mgr.get("127.0.0.1:1".to_owned()).unwrap();
+
+        // Nobody listens on the port, so the probes are failed.
+        health_check(&mgr.core, Duration::from_millis(500)).await;
+        {
+            let core = mgr.core.lock().unwrap();
+            assert!(core.pools.get("127.0.0.1:1").unwrap().channels.is_empty());
+        }
+
+        // The evicted connections are re-established on demand.
+        mgr.get("127.0.0.1:1".to_owned()).unwrap();
+        let core = mgr.core.lock().unwrap();
+        assert_eq!(core.pools.get("127.0.0.1:1").unwrap().channels.len(), 1);
+    }
+}
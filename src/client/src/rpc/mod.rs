@@ -12,12 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod auth;
 mod conn_manager;
 mod node_client;
 mod root_client;
 mod router;
+mod tls;
 
-pub use self::conn_manager::ConnManager;
+pub use self::auth::{AuthChannel, TokenInterceptor, AUTHORIZATION_KEY};
+pub use self::conn_manager::{ConnManager, ConnPoolOptions};
 pub use self::node_client::{Client as NodeClient, RequestBatchBuilder, RpcTimeout};
 pub use self::root_client::Client as RootClient;
 pub use self::router::{Router, RouterGroupState};
+pub use self::tls::TlsOptions;
@@ -15,25 +15,33 @@
 
 use std::time::Duration;
 
+use opentelemetry::propagation::Injector;
 use prost::Message;
 use sekas_api::server::v1::*;
-use tonic::transport::Channel;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
 use tonic::IntoRequest;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::{AuthChannel, TokenInterceptor};
 
 #[derive(Debug, Clone)]
 pub struct Client {
-    client: node_client::NodeClient<Channel>,
+    client: node_client::NodeClient<AuthChannel>,
 }
 
 impl Client {
     pub fn new(channel: Channel) -> Self {
-        Client { client: node_client::NodeClient::new(channel) }
+        Client::with_interceptor(channel, TokenInterceptor::default())
+    }
+
+    pub fn with_interceptor(channel: Channel, interceptor: TokenInterceptor) -> Self {
+        Client { client: node_client::NodeClient::with_interceptor(channel, interceptor) }
     }
 
     pub async fn connect(addr: String) -> Result<Self, tonic::transport::Error> {
-        let addr = format!("http://{}", addr);
-        let client = node_client::NodeClient::connect(addr).await?;
-        Ok(Self { client })
+        let channel = Endpoint::new(format!("http://{}", addr))?.connect().await?;
+        Ok(Client::new(channel))
     }
 
     pub async fn get_root(&self) -> Result<RootDesc, tonic::Status> {
@@ -93,11 +101,86 @@ impl Client {
         }
     }
 
+    /// Upload a chunk of the ingest file of the shard, return the size of the
+    /// file uploaded so far.
+    pub async fn upload_ingest_file(
+        &self,
+        group_id: u64,
+        shard_id: u64,
+        name: String,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> Result<u64, tonic::Status> {
+        let mut client = self.client.clone();
+        let req = UploadIngestFileRequest { name, offset, data, group_id, shard_id };
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::UploadIngestFile(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::UploadIngestFile(resp)) => Ok(resp.size),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `UploadIngestFileResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// Verify that the ingest files of the shard are uploaded to the node, and
+    /// their fingerprints are matched. The files are sealed under the token,
+    /// so they are not changed by the later uploads.
+    pub async fn verify_ingest_files(
+        &self,
+        shard_id: u64,
+        token: String,
+        files: Vec<IngestFile>,
+    ) -> Result<(), tonic::Status> {
+        let mut client = self.client.clone();
+        let req = VerifyIngestFilesRequest { files, shard_id, token };
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::VerifyIngestFiles(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::VerifyIngestFiles(_)) => Ok(()),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `VerifyIngestFilesResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// Force the replica to be the only voter of the group, return the
+    /// descriptor of the recovered group.
+    pub async fn unsafe_recover_replica(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Result<GroupDesc, tonic::Status> {
+        let mut client = self.client.clone();
+        let req = UnsafeRecoverReplicaRequest { group_id, replica_id };
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::UnsafeRecoverReplica(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::UnsafeRecoverReplica(resp)) => resp
+                .group
+                .ok_or_else(|| tonic::Status::internal("the field `group` is empty".to_owned())),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `UnsafeRecoverReplicaResponse` is required".to_owned(),
+            )),
+        }
+    }
+
     pub async fn batch_group_requests(
         &self,
         req: impl IntoRequest<BatchRequest>,
     ) -> Result<Vec<GroupResponse>, tonic::Status> {
         let mut client = self.client.clone();
+        let mut req = req.into_request();
+        inject_trace_context(req.metadata_mut());
         let res = client.batch(req).await?;
         Ok(res.into_inner().responses)
     }
@@ -133,7 +216,10 @@ impl Client {
         }
     }
 
-    pub async fn acquire_shard(&self, desc: MoveShardDesc) -> Result<(), tonic::Status> {
+    pub async fn acquire_shard(
+        &self,
+        desc: MoveShardDesc,
+    ) -> Result<AcquireShardResponse, tonic::Status> {
         let mut client = self.client.clone();
         let resp = client
             .move_shard(MoveShardRequest {
@@ -143,7 +229,7 @@ impl Client {
             })
             .await?;
         match resp.into_inner().response {
-            Some(move_shard_response::Response::AcquireShard(_)) => Ok(()),
+            Some(move_shard_response::Response::AcquireShard(resp)) => Ok(resp),
             _ => Err(tonic::Status::internal(
                 "Invalid response type, `AcquireShardResponse` is required".to_owned(),
             )),
@@ -166,6 +252,17 @@ impl Client {
             )),
         }
     }
+
+    /// Pull the data of a shard as a stream of checksummed chunks, starting
+    /// after `last_key`.
+    pub async fn pull_shard(
+        &self,
+        req: PullShardRequest,
+    ) -> Result<tonic::Streaming<PullShardChunk>, tonic::Status> {
+        let mut client = self.client.clone();
+        let resp = client.pull_shard(req).await?;
+        Ok(resp.into_inner())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -192,6 +289,62 @@ impl RequestBatchBuilder {
         self
     }
 
+    pub fn truncate_shard(
+        mut self,
+        group_id: u64,
+        epoch: u64,
+        shard_id: u64,
+        new_shard: ShardDesc,
+    ) -> Self {
+        self.requests.push(GroupRequest {
+            group_id,
+            epoch,
+            request: Some(GroupRequestUnion {
+                request: Some(group_request_union::Request::TruncateShard(TruncateShardRequest {
+                    shard_id,
+                    new_shard: Some(new_shard),
+                })),
+            }),
+        });
+        self
+    }
+
+    pub fn freeze_shard(mut self, group_id: u64, epoch: u64, shard_id: u64, frozen: bool) -> Self {
+        self.requests.push(GroupRequest {
+            group_id,
+            epoch,
+            request: Some(GroupRequestUnion {
+                request: Some(group_request_union::Request::FreezeShard(FreezeShardRequest {
+                    shard_id,
+                    frozen,
+                })),
+            }),
+        });
+        self
+    }
+
+    pub fn split_shard(
+        mut self,
+        group_id: u64,
+        epoch: u64,
+        shard_id: u64,
+        split_key: Vec<u8>,
+        new_shard_id: u64,
+    ) -> Self {
+        self.requests.push(GroupRequest {
+            group_id,
+            epoch,
+            request: Some(GroupRequestUnion {
+                request: Some(group_request_union::Request::SplitShard(SplitShardRequest {
+                    shard_id,
+                    split_key,
+                    new_shard_id,
+                })),
+            }),
+        });
+        self
+    }
+
     pub fn add_replica(mut self, group_id: u64, epoch: u64, replica_id: u64, node_id: u64) -> Self {
         let change_replicas = ChangeReplicasRequest {
             change_replicas: Some(ChangeReplicas {
@@ -291,7 +444,7 @@ impl RequestBatchBuilder {
     }
 
     pub fn build(self) -> BatchRequest {
-        BatchRequest { node_id: self.node_id, requests: self.requests }
+        BatchRequest { node_id: self.node_id, requests: self.requests, ..Default::default() }
     }
 }
 
@@ -319,6 +472,27 @@ impl<T: Message> IntoRequest<T> for RpcTimeout<T> {
     }
 }
 
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl<'a> Injector for MetadataInjector<'a> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) =
+            (MetadataKey::from_bytes(key.as_bytes()), MetadataValue::try_from(value.as_str()))
+        {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+/// Propagate the trace context of the current span to the server, it is a
+/// no-op unless the application installs a global text map propagator.
+fn inject_trace_context(metadata: &mut MetadataMap) {
+    let cx = tracing::Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut MetadataInjector(metadata))
+    });
+}
+
 #[cfg(test)]
 mod timeout_error_tests {
     use std::net::SocketAddr;
@@ -401,6 +575,9 @@ mod transport_error_tests {
     #[allow(unused)]
     #[tonic::async_trait]
     impl node_server::Node for MockedServer {
+        type PullShardStream =
+            futures::stream::Empty<Result<sekas_api::server::v1::PullShardChunk, tonic::Status>>;
+
         async fn batch(
             &self,
             request: tonic::Request<sekas_api::server::v1::BatchRequest>,
@@ -423,6 +600,13 @@ mod transport_error_tests {
         {
             todo!()
         }
+
+        async fn pull_shard(
+            &self,
+            request: tonic::Request<sekas_api::server::v1::PullShardRequest>,
+        ) -> Result<tonic::Response<Self::PullShardStream>, tonic::Status> {
+            todo!()
+        }
     }
 
     #[tokio::test]
@@ -25,13 +25,12 @@ use sekas_api::server::v1::admin_response_union::Response;
 use sekas_api::server::v1::root_client::RootClient;
 use sekas_api::server::v1::*;
 use tokio::sync::Mutex;
-use tonic::transport::Channel;
 use tonic::{Code, Status, Streaming};
 
 use crate::discovery::ServiceDiscovery;
 use crate::error::retryable_rpc_err;
-use crate::rpc::{ConnManager, NodeClient};
-use crate::{Error as ClientError, Result};
+use crate::rpc::{AuthChannel, ConnManager, NodeClient};
+use crate::{CollectionOptions, Error as ClientError, Result, RetryPolicy};
 
 macro_rules! extract_admin_response {
     ($resp:expr, $cond:path) => {
@@ -69,6 +68,7 @@ struct ClientShared {
     #[derivative(Debug = "ignore")]
     discovery: Arc<dyn ServiceDiscovery>,
     conn_manager: ConnManager,
+    retry_policy: RetryPolicy,
     core: Mutex<ClientCore>,
 
     // Only one task is allowed to refresh root descriptor at a time.
@@ -86,10 +86,19 @@ struct ClientCore {
 
 impl Client {
     pub fn new(discovery: Arc<dyn ServiceDiscovery>, conn_manager: ConnManager) -> Self {
+        Client::with_retry_policy(discovery, conn_manager, RetryPolicy::default())
+    }
+
+    pub fn with_retry_policy(
+        discovery: Arc<dyn ServiceDiscovery>,
+        conn_manager: ConnManager,
+        retry_policy: RetryPolicy,
+    ) -> Self {
         Client {
             shared: Arc::new(ClientShared {
                 discovery,
                 conn_manager,
+                retry_policy,
                 core: Mutex::new(ClientCore { leader: None, term: 0, root: Arc::default() }),
                 refresh_descriptor_lock: Mutex::new(0),
             }),
@@ -116,27 +125,27 @@ impl Client {
         Ok(res.into_inner())
     }
 
-    pub async fn create_database(&self, name: String) -> Result<DatabaseDesc> {
-        let resp = self.admin(AdminRequestBuilder::create_database(name)).await?;
+    pub async fn create_database(&self, tenant: String, name: String) -> Result<DatabaseDesc> {
+        let resp = self.admin(AdminRequestBuilder::create_database(tenant, name)).await?;
         let resp = extract_admin_response!(resp.response, Response::CreateDatabase);
         resp.database
             .ok_or_else(|| ClientError::Internal("The database is not set".to_owned().into()))
     }
 
-    pub async fn delete_database(&self, name: String) -> Result<()> {
-        let resp = self.admin(AdminRequestBuilder::delete_database(name)).await?;
+    pub async fn delete_database(&self, tenant: String, name: String) -> Result<()> {
+        let resp = self.admin(AdminRequestBuilder::delete_database(tenant, name)).await?;
         extract_admin_response!(resp.response, Response::DeleteDatabase);
         Ok(())
     }
 
-    pub async fn list_database(&self) -> Result<Vec<DatabaseDesc>> {
-        let resp = self.admin(AdminRequestBuilder::list_database()).await?;
+    pub async fn list_database(&self, tenant: String) -> Result<Vec<DatabaseDesc>> {
+        let resp = self.admin(AdminRequestBuilder::list_database(tenant)).await?;
         let resp = extract_admin_response!(resp.response, Response::ListDatabases);
         Ok(resp.databases)
     }
 
-    pub async fn get_database(&self, name: String) -> Result<Option<DatabaseDesc>> {
-        let resp = self.admin(AdminRequestBuilder::get_database(name.clone())).await?;
+    pub async fn get_database(&self, tenant: String, name: String) -> Result<Option<DatabaseDesc>> {
+        let resp = self.admin(AdminRequestBuilder::get_database(tenant, name)).await?;
         let resp = extract_admin_response!(resp.response, Response::GetDatabase);
         Ok(resp.database)
     }
@@ -145,13 +154,28 @@ impl Client {
         &self,
         db_desc: DatabaseDesc,
         name: String,
+        options: CollectionOptions,
     ) -> Result<CollectionDesc> {
-        let resp = self.admin(AdminRequestBuilder::create_collection(db_desc, name)).await?;
+        let resp =
+            self.admin(AdminRequestBuilder::create_collection(db_desc, name, options)).await?;
         let resp = extract_admin_response!(resp.response, Response::CreateCollection);
         resp.collection
             .ok_or_else(|| ClientError::Internal("The collection is not set".to_owned().into()))
     }
 
+    pub async fn clone_collection(
+        &self,
+        db_desc: DatabaseDesc,
+        source: String,
+        target: String,
+    ) -> Result<CollectionDesc> {
+        let resp =
+            self.admin(AdminRequestBuilder::clone_collection(db_desc, source, target)).await?;
+        let resp = extract_admin_response!(resp.response, Response::CloneCollection);
+        resp.collection
+            .ok_or_else(|| ClientError::Internal("The collection is not set".to_owned().into()))
+    }
+
     pub async fn delete_collection(&self, db_desc: DatabaseDesc, name: String) -> Result<()> {
         let resp =
             self.admin(AdminRequestBuilder::delete_collection(db_desc.clone(), name)).await?;
@@ -175,6 +199,43 @@ impl Client {
         Ok(resp.collection)
     }
 
+    pub async fn describe_collection(
+        &self,
+        db_desc: DatabaseDesc,
+        name: String,
+    ) -> Result<Option<CollectionDescription>> {
+        let resp = self.admin(AdminRequestBuilder::describe_collection(db_desc, name)).await?;
+        let resp = extract_admin_response!(resp.response, Response::DescribeCollection);
+        Ok(resp.description)
+    }
+
+    pub async fn min_gc_timestamp(&self, collection_id: u64) -> Result<u64> {
+        let resp = self.admin(AdminRequestBuilder::get_gc_timestamp(collection_id)).await?;
+        let resp = extract_admin_response!(resp.response, Response::GetGcTimestamp);
+        Ok(resp.min_gc_timestamp)
+    }
+
+    /// Acquire or renew a GC lease, return the lease id and the expired
+    /// timestamp.
+    pub async fn hold_gc_lease(
+        &self,
+        lease_id: u64,
+        collection_id: u64,
+        timestamp: u64,
+        ttl: Duration,
+    ) -> Result<(u64, u64)> {
+        let req = AdminRequestBuilder::hold_gc_lease(lease_id, collection_id, timestamp, ttl);
+        let resp = self.admin(req).await?;
+        let resp = extract_admin_response!(resp.response, Response::HoldGcLease);
+        Ok((resp.lease_id, resp.expired_at))
+    }
+
+    pub async fn release_gc_lease(&self, lease_id: u64) -> Result<()> {
+        let resp = self.admin(AdminRequestBuilder::release_gc_lease(lease_id)).await?;
+        extract_admin_response!(resp.response, Response::ReleaseGcLease);
+        Ok(())
+    }
+
     pub async fn join_node(&self, req: JoinNodeRequest) -> Result<JoinNodeResponse> {
         let res = self
             .invoke(|mut client| {
@@ -199,11 +260,15 @@ impl Client {
         Ok(res.base_txn_id)
     }
 
+    /// Watch the events of the resources of `resource_types`, all types are
+    /// watched if it is empty.
     pub async fn watch(
         &self,
         cur_group_epochs: HashMap<u64, u64>,
+        resource_types: &[watch_request::ResourceType],
     ) -> Result<Streaming<WatchResponse>> {
-        let req = WatchRequest { cur_group_epochs };
+        let resource_types = resource_types.iter().map(|t| *t as i32).collect();
+        let req = WatchRequest { cur_group_epochs, resource_types };
         let res = self
             .invoke(|mut client| {
                 let req = req.clone();
@@ -225,7 +290,7 @@ impl Client {
 
     async fn invoke<F, O, V>(&self, op: F) -> Result<V>
     where
-        F: Fn(root_client::RootClient<Channel>) -> O,
+        F: Fn(root_client::RootClient<AuthChannel>) -> O,
         O: Future<Output = Result<V, Status>>,
     {
         self.invoke_with_timeout(None, op).await
@@ -233,14 +298,16 @@ impl Client {
 
     async fn invoke_with_timeout<F, O, V>(&self, timeout: Option<Duration>, op: F) -> Result<V>
     where
-        F: Fn(root_client::RootClient<Channel>) -> O,
+        F: Fn(root_client::RootClient<AuthChannel>) -> O,
         O: Future<Output = Result<V, Status>>,
     {
+        let policy = &self.shared.retry_policy;
         let mut interval = 1;
+        let mut attempts = 0;
         let mut save_core = false;
         let mut core = self.core().await;
 
-        let deadline = timeout.map(|duration| Instant::now() + duration);
+        let deadline = timeout.or(policy.deadline).map(|duration| Instant::now() + duration);
         'OUTER: loop {
             if let Some(leader) = core.leader {
                 // Fast path of invoking.
@@ -317,6 +384,13 @@ impl Client {
                 return Err(crate::Error::DeadlineExceeded("issue rpc".to_owned()));
             }
 
+            // Each round of iterating all root nodes is counted as an attempt.
+            attempts += 1;
+            if policy.max_attempts.map(|max| attempts >= max).unwrap_or_default() {
+                trace!("root is not accessable after {attempts} attempts");
+                return Err(ClientError::GroupNotAccessable(sekas_schema::ROOT_GROUP_ID));
+            }
+
             tokio::time::sleep(Duration::from_millis(interval)).await;
             interval = std::cmp::min(interval * 2, 1000);
         }
@@ -368,7 +442,7 @@ impl Client {
     }
 
     #[inline]
-    fn get_root_client(&self, addr: String) -> Result<RootClient<Channel>> {
+    fn get_root_client(&self, addr: String) -> Result<RootClient<AuthChannel>> {
         let root_client = self.shared.conn_manager.get_root_client(addr)?;
         Ok(root_client)
     }
@@ -392,45 +466,85 @@ impl ClientCore {
     }
 }
 
+/// Generate the idempotency token of a DDL request, the request is cloned for
+/// each retry so that all retries carry the same token.
+fn idempotency_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
 impl AdminRequestBuilder {
-    pub fn create_database(name: String) -> AdminRequest {
+    pub fn create_database(tenant: String, name: String) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
-                request: Some(Request::CreateDatabase(CreateDatabaseRequest { name })),
+                request: Some(Request::CreateDatabase(CreateDatabaseRequest {
+                    name,
+                    tenant,
+                    idempotency_token: idempotency_token(),
+                })),
             }),
         }
     }
 
-    pub fn delete_database(name: String) -> AdminRequest {
+    pub fn delete_database(tenant: String, name: String) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
-                request: Some(Request::DeleteDatabase(DeleteDatabaseRequest { name })),
+                request: Some(Request::DeleteDatabase(DeleteDatabaseRequest {
+                    name,
+                    tenant,
+                    idempotency_token: idempotency_token(),
+                })),
             }),
         }
     }
 
-    pub fn list_database() -> AdminRequest {
+    pub fn list_database(tenant: String) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
-                request: Some(Request::ListDatabases(ListDatabasesRequest {})),
+                request: Some(Request::ListDatabases(ListDatabasesRequest { tenant })),
             }),
         }
     }
 
-    pub fn get_database(name: String) -> AdminRequest {
+    pub fn get_database(tenant: String, name: String) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
-                request: Some(Request::GetDatabase(GetDatabaseRequest { name })),
+                request: Some(Request::GetDatabase(GetDatabaseRequest { name, tenant })),
             }),
         }
     }
 
-    pub fn create_collection(database: DatabaseDesc, co_name: String) -> AdminRequest {
+    pub fn create_collection(
+        database: DatabaseDesc,
+        co_name: String,
+        options: CollectionOptions,
+    ) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
                 request: Some(Request::CreateCollection(CreateCollectionRequest {
                     name: co_name,
                     database: Some(database),
+                    encrypted: options.encrypted,
+                    compression: options.compression as i32,
+                    key_schema: options.key_schema,
+                    constraints: options.constraints,
+                    idempotency_token: idempotency_token(),
+                })),
+            }),
+        }
+    }
+
+    pub fn clone_collection(
+        database: DatabaseDesc,
+        source_name: String,
+        target_name: String,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(Request::CloneCollection(CloneCollectionRequest {
+                    source_name,
+                    target_name,
+                    database: Some(database),
+                    idempotency_token: idempotency_token(),
                 })),
             }),
         }
@@ -442,6 +556,7 @@ impl AdminRequestBuilder {
                 request: Some(Request::DeleteCollection(DeleteCollectionRequest {
                     name: co_name,
                     database: Some(database),
+                    idempotency_token: idempotency_token(),
                 })),
             }),
         }
@@ -467,6 +582,51 @@ impl AdminRequestBuilder {
             }),
         }
     }
+
+    pub fn describe_collection(database: DatabaseDesc, co_name: String) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(Request::DescribeCollection(DescribeCollectionRequest {
+                    name: co_name,
+                    database: Some(database),
+                })),
+            }),
+        }
+    }
+
+    pub fn get_gc_timestamp(collection_id: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(Request::GetGcTimestamp(GetGcTimestampRequest { collection_id })),
+            }),
+        }
+    }
+
+    pub fn hold_gc_lease(
+        lease_id: u64,
+        collection_id: u64,
+        timestamp: u64,
+        ttl: Duration,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(Request::HoldGcLease(HoldGcLeaseRequest {
+                    lease_id,
+                    collection_id,
+                    timestamp,
+                    ttl_ms: ttl.as_millis() as u64,
+                })),
+            }),
+        }
+    }
+
+    pub fn release_gc_lease(lease_id: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(Request::ReleaseGcLease(ReleaseGcLeaseRequest { lease_id })),
+            }),
+        }
+    }
 }
 
 fn extract_root_descriptor(status: &tonic::Status) -> Option<(RootDesc, u64, Option<ReplicaDesc>)> {
@@ -492,9 +652,12 @@ fn extract_root_descriptor(status: &tonic::Status) -> Option<(RootDesc, u64, Opt
     None
 }
 
-async fn invoke<F, O, V>(client: root_client::RootClient<Channel>, op: &F) -> Result<V, RootError>
+async fn invoke<F, O, V>(
+    client: root_client::RootClient<AuthChannel>,
+    op: &F,
+) -> Result<V, RootError>
 where
-    F: Fn(root_client::RootClient<Channel>) -> O,
+    F: Fn(root_client::RootClient<AuthChannel>) -> O,
     O: Future<Output = Result<V, Status>>,
 {
     match op(client).await {
@@ -12,10 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use derivative::Derivative;
 use futures::StreamExt;
 use log::{info, trace, warn};
 use sekas_api::server::v1::watch_response::delete_event::Event as DeleteEvent;
@@ -25,22 +26,27 @@ use tokio::task::JoinHandle;
 use tonic::Streaming;
 
 use crate::rpc::RootClient;
+use crate::ClientInstrument;
 
 #[derive(Debug, Clone)]
 pub struct Router {
     core: Arc<RouterCore>,
 }
 
-#[derive(Debug)]
+#[derive(Derivative)]
+#[derivative(Debug)]
 pub struct RouterCore {
     handle: JoinHandle<()>,
     state: Arc<Mutex<State>>,
+    #[derivative(Debug = "ignore")]
+    instrument: Option<Arc<dyn ClientInstrument>>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct State {
     node_id_lookup: HashMap<u64, String /* ip:port */>,
     db_id_lookup: HashMap<u64, DatabaseDesc>,
+    /// Only the databases of the default tenant are looked up by names.
     db_name_lookup: HashMap<String, u64>,
     co_id_lookup: HashMap<u64, CollectionDesc>,
     co_name_lookup: HashMap<(u64 /* db */, String), u64>,
@@ -49,6 +55,16 @@ pub struct State {
     group_id_lookup: HashMap<u64 /* group */, RouterGroupState>,
 
     cached_group_states: HashMap<u64, GroupState>,
+
+    /// The ids of deleted databases and collections, the staled update events
+    /// of them are ignored. The ids are only reused by the databases and
+    /// collections recovered from the trash, which are cached again once the
+    /// metadata is reset.
+    deleted_db_ids: HashSet<u64>,
+    deleted_co_ids: HashSet<u64>,
+    /// The ids of deleted groups, eg. merged into others. The group ids are
+    /// never reused, so the staled descriptors and hints of them are ignored.
+    deleted_group_ids: HashSet<u64>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -61,6 +77,14 @@ pub struct RouterGroupState {
 
 impl Router {
     pub async fn new(root_client: RootClient) -> Self {
+        Router::with_instrument(root_client, None).await
+    }
+
+    /// Create a router, the lookups are reported to the `instrument`.
+    pub async fn with_instrument(
+        root_client: RootClient,
+        instrument: Option<Arc<dyn ClientInstrument>>,
+    ) -> Self {
         let state = Arc::new(Mutex::new(State::default()));
         let state_clone = state.clone();
         let handle = tokio::spawn(async move {
@@ -68,7 +92,7 @@ impl Router {
             state_main(state_clone, root_client).await;
             log::info!("router end");
         });
-        Router { core: Arc::new(RouterCore { handle, state }) }
+        Router { core: Arc::new(RouterCore { handle, state, instrument }) }
     }
 
     pub fn find_shard(
@@ -76,34 +100,23 @@ impl Router {
         collection_id: u64,
         key: &[u8],
     ) -> Result<(RouterGroupState, ShardDesc), crate::Error> {
-        let state = self.core.state.lock().unwrap();
-        let shards = state
-            .co_shards_lookup
-            .get(&collection_id)
-            .ok_or_else(|| crate::Error::NotFound(format!("shard (key={:?})", key)))?;
-        for shard in shards {
-            if let Some(RangePartition { start, end }) = shard.range.clone() {
-                if start.as_slice() > key {
-                    continue;
-                }
-                if (end.as_slice() < key) || (end.is_empty())
-                // end = vec![] means MAX
-                {
-                    let group_state = state.find_group_by_shard(shard.id).ok_or_else(|| {
-                        crate::Error::NotFound(format!("shard (key={key:?}) group"))
-                    })?;
-                    return Ok((group_state, shard.clone()));
-                }
-            }
-        }
-        Err(crate::Error::NotFound(format!("shard (key={:?})", key)))
+        let result = self.find_shard_inner(collection_id, key);
+        self.report_lookup(result.is_ok());
+        result
+    }
+
+    fn find_shard_inner(
+        &self,
+        collection_id: u64,
+        key: &[u8],
+    ) -> Result<(RouterGroupState, ShardDesc), crate::Error> {
+        self.core.state.lock().unwrap().find_shard(collection_id, key)
     }
 
     pub fn find_group_by_shard(&self, shard: u64) -> Result<RouterGroupState, crate::Error> {
-        let state = self.core.state.lock().unwrap();
-        state
-            .find_group_by_shard(shard)
-            .ok_or_else(|| crate::Error::NotFound(format!("group (shard={shard:?})")))
+        let group_state = self.core.state.lock().unwrap().find_group_by_shard(shard);
+        self.report_lookup(group_state.is_some());
+        group_state.ok_or_else(|| crate::Error::NotFound(format!("group (shard={shard:?})")))
     }
 
     pub fn find_group(&self, id: u64) -> Result<RouterGroupState, crate::Error> {
@@ -121,6 +134,82 @@ impl Router {
     pub fn total_nodes(&self) -> usize {
         self.core.state.lock().unwrap().node_id_lookup.len()
     }
+
+    /// Find the cached database descriptor by name, the cache is maintained
+    /// by the watch stream.
+    pub fn find_database(&self, name: &str) -> Option<DatabaseDesc> {
+        let state = self.core.state.lock().unwrap();
+        let id = state.db_name_lookup.get(name)?;
+        state.db_id_lookup.get(id).cloned()
+    }
+
+    /// Find the cached collection descriptor by name, the cache is maintained
+    /// by the watch stream.
+    pub fn find_collection(&self, db: u64, name: &str) -> Option<CollectionDesc> {
+        let state = self.core.state.lock().unwrap();
+        let id = state.co_name_lookup.get(&(db, name.to_owned()))?;
+        state.co_id_lookup.get(id).cloned()
+    }
+
+    /// Find the cached collection descriptor by id, the cache is maintained
+    /// by the watch stream.
+    pub fn find_collection_by_id(&self, id: u64) -> Option<CollectionDesc> {
+        let state = self.core.state.lock().unwrap();
+        state.co_id_lookup.get(&id).cloned()
+    }
+
+    /// Cache the database descriptor, eg. the one created by this client.
+    pub fn apply_database(&self, desc: DatabaseDesc) {
+        self.core.state.lock().unwrap().apply_database(desc);
+    }
+
+    /// Cache the collection descriptor, eg. the one created by this client.
+    pub fn apply_collection(&self, desc: CollectionDesc) {
+        self.core.state.lock().unwrap().apply_collection(desc);
+    }
+
+    /// Invalidate the cached database, eg. the one deleted by this client.
+    pub fn invalidate_database(&self, name: &str) {
+        let mut state = self.core.state.lock().unwrap();
+        if let Some(id) = state.db_name_lookup.get(name).cloned() {
+            state.apply_delete_event(DeleteEvent::Database(id));
+        }
+    }
+
+    /// Invalidate the cached collection, eg. the one deleted by this client.
+    pub fn invalidate_collection(&self, db: u64, name: &str) {
+        let mut state = self.core.state.lock().unwrap();
+        if let Some(id) = state.co_name_lookup.get(&(db, name.to_owned())).cloned() {
+            state.apply_delete_event(DeleteEvent::Collection(id));
+        }
+    }
+
+    /// Replace the cached database and collection descriptors.
+    pub fn reset_metadata(&self, databases: Vec<DatabaseDesc>, collections: Vec<CollectionDesc>) {
+        let mut state = self.core.state.lock().unwrap();
+        state.reset_metadata(databases, collections);
+    }
+
+    #[inline]
+    fn report_lookup(&self, hit: bool) {
+        if let Some(instrument) = self.core.instrument.as_deref() {
+            instrument.on_router_lookup(hit);
+        }
+    }
+
+    /// Apply the group descriptor carried by error responses. It is ignored
+    /// if the local group state is not staled.
+    pub fn apply_group_hint(&self, group_desc: GroupDesc) {
+        let mut state = self.core.state.lock().unwrap();
+        state.apply_group_hint(group_desc);
+    }
+
+    /// Apply the leader carried by `NotLeader` responses. It is ignored if
+    /// the local leader term is not less than the `term`.
+    pub fn apply_leader_hint(&self, group_id: u64, leader: ReplicaDesc, term: u64) {
+        let mut state = self.core.state.lock().unwrap();
+        state.apply_leader_hint(group_id, leader, term);
+    }
 }
 
 impl Drop for RouterCore {
@@ -130,6 +219,32 @@ impl Drop for RouterCore {
 }
 
 impl State {
+    fn find_shard(
+        &self,
+        collection_id: u64,
+        key: &[u8],
+    ) -> Result<(RouterGroupState, ShardDesc), crate::Error> {
+        let shards = self
+            .co_shards_lookup
+            .get(&collection_id)
+            .ok_or_else(|| crate::Error::NotFound(format!("shard (key={:?})", key)))?;
+        for shard in shards {
+            if let Some(RangePartition { start, end }) = shard.range.as_ref() {
+                if start.as_slice() > key {
+                    continue;
+                }
+                // end = vec![] means MAX
+                if key < end.as_slice() || end.is_empty() {
+                    let group_state = self.find_group_by_shard(shard.id).ok_or_else(|| {
+                        crate::Error::NotFound(format!("shard (key={key:?}) group"))
+                    })?;
+                    return Ok((group_state, shard.clone()));
+                }
+            }
+        }
+        Err(crate::Error::NotFound(format!("shard (key={:?})", key)))
+    }
+
     fn find_group_by_shard(&self, shard_id: u64) -> Option<RouterGroupState> {
         let (group_id, epoch) = self.shard_group_lookup.get(&shard_id).cloned()?;
         let group_state = self.group_id_lookup.get(&group_id).cloned()?;
@@ -154,35 +269,50 @@ impl State {
                 let id = group_state.group_id;
                 if let Some(group) = self.group_id_lookup.get_mut(&id) {
                     group.leader_state = leader_state(&group_state);
-                } else {
+                } else if !self.deleted_group_ids.contains(&id) {
                     self.cached_group_states.insert(id, group_state);
                 }
             }
-            UpdateEvent::Database(db_desc) => {
-                let desc = db_desc.clone();
-                let (id, name) = (db_desc.id, db_desc.name);
-                if let Some(old_desc) = self.db_id_lookup.insert(id, desc) {
-                    if old_desc.name != name {
-                        self.db_name_lookup.remove(&name);
-                    }
-                }
-                self.db_name_lookup.insert(name, id);
+            UpdateEvent::Database(db_desc) => self.apply_database(db_desc),
+            UpdateEvent::Collection(co_desc) => self.apply_collection(co_desc),
+        }
+    }
+
+    fn apply_database(&mut self, db_desc: DatabaseDesc) {
+        if self.deleted_db_ids.contains(&db_desc.id) {
+            return;
+        }
+        let desc = db_desc.clone();
+        let (id, name) = (db_desc.id, db_desc.name);
+        if let Some(old_desc) = self.db_id_lookup.insert(id, desc) {
+            if old_desc.name != name && old_desc.tenant_id == 0 {
+                self.db_name_lookup.remove(&old_desc.name);
             }
-            UpdateEvent::Collection(co_desc) => {
-                let desc = co_desc.clone();
-                let (id, name, db) = (co_desc.id, co_desc.name, co_desc.db);
-                if let Some(old_desc) = self.co_id_lookup.insert(id, desc) {
-                    if old_desc.name != name {
-                        self.co_name_lookup.remove(&(db, old_desc.name));
-                    }
-                }
-                self.co_name_lookup.insert((db, name), id);
+        }
+        if db_desc.tenant_id == 0 {
+            self.db_name_lookup.insert(name, id);
+        }
+    }
+
+    fn apply_collection(&mut self, co_desc: CollectionDesc) {
+        if self.deleted_co_ids.contains(&co_desc.id) {
+            return;
+        }
+        let desc = co_desc.clone();
+        let (id, name, db) = (co_desc.id, co_desc.name, co_desc.db);
+        if let Some(old_desc) = self.co_id_lookup.insert(id, desc) {
+            if old_desc.name != name {
+                self.co_name_lookup.remove(&(db, old_desc.name));
             }
         }
+        self.co_name_lookup.insert((db, name), id);
     }
 
     fn apply_group_descriptor(&mut self, group_desc: GroupDesc) {
         trace!("update event; group {group_desc:?}");
+        if self.deleted_group_ids.contains(&group_desc.id) {
+            return;
+        }
         let (id, epoch) = (group_desc.id, group_desc.epoch);
         let (shards, replicas) = (group_desc.shards, group_desc.replicas);
 
@@ -196,6 +326,24 @@ impl State {
         }
         self.group_id_lookup.insert(id, group_state);
 
+        // The shards removed from the group, eg truncated, are no longer routable.
+        let removed_shards = self
+            .shard_group_lookup
+            .iter()
+            .filter(|(shard_id, (group_id, shard_epoch))| {
+                *group_id == id
+                    && *shard_epoch < epoch
+                    && !shards.iter().any(|s| s.id == **shard_id)
+            })
+            .map(|(shard_id, _)| *shard_id)
+            .collect::<HashSet<_>>();
+        if !removed_shards.is_empty() {
+            self.shard_group_lookup.retain(|shard_id, _| !removed_shards.contains(shard_id));
+            for shards in self.co_shards_lookup.values_mut() {
+                shards.retain(|s| !removed_shards.contains(&s.id));
+            }
+        }
+
         for shard in shards {
             match self.shard_group_lookup.get_mut(&shard.id) {
                 None => {
@@ -222,19 +370,84 @@ impl State {
         }
     }
 
+    fn reset_metadata(&mut self, databases: Vec<DatabaseDesc>, collections: Vec<CollectionDesc>) {
+        self.db_id_lookup.clear();
+        self.db_name_lookup.clear();
+        self.co_id_lookup.clear();
+        self.co_name_lookup.clear();
+        // The descriptors listed from root are the latest ones, including the
+        // recovered.
+        for desc in databases {
+            self.deleted_db_ids.remove(&desc.id);
+            self.apply_database(desc);
+        }
+        for desc in collections {
+            self.deleted_co_ids.remove(&desc.id);
+            self.apply_collection(desc);
+        }
+    }
+
+    fn apply_group_hint(&mut self, group_desc: GroupDesc) {
+        let staled = self
+            .group_id_lookup
+            .get(&group_desc.id)
+            .map(|s| s.epoch < group_desc.epoch)
+            .unwrap_or(true);
+        if staled {
+            trace!("apply group hint {group_desc:?}");
+            self.apply_group_descriptor(group_desc);
+        }
+    }
+
+    fn apply_leader_hint(&mut self, group_id: u64, leader: ReplicaDesc, term: u64) {
+        let Some(group_state) = self.group_id_lookup.get_mut(&group_id) else {
+            return;
+        };
+        if group_state.leader_state.map(|(_, local_term)| local_term < term).unwrap_or(true) {
+            trace!("apply leader hint of group {group_id}, leader {leader:?} term {term}");
+            group_state.leader_state = Some((leader.id, term));
+            group_state.replicas.entry(leader.id).or_insert(leader);
+        }
+    }
+
     fn apply_delete_event(&mut self, event: DeleteEvent) {
         match event {
             DeleteEvent::Node(node) => {
                 self.node_id_lookup.remove(&node);
             }
-            DeleteEvent::Group(_) => todo!(),
-            DeleteEvent::GroupState(_) => todo!(),
+            DeleteEvent::Group(group) => {
+                self.deleted_group_ids.insert(group);
+                self.group_id_lookup.remove(&group);
+                self.cached_group_states.remove(&group);
+                // The shards still routed to the deleted group are no longer routable, the
+                // moved out shards are routed by the descriptors of their new groups.
+                let removed_shards = self
+                    .shard_group_lookup
+                    .iter()
+                    .filter(|(_, (group_id, _))| *group_id == group)
+                    .map(|(shard_id, _)| *shard_id)
+                    .collect::<HashSet<_>>();
+                self.shard_group_lookup.retain(|shard_id, _| !removed_shards.contains(shard_id));
+                for shards in self.co_shards_lookup.values_mut() {
+                    shards.retain(|s| !removed_shards.contains(&s.id));
+                }
+            }
+            DeleteEvent::GroupState(group) => {
+                self.cached_group_states.remove(&group);
+                if let Some(group_state) = self.group_id_lookup.get_mut(&group) {
+                    group_state.leader_state = None;
+                }
+            }
             DeleteEvent::Database(db) => {
+                self.deleted_db_ids.insert(db);
                 if let Some(desc) = self.db_id_lookup.remove(&db) {
-                    self.db_name_lookup.remove(desc.name.as_str());
+                    if desc.tenant_id == 0 {
+                        self.db_name_lookup.remove(desc.name.as_str());
+                    }
                 }
             }
             DeleteEvent::Collection(co) => {
+                self.deleted_co_ids.insert(co);
                 if let Some(desc) = self.co_id_lookup.remove(&co) {
                     self.co_name_lookup.remove(&(desc.db, desc.name));
                 }
@@ -252,7 +465,8 @@ async fn state_main(state: Arc<Mutex<State>>, root_client: RootClient) {
             let state = state.lock().unwrap();
             state.group_id_lookup.iter().map(|(id, s)| (*id, s.epoch)).collect()
         };
-        let events = match root_client.watch(cur_group_epochs).await {
+        // All types of events are applied by the router.
+        let events = match root_client.watch(cur_group_epochs, &[]).await {
             Ok(events) => events,
             Err(e) => {
                 warn!("watch events: {e:?}");
@@ -323,6 +537,12 @@ mod tests {
             id,
             collection_id: 1,
             range: Some(RangePartition { start: vec![], end: vec![] }),
+            clone_source: None,
+            encrypted: false,
+            compression: CompressionCodec::Uncompressed as i32,
+            key_schema: None,
+            constraints: vec![],
+            frozen: false,
         }
     }
 
@@ -404,4 +624,158 @@ mod tests {
             assert!(matches!(find, Some(RouterGroupState { id, .. }) if id == 2));
         }
     }
+
+    #[test]
+    fn remove_truncated_shard() {
+        let mut state = State::default();
+        let mut desc = descriptor(1, 1);
+        desc.shards.push(shard(1));
+        state.apply_group_descriptor(desc);
+        assert_eq!(state.co_shards_lookup.get(&1).unwrap().len(), 1);
+
+        // Shard 1 is truncated and replaced by shard 2.
+        let mut desc = descriptor(1, 1 + (1 << 32));
+        desc.shards.push(shard(2));
+        state.apply_group_descriptor(desc);
+        assert!(state.find_group_by_shard(1).is_none());
+        let shards = state.co_shards_lookup.get(&1).unwrap();
+        assert_eq!(shards.iter().map(|s| s.id).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn find_split_shards() {
+        let mut state = State::default();
+        let mut desc = descriptor(1, 1);
+        desc.shards.push(shard(1));
+        state.apply_group_descriptor(desc);
+        assert_eq!(state.find_shard(1, b"z").unwrap().1.id, 1);
+
+        // Shard 1 is split at `m` into shard 1 and 2.
+        let mut desc = descriptor(1, 1 + (1 << 32));
+        let mut left = shard(1);
+        left.range.as_mut().unwrap().end = b"m".to_vec();
+        let mut right = shard(2);
+        right.range.as_mut().unwrap().start = b"m".to_vec();
+        desc.shards.extend([left, right]);
+        state.apply_group_descriptor(desc);
+        assert_eq!(state.find_shard(1, b"a").unwrap().1.id, 1);
+        assert_eq!(state.find_shard(1, b"m").unwrap().1.id, 2);
+        assert_eq!(state.find_shard(1, b"z").unwrap().1.id, 2);
+        assert!(state.find_shard(2, b"a").is_err());
+    }
+
+    #[test]
+    fn apply_group_and_leader_hints() {
+        let mut state = State::default();
+        let mut desc = descriptor(1, 1);
+        desc.shards.push(shard(1));
+        state.apply_group_descriptor(desc);
+        state.apply_group_descriptor(descriptor(2, 1));
+
+        // The shard is migrated to group 2, but the watch stream is lagging.
+        let mut group_2 = descriptor(2, 1 + (1 << 32));
+        group_2.shards.push(shard(1));
+        state.apply_group_hint(group_2);
+        let find = state.find_group_by_shard(1);
+        assert!(matches!(find, Some(RouterGroupState { id, .. }) if id == 2));
+
+        // The staled hint is ignored.
+        let mut group_2 = descriptor(2, 1);
+        group_2.replicas.push(ReplicaDesc { id: 3, node_id: 3, ..Default::default() });
+        state.apply_group_hint(group_2);
+        let group_state = state.group_id_lookup.get(&2).unwrap();
+        assert_eq!(group_state.epoch, 1 + (1 << 32));
+        assert!(group_state.replicas.is_empty());
+
+        let leader = ReplicaDesc { id: 4, node_id: 4, ..Default::default() };
+        state.apply_leader_hint(2, leader.clone(), 2);
+        let group_state = state.group_id_lookup.get(&2).unwrap();
+        assert_eq!(group_state.leader_state, Some((4, 2)));
+        assert!(group_state.replicas.contains_key(&4));
+
+        // The leader of staled term is ignored.
+        let leader = ReplicaDesc { id: 5, node_id: 5, ..Default::default() };
+        state.apply_leader_hint(2, leader, 1);
+        let group_state = state.group_id_lookup.get(&2).unwrap();
+        assert_eq!(group_state.leader_state, Some((4, 2)));
+    }
+
+    #[test]
+    fn delete_merged_group() {
+        let mut state = State::default();
+        let mut desc = descriptor(1, 1);
+        desc.shards.push(shard(1));
+        state.apply_group_descriptor(desc);
+        let mut desc = descriptor(2, 1);
+        desc.shards.push(shard(2));
+        state.apply_group_descriptor(desc);
+
+        // Shard 2 is moved to group 1, and group 2 is deleted.
+        let mut desc = descriptor(1, 1 + (1 << 32));
+        desc.shards.extend([shard(1), shard(2)]);
+        state.apply_group_descriptor(desc);
+        state.apply_delete_event(DeleteEvent::GroupState(2));
+        state.apply_delete_event(DeleteEvent::Group(2));
+        assert!(!state.group_id_lookup.contains_key(&2));
+        let find = state.find_group_by_shard(2);
+        assert!(matches!(find, Some(RouterGroupState { id, .. }) if id == 1));
+
+        // The shards still routed to the deleted group are removed.
+        let mut desc = descriptor(3, 1);
+        desc.shards.push(shard(3));
+        state.apply_group_descriptor(desc);
+        state.apply_delete_event(DeleteEvent::Group(3));
+        assert!(state.find_group_by_shard(3).is_none());
+        let shards = state.co_shards_lookup.get(&1).unwrap();
+        assert!(shards.iter().all(|s| s.id != 3));
+
+        // The staled hint of the deleted group is ignored.
+        state.apply_group_hint(descriptor(3, 2));
+        assert!(!state.group_id_lookup.contains_key(&3));
+    }
+
+    #[test]
+    fn update_and_delete_metadata() {
+        let mut state = State::default();
+        let db = DatabaseDesc { id: 1, name: "db".to_owned(), tenant_id: 0 };
+        state.apply_update_event(UpdateEvent::Database(db.clone()));
+        let co = CollectionDesc { id: 2, name: "co".to_owned(), db: 1, ..Default::default() };
+        state.apply_update_event(UpdateEvent::Collection(co.clone()));
+        assert_eq!(state.db_name_lookup.get("db"), Some(&1));
+        assert_eq!(state.co_name_lookup.get(&(1, "co".to_owned())), Some(&2));
+
+        // Rename the database.
+        let renamed_db = DatabaseDesc { id: 1, name: "db-1".to_owned(), tenant_id: 0 };
+        state.apply_update_event(UpdateEvent::Database(renamed_db));
+        assert!(!state.db_name_lookup.contains_key("db"));
+        assert_eq!(state.db_name_lookup.get("db-1"), Some(&1));
+
+        // The databases of tenants are not looked up by names.
+        let tenant_db = DatabaseDesc { id: 4, name: "db-1".to_owned(), tenant_id: 1 };
+        state.apply_update_event(UpdateEvent::Database(tenant_db));
+        state.apply_delete_event(DeleteEvent::Database(4));
+        assert_eq!(state.db_name_lookup.get("db-1"), Some(&1));
+
+        state.apply_delete_event(DeleteEvent::Collection(2));
+        assert!(state.co_id_lookup.is_empty());
+        assert!(state.co_name_lookup.is_empty());
+
+        // The staled update event of the deleted collection is ignored.
+        state.apply_update_event(UpdateEvent::Collection(co.clone()));
+        assert!(state.co_id_lookup.is_empty());
+        let co = CollectionDesc { id: 3, ..co };
+
+        state.reset_metadata(vec![db], vec![co]);
+        assert_eq!(state.db_name_lookup.len(), 1);
+        assert_eq!(state.db_name_lookup.get("db"), Some(&1));
+        assert_eq!(state.co_name_lookup.get(&(1, "co".to_owned())), Some(&3));
+
+        // The recovered collection is cached again once the metadata is reset.
+        let recovered = CollectionDesc { id: 2, name: "co-2".to_owned(), ..co };
+        state.reset_metadata(vec![db], vec![co, recovered.clone()]);
+        assert_eq!(state.co_name_lookup.get(&(1, "co-2".to_owned())), Some(&2));
+        state.apply_delete_event(DeleteEvent::Collection(2));
+        state.apply_update_event(UpdateEvent::Collection(recovered));
+        assert!(!state.co_id_lookup.contains_key(&2));
+    }
 }
@@ -0,0 +1,174 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+
+use crate::{Error, Result};
+
+/// The TLS options of the connections to endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// The PEM encoded CA certificates to verify the certificates of servers.
+    pub ca_cert: PathBuf,
+
+    /// The PEM encoded certificate chain presented to servers, it is required
+    /// if the servers enable mutual TLS.
+    ///
+    /// Default: None.
+    pub client_cert: Option<PathBuf>,
+
+    /// The PEM encoded private key of `client_cert`.
+    ///
+    /// Default: None.
+    pub client_key: Option<PathBuf>,
+
+    /// The name to verify the certificates of servers against. The host of
+    /// the endpoint address is used if it is `None`.
+    ///
+    /// Default: None.
+    pub domain_name: Option<String>,
+
+    /// The interval of checking whether the certificate files are changed.
+    /// Once changed, the certificates are reloaded and the connections are
+    /// re-established. The reloading is disabled if it is `None`.
+    ///
+    /// Default: None.
+    pub reload_interval: Option<Duration>,
+}
+
+/// The loaded TLS config of a `ConnManager`.
+#[derive(Debug)]
+pub(crate) struct TlsState {
+    opts: TlsOptions,
+    loaded: Mutex<Option<LoadedConfig>>,
+}
+
+#[derive(Debug)]
+struct LoadedConfig {
+    /// The contents of the files that the config is built from.
+    contents: Vec<Vec<u8>>,
+    config: ClientTlsConfig,
+}
+
+impl TlsState {
+    pub(crate) fn new(opts: TlsOptions) -> Self {
+        TlsState { opts, loaded: Mutex::default() }
+    }
+
+    #[inline]
+    pub(crate) fn reload_interval(&self) -> Option<Duration> {
+        self.opts.reload_interval
+    }
+
+    /// Return the config to establish connections, the certificates are
+    /// loaded on the first call.
+    pub(crate) fn client_config(&self) -> Result<ClientTlsConfig> {
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some(loaded) = loaded.as_ref() {
+            return Ok(loaded.config.clone());
+        }
+        let config = load_config(&self.opts)?;
+        let cloned_config = config.config.clone();
+        *loaded = Some(config);
+        Ok(cloned_config)
+    }
+
+    /// Reload the certificates if the files are changed, return whether the
+    /// config is reloaded.
+    pub(crate) fn reload_if_changed(&self) -> Result<bool> {
+        let contents = read_files(&self.opts)?;
+        let mut loaded = self.loaded.lock().unwrap();
+        if loaded.as_ref().map(|l| l.contents == contents).unwrap_or(true) {
+            // The certificates are loaded lazily if there is no config yet.
+            return Ok(false);
+        }
+        *loaded = Some(build_config(&self.opts, contents)?);
+        Ok(true)
+    }
+}
+
+fn load_config(opts: &TlsOptions) -> Result<LoadedConfig> {
+    let contents = read_files(opts)?;
+    build_config(opts, contents)
+}
+
+/// Read the CA certificates, and the client certificate and key if exists.
+fn read_files(opts: &TlsOptions) -> Result<Vec<Vec<u8>>> {
+    let mut paths = vec![&opts.ca_cert];
+    match (&opts.client_cert, &opts.client_key) {
+        (Some(cert), Some(key)) => {
+            paths.push(cert);
+            paths.push(key);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(Error::InvalidArgument(
+                "the client cert and key must be specified together".to_owned(),
+            ))
+        }
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            std::fs::read(path)
+                .map_err(|e| Error::InvalidArgument(format!("read {}: {e}", path.display())))
+        })
+        .collect()
+}
+
+fn build_config(opts: &TlsOptions, contents: Vec<Vec<u8>>) -> Result<LoadedConfig> {
+    let mut config = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(&contents[0]));
+    if contents.len() == 3 {
+        config = config.identity(Identity::from_pem(&contents[1], &contents[2]));
+    }
+    if let Some(domain_name) = &opts.domain_name {
+        config = config.domain_name(domain_name.clone());
+    }
+    Ok(LoadedConfig { contents, config })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_and_reload_certificates() {
+        let dir = std::env::temp_dir().join(format!("sekas-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ca_cert = dir.join("ca.pem");
+        std::fs::write(&ca_cert, b"ca").unwrap();
+
+        let opts = TlsOptions {
+            ca_cert: ca_cert.clone(),
+            client_cert: Some(dir.join("client.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(TlsState::new(opts).client_config(), Err(Error::InvalidArgument(_))));
+
+        let state = TlsState::new(TlsOptions { ca_cert: ca_cert.clone(), ..Default::default() });
+        // Nothing to reload before the config is loaded.
+        assert!(!state.reload_if_changed().unwrap());
+        state.client_config().unwrap();
+        assert!(!state.reload_if_changed().unwrap());
+        std::fs::write(&ca_cert, b"rotated ca").unwrap();
+        assert!(state.reload_if_changed().unwrap());
+        assert!(!state.reload_if_changed().unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -18,7 +18,6 @@ use sekas_api::server::v1::*;
 use sekas_schema::system::txn::TXN_MAX_VERSION;
 
 use crate::group_client::GroupClient;
-use crate::retry::RetryState;
 use crate::{Error, Result, SekasClient, WriteBuilder};
 
 /// `ShardClient` wraps `GroupClient` and provides retry for shard-related
@@ -38,7 +37,7 @@ impl ShardClient {
     }
 
     pub async fn prefix_list(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = self.client.retry_state(None);
 
         loop {
             match self.prefix_list_inner(prefix).await {
@@ -51,7 +50,7 @@ impl ShardClient {
     }
 
     pub async fn delete(&self, key: &[u8]) -> Result<()> {
-        let mut retry_state = RetryState::new(None);
+        let mut retry_state = self.client.retry_state(None);
 
         loop {
             match self.delete_inner(key).await {
@@ -77,6 +76,7 @@ impl ShardClient {
             include_raw_data: true,
             ignore_txn_intent: true,
             allow_scan_moving_shard: true,
+            min_version: 0,
         });
         let mut client = GroupClient::lazy(self.group_id, self.client.clone());
         match client.request(&req).await? {
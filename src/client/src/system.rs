@@ -0,0 +1,74 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use prost::Message;
+use sekas_api::server::v1::*;
+use sekas_schema::system::col;
+
+use crate::{AppError, AppResult, Database};
+
+/// The cluster metadata maintained by root, which is exposed as read-only
+/// collections of the system database. The records are read by the normal scan
+/// API, so they are readable from any node and by the users without the
+/// superuser privilege.
+#[derive(Debug, Clone)]
+pub struct SystemCollections {
+    db: Database,
+}
+
+impl SystemCollections {
+    pub(crate) fn new(db: Database) -> Self {
+        SystemCollections { db }
+    }
+
+    /// List the nodes of the cluster.
+    pub async fn nodes(&self) -> AppResult<Vec<NodeDesc>> {
+        self.scan_all(col::NODE_ID).await
+    }
+
+    /// List the descriptors of all groups.
+    pub async fn groups(&self) -> AppResult<Vec<GroupDesc>> {
+        self.scan_all(col::GROUP_ID).await
+    }
+
+    /// List the raft states of all replicas reported to root.
+    pub async fn replica_states(&self) -> AppResult<Vec<ReplicaState>> {
+        self.scan_all(col::REPLICA_STATE_ID).await
+    }
+
+    /// List the records of a system collection, decoded as `T`. It is used to
+    /// read the collections whose messages are defined by the server, eg. the
+    /// background jobs in [`col::JOB_ID`] and [`col::JOB_HISTORY_ID`].
+    pub async fn list<T: Message + Default>(&self, collection_id: u64) -> AppResult<Vec<T>> {
+        if !col::is_metadata_view(collection_id) {
+            return Err(AppError::InvalidArgument(format!(
+                "collection {collection_id} is not a view of the cluster metadata"
+            )));
+        }
+        self.scan_all(collection_id).await
+    }
+
+    async fn scan_all<T: Message + Default>(&self, collection_id: u64) -> AppResult<Vec<T>> {
+        let kvs = self.db.scan(collection_id, vec![], None, 0).await?;
+        kvs.into_iter()
+            .map(|(_, value)| {
+                T::decode(value.as_slice()).map_err(|err| {
+                    AppError::Internal(
+                        format!("decode record of system collection {collection_id}: {err}").into(),
+                    )
+                })
+            })
+            .collect()
+    }
+}
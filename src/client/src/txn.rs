@@ -22,7 +22,7 @@ use sekas_rock::time::timestamp_millis;
 use sekas_schema::system::keys::{self, txn_lower_key};
 use sekas_schema::system::{self, col};
 
-use crate::{Error, GroupClient, Result, RetryState, SekasClient, WriteBuilder};
+use crate::{Error, GroupClient, Result, SekasClient, WriteBuilder};
 
 const TXN_TIMEOUT: Duration = Duration::from_secs(5);
 
@@ -49,15 +49,23 @@ struct TxnWriteRequest {
     deletes: Vec<DeleteRequest>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TxnStateTable {
     client: SekasClient,
     timeout: Option<Duration>,
+    database_id: u64,
 }
 
 impl TxnStateTable {
     pub fn new(client: SekasClient, timeout: Option<Duration>) -> Self {
-        TxnStateTable { client, timeout }
+        TxnStateTable { client, timeout, database_id: 0 }
+    }
+
+    /// Access the txn records owned by the database, see
+    /// [`keys::txn_prefix`].
+    pub fn with_database(mut self, database_id: u64) -> Self {
+        self.database_id = database_id;
+        self
     }
 
     /// Begin a new transaction with the specified txn version.
@@ -68,14 +76,15 @@ impl TxnStateTable {
         let state_value = TxnState::Running.as_str_name().as_bytes().to_vec();
         let heartbeat_value = timestamp_millis().to_le_bytes().to_vec();
         let hash_tag = system::txn::hash_tag(start_version);
+        let database_id = self.database_id;
         let request = TxnWriteRequest {
             hash_tag,
             puts: vec![
-                WriteBuilder::new(keys::txn_state_key(hash_tag, start_version))
+                WriteBuilder::new(keys::txn_state_key(hash_tag, database_id, start_version))
                     .expect_not_exists()
                     .take_prev_value()
                     .ensure_put(state_value),
-                WriteBuilder::new(keys::txn_heartbeat_key(hash_tag, start_version))
+                WriteBuilder::new(keys::txn_heartbeat_key(hash_tag, database_id, start_version))
                     .ensure_put(heartbeat_value),
             ],
             ..Default::default()
@@ -109,9 +118,10 @@ impl TxnStateTable {
     pub async fn heartbeat(&self, start_version: u64) -> Result<()> {
         let heartbeat_value = txn_u64_value(timestamp_millis());
         let hash_tag = system::txn::hash_tag(start_version);
+        let heartbeat_key = keys::txn_heartbeat_key(hash_tag, self.database_id, start_version);
         let request = TxnWriteRequest {
             hash_tag,
-            puts: vec![WriteBuilder::new(keys::txn_heartbeat_key(hash_tag, start_version))
+            puts: vec![WriteBuilder::new(heartbeat_key)
                 .expect_exists()
                 .ensure_put(heartbeat_value)],
             ..Default::default()
@@ -135,16 +145,17 @@ impl TxnStateTable {
         debug_assert!(start_version < commit_version);
 
         let hash_tag = system::txn::hash_tag(start_version);
+        let database_id = self.database_id;
         let request = TxnWriteRequest {
             hash_tag,
             puts: vec![
-                WriteBuilder::new(keys::txn_state_key(hash_tag, start_version))
+                WriteBuilder::new(keys::txn_state_key(hash_tag, database_id, start_version))
                     .expect_value(txn_state_value(TxnState::Running))
                     .take_prev_value()
                     .ensure_put(txn_state_value(TxnState::Committed)),
-                WriteBuilder::new(keys::txn_commit_key(hash_tag, start_version))
+                WriteBuilder::new(keys::txn_commit_key(hash_tag, database_id, start_version))
                     .ensure_put(txn_u64_value(commit_version)),
-                WriteBuilder::new(keys::txn_heartbeat_key(hash_tag, start_version))
+                WriteBuilder::new(keys::txn_heartbeat_key(hash_tag, database_id, start_version))
                     .ensure_put(txn_u64_value(timestamp_millis())),
             ],
             ..Default::default()
@@ -185,9 +196,10 @@ impl TxnStateTable {
     /// Get the corresponding txn record.
     pub async fn get_txn_record(&self, start_version: u64) -> Result<Option<TxnRecord>> {
         let hash_tag = system::txn::hash_tag(start_version);
-        let txn_prefix = keys::txn_prefix(hash_tag, start_version);
+        let database_id = self.database_id;
+        let txn_prefix = keys::txn_prefix(hash_tag, database_id, start_version);
         let scan_resp = self.scan_txn_keys(&txn_prefix).await?;
-        parse_txn_record(hash_tag, start_version, scan_resp.data)
+        parse_txn_record(hash_tag, database_id, start_version, scan_resp.data)
     }
 
     /// Abort the transaction specified by `start_version`.
@@ -199,14 +211,15 @@ impl TxnStateTable {
         let state_value = txn_state_value(TxnState::Aborted);
         let heartbeat_value = txn_u64_value(timestamp_millis());
         let hash_tag = system::txn::hash_tag(start_version);
+        let database_id = self.database_id;
         let request = TxnWriteRequest {
             hash_tag,
             puts: vec![
-                WriteBuilder::new(keys::txn_state_key(hash_tag, start_version))
+                WriteBuilder::new(keys::txn_state_key(hash_tag, database_id, start_version))
                     .expect_value(expect_state_value)
                     .take_prev_value()
                     .ensure_put(state_value),
-                WriteBuilder::new(keys::txn_heartbeat_key(hash_tag, start_version))
+                WriteBuilder::new(keys::txn_heartbeat_key(hash_tag, database_id, start_version))
                     .ensure_put(heartbeat_value),
             ],
             ..Default::default()
@@ -244,7 +257,7 @@ impl TxnStateTable {
 impl TxnStateTable {
     async fn scan_txn_keys(&self, txn_prefix: &[u8]) -> Result<ShardScanResponse> {
         let router = self.client.router();
-        let mut retry_state = RetryState::new(Some(TXN_TIMEOUT));
+        let mut retry_state = self.client.retry_state(Some(TXN_TIMEOUT));
         loop {
             let (group_state, shard_desc) = router.find_shard(col::txn_col_id(), txn_prefix)?;
             let mut group_client = GroupClient::new(group_state, self.client.clone());
@@ -266,7 +279,7 @@ impl TxnStateTable {
     }
 
     async fn write(&self, request: TxnWriteRequest) -> Result<ShardWriteResponse> {
-        let mut retry_state = RetryState::new(self.timeout);
+        let mut retry_state = self.client.retry_state(self.timeout);
         loop {
             match self.write_inner(&request, retry_state.timeout()).await {
                 Ok(value) => return Ok(value),
@@ -306,13 +319,14 @@ impl TxnStateTable {
 
 fn parse_txn_record(
     hash_tag: u8,
+    database_id: u64,
     start_version: u64,
     values: Vec<ValueSet>,
 ) -> Result<Option<TxnRecord>> {
     // The scan response output orders.
-    let txn_commit_key = keys::txn_commit_key(hash_tag, start_version);
-    let txn_heartbeat_key = keys::txn_heartbeat_key(hash_tag, start_version);
-    let txn_state_key = keys::txn_state_key(hash_tag, start_version);
+    let txn_commit_key = keys::txn_commit_key(hash_tag, database_id, start_version);
+    let txn_heartbeat_key = keys::txn_heartbeat_key(hash_tag, database_id, start_version);
+    let txn_state_key = keys::txn_state_key(hash_tag, database_id, start_version);
 
     let mut txn_record = TxnRecord::default();
     let mut it = values.into_iter().peekable();
@@ -421,16 +435,17 @@ mod tests {
 
     #[test]
     fn parse_empty_txn_record() {
-        let result = parse_txn_record(1, 1, vec![]);
+        let result = parse_txn_record(1, 1, 1, vec![]);
         assert!(matches!(result, Ok(None)));
     }
 
     #[test]
     fn parse_begin_txn_record() {
         let hash_tag = 1;
+        let database_id = 2;
         let txn_id = 123;
-        let state_key = txn_state_key(hash_tag, txn_id);
-        let heartbeat_key = txn_heartbeat_key(hash_tag, txn_id);
+        let state_key = txn_state_key(hash_tag, database_id, txn_id);
+        let heartbeat_key = txn_heartbeat_key(hash_tag, database_id, txn_id);
         let values = vec![
             ValueSet {
                 user_key: heartbeat_key.clone(),
@@ -446,8 +461,9 @@ mod tests {
             },
         ];
 
-        let txn_record =
-            parse_txn_record(hash_tag, txn_id, values).expect("no error").expect("value exists");
+        let txn_record = parse_txn_record(hash_tag, database_id, txn_id, values)
+            .expect("no error")
+            .expect("value exists");
         assert_eq!(txn_record.heartbeat, 123);
         assert_eq!(txn_record.state, TxnState::Running);
     }
@@ -455,10 +471,11 @@ mod tests {
     #[test]
     fn parse_commit_txn_record() {
         let hash_tag = 1;
+        let database_id = 2;
         let txn_id = 123;
-        let commit_key = txn_commit_key(hash_tag, txn_id);
-        let state_key = txn_state_key(hash_tag, txn_id);
-        let heartbeat_key = txn_heartbeat_key(hash_tag, txn_id);
+        let commit_key = txn_commit_key(hash_tag, database_id, txn_id);
+        let state_key = txn_state_key(hash_tag, database_id, txn_id);
+        let heartbeat_key = txn_heartbeat_key(hash_tag, database_id, txn_id);
         let values = vec![
             ValueSet {
                 user_key: commit_key.clone(),
@@ -480,8 +497,9 @@ mod tests {
             },
         ];
 
-        let txn_record =
-            parse_txn_record(hash_tag, txn_id, values).expect("no error").expect("value exists");
+        let txn_record = parse_txn_record(hash_tag, database_id, txn_id, values)
+            .expect("no error")
+            .expect("value exists");
         assert_eq!(txn_record.commit_version, Some(321));
         assert_eq!(txn_record.heartbeat, 123);
         assert_eq!(txn_record.state, TxnState::Running);
@@ -490,9 +508,10 @@ mod tests {
     #[test]
     fn parse_abort_txn_record() {
         let hash_tag = 1;
+        let database_id = 2;
         let txn_id = 123;
-        let state_key = txn_state_key(hash_tag, txn_id);
-        let heartbeat_key = txn_heartbeat_key(hash_tag, txn_id);
+        let state_key = txn_state_key(hash_tag, database_id, txn_id);
+        let heartbeat_key = txn_heartbeat_key(hash_tag, database_id, txn_id);
         let values = vec![
             ValueSet {
                 user_key: heartbeat_key.clone(),
@@ -508,8 +527,9 @@ mod tests {
             },
         ];
 
-        let txn_record =
-            parse_txn_record(hash_tag, txn_id, values).expect("no error").expect("value exists");
+        let txn_record = parse_txn_record(hash_tag, database_id, txn_id, values)
+            .expect("no error")
+            .expect("value exists");
         assert_eq!(txn_record.heartbeat, 123);
         assert_eq!(txn_record.state, TxnState::Aborted);
     }
@@ -517,8 +537,9 @@ mod tests {
     #[test]
     fn parse_partial_txn_record() {
         let hash_tag = 1;
+        let database_id = 2;
         let txn_id = 123;
-        let state_key = txn_state_key(hash_tag, txn_id);
+        let state_key = txn_state_key(hash_tag, database_id, txn_id);
         let values = vec![ValueSet {
             user_key: state_key.clone(),
             values: vec![
@@ -526,17 +547,18 @@ mod tests {
             ],
         }];
 
-        let result = parse_txn_record(hash_tag, txn_id, values);
+        let result = parse_txn_record(hash_tag, database_id, txn_id, values);
         assert!(matches!(result, Err(Error::Internal(_))));
     }
 
     #[test]
     fn parse_txn_record_consume_all_keys() {
         let hash_tag = 1;
+        let database_id = 2;
         let txn_id = 123;
-        let state_key = txn_state_key(hash_tag, txn_id);
-        let heartbeat_key = txn_heartbeat_key(hash_tag, txn_id);
-        let mut other_key = txn_prefix(hash_tag, txn_id);
+        let state_key = txn_state_key(hash_tag, database_id, txn_id);
+        let heartbeat_key = txn_heartbeat_key(hash_tag, database_id, txn_id);
+        let mut other_key = txn_prefix(hash_tag, database_id, txn_id);
         other_key.extend_from_slice(b"zzzz");
         let values = vec![
             ValueSet {
@@ -559,7 +581,7 @@ mod tests {
             },
         ];
 
-        let result = parse_txn_record(hash_tag, txn_id, values);
+        let result = parse_txn_record(hash_tag, database_id, txn_id, values);
         assert!(matches!(result, Err(Error::Internal(_))));
     }
 }
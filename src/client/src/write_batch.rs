@@ -21,7 +21,7 @@ use sekas_api::server::v1::*;
 
 use crate::group_client::GroupClient;
 use crate::retry::RetryState;
-use crate::{AppResult, Error, Result, SekasClient, TxnStateTable};
+use crate::{chunk, AppResult, Error, Result, SekasClient, TxnStateTable};
 
 #[derive(Debug, Default, Clone)]
 pub struct WriteBatchRequest {
@@ -41,6 +41,10 @@ pub struct WriteBatchResponse {
     ///
     /// Only for the requests with `take_prev_value`.
     pub puts: Vec<Option<Value>>,
+    /// The resulting value of the puts, parallel to `puts`.
+    ///
+    /// Only for the requests with `PutType::AddI64`.
+    pub add_results: Vec<Option<i64>>,
 }
 
 pub struct WriteBuilder {
@@ -50,6 +54,8 @@ pub struct WriteBuilder {
     conditions: Vec<WriteCondition>,
     /// The TTL of key.
     ttl: Option<u64>,
+    /// The unix timestamp in seconds at which the key expires.
+    expire_at: Option<u64>,
     /// Whether to take prev values.
     take_prev_value: bool,
 }
@@ -66,6 +72,9 @@ struct WriteContext {
     index: usize,
     /// Is this request has been accepted.
     done: bool,
+    /// Whether this request writes the chunks of a large value, its response
+    /// is not returned to the caller.
+    hidden: bool,
 }
 
 /// A structure to hold the context about a write batch request.
@@ -80,6 +89,8 @@ pub struct WriteBatchContext {
 
     start_version: u64,
     commit_version: u64,
+    /// The database owning the txn record.
+    database_id: u64,
 
     retry_state: RetryState,
 }
@@ -98,7 +109,7 @@ impl WriteBatchRequest {
 
 impl WriteBuilder {
     pub fn new(key: Vec<u8>) -> Self {
-        WriteBuilder { key, conditions: vec![], ttl: None, take_prev_value: false }
+        WriteBuilder { key, conditions: vec![], ttl: None, expire_at: None, take_prev_value: false }
     }
 
     /// With ttl, in seconds.
@@ -109,6 +120,15 @@ impl WriteBuilder {
         self
     }
 
+    /// With the expire time, in unix seconds. The value is read as deleted once
+    /// it expires.
+    ///
+    /// Only works for put request.
+    pub fn with_expire_at(mut self, expire_at: u64) -> Self {
+        self.expire_at = Some(expire_at);
+        self
+    }
+
     /// Build a put request.
     pub fn put(self, value: Vec<u8>) -> AppResult<PutRequest> {
         self.verify_conditions()?;
@@ -119,6 +139,8 @@ impl WriteBuilder {
             ttl: self.ttl.unwrap_or_default(),
             take_prev_value: self.take_prev_value,
             conditions: self.conditions,
+            expire_at: self.expire_at.unwrap_or_default(),
+            num_chunks: 0,
         })
     }
 
@@ -152,6 +174,8 @@ impl WriteBuilder {
             ttl: 0,
             conditions: self.conditions,
             take_prev_value: false,
+            expire_at: 0,
+            num_chunks: 0,
         })
     }
 
@@ -171,6 +195,8 @@ impl WriteBuilder {
             ttl: self.ttl.unwrap_or_default(),
             conditions: self.conditions,
             take_prev_value: self.take_prev_value,
+            expire_at: self.expire_at.unwrap_or_default(),
+            num_chunks: 0,
         })
     }
 
@@ -333,6 +359,7 @@ impl WriteContext {
             response: None,
             index,
             done: false,
+            hidden: false,
         }
     }
 
@@ -343,9 +370,14 @@ impl WriteContext {
             response: None,
             index,
             done: false,
+            hidden: false,
         }
     }
 
+    fn with_chunk(index: usize, collection_id: u64, request: WriteRequest) -> Self {
+        WriteContext { collection_id, request, response: None, index, done: false, hidden: true }
+    }
+
     fn user_key(&self) -> &[u8] {
         match &self.request {
             WriteRequest::Put(put) => &put.key,
@@ -363,6 +395,7 @@ impl WriteBatchContext {
         writes.extend(request.deletes.into_iter().enumerate().map(WriteContext::with_delete));
         writes.extend(request.puts.into_iter().enumerate().map(WriteContext::with_put));
 
+        let retry_state = client.retry_state(timeout);
         WriteBatchContext {
             client,
             writes,
@@ -370,10 +403,18 @@ impl WriteBatchContext {
             num_doing_writes,
             start_version: 0,
             commit_version: 0,
-            retry_state: RetryState::new(timeout),
+            database_id: 0,
+            retry_state,
         }
     }
 
+    /// Keep the txn record under the database, see
+    /// [`TxnStateTable::with_database`].
+    pub fn with_database(mut self, database_id: u64) -> Self {
+        self.database_id = database_id;
+        self
+    }
+
     pub async fn commit(mut self) -> Result<WriteBatchResponse> {
         // TODO: check parameters
 
@@ -381,11 +422,12 @@ impl WriteBatchContext {
         log::info!("try alloc txn version");
         self.start_version = self.alloc_txn_version().await?;
         log::info!("alloc txn version {}", self.start_version);
+        self.prepare_chunks();
         self.start_txn().await?;
         log::info!("start txn {}", self.start_version);
 
         let start_version = self.start_version;
-        let txn_table = TxnStateTable::new(self.client.clone(), self.retry_state.timeout());
+        let txn_table = self.txn_table();
 
         tokio::select! {
             _ = Self::lease_txn(txn_table, start_version) => {
@@ -408,6 +450,9 @@ impl WriteBatchContext {
 
     async fn commit_inner(mut self) -> Result<WriteBatchResponse> {
         self.prepare_intents().await?;
+        if self.delete_stale_chunks() {
+            self.prepare_intents().await?;
+        }
         log::info!("prepare intents {}", self.start_version);
         self.commit_version = self.alloc_txn_version().await?;
         log::info!("allocate commit txn version {} {}", self.start_version, self.commit_version);
@@ -417,20 +462,26 @@ impl WriteBatchContext {
 
         let mut deletes = Vec::with_capacity(self.num_deletes);
         let mut puts = Vec::with_capacity(self.writes.len() - self.num_deletes);
+        let mut add_results = Vec::with_capacity(self.writes.len() - self.num_deletes);
         for write in &mut self.writes {
+            if write.hidden {
+                continue;
+            }
             match &write.request {
                 WriteRequest::Delete(_) => {
                     deletes.push(write.response.take().and_then(|v| v.prev_value));
                 }
                 WriteRequest::Put(_) => {
-                    puts.push(write.response.take().and_then(|v| v.prev_value));
+                    let resp = write.response.take().unwrap_or_default();
+                    puts.push(resp.prev_value);
+                    add_results.push(resp.add_result);
                 }
             }
         }
 
         self.commit_intents();
         log::info!("commit intents");
-        Ok(WriteBatchResponse { version, deletes, puts })
+        Ok(WriteBatchResponse { version, deletes, puts, add_results })
     }
 
     async fn alloc_txn_version(&mut self) -> Result<u64> {
@@ -447,10 +498,78 @@ impl WriteBatchContext {
         }
     }
 
-    async fn start_txn(&mut self) -> Result<()> {
+    /// Split the large values into chunks, the user keys hold the manifests
+    /// and the chunks are written to the chunk keys in the same txn.
+    fn prepare_chunks(&mut self) {
+        let Some(chunk_size) = self.client.chunk_size() else {
+            return;
+        };
+
+        let mut chunk_writes = Vec::new();
+        for write in &mut self.writes {
+            let WriteRequest::Put(put) = &mut write.request else {
+                continue;
+            };
+            if put.put_type != PutType::None as i32 || put.value.len() <= chunk_size {
+                continue;
+            }
+            let value = std::mem::take(&mut put.value);
+            let chunks = chunk::split(&value, chunk_size);
+            put.num_chunks = chunks.len() as u32;
+            for (index, chunk) in chunks.into_iter().enumerate() {
+                let chunk_put = PutRequest {
+                    key: chunk::chunk_key(&put.key, index as u32),
+                    value: chunk.to_owned(),
+                    ttl: put.ttl,
+                    expire_at: put.expire_at,
+                    ..Default::default()
+                };
+                chunk_writes.push((write.collection_id, WriteRequest::Put(chunk_put)));
+            }
+        }
+        self.push_chunk_writes(chunk_writes);
+    }
+
+    /// Delete the chunks of the previous values which are not overwritten,
+    /// return whether there are any. The previous values are read under the
+    /// latches of the keys when writing the intents, and the intents block the
+    /// other writers until this txn is committed, so the concurrent overwrites
+    /// never leave stale chunks.
+    fn delete_stale_chunks(&mut self) -> bool {
+        let mut chunk_writes = Vec::new();
+        for write in self.writes.iter().filter(|w| !w.hidden) {
+            let num_chunks = match &write.request {
+                WriteRequest::Put(put) if put.put_type == PutType::Nop as i32 => continue,
+                WriteRequest::Put(put) => put.num_chunks,
+                WriteRequest::Delete(_) => 0,
+            };
+            let prev_num_chunks = write.response.as_ref().map(|r| r.prev_num_chunks);
+            for index in num_chunks..prev_num_chunks.unwrap_or_default() {
+                let key = chunk::chunk_key(write.user_key(), index);
+                let chunk_delete = DeleteRequest { key, ..Default::default() };
+                chunk_writes.push((write.collection_id, WriteRequest::Delete(chunk_delete)));
+            }
+        }
+        let has_chunk_writes = !chunk_writes.is_empty();
+        self.push_chunk_writes(chunk_writes);
+        has_chunk_writes
+    }
+
+    fn push_chunk_writes(&mut self, chunk_writes: Vec<(u64, WriteRequest)>) {
+        for (collection_id, request) in chunk_writes {
+            let index = self.writes.len();
+            self.writes.push(WriteContext::with_chunk(index, collection_id, request));
+            self.num_doing_writes += 1;
+        }
+    }
+
+    fn txn_table(&self) -> TxnStateTable {
         TxnStateTable::new(self.client.clone(), self.retry_state.timeout())
-            .begin_txn(self.start_version)
-            .await
+            .with_database(self.database_id)
+    }
+
+    async fn start_txn(&mut self) -> Result<()> {
+        self.txn_table().begin_txn(self.start_version).await
     }
 
     async fn prepare_intents(&mut self) -> Result<()> {
@@ -476,6 +595,7 @@ impl WriteBatchContext {
                 start_version: self.start_version,
                 shard_id: shard_desc.id,
                 write: Some(write.request.clone()),
+                txn_database_id: self.database_id,
             });
             if let Some(duration) = self.retry_state.timeout() {
                 client.set_timeout(duration);
@@ -516,16 +636,12 @@ impl WriteBatchContext {
     }
 
     async fn commit_txn(&mut self) -> Result<()> {
-        TxnStateTable::new(self.client.clone(), self.retry_state.timeout())
-            .commit_txn(self.start_version, self.commit_version)
-            .await
+        self.txn_table().commit_txn(self.start_version, self.commit_version).await
     }
 
     #[allow(unused)]
     async fn abort_txn(&mut self) -> Result<()> {
-        TxnStateTable::new(self.client.clone(), self.retry_state.timeout())
-            .abort_txn(self.start_version)
-            .await
+        self.txn_table().abort_txn(self.start_version).await
     }
 
     fn commit_intents(mut self) {
@@ -0,0 +1,239 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use log::trace;
+use sekas_api::server::v1::*;
+use tokio::sync::oneshot;
+
+use crate::{Database, Error, Result, SekasClient};
+
+/// The options of `WriteCoalescer`.
+#[derive(Debug, Clone)]
+pub struct WriteCoalescerOptions {
+    /// The writes to the same shard within the window are merged into one
+    /// request.
+    ///
+    /// Default: 1ms.
+    pub window: Duration,
+
+    /// The pending writes of a shard are flushed immediately once the number
+    /// of them reaches this limit.
+    ///
+    /// Default: 128.
+    pub max_batch_writes: usize,
+}
+
+/// Merge the concurrent writes to the same shard into one
+/// `ShardWriteRequest`, to improve the throughput of many small writers.
+///
+/// The writes are not transactional, they are applied with the same semantic
+/// as `ShardWriteRequest`. The writes of a merged request are applied
+/// atomically.
+#[derive(Clone)]
+pub struct WriteCoalescer {
+    inner: Arc<CoalescerInner>,
+}
+
+/// The future of a write submitted to `WriteCoalescer`, it is resolved once
+/// the merged request is finished.
+pub struct WriteFuture {
+    receiver: oneshot::Receiver<Result<WriteResponse>>,
+}
+
+struct CoalescerInner {
+    client: SekasClient,
+    db: Database,
+    opts: WriteCoalescerOptions,
+    batches: Mutex<HashMap<u64 /* shard */, PendingBatch>>,
+}
+
+type WriteSender = oneshot::Sender<Result<WriteResponse>>;
+
+#[derive(Default)]
+struct PendingBatch {
+    deletes: Vec<(DeleteRequest, WriteSender)>,
+    puts: Vec<(PutRequest, WriteSender)>,
+}
+
+impl Default for WriteCoalescerOptions {
+    fn default() -> Self {
+        WriteCoalescerOptions { window: Duration::from_millis(1), max_batch_writes: 128 }
+    }
+}
+
+impl WriteCoalescer {
+    pub(crate) fn new(client: SekasClient, db: Database, opts: WriteCoalescerOptions) -> Self {
+        let inner = CoalescerInner { client, db, opts, batches: Mutex::default() };
+        WriteCoalescer { inner: Arc::new(inner) }
+    }
+
+    /// Submit a put request, only `PutType::None` is supported.
+    pub fn put(&self, collection_id: u64, put: PutRequest) -> Result<WriteFuture> {
+        if put.put_type != PutType::None as i32 {
+            return Err(Error::InvalidArgument(
+                "only put without put type could be coalesced".to_owned(),
+            ));
+        }
+        self.submit(collection_id, WriteRequest::Put(put))
+    }
+
+    /// Submit a delete request.
+    pub fn delete(&self, collection_id: u64, delete: DeleteRequest) -> Result<WriteFuture> {
+        self.submit(collection_id, WriteRequest::Delete(delete))
+    }
+
+    /// Flush all pending writes immediately.
+    pub fn flush(&self) {
+        let shard_ids = self.inner.batches.lock().unwrap().keys().cloned().collect::<Vec<_>>();
+        for shard_id in shard_ids {
+            self.inner.flush_shard(shard_id);
+        }
+    }
+
+    fn submit(&self, collection_id: u64, request: WriteRequest) -> Result<WriteFuture> {
+        let router = self.inner.client.router();
+        let (_, shard) = router.find_shard(collection_id, request.user_key())?;
+        let (sender, receiver) = oneshot::channel();
+        let (is_first, is_full) = {
+            let mut batches = self.inner.batches.lock().unwrap();
+            let batch = batches.entry(shard.id).or_default();
+            let is_first = batch.is_empty();
+            match request {
+                WriteRequest::Put(put) => batch.puts.push((put, sender)),
+                WriteRequest::Delete(delete) => batch.deletes.push((delete, sender)),
+            }
+            (is_first, batch.len() >= self.inner.opts.max_batch_writes)
+        };
+        if is_full {
+            self.inner.flush_shard(shard.id);
+        } else if is_first {
+            let inner = self.inner.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(inner.opts.window).await;
+                inner.flush_shard(shard.id);
+            });
+        }
+        Ok(WriteFuture { receiver })
+    }
+}
+
+impl CoalescerInner {
+    fn flush_shard(self: &Arc<Self>, shard_id: u64) {
+        let Some(batch) = self.batches.lock().unwrap().remove(&shard_id) else {
+            return;
+        };
+        if batch.is_empty() {
+            return;
+        }
+        let inner = self.clone();
+        tokio::spawn(async move {
+            inner.write(shard_id, batch).await;
+        });
+    }
+
+    async fn write(&self, shard_id: u64, batch: PendingBatch) {
+        trace!("shard {shard_id} flush {} coalesced writes", batch.len());
+        let (deletes, delete_senders): (Vec<_>, Vec<_>) = batch.deletes.into_iter().unzip();
+        let (puts, put_senders): (Vec<_>, Vec<_>) = batch.puts.into_iter().unzip();
+        let num_writes = deletes.len() + puts.len();
+        let request = ShardWriteRequest { shard_id, deletes, puts };
+        match self.db.write(&request).await {
+            Ok(resp) => {
+                for (sender, resp) in delete_senders.into_iter().zip(resp.deletes) {
+                    sender.send(Ok(resp)).unwrap_or_default();
+                }
+                for (sender, resp) in put_senders.into_iter().zip(resp.puts) {
+                    sender.send(Ok(resp)).unwrap_or_default();
+                }
+            }
+            Err(Error::CasFailed(..)) if num_writes > 1 => {
+                // The merged request is rejected as a whole, issue the writes individually so
+                // that each write receives its own result.
+                let ShardWriteRequest { deletes, puts, .. } = request;
+                let writes = deletes
+                    .into_iter()
+                    .zip(delete_senders)
+                    .map(|(delete, sender)| {
+                        let req =
+                            ShardWriteRequest { shard_id, deletes: vec![delete], puts: vec![] };
+                        (req, sender)
+                    })
+                    .chain(puts.into_iter().zip(put_senders).map(|(put, sender)| {
+                        let req = ShardWriteRequest { shard_id, deletes: vec![], puts: vec![put] };
+                        (req, sender)
+                    }));
+                let futures = writes.map(|(req, sender)| async move {
+                    let result = self.db.write(&req).await.map(|resp| {
+                        resp.deletes.into_iter().chain(resp.puts).next().unwrap_or_default()
+                    });
+                    sender.send(result).unwrap_or_default();
+                });
+                futures::future::join_all(futures).await;
+            }
+            Err(err) => {
+                for sender in delete_senders.into_iter().chain(put_senders) {
+                    sender.send(Err(duplicate_error(&err))).unwrap_or_default();
+                }
+            }
+        }
+    }
+}
+
+impl PendingBatch {
+    #[inline]
+    fn len(&self) -> usize {
+        self.deletes.len() + self.puts.len()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Future for WriteFuture {
+    type Output = Result<WriteResponse>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|result| {
+            result.unwrap_or_else(|_| Err(Error::Internal("the coalesced write is dropped".into())))
+        })
+    }
+}
+
+/// Duplicate the error for each write of the merged request.
+fn duplicate_error(err: &Error) -> Error {
+    match err {
+        Error::InvalidArgument(v) => Error::InvalidArgument(v.clone()),
+        Error::DeadlineExceeded(v) => Error::DeadlineExceeded(v.clone()),
+        Error::NotFound(v) => Error::NotFound(v.clone()),
+        Error::ResourceExhausted(v) => Error::ResourceExhausted(v.clone()),
+        Error::QuotaExceeded(v, retry_after) => Error::QuotaExceeded(v.clone(), *retry_after),
+        Error::EpochNotMatch(desc) => Error::EpochNotMatch(desc.clone()),
+        Error::GroupBusy(group_id, retry_after) => Error::GroupBusy(*group_id, *retry_after),
+        Error::GroupNotAccessable(group_id) => Error::GroupNotAccessable(*group_id),
+        Error::CasFailed(index, cond_index, prev_value) => {
+            Error::CasFailed(*index, *cond_index, prev_value.clone())
+        }
+        Error::CollectionFrozen(id) => Error::CollectionFrozen(*id),
+        _ => Error::Internal(err.to_string().into()),
+    }
+}
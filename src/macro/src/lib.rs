@@ -18,18 +18,35 @@ use syn::spanned::Spanned;
 use syn::ItemFn;
 
 #[proc_macro_attribute]
-pub fn test(_args: TokenStream, item: TokenStream) -> TokenStream {
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
     let mut input = syn::parse_macro_input!(item as ItemFn);
     if input.sig.asyncness.is_none() {
         return syn::Error::new(input.sig.span(), "async fn is required").to_compile_error().into();
     }
+    // `#[sekas_macro::test(mock_time)]` runs the test with a frozen clock, it
+    // requires the `mock-time` feature of `sekas-runtime`.
+    let mock_time = match syn::parse_macro_input!(args as Option<syn::Ident>) {
+        Some(ident) if ident == "mock_time" => true,
+        Some(ident) => {
+            return syn::Error::new(ident.span(), "unknown argument").to_compile_error().into()
+        }
+        None => false,
+    };
     input.sig.asyncness = None;
     let body = input.block;
-    input.block = syn::parse_quote! {
-        {
-            sekas_runtime::ExecutorOwner::new(1)
-                .executor()
-                .block_on(async move { #body });
+    input.block = if mock_time {
+        syn::parse_quote! {
+            {
+                sekas_runtime::ExecutorOwner::with_mock_clock().block_on(async move { #body });
+            }
+        }
+    } else {
+        syn::parse_quote! {
+            {
+                sekas_runtime::ExecutorOwner::new(1)
+                    .executor()
+                    .block_on(async move { #body });
+            }
         }
     };
 
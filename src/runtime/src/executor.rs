@@ -77,9 +77,28 @@ impl ExecutorOwner {
         ExecutorOwner { runtime }
     }
 
+    /// New executor with a frozen clock, see [`crate::time`]. The tasks are
+    /// driven by the thread calling [`ExecutorOwner::block_on`], since the mock
+    /// clock requires a single threaded scheduler.
+    #[cfg(feature = "mock-time")]
+    pub fn with_mock_clock() -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .expect("build tokio runtime");
+        ExecutorOwner { runtime }
+    }
+
     pub fn executor(&self) -> Executor {
         Executor { handle: self.runtime.handle().clone() }
     }
+
+    /// Runs a future to completion, and drives the IO and timers of the
+    /// executor in the current thread.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
 }
 
 impl Executor {
@@ -64,6 +64,23 @@ impl ShutdownNotifier {
         tokio::signal::ctrl_c().await.expect("failed to listen ctrl c event");
     }
 
+    /// Like [`ShutdownNotifier::ctrl_c`], but `SIGTERM` is also accepted on
+    /// unix.
+    pub async fn terminate(self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut sigterm = signal(SignalKind::terminate()).expect("failed to listen SIGTERM");
+            tokio::select! {
+                res = tokio::signal::ctrl_c() => res.expect("failed to listen ctrl c event"),
+                _ = sigterm.recv() => {}
+            }
+        }
+        #[cfg(not(unix))]
+        tokio::signal::ctrl_c().await.expect("failed to listen ctrl c event");
+    }
+
     pub fn subscribe(&self) -> Shutdown {
         Shutdown::new(self.core.clone())
     }
@@ -12,4 +12,42 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-pub use tokio::time::{sleep, timeout, timeout_at};
+
+//! The time utilities of the runtime.
+//!
+//! With the `mock-time` feature, the clock of an executor built by
+//! [`crate::ExecutorOwner::with_mock_clock`] is frozen, it is advanced by
+//! [`advance`] in tests instead of the wall clock. Once all tasks are idle, the
+//! frozen clock jumps to the next timer, so the sleeps in polling loops don't
+//! block the tests. The [`Instant`] should be used instead of
+//! `std::time::Instant` to observe the mock clock.
+
+#[cfg(feature = "mock-time")]
+pub use tokio::time::{advance, pause, resume};
+pub use tokio::time::{sleep, timeout, timeout_at, Instant};
+
+#[cfg(all(test, feature = "mock-time"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ExecutorOwner;
+
+    #[test]
+    fn advance_mock_clock() {
+        let owner = ExecutorOwner::with_mock_clock();
+        owner.block_on(async {
+            let start = Instant::now();
+            advance(Duration::from_secs(10)).await;
+            assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+            // The frozen clock jumps to the timer once the tasks are idle.
+            sleep(Duration::from_secs(3600)).await;
+            assert_eq!(start.elapsed(), Duration::from_secs(3610));
+
+            let fired = timeout(Duration::from_secs(1), futures::future::pending::<()>()).await;
+            assert!(fired.is_err());
+            assert_eq!(start.elapsed(), Duration::from_secs(3611));
+        });
+    }
+}
@@ -41,3 +41,20 @@ pub fn start_key(shard: &ShardDesc) -> Vec<u8> {
 pub fn end_key(shard: &ShardDesc) -> Vec<u8> {
     shard.range.as_ref().map(|range| range.end.clone()).unwrap_or_default()
 }
+
+/// Return whether the ranges of the shards overlap.
+pub fn is_overlapped(lhs: &ShardDesc, rhs: &ShardDesc) -> bool {
+    let (lhs_start, lhs_end) = (start_key(lhs), end_key(lhs));
+    let (rhs_start, rhs_end) = (start_key(rhs), end_key(rhs));
+    (lhs_end.is_empty() || rhs_start < lhs_end) && (rhs_end.is_empty() || lhs_start < rhs_end)
+}
+
+/// Return whether the shard is the clone source of another shard in the group,
+/// including the shards split from the clone source. The clone source must
+/// stay with the cloned shards until the data is realized.
+pub fn is_clone_source(group: &GroupDesc, shard: &ShardDesc) -> bool {
+    group.shards.iter().any(|s| {
+        s.clone_source.as_ref().map(|c| c.collection_id) == Some(shard.collection_id)
+            && is_overlapped(s, shard)
+    })
+}
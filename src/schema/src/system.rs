@@ -29,6 +29,7 @@ pub fn unity_col_shards() -> Vec<ShardDesc> {
         col::replica_state_shard_desc(),
         col::job_shard_desc(),
         col::job_history_shard_desc(),
+        col::user_shard_desc(),
         col::txn_shard_desc(),
     ]
 }
@@ -44,6 +45,7 @@ pub fn collections() -> Vec<CollectionDesc> {
         col::replica_state_desc(),
         col::job_desc(),
         col::job_history_desc(),
+        col::user_desc(),
         col::txn_desc(),
     ]
 }
@@ -28,6 +28,11 @@ macro_rules! decl_unity_range_col {
                     id: $col_id,
                     name: stringify!($name).to_owned(),
                     db: crate::system::db::ID,
+                    encrypted: false,
+                    compression: CompressionCodec::Uncompressed as i32,
+                    key_schema: None,
+                    constraints: vec![],
+                    frozen: false,
                 }
             }
 
@@ -39,6 +44,12 @@ macro_rules! decl_unity_range_col {
                         start: crate::shard::SHARD_MIN.to_owned(),
                         end: crate::shard::SHARD_MAX.to_owned(),
                     }),
+                    clone_source: None,
+                    encrypted: false,
+                    compression: CompressionCodec::Uncompressed as i32,
+                    key_schema: None,
+                    constraints: vec![],
+                    frozen: false,
                 }
             }
         }
@@ -55,6 +66,7 @@ decl_unity_range_col!(group, 5);
 decl_unity_range_col!(replica_state, 6);
 decl_unity_range_col!(job, 7);
 decl_unity_range_col!(job_history, 8);
+decl_unity_range_col!(user, 9);
 decl_unity_range_col!(end_unity_col, 100);
 
 decl_unity_range_col!(txn, crate::FIRST_TXN_SHARD_ID);
@@ -64,6 +76,13 @@ pub fn is_unity_col(col_id: u64) -> bool {
     LOCAL_COLLECTION_ID < col_id && col_id < END_UNITY_COL_ID
 }
 
+/// Whether the collection is a view of the root metadata, eg. the nodes and
+/// groups of the cluster. They are readable by all users but only written by
+/// root itself.
+pub fn is_metadata_view(col_id: u64) -> bool {
+    matches!(col_id, NODE_ID | GROUP_ID | REPLICA_STATE_ID | JOB_ID | JOB_HISTORY_ID)
+}
+
 /// The associated shard id of a collection.
 ///
 /// See [`decl_range_col`] for details.
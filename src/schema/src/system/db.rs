@@ -18,5 +18,5 @@ pub const ID: u64 = 1;
 
 #[inline]
 pub fn database_desc() -> DatabaseDesc {
-    DatabaseDesc { id: ID, name: NAME.to_owned() }
+    DatabaseDesc { id: ID, name: NAME.to_owned(), tenant_id: 0 }
 }
@@ -28,34 +28,46 @@ pub fn txn_lower_key(hash_tag: u8) -> Vec<u8> {
     buf
 }
 
-/// The prefix of a txn key.
+/// The prefix of a txn key. The txn records are keyed under the database
+/// owning the txn, so that the writes to them could be authorized by the
+/// permissions of the database.
 #[inline]
-pub fn txn_prefix(hash_tag: u8, txn_id: u64) -> Vec<u8> {
+pub fn txn_prefix(hash_tag: u8, database_id: u64, txn_id: u64) -> Vec<u8> {
     let mut buf = txn_lower_key(hash_tag);
+    buf.extend_from_slice(&database_id.to_be_bytes());
     buf.extend_from_slice(&txn_id.to_be_bytes());
     buf
 }
 
 /// The txn state key.
 #[inline]
-pub fn txn_state_key(hash_tag: u8, txn_id: u64) -> Vec<u8> {
-    let mut buf = txn_prefix(hash_tag, txn_id);
+pub fn txn_state_key(hash_tag: u8, database_id: u64, txn_id: u64) -> Vec<u8> {
+    let mut buf = txn_prefix(hash_tag, database_id, txn_id);
     buf.extend_from_slice(TXN_SUFFIX_STATE);
     buf
 }
 
 /// The txn heartbeat key.
 #[inline]
-pub fn txn_heartbeat_key(hash_tag: u8, txn_id: u64) -> Vec<u8> {
-    let mut buf = txn_prefix(hash_tag, txn_id);
+pub fn txn_heartbeat_key(hash_tag: u8, database_id: u64, txn_id: u64) -> Vec<u8> {
+    let mut buf = txn_prefix(hash_tag, database_id, txn_id);
     buf.extend_from_slice(TXN_SUFFIX_HEARTBEAT);
     buf
 }
 
 /// The txn commit key.
 #[inline]
-pub fn txn_commit_key(hash_tag: u8, txn_id: u64) -> Vec<u8> {
-    let mut buf = txn_prefix(hash_tag, txn_id);
+pub fn txn_commit_key(hash_tag: u8, database_id: u64, txn_id: u64) -> Vec<u8> {
+    let mut buf = txn_prefix(hash_tag, database_id, txn_id);
     buf.extend_from_slice(TXN_SUFFIX_COMMIT);
     buf
 }
+
+/// Return the id of the database owning the txn key, `None` if it is not a
+/// txn key.
+#[inline]
+pub fn txn_key_database(key: &[u8]) -> Option<u64> {
+    // Skip the hash tag.
+    let database_id = key.strip_prefix(TXN_PREFIX)?.get(1..9)?;
+    Some(u64::from_be_bytes(database_id.try_into().ok()?))
+}
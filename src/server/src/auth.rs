@@ -0,0 +1,263 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::lock::Mutex;
+use log::warn;
+use tonic::Status;
+
+use crate::root::RemoteStore;
+pub(crate) use crate::serverpb::v1::Permission;
+use crate::serverpb::v1::UserDesc;
+use crate::{AuthConfig, Error, Result};
+
+/// The number of random bytes of the secret of tokens.
+const SECRET_LEN: usize = 24;
+
+/// The min interval of reloading users if the user of a token is not found,
+/// so that the users just created are visible soon, without flooding root
+/// with the invalid tokens.
+const RELOAD_ON_MISS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Who issues the request, it is attached to the extensions of requests once
+/// the token is verified.
+#[derive(Debug, Clone)]
+pub(crate) enum Principal {
+    /// The other nodes of this cluster, which hold the cluster token.
+    Cluster,
+    User(Arc<UserDesc>),
+}
+
+impl Principal {
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Principal::Cluster => "cluster",
+            Principal::User(user) => &user.name,
+        }
+    }
+
+    pub(crate) fn is_superuser(&self) -> bool {
+        match self {
+            Principal::Cluster => true,
+            Principal::User(user) => user.superuser,
+        }
+    }
+
+    /// Return the permission granted on the database.
+    pub(crate) fn permission(&self, database_id: u64) -> Permission {
+        match self {
+            Principal::User(user) if !user.superuser => user
+                .permissions
+                .iter()
+                .find(|p| p.database_id == database_id)
+                .map(|p| p.permission())
+                .unwrap_or(Permission::None),
+            _ => Permission::Admin,
+        }
+    }
+}
+
+/// Check whether the principal is granted the permission on the database. All
+/// requests are allowed if the authentication is disabled, in which case no
+/// principal is attached.
+pub(crate) fn check_database(
+    principal: Option<&Principal>,
+    database_id: u64,
+    required: Permission,
+) -> Result<(), Status> {
+    match principal {
+        Some(principal) if principal.permission(database_id) < required => {
+            Err(Status::permission_denied(format!(
+                "user {} requires {} permission on database {database_id}",
+                principal.name(),
+                required.as_str_name(),
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check whether the principal is a superuser, see [`check_database`].
+pub(crate) fn check_superuser(principal: Option<&Principal>) -> Result<(), Status> {
+    match principal {
+        Some(principal) if !principal.is_superuser() => {
+            Err(Status::permission_denied(format!("user {} is not a superuser", principal.name())))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Verify the tokens of requests. The users are loaded from root and cached
+/// for a while, so a deleted user or a reset token takes effect after the
+/// cache expires.
+pub(crate) struct Authenticator {
+    cluster_token: String,
+    cache_ttl: Duration,
+    store: RemoteStore,
+    cache: Mutex<UserCache>,
+}
+
+#[derive(Default)]
+struct UserCache {
+    users: HashMap<String, Arc<UserDesc>>,
+    loaded_at: Option<Instant>,
+}
+
+impl Authenticator {
+    pub(crate) fn new(cfg: &AuthConfig, store: RemoteStore) -> Self {
+        Authenticator {
+            cluster_token: cfg.cluster_token.clone(),
+            cache_ttl: cfg.user_cache_ttl(),
+            store,
+            cache: Mutex::default(),
+        }
+    }
+
+    pub(crate) async fn authenticate(&self, token: &str) -> Result<Principal, Status> {
+        if !self.cluster_token.is_empty()
+            && secure_eq(token.as_bytes(), self.cluster_token.as_bytes())
+        {
+            return Ok(Principal::Cluster);
+        }
+
+        let invalid_token = || Status::unauthenticated("invalid token");
+        let (name, secret) = parse_token(token).ok_or_else(invalid_token)?;
+        let user = self.find_user(name).await?.ok_or_else(invalid_token)?;
+        if !secure_eq(&token_digest(secret), &user.token_digest) {
+            return Err(invalid_token());
+        }
+        Ok(Principal::User(user))
+    }
+
+    async fn find_user(&self, name: &str) -> Result<Option<Arc<UserDesc>>, Status> {
+        let mut cache = self.cache.lock().await;
+        let elapsed = cache.loaded_at.map(|loaded_at| loaded_at.elapsed());
+        let expired = elapsed.map(|v| v >= self.cache_ttl).unwrap_or(true);
+        let missed = !cache.users.contains_key(name)
+            && elapsed.map(|v| v >= RELOAD_ON_MISS_INTERVAL).unwrap_or(true);
+        if expired || missed {
+            let users = self.store.list_users().await.map_err(|err| {
+                warn!("load users: {err:?}");
+                Status::unavailable("load users from root")
+            })?;
+            cache.users =
+                users.into_iter().map(|user| (user.name.clone(), Arc::new(user))).collect();
+            cache.loaded_at = Some(Instant::now());
+        }
+        Ok(cache.users.get(name).cloned())
+    }
+}
+
+/// Generate a token for the user, return the token and its digest. The token
+/// is in the form of `<name>:<secret>`, and only the digest of the secret is
+/// stored.
+pub(crate) fn generate_token(name: &str) -> Result<(String, Vec<u8>)> {
+    let mut secret = [0u8; SECRET_LEN];
+    openssl::rand::rand_bytes(&mut secret)
+        .map_err(|err| Error::InvalidData(format!("crypto: {err}")))?;
+    let secret = URL_SAFE_NO_PAD.encode(secret);
+    let digest = token_digest(&secret);
+    Ok((format!("{name}:{secret}"), digest))
+}
+
+/// The user names are used in tokens, so only the alphanumeric characters,
+/// `_` and `-` are allowed.
+pub(crate) fn validate_user_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(Error::InvalidArgument(format!("invalid user name {name:?}")));
+    }
+    Ok(())
+}
+
+fn parse_token(token: &str) -> Option<(&str, &str)> {
+    let (name, secret) = token.split_once(':')?;
+    if name.is_empty() || secret.is_empty() {
+        return None;
+    }
+    Some((name, secret))
+}
+
+fn token_digest(secret: &str) -> Vec<u8> {
+    openssl::sha::sha256(secret.as_bytes()).to_vec()
+}
+
+/// Compare in constant time, so that the secrets are not leaked by timing.
+fn secure_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && openssl::memcmp::eq(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverpb::v1::DatabasePermission;
+
+    #[test]
+    fn generate_and_verify_token() {
+        let (token, digest) = generate_token("alice").unwrap();
+        let (name, secret) = parse_token(&token).unwrap();
+        assert_eq!(name, "alice");
+        assert!(secure_eq(&token_digest(secret), &digest));
+        assert!(!secure_eq(&token_digest("other"), &digest));
+
+        let (other_token, _) = generate_token("alice").unwrap();
+        assert_ne!(token, other_token);
+
+        assert!(parse_token("alice").is_none());
+        assert!(parse_token(":secret").is_none());
+        assert!(parse_token("alice:").is_none());
+    }
+
+    #[test]
+    fn user_name() {
+        assert!(validate_user_name("alice_01-a").is_ok());
+        assert!(validate_user_name("").is_err());
+        assert!(validate_user_name("alice:bob").is_err());
+        assert!(validate_user_name("alice bob").is_err());
+    }
+
+    #[test]
+    fn principal_permission() {
+        let user = UserDesc {
+            name: "alice".to_owned(),
+            permissions: vec![DatabasePermission {
+                database_id: 1,
+                database: "db".to_owned(),
+                permission: Permission::Write as i32,
+            }],
+            ..Default::default()
+        };
+        let principal = Principal::User(Arc::new(user));
+        assert_eq!(principal.permission(1), Permission::Write);
+        assert_eq!(principal.permission(2), Permission::None);
+        assert!(check_database(Some(&principal), 1, Permission::Read).is_ok());
+        assert!(check_database(Some(&principal), 1, Permission::Admin).is_err());
+        assert!(check_database(Some(&principal), 2, Permission::Read).is_err());
+        assert!(check_superuser(Some(&principal)).is_err());
+
+        let superuser = UserDesc { name: "root".to_owned(), superuser: true, ..Default::default() };
+        let principal = Principal::User(Arc::new(superuser));
+        assert_eq!(principal.permission(2), Permission::Admin);
+        assert!(check_superuser(Some(&principal)).is_ok());
+        assert!(check_superuser(Some(&Principal::Cluster)).is_ok());
+
+        // The authentication is disabled.
+        assert!(check_database(None, 1, Permission::Admin).is_ok());
+        assert!(check_superuser(None).is_ok());
+    }
+}
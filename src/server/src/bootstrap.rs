@@ -13,26 +13,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 use std::vec;
 
 use log::{debug, info, warn};
+use sekas_api::gateway::v1::gateway_server::GatewayServer;
 use sekas_api::server::v1::node_server::NodeServer;
 use sekas_api::server::v1::root_server::RootServer;
 use sekas_api::server::v1::*;
-use sekas_client::RootClient;
+use sekas_client::{RootClient, TokenInterceptor};
+use sekas_rock::time::timestamp_nanos;
 use sekas_runtime::{Executor, Shutdown};
 
+use crate::auth::Authenticator;
 use crate::constants::*;
 use crate::engine::{Engines, StateEngine};
 use crate::node::Node;
-use crate::root::Root;
+use crate::root::{RemoteStore, Root};
 use crate::serverpb::v1::raft_server::RaftServer;
 use crate::serverpb::v1::NodeIdent;
-use crate::service::ProxyServer;
+use crate::service::{ProxyServer, RestService, WorkloadController};
 use crate::transport::TransportManager;
-use crate::{Config, Error, Result, Server};
+use crate::{Config, Error, Result, Server, TlsConfig};
 
 /// The main entrance of sekas server.
 pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()> {
@@ -40,69 +45,257 @@ pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()>
 }
 
 async fn run_in_async(config: Config, shutdown: Shutdown) -> Result<()> {
-    let engines = Engines::open(&config.root_dir, &config.db)?;
+    let engines = Engines::open(&config.root_dir, &config.db, &config.raft)?;
 
     let root_list = if config.init { vec![config.addr.clone()] } else { config.join_list.clone() };
-    let transport_manager = TransportManager::new(root_list, engines.state()).await;
+    let tls = config.tls.as_ref().map(TlsConfig::client_options);
+    if config.auth.enable && config.auth.token().is_none() {
+        return Err(Error::InvalidArgument(
+            "the cluster token is required to enable the authentication".into(),
+        ));
+    }
+    // The redis layer serves the requests with the cluster token and has no
+    // `AUTH`, it would bypass the ACLs of the authenticated users.
+    #[cfg(feature = "layer_redis")]
+    if config.auth.enable && config.redis.addr.is_some() {
+        return Err(Error::InvalidArgument(
+            "the redis layer could not be served if the authentication is enabled".into(),
+        ));
+    }
+    let interceptor = TokenInterceptor::new(config.auth.token())?;
+    let transport_manager =
+        TransportManager::new(root_list, engines.state(), tls, interceptor).await;
     let address_resolver = transport_manager.address_resolver();
     let node = Node::new(config.clone(), engines, transport_manager.clone()).await?;
 
     let ident = bootstrap_or_join_cluster(&config, &node, transport_manager.root_client()).await?;
     node.bootstrap(&ident).await?;
-    let root = Root::new(transport_manager.clone(), &ident, config.clone());
+    let root = Root::new(
+        transport_manager.clone(),
+        &ident,
+        config.clone(),
+        node.key_manager(),
+        node.dynamic_config(),
+    );
     let initial_node_descs = root.bootstrap(&node).await?;
     address_resolver.set_initial_nodes(initial_node_descs);
 
     info!("node {} starts serving requests", ident.node_id);
 
-    let server = Server { node: Arc::new(node), root, address_resolver };
+    let workload = Arc::new(WorkloadController::new(&config.node.workload, node.dynamic_config()));
+    let server = Server { node: Arc::new(node), root, address_resolver, workload };
 
     let proxy_server =
         if config.enable_proxy_service { Some(ProxyServer::new(&transport_manager)) } else { None };
-    bootstrap_services(&config.addr, server, proxy_server, shutdown).await
+    // The redis layer is stopped once the handle is dropped.
+    #[cfg(feature = "layer_redis")]
+    let _redis_handle = match (&config.redis.addr, &proxy_server) {
+        (Some(addr), Some(proxy_server)) => {
+            Some(serve_redis_layer(addr, &config.redis, proxy_server.client.clone()).await?)
+        }
+        _ => None,
+    };
+    let authenticator = if config.auth.enable {
+        let store = RemoteStore::new(transport_manager.clone());
+        Some(Arc::new(Authenticator::new(&config.auth, store)))
+    } else {
+        None
+    };
+    bootstrap_services(
+        &config.addr,
+        config.tls.as_ref(),
+        authenticator,
+        server,
+        proxy_server,
+        shutdown,
+        config.node.shutdown_timeout(),
+    )
+    .await
 }
 
 /// Listen and serve incoming rpc requests.
 async fn bootstrap_services(
     addr: &str,
+    tls: Option<&TlsConfig>,
+    authenticator: Option<Arc<Authenticator>>,
     server: Server,
-    _proxy_server: Option<ProxyServer>,
+    proxy_server: Option<ProxyServer>,
     shutdown: Shutdown,
+    shutdown_timeout: Duration,
 ) -> Result<()> {
     use sekas_runtime::TcpIncoming;
     use tokio::net::TcpListener;
     use tonic::transport::Server;
 
     use crate::service::admin::make_admin_service;
+    use crate::service::{health_main, tls_incoming, tls_reload_main, AuthLayer, TlsAcceptor};
 
     let listener = TcpListener::bind(addr).await?;
     let incoming = TcpIncoming::from_listener(listener, true);
+    let acceptor = tls.map(TlsAcceptor::new).transpose()?.map(Arc::new);
+    let _reload_handle = match (&acceptor, tls.and_then(TlsConfig::reload_interval)) {
+        (Some(acceptor), Some(interval)) => {
+            let acceptor = acceptor.clone();
+            Some(sekas_runtime::spawn(async move { tls_reload_main(acceptor, interval).await }))
+        }
+        _ => None,
+    };
+
+    #[cfg(feature = "layer_etcd")]
+    let etcd_store = {
+        let auth_required = authenticator.is_some();
+        proxy_server.as_ref().map(|proxy_server| {
+            let store = sekas_etcd_proxy::Store::new(proxy_server.client.clone());
+            Arc::new(store.with_auth_required(auth_required))
+        })
+    };
 
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    let _health_handle = {
+        let server = server.clone();
+        sekas_runtime::spawn(async move { health_main(health_reporter, server).await })
+    };
+
+    let node = server.node.clone();
     let builder = Server::builder()
         .accept_http1(true) // Support http1 for admin service.
+        .layer(AuthLayer::new(authenticator))
         .add_service(NodeServer::new(server.clone()))
         .add_service(RaftServer::new(server.clone()))
         .add_service(RootServer::new(server.clone()))
-        .add_service(make_admin_service(server.clone()));
+        .add_service(make_admin_service(server.clone()))
+        .add_service(health_service)
+        .add_optional_service(proxy_server.clone().map(RestService::new))
+        .add_optional_service(proxy_server.map(GatewayServer::new));
 
     #[cfg(feature = "layer_etcd")]
     let builder = {
         builder
-            .add_service(sekas_etcd_proxy::make_etcd_kv_service())
-            .add_service(sekas_etcd_proxy::make_etcd_watch_service())
-            .add_service(sekas_etcd_proxy::make_etcd_lease_service())
+            .add_optional_service(etcd_store.clone().map(sekas_etcd_proxy::make_etcd_kv_service))
+            .add_optional_service(etcd_store.clone().map(sekas_etcd_proxy::make_etcd_watch_service))
+            .add_optional_service(etcd_store.clone().map(sekas_etcd_proxy::make_etcd_lease_service))
+            .add_optional_service(etcd_store.clone().map(sekas_etcd_proxy::make_etcd_auth_service))
+            .add_optional_service(etcd_store.map(|store| {
+                let cluster = Arc::new(EtcdClusterInfo { server: server.clone() });
+                sekas_etcd_proxy::make_etcd_maintenance_service(store, cluster)
+            }))
     };
 
-    let server = builder.serve_with_incoming(incoming);
+    let mut server: Pin<Box<dyn Future<Output = Result<(), tonic::transport::Error>>>> =
+        match acceptor {
+            Some(acceptor) => {
+                Box::pin(builder.serve_with_incoming(tls_incoming(incoming, acceptor)))
+            }
+            None => Box::pin(builder.serve_with_incoming(incoming)),
+        };
 
     sekas_runtime::select! {
-        res = server => { res? }
+        res = &mut server => { return Ok(res?) }
         _ = shutdown => {}
     };
 
+    // Keep serving during the graceful shutdown, the raft messages are required to
+    // transfer the leaderships.
+    sekas_runtime::select! {
+        res = server => { res? }
+        _ = graceful_shutdown(&node, shutdown_timeout) => {}
+    };
+
     Ok(())
 }
 
+/// Stop accepting new requests, transfer the leaderships off, flush engines and
+/// notify root that the node is going down. The engines are always flushed,
+/// the other steps are abandoned once the deadline is exceeded.
+async fn graceful_shutdown(node: &Node, timeout: Duration) {
+    use sekas_runtime::time::{sleep, timeout_at};
+    use tokio::time::Instant;
+
+    info!("node starts shutting down gracefully, timeout {timeout:?}");
+    let deadline = Instant::now() + timeout;
+    node.begin_shutdown();
+    let transfer_leaders = async {
+        loop {
+            let num_leaders = node.transfer_leaders_off().await;
+            if num_leaders == 0 {
+                break;
+            }
+            debug!("wait {num_leaders} leaders to be transferred off");
+            sleep(Duration::from_millis(100)).await;
+        }
+    };
+    if timeout_at(deadline, transfer_leaders).await.is_err() {
+        warn!("transfer leaderships off is not finished in {timeout:?}");
+    }
+    if let Err(err) = node.flush_engines().await {
+        warn!("flush engines: {err:?}");
+    }
+    match timeout_at(deadline, node.notify_shutdown()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => warn!("notify root of shutting down: {err:?}"),
+        Err(_) => warn!("notify root of shutting down is not finished in {timeout:?}"),
+    }
+    info!("node is shut down gracefully");
+}
+
+/// Report the status of sekas cluster to the etcd maintenance service.
+#[cfg(feature = "layer_etcd")]
+struct EtcdClusterInfo {
+    server: Server,
+}
+
+#[cfg(feature = "layer_etcd")]
+#[crate::async_trait]
+impl sekas_etcd_proxy::ClusterInfo for EtcdClusterInfo {
+    async fn cluster_status(&self) -> sekas_etcd_proxy::ClusterStatus {
+        let root = &self.server.root;
+        let mut status = sekas_etcd_proxy::ClusterStatus {
+            member_id: root.current_node_id(),
+            ..Default::default()
+        };
+        match root.info().await {
+            Ok(info) => {
+                status.leader = status.member_id;
+                status.raft_term = info
+                    .groups
+                    .iter()
+                    .flat_map(|g| g.replicas.iter().map(|r| r.term))
+                    .max()
+                    .unwrap_or_default();
+            }
+            Err(Error::NotRootLeader(root_desc, _, leader)) => {
+                let leader =
+                    leader.map(|r| r.node_id).or(root_desc.root_nodes.first().map(|n| n.id));
+                match leader {
+                    Some(node_id) => status.leader = node_id,
+                    None => status.errors.push("the root leader is unknown".to_owned()),
+                }
+            }
+            Err(err) => status.errors.push(format!("read cluster info: {err}")),
+        }
+        status
+    }
+}
+
+/// Listen and serve the redis protocol in background.
+#[cfg(feature = "layer_redis")]
+async fn serve_redis_layer(
+    addr: &str,
+    config: &crate::RedisConfig,
+    client: sekas_client::SekasClient,
+) -> Result<sekas_runtime::JoinHandle<()>> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let opts = sekas_redis_proxy::RedisOptions {
+        database: config.database.clone().unwrap_or_else(|| "redis".to_owned()),
+        collection: config.collection.clone().unwrap_or_else(|| "default".to_owned()),
+    };
+    Ok(sekas_runtime::spawn(async move {
+        if let Err(err) = sekas_redis_proxy::serve(listener, client, opts).await {
+            warn!("the redis layer is stopped: {err}");
+        }
+    }))
+}
+
 async fn bootstrap_or_join_cluster(
     config: &Config,
     node: &Node,
@@ -118,8 +311,16 @@ async fn bootstrap_or_join_cluster(
     Ok(if config.init {
         bootstrap_cluster(node, &config.addr).await?
     } else {
-        try_join_cluster(node, &config.addr, config.join_list.clone(), config.cpu_nums, root_client)
-            .await?
+        try_join_cluster(
+            node,
+            &config.addr,
+            config.join_list.clone(),
+            config.cpu_nums,
+            config.node.capacity_weight,
+            config.node.labels.clone(),
+            root_client,
+        )
+        .await?
     })
 }
 
@@ -128,6 +329,8 @@ async fn try_join_cluster(
     local_addr: &str,
     join_list: Vec<String>,
     cpu_nums: u32,
+    capacity_weight: Option<f64>,
+    labels: Vec<String>,
     root_client: &RootClient,
 ) -> Result<NodeIdent> {
     info!("try join a bootstrapted cluster");
@@ -137,9 +340,18 @@ async fn try_join_cluster(
         return Err(Error::InvalidArgument("the filtered join list is empty".into()));
     }
 
-    let capacity = NodeCapacity { cpu_nums: cpu_nums as f64, ..Default::default() };
+    let capacity = NodeCapacity {
+        cpu_nums: cpu_nums as f64,
+        weight: capacity_weight.unwrap_or_default(),
+        ..Default::default()
+    };
 
-    let req = JoinNodeRequest { addr: local_addr.to_owned(), capacity: Some(capacity) };
+    let req = JoinNodeRequest {
+        addr: local_addr.to_owned(),
+        capacity: Some(capacity),
+        binary_version: BINARY_VERSION,
+        labels,
+    };
 
     let mut backoff: u64 = 1;
     loop {
@@ -147,8 +359,13 @@ async fn try_join_cluster(
         match root_client.join_node(req.clone()).await {
             Ok(res) => {
                 debug!("issue join request to root server success");
-                let node_ident =
-                    save_node_ident(node.state_engine(), res.cluster_id, res.node_id).await;
+                let node_ident = save_node_ident(
+                    node.state_engine(),
+                    res.cluster_id,
+                    res.node_id,
+                    res.incarnation,
+                )
+                .await;
                 node.update_root(res.root.unwrap_or_default()).await?;
                 return node_ident;
             }
@@ -170,7 +387,11 @@ pub(crate) async fn bootstrap_cluster(node: &Node, addr: &str) -> Result<NodeIde
     let state_engine = node.state_engine();
     let cluster_id = vec![];
 
-    let ident = save_node_ident(state_engine, cluster_id.to_owned(), FIRST_NODE_ID).await?;
+    // The first node is not joined via root, so it assigns the incarnation itself,
+    // which is recorded by root during bootstrapping.
+    let incarnation = timestamp_nanos();
+    let ident =
+        save_node_ident(state_engine, cluster_id.to_owned(), FIRST_NODE_ID, incarnation).await?;
 
     info!("bootstrap cluster successfully");
 
@@ -181,11 +402,12 @@ async fn save_node_ident(
     state_engine: &StateEngine,
     cluster_id: Vec<u8>,
     node_id: u64,
+    incarnation: u64,
 ) -> Result<NodeIdent> {
-    let node_ident = NodeIdent { cluster_id, node_id };
+    let node_ident = NodeIdent { cluster_id, node_id, incarnation };
     state_engine.save_ident(&node_ident).await?;
 
-    info!("save node ident, node id {}", node_id);
+    info!("save node ident, node id {node_id}, incarnation {incarnation}");
 
     Ok(node_ident)
 }
@@ -199,7 +421,11 @@ async fn write_initial_cluster_data(node: &Node, addr: &str) -> Result<()> {
     node.create_replica(INIT_USER_REPLICA_ID, sekas_schema::system::init_group()).await?;
 
     let root_node = NodeDesc { id: FIRST_NODE_ID, addr: addr.to_owned(), ..Default::default() };
-    let root_desc = RootDesc { epoch: INITIAL_EPOCH, root_nodes: vec![root_node] };
+    let root_desc = RootDesc {
+        epoch: INITIAL_EPOCH,
+        root_nodes: vec![root_node],
+        cluster_version: BINARY_VERSION,
+    };
     node.update_root(root_desc).await?;
 
     Ok(())
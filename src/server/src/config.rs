@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -51,6 +52,21 @@ pub struct Config {
 
     #[serde(default)]
     pub db: DbConfig,
+
+    #[serde(default)]
+    pub redis: RedisConfig,
+
+    #[serde(default)]
+    pub trace: TraceConfig,
+
+    /// Serve and connect to the other nodes with TLS.
+    ///
+    /// Default: disabled
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+
+    #[serde(default)]
+    pub auth: AuthConfig,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -65,11 +81,41 @@ pub struct NodeConfig {
     /// Default: 256.
     pub shard_gc_keys: usize,
 
+    /// The capacity weight of this node, the root places replicas and leaders
+    /// in proportion to the weights of nodes. The weight of a node is its
+    /// `cpu_nums` scaled by this factor. It is reported when the node joins
+    /// the cluster.
+    ///
+    /// Default: None, the weight is the `cpu_nums`.
+    pub capacity_weight: Option<f64>,
+
+    /// The labels of this node, it is reported when the node joins the
+    /// cluster. The label `system` dedicates this node to the root group,
+    /// isolating the metadata from the user workloads.
+    ///
+    /// Default: no labels
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// The deadline of the graceful shutdown, during which the node stops
+    /// accepting new requests, transfers the leaderships off, flushes engines
+    /// and notifies root, before exiting.
+    ///
+    /// Default: 30
+    #[serde(default)]
+    pub shutdown_timeout_sec: Option<u64>,
+
     #[serde(default)]
     pub replica: ReplicaConfig,
 
     #[serde(default)]
     pub engine: EngineConfig,
+
+    #[serde(default)]
+    pub workload: WorkloadConfig,
+
+    #[serde(default)]
+    pub resource: ResourceConfig,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -86,6 +132,14 @@ pub struct ReplicaConfig {
     /// Default: 64MB.
     pub snap_file_size: u64,
 
+    /// Log the shard reads, writes and txn steps if it exceeds the specified
+    /// threshold, along with the durations of latch waiting, raft proposing
+    /// and applying.
+    ///
+    /// Default: disabled
+    #[serde(default)]
+    pub slow_op_threshold_ms: Option<u64>,
+
     #[serde(skip)]
     pub testing_knobs: ReplicaTestingKnobs,
 }
@@ -96,6 +150,184 @@ pub struct EngineConfig {
     ///
     /// Default: disabled
     pub engine_slow_io_threshold_ms: Option<u64>,
+
+    /// The file holds the 256-bit master key in hex, which encrypts the data
+    /// keys of encrypted collections. The encryption is available only if all
+    /// nodes share the same master key.
+    ///
+    /// Default: disabled
+    pub encryption_key_file: Option<String>,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct WorkloadConfig {
+    /// Log the group requests if it exceeds the specified threshold, along with
+    /// the workload tag.
+    ///
+    /// Default: disabled
+    pub slow_request_threshold_ms: Option<u64>,
+
+    /// The limit number of group requests per second of each workload tag, the
+    /// requests exceeding the limit are rejected with `ResourceExhausted`. The
+    /// requests without tag are limited by the key `default`.
+    ///
+    /// Default: no limits
+    #[serde(default)]
+    pub rate_limits: HashMap<String, u64>,
+
+    /// The max size of the encoded batch request in bytes, the larger requests
+    /// are rejected and never admitted.
+    ///
+    /// Default: 0, no limit
+    #[serde(default)]
+    pub max_request_bytes: u64,
+
+    /// The limit number of batch requests per second of each client, which is
+    /// identified by the user of its token, or the remote address if the
+    /// authentication is disabled. The requests forwarded by the proxies are
+    /// counted against the users they are issued for, and the other requests
+    /// from the nodes of this cluster are not limited.
+    ///
+    /// Default: 0, no limit
+    #[serde(default)]
+    pub client_requests_per_sec: u64,
+
+    /// The limit bytes of batch requests per second of each client, see
+    /// `client_requests_per_sec`.
+    ///
+    /// Default: 0, no limit
+    #[serde(default)]
+    pub client_bytes_per_sec: u64,
+
+    /// The max number of distinct workload tags reported in the metrics, the
+    /// tags beyond it are reported as `other`. The tags configured in
+    /// `rate_limits` are always reported.
+    ///
+    /// Default: 64
+    pub max_metric_app_tags: Option<usize>,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct ResourceConfig {
+    /// The disk bandwidth of the node in bytes per second, which is shared by
+    /// the foreground requests and the background jobs, eg. moving shards and
+    /// gc. The foreground requests are never throttled, and the background
+    /// jobs use the remaining bandwidth.
+    ///
+    /// Default: 0, no limit
+    #[serde(default)]
+    pub io_bytes_per_sec: u64,
+
+    /// The ratio of bandwidth reserved for the background jobs, so that they
+    /// still make progress under the heavy foreground requests.
+    ///
+    /// Default: 0.1
+    #[serde(default)]
+    pub min_background_io_ratio: Option<f64>,
+
+    /// The limit number of concurrent background jobs of the node.
+    ///
+    /// Default: 0, no limit
+    #[serde(default)]
+    pub max_background_jobs: usize,
+}
+
+/// The redis protocol layer, it only takes effect if the feature `layer_redis`
+/// is built and the proxy service is enabled. The redis layer does not support
+/// `AUTH`, so it is refused to start if the authentication is enabled.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct RedisConfig {
+    /// The listening address of the redis protocol.
+    ///
+    /// Default: disabled
+    pub addr: Option<String>,
+
+    /// The database to store the redis keys, it is created if not exists.
+    ///
+    /// Default: "redis"
+    pub database: Option<String>,
+
+    /// The collection to store the redis keys, it is created if not exists.
+    ///
+    /// Default: "default"
+    pub collection: Option<String>,
+}
+
+/// Export the spans of requests to an OpenTelemetry collector.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraceConfig {
+    /// The OTLP (gRPC) endpoint of the collector, eg. `http://127.0.0.1:4317`.
+    ///
+    /// Default: disabled
+    pub otlp_endpoint: Option<String>,
+
+    /// The `service.name` of the exported spans.
+    ///
+    /// Default: "sekas"
+    pub service_name: String,
+
+    /// The ratio of the traces sampled, the traces started by upstream follow
+    /// the sampling decision of the parent.
+    ///
+    /// Default: 0.01
+    pub sample_ratio: f64,
+}
+
+/// Authenticate the requests by tokens and check the permissions of users.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Reject the requests without a valid token.
+    ///
+    /// Default: false
+    pub enable: bool,
+
+    /// The token shared by the nodes of this cluster, it is attached to the
+    /// intra-cluster requests and is granted all permissions. It is sent as
+    /// long as it is not empty, so that the authentication could be enabled
+    /// node by node.
+    ///
+    /// Default: ""
+    pub cluster_token: String,
+
+    /// The duration that the users loaded from root are cached.
+    ///
+    /// Default: 10
+    pub user_cache_ttl_sec: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// The PEM encoded certificate chain of this node, it is presented to
+    /// both the clients and the other nodes.
+    pub cert: PathBuf,
+
+    /// The PEM encoded private key of `cert`.
+    pub key: PathBuf,
+
+    /// The PEM encoded CA certificates to verify the other nodes, and the
+    /// clients if `mutual` is enabled.
+    pub ca: PathBuf,
+
+    /// Require the clients to present a certificate signed by `ca`.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub mutual: bool,
+
+    /// The name to verify the certificates of the other nodes against.
+    ///
+    /// Default: None, the host of the node address is used.
+    #[serde(default)]
+    pub domain_name: Option<String>,
+
+    /// The interval of checking the certificate files, the changed
+    /// certificates are reloaded without restarting. 0 means disabled.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub reload_interval_sec: u64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -113,6 +345,12 @@ pub struct DbConfig {
     // block & block cache cache related configs
     pub block_size: usize,
     pub block_cache_size: usize,
+    /// The ratio of the total memory used by the block cache, which is shared
+    /// by all groups of the node. It overrides `block_cache_size` if set.
+    ///
+    /// Default: None
+    #[serde(default)]
+    pub block_cache_memory_ratio: Option<f64>,
 
     // write buffer related configs
     pub write_buffer_size: usize,
@@ -129,6 +367,9 @@ pub struct DbConfig {
     pub max_bytes_for_level_multiplier: f64,
     pub max_compaction_bytes: u64,
     pub level_compaction_dynamic_level_bytes: bool,
+    /// The limit number of concurrent background compactions, -1 means it is
+    /// derived from `max_background_jobs`.
+    pub max_background_compactions: i32,
 
     // write slowdown related configs
     pub level0_stop_write_trigger: i32,
@@ -140,6 +381,9 @@ pub struct DbConfig {
     pub rate_limiter_bytes_per_sec: i64,
     pub rate_limiter_refill_period: i64,
     pub rate_limiter_auto_tuned: bool,
+    /// The fairness between the low and high priority requests (compactions
+    /// and flushes) of the rate limiter.
+    pub rate_limiter_fairness: i32,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -191,6 +435,111 @@ pub struct RaftConfig {
     /// Default: false
     pub enable_log_recycle: bool,
 
+    /// The dir of raft logs. Placing it on a separate device keeps the fsync
+    /// heavy log traffic away from the compactions of the data engine.
+    ///
+    /// Default: `{root_dir}/log`
+    #[serde(default)]
+    pub log_dir: Option<PathBuf>,
+
+    /// Sync the raft logs to disk before acknowledging the writes. The sync
+    /// policy of the data engine is configured independently in `db`.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub sync_log: bool,
+
+    /// Sync the raft log files incrementally in the background whenever the
+    /// specified bytes are written, 0 means use the default of raft engine.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub log_bytes_per_sync: u64,
+
+    /// Limit the number of concurrent outgoing snapshots of this node, the
+    /// exceeded requests are rejected and retried by the receivers later. 0
+    /// means no limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub max_sending_snapshots: usize,
+
+    /// Limit the total bandwidth of outgoing snapshots of this node, in bytes
+    /// per second. 0 means no limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub snapshot_send_bytes_per_sec: u64,
+
+    /// Serve the reads of leader without a read index round trip if the
+    /// leader lease is held. It requires the clock skew between nodes is
+    /// bounded by `lease_clock_skew_ms`.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub enable_lease_read: bool,
+
+    /// The safety margin of clock skew between nodes, it is excluded from the
+    /// leader lease.
+    ///
+    /// Default: `tick_interval_ms`
+    #[serde(default)]
+    pub lease_clock_skew_ms: Option<u64>,
+
+    /// Merge the write batches of the queued independent proposals into a
+    /// single raft entry, until it exceeds the specified bytes. 0 means each
+    /// proposal is proposed in its own entry.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub max_proposal_batch_bytes: u64,
+
+    /// The max time that a proposal waits for other proposals to merge, in
+    /// micros. 0 means only the proposals already queued are merged.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub max_proposal_batch_delay_us: u64,
+
+    /// The max time that a read index request waits for other read index
+    /// requests to share a single quorum confirmation, in micros. 0 means only
+    /// the requests already queued share the confirmation.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub max_read_index_batch_delay_us: u64,
+
+    /// Issue the read index round without waiting once the queued read index
+    /// requests reach the limit. 0 means no limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub max_read_index_batch_size: u64,
+
+    /// Reject new writes of the group with `GroupBusy` once the number of
+    /// raft entries proposed but not committed exceeds the limit. 0 means no
+    /// limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub max_uncommitted_entries: u64,
+
+    /// Reject new writes of the group with `GroupBusy` once the number of
+    /// raft entries committed but not applied exceeds the limit. 0 means no
+    /// limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub max_unapplied_entries: u64,
+
+    /// The replicas recovered after the node restarts don't campaign or accept
+    /// the leadership until the lag of their applied index behind the leader
+    /// is under the threshold.
+    ///
+    /// Default: 1024
+    #[serde(default)]
+    pub startup_catch_up_lag: Option<u64>,
+
     #[serde(skip)]
     pub testing_knobs: RaftTestingKnobs,
 }
@@ -204,8 +553,171 @@ pub struct RootConfig {
     pub enable_leader_balance: bool,
     pub liveness_threshold_sec: u64,
     pub heartbeat_timeout_sec: u64,
+    /// The max number of nodes the root sends heartbeats to concurrently, so
+    /// that a large cluster doesn't flood the connections at once.
+    ///
+    /// Default: 64
+    #[serde(default)]
+    pub heartbeat_parallelism: Option<usize>,
+    /// The max number of the background jobs running concurrently, such as
+    /// creating and purging collections.
+    ///
+    /// Default: 4
+    #[serde(default)]
+    pub job_parallelism: Option<usize>,
+    /// The policy to measure the load of nodes when balancing the replicas,
+    /// see [`BalancePolicyKind`].
+    ///
+    /// Default: "replica_count"
+    #[serde(default)]
+    pub balance_policy: BalancePolicyKind,
     pub schedule_interval_sec: u64,
     pub max_create_group_retry_before_rollback: u64,
+    /// The versions older than the retention are allowed to be collected,
+    /// unless they are held by GC leases.
+    pub gc_retention_sec: u64,
+    /// Encrypt all new collections at rest, it requires the encryption key
+    /// file.
+    pub encrypt_all_collections: bool,
+    /// The interval to rotate the data key, the values are encrypted by the
+    /// latest data key.
+    pub data_key_rotation_sec: u64,
+    /// The max keys copied per second by the shards moving into a node, 0
+    /// means no limit. It can be adjusted at runtime via the admin service.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub move_shard_keys_per_sec: u64,
+    /// The max bytes copied per second by the shards moving into a node, 0
+    /// means no limit. It can be adjusted at runtime via the admin service.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub move_shard_bytes_per_sec: u64,
+    /// The copying of a moving shard backs off once the latency of pulling
+    /// from the source group exceeds it, so that the foreground requests of
+    /// the source group take priority. 0 means never back off.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub move_shard_backoff_latency_ms: u64,
+    /// The shards whose logical bytes, the sum of the size of live keys and
+    /// values, exceed it are reported as split candidates. 0 means no limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub shard_split_threshold_bytes: u64,
+    /// The shards whose live keys exceed it are reported as split candidates.
+    /// 0 means no limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub shard_split_threshold_keys: u64,
+    /// The shard is split at its hottest key once the sampled accesses of it
+    /// exceed it and dominate its group, see `hot_shard_split_ratio`. 0 means
+    /// the hot shards are never split.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub hot_shard_split_min_accesses: u64,
+    /// The min ratio of the accesses of a shard to the accesses of its group,
+    /// to treat the shard as dominating the group.
+    ///
+    /// Default: 0.8
+    #[serde(default)]
+    pub hot_shard_split_ratio: Option<f64>,
+    /// The replicas whose applied index falls behind the committed index of
+    /// the group leader by more than it are lagging, see
+    /// `replica_apply_lag_persist_sec`. 0 means the lag is never checked.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub replica_apply_lag_threshold: u64,
+    /// The replicas lagging longer than it are flagged in the diagnosis.
+    ///
+    /// Default: 60
+    #[serde(default)]
+    pub replica_apply_lag_persist_sec: Option<u64>,
+    /// Don't transfer the leadership to the flagged lagging replicas when
+    /// balancing the leaders.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub exclude_lagging_leader_targets: bool,
+    /// The audit records of admin and DDL operations older than it are
+    /// purged. 0 means the records are kept forever.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub audit_retention_sec: u64,
+    /// The dropped databases and collections are kept in the trash within it,
+    /// and could be recovered before they are purged. 0 means they are purged
+    /// immediately.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub trash_retention_sec: u64,
+    /// The results of the DDL operations carrying idempotency tokens are kept
+    /// within it, the retries after it are executed again.
+    ///
+    /// Default: 86400
+    #[serde(default)]
+    pub idempotency_retention_sec: Option<u64>,
+    /// The databases and collections created by the root after the cluster is
+    /// bootstrapped, see [`BootstrapSpec`].
+    ///
+    /// Default: None
+    #[serde(default)]
+    pub bootstrap_spec: Option<BootstrapSpec>,
+}
+
+/// The goals of balancing the replicas between nodes, the load of each node is
+/// kept in proportion to its capacity weight.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalancePolicyKind {
+    /// Balance the number of replicas.
+    #[default]
+    ReplicaCount,
+    /// Balance the used disk space.
+    Size,
+    /// Balance the read and write QPS.
+    Load,
+}
+
+/// The declarative spec of the initial databases and collections of a
+/// cluster, it is applied by the root leader after bootstrapping. The
+/// existing databases and collections are skipped, so that the same spec
+/// could be applied again after restarting.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BootstrapSpec {
+    /// The replicas of each group expected by the spec, it must be equal to
+    /// `root.replicas_per_group` if set.
+    #[serde(default)]
+    pub replication_factor: Option<usize>,
+    #[serde(default)]
+    pub databases: Vec<DatabaseSpec>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DatabaseSpec {
+    pub name: String,
+    #[serde(default)]
+    pub collections: Vec<CollectionSpec>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CollectionSpec {
+    pub name: String,
+    /// The keys to split the collection at in advance, each of them is the
+    /// start key of a shard. They must be in ascending order.
+    #[serde(default)]
+    pub split_keys: Vec<String>,
+    /// The replicas of the shards expected by the collection. The shards are
+    /// replicated by groups, so it must be equal to `root.replicas_per_group`
+    /// if set.
+    #[serde(default)]
+    pub replication_factor: Option<usize>,
 }
 
 impl Default for NodeConfig {
@@ -213,24 +725,45 @@ impl Default for NodeConfig {
         NodeConfig {
             shard_chunk_size: 64 * 1024 * 1024,
             shard_gc_keys: 256,
+            capacity_weight: None,
+            labels: vec![],
+            shutdown_timeout_sec: None,
             replica: ReplicaConfig::default(),
             engine: EngineConfig::default(),
+            workload: WorkloadConfig::default(),
+            resource: ResourceConfig::default(),
         }
     }
 }
 
+impl NodeConfig {
+    #[inline]
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_sec.unwrap_or(30))
+    }
+}
+
 impl Default for ReplicaConfig {
     fn default() -> Self {
         ReplicaConfig {
             snap_file_size: 64 * 1024 * 1024 * 1024,
+            slow_op_threshold_ms: None,
             testing_knobs: ReplicaTestingKnobs::default(),
         }
     }
 }
 
 impl DbConfig {
-    pub fn to_options(&self) -> rocksdb::Options {
-        use rocksdb::{BlockBasedIndexType, BlockBasedOptions, Cache, Options};
+    /// Return the capacity of the block cache shared by all groups.
+    pub fn block_cache_capacity(&self) -> usize {
+        match self.block_cache_memory_ratio {
+            Some(ratio) => (total_memory() as f64 * ratio.clamp(0.0, 1.0)) as usize,
+            None => self.block_cache_size,
+        }
+    }
+
+    pub fn to_options(&self, block_cache: &rocksdb::Cache) -> rocksdb::Options {
+        use rocksdb::{BlockBasedIndexType, BlockBasedOptions, Options};
 
         let cfg = self;
 
@@ -260,6 +793,8 @@ impl DbConfig {
         opts.set_max_bytes_for_level_multiplier(cfg.max_bytes_for_level_multiplier);
         opts.set_max_compaction_bytes(cfg.max_compaction_bytes);
         opts.set_level_compaction_dynamic_level_bytes(true);
+        #[allow(deprecated)]
+        opts.set_max_background_compactions(cfg.max_background_compactions);
 
         opts.set_level_zero_slowdown_writes_trigger(cfg.level0_slowdown_writes_trigger);
         opts.set_level_zero_stop_writes_trigger(cfg.level0_slowdown_writes_trigger);
@@ -269,16 +804,14 @@ impl DbConfig {
         opts.set_auto_tuned_ratelimiter(
             cfg.rate_limiter_bytes_per_sec,
             cfg.rate_limiter_refill_period,
-            10,
+            cfg.rate_limiter_fairness,
             cfg.rate_limiter_auto_tuned,
         );
 
-        let cache = Cache::new_lru_cache(cfg.block_cache_size);
-
         let mut blk_opts = BlockBasedOptions::default();
         blk_opts.set_index_type(BlockBasedIndexType::TwoLevelIndexSearch);
         blk_opts.set_block_size(cfg.block_size);
-        blk_opts.set_block_cache(&cache);
+        blk_opts.set_block_cache(block_cache);
         blk_opts.set_cache_index_and_filter_blocks(true);
         blk_opts.set_bloom_filter(10.0, false);
         opts.set_block_based_table_factory(&blk_opts);
@@ -302,6 +835,7 @@ impl Default for DbConfig {
 
             block_size: 4 << 10,
             block_cache_size: adaptive_block_cache_size(),
+            block_cache_memory_ratio: None,
             write_buffer_size: 64 << 20,
             max_write_buffer_number: 5,
             min_write_buffer_number_to_merge: 1,
@@ -323,6 +857,7 @@ impl Default for DbConfig {
             max_bytes_for_level_multiplier: 10.0,
             max_compaction_bytes: 0,
             level_compaction_dynamic_level_bytes: true,
+            max_background_compactions: -1,
 
             level0_stop_write_trigger: 36,
             level0_slowdown_writes_trigger: 20,
@@ -332,17 +867,50 @@ impl Default for DbConfig {
             rate_limiter_bytes_per_sec: 10 << 30,
             rate_limiter_refill_period: 100_000,
             rate_limiter_auto_tuned: true,
+            rate_limiter_fairness: 10,
         }
     }
 }
 
 impl RaftConfig {
+    /// Return the duration of leader lease, `None` if the lease reads are
+    /// disabled or the clock skew exceeds the election timeout.
+    pub fn lease_duration(&self) -> Option<Duration> {
+        if !self.enable_lease_read {
+            return None;
+        }
+        let election_timeout = self.election_tick as u64 * self.tick_interval_ms;
+        let clock_skew = self.lease_clock_skew_ms.unwrap_or(self.tick_interval_ms);
+        election_timeout.checked_sub(clock_skew).filter(|v| *v > 0).map(Duration::from_millis)
+    }
+
+    /// Return the max applied lag of a replica recovered after restart to
+    /// finish catching up with the leader.
+    #[inline]
+    pub fn startup_catch_up_lag(&self) -> u64 {
+        self.startup_catch_up_lag.unwrap_or(1024)
+    }
+
+    /// Return the max time that a proposal waits in the proposal batch.
+    #[inline]
+    pub fn proposal_batch_delay(&self) -> Duration {
+        Duration::from_micros(self.max_proposal_batch_delay_us)
+    }
+
+    /// Return the max time that a read index request waits in the batch.
+    #[inline]
+    pub fn read_index_batch_delay(&self) -> Duration {
+        Duration::from_micros(self.max_read_index_batch_delay_us)
+    }
+
     pub(crate) fn to_raft_config(&self, replica_id: u64, applied: u64) -> raft::Config {
         raft::Config {
             id: replica_id,
             election_tick: self.election_tick,
             heartbeat_tick: 1,
             applied,
+            // The pre vote prevents the partitioned replicas from disrupting the
+            // cluster, and the check quorum is required by the leader lease.
             pre_vote: true,
             batch_append: true,
             check_quorum: true,
@@ -366,6 +934,20 @@ impl Default for RaftConfig {
             max_inflight_msgs: 10 * 1000,
             engine_slow_io_threshold_ms: None,
             enable_log_recycle: false,
+            log_dir: None,
+            sync_log: false,
+            log_bytes_per_sync: 0,
+            max_sending_snapshots: 0,
+            snapshot_send_bytes_per_sec: 0,
+            enable_lease_read: false,
+            lease_clock_skew_ms: None,
+            max_proposal_batch_bytes: 0,
+            max_proposal_batch_delay_us: 0,
+            max_read_index_batch_delay_us: 0,
+            max_read_index_batch_size: 0,
+            max_uncommitted_entries: 0,
+            max_unapplied_entries: 0,
+            startup_catch_up_lag: None,
             testing_knobs: RaftTestingKnobs::default(),
         }
     }
@@ -375,6 +957,84 @@ impl RootConfig {
     pub fn heartbeat_interval(&self) -> Duration {
         Duration::from_secs(self.liveness_threshold_sec - self.heartbeat_timeout_sec)
     }
+
+    pub fn heartbeat_parallelism(&self) -> usize {
+        self.heartbeat_parallelism.unwrap_or(64).max(1)
+    }
+
+    pub fn job_parallelism(&self) -> usize {
+        self.job_parallelism.unwrap_or(4).max(1)
+    }
+
+    pub fn idempotency_retention_sec(&self) -> u64 {
+        self.idempotency_retention_sec.unwrap_or(24 * 60 * 60)
+    }
+
+    pub fn hot_shard_split_ratio(&self) -> f64 {
+        self.hot_shard_split_ratio.unwrap_or(0.8)
+    }
+
+    pub fn replica_apply_lag_persist(&self) -> Duration {
+        Duration::from_secs(self.replica_apply_lag_persist_sec.unwrap_or(60))
+    }
+
+    /// Return whether the shard exceeds the split thresholds.
+    pub fn exceeds_split_threshold(&self, num_keys: u64, logical_bytes: u64) -> bool {
+        (self.shard_split_threshold_bytes != 0 && logical_bytes > self.shard_split_threshold_bytes)
+            || (self.shard_split_threshold_keys != 0 && num_keys > self.shard_split_threshold_keys)
+    }
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        TraceConfig { otlp_endpoint: None, service_name: "sekas".to_owned(), sample_ratio: 0.01 }
+    }
+}
+
+impl AuthConfig {
+    /// The token attached to the requests sent to the other nodes.
+    #[inline]
+    pub fn token(&self) -> Option<&str> {
+        if self.cluster_token.is_empty() {
+            None
+        } else {
+            Some(&self.cluster_token)
+        }
+    }
+
+    #[inline]
+    pub fn user_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.user_cache_ttl_sec)
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig { enable: false, cluster_token: String::new(), user_cache_ttl_sec: 10 }
+    }
+}
+
+impl TlsConfig {
+    #[inline]
+    pub fn reload_interval(&self) -> Option<Duration> {
+        if self.reload_interval_sec == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.reload_interval_sec))
+        }
+    }
+
+    /// The options to connect to the other nodes, this node presents its own
+    /// certificate so that the intra-cluster traffic is mutually authenticated.
+    pub fn client_options(&self) -> sekas_client::TlsOptions {
+        sekas_client::TlsOptions {
+            ca_cert: self.ca.clone(),
+            client_cert: Some(self.cert.clone()),
+            client_key: Some(self.key.clone()),
+            domain_name: self.domain_name.clone(),
+            reload_interval: self.reload_interval(),
+        }
+    }
 }
 
 impl Default for RootConfig {
@@ -387,8 +1047,28 @@ impl Default for RootConfig {
             enable_leader_balance: true,
             liveness_threshold_sec: 30,
             heartbeat_timeout_sec: 4,
+            heartbeat_parallelism: None,
+            job_parallelism: None,
+            balance_policy: BalancePolicyKind::ReplicaCount,
             schedule_interval_sec: 3,
             max_create_group_retry_before_rollback: 10,
+            gc_retention_sec: 600,
+            encrypt_all_collections: false,
+            data_key_rotation_sec: 7 * 24 * 60 * 60,
+            move_shard_keys_per_sec: 0,
+            move_shard_bytes_per_sec: 0,
+            move_shard_backoff_latency_ms: 0,
+            shard_split_threshold_bytes: 0,
+            shard_split_threshold_keys: 0,
+            hot_shard_split_min_accesses: 0,
+            hot_shard_split_ratio: None,
+            replica_apply_lag_threshold: 0,
+            replica_apply_lag_persist_sec: None,
+            exclude_lagging_leader_targets: false,
+            audit_retention_sec: 0,
+            trash_retention_sec: 0,
+            idempotency_retention_sec: None,
+            bootstrap_spec: None,
         }
     }
 }
@@ -398,9 +1078,13 @@ fn adaptive_block_cache_size() -> usize {
         return 32 << 20;
     }
 
+    (total_memory() / 2) as usize
+}
+
+fn total_memory() -> u64 {
     use sysinfo::{RefreshKind, System, SystemExt};
     let info = System::new_with_specifics(RefreshKind::new().with_memory());
-    (info.total_memory() / 2) as usize
+    info.total_memory()
 }
 
 fn adaptive_max_background_jobs() -> i32 {
@@ -19,3 +19,22 @@ pub use sekas_schema::{
 };
 
 pub const REPLICA_PER_GROUP: usize = 3;
+
+/// The node label of the system tier. The replicas of the root group, which
+/// serves the system collections including txn records, are constrained to
+/// the system tier nodes if any exists, and the replicas of user groups avoid
+/// them.
+pub const SYSTEM_TIER_LABEL: &str = "system";
+
+/// The cluster version of the initial replicated features.
+pub const CLUSTER_VERSION_INITIAL: u64 = 1;
+/// The cluster version which enables ingesting pre-sorted files.
+pub const CLUSTER_VERSION_INGEST: u64 = 2;
+/// The cluster version which enables encrypting collections at rest.
+pub const CLUSTER_VERSION_ENCRYPTION: u64 = 3;
+/// The cluster version which enables the users and their permissions.
+pub const CLUSTER_VERSION_AUTH: u64 = 4;
+/// The max cluster version supported by this binary. A new replicated feature
+/// should bump it, and the feature is only enabled once all nodes support it
+/// and the cluster version is bumped.
+pub const BINARY_VERSION: u64 = CLUSTER_VERSION_AUTH;
@@ -0,0 +1,332 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_api::server::v1::ConfigEntry;
+
+use crate::{Config, Error, Result};
+
+/// The options which could be adjusted at runtime via `set_config`, without
+/// restarting the servers. The values are persisted by root and pushed to
+/// nodes via heartbeats.
+pub const HOT_RELOADABLE_OPTIONS: &[&str] = &[
+    "root.enable_group_balance",
+    "root.enable_replica_balance",
+    "root.enable_shard_balance",
+    "root.enable_leader_balance",
+    "root.liveness_threshold_sec",
+    "root.heartbeat_timeout_sec",
+    "root.schedule_interval_sec",
+    "replica.slow_op_threshold_ms",
+    "workload.max_request_bytes",
+    "workload.client_requests_per_sec",
+    "workload.client_bytes_per_sec",
+];
+
+/// The hot reloadable options of [`Config`], it is initialized by the static
+/// config and updated once the node receives the new values.
+#[derive(Debug)]
+pub struct DynamicConfig {
+    enable_group_balance: AtomicBool,
+    enable_replica_balance: AtomicBool,
+    enable_shard_balance: AtomicBool,
+    enable_leader_balance: AtomicBool,
+    liveness_threshold_sec: AtomicU64,
+    heartbeat_timeout_sec: AtomicU64,
+    schedule_interval_sec: AtomicU64,
+    /// 0 means the slow ops are not logged.
+    slow_op_threshold_ms: AtomicU64,
+    /// 0 means no limit, and so do the client quotas.
+    max_request_bytes: AtomicU64,
+    client_requests_per_sec: AtomicU64,
+    client_bytes_per_sec: AtomicU64,
+}
+
+impl DynamicConfig {
+    pub fn new(cfg: &Config) -> Self {
+        DynamicConfig {
+            enable_group_balance: AtomicBool::new(cfg.root.enable_group_balance),
+            enable_replica_balance: AtomicBool::new(cfg.root.enable_replica_balance),
+            enable_shard_balance: AtomicBool::new(cfg.root.enable_shard_balance),
+            enable_leader_balance: AtomicBool::new(cfg.root.enable_leader_balance),
+            liveness_threshold_sec: AtomicU64::new(cfg.root.liveness_threshold_sec),
+            heartbeat_timeout_sec: AtomicU64::new(cfg.root.heartbeat_timeout_sec),
+            schedule_interval_sec: AtomicU64::new(cfg.root.schedule_interval_sec),
+            slow_op_threshold_ms: AtomicU64::new(
+                cfg.node.replica.slow_op_threshold_ms.unwrap_or(0),
+            ),
+            max_request_bytes: AtomicU64::new(cfg.node.workload.max_request_bytes),
+            client_requests_per_sec: AtomicU64::new(cfg.node.workload.client_requests_per_sec),
+            client_bytes_per_sec: AtomicU64::new(cfg.node.workload.client_bytes_per_sec),
+        }
+    }
+
+    /// Set the option, the value is validated before applying.
+    pub fn set(&self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "root.enable_group_balance" => store_bool(&self.enable_group_balance, key, value),
+            "root.enable_replica_balance" => store_bool(&self.enable_replica_balance, key, value),
+            "root.enable_shard_balance" => store_bool(&self.enable_shard_balance, key, value),
+            "root.enable_leader_balance" => store_bool(&self.enable_leader_balance, key, value),
+            "root.liveness_threshold_sec" => {
+                let liveness_threshold_sec = parse_u64(key, value)?;
+                if liveness_threshold_sec <= self.heartbeat_timeout_sec.load(Ordering::Relaxed) {
+                    return Err(Error::InvalidArgument(format!(
+                        "{key} should be greater than root.heartbeat_timeout_sec"
+                    )));
+                }
+                self.liveness_threshold_sec.store(liveness_threshold_sec, Ordering::Relaxed);
+                Ok(())
+            }
+            "root.heartbeat_timeout_sec" => {
+                let heartbeat_timeout_sec = parse_u64(key, value)?;
+                if heartbeat_timeout_sec >= self.liveness_threshold_sec.load(Ordering::Relaxed) {
+                    return Err(Error::InvalidArgument(format!(
+                        "{key} should be less than root.liveness_threshold_sec"
+                    )));
+                }
+                self.heartbeat_timeout_sec.store(heartbeat_timeout_sec, Ordering::Relaxed);
+                Ok(())
+            }
+            "root.schedule_interval_sec" => {
+                let schedule_interval_sec = parse_u64(key, value)?;
+                if schedule_interval_sec == 0 {
+                    return Err(Error::InvalidArgument(format!("{key} should be positive")));
+                }
+                self.schedule_interval_sec.store(schedule_interval_sec, Ordering::Relaxed);
+                Ok(())
+            }
+            "replica.slow_op_threshold_ms" => {
+                self.slow_op_threshold_ms.store(parse_u64(key, value)?, Ordering::Relaxed);
+                Ok(())
+            }
+            "workload.max_request_bytes" => {
+                self.max_request_bytes.store(parse_u64(key, value)?, Ordering::Relaxed);
+                Ok(())
+            }
+            "workload.client_requests_per_sec" => {
+                self.client_requests_per_sec.store(parse_u64(key, value)?, Ordering::Relaxed);
+                Ok(())
+            }
+            "workload.client_bytes_per_sec" => {
+                self.client_bytes_per_sec.store(parse_u64(key, value)?, Ordering::Relaxed);
+                Ok(())
+            }
+            _ => Err(Error::InvalidArgument(format!("{key} is not hot reloadable"))),
+        }
+    }
+
+    /// Apply the options set by admin, the invalid ones are skipped.
+    pub fn apply(&self, entries: &[ConfigEntry]) {
+        for entry in entries {
+            if self.get(&entry.key).as_deref() == Some(entry.value.as_str()) {
+                continue;
+            }
+            match self.set(&entry.key, &entry.value) {
+                Ok(()) => info!("update config {} to {}", entry.key, entry.value),
+                Err(err) => warn!("update config {} to {}: {err:?}", entry.key, entry.value),
+            }
+        }
+    }
+
+    /// Return the current value of the option.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let value = match key {
+            "root.enable_group_balance" => self.enable_group_balance().to_string(),
+            "root.enable_replica_balance" => self.enable_replica_balance().to_string(),
+            "root.enable_shard_balance" => self.enable_shard_balance().to_string(),
+            "root.enable_leader_balance" => self.enable_leader_balance().to_string(),
+            "root.liveness_threshold_sec" => self.liveness_threshold().as_secs().to_string(),
+            "root.heartbeat_timeout_sec" => {
+                self.heartbeat_timeout_sec.load(Ordering::Relaxed).to_string()
+            }
+            "root.schedule_interval_sec" => self.schedule_interval().as_secs().to_string(),
+            "replica.slow_op_threshold_ms" => {
+                self.slow_op_threshold_ms.load(Ordering::Relaxed).to_string()
+            }
+            "workload.max_request_bytes" => {
+                self.max_request_bytes.load(Ordering::Relaxed).to_string()
+            }
+            "workload.client_requests_per_sec" => {
+                self.client_requests_per_sec.load(Ordering::Relaxed).to_string()
+            }
+            "workload.client_bytes_per_sec" => {
+                self.client_bytes_per_sec.load(Ordering::Relaxed).to_string()
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Return the current values of all hot reloadable options.
+    pub fn list(&self) -> Vec<(String, String)> {
+        HOT_RELOADABLE_OPTIONS
+            .iter()
+            .map(|key| (key.to_string(), self.get(key).unwrap_or_default()))
+            .collect()
+    }
+
+    #[inline]
+    pub fn enable_group_balance(&self) -> bool {
+        self.enable_group_balance.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn enable_replica_balance(&self) -> bool {
+        self.enable_replica_balance.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn enable_shard_balance(&self) -> bool {
+        self.enable_shard_balance.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn enable_leader_balance(&self) -> bool {
+        self.enable_leader_balance.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn liveness_threshold(&self) -> Duration {
+        Duration::from_secs(self.liveness_threshold_sec.load(Ordering::Relaxed))
+    }
+
+    /// See [`crate::RootConfig::heartbeat_interval`].
+    pub fn heartbeat_interval(&self) -> Duration {
+        let heartbeat_timeout_sec = self.heartbeat_timeout_sec.load(Ordering::Relaxed);
+        self.liveness_threshold().saturating_sub(Duration::from_secs(heartbeat_timeout_sec))
+    }
+
+    /// The timeout of the heartbeat RPC to each node, 0 means no timeout.
+    #[inline]
+    pub fn heartbeat_timeout(&self) -> Option<Duration> {
+        non_zero(self.heartbeat_timeout_sec.load(Ordering::Relaxed)).map(Duration::from_secs)
+    }
+
+    #[inline]
+    pub fn schedule_interval(&self) -> Duration {
+        Duration::from_secs(self.schedule_interval_sec.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn slow_op_threshold(&self) -> Option<Duration> {
+        match self.slow_op_threshold_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    #[inline]
+    pub fn max_request_bytes(&self) -> Option<u64> {
+        non_zero(self.max_request_bytes.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn client_requests_per_sec(&self) -> Option<u64> {
+        non_zero(self.client_requests_per_sec.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn client_bytes_per_sec(&self) -> Option<u64> {
+        non_zero(self.client_bytes_per_sec.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for DynamicConfig {
+    fn default() -> Self {
+        DynamicConfig::new(&Config::default())
+    }
+}
+
+fn store_bool(target: &AtomicBool, key: &str, value: &str) -> Result<()> {
+    let value =
+        value.parse::<bool>().map_err(|_| Error::InvalidArgument(format!("illegal {key}")))?;
+    target.store(value, Ordering::Relaxed);
+    Ok(())
+}
+
+fn non_zero(value: u64) -> Option<u64> {
+    (value != 0).then_some(value)
+}
+
+fn parse_u64(key: &str, value: &str) -> Result<u64> {
+    value.parse::<u64>().map_err(|_| Error::InvalidArgument(format!("illegal {key}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get_options() {
+        let cfg = DynamicConfig::default();
+        for key in HOT_RELOADABLE_OPTIONS {
+            let value = cfg.get(key).unwrap();
+            cfg.set(key, &value).unwrap();
+        }
+
+        cfg.set("root.enable_shard_balance", "false").unwrap();
+        assert!(!cfg.enable_shard_balance());
+        assert_eq!(cfg.get("root.enable_shard_balance").as_deref(), Some("false"));
+
+        cfg.set("replica.slow_op_threshold_ms", "100").unwrap();
+        assert_eq!(cfg.slow_op_threshold(), Some(Duration::from_millis(100)));
+        cfg.set("replica.slow_op_threshold_ms", "0").unwrap();
+        assert_eq!(cfg.slow_op_threshold(), None);
+
+        assert_eq!(cfg.client_requests_per_sec(), None);
+        cfg.set("workload.client_requests_per_sec", "1000").unwrap();
+        assert_eq!(cfg.client_requests_per_sec(), Some(1000));
+        assert!(cfg.set("workload.max_request_bytes", "-1").is_err());
+
+        assert!(cfg.set("root.enable_shard_balance", "yes").is_err());
+        assert!(cfg.set("root.replicas_per_group", "5").is_err());
+        assert!(cfg.get("root.replicas_per_group").is_none());
+        assert_eq!(cfg.list().len(), HOT_RELOADABLE_OPTIONS.len());
+    }
+
+    #[test]
+    fn apply_entries() {
+        let cfg = DynamicConfig::default();
+        let entry =
+            |key: &str, value: &str| ConfigEntry { key: key.to_owned(), value: value.to_owned() };
+        cfg.apply(&[
+            entry("root.enable_leader_balance", "false"),
+            entry("root.unknown", "1"),
+            entry("root.schedule_interval_sec", "10"),
+        ]);
+        assert!(!cfg.enable_leader_balance());
+        assert_eq!(cfg.schedule_interval(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn heartbeat_interval() {
+        let cfg = DynamicConfig::default();
+        cfg.set("root.liveness_threshold_sec", "20").unwrap();
+        cfg.set("root.heartbeat_timeout_sec", "5").unwrap();
+        assert_eq!(cfg.heartbeat_interval(), Duration::from_secs(15));
+
+        // The heartbeat timeout must be less than the liveness threshold.
+        assert!(cfg.set("root.heartbeat_timeout_sec", "20").is_err());
+        assert!(cfg.set("root.liveness_threshold_sec", "5").is_err());
+        assert!(cfg.set("root.schedule_interval_sec", "0").is_err());
+        assert_eq!(cfg.heartbeat_interval(), Duration::from_secs(15));
+        assert_eq!(cfg.heartbeat_timeout(), Some(Duration::from_secs(5)));
+        cfg.set("root.heartbeat_timeout_sec", "0").unwrap();
+        assert_eq!(cfg.heartbeat_timeout(), None);
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rocksdb::perf::{set_perf_stats, PerfContext, PerfMetric, PerfStatsLevel};
+
+thread_local! {
+    static PERF_CONTEXT: RefCell<PerfContext> = {
+        // The perf level of rocksdb is thread local.
+        set_perf_stats(PerfStatsLevel::EnableCount);
+        RefCell::new(PerfContext::default())
+    };
+}
+
+/// The block cache hit and miss counters of a group engine. The block cache is
+/// shared by all group engines of a node, so the accesses are attributed to
+/// groups by the thread local perf context of rocksdb.
+#[derive(Default)]
+pub struct BlockCacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BlockCacheStats {
+    /// Return the number of block cache hits.
+    #[inline]
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of block cache misses.
+    #[inline]
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Run `f` and record the block cache accesses of it.
+    pub(crate) fn observe<T>(&self, f: impl FnOnce() -> T) -> T {
+        PERF_CONTEXT.with(|ctx| {
+            ctx.borrow_mut().reset();
+            let value = f();
+            let ctx = ctx.borrow();
+            let hits = ctx.metric(PerfMetric::BlockCacheHitCount);
+            let misses = ctx.metric(PerfMetric::BlockReadCount);
+            if hits > 0 {
+                self.hits.fetch_add(hits, Ordering::Relaxed);
+            }
+            if misses > 0 {
+                self.misses.fetch_add(misses, Ordering::Relaxed);
+            }
+            value
+        })
+    }
+}
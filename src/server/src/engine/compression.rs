@@ -0,0 +1,103 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sekas_api::server::v1::CompressionCodec;
+
+use super::metrics::*;
+use crate::{Error, Result};
+
+/// The values smaller than this size are not compressed, since the saving is
+/// negligible.
+const MIN_COMPRESS_SIZE: usize = 64;
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compress the value with the codec. The output is the codec followed by the
+/// compressed data, `None` is returned if the compression doesn't cut the size.
+pub fn compress(codec: CompressionCodec, value: &[u8]) -> Result<Option<Vec<u8>>> {
+    if codec == CompressionCodec::Uncompressed || value.len() < MIN_COMPRESS_SIZE {
+        return Ok(None);
+    }
+
+    let compressed = match codec {
+        CompressionCodec::Uncompressed => unreachable!(),
+        CompressionCodec::Lz4 => lz4::block::compress(value, None, true),
+        CompressionCodec::Zstd => zstd::bulk::compress(value, ZSTD_LEVEL),
+    }
+    .map_err(|e| Error::InvalidData(format!("compress value by {codec:?}: {e}")))?;
+
+    let output_size = compressed.len() + 1;
+    ENGINE_COMPRESSION_INPUT_BYTES_TOTAL.inc_by(value.len() as u64);
+    ENGINE_COMPRESSION_OUTPUT_BYTES_TOTAL.inc_by(std::cmp::min(output_size, value.len()) as u64);
+    ENGINE_COMPRESSION_RATIO.observe(output_size as f64 / value.len() as f64);
+    if output_size >= value.len() {
+        return Ok(None);
+    }
+
+    let mut buf = Vec::with_capacity(output_size);
+    buf.push(codec as i32 as u8);
+    buf.extend_from_slice(&compressed);
+    Ok(Some(buf))
+}
+
+/// Decompress the value generated by [`compress`].
+pub fn decompress(value: &[u8]) -> Result<Vec<u8>> {
+    let Some((&codec, data)) = value.split_first() else {
+        return Err(Error::InvalidData("the compressed value is empty".into()));
+    };
+    let codec = CompressionCodec::from_i32(codec as i32)
+        .ok_or_else(|| Error::InvalidData(format!("unknown compression codec {codec} of value")))?;
+    match codec {
+        CompressionCodec::Uncompressed => Ok(data.to_owned()),
+        CompressionCodec::Lz4 => lz4::block::decompress(data, None),
+        CompressionCodec::Zstd => zstd::stream::decode_all(data),
+    }
+    .map_err(|e| Error::InvalidData(format!("decompress value by {codec:?}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress_and_decompress() {
+        let value = "sekas is a distributed key-value store. ".repeat(16).into_bytes();
+        for codec in [CompressionCodec::Lz4, CompressionCodec::Zstd] {
+            let compressed = compress(codec, &value).unwrap().unwrap();
+            assert!(compressed.len() < value.len());
+            assert_eq!(compressed[0], codec as i32 as u8);
+            assert_eq!(decompress(&compressed).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn skip_compression() {
+        let value = b"small value";
+        assert!(compress(CompressionCodec::Zstd, value).unwrap().is_none());
+
+        let value = "large value".repeat(16).into_bytes();
+        assert!(compress(CompressionCodec::Uncompressed, &value).unwrap().is_none());
+
+        // The random bytes can not be compressed.
+        let value = (0..1024).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        assert!(compress(CompressionCodec::Lz4, &value).unwrap().is_none());
+    }
+
+    #[test]
+    fn decompress_corrupted_value() {
+        assert!(decompress(&[]).is_err());
+        assert!(decompress(&[u8::MAX, 1, 2, 3]).is_err());
+        assert!(decompress(&[CompressionCodec::Zstd as i32 as u8, 1, 2, 3]).is_err());
+    }
+}
@@ -24,7 +24,7 @@ use prost::Message;
 use sekas_api::server::v1::*;
 use sekas_schema::shard;
 
-use super::RawDb;
+use super::{BlockCacheStats, KeyManager, RawDb};
 use crate::constants::{INITIAL_EPOCH, LOCAL_COLLECTION_ID};
 use crate::serverpb::v1::*;
 use crate::{EngineConfig, Error, Result};
@@ -34,6 +34,7 @@ pub struct WriteStates {
     pub apply_state: Option<ApplyState>,
     pub descriptor: Option<GroupDesc>,
     pub move_shard_state: Option<MoveShardState>,
+    pub ingest_state: Option<IngestState>,
 }
 
 #[derive(Default)]
@@ -55,6 +56,8 @@ where
     cfg: EngineConfig,
     name: String,
     raw_db: Arc<RawDb>,
+    key_manager: Arc<KeyManager>,
+    cache_stats: Arc<BlockCacheStats>,
     core: Arc<RwLock<GroupEngineCore>>,
 }
 
@@ -81,21 +84,39 @@ enum SnapshotRange {
 
 /// A snapshot of data, to traverse the data of a shard in the group engine,
 /// analyze and return the data (including tombstone).
+///
+/// For a cloned shard, the entries of the clone source are merged, see
+/// [`CloneSource`] for details.
 #[derive(Debug)]
 pub(crate) struct Snapshot<'a> {
-    collection_id: u64,
     range: Option<SnapshotRange>,
 
     core: SnapshotCore<'a>,
 }
 
+#[derive(Debug)]
+pub(crate) struct SnapshotCore<'a> {
+    entries: EntryIter<'a>,
+    clone_source: Option<EntryIter<'a>>,
+    current_key: Option<Vec<u8>>,
+    cached_entry: Option<MvccEntry>,
+}
+
+/// Traverse the mvcc entries of a collection.
 #[derive(derivative::Derivative)]
 #[derivative(Debug)]
-pub(crate) struct SnapshotCore<'a> {
+struct EntryIter<'a> {
+    collection_id: u64,
+    /// The entries with a greater version are skipped.
+    max_version: u64,
     #[derivative(Debug = "ignore")]
     db_iter: rocksdb::DBIterator<'a>,
-    current_key: Option<Vec<u8>>,
-    cached_entry: Option<MvccEntry>,
+    #[derivative(Debug = "ignore")]
+    key_manager: &'a KeyManager,
+    #[derivative(Debug = "ignore")]
+    cache_stats: &'a BlockCacheStats,
+    peeked_entry: Option<MvccEntry>,
+    exhausted: bool,
 }
 
 /// Traverse multi-version of a single key.
@@ -109,6 +130,8 @@ pub(crate) struct MvccEntry {
     key: Box<[u8]>,
     user_key: Vec<u8>,
     value: Box<[u8]>,
+    /// The unix timestamp in seconds when the value expires, 0 means never.
+    expire_at: u64,
 }
 
 #[derive(Debug)]
@@ -133,6 +156,7 @@ impl GroupEngine {
     pub(crate) async fn create(
         cfg: &EngineConfig,
         raw_db: Arc<RawDb>,
+        key_manager: Arc<KeyManager>,
         group_id: u64,
         replica_id: u64,
     ) -> Result<Self> {
@@ -149,6 +173,8 @@ impl GroupEngine {
             cfg: cfg.clone(),
             name,
             raw_db: raw_db.clone(),
+            key_manager,
+            cache_stats: Arc::default(),
             core: Arc::new(RwLock::new(GroupEngineCore {
                 group_desc: desc.clone(),
                 shard_descs: Default::default(),
@@ -174,6 +200,7 @@ impl GroupEngine {
     pub(crate) async fn open(
         cfg: &EngineConfig,
         raw_db: Arc<RawDb>,
+        key_manager: Arc<KeyManager>,
         group_id: u64,
         replica_id: u64,
     ) -> Result<Option<Self>> {
@@ -197,6 +224,8 @@ impl GroupEngine {
             cfg: cfg.clone(),
             name,
             raw_db: raw_db.clone(),
+            key_manager,
+            cache_stats: Arc::default(),
             core: Arc::new(RwLock::new(core)),
         }))
     }
@@ -209,6 +238,12 @@ impl GroupEngine {
         Ok(())
     }
 
+    /// Return the block cache stats of this group engine.
+    #[inline]
+    pub fn block_cache_stats(&self) -> &BlockCacheStats {
+        &self.cache_stats
+    }
+
     /// Return the move shard state.
     #[inline]
     pub fn move_shard_state(&self) -> Option<MoveShardState> {
@@ -227,6 +262,19 @@ impl GroupEngine {
         internal::flushed_apply_state(&self.raw_db, &self.cf_handle())
     }
 
+    /// Return the ingest files kept by this replica.
+    #[inline]
+    pub fn ingest_state(&self) -> Result<IngestState> {
+        internal::ingest_state(&self.raw_db, &self.cf_handle())
+    }
+
+    /// Flush the memtables of this group, so that all applied states are
+    /// persisted.
+    pub fn flush(&self) -> Result<()> {
+        self.raw_db.flush_cf(&self.cf_handle())?;
+        Ok(())
+    }
+
     /// Get the latest key value from the corresponding shard.
     pub async fn get(&self, shard_id: u64, key: &[u8]) -> Result<Option<Value>> {
         let snapshot_mode = SnapshotMode::Key { key };
@@ -256,6 +304,7 @@ impl GroupEngine {
     }
 
     /// Put key value into the corresponding shard.
+    #[inline]
     pub fn put(
         &self,
         wb: &mut WriteBatch,
@@ -263,17 +312,86 @@ impl GroupEngine {
         key: &[u8],
         value: &[u8],
         version: u64,
+    ) -> Result<()> {
+        self.put_with_expire_at(wb, shard_id, key, value, version, 0)
+    }
+
+    /// Put key value into the corresponding shard, the value is read as a
+    /// tombstone once the unix timestamp `expire_at` in seconds is reached, 0
+    /// means never expires.
+    pub fn put_with_expire_at(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        key: &[u8],
+        value: &[u8],
+        version: u64,
+        expire_at: u64,
     ) -> Result<()> {
         let desc = self.shard_desc(shard_id)?;
         let collection_id = desc.collection_id;
         debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
         debug_assert!(shard::belong_to(&desc, key));
 
-        wb.put(keys::mvcc_key(collection_id, key, version), values::data(value));
+        let mut value = values::encode(&desc, &self.key_manager, value)?;
+        if expire_at != 0 {
+            value = values::expiring(expire_at, &value);
+        }
+        wb.put(keys::mvcc_key(collection_id, key, version), value);
 
         Ok(())
     }
 
+    /// Put the manifest of a value split into `num_chunks` chunks by the
+    /// client, the chunks are written to the chunk keys separately.
+    pub fn put_chunked(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        key: &[u8],
+        num_chunks: u32,
+        version: u64,
+        expire_at: u64,
+    ) -> Result<()> {
+        let desc = self.shard_desc(shard_id)?;
+        let collection_id = desc.collection_id;
+        debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
+        debug_assert!(shard::belong_to(&desc, key));
+
+        let mut value = values::chunked(num_chunks);
+        if expire_at != 0 {
+            value = values::expiring(expire_at, &value);
+        }
+        wb.put(keys::mvcc_key(collection_id, key, version), value);
+
+        Ok(())
+    }
+
+    /// Put the value into the corresponding shard, a value without content is
+    /// put as a tombstone.
+    pub fn put_value(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        key: &[u8],
+        value: &Value,
+    ) -> Result<()> {
+        match value.content.as_ref() {
+            None => self.tombstone(wb, shard_id, key, value.version),
+            Some(_) if value.num_chunks != 0 => self.put_chunked(
+                wb,
+                shard_id,
+                key,
+                value.num_chunks,
+                value.version,
+                value.expire_at,
+            ),
+            Some(content) => {
+                self.put_with_expire_at(wb, shard_id, key, content, value.version, value.expire_at)
+            }
+        }
+    }
+
     /// Logically delete key from the corresponding shard.
     pub fn tombstone(
         &self,
@@ -351,34 +469,147 @@ impl GroupEngine {
     }
 
     pub fn snapshot(&self, shard_id: u64, mode: SnapshotMode) -> Result<Snapshot> {
-        use rocksdb::{Direction, IteratorMode, ReadOptions};
-
         let desc = self.shard_desc(shard_id)?;
         let collection_id = desc.collection_id;
         debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
 
-        let opts = ReadOptions::default();
-        let key = match &mode {
+        let start_key = match &mode {
             SnapshotMode::Start { start_key: Some(start_key) } => {
                 debug_assert!(shard::belong_to(&desc, start_key));
-                keys::raw(collection_id, start_key)
+                start_key.to_vec()
             }
             SnapshotMode::Start { start_key: None } => {
                 // An empty key is equivalent to range start key.
-                keys::raw(collection_id, &shard::start_key(&desc))
+                shard::start_key(&desc)
             }
-            SnapshotMode::Key { key } => {
+            SnapshotMode::Key { key } | SnapshotMode::Prefix { key } => {
                 debug_assert!(shard::belong_to(&desc, key));
-                keys::raw(collection_id, key)
+                key.to_vec()
             }
-            SnapshotMode::Prefix { key } => {
-                debug_assert!(shard::belong_to(&desc, key));
-                keys::raw(collection_id, key)
+        };
+        let entries = self.entry_iter(collection_id, u64::MAX, &start_key);
+        let clone_source = desc
+            .clone_source
+            .as_ref()
+            .map(|source| self.entry_iter(source.collection_id, source.version, &start_key));
+        Ok(Snapshot::new(entries, clone_source, mode, &desc))
+    }
+
+    /// Return the start key of the next chunk of the clone source, which
+    /// consists of `limit` user keys from `start_key`. `None` is returned if
+    /// the chunk reaches the end of the shard.
+    pub fn next_clone_chunk(
+        &self,
+        shard_id: u64,
+        start_key: &[u8],
+        limit: usize,
+    ) -> Result<Option<Vec<u8>>> {
+        let desc = self.shard_desc(shard_id)?;
+        let Some(source) = desc.clone_source.as_ref() else {
+            return Ok(None);
+        };
+        let mut entries = self.entry_iter(source.collection_id, source.version, start_key);
+        let mut num_keys = 0;
+        let mut last_key: Option<Vec<u8>> = None;
+        while let Some(entry) = entries.next_entry()? {
+            let user_key = entry.user_key();
+            if !shard::belong_to(&desc, user_key) {
+                break;
             }
+            if last_key.as_deref() != Some(user_key) {
+                if num_keys == limit {
+                    return Ok(Some(user_key.to_owned()));
+                }
+                num_keys += 1;
+                last_key = Some(user_key.to_owned());
+            }
+        }
+        Ok(None)
+    }
+
+    /// Copy the versions of the clone source in range `[start_key, end_key)`
+    /// into the cloned shard, the empty `end_key` means the end of the shard.
+    /// The versions of the cloned shard are always greater than the clone
+    /// version, so the copied versions never overwrite them.
+    pub fn realize_clone(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        start_key: &[u8],
+        end_key: &[u8],
+    ) -> Result<()> {
+        let desc = self.shard_desc(shard_id)?;
+        let Some(source) = desc.clone_source.as_ref() else {
+            return Ok(());
         };
+        let mut entries = self.entry_iter(source.collection_id, source.version, start_key);
+        while let Some(entry) = entries.next_entry()? {
+            let user_key = entry.user_key();
+            if !shard::belong_to(&desc, user_key) || (!end_key.is_empty() && user_key >= end_key) {
+                break;
+            }
+            match entry.value() {
+                Some(_) if entry.num_chunks() != 0 => self.put_chunked(
+                    wb,
+                    shard_id,
+                    user_key,
+                    entry.num_chunks(),
+                    entry.version(),
+                    entry.expire_at(),
+                )?,
+                Some(value) => self.put_with_expire_at(
+                    wb,
+                    shard_id,
+                    user_key,
+                    value,
+                    entry.version(),
+                    entry.expire_at(),
+                )?,
+                None => self.tombstone(wb, shard_id, user_key, entry.version())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the number of live user keys of shard.
+    #[inline]
+    pub fn num_live_keys(&self, shard_id: u64) -> Result<u64> {
+        Ok(self.shard_usage(shard_id)?.0)
+    }
+
+    /// Return the number of live user keys and the sum of the size of their
+    /// keys and values of shard.
+    pub fn shard_usage(&self, shard_id: u64) -> Result<(u64, u64)> {
+        let mut num_keys = 0;
+        let mut logical_bytes = 0;
+        let mut snapshot = self.snapshot(shard_id, SnapshotMode::default())?;
+        while let Some(mvcc_iter) = snapshot.next() {
+            let mut mvcc_iter = mvcc_iter?;
+            let key_len = mvcc_iter.user_key().len();
+            let value_len = match mvcc_iter.next() {
+                Some(entry) => entry?.value().map(<[u8]>::len),
+                None => None,
+            };
+            if let Some(value_len) = value_len {
+                num_keys += 1;
+                logical_bytes += (key_len + value_len) as u64;
+            }
+        }
+        Ok((num_keys, logical_bytes))
+    }
+
+    /// Traverse the entries of the collection from `start_key`, the versions
+    /// greater than `max_version` are skipped.
+    fn entry_iter(&self, collection_id: u64, max_version: u64, start_key: &[u8]) -> EntryIter {
+        use rocksdb::{Direction, IteratorMode, ReadOptions};
+
+        let key = keys::raw(collection_id, start_key);
         let inner_mode = IteratorMode::From(&key, Direction::Forward);
-        let iter = self.raw_db.iterator_cf_opt(&self.cf_handle(), opts, inner_mode);
-        Ok(Snapshot::new(collection_id, iter, mode, &desc))
+        let opts = ReadOptions::default();
+        let db_iter = self
+            .cache_stats
+            .observe(|| self.raw_db.iterator_cf_opt(&self.cf_handle(), opts, inner_mode));
+        EntryIter::new(collection_id, max_version, db_iter, &self.key_manager, &self.cache_stats)
     }
 
     pub fn raw_iter(&self) -> Result<RawIterator> {
@@ -407,6 +638,126 @@ impl GroupEngine {
         Ok(())
     }
 
+    /// Ingest the key/value files into the shard, all keys are written with the
+    /// same version.
+    pub fn ingest_kv_files<P: AsRef<Path>>(
+        &self,
+        shard_id: u64,
+        version: u64,
+        files: &[P],
+    ) -> Result<()> {
+        use rocksdb::{IngestExternalFileOptions, SstFileWriter};
+        use sekas_api::IngestFileReader;
+
+        let desc = self.shard_desc(shard_id)?;
+        let collection_id = desc.collection_id;
+        debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
+
+        let mut sst_files = Vec::with_capacity(files.len());
+        let result = (|| -> Result<()> {
+            for file in files {
+                let file = file.as_ref();
+                let content = std::fs::read(file)?;
+                if content.is_empty() {
+                    continue;
+                }
+
+                let sst_path = file.with_extension("sst");
+                sst_files.push(sst_path.clone());
+                let mut writer = SstFileWriter::create(&self.raw_db.options);
+                writer.open(&sst_path)?;
+                for record in IngestFileReader::new(&content) {
+                    let (key, value) = record.map_err(|_| {
+                        Error::InvalidData(format!("ingest file {} is corrupted", file.display()))
+                    })?;
+                    // The order of keys is validated when evaluating, and the range is checked
+                    // again since the shard might be split after evaluating.
+                    if !shard::belong_to(&desc, key) {
+                        return Err(Error::InvalidArgument(format!(
+                            "the key {key:?} of ingest file {} does not belong to shard \
+                             {shard_id}",
+                            file.display()
+                        )));
+                    }
+                    let value = values::encode(&desc, &self.key_manager, value)?;
+                    writer.put(keys::mvcc_key(collection_id, key, version), value)?;
+                }
+                writer.finish()?;
+            }
+
+            if sst_files.is_empty() {
+                return Ok(());
+            }
+
+            let mut opts = IngestExternalFileOptions::default();
+            opts.set_move_files(true);
+            self.raw_db.ingest_external_file_cf_opts(
+                &self.cf_handle(),
+                &opts,
+                sst_files.clone(),
+            )?;
+            Ok(())
+        })();
+        // The sst files are moved into the engine once ingested, the others are
+        // garbage.
+        for sst_file in &sst_files {
+            std::fs::remove_file(sst_file).unwrap_or_default();
+        }
+        result
+    }
+
+    /// Compact the keys of the group manually. If `range` is specified, only
+    /// the keys of the shard in the range are compacted, an empty start or
+    /// end key means the corresponding boundary of the shard.
+    pub fn compact(&self, range: Option<(u64, &RangePartition)>) -> Result<()> {
+        let cf_handle = self.cf_handle();
+        let Some((shard_id, range)) = range else {
+            self.raw_db.compact_range_cf(&cf_handle, None, None);
+            return Ok(());
+        };
+
+        let desc = self.shard_desc(shard_id)?;
+        let collection_id = desc.collection_id;
+        let start =
+            if range.start.is_empty() { shard::start_key(&desc) } else { range.start.clone() };
+        let end = if range.end.is_empty() { shard::end_key(&desc) } else { range.end.clone() };
+        let start = keys::raw(collection_id, &start);
+        let end = if end.is_empty() {
+            keys::collection_upper_bound(collection_id)
+        } else {
+            Some(keys::raw(collection_id, &end))
+        };
+        self.raw_db.compact_range_cf(&cf_handle, Some(&start), end.as_deref());
+        Ok(())
+    }
+
+    /// Delete all versions of the keys in the range of the shard. The shard is
+    /// not required to be one of the group, since it could be used to drop the
+    /// data of a truncated shard.
+    pub fn delete_shard_data(&self, desc: &ShardDesc) -> Result<()> {
+        use rocksdb::WriteOptions;
+
+        let collection_id = desc.collection_id;
+        debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
+        let start = keys::raw(collection_id, &shard::start_key(desc));
+        let end = shard::end_key(desc);
+        let end = if end.is_empty() {
+            keys::collection_upper_bound(collection_id).ok_or_else(|| {
+                Error::InvalidArgument(format!("collection {collection_id} has no upper bound"))
+            })?
+        } else {
+            keys::raw(collection_id, &end)
+        };
+
+        let mut wb = rocksdb::WriteBatch::default();
+        wb.delete_range_cf(&self.cf_handle(), start, end);
+        let mut opts = WriteOptions::default();
+        opts.disable_wal(true);
+        let _slow_io_guard = self.cfg.engine_slow_io_threshold_ms.map(SlowIoGuard::new);
+        self.raw_db.write_opt(wb, &opts)?;
+        Ok(())
+    }
+
     pub fn apply_core_states(
         &self,
         descriptor: Option<GroupDesc>,
@@ -457,6 +808,13 @@ impl GroupEngine {
         // after deleting the replica.
         format!("{group_id}-{replica_id}")
     }
+
+    /// Parse the group id and replica id from the column family name, `None`
+    /// is returned if the column family doesn't belong to any group engine.
+    pub(crate) fn parse_cf_name(name: &str) -> Option<(u64, u64)> {
+        let (group_id, replica_id) = name.split_once('-')?;
+        Some((group_id.parse().ok()?, replica_id.parse().ok()?))
+    }
 }
 
 impl<'a> RawIterator<'a> {
@@ -493,8 +851,8 @@ impl<'a> Iterator for RawIterator<'a> {
 
 impl<'a> Snapshot<'a> {
     fn new<'b>(
-        collection_id: u64,
-        db_iter: rocksdb::DBIterator<'a>,
+        entries: EntryIter<'a>,
+        clone_source: Option<EntryIter<'a>>,
         snapshot_mode: SnapshotMode<'b>,
         desc: &ShardDesc,
     ) -> Self {
@@ -508,9 +866,8 @@ impl<'a> Snapshot<'a> {
         };
 
         Snapshot {
-            collection_id,
             range,
-            core: SnapshotCore { db_iter, current_key: None, cached_entry: None },
+            core: SnapshotCore { entries, clone_source, current_key: None, cached_entry: None },
         }
     }
 
@@ -537,7 +894,7 @@ impl<'a> Snapshot<'a> {
                 }
             }
 
-            if let Err(err) = core.next_entry(self.collection_id)? {
+            if let Err(err) = core.next_entry()? {
                 return Some(Err(err));
             }
         }
@@ -555,7 +912,7 @@ impl<'a> Snapshot<'a> {
                 }
             }
 
-            if let Err(err) = core.next_entry(self.collection_id)? {
+            if let Err(err) = core.next_entry()? {
                 return Some(Err(err));
             }
         }
@@ -563,18 +920,25 @@ impl<'a> Snapshot<'a> {
 }
 
 impl<'a> SnapshotCore<'a> {
-    fn next_entry(&mut self, collection_id: u64) -> Option<Result<()>> {
-        let (key, value) = match self.db_iter.next()? {
-            Ok(v) => v,
-            Err(err) => return Some(Err(err.into())),
+    fn next_entry(&mut self) -> Option<Result<()>> {
+        let clone_source = self.clone_source.as_mut().map(EntryIter::peek);
+        let take_clone_source = match (self.entries.peek(), clone_source) {
+            (Err(err), _) | (_, Some(Err(err))) => return Some(Err(err)),
+            (Ok(None), None | Some(Ok(None))) => return None,
+            (Ok(None), Some(Ok(Some(_)))) => true,
+            (Ok(Some(_)), None | Some(Ok(None))) => false,
+            // The versions of the cloned shard are always newer than the clone source, so
+            // the entries of the same user key are taken from the cloned shard first.
+            (Ok(Some(entry)), Some(Ok(Some(source_entry)))) => {
+                source_entry.user_key() < entry.user_key()
+            }
         };
 
-        let prefix = &key[..core::mem::size_of::<u64>()];
-        if prefix != collection_id.to_le_bytes().as_slice() {
-            return None;
-        }
-
-        self.cached_entry = Some(MvccEntry::new(key, value));
+        let entries = match self.clone_source.as_mut() {
+            Some(clone_source) if take_clone_source => clone_source,
+            _ => &mut self.entries,
+        };
+        self.cached_entry = entries.peeked_entry.take();
         Some(Ok(()))
     }
 
@@ -584,6 +948,52 @@ impl<'a> SnapshotCore<'a> {
     }
 }
 
+impl<'a> EntryIter<'a> {
+    fn new(
+        collection_id: u64,
+        max_version: u64,
+        db_iter: rocksdb::DBIterator<'a>,
+        key_manager: &'a KeyManager,
+        cache_stats: &'a BlockCacheStats,
+    ) -> Self {
+        EntryIter {
+            collection_id,
+            max_version,
+            db_iter,
+            key_manager,
+            cache_stats,
+            peeked_entry: None,
+            exhausted: false,
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&MvccEntry>> {
+        while self.peeked_entry.is_none() && !self.exhausted {
+            let Some(item) = self.cache_stats.observe(|| self.db_iter.next()) else {
+                self.exhausted = true;
+                break;
+            };
+            let (key, value) = item?;
+            let prefix = &key[..core::mem::size_of::<u64>()];
+            if prefix != self.collection_id.to_le_bytes().as_slice() {
+                self.exhausted = true;
+                break;
+            }
+            let (value, expire_at) = values::decode(self.key_manager, value)?;
+            let entry = MvccEntry::new(key, value, expire_at);
+            if entry.version() <= self.max_version {
+                self.peeked_entry = Some(entry);
+            }
+        }
+        Ok(self.peeked_entry.as_ref())
+    }
+
+    fn next_entry(&mut self) -> Result<Option<MvccEntry>> {
+        self.peek()?;
+        Ok(self.peeked_entry.take())
+    }
+}
+
 impl<'a, 'b> MvccIterator<'a, 'b> {
     /// Return the user key of this mvcc iterator.
     pub fn user_key(&self) -> &[u8] {
@@ -604,9 +1014,9 @@ impl<'a, 'b> Iterator for MvccIterator<'a, 'b> {
 }
 
 impl MvccEntry {
-    fn new(key: Box<[u8]>, value: Box<[u8]>) -> Self {
+    fn new(key: Box<[u8]>, value: Box<[u8]>, expire_at: u64) -> Self {
         let user_key = keys::revert_mvcc_key(&key);
-        MvccEntry { key, user_key, value }
+        MvccEntry { key, user_key, value, expire_at }
     }
 
     #[inline]
@@ -624,16 +1034,32 @@ impl MvccEntry {
     }
 
     /// Return value of this `MvccEntry`. `None` is returned if this entry is a
-    /// tombstone.
+    /// tombstone, and the value of a chunked entry is empty.
     pub fn value(&self) -> Option<&[u8]> {
-        if self.value[0] == values::TOMBSTONE {
-            None
-        } else {
-            debug_assert_eq!(self.value[0], values::DATA);
-            Some(&self.value[1..])
+        match self.value[0] {
+            values::TOMBSTONE => None,
+            values::CHUNKED => Some(&[]),
+            _ => {
+                debug_assert_eq!(self.value[0], values::DATA);
+                Some(&self.value[1..])
+            }
         }
     }
 
+    /// Return the number of chunks of the value, 0 means the value is not
+    /// chunked. See [`GroupEngine::put_chunked`].
+    #[inline]
+    pub fn num_chunks(&self) -> u32 {
+        values::num_chunks(&self.value)
+    }
+
+    /// Return the unix timestamp in seconds when the value expires, 0 means
+    /// never. The expired values are returned as tombstones.
+    #[inline]
+    pub fn expire_at(&self) -> u64 {
+        self.expire_at
+    }
+
     #[allow(dead_code)]
     pub fn is_tombstone(&self) -> bool {
         self.value[0] == values::TOMBSTONE
@@ -641,13 +1067,18 @@ impl MvccEntry {
 
     #[allow(dead_code)]
     pub fn is_data(&self) -> bool {
-        self.value[0] == values::DATA
+        matches!(self.value[0], values::DATA | values::CHUNKED)
     }
 }
 
 impl From<MvccEntry> for Value {
     fn from(entry: MvccEntry) -> Self {
-        Value { content: entry.value().map(ToOwned::to_owned), version: entry.version() }
+        Value {
+            content: entry.value().map(ToOwned::to_owned),
+            version: entry.version(),
+            expire_at: entry.expire_at(),
+            num_chunks: entry.num_chunks(),
+        }
     }
 }
 
@@ -669,10 +1100,11 @@ impl<'a> Default for SnapshotMode<'a> {
     }
 }
 
-mod keys {
+pub(super) mod keys {
     const APPLY_STATE: &[u8] = b"APPLY_STATE";
     const DESCRIPTOR: &[u8] = b"DESCRIPTOR";
     const MIGRATE_STATE: &[u8] = b"MIGRATE_STATE";
+    const INGEST_STATE: &[u8] = b"INGEST_STATE";
 
     #[inline]
     pub fn raw(collection_id: u64, key: &[u8]) -> Vec<u8> {
@@ -683,6 +1115,19 @@ mod keys {
         }
     }
 
+    /// Return the exclusive upper bound of the keys of the collection, `None`
+    /// means unbounded.
+    pub fn collection_upper_bound(collection_id: u64) -> Option<Vec<u8>> {
+        let mut prefix = collection_id.to_le_bytes().to_vec();
+        while let Some(last) = prefix.pop() {
+            if last != u8::MAX {
+                prefix.push(last + 1);
+                return Some(prefix);
+            }
+        }
+        None
+    }
+
     /// Generate mvcc key with the memcomparable format.
     pub fn mvcc_key(collection_id: u64, key: &[u8], version: u64) -> Vec<u8> {
         use std::io::{Cursor, Read};
@@ -751,11 +1196,43 @@ mod keys {
         buf.extend_from_slice(MIGRATE_STATE);
         buf
     }
+
+    #[inline]
+    pub fn ingest_state() -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + INGEST_STATE.len());
+        buf.extend_from_slice(super::LOCAL_COLLECTION_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(INGEST_STATE);
+        buf
+    }
 }
 
-mod values {
+pub(super) mod values {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use sekas_api::server::v1::{CompressionCodec, ShardDesc};
+
+    use crate::engine::{compression, KeyManager};
+    use crate::Result;
+
     pub(super) const DATA: u8 = 0;
     pub(super) const TOMBSTONE: u8 = 1;
+    /// The data encrypted by [`KeyManager`], it is decrypted before returning
+    /// to the readers.
+    pub(super) const ENCRYPTED: u8 = 2;
+    /// The data compressed by the codec of shard, it is decompressed before
+    /// returning to the readers.
+    pub(super) const COMPRESSED: u8 = 3;
+    /// The data compressed then encrypted.
+    pub(super) const COMPRESSED_ENCRYPTED: u8 = 4;
+    /// The value with an expire time, it is followed by the unix timestamp in
+    /// seconds in big-endian and the encoded value.
+    pub(super) const EXPIRING: u8 = 5;
+    const EXPIRING_HEADER_LEN: usize = 1 + core::mem::size_of::<u64>();
+    /// The manifest of a value split into chunks by the client, it is followed
+    /// by the number of chunks in big-endian. It is never encrypted or
+    /// compressed since it holds no user data.
+    pub(super) const CHUNKED: u8 = 6;
+    const CHUNKED_LEN: usize = 1 + core::mem::size_of::<u32>();
 
     #[inline]
     pub fn tombstone() -> &'static [u8] {
@@ -768,6 +1245,99 @@ mod values {
         buf.extend_from_slice(v);
         buf
     }
+
+    pub fn encrypted(key_manager: &KeyManager, v: &[u8]) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(v.len() + 64);
+        buf.push(ENCRYPTED);
+        key_manager.encrypt(v, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Encode the data of the shard, it is compressed by the codec of the shard
+    /// then encrypted if required.
+    pub fn encode(desc: &ShardDesc, key_manager: &KeyManager, v: &[u8]) -> Result<Vec<u8>> {
+        let codec =
+            CompressionCodec::from_i32(desc.compression).unwrap_or(CompressionCodec::Uncompressed);
+        let Some(compressed) = compression::compress(codec, v)? else {
+            return if desc.encrypted { encrypted(key_manager, v) } else { Ok(data(v)) };
+        };
+        let mut buf = Vec::with_capacity(compressed.len() + 64);
+        if desc.encrypted {
+            buf.push(COMPRESSED_ENCRYPTED);
+            key_manager.encrypt(&compressed, &mut buf)?;
+        } else {
+            buf.push(COMPRESSED);
+            buf.extend_from_slice(&compressed);
+        }
+        Ok(buf)
+    }
+
+    /// The manifest of a value split into `num_chunks` chunks.
+    pub fn chunked(num_chunks: u32) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(CHUNKED_LEN);
+        buf.push(CHUNKED);
+        buf.extend_from_slice(&num_chunks.to_be_bytes());
+        buf
+    }
+
+    /// Return the number of chunks of the decoded value, 0 means the value is
+    /// not chunked.
+    pub fn num_chunks(value: &[u8]) -> u32 {
+        if value.len() != CHUNKED_LEN || value[0] != CHUNKED {
+            return 0;
+        }
+        let mut buf = [0u8; core::mem::size_of::<u32>()];
+        buf.copy_from_slice(&value[1..]);
+        u32::from_be_bytes(buf)
+    }
+
+    /// Wrap the encoded value with the expire time.
+    pub fn expiring(expire_at: u64, encoded: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(EXPIRING_HEADER_LEN + encoded.len());
+        buf.push(EXPIRING);
+        buf.extend_from_slice(&expire_at.to_be_bytes());
+        buf.extend_from_slice(encoded);
+        buf
+    }
+
+    /// Return the expire time of the value, `None` if the value never expires.
+    pub fn expire_at(value: &[u8]) -> Option<u64> {
+        if value.len() < EXPIRING_HEADER_LEN || value[0] != EXPIRING {
+            return None;
+        }
+        let mut buf = [0u8; core::mem::size_of::<u64>()];
+        buf.copy_from_slice(&value[1..EXPIRING_HEADER_LEN]);
+        Some(u64::from_be_bytes(buf))
+    }
+
+    #[inline]
+    pub fn unix_timestamp() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+
+    /// Decode the value generated by [`encode`] and [`expiring`], the data is
+    /// returned as plaintext with the expire time. The expired value is
+    /// returned as a tombstone.
+    pub fn decode(key_manager: &KeyManager, value: Box<[u8]>) -> Result<(Box<[u8]>, u64)> {
+        let Some(expire_at) = expire_at(&value) else {
+            return Ok((decode_plain(key_manager, value)?, 0));
+        };
+        if expire_at <= unix_timestamp() {
+            return Ok((tombstone().into(), 0));
+        }
+        let value = decode_plain(key_manager, value[EXPIRING_HEADER_LEN..].into())?;
+        Ok((value, expire_at))
+    }
+
+    fn decode_plain(key_manager: &KeyManager, value: Box<[u8]>) -> Result<Box<[u8]>> {
+        let plain = match value[0] {
+            ENCRYPTED => key_manager.decrypt(&value[1..])?,
+            COMPRESSED => compression::decompress(&value[1..])?,
+            COMPRESSED_ENCRYPTED => compression::decompress(&key_manager.decrypt(&value[1..])?)?,
+            _ => return Ok(value),
+        };
+        Ok(data(&plain).into_boxed_slice())
+    }
 }
 
 impl<'a, 'b> rocksdb::WriteBatchIterator for ColumnFamilyDecorator<'a, 'b> {
@@ -819,6 +1389,9 @@ impl WriteStates {
                 wb.delete_cf(cf_handle, keys::move_shard_state());
             }
         }
+        if let Some(ingest_state) = &self.ingest_state {
+            wb.put_cf(cf_handle, keys::ingest_state(), ingest_state.encode_to_vec());
+        }
     }
 }
 
@@ -871,6 +1444,17 @@ mod internal {
         }
     }
 
+    pub(super) fn ingest_state(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<IngestState> {
+        if let Some(v) = db.get_pinned_cf(cf_handle, keys::ingest_state())? {
+            Ok(IngestState::decode(v.as_ref())?)
+        } else {
+            Ok(IngestState::default())
+        }
+    }
+
     pub(super) fn flushed_apply_state(
         db: &RawDb,
         cf_handle: &impl rocksdb::AsColumnFamilyRef,
@@ -928,10 +1512,15 @@ mod tests {
         let db_dir = path.join("db");
         let db = open_engine_with_default_config(db_dir).unwrap();
         let db = Arc::new(db);
-        let group_engine =
-            GroupEngine::create(&EngineConfig::default(), db.clone(), group_id, shard_id)
-                .await
-                .unwrap();
+        let group_engine = GroupEngine::create(
+            &EngineConfig::default(),
+            db.clone(),
+            Arc::default(),
+            group_id,
+            shard_id,
+        )
+        .await
+        .unwrap();
 
         let wb = WriteBatch::default();
         let states = WriteStates {
@@ -1010,19 +1599,29 @@ mod tests {
         };
 
         // 2. open engine
-        let engine =
-            GroupEngine::open(&EngineConfig::default(), raw_db.clone(), group_id, replica_id)
-                .await
-                .unwrap();
+        let engine = GroupEngine::open(
+            &EngineConfig::default(),
+            raw_db.clone(),
+            Arc::default(),
+            group_id,
+            replica_id,
+        )
+        .await
+        .unwrap();
         assert!(engine.is_some());
 
         // 3. drop engine
         GroupEngine::destory(group_id, replica_id, raw_db.clone()).await.unwrap();
 
-        let engine =
-            GroupEngine::open(&EngineConfig::default(), raw_db.clone(), group_id, replica_id)
-                .await
-                .unwrap();
+        let engine = GroupEngine::open(
+            &EngineConfig::default(),
+            raw_db.clone(),
+            Arc::default(),
+            group_id,
+            replica_id,
+        )
+        .await
+        .unwrap();
         assert!(engine.is_none());
     }
 
@@ -1454,6 +2053,7 @@ mod tests {
                 }),
                 last_moved_key: None,
                 step: MoveShardStep::Prepare.into(),
+                ..Default::default()
             };
             let states = WriteStates {
                 move_shard_state: Some(move_shard_state.clone()),
@@ -1464,11 +2064,25 @@ mod tests {
             let read_state = engine.move_shard_state();
             assert!(matches!(read_state, Some(state) if state == move_shard_state));
         }
+
+        {
+            // with ingest state
+            assert_eq!(engine.ingest_state().unwrap(), IngestState::default());
+            let file = IngestFile { name: "1.kv".to_owned(), crc32: 1, size: 1 };
+            let ingest_state = IngestState {
+                records: vec![IngestRecord { index: 11, files: vec![file], token: "t".to_owned() }],
+                last_index: 11,
+            };
+            let states =
+                WriteStates { ingest_state: Some(ingest_state.clone()), ..Default::default() };
+            engine.commit(WriteBatch::default(), states, false).unwrap();
+            assert_eq!(engine.ingest_state().unwrap(), ingest_state);
+        }
     }
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, 1, key, value, *version).unwrap();
             } else {
@@ -1484,18 +2098,18 @@ mod tests {
             // empty values.
             vec![],
             // a tombstone.
-            vec![Value { version: 1, content: None }],
+            vec![Value { version: 1, content: None, expire_at: 0, num_chunks: 0 }],
             // a write.
-            vec![Value { version: 1, content: Some(vec![b'1']) }],
+            vec![Value { version: 1, content: Some(vec![b'1']), expire_at: 0, num_chunks: 0 }],
             // a write overwrite a tombstone.
             vec![
-                Value { version: 2, content: Some(vec![b'1']) },
-                Value { version: 1, content: None },
+                Value { version: 2, content: Some(vec![b'1']), expire_at: 0, num_chunks: 0 },
+                Value { version: 1, content: None, expire_at: 0, num_chunks: 0 },
             ],
             // a tombstone overwrite a write.
             vec![
-                Value { version: 2, content: None },
-                Value { version: 1, content: Some(vec![b'1']) },
+                Value { version: 2, content: None, expire_at: 0, num_chunks: 0 },
+                Value { version: 1, content: Some(vec![b'1']), expire_at: 0, num_chunks: 0 },
             ],
         ];
 
@@ -1509,4 +2123,397 @@ mod tests {
             assert_eq!(value_set.values, case, "idx = {idx}");
         }
     }
+
+    #[sekas_macro::test]
+    async fn count_live_keys() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        assert_eq!(engine.num_live_keys(1).unwrap(), 0);
+
+        for i in 0..10 {
+            let key = format!("{i:02}");
+            commit_values(&engine, key.as_bytes(), &[Value::with_value(b"".to_vec(), 1)]);
+        }
+        // The tombstones are not counted.
+        commit_values(&engine, b"10", &[Value::tombstone(1)]);
+        assert_eq!(engine.num_live_keys(1).unwrap(), 10);
+    }
+
+    #[sekas_macro::test]
+    async fn shard_usage() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        assert_eq!(engine.shard_usage(1).unwrap(), (0, 0));
+
+        commit_values(&engine, b"a", &[Value::with_value(b"123".to_vec(), 1)]);
+        commit_values(&engine, b"bb", &[Value::with_value(b"4".to_vec(), 1)]);
+        // Only the latest version is counted.
+        commit_values(
+            &engine,
+            b"cc",
+            &[Value::with_value(b"5678".to_vec(), 2), Value::with_value(b"9".to_vec(), 1)],
+        );
+        // The tombstones are not counted.
+        commit_values(&engine, b"d", &[Value::tombstone(2), Value::with_value(b"0".to_vec(), 1)]);
+
+        assert_eq!(engine.shard_usage(1).unwrap(), (3, 4 + 3 + 6));
+    }
+
+    #[sekas_macro::test]
+    async fn ingest_kv_files() {
+        use sekas_api::IngestFileBuilder;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine_with_range(1, 1, b"a".to_vec(), b"c".to_vec(), dir.path()).await;
+        commit_values(&engine, b"a", &[Value::with_value(b"0".to_vec(), 1)]);
+
+        let mut builder = IngestFileBuilder::default();
+        builder.add(b"a", b"1");
+        builder.add(b"b", b"2");
+        let path = dir.path().join("1.kv");
+        std::fs::write(&path, builder.finish()).unwrap();
+        engine.ingest_kv_files(1, 10, &[&path]).unwrap();
+
+        let value = engine.get(1, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"1".to_vec(), 10));
+        let value = engine.get(1, b"b").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"2".to_vec(), 10));
+
+        // The keys out of shard range are rejected.
+        let mut builder = IngestFileBuilder::default();
+        builder.add(b"c", b"3");
+        std::fs::write(&path, builder.finish()).unwrap();
+        assert!(engine.ingest_kv_files(1, 11, &[&path]).is_err());
+    }
+
+    #[sekas_macro::test]
+    async fn compact_group_engine() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        for i in 0..10 {
+            let key = format!("{i:02}");
+            commit_values(&engine, key.as_bytes(), &[Value::with_value(b"".to_vec(), 1)]);
+        }
+        commit_values(&engine, b"05", &[Value::tombstone(2)]);
+
+        engine.compact(None).unwrap();
+        let range = RangePartition { start: b"03".to_vec(), end: vec![] };
+        engine.compact(Some((1, &range))).unwrap();
+        assert!(engine.compact(Some((2, &range))).is_err());
+
+        let value = engine.get(1, b"01").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"".to_vec(), 1));
+        let value = engine.get(1, b"05").await.unwrap().unwrap();
+        assert_eq!(value, Value::tombstone(2));
+    }
+
+    #[sekas_macro::test]
+    async fn delete_shard_data() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        for i in 0..10 {
+            let key = format!("{i:02}");
+            commit_values(&engine, key.as_bytes(), &[Value::with_value(b"".to_vec(), 1)]);
+        }
+
+        // Only the keys in range of the shard are deleted.
+        engine
+            .delete_shard_data(&ShardDesc::with_range(2, 1, b"03".to_vec(), b"05".to_vec()))
+            .unwrap();
+        assert!(engine.get(1, b"02").await.unwrap().is_some());
+        assert!(engine.get(1, b"03").await.unwrap().is_none());
+        assert!(engine.get(1, b"04").await.unwrap().is_none());
+        assert!(engine.get(1, b"05").await.unwrap().is_some());
+
+        engine.delete_shard_data(&ShardDesc::with_range(3, 1, vec![], vec![])).unwrap();
+        let mut snapshot = engine.snapshot(1, SnapshotMode::default()).unwrap();
+        assert!(snapshot.next().is_none());
+    }
+
+    #[sekas_macro::test]
+    async fn read_clone_shard() {
+        use sekas_api::server::v1::CloneSource;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        let mut clone_shard = ShardDesc::with_range(2, 2, vec![], vec![]);
+        clone_shard.clone_source = Some(CloneSource { collection_id: 1, version: 10 });
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![ShardDesc::with_range(1, 1, vec![], vec![]), clone_shard],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        commit_values(&engine, b"a", &[Value::with_value(b"a".to_vec(), 5)]);
+        commit_values(
+            &engine,
+            b"b",
+            &[Value::with_value(b"b".to_vec(), 5), Value::with_value(b"b1".to_vec(), 20)],
+        );
+        commit_values(&engine, b"c", &[Value::with_value(b"c".to_vec(), 5)]);
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, 2, b"c", b"c2", 15).unwrap();
+        engine.tombstone(&mut wb, 2, b"a", 15).unwrap();
+        engine.put(&mut wb, 2, b"d", b"d2", 15).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        // The writes after the clone version are not visible.
+        let value = engine.get(2, b"b").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"b".to_vec(), 5));
+        // The own writes shadow the source.
+        let value = engine.get(2, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::tombstone(15));
+        let value_set = engine.get_all_versions(2, b"c").await.unwrap();
+        assert_eq!(value_set.values[0], Value::with_value(b"c2".to_vec(), 15));
+        // The source is not affected by the clone.
+        let value = engine.get(1, b"c").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"c".to_vec(), 5));
+        assert!(engine.get(1, b"d").await.unwrap().is_none());
+
+        let mut keys = vec![];
+        let mut snapshot = engine.snapshot(2, SnapshotMode::default()).unwrap();
+        while let Some(mvcc_iter) = snapshot.next() {
+            let mut mvcc_iter = mvcc_iter.unwrap();
+            let entry = mvcc_iter.next().unwrap().unwrap();
+            keys.push(entry.user_key().to_vec());
+        }
+        assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]);
+    }
+
+    #[sekas_macro::test]
+    async fn realize_clone_shard() {
+        use sekas_api::server::v1::CloneSource;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        let mut clone_shard = ShardDesc::with_range(2, 2, vec![], vec![]);
+        clone_shard.clone_source = Some(CloneSource { collection_id: 1, version: 10 });
+        let source_shard = ShardDesc::with_range(1, 1, vec![], vec![]);
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![source_shard.clone(), clone_shard.clone()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        for key in [b"a", b"b", b"c"] {
+            commit_values(&engine, key, &[Value::with_value(key.to_vec(), 5)]);
+        }
+        commit_values(&engine, b"b", &[Value::with_value(b"b1".to_vec(), 20)]);
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, 2, b"c", b"c2", 15).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        assert_eq!(engine.next_clone_chunk(2, b"", 2).unwrap(), Some(b"c".to_vec()));
+        assert_eq!(engine.next_clone_chunk(2, b"c", 2).unwrap(), None);
+
+        let mut wb = WriteBatch::default();
+        engine.realize_clone(&mut wb, 2, b"", b"c").unwrap();
+        engine.realize_clone(&mut wb, 2, b"c", b"").unwrap();
+        clone_shard.clone_source = None;
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![source_shard, clone_shard],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        engine.commit(wb, states, false).unwrap();
+
+        // The data is readable without the clone source.
+        let value = engine.get(2, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"a".to_vec(), 5));
+        let value = engine.get(2, b"b").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"b".to_vec(), 5));
+        let value_set = engine.get_all_versions(2, b"c").await.unwrap();
+        assert_eq!(value_set.values.len(), 2);
+        assert_eq!(value_set.values[0], Value::with_value(b"c2".to_vec(), 15));
+    }
+
+    #[sekas_macro::test]
+    async fn read_and_write_encrypted_shard() {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let db = Arc::new(open_engine_with_default_config(dir.path().join("db")).unwrap());
+        let key_manager = Arc::new(KeyManager::new(Some([7; 32])));
+        let data_key = key_manager.generate_data_key(1, 0).unwrap();
+        key_manager.update_data_keys(&[data_key]).unwrap();
+        let engine = GroupEngine::create(&EngineConfig::default(), db, key_manager.clone(), 1, 1)
+            .await
+            .unwrap();
+        let mut shard = ShardDesc::whole(1, 1);
+        shard.encrypted = true;
+        let states = WriteStates {
+            descriptor: Some(GroupDesc { id: 1, shards: vec![shard], ..Default::default() }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        commit_values(&engine, b"a", &[Value::with_value(b"plaintext".to_vec(), 1)]);
+        let value = engine.get(1, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"plaintext".to_vec(), 1));
+
+        // The value is not persisted in plaintext.
+        let raw = engine
+            .raw_db
+            .get_pinned_cf(&engine.cf_handle(), keys::mvcc_key(1, b"a", 1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(raw[0], values::ENCRYPTED);
+        assert!(!raw.windows(b"plaintext".len()).any(|w| w == b"plaintext"));
+
+        // The values can't be read without data keys.
+        key_manager.update_data_keys(&[]).unwrap();
+        assert!(engine.get(1, b"a").await.is_err());
+    }
+
+    #[sekas_macro::test]
+    async fn read_and_write_compressed_shard() {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let db = Arc::new(open_engine_with_default_config(dir.path().join("db")).unwrap());
+        let key_manager = Arc::new(KeyManager::new(Some([7; 32])));
+        let data_key = key_manager.generate_data_key(1, 0).unwrap();
+        key_manager.update_data_keys(&[data_key]).unwrap();
+        let engine =
+            GroupEngine::create(&EngineConfig::default(), db, key_manager, 1, 1).await.unwrap();
+        let mut compressed_shard = ShardDesc::whole(1, 1);
+        compressed_shard.compression = CompressionCodec::Zstd as i32;
+        let mut encrypted_shard = ShardDesc::whole(2, 2);
+        encrypted_shard.compression = CompressionCodec::Lz4 as i32;
+        encrypted_shard.encrypted = true;
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![compressed_shard, encrypted_shard],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        let large_value = b"large value".repeat(64);
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, 1, b"a", &large_value, 1).unwrap();
+        engine.put(&mut wb, 1, b"b", b"small value", 1).unwrap();
+        engine.put(&mut wb, 2, b"a", &large_value, 1).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        let raw_value = |collection_id: u64, key: &[u8]| {
+            engine
+                .raw_db
+                .get_pinned_cf(&engine.cf_handle(), keys::mvcc_key(collection_id, key, 1))
+                .unwrap()
+                .unwrap()
+                .to_vec()
+        };
+        let raw = raw_value(1, b"a");
+        assert_eq!(raw[0], values::COMPRESSED);
+        assert!(raw.len() < large_value.len());
+        assert_eq!(raw_value(1, b"b")[0], values::DATA);
+        assert_eq!(raw_value(2, b"a")[0], values::COMPRESSED_ENCRYPTED);
+
+        // The reads are transparent.
+        let value = engine.get(1, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(large_value.clone(), 1));
+        let value = engine.get(1, b"b").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"small value".to_vec(), 1));
+        let value = engine.get(2, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(large_value, 1));
+    }
+
+    #[sekas_macro::test]
+    async fn read_expiring_values() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+
+        let now = values::unix_timestamp();
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, 1, b"a", b"old", 1).unwrap();
+        engine.put_with_expire_at(&mut wb, 1, b"a", b"expired", 2, now - 1).unwrap();
+        engine.put_with_expire_at(&mut wb, 1, b"b", b"alive", 1, now + 3600).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        // The expired value hides the older versions.
+        let value = engine.get(1, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::tombstone(2));
+
+        let value = engine.get(1, b"b").await.unwrap().unwrap();
+        assert_eq!(value.content, Some(b"alive".to_vec()));
+        assert_eq!(value.expire_at, now + 3600);
+    }
+
+    #[sekas_macro::test]
+    async fn read_chunked_values() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+
+        let mut wb = WriteBatch::default();
+        engine.put_chunked(&mut wb, 1, b"a", 3, 1, 0).unwrap();
+        engine.put(&mut wb, 1, b"b", &values::chunked(3), 1).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        let value = engine.get(1, b"a").await.unwrap().unwrap();
+        assert_eq!(value.content, Some(vec![]));
+        assert_eq!(value.num_chunks, 3);
+        assert_eq!(engine.num_live_keys(1).unwrap(), 2);
+
+        // The user value is never read as a chunked value.
+        let value = engine.get(1, b"b").await.unwrap().unwrap();
+        assert_eq!(value.content, Some(values::chunked(3)));
+        assert_eq!(value.num_chunks, 0);
+    }
+
+    #[sekas_macro::test]
+    async fn block_cache_stats_of_groups() {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let db = Arc::new(open_engine_with_default_config(dir.path().join("db")).unwrap());
+        let create_engine = |group_id: u64| {
+            GroupEngine::create(&EngineConfig::default(), db.clone(), Arc::default(), group_id, 1)
+        };
+        let engine = create_engine(1).await.unwrap();
+        let other_engine = create_engine(2).await.unwrap();
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![ShardDesc::whole(1, 1)],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, 1, b"a", b"value", 1).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+        db.flush_cf(&engine.cf_handle()).unwrap();
+
+        let value = engine.get(1, b"a").await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(b"value".to_vec(), 1));
+        let stats = engine.block_cache_stats();
+        assert!(stats.hits() + stats.misses() > 0);
+        let other_stats = other_engine.block_cache_stats();
+        assert_eq!(other_stats.hits() + other_stats.misses(), 0);
+        assert!(db.block_cache_usage() > 0);
+    }
+
+    #[test]
+    fn collection_upper_bound() {
+        // The collection id is encoded in little endian.
+        assert_eq!(keys::collection_upper_bound(0x1ff), Some(vec![0xff, 1, 0, 0, 0, 0, 0, 1]));
+        assert_eq!(keys::collection_upper_bound(0xff << 56), Some(vec![0, 0, 0, 0, 0, 0, 1]));
+        assert_eq!(keys::collection_upper_bound(u64::MAX), None);
+    }
 }
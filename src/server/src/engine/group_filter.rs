@@ -1,3 +1,4 @@
+// Copyright 2023-present The Sekas Authors.
 // Copyright 2023 The Engula Authors.
 //
 // Licensed under the Apache License, Version 2.0 (the "License");
@@ -12,37 +13,198 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use rocksdb::{
-    compaction_filter::{CompactionFilter, Decision},
-    compaction_filter_factory::CompactionFilterFactory,
-};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::sync::{Arc, RwLock};
 
-struct GroupCompactionFilter {
-    min_allowed_version: u64,
+use rocksdb::compaction_filter::{CompactionFilter, Decision};
+use rocksdb::compaction_filter_factory::{CompactionFilterContext, CompactionFilterFactory};
+use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+use super::group::values;
+use crate::constants::LOCAL_COLLECTION_ID;
+
+const FILTER_NAME: &[u8] = b"sekas.group_compaction_filter\0";
+const FACTORY_NAME: &[u8] = b"sekas.group_compaction_filter_factory\0";
+
+const L: usize = core::mem::size_of::<u64>();
+
+/// The GC horizons of collections, the versions shadowed by a version not
+/// greater than the horizon are no longer readable and could be collected.
+///
+/// The horizons are synced from the root, see `Root::min_gc_timestamp`.
+#[derive(Clone, Default)]
+pub(crate) struct GcHorizons {
+    horizons: Arc<RwLock<HashMap<u64, u64>>>,
+}
+
+impl GcHorizons {
+    /// Advance the GC horizon of the collection, the horizon never goes back.
+    pub(crate) fn advance(&self, collection_id: u64, horizon: u64) {
+        let mut horizons = self.horizons.write().expect("poisoned");
+        let current = horizons.entry(collection_id).or_default();
+        *current = std::cmp::max(*current, horizon);
+    }
+
+    /// Remove the GC horizons of the collections not in `collection_ids`.
+    pub(crate) fn retain(&self, collection_ids: &[u64]) {
+        let mut horizons = self.horizons.write().expect("poisoned");
+        horizons.retain(|id, _| collection_ids.contains(id));
+    }
+
+    fn snapshot(&self) -> HashMap<u64, u64> {
+        self.horizons.read().expect("poisoned").clone()
+    }
+}
+
+/// Replace the expired values with tombstones and remove the versions below
+/// the GC horizon during compaction, to reclaim the space of them. The
+/// tombstones are still required to hide the older versions of the same keys.
+///
+/// It is safe to rewrite the values independently on each replica, since the
+/// expired values are already read as tombstones, and the versions shadowed
+/// by a version below the horizon are never read.
+pub(super) struct GroupCompactionFilter {
+    now: u64,
+    horizons: HashMap<u64, u64>,
+    /// The mvcc key prefix (without version) of the last visited key, and
+    /// whether a version not greater than the horizon of it has been kept.
+    last_key: Vec<u8>,
+    shadowed: bool,
+}
+
+impl GroupCompactionFilter {
+    fn new(now: u64, horizons: HashMap<u64, u64>) -> Self {
+        GroupCompactionFilter { now, horizons, last_key: Vec::default(), shadowed: false }
+    }
+
+    fn is_expired(&self, value: &[u8]) -> bool {
+        values::expire_at(value).map(|expire_at| expire_at <= self.now).unwrap_or_default()
+    }
+
+    /// Whether the version is shadowed by a newer version not greater than
+    /// the GC horizon. The versions of a key are visited in descending order.
+    fn is_shadowed(&mut self, collection_id: u64, key: &[u8]) -> bool {
+        let (prefix, version) = key.split_at(key.len() - L);
+        let version = !u64::from_be_bytes(version.try_into().expect("8 bytes"));
+        if self.last_key != prefix {
+            self.last_key.clear();
+            self.last_key.extend_from_slice(prefix);
+            self.shadowed = false;
+        }
+        if version == TXN_INTENT_VERSION {
+            return false;
+        }
+        if self.shadowed {
+            return true;
+        }
+        let horizon = self.horizons.get(&collection_id).cloned().unwrap_or_default();
+        self.shadowed = version <= horizon;
+        false
+    }
 }
 
 impl CompactionFilter for GroupCompactionFilter {
-    fn filter(&mut self, level: u32, key: &[u8], value: &[u8]) -> Decision {
-        todo!()
+    fn filter(&mut self, _level: u32, key: &[u8], value: &[u8]) -> Decision {
+        // Only the mvcc keys of the user collections are rewritten.
+        if key.len() <= 2 * L || (key.len() - 2 * L) % 9 != 0 {
+            return Decision::Keep;
+        }
+        let collection_id = u64::from_le_bytes(key[..L].try_into().expect("8 bytes"));
+        if collection_id == LOCAL_COLLECTION_ID {
+            return Decision::Keep;
+        }
+        if self.is_shadowed(collection_id, key) {
+            Decision::Remove
+        } else if self.is_expired(value) {
+            Decision::Change(values::tombstone())
+        } else {
+            Decision::Keep
+        }
     }
 
-    /// Returns a name that identifies this compaction filter.
-    /// The name will be printed to LOG file on start up for diagnosis.
     fn name(&self) -> &CStr {
-        todo!()
+        CStr::from_bytes_with_nul(FILTER_NAME).expect("nul terminated")
     }
 }
 
-struct GroupCompactionFactory {}
+/// Create a [`GroupCompactionFilter`] for each compaction.
+pub(super) struct GroupCompactionFactory {
+    horizons: GcHorizons,
+}
+
+impl GroupCompactionFactory {
+    pub(super) fn new(horizons: GcHorizons) -> Self {
+        GroupCompactionFactory { horizons }
+    }
+}
 
 impl CompactionFilterFactory for GroupCompactionFactory {
-    type Filter: CompactionFilter;
+    type Filter = GroupCompactionFilter;
 
-    /// Returns a CompactionFilter for the compaction process
-    fn create(&mut self, context: CompactionFilterContext) -> Self::Filter;
+    fn create(&mut self, _context: CompactionFilterContext) -> Self::Filter {
+        GroupCompactionFilter::new(values::unix_timestamp(), self.horizons.snapshot())
+    }
 
-    /// Returns a name that identifies this compaction filter factory.
     fn name(&self) -> &CStr {
-        &Cstr::new("group compaction filter")
+        CStr::from_bytes_with_nul(FACTORY_NAME).expect("nul terminated")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::group::keys;
+
+    #[test]
+    fn replace_expired_values() {
+        let mut filter = GroupCompactionFilter::new(100, HashMap::default());
+        let key = keys::mvcc_key(1, b"key", 10);
+        let expired = values::expiring(100, &values::data(b"value"));
+        let alive = values::expiring(101, &values::data(b"value"));
+        let tombstone = values::tombstone();
+        assert!(matches!(filter.filter(0, &key, &expired), Decision::Change(v) if v == tombstone));
+        assert!(matches!(filter.filter(0, &key, &alive), Decision::Keep));
+        assert!(matches!(filter.filter(0, &key, &values::data(b"value")), Decision::Keep));
+
+        // The local states are never changed.
+        let local_key = [LOCAL_COLLECTION_ID.to_le_bytes().as_slice(), b"key"].concat();
+        assert!(matches!(filter.filter(0, &local_key, &expired), Decision::Keep));
+    }
+
+    #[test]
+    fn remove_versions_below_gc_horizon() {
+        let horizons = GcHorizons::default();
+        horizons.advance(1, 10);
+        let mut filter = GroupCompactionFilter::new(0, horizons.snapshot());
+        let value = values::data(b"value");
+
+        // The intents and the versions above the horizon are kept.
+        let key = keys::mvcc_key(1, b"key", TXN_INTENT_VERSION);
+        assert!(matches!(filter.filter(0, &key, &value), Decision::Keep));
+        let key = keys::mvcc_key(1, b"key", 12);
+        assert!(matches!(filter.filter(0, &key, &value), Decision::Keep));
+        // The newest version not greater than the horizon is kept.
+        let key = keys::mvcc_key(1, b"key", 10);
+        assert!(matches!(filter.filter(0, &key, &value), Decision::Keep));
+        // The versions shadowed by it are removed.
+        let key = keys::mvcc_key(1, b"key", 8);
+        assert!(matches!(filter.filter(0, &key, &value), Decision::Remove));
+        let key = keys::mvcc_key(1, b"key", 6);
+        assert!(matches!(filter.filter(0, &key, &values::tombstone()), Decision::Remove));
+
+        // The versions of other keys and collections are kept.
+        let key = keys::mvcc_key(1, b"key1", 8);
+        assert!(matches!(filter.filter(0, &key, &value), Decision::Keep));
+        let key = keys::mvcc_key(2, b"key1", 8);
+        assert!(matches!(filter.filter(0, &key, &value), Decision::Keep));
+        let key = keys::mvcc_key(2, b"key1", 6);
+        assert!(matches!(filter.filter(0, &key, &value), Decision::Keep));
+
+        // The horizon never goes back.
+        horizons.advance(1, 5);
+        assert_eq!(horizons.snapshot().get(&1), Some(&10));
+        horizons.retain(&[2]);
+        assert!(horizons.snapshot().is_empty());
     }
 }
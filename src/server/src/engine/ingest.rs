@@ -0,0 +1,305 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use sekas_api::server::v1::IngestFile;
+
+use crate::{Error, Result};
+
+/// The size of the buffer to read the ingest files when verifying.
+const VERIFY_BUFFER_SIZE: usize = 64 << 10;
+
+/// Staging the uploaded ingest files, until they are ingested by the group
+/// engines.
+///
+/// The files are uploaded to the staging dir of the shard, and moved to the
+/// dir of a token unique to the proposal once the ingestion is confirmed, so
+/// the files confirmed are never changed by the later uploads.
+#[derive(Clone)]
+pub(crate) struct IngestStore {
+    dir: PathBuf,
+    /// Serialize the uploads and the sealing, so that no upload writes to a
+    /// file which is sealed.
+    lock: Arc<Mutex<()>>,
+}
+
+impl IngestStore {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        IngestStore { dir, lock: Arc::default() }
+    }
+
+    /// Append a chunk of data to the staged ingest file of the shard, return
+    /// the size of the file. The file will be truncated if the offset is 0.
+    pub(crate) fn append(
+        &self,
+        shard_id: u64,
+        name: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<u64> {
+        let dir = self.staging_dir(shard_id);
+        let path = dir.join(validate_name(name)?);
+        let _guard = self.lock.lock().expect("poisoned");
+        std::fs::create_dir_all(&dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(offset == 0)
+            .append(offset != 0)
+            .open(&path)?;
+        let size = file.metadata()?.len();
+        if size != offset {
+            return Err(Error::InvalidArgument(format!(
+                "ingest file {name} has {size} bytes, but the offset of chunk is {offset}"
+            )));
+        }
+        file.write_all(data)?;
+        file.sync_data()?;
+        Ok(size + data.len() as u64)
+    }
+
+    /// Move the staged files of the shard to the dir of the token, return the
+    /// paths of the sealed files. The files already sealed are skipped, so it
+    /// could be retried.
+    pub(crate) fn seal(
+        &self,
+        shard_id: u64,
+        token: &str,
+        files: &[IngestFile],
+    ) -> Result<Vec<PathBuf>> {
+        let staging_dir = self.staging_dir(shard_id);
+        let sealed_dir = self.sealed_dir(token)?;
+        let _guard = self.lock.lock().expect("poisoned");
+        std::fs::create_dir_all(&sealed_dir)?;
+        let mut paths = Vec::with_capacity(files.len());
+        for file in files {
+            let name = validate_name(&file.name)?;
+            let path = sealed_dir.join(name);
+            match std::fs::rename(staging_dir.join(name), &path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound && path.exists() => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(Error::InvalidArgument(format!(
+                        "ingest file {name} is not uploaded"
+                    )));
+                }
+                Err(err) => return Err(err.into()),
+            }
+            paths.push(path);
+        }
+        File::open(&sealed_dir)?.sync_all()?;
+        Ok(paths)
+    }
+
+    /// Return the paths of the files sealed under the token.
+    pub(crate) fn sealed_paths(&self, token: &str, files: &[IngestFile]) -> Result<Vec<PathBuf>> {
+        let sealed_dir = self.sealed_dir(token)?;
+        let mut paths = Vec::with_capacity(files.len());
+        for file in files {
+            let path = sealed_dir.join(validate_name(&file.name)?);
+            if !path.exists() {
+                return Err(Error::InvalidData(format!(
+                    "the sealed ingest file {} of token {token} is lost",
+                    file.name
+                )));
+            }
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Remove the files sealed under the token, it is not an error if they do
+    /// not exist.
+    pub(crate) fn remove(&self, token: &str) -> Result<()> {
+        match std::fs::remove_dir_all(self.sealed_dir(token)?) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn staging_dir(&self, shard_id: u64) -> PathBuf {
+        self.dir.join("staging").join(shard_id.to_string())
+    }
+
+    fn sealed_dir(&self, token: &str) -> Result<PathBuf> {
+        Ok(self.dir.join("sealed").join(validate_name(token)?))
+    }
+}
+
+/// Verify the fingerprint of the ingest file by streaming it, the keys of its
+/// records are checked by `check_key` along the way.
+pub(crate) fn verify_ingest_file(
+    path: &Path,
+    file: &IngestFile,
+    mut check_key: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<()> {
+    let not_matched = || {
+        Error::InvalidData(format!("the fingerprint of ingest file {} is not matched", file.name))
+    };
+    let content = File::open(path)?;
+    if content.metadata()?.len() != file.size {
+        return Err(not_matched());
+    }
+    let mut reader = HashReader {
+        inner: BufReader::with_capacity(VERIFY_BUFFER_SIZE, content),
+        hasher: crc32fast::Hasher::new(),
+    };
+    let mut offset = 0;
+    let mut key = Vec::new();
+    while offset < file.size {
+        let key_len = read_len(&mut reader, &mut offset, file)?;
+        key.resize(key_len as usize, 0);
+        reader.read_exact(&mut key)?;
+        check_key(&key)?;
+        let value_len = read_len(&mut reader, &mut offset, file)?;
+        std::io::copy(&mut (&mut reader).take(value_len), &mut std::io::sink())?;
+    }
+    if reader.hasher.finalize() != file.crc32 {
+        return Err(not_matched());
+    }
+    Ok(())
+}
+
+/// Read the length of the slice following it, and advance the offset over
+/// both of them. The slice must be within the file.
+fn read_len<R: Read>(reader: &mut R, offset: &mut u64, file: &IngestFile) -> Result<u64> {
+    let corrupted = || Error::InvalidData(format!("ingest file {} is corrupted", file.name));
+    let remaining = file.size - *offset;
+    if remaining < 4 {
+        return Err(corrupted());
+    }
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    let len = u32::from_le_bytes(buf) as u64;
+    if len > remaining - 4 {
+        return Err(corrupted());
+    }
+    *offset += 4 + len;
+    Ok(len)
+}
+
+fn validate_name(name: &str) -> Result<&str> {
+    let is_valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && Path::new(name).file_name().map(|n| n == name).unwrap_or_default();
+    if !is_valid {
+        return Err(Error::InvalidArgument(format!("invalid ingest file name {name:?}")));
+    }
+    Ok(name)
+}
+
+/// Feed the bytes read to the hasher.
+struct HashReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::IngestFileBuilder;
+    use sekas_rock::fn_name;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn verify(path: &Path, file: &IngestFile) -> Result<Vec<Vec<u8>>> {
+        let mut keys = vec![];
+        verify_ingest_file(path, file, |key| {
+            keys.push(key.to_owned());
+            Ok(())
+        })?;
+        Ok(keys)
+    }
+
+    #[test]
+    fn ingest_store_append_and_seal() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let store = IngestStore::new(dir.path().join("ingest"));
+
+        assert!(store.append(1, "../a", 0, b"123").is_err());
+        assert_eq!(store.append(1, "a.kv", 0, b"123").unwrap(), 3);
+        assert!(store.append(1, "a.kv", 1, b"456").is_err());
+        assert_eq!(store.append(1, "a.kv", 3, b"456").unwrap(), 6);
+
+        // The files are staged per shard.
+        let file = IngestFile::fingerprint("a.kv".to_owned(), b"123456");
+        assert!(store.seal(2, "t1", &[file.clone()]).is_err());
+        let paths = store.seal(1, "t1", &[file.clone()]).unwrap();
+        assert_eq!(paths, vec![dir.path().join("ingest").join("sealed").join("t1").join("a.kv")]);
+        // Seal again is a no-op.
+        assert_eq!(store.seal(1, "t1", &[file.clone()]).unwrap(), paths);
+        assert_eq!(store.sealed_paths("t1", &[file.clone()]).unwrap(), paths);
+
+        // The sealed files are not changed by the later uploads.
+        assert_eq!(store.append(1, "a.kv", 0, b"12345").unwrap(), 5);
+        assert_eq!(std::fs::read(&paths[0]).unwrap(), b"123456");
+        let file = IngestFile::fingerprint("a.kv".to_owned(), b"12345");
+        assert!(store.seal(1, "t2", &[file.clone()]).is_ok());
+
+        store.remove("t1").unwrap();
+        assert!(store.sealed_paths("t1", &[file.clone()]).is_err());
+        store.remove("t1").unwrap();
+        assert!(store.remove("../t2").is_err());
+        assert!(store.sealed_paths("t2", &[file]).is_ok());
+    }
+
+    #[test]
+    fn verify_ingest_file_content() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let path = dir.path().join("a.kv");
+        let mut builder = IngestFileBuilder::default();
+        builder.add(b"a", b"1");
+        builder.add(b"b", &vec![2; VERIFY_BUFFER_SIZE * 2]);
+        builder.add(b"c", b"");
+        let content = builder.finish();
+        std::fs::write(&path, &content).unwrap();
+
+        let file = IngestFile::fingerprint("a.kv".to_owned(), &content);
+        assert_eq!(
+            verify(&path, &file).unwrap(),
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]
+        );
+        let file = IngestFile::fingerprint("a.kv".to_owned(), &content[1..]);
+        assert!(verify(&path, &file).is_err());
+        let mut file = IngestFile::fingerprint("a.kv".to_owned(), &content);
+        file.crc32 = file.crc32.wrapping_add(1);
+        assert!(verify(&path, &file).is_err());
+
+        // The truncated records.
+        std::fs::write(&path, &content[..content.len() - 4]).unwrap();
+        let file = IngestFile::fingerprint("a.kv".to_owned(), &content[..content.len() - 4]);
+        assert!(verify(&path, &file).is_err());
+        std::fs::write(&path, u32::MAX.to_le_bytes()).unwrap();
+        let file = IngestFile::fingerprint("a.kv".to_owned(), &u32::MAX.to_le_bytes());
+        assert!(verify(&path, &file).is_err());
+
+        // The errors of checking keys are returned.
+        std::fs::write(&path, &content).unwrap();
+        let file = IngestFile::fingerprint("a.kv".to_owned(), &content);
+        let r = verify_ingest_file(&path, &file, |_| Err(Error::InvalidArgument("key".into())));
+        assert!(matches!(r, Err(Error::InvalidArgument(_))));
+    }
+}
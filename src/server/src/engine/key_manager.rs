@@ -0,0 +1,238 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use log::info;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use sekas_api::server::v1::DataKey;
+
+use crate::{EngineConfig, Error, Result};
+
+/// The length of master key and data keys, in bytes.
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KEY_ID_LEN: usize = core::mem::size_of::<u64>();
+
+/// Manage the keys of the envelope encryption.
+///
+/// The values of encrypted shards are encrypted by data keys with AES-256-GCM,
+/// and the data keys are encrypted by the master key, so only the wrapped data
+/// keys are persisted or transferred between nodes. The master key is loaded
+/// from the local file specified by [`EngineConfig::encryption_key_file`].
+#[derive(Default)]
+pub struct KeyManager {
+    master_key: Option<[u8; KEY_LEN]>,
+    data_keys: RwLock<DataKeys>,
+}
+
+#[derive(Default)]
+struct DataKeys {
+    /// The id of data key to encrypt new values.
+    current: Option<u64>,
+    keys: HashMap<u64, [u8; KEY_LEN]>,
+}
+
+impl KeyManager {
+    pub fn new(master_key: Option<[u8; KEY_LEN]>) -> Self {
+        KeyManager { master_key, data_keys: RwLock::default() }
+    }
+
+    /// Load the master key from the key file, the encryption is disabled if no
+    /// key file is specified.
+    pub fn open(cfg: &EngineConfig) -> Result<Self> {
+        let Some(path) = cfg.encryption_key_file.as_ref() else {
+            return Ok(KeyManager::default());
+        };
+        let content = std::fs::read_to_string(path)?;
+        let master_key = parse_hex_key(content.trim()).ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "the encryption key file {path} should contain a 256-bit key in hex"
+            ))
+        })?;
+        info!("load encryption master key from {path}");
+        Ok(KeyManager::new(Some(master_key)))
+    }
+
+    /// Whether the master key is configured.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.master_key.is_some()
+    }
+
+    /// Generate a new data key wrapped by the master key.
+    pub fn generate_data_key(&self, id: u64, created_at: u64) -> Result<DataKey> {
+        let master_key = self.master_key()?;
+        let mut data_key = [0u8; KEY_LEN];
+        openssl::rand::rand_bytes(&mut data_key).map_err(crypto_error)?;
+        let mut wrapped_key = Vec::with_capacity(NONCE_LEN + TAG_LEN + KEY_LEN);
+        seal(master_key, &id.to_le_bytes(), &data_key, &mut wrapped_key)?;
+        Ok(DataKey { id, wrapped_key, created_at })
+    }
+
+    /// Unwrap and install the data keys, the data key with the largest id
+    /// becomes the current key. Return whether the data keys are changed.
+    pub fn update_data_keys(&self, data_keys: &[DataKey]) -> Result<bool> {
+        let master_key = self.master_key()?;
+        {
+            let installed = self.data_keys.read().expect("poisoned");
+            if installed.keys.len() == data_keys.len()
+                && data_keys.iter().all(|k| installed.keys.contains_key(&k.id))
+            {
+                return Ok(false);
+            }
+        }
+
+        let mut keys = HashMap::with_capacity(data_keys.len());
+        for data_key in data_keys {
+            let key = unseal(master_key, &data_key.id.to_le_bytes(), &data_key.wrapped_key)?;
+            let key = key.try_into().map_err(|_| {
+                Error::InvalidData(format!("the length of data key {} is invalid", data_key.id))
+            })?;
+            keys.insert(data_key.id, key);
+        }
+        let current = keys.keys().max().cloned();
+
+        let mut data_keys = self.data_keys.write().expect("poisoned");
+        if data_keys.current != current {
+            info!("update data keys, current data key {current:?}");
+        }
+        *data_keys = DataKeys { current, keys };
+        Ok(true)
+    }
+
+    /// Encrypt the plaintext with the current data key, and append the result
+    /// to `buf`.
+    pub fn encrypt(&self, plaintext: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+        let data_keys = self.data_keys.read().expect("poisoned");
+        let (key_id, key) = data_keys
+            .current
+            .and_then(|id| data_keys.keys.get(&id).map(|key| (id, key)))
+            .ok_or_else(|| Error::InvalidData("no data key is available".to_owned()))?;
+        buf.extend_from_slice(&key_id.to_le_bytes());
+        seal(key, &key_id.to_le_bytes(), plaintext, buf)
+    }
+
+    /// Decrypt the data produced by [`KeyManager::encrypt`].
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < KEY_ID_LEN {
+            return Err(Error::InvalidData("the encrypted value is truncated".to_owned()));
+        }
+        let (key_id, sealed) = data.split_at(KEY_ID_LEN);
+        let data_keys = self.data_keys.read().expect("poisoned");
+        let key = u64::from_le_bytes(key_id.try_into().unwrap());
+        let key = data_keys
+            .keys
+            .get(&key)
+            .ok_or_else(|| Error::InvalidData(format!("data key {key} not found")))?;
+        unseal(key, key_id, sealed)
+    }
+
+    fn master_key(&self) -> Result<&[u8; KEY_LEN]> {
+        self.master_key
+            .as_ref()
+            .ok_or_else(|| Error::InvalidArgument("the encryption is not enabled".to_owned()))
+    }
+}
+
+/// Encrypt the plaintext and append `nonce | tag | ciphertext` to `buf`.
+fn seal(key: &[u8; KEY_LEN], aad: &[u8], plaintext: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    let mut nonce = [0u8; NONCE_LEN];
+    openssl::rand::rand_bytes(&mut nonce).map_err(crypto_error)?;
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext =
+        encrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce), aad, plaintext, &mut tag)
+            .map_err(crypto_error)?;
+    buf.extend_from_slice(&nonce);
+    buf.extend_from_slice(&tag);
+    buf.extend_from_slice(&ciphertext);
+    Ok(())
+}
+
+fn unseal(key: &[u8; KEY_LEN], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err(Error::InvalidData("the encrypted value is truncated".to_owned()));
+    }
+    let (nonce, rest) = sealed.split_at(NONCE_LEN);
+    let (tag, ciphertext) = rest.split_at(TAG_LEN);
+    decrypt_aead(Cipher::aes_256_gcm(), key, Some(nonce), aad, ciphertext, tag)
+        .map_err(crypto_error)
+}
+
+fn parse_hex_key(hex: &str) -> Option<[u8; KEY_LEN]> {
+    if hex.len() != KEY_LEN * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; KEY_LEN];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+fn crypto_error(err: openssl::error::ErrorStack) -> Error {
+    Error::InvalidData(format!("crypto: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let manager = KeyManager::new(Some([1; KEY_LEN]));
+        let mut buf = vec![];
+        assert!(manager.encrypt(b"value", &mut buf).is_err());
+
+        let key_1 = manager.generate_data_key(1, 0).unwrap();
+        assert!(manager.update_data_keys(&[key_1.clone()]).unwrap());
+        assert!(!manager.update_data_keys(&[key_1.clone()]).unwrap());
+        let mut encrypted = vec![];
+        manager.encrypt(b"value", &mut encrypted).unwrap();
+        assert_ne!(&encrypted[KEY_ID_LEN..], b"value");
+        assert_eq!(manager.decrypt(&encrypted).unwrap(), b"value");
+
+        // The values encrypted by the old key are still readable after rotation.
+        let key_2 = manager.generate_data_key(2, 0).unwrap();
+        manager.update_data_keys(&[key_1, key_2]).unwrap();
+        let mut rotated = vec![];
+        manager.encrypt(b"value", &mut rotated).unwrap();
+        assert_eq!(&rotated[..KEY_ID_LEN], 2u64.to_le_bytes().as_slice());
+        assert_eq!(manager.decrypt(&encrypted).unwrap(), b"value");
+        assert_eq!(manager.decrypt(&rotated).unwrap(), b"value");
+
+        // The tampered value is rejected.
+        let last = rotated.len() - 1;
+        rotated[last] ^= 1;
+        assert!(manager.decrypt(&rotated).is_err());
+    }
+
+    #[test]
+    fn unwrap_with_wrong_master_key() {
+        let manager = KeyManager::new(Some([1; KEY_LEN]));
+        let data_key = manager.generate_data_key(1, 0).unwrap();
+        let other = KeyManager::new(Some([2; KEY_LEN]));
+        assert!(other.update_data_keys(&[data_key]).is_err());
+        assert!(!KeyManager::default().is_enabled());
+    }
+
+    #[test]
+    fn parse_master_key() {
+        assert_eq!(parse_hex_key(&"0f".repeat(KEY_LEN)), Some([0xf; KEY_LEN]));
+        assert!(parse_hex_key("0f").is_none());
+        assert!(parse_hex_key(&"zz".repeat(KEY_LEN)).is_none());
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use lazy_static::lazy_static;
+use prometheus::*;
+
+lazy_static! {
+    pub static ref ENGINE_COMPRESSION_INPUT_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "engine_compression_input_bytes_total",
+        "The total bytes of values before compression"
+    )
+    .unwrap();
+    pub static ref ENGINE_COMPRESSION_OUTPUT_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "engine_compression_output_bytes_total",
+        "The total bytes of values after compression"
+    )
+    .unwrap();
+    pub static ref ENGINE_COMPRESSION_RATIO: Histogram = register_histogram!(
+        "engine_compression_ratio",
+        "The ratio of the compressed size to the original size of values",
+        linear_buckets(0.05, 0.05, 20).unwrap(),
+    )
+    .unwrap();
+}
@@ -12,34 +12,61 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod block_cache;
+mod compression;
 mod group;
+mod group_filter;
+mod ingest;
+mod key_manager;
+mod metrics;
 mod state;
 
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use log::info;
 use sekas_rock::fs::create_dir_all_if_not_exists;
 
+pub(crate) use self::block_cache::BlockCacheStats;
 pub(crate) use self::group::{
     GroupEngine, MvccIterator, RawIterator, Snapshot, SnapshotMode, WriteBatch, WriteStates,
 };
+pub(crate) use self::group_filter::GcHorizons;
+pub(crate) use self::ingest::{verify_ingest_file, IngestStore};
+pub(crate) use self::key_manager::KeyManager;
 pub(crate) use self::state::StateEngine;
-use crate::{DbConfig, Result};
+use crate::{DbConfig, Error, RaftConfig, Result};
 
 // The disk layouts.
 const LAYOUT_DATA: &str = "db";
 const LAYOUT_LOG: &str = "log";
+const LAYOUT_LOG_ENGINE: &str = "engine";
 const LAYOUT_SNAP: &str = "snap";
+const LAYOUT_INGEST: &str = "ingest";
 
 type DbResult<T> = Result<T, rocksdb::Error>;
 
 pub(crate) struct RawDb {
     pub options: rocksdb::Options,
     pub db: rocksdb::DB,
+    /// The GC horizons consulted by the compaction filter.
+    pub gc_horizons: GcHorizons,
+    /// The block cache shared by all column families, eg group engines.
+    pub block_cache: rocksdb::Cache,
 }
 
 impl RawDb {
+    /// Return the memory size of entries residing in the block cache.
+    #[inline]
+    pub fn block_cache_usage(&self) -> usize {
+        self.block_cache.get_usage()
+    }
+
+    /// Return the names of all column families.
+    pub fn cf_names(&self) -> DbResult<Vec<String>> {
+        rocksdb::DB::list_cf(&self.options, self.db.path())
+    }
+
     #[inline]
     pub fn cf_handle(&self, name: &str) -> Option<Arc<rocksdb::BoundColumnFamily>> {
         self.db.cf_handle(name)
@@ -98,6 +125,16 @@ impl RawDb {
         self.db.iterator_cf_opt(cf_handle, readopts, mode)
     }
 
+    #[inline]
+    pub fn compact_range_cf(
+        &self,
+        cf: &impl rocksdb::AsColumnFamilyRef,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+    ) {
+        self.db.compact_range_cf(cf, start, end)
+    }
+
     #[inline]
     pub fn ingest_external_file_cf_opts<P: AsRef<Path>>(
         &self,
@@ -109,23 +146,48 @@ impl RawDb {
     }
 }
 
+/// Pauses the compaction and purging of raft logs while a checkpoint is being
+/// created, so the copied logs still cover the applied index of the
+/// checkpointed group engines.
+#[derive(Clone, Default)]
+pub(crate) struct LogPurgeFence {
+    lock: Arc<RwLock<()>>,
+}
+
+impl LogPurgeFence {
+    /// Enter the fence before purging raft logs, `None` if the purging is
+    /// paused.
+    #[inline]
+    pub fn try_enter(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        self.lock.try_read().ok()
+    }
+
+    fn pause(&self) -> RwLockWriteGuard<'_, ()> {
+        self.lock.write().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct Engines {
     log_path: PathBuf,
-    _db_path: PathBuf,
+    db_path: PathBuf,
     log: Arc<raft_engine::Engine>,
     db: Arc<RawDb>,
     state: StateEngine,
+    ingest_store: IngestStore,
+    log_purge_fence: LogPurgeFence,
 }
 
 impl Engines {
-    pub(crate) fn open(root_dir: &Path, db_cfg: &DbConfig) -> Result<Self> {
+    pub(crate) fn open(root_dir: &Path, db_cfg: &DbConfig, raft_cfg: &RaftConfig) -> Result<Self> {
         let db_path = root_dir.join(LAYOUT_DATA);
-        let log_path = root_dir.join(LAYOUT_LOG);
+        let log_path = raft_cfg.log_dir.clone().unwrap_or_else(|| root_dir.join(LAYOUT_LOG));
         let db = Arc::new(open_raw_db(db_cfg, &db_path)?);
-        let log = Arc::new(open_raft_engine(&log_path)?);
+        let log = Arc::new(open_raft_engine(&log_path, raft_cfg)?);
         let state = StateEngine::new(log.clone());
-        Ok(Engines { log_path, _db_path: db_path, log, db, state })
+        let ingest_store = IngestStore::new(root_dir.join(LAYOUT_INGEST));
+        let log_purge_fence = LogPurgeFence::default();
+        Ok(Engines { log_path, db_path, log, db, state, ingest_store, log_purge_fence })
     }
 
     #[inline]
@@ -138,22 +200,96 @@ impl Engines {
         self.db.clone()
     }
 
+    #[inline]
+    pub(crate) fn gc_horizons(&self) -> GcHorizons {
+        self.db.gc_horizons.clone()
+    }
+
     #[inline]
     pub(crate) fn state(&self) -> StateEngine {
         self.state.clone()
     }
 
+    #[inline]
+    pub(crate) fn ingest_store(&self) -> IngestStore {
+        self.ingest_store.clone()
+    }
+
+    #[inline]
+    pub(crate) fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
     #[inline]
     pub(crate) fn snap_dir(&self) -> PathBuf {
         self.log_path.join(LAYOUT_SNAP)
     }
+
+    #[inline]
+    pub(crate) fn log_purge_fence(&self) -> LogPurgeFence {
+        self.log_purge_fence.clone()
+    }
+
+    /// Create a consistent checkpoint of the state engine and all group
+    /// engines in `dir`, which has the same layout as the root dir, so a node
+    /// could be started from it.
+    ///
+    /// The group engines and snapshots are checkpointed by hard links, the raft
+    /// logs are copied since they are appended in place.
+    pub(crate) fn checkpoint(&self, dir: &Path) -> Result<()> {
+        if dir.exists() {
+            return Err(Error::InvalidArgument(format!(
+                "checkpoint dir {} already exists",
+                dir.display()
+            )));
+        }
+
+        // The raft logs are copied after the group engines, they must not be
+        // purged beyond the applied index of the checkpointed group engines.
+        let _fence = self.log_purge_fence.pause();
+        std::fs::create_dir_all(dir)?;
+        rocksdb::checkpoint::Checkpoint::new(&self.db.db)?
+            .create_checkpoint(dir.join(LAYOUT_DATA))?;
+        let log_dir = dir.join(LAYOUT_LOG);
+        copy_dir(&self.log_path.join(LAYOUT_LOG_ENGINE), &log_dir.join(LAYOUT_LOG_ENGINE), false)?;
+        copy_dir(&self.log_path.join(LAYOUT_SNAP), &log_dir.join(LAYOUT_SNAP), true)?;
+        info!("create checkpoint of engines in {}", dir.display());
+        Ok(())
+    }
+}
+
+/// Copy the files of `from` to `to` recursively, by hard links if `link` is
+/// set. The files removed during copying are skipped.
+fn copy_dir(from: &Path, to: &Path, link: bool) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let (src, dst) = (entry.path(), to.join(entry.file_name()));
+        let result = if entry.file_type()?.is_dir() {
+            copy_dir(&src, &dst, link)
+        } else if link {
+            std::fs::hard_link(&src, &dst).map_err(Error::from)
+        } else {
+            std::fs::copy(&src, &dst).map(|_| ()).map_err(Error::from)
+        };
+        match result {
+            Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {}
+            result => result?,
+        }
+    }
+    Ok(())
 }
 
 pub(crate) fn open_raw_db<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<RawDb> {
-    use rocksdb::DB;
+    use rocksdb::{Cache, DB};
 
     std::fs::create_dir_all(&path)?;
-    let options = cfg.to_options();
+    let block_cache = Cache::new_lru_cache(cfg.block_cache_capacity());
+    let mut options = cfg.to_options(&block_cache);
+    let gc_horizons = GcHorizons::default();
+    options.set_compaction_filter_factory(group_filter::GroupCompactionFactory::new(
+        gc_horizons.clone(),
+    ));
 
     // List column families and open database with column families.
     match DB::list_cf(&options, &path) {
@@ -164,13 +300,13 @@ pub(crate) fn open_raw_db<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<Raw
                 path,
                 cfs.into_iter().map(|name| (name, options.clone())),
             )?;
-            Ok(RawDb { db, options })
+            Ok(RawDb { db, options, gc_horizons, block_cache })
         }
         Err(e) => {
             if e.as_ref().ends_with("CURRENT: No such file or directory") {
                 info!("create new local db: {}", path.as_ref().display());
                 let db = DB::open(&options, &path)?;
-                Ok(RawDb { db, options })
+                Ok(RawDb { db, options, gc_horizons, block_cache })
             } else {
                 Err(e.into())
             }
@@ -178,17 +314,20 @@ pub(crate) fn open_raw_db<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<Raw
     }
 }
 
-pub(crate) fn open_raft_engine(log_path: &Path) -> Result<raft_engine::Engine> {
-    use raft_engine::{Config, Engine};
-    let engine_dir = log_path.join("engine");
-    let snap_dir = log_path.join("snap");
+pub(crate) fn open_raft_engine(log_path: &Path, cfg: &RaftConfig) -> Result<raft_engine::Engine> {
+    use raft_engine::{Config, Engine, ReadableSize};
+    let engine_dir = log_path.join(LAYOUT_LOG_ENGINE);
+    let snap_dir = log_path.join(LAYOUT_SNAP);
     create_dir_all_if_not_exists(&engine_dir)?;
     create_dir_all_if_not_exists(&snap_dir)?;
-    let engine_cfg = Config {
+    let mut engine_cfg = Config {
         dir: engine_dir.to_str().unwrap().to_owned(),
         enable_log_recycle: false,
         ..Default::default()
     };
+    if cfg.log_bytes_per_sync > 0 {
+        engine_cfg.bytes_per_sync = ReadableSize(cfg.log_bytes_per_sync);
+    }
     Ok(Engine::open(engine_cfg)?)
 }
 
@@ -207,10 +346,15 @@ pub async fn create_group_engine(
     const COL_ID: u64 = 1;
     let db = Arc::new(open_raw_db(&DbConfig::default(), dir).unwrap());
 
-    let group_engine =
-        GroupEngine::create(&EngineConfig::default(), db.clone(), group_id, replica_id)
-            .await
-            .unwrap();
+    let group_engine = GroupEngine::create(
+        &EngineConfig::default(),
+        db.clone(),
+        Arc::default(),
+        group_id,
+        replica_id,
+    )
+    .await
+    .unwrap();
     let wb = WriteBatch::default();
     let states = WriteStates {
         descriptor: Some(GroupDesc {
@@ -259,18 +403,84 @@ mod tests {
         let dir = TempDir::new(fn_name!()).unwrap();
 
         {
-            let engine = open_raft_engine(dir.path()).unwrap();
+            let engine = open_raft_engine(dir.path(), &RaftConfig::default()).unwrap();
             let mut batch = LogBatch::default();
             batch.put(1, vec![1, 2, 3], vec![4, 5, 6]);
             engine.write(&mut batch, true).unwrap();
         }
 
         {
-            let engine = open_raft_engine(dir.path()).unwrap();
+            let engine = open_raft_engine(dir.path(), &RaftConfig::default()).unwrap();
             let result = engine.get(1, &[1, 2, 3]);
             assert!(matches!(result, Some(x) if x == vec![4, 5, 6]));
             let result = engine.get(1, &[4, 5, 6]);
             assert!(result.is_none());
         }
     }
+
+    #[test]
+    fn open_engines_with_separated_log_dir() {
+        let root_dir = TempDir::new(fn_name!()).unwrap();
+        let log_dir = TempDir::new(fn_name!()).unwrap();
+        let raft_cfg = RaftConfig {
+            log_dir: Some(log_dir.path().to_owned()),
+            sync_log: true,
+            log_bytes_per_sync: 1 << 20,
+            ..Default::default()
+        };
+
+        {
+            let engines = Engines::open(root_dir.path(), &DbConfig::default(), &raft_cfg).unwrap();
+            assert_eq!(engines.snap_dir(), log_dir.path().join(LAYOUT_SNAP));
+            let mut batch = LogBatch::default();
+            batch.put(1, vec![1, 2, 3], vec![4, 5, 6]);
+            engines.log().write(&mut batch, true).unwrap();
+        }
+
+        assert!(log_dir.path().join("engine").exists());
+        assert!(root_dir.path().join(LAYOUT_DATA).exists());
+        assert!(!root_dir.path().join(LAYOUT_LOG).exists());
+
+        let engines = Engines::open(root_dir.path(), &DbConfig::default(), &raft_cfg).unwrap();
+        let result = engines.log().get(1, &[1, 2, 3]);
+        assert!(matches!(result, Some(x) if x == vec![4, 5, 6]));
+    }
+
+    #[test]
+    fn open_engines_from_checkpoint() {
+        let root_dir = TempDir::new(fn_name!()).unwrap();
+        let log_dir = TempDir::new(fn_name!()).unwrap();
+        let checkpoint_dir = TempDir::new(fn_name!()).unwrap();
+        let checkpoint_dir = checkpoint_dir.path().join("checkpoint");
+        let raft_cfg =
+            RaftConfig { log_dir: Some(log_dir.path().to_owned()), ..Default::default() };
+
+        let engines = Engines::open(root_dir.path(), &DbConfig::default(), &raft_cfg).unwrap();
+        engines.db().create_cf("cf1").unwrap();
+        let cf = engines.db().cf_handle("cf1").unwrap();
+        engines.db().db.put_cf(&cf, [1, 2, 3], [4, 5, 6]).unwrap();
+        let mut batch = LogBatch::default();
+        batch.put(1, vec![1, 2, 3], vec![4, 5, 6]);
+        engines.log().write(&mut batch, false).unwrap();
+        engines.checkpoint(&checkpoint_dir).unwrap();
+
+        // The data written after the checkpoint is not visible.
+        engines.db().db.put_cf(&cf, [4, 5, 6], [1, 2, 3]).unwrap();
+        let mut batch = LogBatch::default();
+        batch.put(1, vec![4, 5, 6], vec![1, 2, 3]);
+        engines.log().write(&mut batch, false).unwrap();
+
+        // The checkpoint dir must not exist.
+        assert!(matches!(engines.checkpoint(&checkpoint_dir), Err(Error::InvalidArgument(_))));
+
+        let engines =
+            Engines::open(&checkpoint_dir, &DbConfig::default(), &RaftConfig::default()).unwrap();
+        let cf = engines.db().cf_handle("cf1").unwrap();
+        let result = engines.db().get_pinned_cf(&cf, [1, 2, 3]).unwrap();
+        assert!(matches!(result, Some(x) if x.as_ref() == [4, 5, 6]));
+        assert!(engines.db().get_pinned_cf(&cf, [4, 5, 6]).unwrap().is_none());
+        let result = engines.log().get(1, &[1, 2, 3]);
+        assert!(matches!(result, Some(x) if x == vec![4, 5, 6]));
+        assert!(engines.log().get(1, &[4, 5, 6]).is_none());
+    }
 }
@@ -27,6 +27,7 @@ use crate::Result;
 /// - node ident
 /// - root node descriptors
 /// - replica states
+/// - wrapped data keys
 ///
 /// NOTE: The group descriptors is stored in the corresponding GroupEngine,
 /// which is to ensure that both the changes of group descriptor and data are
@@ -77,6 +78,24 @@ impl StateEngine {
         Ok(self.raw.get_message::<RootDesc>(STATE_REPLICA_ID, keys::root_desc())?)
     }
 
+    /// Save the wrapped data keys.
+    pub async fn save_data_keys(&self, keys: &[DataKey]) -> Result<()> {
+        use raft_engine::LogBatch;
+
+        let data_keys = DataKeySet { keys: keys.to_owned() };
+        let mut lb = LogBatch::default();
+        lb.put_message(STATE_REPLICA_ID, keys::data_keys().to_owned(), &data_keys)
+            .expect("DataKeySet is Serializable");
+        self.raw.write(&mut lb, true)?;
+        Ok(())
+    }
+
+    /// Load the wrapped data keys.
+    pub async fn load_data_keys(&self) -> Result<Vec<DataKey>> {
+        let data_keys = self.raw.get_message::<DataKeySet>(STATE_REPLICA_ID, keys::data_keys())?;
+        Ok(data_keys.map(|d| d.keys).unwrap_or_default())
+    }
+
     /// Save replica state.
     pub async fn save_replica_state(
         &self,
@@ -125,6 +144,7 @@ mod keys {
     const ROOT_DESCRIPTOR_KEY: &[u8] = &[0x2];
     const REPLICA_STATE_PREFIX: &[u8] = &[0x3];
     const REPLICA_STATE_END: &[u8] = &[0x4];
+    const DATA_KEYS_KEY: &[u8] = &[0x5];
 
     pub fn node_ident() -> &'static [u8] {
         IDENT_KEY
@@ -134,6 +154,10 @@ mod keys {
         ROOT_DESCRIPTOR_KEY
     }
 
+    pub fn data_keys() -> &'static [u8] {
+        DATA_KEYS_KEY
+    }
+
     pub fn replica_state_prefix() -> &'static [u8] {
         REPLICA_STATE_PREFIX
     }
@@ -157,18 +181,22 @@ mod tests {
 
     use super::*;
     use crate::engine::open_raft_engine;
+    use crate::RaftConfig;
 
     #[sekas_macro::test]
     async fn save_and_load_node_ident() {
         let dir = TempDir::new(fn_name!()).unwrap();
-        let engine = StateEngine::new(Arc::new(open_raft_engine(dir.path()).unwrap()));
+        let engine = StateEngine::new(Arc::new(
+            open_raft_engine(dir.path(), &RaftConfig::default()).unwrap(),
+        ));
 
         // Read ident not exists.
         let ident = engine.read_ident().await.unwrap();
         assert!(ident.is_none());
 
         // Save ident
-        let ident = NodeIdent { cluster_id: vec![1, 7, 9, 3, 9, 4], node_id: 123321 };
+        let ident =
+            NodeIdent { cluster_id: vec![1, 7, 9, 3, 9, 4], node_id: 123321, incarnation: 7 };
         engine.save_ident(&ident).await.unwrap();
 
         // Read ident again.
@@ -179,7 +207,9 @@ mod tests {
     #[sekas_macro::test]
     async fn save_and_load_root_desc() {
         let dir = TempDir::new(fn_name!()).unwrap();
-        let engine = StateEngine::new(Arc::new(open_raft_engine(dir.path()).unwrap()));
+        let engine = StateEngine::new(Arc::new(
+            open_raft_engine(dir.path(), &RaftConfig::default()).unwrap(),
+        ));
 
         // Load node desc not exists.
         let desc = engine.load_root_desc().await.unwrap();
@@ -193,7 +223,11 @@ mod tests {
                 addr: "localhost:10011".into(),
                 capacity: None,
                 status: NodeStatus::Active.into(),
+                binary_version: 0,
+                labels: vec![],
+                incarnation: 0,
             }],
+            cluster_version: 0,
         };
         engine.save_root_desc(&desc).await.unwrap();
 
@@ -205,7 +239,9 @@ mod tests {
     #[sekas_macro::test]
     async fn save_and_read_replica_states() {
         let dir = TempDir::new(fn_name!()).unwrap();
-        let engine = StateEngine::new(Arc::new(open_raft_engine(dir.path()).unwrap()));
+        let engine = StateEngine::new(Arc::new(
+            open_raft_engine(dir.path(), &RaftConfig::default()).unwrap(),
+        ));
         let expect_states = vec![
             (1, 1, ReplicaLocalState::Normal),
             (2, 2, ReplicaLocalState::Pending),
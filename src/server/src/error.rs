@@ -12,7 +12,9 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use sekas_api::server::v1::{GroupDesc, ReplicaDesc, RootDesc, Value};
+use std::time::Duration;
+
+use sekas_api::server::v1::{GroupDesc, NodeStatus, ReplicaDesc, RootDesc, Value};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -38,6 +40,22 @@ pub enum Error {
     #[error("condition {1} not satisfied, operation index {0}")]
     CasFailed(/* index */ u64, /* cond_index */ u64, Option<Value>),
 
+    /// The collection is read-only, the writes to its shards are rejected.
+    #[error("collection {0} is frozen")]
+    CollectionFrozen(u64),
+
+    /// The request is issued by or to a stale incarnation of the node, eg. the
+    /// node is wiped and re-joined the cluster.
+    #[error("node {0} incarnation {2} not match, {1} is expected")]
+    NodeIncarnationMismatch(/* node_id */ u64, /* expected */ u64, /* actual */ u64),
+
+    #[error("node {0} not found")]
+    NodeNotFound(u64),
+
+    /// The current status of the node doesn't allow the operation.
+    #[error("node {0} is {1:?}")]
+    NodeStatusNotMatch(/* node_id */ u64, NodeStatus),
+
     // internal errors
     #[error("shard {0} not found")]
     ShardNotFound(u64),
@@ -85,6 +103,16 @@ pub enum Error {
     #[error("group {0} not found")]
     GroupNotFound(u64),
 
+    /// The writes of the group are rejected by admission control, the client
+    /// should retry after the duration.
+    #[error("group {0} is busy")]
+    GroupBusy(u64, Duration),
+
+    /// The request exceeds the quota of the client, the client should retry
+    /// after the duration. Zero means it is never admitted.
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String, Duration),
+
     #[error("not root leader")]
     NotRootLeader(RootDesc, u64, Option<ReplicaDesc>),
 
@@ -109,6 +137,7 @@ pub enum BusyReason {
     Moving,
     AclGuard,
     PendingConfigChange,
+    PendingIngest,
     RequestChannelFulled,
     ProposalDropped,
 }
@@ -119,6 +148,7 @@ impl std::fmt::Display for BusyReason {
             BusyReason::AclGuard => "take acl guard",
             BusyReason::Moving => "in shard migrating",
             BusyReason::PendingConfigChange => "has pending config change",
+            BusyReason::PendingIngest => "has pending ingested entries",
             BusyReason::Transfering => "leader transfering",
             BusyReason::RequestChannelFulled => "request channel fulled",
             BusyReason::ProposalDropped => "proposal dropped by raft",
@@ -154,12 +184,44 @@ impl From<Error> for tonic::Status {
                 "cas failed".to_string(),
                 v1::Error::cas_failed(index, cond_index, prev_value).encode_to_vec().into(),
             ),
+            Error::CollectionFrozen(collection_id) => Status::with_details(
+                Code::Unknown,
+                e.to_string(),
+                v1::Error::collection_frozen(collection_id).encode_to_vec().into(),
+            ),
+            Error::NodeIncarnationMismatch(node_id, expected, actual) => Status::with_details(
+                Code::Unknown,
+                e.to_string(),
+                v1::Error::node_incarnation_mismatch(node_id, expected, actual)
+                    .encode_to_vec()
+                    .into(),
+            ),
+            Error::NodeNotFound(node_id) => Status::with_details(
+                Code::NotFound,
+                e.to_string(),
+                v1::Error::node_not_found(node_id).encode_to_vec().into(),
+            ),
+            Error::NodeStatusNotMatch(node_id, status) => Status::with_details(
+                Code::FailedPrecondition,
+                e.to_string(),
+                v1::Error::node_status_not_match(node_id, status).encode_to_vec().into(),
+            ),
 
             Error::GroupNotFound(group_id) => Status::with_details(
                 Code::Unknown,
                 e.to_string(),
                 v1::Error::group_not_found(group_id).encode_to_vec().into(),
             ),
+            Error::GroupBusy(group_id, retry_after) => Status::with_details(
+                Code::Unknown,
+                e.to_string(),
+                v1::Error::group_busy(group_id, retry_after.as_millis() as u64)
+                    .encode_to_vec()
+                    .into(),
+            ),
+            Error::QuotaExceeded(msg, retry_after) => {
+                sekas_client::error::quota_exceeded_status(msg, retry_after)
+            }
             Error::NotLeader(group_id, term, leader) => Status::with_details(
                 Code::Unknown,
                 format!("not leader of group {}", group_id),
@@ -221,6 +283,12 @@ impl From<Error> for sekas_api::server::v1::Error {
 
         match err {
             Error::GroupNotFound(group_id) => v1::Error::group_not_found(group_id),
+            Error::GroupBusy(group_id, retry_after) => {
+                v1::Error::group_busy(group_id, retry_after.as_millis() as u64)
+            }
+            Error::QuotaExceeded(msg, retry_after) => {
+                v1::Error::quota_exceeded(msg, retry_after.as_millis() as u64)
+            }
             Error::NotLeader(group_id, term, leader) => {
                 v1::Error::not_leader(group_id, term, leader)
             }
@@ -234,6 +302,14 @@ impl From<Error> for sekas_api::server::v1::Error {
             Error::CasFailed(index, cond_index, prev_value) => {
                 v1::Error::cas_failed(index, cond_index, prev_value)
             }
+            Error::CollectionFrozen(collection_id) => v1::Error::collection_frozen(collection_id),
+            Error::NodeIncarnationMismatch(node_id, expected, actual) => {
+                v1::Error::node_incarnation_mismatch(node_id, expected, actual)
+            }
+            Error::NodeNotFound(node_id) => v1::Error::node_not_found(node_id),
+            Error::NodeStatusNotMatch(node_id, status) => {
+                v1::Error::node_status_not_match(node_id, status)
+            }
 
             Error::Forward(_) => panic!("Forward only used inside node"),
             Error::ServiceIsBusy(_) => panic!("ServiceIsBusy only used inside node"),
@@ -274,15 +350,27 @@ impl From<sekas_client::Error> for Error {
             sekas_client::Error::CasFailed(index, cond_index, prev_value) => {
                 Error::CasFailed(index, cond_index, prev_value)
             }
+            sekas_client::Error::CollectionFrozen(v) => Error::CollectionFrozen(v),
+            sekas_client::Error::NodeIncarnationMismatch(node_id, expected, actual) => {
+                Error::NodeIncarnationMismatch(node_id, expected, actual)
+            }
+            sekas_client::Error::NodeNotFound(v) => Error::NodeNotFound(v),
+            sekas_client::Error::NodeStatusNotMatch(node_id, status) => {
+                Error::NodeStatusNotMatch(node_id, status)
+            }
             sekas_client::Error::Rpc(err) => Error::Rpc(err),
             sekas_client::Error::Connect(err) => Error::Rpc(err),
             sekas_client::Error::Transport(err) => Error::Rpc(err),
 
             sekas_client::Error::GroupNotFound(v) => Error::GroupNotFound(v),
+            sekas_client::Error::GroupBusy(v, retry_after) => Error::GroupBusy(v, retry_after),
+            sekas_client::Error::QuotaExceeded(v, retry_after) => {
+                Error::QuotaExceeded(v, retry_after)
+            }
             sekas_client::Error::NotRootLeader(desc, term, leader) => {
                 Error::NotRootLeader(desc, term, leader)
             }
-            sekas_client::Error::NotLeader(group, term, leader) => {
+            sekas_client::Error::NotLeader(group, term, leader, _) => {
                 Error::NotLeader(group, term, leader)
             }
             sekas_client::Error::EpochNotMatch(v) => Error::EpochNotMatch(v),
@@ -298,3 +386,42 @@ impl From<sekas_client::Error> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn structured_errors_across_rpc() {
+        let status = tonic::Status::from(Error::NodeStatusNotMatch(1, NodeStatus::Cordoned));
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        let err = sekas_client::Error::from(status);
+        assert!(
+            matches!(err, sekas_client::Error::NodeStatusNotMatch(1, NodeStatus::Cordoned)),
+            "{err:?}"
+        );
+
+        let status = tonic::Status::from(Error::NodeNotFound(2));
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        let err = sekas_client::Error::from(status);
+        assert!(matches!(err, sekas_client::Error::NodeNotFound(2)), "{err:?}");
+
+        // The plain statuses are kept.
+        let err = sekas_client::Error::from(tonic::Status::not_found("database"));
+        assert!(matches!(err, sekas_client::Error::NotFound(_)), "{err:?}");
+    }
+
+    #[test]
+    fn retryable_error_details() {
+        use sekas_api::server::v1;
+
+        let err = v1::Error::from(Error::GroupNotFound(1));
+        assert!(err.details[0].retryable);
+        let err = v1::Error::from(Error::NodeNotFound(1));
+        assert!(!err.details[0].retryable);
+
+        // The flag is respected even the detail is unknown.
+        let detail = v1::ErrorDetail { retryable: true, ..Default::default() };
+        assert!(detail.is_retryable());
+    }
+}
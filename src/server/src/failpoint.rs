@@ -0,0 +1,250 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The named injection points to exercise the failure windows, such as
+//! crashing between two steps of moving shard, deterministically in tests.
+//!
+//! The failpoints are only compiled with the `failpoints` feature, otherwise
+//! [`fail_point!`] expands to nothing. The actions of a failpoint are
+//! configured by [`configure`] or the `/admin/failpoints` endpoint, in the
+//! format of `[<count>*]<action>[(<arg>)]`:
+//! - `off`: do nothing.
+//! - `return(msg)`: return an injected error, the message is optional.
+//! - `panic(msg)`: panic, to simulate the crash of the node.
+//! - `sleep(ms)`: block the current thread for the milliseconds.
+//!
+//! The `count` limits the times the action is triggered, eg. `3*return` returns
+//! errors for the first three times, then the failpoint is turned off.
+//!
+//! The faults of network between nodes, such as partitions and latency, are
+//! injected into the raft transport by [`set_network_fault`].
+
+/// Evaluate the failpoint by name. For the `return` action, the function
+/// returns an injected error by default, or the value of the closure-like
+/// body with the message of the action. The infallible paths use the `panic`
+/// form, the `return` action panics there.
+#[cfg(feature = "failpoints")]
+macro_rules! fail_point {
+    ($name:expr) => {
+        $crate::failpoint::fail_point!($name, |msg| {
+            Err($crate::failpoint::injected_error($name, &msg))
+        })
+    };
+    ($name:expr, | $msg:pat_param | $body:expr) => {
+        if let Some($msg) = $crate::failpoint::eval($name) {
+            return $body;
+        }
+    };
+    ($name:expr,panic) => {
+        if let Some(msg) = $crate::failpoint::eval($name) {
+            panic!("failpoint {} returns in an infallible path: {msg}", $name);
+        }
+    };
+}
+
+#[cfg(not(feature = "failpoints"))]
+macro_rules! fail_point {
+    ($name:expr) => {};
+    ($name:expr, | $msg:pat_param | $body:expr) => {};
+    ($name:expr,panic) => {};
+}
+
+pub(crate) use fail_point;
+
+#[cfg(feature = "failpoints")]
+pub use self::registry::*;
+
+#[cfg(feature = "failpoints")]
+mod registry {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use lazy_static::lazy_static;
+    use log::info;
+
+    use crate::{Error, Result};
+
+    lazy_static! {
+        static ref REGISTRY: Mutex<HashMap<String, FailPoint>> = Mutex::default();
+        static ref NETWORK_FAULTS: Mutex<HashMap<(u64, u64), NetworkFault>> = Mutex::default();
+    }
+
+    /// The faults of the raft messages sent from one node to another.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct NetworkFault {
+        /// The messages are dropped.
+        pub partitioned: bool,
+        /// The messages are delayed.
+        pub latency: Duration,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Action {
+        Off,
+        Return(String),
+        Panic(String),
+        Sleep(u64),
+    }
+
+    #[derive(Debug, Clone)]
+    struct FailPoint {
+        actions: String,
+        action: Action,
+        /// The remaining times to trigger the action, `None` means no limit.
+        remaining: Option<u64>,
+    }
+
+    /// Configure the actions of the failpoint, see the module doc for the
+    /// format. The `off` action removes the failpoint.
+    pub fn configure(name: &str, actions: &str) -> Result<()> {
+        let (remaining, action) = parse(actions)?;
+        let mut registry = REGISTRY.lock().unwrap();
+        if action == Action::Off {
+            registry.remove(name);
+        } else {
+            let fp = FailPoint { actions: actions.to_owned(), action, remaining };
+            registry.insert(name.to_owned(), fp);
+        }
+        info!("configure failpoint {name} with actions {actions:?}");
+        Ok(())
+    }
+
+    /// Remove all failpoints.
+    pub fn clear() {
+        REGISTRY.lock().unwrap().clear();
+    }
+
+    /// List the configured failpoints and their actions.
+    pub fn list() -> Vec<(String, String)> {
+        let registry = REGISTRY.lock().unwrap();
+        let mut fps = registry
+            .iter()
+            .map(|(name, fp)| (name.clone(), fp.actions.clone()))
+            .collect::<Vec<_>>();
+        fps.sort_unstable();
+        fps
+    }
+
+    /// Trigger the action of the failpoint, return the message if the
+    /// failpoint is required to return.
+    pub fn eval(name: &str) -> Option<String> {
+        let action = {
+            let mut registry = REGISTRY.lock().unwrap();
+            let fp = registry.get_mut(name)?;
+            let action = fp.action.clone();
+            if let Some(remaining) = fp.remaining.as_mut() {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    registry.remove(name);
+                }
+            }
+            action
+        };
+        match action {
+            Action::Off => None,
+            Action::Return(msg) => Some(msg),
+            Action::Panic(msg) => panic!("failpoint {name} panics: {msg}"),
+            Action::Sleep(ms) => {
+                std::thread::sleep(Duration::from_millis(ms));
+                None
+            }
+        }
+    }
+
+    /// Set the faults of the raft messages sent from node `from` to node `to`,
+    /// the default fault removes them.
+    pub fn set_network_fault(from: u64, to: u64, fault: NetworkFault) {
+        let mut faults = NETWORK_FAULTS.lock().unwrap();
+        if fault == NetworkFault::default() {
+            faults.remove(&(from, to));
+        } else {
+            faults.insert((from, to), fault);
+        }
+    }
+
+    /// Remove the faults of network between all nodes.
+    pub fn clear_network_faults() {
+        NETWORK_FAULTS.lock().unwrap().clear();
+    }
+
+    pub fn network_fault(from: u64, to: u64) -> NetworkFault {
+        NETWORK_FAULTS.lock().unwrap().get(&(from, to)).cloned().unwrap_or_default()
+    }
+
+    /// Return the error injected by the failpoint.
+    pub fn injected_error(name: &str, msg: &str) -> Error {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("failpoint {name} injected: {msg}"),
+        ))
+    }
+
+    fn parse(actions: &str) -> Result<(Option<u64>, Action)> {
+        let invalid = || Error::InvalidArgument(format!("invalid failpoint actions {actions:?}"));
+        let (remaining, action) = match actions.split_once('*') {
+            Some((count, action)) => {
+                let count = count.trim().parse::<u64>().map_err(|_| invalid())?;
+                if count == 0 {
+                    return Err(invalid());
+                }
+                (Some(count), action.trim())
+            }
+            None => (None, actions.trim()),
+        };
+        let (kind, arg) = match action.split_once('(') {
+            Some((kind, arg)) => (kind, arg.strip_suffix(')').ok_or_else(invalid)?),
+            None => (action, ""),
+        };
+        let action = match kind {
+            "off" => Action::Off,
+            "return" => Action::Return(arg.to_owned()),
+            "panic" => Action::Panic(arg.to_owned()),
+            "sleep" => Action::Sleep(arg.parse().map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        };
+        Ok((remaining, action))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parse_failpoint_actions() {
+            assert_eq!(parse("off").unwrap(), (None, Action::Off));
+            assert_eq!(parse("return").unwrap(), (None, Action::Return(String::new())));
+            assert_eq!(parse("3*return(io)").unwrap(), (Some(3), Action::Return("io".into())));
+            assert_eq!(parse("sleep(10)").unwrap(), (None, Action::Sleep(10)));
+            assert!(parse("0*panic").is_err());
+            assert!(parse("sleep(ms)").is_err());
+            assert!(parse("return(").is_err());
+            assert!(parse("unknown").is_err());
+        }
+
+        #[test]
+        fn trigger_failpoint_limited_times() {
+            fn inject() -> Result<()> {
+                crate::failpoint::fail_point!("failpoint_test_inject");
+                Ok(())
+            }
+
+            configure("failpoint_test_inject", "2*return(test)").unwrap();
+            assert!(inject().is_err());
+            assert!(inject().is_err());
+            assert!(inject().is_ok());
+            assert!(list().iter().all(|(name, _)| name != "failpoint_test_inject"));
+        }
+    }
+}
@@ -18,15 +18,19 @@
 #![feature(type_name_of_val)]
 #![feature(const_type_name)]
 
+mod auth;
 mod bootstrap;
 mod config;
 mod constants;
+mod dynamic_config;
 mod engine;
 mod error;
+pub mod failpoint;
 mod replica;
 mod root;
 mod schedule;
 mod service;
+mod trace;
 mod transport;
 
 pub mod node;
@@ -37,9 +41,13 @@ pub(crate) use tonic::async_trait;
 
 pub use crate::bootstrap::run;
 pub use crate::config::*;
+pub use crate::dynamic_config::{DynamicConfig, HOT_RELOADABLE_OPTIONS};
 pub use crate::error::{Error, Result};
 pub use crate::root::diagnosis;
+#[cfg(feature = "sim")]
+pub use crate::root::sim;
 pub use crate::service::Server;
+pub use crate::trace::{init_tracer, shutdown_tracer};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,234 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dump the data and raft state of a replica into a human-inspectable
+//! directory, to debug the corruption or divergence of replicas offline.
+//!
+//! The layout of the directory:
+//! - `summary.json`: the number of dumped keys, versions and intents.
+//! - `descriptor.json`: the group descriptor of the replica.
+//! - `raft_state.json`: the raft and apply states of the replica.
+//! - `shard-<id>.jsonl`: one line for each version of the keys in the shard.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use prost::Message;
+use sekas_api::server::v1::*;
+use sekas_schema::system::txn::TXN_INTENT_VERSION;
+use serde::Serialize;
+
+use crate::engine::{GroupEngine, SnapshotMode};
+use crate::raftgroup::RaftGroupState;
+use crate::{Error, Result};
+
+/// The summary of a dumped replica.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DumpSummary {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub num_shards: usize,
+    pub num_keys: u64,
+    pub num_versions: u64,
+    pub num_intents: u64,
+}
+
+#[derive(Serialize)]
+struct DumpEntry {
+    key: String,
+    version: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    tombstone: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    intent: Option<DumpIntent>,
+}
+
+#[derive(Serialize)]
+struct DumpIntent {
+    start_version: u64,
+    is_delete: bool,
+    value: Option<String>,
+}
+
+/// Dump the replica into `dir`, the dir must not exist.
+pub(crate) fn dump_replica(
+    replica_id: u64,
+    group_engine: &GroupEngine,
+    raft_state: &RaftGroupState,
+    dir: &Path,
+) -> Result<DumpSummary> {
+    if dir.exists() {
+        return Err(Error::InvalidArgument(format!("{} already exists", dir.display())));
+    }
+    std::fs::create_dir_all(dir)?;
+
+    let descriptor = group_engine.descriptor();
+    let apply_state = group_engine.flushed_apply_state()?;
+    write_json(&dir.join("descriptor.json"), &descriptor_json(&descriptor))?;
+    let raft_json = serde_json::json!({
+        "term": raft_state.hs.term,
+        "vote": raft_state.hs.vote,
+        "commit": raft_state.hs.commit,
+        "leader_id": raft_state.ss.leader_id,
+        "role": format!("{:?}", raft_state.ss.raft_state),
+        "applied": raft_state.applied,
+        "committed": raft_state.committed,
+        "first_index": raft_state.first_index,
+        "last_index": raft_state.last_index,
+        "flushed_apply_index": apply_state.index,
+        "flushed_apply_term": apply_state.term,
+    });
+    write_json(&dir.join("raft_state.json"), &raft_json)?;
+
+    let mut summary = DumpSummary {
+        group_id: descriptor.id,
+        replica_id,
+        num_shards: descriptor.shards.len(),
+        ..Default::default()
+    };
+    for shard in &descriptor.shards {
+        let path = dir.join(format!("shard-{}.jsonl", shard.id));
+        dump_shard(group_engine, shard.id, &path, &mut summary)?;
+    }
+    write_json(&dir.join("summary.json"), &summary)?;
+    Ok(summary)
+}
+
+fn dump_shard(
+    group_engine: &GroupEngine,
+    shard_id: u64,
+    path: &Path,
+    summary: &mut DumpSummary,
+) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut snapshot = group_engine.snapshot(shard_id, SnapshotMode::Start { start_key: None })?;
+    while let Some(mvcc_iter) = snapshot.next() {
+        summary.num_keys += 1;
+        for entry in mvcc_iter? {
+            let entry = entry?;
+            summary.num_versions += 1;
+            let intent = if entry.version() == TXN_INTENT_VERSION {
+                summary.num_intents += 1;
+                let intent = TxnIntent::decode(entry.value().unwrap_or_default())?;
+                Some(DumpIntent {
+                    start_version: intent.start_version,
+                    is_delete: intent.is_delete,
+                    value: intent.value.as_deref().map(escape),
+                })
+            } else {
+                None
+            };
+            let dump_entry = DumpEntry {
+                key: escape(entry.user_key()),
+                version: entry.version(),
+                value: if intent.is_none() { entry.value().map(escape) } else { None },
+                tombstone: entry.is_tombstone(),
+                intent,
+            };
+            serde_json::to_writer(&mut writer, &dump_entry)
+                .map_err(|e| Error::InvalidData(e.to_string()))?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn descriptor_json(desc: &GroupDesc) -> serde_json::Value {
+    let replicas = desc
+        .replicas
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "id": r.id,
+                "node_id": r.node_id,
+                "role": ReplicaRole::from_i32(r.role).map(|role| role.as_str_name()),
+            })
+        })
+        .collect::<Vec<_>>();
+    let shards = desc
+        .shards
+        .iter()
+        .map(|s| {
+            let range = s.range.clone().unwrap_or_default();
+            serde_json::json!({
+                "id": s.id,
+                "collection_id": s.collection_id,
+                "start": escape(&range.start),
+                "end": escape(&range.end),
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({
+        "id": desc.id,
+        "epoch": desc.epoch,
+        "replicas": replicas,
+        "shards": shards,
+    })
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let content =
+        serde_json::to_vec_pretty(value).map_err(|e| Error::InvalidData(e.to_string()))?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Escape the bytes as printable ASCII, the non-printable bytes are shown as
+/// `\xNN`.
+fn escape(bytes: &[u8]) -> String {
+    bytes.escape_ascii().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_rock::fn_name;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::engine::{create_group_engine, WriteBatch, WriteStates};
+
+    const SHARD_ID: u64 = 1;
+
+    #[sekas_macro::test]
+    async fn dump_replica_data_and_intents() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, SHARD_ID, 1).await;
+
+        let intent = TxnIntent::with_put(9, Some(b"v2".to_vec()));
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, SHARD_ID, b"a", b"v1", 1).unwrap();
+        engine.put(&mut wb, SHARD_ID, b"a", &intent.encode_to_vec(), TXN_INTENT_VERSION).unwrap();
+        engine.tombstone(&mut wb, SHARD_ID, b"\x01", 2).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        let dump_dir = dir.path().join("dump");
+        let summary = dump_replica(1, &engine, &RaftGroupState::default(), &dump_dir).unwrap();
+        assert_eq!(summary.num_keys, 2);
+        assert_eq!(summary.num_versions, 3);
+        assert_eq!(summary.num_intents, 1);
+
+        let content =
+            std::fs::read_to_string(dump_dir.join(format!("shard-{SHARD_ID}.jsonl"))).unwrap();
+        assert_eq!(content.lines().count(), 3);
+        assert!(content.contains(r#""key":"\\x01""#));
+        assert!(content.contains(r#""start_version":9"#));
+        assert!(dump_dir.join("raft_state.json").exists());
+
+        // The dir must not exist.
+        assert!(dump_replica(1, &engine, &RaftGroupState::default(), &dump_dir).is_err());
+    }
+}
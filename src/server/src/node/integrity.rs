@@ -0,0 +1,140 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use serde::Serialize;
+
+use crate::engine::{Engines, GroupEngine, KeyManager};
+use crate::raftgroup::check_storage_integrity;
+use crate::serverpb::v1::ReplicaLocalState;
+use crate::{EngineConfig, RaftConfig, Result};
+
+/// A replica refused to serve by the startup integrity check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BrokenReplica {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub reason: String,
+}
+
+/// The result of the integrity check when the node starts.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StartupReport {
+    /// The number of replicas checked.
+    pub num_checked: usize,
+    /// The replicas that won't be served, to avoid corrupting them further.
+    pub broken_replicas: Vec<BrokenReplica>,
+    /// The group engines without any replica metadata. They are kept
+    /// untouched for manual inspection.
+    pub orphan_engines: Vec<String>,
+}
+
+impl StartupReport {
+    /// Return whether any problem is found.
+    #[inline]
+    pub fn has_problems(&self) -> bool {
+        !self.broken_replicas.is_empty() || !self.orphan_engines.is_empty()
+    }
+
+    /// Return whether the replica of the group is broken.
+    #[inline]
+    pub fn is_group_broken(&self, group_id: u64) -> bool {
+        self.broken_replicas.iter().any(|r| r.group_id == group_id)
+    }
+}
+
+/// Verify the engine manifests, replica metadata and raft states of all local
+/// replicas.
+pub(super) async fn check_integrity(
+    engine_cfg: &EngineConfig,
+    raft_cfg: &RaftConfig,
+    engines: &Engines,
+    key_manager: Arc<KeyManager>,
+    replica_states: &[(u64, u64, ReplicaLocalState)],
+) -> Result<StartupReport> {
+    let mut report = StartupReport::default();
+    for &(group_id, replica_id, state) in replica_states {
+        if matches!(state, ReplicaLocalState::Tombstone | ReplicaLocalState::Terminated) {
+            continue;
+        }
+
+        report.num_checked += 1;
+        let result = check_replica(
+            engine_cfg,
+            raft_cfg,
+            engines,
+            key_manager.clone(),
+            group_id,
+            replica_id,
+            state,
+        )
+        .await;
+        if let Err(reason) = result {
+            error!("group {group_id} replica {replica_id} is broken: {reason}");
+            report.broken_replicas.push(BrokenReplica { group_id, replica_id, reason });
+        }
+    }
+
+    let known_replicas =
+        replica_states.iter().map(|(_, replica_id, _)| *replica_id).collect::<HashSet<_>>();
+    for name in engines.db().cf_names()? {
+        let Some((group_id, replica_id)) = GroupEngine::parse_cf_name(&name) else { continue };
+        if !known_replicas.contains(&replica_id) {
+            warn!("group {group_id} replica {replica_id} group engine {name} has no replica meta");
+            report.orphan_engines.push(name);
+        }
+    }
+
+    if report.has_problems() {
+        error!(
+            "startup integrity check found {} broken replicas and {} orphan group engines",
+            report.broken_replicas.len(),
+            report.orphan_engines.len()
+        );
+    } else {
+        info!("startup integrity check passed, total {} replicas", report.num_checked);
+    }
+    Ok(report)
+}
+
+async fn check_replica(
+    engine_cfg: &EngineConfig,
+    raft_cfg: &RaftConfig,
+    engines: &Engines,
+    key_manager: Arc<KeyManager>,
+    group_id: u64,
+    replica_id: u64,
+    state: ReplicaLocalState,
+) -> std::result::Result<(), String> {
+    let group_engine =
+        GroupEngine::open(engine_cfg, engines.db(), key_manager, group_id, replica_id)
+            .await
+            .map_err(|e| format!("open group engine: {e}"))?;
+    let applied_index = match group_engine {
+        Some(group_engine) => {
+            let desc = group_engine.descriptor();
+            if desc.id != group_id {
+                return Err(format!("the group engine belongs to group {}", desc.id));
+            }
+            group_engine.flushed_apply_state().map_err(|e| format!("read apply state: {e}"))?.index
+        }
+        // The group engine is created lazily for initial replicas.
+        None if state == ReplicaLocalState::Initial => 0,
+        None => return Err("group engine not exists".to_owned()),
+    };
+    check_storage_integrity(raft_cfg, &engines.log(), replica_id, applied_index)
+}
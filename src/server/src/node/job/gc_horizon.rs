@@ -0,0 +1,68 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use log::warn;
+use sekas_client::RootClient;
+use sekas_runtime::JoinHandle;
+use sekas_schema::FIRST_USER_COLLECTION_ID;
+
+use crate::engine::GcHorizons;
+use crate::node::route_table::ReplicaRouteTable;
+use crate::transport::TransportManager;
+
+const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sync the GC horizons of the collections served by this node from the root
+/// periodically, the versions below the horizons are collected by compaction.
+pub(crate) fn setup(
+    transport_manager: &TransportManager,
+    replica_route_table: ReplicaRouteTable,
+    gc_horizons: GcHorizons,
+) -> JoinHandle<()> {
+    let client = transport_manager.root_client().clone();
+    sekas_runtime::spawn(async move {
+        loop {
+            sekas_runtime::time::sleep(SYNC_INTERVAL).await;
+            sync_gc_horizons(&client, &replica_route_table, &gc_horizons).await;
+        }
+    })
+}
+
+async fn sync_gc_horizons(
+    root_client: &RootClient,
+    replica_route_table: &ReplicaRouteTable,
+    gc_horizons: &GcHorizons,
+) {
+    let mut collection_ids = replica_route_table
+        .replicas()
+        .iter()
+        .flat_map(|replica| replica.descriptor().shards)
+        .map(|shard| shard.collection_id)
+        .filter(|id| *id >= FIRST_USER_COLLECTION_ID)
+        .collect::<Vec<_>>();
+    collection_ids.sort_unstable();
+    collection_ids.dedup();
+
+    gc_horizons.retain(&collection_ids);
+    for collection_id in collection_ids {
+        match root_client.min_gc_timestamp(collection_id).await {
+            Ok(horizon) => gc_horizons.advance(collection_id, horizon),
+            Err(err) => {
+                warn!("sync GC horizon of collection {collection_id}: {err:?}");
+            }
+        }
+    }
+}
@@ -13,7 +13,9 @@
 // limitations under the License.
 
 mod destory_replica;
+mod gc_horizon;
 mod report_state;
 
 pub(crate) use destory_replica::setup as setup_destory_replica;
+pub(crate) use gc_horizon::setup as setup_gc_horizon;
 pub(crate) use report_state::{setup as setup_report_state, StateChannel};
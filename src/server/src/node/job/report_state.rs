@@ -16,14 +16,15 @@ use std::time::Duration;
 
 use futures::channel::mpsc;
 use futures::StreamExt;
-use log::warn;
+use log::{error, warn};
 use sekas_api::server::v1::report_request::GroupUpdates;
 use sekas_api::server::v1::{GroupDesc, ReplicaState, ReportRequest, ScheduleState};
-use sekas_client::RootClient;
+use sekas_client::{Error, RootClient};
 use sekas_runtime::JoinHandle;
 
 use crate::node::metrics::take_report_metrics;
 use crate::record_latency;
+use crate::serverpb::v1::NodeIdent;
 use crate::transport::TransportManager;
 
 pub struct StateChannel {
@@ -31,12 +32,13 @@ pub struct StateChannel {
     _worker_handle: Option<JoinHandle<()>>,
 }
 
-pub(crate) fn setup(transport_manager: &TransportManager) -> StateChannel {
+pub(crate) fn setup(transport_manager: &TransportManager, ident: &NodeIdent) -> StateChannel {
     let (sender, receiver) = mpsc::unbounded();
 
     let client = transport_manager.root_client().clone();
+    let ident = ident.clone();
     let task_handle = sekas_runtime::spawn(async move {
-        report_state_worker(receiver, client).await;
+        report_state_worker(receiver, client, ident).await;
     });
 
     StateChannel::new(sender, task_handle)
@@ -45,9 +47,15 @@ pub(crate) fn setup(transport_manager: &TransportManager) -> StateChannel {
 async fn report_state_worker(
     mut receiver: mpsc::UnboundedReceiver<GroupUpdates>,
     root_client: RootClient,
+    ident: NodeIdent,
 ) {
     while let Some(updates) = wait_state_updates(&mut receiver).await {
-        let req = ReportRequest { updates };
+        let req = ReportRequest {
+            updates,
+            shutdown_node_id: None,
+            node_id: ident.node_id,
+            incarnation: ident.incarnation,
+        };
         record_latency!(take_report_metrics());
         report_state_updates(&root_client, req).await;
     }
@@ -90,6 +98,11 @@ async fn wait_state_updates(
 async fn report_state_updates(root_client: &RootClient, request: ReportRequest) {
     let mut interval = 1;
     while let Err(e) = root_client.report(&request).await {
+        if let Error::NodeIncarnationMismatch(..) = e {
+            // Retrying never succeeds, the node is fenced by root.
+            error!("report state updates: {e}, the updates are dropped");
+            return;
+        }
         warn!("report state updates: {e}");
         sekas_runtime::time::sleep(Duration::from_millis(interval)).await;
         interval = std::cmp::min(interval * 2, 120);
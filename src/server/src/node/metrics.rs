@@ -46,6 +46,36 @@ lazy_static! {
     pub static ref NODE_INGEST_CHUNK_TOTAL: IntCounter =
         register_int_counter!("node_ingest_chunk_total", "The total of ingest chunks of node")
             .unwrap();
+    pub static ref NODE_MOVE_SHARD_THROTTLE_DURATION_SECONDS: Histogram = register_histogram!(
+        "node_move_shard_throttle_duration_seconds",
+        "The throttled intervals of pulling moving shards of node",
+        exponential_buckets(0.0001, 1.8, 22).unwrap(),
+    )
+    .unwrap();
+    pub static ref NODE_MOVE_SHARD_BACKOFF_TOTAL: IntCounter = register_int_counter!(
+        "node_move_shard_backoff_total",
+        "The total of backoff of pulling moving shards since the source group is slow"
+    )
+    .unwrap();
+    pub static ref NODE_SLOW_OP_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_slow_op_total",
+        "The total of shard requests of node which exceed the slow op threshold",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref NODE_RESOURCE_BYTES_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_resource_bytes_total",
+        "The total bytes of node by resource groups",
+        &["group"]
+    )
+    .unwrap();
+    pub static ref NODE_RESOURCE_THROTTLE_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
+        "node_resource_throttle_duration_seconds",
+        "The throttled intervals of node by resource groups",
+        &["group"],
+        exponential_buckets(0.0001, 1.8, 22).unwrap(),
+    )
+    .unwrap();
 }
 
 pub fn take_destory_replica_metrics() -> &'static Histogram {
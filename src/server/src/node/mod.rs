@@ -15,11 +15,16 @@
 
 pub mod metrics;
 
+pub mod dump;
+pub mod integrity;
 pub mod job;
 pub mod move_shard;
+pub mod resource;
 pub mod route_table;
 
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use futures::channel::mpsc;
@@ -31,20 +36,28 @@ use sekas_api::server::v1::*;
 use sekas_client::ClientOptions;
 use sekas_runtime::TaskGroup;
 
+use self::dump::DumpSummary;
+use self::integrity::StartupReport;
 use self::job::StateChannel;
 use self::move_shard::{ForwardCtx, MoveShardController};
+use self::resource::ResourceController;
 pub use self::route_table::{RaftRouteTable, ReplicaRouteTable};
-use crate::constants::ROOT_GROUP_ID;
-use crate::engine::{Engines, GroupEngine, RawDb, StateEngine};
+use crate::constants::{BINARY_VERSION, CLUSTER_VERSION_INGEST, ROOT_GROUP_ID};
+use crate::engine::{
+    verify_ingest_file, Engines, GroupEngine, IngestStore, KeyManager, RawDb, StateEngine,
+    WriteBatch, WriteStates,
+};
 use crate::raftgroup::snap::RecycleSnapMode;
-use crate::raftgroup::{ChannelManager, RaftGroup, RaftManager, SnapManager};
+use crate::raftgroup::{
+    reset_for_unsafe_recovery, ChannelManager, RaftGroup, RaftManager, SnapManager,
+};
 use crate::replica::fsm::GroupStateMachine;
 pub use crate::replica::Replica;
-use crate::replica::{ExecCtx, LeaseState, LeaseStateObserver, ReplicaInfo};
+use crate::replica::{ExecCtx, LeaseState, LeaseStateObserver, ReplicaInfo, ReplicaParts};
 use crate::schedule::MoveReplicasProvider;
 use crate::serverpb::v1::*;
 use crate::transport::TransportManager;
-use crate::{Config, EngineConfig, Error, NodeConfig, Result};
+use crate::{Config, DynamicConfig, EngineConfig, Error, NodeConfig, Result};
 
 struct ReplicaContext {
     #[allow(dead_code)]
@@ -69,6 +82,10 @@ where
 
     root: RootDesc,
     channel: Option<Arc<StateChannel>>,
+
+    /// The result of the integrity check when the node starts, the broken
+    /// replicas are refused to serve.
+    startup_report: StartupReport,
 }
 
 /// Node is used to manage replicas lifecycle, and provides replica query.
@@ -82,9 +99,12 @@ where
 
     raft_mgr: Arc<RaftManager>,
     move_shard_ctrl: MoveShardController,
+    resource_ctrl: Arc<ResourceController>,
     transport_manager: TransportManager,
     engines: Engines,
     state_engine: StateEngine,
+    key_manager: Arc<KeyManager>,
+    dynamic_config: Arc<DynamicConfig>,
     task_group: TaskGroup,
 
     /// Node related metadata, including serving replicas, root desc.
@@ -93,6 +113,13 @@ where
     /// A lock is used to ensure serialization of create/terminate replica
     /// operations.
     replica_mutation: Arc<Mutex<()>>,
+
+    /// The node is shutting down gracefully, the new requests are rejected.
+    shutting_down: AtomicBool,
+
+    /// All replicas recovered after restart have caught up with the leaders,
+    /// there is no need to collect the catch up states anymore.
+    caught_up: AtomicBool,
 }
 
 impl Node {
@@ -105,14 +132,32 @@ impl Node {
         let trans_mgr = Arc::new(ChannelManager::new(
             transport_manager.address_resolver(),
             raft_route_table.clone(),
+            transport_manager.conn_manager().clone(),
         ));
         let snap_dir = engines.snap_dir();
-        let snap_mgr = SnapManager::recovery(snap_dir).await?;
+        let snap_mgr = SnapManager::recovery(snap_dir, &cfg.raft).await?;
         let raft_mgr = Arc::new(
-            RaftManager::open(cfg.raft.clone(), engines.log(), snap_mgr, trans_mgr).await?,
+            RaftManager::open(
+                cfg.raft.clone(),
+                engines.log(),
+                engines.log_purge_fence(),
+                snap_mgr,
+                trans_mgr,
+            )
+            .await?,
+        );
+        let resource_ctrl = Arc::new(ResourceController::new(&cfg.node.resource));
+        let migrate_ctrl = MoveShardController::new(
+            cfg.node.clone(),
+            transport_manager.clone(),
+            resource_ctrl.clone(),
         );
-        let migrate_ctrl = MoveShardController::new(cfg.node.clone(), transport_manager.clone());
         let state_engine = engines.state();
+        let key_manager = Arc::new(KeyManager::open(&cfg.node.engine)?);
+        if key_manager.is_enabled() {
+            key_manager.update_data_keys(&state_engine.load_data_keys().await?)?;
+        }
+        let dynamic_config = Arc::new(DynamicConfig::new(&cfg));
         Ok(Node {
             cfg: cfg.node,
             transport_manager,
@@ -120,11 +165,16 @@ impl Node {
             replica_route_table: ReplicaRouteTable::new(),
             raft_mgr,
             move_shard_ctrl: migrate_ctrl,
+            resource_ctrl,
             engines,
             state_engine,
+            key_manager,
+            dynamic_config,
             task_group: TaskGroup::default(),
             node_state: Arc::new(Mutex::new(NodeState::default())),
             replica_mutation: Arc::default(),
+            shutting_down: AtomicBool::new(false),
+            caught_up: AtomicBool::new(false),
         })
     }
 
@@ -139,10 +189,25 @@ impl Node {
         );
 
         node_state.ident = Some(node_ident.to_owned());
-        let state_channel = Arc::new(setup_report_state(&self.transport_manager));
+        let state_channel = Arc::new(setup_report_state(&self.transport_manager, node_ident));
+        self.task_group.add_task(setup_gc_horizon(
+            &self.transport_manager,
+            self.replica_route_table.clone(),
+            self.engines.gc_horizons(),
+        ));
+
+        let replica_states = self.state_engine.replica_states().await?;
+        let report = self::integrity::check_integrity(
+            &self.cfg.engine,
+            &self.raft_mgr.cfg,
+            &self.engines,
+            self.key_manager.clone(),
+            &replica_states,
+        )
+        .await?;
 
         let node_id = node_ident.node_id;
-        for (group_id, replica_id, state) in self.state_engine.replica_states().await? {
+        for (group_id, replica_id, state) in replica_states {
             if state == ReplicaLocalState::Terminated {
                 let destory_replica_handle =
                     setup_destory_replica(group_id, replica_id, self.engines.clone());
@@ -154,13 +219,19 @@ impl Node {
                     .recycle_snapshots(replica_id, RecycleSnapMode::All);
                 continue;
             }
+            if report.is_group_broken(group_id) {
+                warn!("group {group_id} replica {replica_id} is broken, skip serving it");
+                continue;
+            }
 
             let desc = ReplicaDesc { id: replica_id, node_id, ..Default::default() };
-            let context = self.serve_replica(group_id, desc, state, state_channel.clone()).await?;
+            let context =
+                self.serve_replica(group_id, desc, state, state_channel.clone(), true).await?;
             node_state.serving_replicas.insert(replica_id, context);
             node_state.serving_groups.insert(group_id);
         }
         node_state.channel = Some(state_channel);
+        node_state.startup_report = report;
 
         Ok(())
     }
@@ -210,6 +281,7 @@ impl Node {
                     desc,
                     ReplicaLocalState::Initial,
                     node_state.channel.as_ref().unwrap().clone(),
+                    false,
                 )
                 .await?;
             node_state.serving_replicas.insert(replica_id, context);
@@ -231,6 +303,11 @@ impl Node {
             return Err(Error::AlreadyExists(format!("group {group_id}")));
         }
 
+        if node_state.startup_report.is_group_broken(group_id) {
+            warn!("group {group_id} create replica {replica_id}: the local replica is broken");
+            return Err(Error::InvalidData(format!("group {group_id} local replica is broken")));
+        }
+
         Ok(false)
     }
 
@@ -280,19 +357,96 @@ impl Node {
         Ok(())
     }
 
-    /// Open, recover replica and start serving.
+    /// Force the specified replica to be the only voter of its group, and
+    /// restart it. This is used to recover a group which has permanently
+    /// lost its quorum, the log entries not applied by this replica are
+    /// discarded. Return the descriptor of the recovered group.
+    pub async fn unsafe_recover_replica(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Result<GroupDesc> {
+        let _mut_guard = self.replica_mutation.lock().await;
+
+        let replica = match self.replica_route_table.find(group_id) {
+            Some(replica) if replica.replica_info().replica_id == replica_id => replica,
+            _ => {
+                warn!("group {group_id} unsafe recover replica {replica_id}: replica not existed");
+                return Err(Error::GroupNotFound(group_id));
+            }
+        };
+        let info = replica.replica_info();
+        let local_state = info.local_state();
+        if matches!(local_state, ReplicaLocalState::Tombstone | ReplicaLocalState::Terminated) {
+            return Err(Error::InvalidArgument(format!(
+                "replica {replica_id} is in {local_state:?} state"
+            )));
+        }
+
+        warn!("group {group_id} start unsafe recovery from replica {replica_id}");
+
+        // Stop serving, the raft states will be rewritten.
+        let mut desc = replica.descriptor();
+        let group_engine = replica.group_engine();
+        replica.shutdown(&desc).await?;
+        self.replica_route_table.remove(group_id);
+        self.raft_route_table.delete(replica_id);
+        let (node_id, channel, task_group) = {
+            let mut node_state = self.node_state.lock().await;
+            let ctx = node_state
+                .serving_replicas
+                .remove(&replica_id)
+                .expect("replica should exists before recovering");
+            let node_id = node_state.ident.as_ref().unwrap().node_id;
+            (node_id, node_state.channel.as_ref().unwrap().clone(), ctx.task_group)
+        };
+        drop(task_group);
+
+        // Only the local replica is kept, and the epoch is bumped as a config change.
+        desc.replicas.retain(|r| r.id == replica_id);
+        for r in &mut desc.replicas {
+            r.role = ReplicaRole::Voter as i32;
+        }
+        desc.epoch += 1;
+        let states = WriteStates { descriptor: Some(desc.clone()), ..Default::default() };
+        group_engine.commit(WriteBatch::default(), states, true)?;
+        group_engine.flush()?;
+        let apply_state = group_engine.flushed_apply_state()?;
+        let applied = EntryId { index: apply_state.index, term: apply_state.term };
+        reset_for_unsafe_recovery(&self.raft_mgr.engine(), replica_id, applied).await?;
+        self.raft_mgr.snapshot_manager().recycle_snapshots(replica_id, RecycleSnapMode::All);
+
+        let replica_desc = ReplicaDesc { id: replica_id, node_id, ..Default::default() };
+        let context =
+            self.serve_replica(group_id, replica_desc, local_state, channel, false).await?;
+        self.node_state.lock().await.serving_replicas.insert(replica_id, context);
+
+        warn!("group {group_id} is recovered from replica {replica_id}, epoch {}", desc.epoch);
+
+        Ok(desc)
+    }
+
+    /// Open, recover replica and start serving. The replica recovered after
+    /// restart should `catch_up` with the leader before campaigning.
     async fn serve_replica(
         &self,
         group_id: u64,
         desc: ReplicaDesc,
         local_state: ReplicaLocalState,
         channel: Arc<StateChannel>,
+        catch_up: bool,
     ) -> Result<ReplicaContext> {
         use crate::schedule::setup_scheduler;
 
-        let group_engine =
-            open_group_engine(&self.cfg.engine, self.engines.db(), group_id, desc.id, local_state)
-                .await?;
+        let group_engine = open_group_engine(
+            &self.cfg.engine,
+            self.engines.db(),
+            self.key_manager.clone(),
+            group_id,
+            desc.id,
+            local_state,
+        )
+        .await?;
         let task_group = TaskGroup::default();
         let (sender, receiver) = mpsc::unbounded();
 
@@ -309,7 +463,9 @@ impl Node {
             lease_state.clone(),
             channel.clone(),
             group_engine.clone(),
+            self.engines.ingest_store(),
             &task_group,
+            catch_up,
         )
         .await?;
 
@@ -320,14 +476,17 @@ impl Node {
 
         // TODO: config client options.
         let client = self.transport_manager.build_client(ClientOptions::default());
-        let replica = Replica::new(
-            info.clone(),
+        let replica = Replica::new(ReplicaParts {
+            info: info.clone(),
             lease_state,
-            raft_node.clone(),
+            raft_group: raft_node.clone(),
             group_engine,
-            client,
-            move_replicas_provider.clone(),
-        );
+            ingest_store: self.engines.ingest_store(),
+            sekas_client: client,
+            transport_manager: self.transport_manager.clone(),
+            move_replicas_provider: move_replicas_provider.clone(),
+            dynamic_config: self.dynamic_config.clone(),
+        });
         let replica = Arc::new(replica);
         self.replica_route_table.update(replica.clone());
         self.raft_route_table.update(replica_id, raft_node);
@@ -377,6 +536,221 @@ impl Node {
         }
     }
 
+    /// Install the data keys synced from root. The wrapped data keys are
+    /// persisted, so that the encrypted values are readable after restarting.
+    pub async fn update_data_keys(&self, keys: &[DataKey]) {
+        if !self.key_manager.is_enabled() {
+            warn!("receive data keys from root, but the encryption key file is not configured");
+            return;
+        }
+        match self.key_manager.update_data_keys(keys) {
+            Ok(false) => {}
+            Ok(true) => {
+                if let Err(err) = self.state_engine.save_data_keys(keys).await {
+                    warn!("save data keys: {err:?}");
+                }
+            }
+            Err(err) => warn!("update data keys: {err:?}"),
+        }
+    }
+
+    #[inline]
+    pub fn key_manager(&self) -> Arc<KeyManager> {
+        self.key_manager.clone()
+    }
+
+    #[inline]
+    pub fn dynamic_config(&self) -> Arc<DynamicConfig> {
+        self.dynamic_config.clone()
+    }
+
+    /// Mark the node as shutting down, the new batch requests and heartbeats
+    /// are rejected since then.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Acquire)
+    }
+
+    /// Transfer the leaderships of the local replicas to the other voters,
+    /// return the number of leaders which are still serving on this node. The
+    /// groups without other voters are skipped.
+    pub async fn transfer_leaders_off(&self) -> usize {
+        let mut num_leaders = 0;
+        for group_id in self.serving_group_id_list().await {
+            let Some(replica) = self.replica_route_table.find(group_id) else {
+                continue;
+            };
+            let info = replica.replica_info();
+            if info.is_terminated() || replica.replica_state().role != RaftRole::Leader as i32 {
+                continue;
+            }
+            let Some(transferee) = replica
+                .descriptor()
+                .replicas
+                .iter()
+                .find(|r| r.id != info.replica_id && r.role == ReplicaRole::Voter as i32)
+                .map(|r| r.id)
+            else {
+                continue;
+            };
+            num_leaders += 1;
+            info!("group {group_id} transfer leadership from {} to {transferee}", info.replica_id);
+            if let Err(err) = replica.raft_node().transfer_leader(transferee) {
+                warn!("group {group_id} transfer leadership to {transferee}: {err:?}");
+            }
+        }
+        num_leaders
+    }
+
+    /// Flush the memtables of the local replicas and sync the raft logs.
+    pub async fn flush_engines(&self) -> Result<()> {
+        for group_id in self.serving_group_id_list().await {
+            if let Some(replica) = self.replica_route_table.find(group_id) {
+                replica.group_engine().flush()?;
+            }
+        }
+        self.engines.log().sync()?;
+        Ok(())
+    }
+
+    /// Notify root that this node is shutting down, so that it is regarded as
+    /// dead at once.
+    pub async fn notify_shutdown(&self) -> Result<()> {
+        let ident = self.node_state.lock().await.ident.clone();
+        let Some(ident) = ident else {
+            return Ok(());
+        };
+        let request = ReportRequest {
+            updates: vec![],
+            shutdown_node_id: Some(ident.node_id),
+            node_id: ident.node_id,
+            incarnation: ident.incarnation,
+        };
+        self.transport_manager.root_client().report(&request).await?;
+        Ok(())
+    }
+
+    /// Reject the heartbeat sent to another node or a stale incarnation of this
+    /// node, eg. the root still records the node before it is wiped and
+    /// re-joined with the same address.
+    pub async fn check_incarnation(&self, node_id: u64, incarnation: u64) -> Result<()> {
+        if node_id == 0 {
+            return Ok(());
+        }
+        let node_state = self.node_state.lock().await;
+        let Some(ident) = node_state.ident.as_ref() else {
+            return Ok(());
+        };
+        if ident.node_id != node_id || ident.incarnation != incarnation {
+            return Err(Error::NodeIncarnationMismatch(node_id, ident.incarnation, incarnation));
+        }
+        Ok(())
+    }
+
+    /// Apply the options set by admin, received via heartbeat.
+    #[inline]
+    pub fn update_dynamic_config(&self, entries: &[ConfigEntry]) {
+        self.dynamic_config.apply(entries);
+    }
+
+    /// Ensure the replicated feature is enabled by the cluster version.
+    async fn ensure_cluster_version(&self, required_version: u64, feature: &str) -> Result<()> {
+        let cluster_version = self.get_root().await.cluster_version;
+        if cluster_version < required_version {
+            return Err(Error::InvalidArgument(format!(
+                "{feature} requires cluster version {required_version}, but the cluster version is {cluster_version}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Append a chunk of data to the staged ingest file of the shard, return
+    /// the size of the file.
+    pub async fn upload_ingest_file(
+        &self,
+        shard_id: u64,
+        name: String,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> Result<u64> {
+        let ingest_store = self.engines.ingest_store();
+        sekas_runtime::spawn_blocking(move || ingest_store.append(shard_id, &name, offset, &data))
+            .await?
+    }
+
+    /// Seal the staged ingest files of the shard under the token, and verify
+    /// their fingerprints.
+    pub async fn verify_ingest_files(
+        &self,
+        shard_id: u64,
+        token: String,
+        files: Vec<IngestFile>,
+    ) -> Result<()> {
+        let ingest_store = self.engines.ingest_store();
+        sekas_runtime::spawn_blocking(move || {
+            let paths = ingest_store.seal(shard_id, &token, &files)?;
+            for (path, file) in paths.iter().zip(&files) {
+                verify_ingest_file(path, file, |_| Ok(()))?;
+            }
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Compact the group engine of the replica manually, see
+    /// [`GroupEngine::compact`] for the details of `range`.
+    pub async fn compact_group(
+        &self,
+        group_id: u64,
+        range: Option<(u64, RangePartition)>,
+    ) -> Result<()> {
+        let Some(replica) = self.replica_route_table.find(group_id) else {
+            return Err(Error::GroupNotFound(group_id));
+        };
+        let group_engine = replica.group_engine();
+        sekas_runtime::spawn_blocking(move || {
+            group_engine.compact(range.as_ref().map(|(shard_id, range)| (*shard_id, range)))
+        })
+        .await?
+    }
+
+    /// Create a consistent checkpoint of the state engine and all group engines
+    /// of this node in `dir`, see [`Engines::checkpoint`] for details.
+    pub async fn checkpoint(&self, dir: PathBuf) -> Result<()> {
+        let engines = self.engines.clone();
+        sekas_runtime::spawn_blocking(move || engines.checkpoint(&dir)).await?
+    }
+
+    /// Dump the data and raft state of the local replica into `dir`, see
+    /// [`dump`] for the layout.
+    pub async fn dump_replica(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        dir: PathBuf,
+    ) -> Result<DumpSummary> {
+        let Some(replica) = self.replica_route_table.find(group_id) else {
+            return Err(Error::GroupNotFound(group_id));
+        };
+        if replica.replica_info().replica_id != replica_id {
+            return Err(Error::InvalidArgument(format!(
+                "replica {replica_id} of group {group_id} is not served by this node"
+            )));
+        }
+        let Some(raft_state) = replica.raft_node().raft_group_state().await else {
+            return Err(Error::GroupNotFound(group_id));
+        };
+        let group_engine = replica.group_engine();
+        sekas_runtime::spawn_blocking(move || {
+            dump::dump_replica(replica_id, &group_engine, &raft_state, &dir)
+        })
+        .await?
+    }
+
     pub async fn reload_root_from_engine(&self) -> Result<()> {
         let root_desc = self
             .state_engine()
@@ -394,6 +768,11 @@ impl Node {
             return Err(Error::GroupNotFound(request.group_id));
         };
 
+        if let Some(Request::Ingest(_)) = request.request.as_ref().and_then(|r| r.request.as_ref())
+        {
+            self.ensure_cluster_version(CLUSTER_VERSION_INGEST, "ingest").await?;
+        }
+
         match execute(&replica, &ExecCtx::default(), request).await {
             Err(Error::Forward(forward_ctx)) => {
                 let request = request
@@ -417,6 +796,34 @@ impl Node {
         }
     }
 
+    /// Read the next chunk of a moving shard after `last_key`, the chunk is
+    /// empty once all data of the shard is read.
+    pub async fn pull_shard_chunk(&self, req: &PullShardRequest) -> Result<Vec<ValueSet>> {
+        let Some(replica) = self.replica_route_table.find(req.group_id) else {
+            return Err(Error::GroupNotFound(req.group_id));
+        };
+        let scan = ShardScanRequest {
+            shard_id: req.shard_id,
+            start_version: sekas_schema::system::txn::TXN_INTENT_VERSION,
+            limit_bytes: req.chunk_bytes,
+            exclude_start_key: true,
+            start_key: if req.last_key.is_empty() { None } else { Some(req.last_key.clone()) },
+            include_raw_data: true,
+            ignore_txn_intent: true,
+            allow_scan_moving_shard: true,
+            ..Default::default()
+        };
+        let request = GroupRequest {
+            group_id: req.group_id,
+            epoch: replica.epoch(),
+            request: Some(GroupRequestUnion { request: Some(Request::Scan(scan)) }),
+        };
+        match self.execute_request(&request).await?.response.and_then(|r| r.response) {
+            Some(Response::Scan(resp)) => Ok(resp.data),
+            _ => Err(Error::InvalidData("invalid response type, Scan is required".into())),
+        }
+    }
+
     pub async fn forward(&self, request: ForwardRequest) -> Result<ForwardResponse> {
         use crate::replica::retry::execute;
 
@@ -483,6 +890,40 @@ impl Node {
         Ok(())
     }
 
+    /// Setup the shard moving in the source group, and return the number of
+    /// keys of the shard, which is used by the dest group to report progress.
+    pub async fn acquire_shard(&self, desc: MoveShardDesc) -> Result<AcquireShardResponse> {
+        let group_id = desc.src_group_id;
+        let shard_id = desc.shard_desc.as_ref().map(|s| s.id).unwrap_or_default();
+        self.move_shard(MoveShardEvent::Setup, desc).await?;
+
+        let replica =
+            self.replica_route_table.find(group_id).ok_or(Error::GroupNotFound(group_id))?;
+        let num_keys = match replica.num_live_keys(shard_id) {
+            Ok(num_keys) => num_keys,
+            Err(err) => {
+                // The number of keys is only used to report progress, so the moving should
+                // not be blocked by it.
+                warn!("group {group_id} shard {shard_id} count live keys: {err:?}");
+                0
+            }
+        };
+        Ok(AcquireShardResponse { num_keys })
+    }
+
+    /// Update the limits of copying data of the shards moving into this node.
+    #[inline]
+    pub fn update_move_shard_limit(&self, limit: MoveShardLimit) {
+        self.move_shard_ctrl.update_limit(limit);
+    }
+
+    /// Return the database of the collection, it is resolved by the router
+    /// which follows the changes of root.
+    #[inline]
+    pub fn collection_database(&self, collection_id: u64) -> Option<u64> {
+        self.transport_manager.router().find_collection_by_id(collection_id).map(|desc| desc.db)
+    }
+
     #[inline]
     pub fn replica_table(&self) -> &ReplicaRouteTable {
         &self.replica_route_table
@@ -503,11 +944,30 @@ impl Node {
         &self.raft_mgr
     }
 
+    #[inline]
+    pub fn resource_controller(&self) -> &ResourceController {
+        &self.resource_ctrl
+    }
+
     pub async fn collect_stats(&self, _req: &CollectStatsRequest) -> CollectStatsResponse {
         // TODO(walter) add read/write qps.
-        let mut ns = NodeStats::default();
+        let snap_throttle = self.raft_mgr.snapshot_manager().throttle();
+        let (total_space, available_space, load_average) = system_usage(self.engines.db_path());
+        let mut ns = NodeStats {
+            binary_version: BINARY_VERSION,
+            block_cache_usage: self.engines.db().block_cache_usage() as u64,
+            sending_snapshots: snap_throttle.num_sending() as u64,
+            receiving_snapshots: snap_throttle.num_receiving() as u64,
+            total_space,
+            available_space,
+            load_average,
+            ..Default::default()
+        };
         let mut group_stats = vec![];
         let mut replica_stats = vec![];
+        let mut moving_shards = vec![];
+        let collect_catch_up = !self.caught_up.load(Ordering::Acquire);
+        let mut all_caught_up = true;
         let group_id_list = self.serving_group_id_list().await;
         for group_id in group_id_list {
             if let Some(replica) = self.replica_route_table.find(group_id) {
@@ -515,6 +975,23 @@ impl Node {
                 if info.is_terminated() {
                     continue;
                 }
+                let raft_state = replica.raft_node().raft_group_state().await;
+                if collect_catch_up {
+                    match &raft_state {
+                        Some(state) => {
+                            if let Some(committed_index) = state.catch_up_target {
+                                all_caught_up = false;
+                                ns.catching_up_replicas.push(ReplicaCatchUp {
+                                    group_id: info.group_id,
+                                    replica_id: info.replica_id,
+                                    applied_index: state.applied,
+                                    committed_index,
+                                });
+                            }
+                        }
+                        None => all_caught_up = false,
+                    }
+                }
                 if info.group_id == ROOT_GROUP_ID {
                     continue;
                 }
@@ -529,25 +1006,54 @@ impl Node {
                 let replica_state = replica.replica_state();
                 if replica_state.role == RaftRole::Leader as i32 {
                     ns.leader_count += 1;
+                    let group_engine = replica.group_engine();
+                    let cache_stats = group_engine.block_cache_stats();
+                    let shard_stats = replica.all_shard_usage().unwrap_or_else(|err| {
+                        warn!("group {} collect shard usage: {err:?}", info.group_id);
+                        vec![]
+                    });
                     let gs = GroupStats {
                         group_id: info.group_id,
                         shard_count: descriptor.shards.len() as u64,
                         read_qps: 0.,
                         write_qps: 0.,
+                        block_cache_hits: cache_stats.hits(),
+                        block_cache_misses: cache_stats.misses(),
+                        shard_stats,
+                        committed_index: raft_state
+                            .as_ref()
+                            .map(|s| s.committed)
+                            .unwrap_or_default(),
                     };
                     group_stats.push(gs);
+                    if let Some(ms) = replica.move_shard_state() {
+                        // The progress is only tracked by the dest group.
+                        let desc = ms.move_shard.as_ref().filter(|d| d.dest_group_id == group_id);
+                        if let Some(desc) = desc {
+                            moving_shards.push(MovingShardStats {
+                                desc: Some(desc.clone()),
+                                progress: Some(ms.progress()),
+                            });
+                        }
+                    }
                 }
                 let rs = ReplicaStats {
                     replica_id: info.replica_id,
                     group_id: info.group_id,
                     read_qps: 0.,
                     write_qps: 0.,
+                    applied_index: raft_state.as_ref().map(|s| s.applied).unwrap_or_default(),
                 };
                 replica_stats.push(rs);
             }
         }
 
-        CollectStatsResponse { node_stats: Some(ns), group_stats, replica_stats }
+        if collect_catch_up && all_caught_up {
+            info!("all replicas recovered after restart have caught up");
+            self.caught_up.store(true, Ordering::Release);
+        }
+
+        CollectStatsResponse { node_stats: Some(ns), group_stats, replica_stats, moving_shards }
     }
 
     pub async fn collect_group_detail(
@@ -584,7 +1090,11 @@ impl Node {
     ) -> CollectMovingShardStateResponse {
         use collect_moving_shard_state_response::State;
 
-        let mut resp = CollectMovingShardStateResponse { state: State::None as i32, desc: None };
+        let mut resp = CollectMovingShardStateResponse {
+            state: State::None as i32,
+            desc: None,
+            progress: None,
+        };
 
         let group_id = req.group;
         if let Some(replica) = self.replica_route_table.find(group_id) {
@@ -600,6 +1110,7 @@ impl Node {
                         state = State::None;
                     }
                     resp.state = state as i32;
+                    resp.progress = Some(ms.progress());
                     resp.desc = ms.move_shard;
                 }
             }
@@ -657,6 +1168,11 @@ impl Node {
         Ok(merge_scan_response(target_resp, source_resp))
     }
 
+    /// Return the report of the integrity check when the node starts.
+    pub async fn startup_report(&self) -> StartupReport {
+        self.node_state.lock().await.startup_report.clone()
+    }
+
     #[inline]
     async fn serving_group_id_list(&self) -> Vec<u64> {
         let node_state = self.node_state.lock().await;
@@ -674,14 +1190,15 @@ impl NodeState {
 async fn open_group_engine(
     cfg: &EngineConfig,
     raw_db: Arc<RawDb>,
+    key_manager: Arc<KeyManager>,
     group_id: u64,
     replica_id: u64,
     replica_state: ReplicaLocalState,
 ) -> Result<GroupEngine> {
-    match GroupEngine::open(cfg, raw_db.clone(), group_id, replica_id).await? {
+    match GroupEngine::open(cfg, raw_db.clone(), key_manager.clone(), group_id, replica_id).await? {
         Some(group_engine) => Ok(group_engine),
         None if matches!(replica_state, ReplicaLocalState::Initial) => {
-            GroupEngine::create(cfg, raw_db, group_id, replica_id).await
+            GroupEngine::create(cfg, raw_db, key_manager, group_id, replica_id).await
         }
         None => {
             panic!("group {group_id} replica {replica_id} open group engine: no such group engine exists");
@@ -689,6 +1206,7 @@ async fn open_group_engine(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn start_raft_group(
     cfg: &NodeConfig,
     raft_mgr: &RaftManager,
@@ -696,7 +1214,9 @@ async fn start_raft_group(
     lease_state: Arc<std::sync::Mutex<LeaseState>>,
     channel: Arc<StateChannel>,
     group_engine: GroupEngine,
+    ingest_store: IngestStore,
     task_group: &TaskGroup,
+    catch_up: bool,
 ) -> Result<RaftGroup> {
     let group_id = info.group_id;
     let state_observer =
@@ -705,13 +1225,39 @@ async fn start_raft_group(
         cfg.replica.clone(),
         info.clone(),
         group_engine.clone(),
+        ingest_store,
         state_observer.clone(),
     );
     raft_mgr
-        .start_raft_group(group_id, info.replica_id, info.node_id, fsm, state_observer, task_group)
+        .start_raft_group(
+            group_id,
+            info.replica_id,
+            info.node_id,
+            fsm,
+            state_observer,
+            task_group,
+            catch_up,
+        )
         .await
 }
 
+/// Return the total and available space of the disk mounted the path, and the
+/// load average of the last minute.
+fn system_usage(path: &std::path::Path) -> (u64, u64, f64) {
+    use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
+
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let info = System::new_with_specifics(RefreshKind::new().with_disks_list());
+    let (total_space, available_space) = info
+        .disks()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.total_space(), disk.available_space()))
+        .unwrap_or_default();
+    (total_space, available_space, info.load_average().one)
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -738,14 +1284,20 @@ mod tests {
         let root_dir = root_dir.as_ref().to_owned();
         let config = Config { root_dir, ..Default::default() };
 
-        let engines = Engines::open(&config.root_dir, &config.db).unwrap();
-        let transport_manager = TransportManager::new(vec![], engines.state()).await;
+        let engines = Engines::open(&config.root_dir, &config.db, &config.raft).unwrap();
+        let transport_manager = TransportManager::new(
+            vec![],
+            engines.state(),
+            None,
+            sekas_client::TokenInterceptor::default(),
+        )
+        .await;
         Node::new(config, engines, transport_manager).await.unwrap()
     }
 
     async fn bootstrap_node<P: AsRef<Path>>(root_dir: P) -> Node {
         let node = create_node(root_dir).await;
-        let node_ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID };
+        let node_ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID, incarnation: 0 };
         node.bootstrap(&node_ident).await.unwrap();
         node
     }
@@ -808,7 +1360,7 @@ mod tests {
         {
             // Bootstrap replica after restart node.
             let node = create_node(dir.path()).await;
-            let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID };
+            let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID, incarnation: 0 };
             node.bootstrap(&ident).await.unwrap();
         }
     }
@@ -820,7 +1372,7 @@ mod tests {
 
         let group = group_descriptor();
         node.create_replica(REPLICA_ID, group.clone()).await.unwrap();
-        let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID };
+        let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID, incarnation: 0 };
         node.bootstrap(&ident).await.unwrap();
 
         sekas_runtime::time::sleep(Duration::from_millis(10)).await;
@@ -836,7 +1388,7 @@ mod tests {
 
         let group = GroupDesc { id: GROUP_ID, epoch: INITIAL_EPOCH, ..Default::default() };
         node.create_replica(REPLICA_ID, group.clone()).await.unwrap();
-        let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID };
+        let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID, incarnation: 0 };
         node.bootstrap(&ident).await.unwrap();
 
         sekas_runtime::time::sleep(Duration::from_millis(10)).await;
@@ -856,7 +1408,7 @@ mod tests {
 
         let group = GroupDesc { id: GROUP_ID, epoch: INITIAL_EPOCH, ..Default::default() };
         node.create_replica(REPLICA_ID, group.clone()).await.unwrap();
-        let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID };
+        let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID, incarnation: 0 };
         node.bootstrap(&ident).await.unwrap();
 
         let new_replica_id = REPLICA_ID + 1;
@@ -875,9 +1427,15 @@ mod tests {
         let (sender, mut receiver) = mpsc::unbounded();
         let replica_desc = ReplicaDesc { id: REPLICA_ID, ..Default::default() };
         let state_channel = Arc::new(StateChannel::without_handle(sender));
-        node.serve_replica(GROUP_ID, replica_desc, ReplicaLocalState::Initial, state_channel)
-            .await
-            .unwrap();
+        node.serve_replica(
+            GROUP_ID,
+            replica_desc,
+            ReplicaLocalState::Initial,
+            state_channel,
+            false,
+        )
+        .await
+        .unwrap();
 
         use futures::stream::StreamExt;
 
@@ -918,6 +1476,7 @@ mod tests {
             shard_id: SHARD_ID,
             start_version: version,
             user_key: key.to_vec(),
+            ..Default::default()
         })
     }
 
@@ -1046,6 +1605,31 @@ mod tests {
         }
     }
 
+    #[sekas_macro::test]
+    async fn bootstrap_skips_broken_replicas() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        {
+            let node = bootstrap_node(dir.path()).await;
+            // The replica is normal, but there is no such group engine.
+            node.state_engine
+                .save_replica_state(GROUP_ID, REPLICA_ID, ReplicaLocalState::Normal)
+                .await
+                .unwrap();
+        }
+
+        {
+            // Mock reboot.
+            let node = bootstrap_node(dir.path()).await;
+            let report = node.startup_report().await;
+            assert_eq!(report.num_checked, 1);
+            assert!(report.is_group_broken(GROUP_ID));
+            assert!(node.replica_route_table.find(GROUP_ID).is_none());
+
+            let group_desc = GroupDesc { id: GROUP_ID, epoch: INITIAL_EPOCH, ..Default::default() };
+            assert!(node.create_replica(REPLICA_ID, group_desc).await.is_err());
+        }
+    }
+
     fn build_prepare_request(start_version: u64, key: &[u8], value: &[u8]) -> Request {
         Request::WriteIntent(WriteIntentRequest {
             start_version,
@@ -1057,6 +1641,7 @@ mod tests {
                 take_prev_value: true,
                 ..Default::default()
             })),
+            ..Default::default()
         })
     }
 
@@ -1184,4 +1769,20 @@ mod tests {
             matches!(value, Some(v) if v.content.as_ref().unwrap() == b"456" && v.version == second_commit_version)
         );
     }
+
+    #[sekas_macro::test]
+    async fn reject_heartbeat_of_stale_incarnation() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let node = create_node(dir.path()).await;
+        let ident = NodeIdent { cluster_id: vec![], node_id: NODE_ID, incarnation: 10 };
+        node.bootstrap(&ident).await.unwrap();
+
+        assert!(node.check_incarnation(NODE_ID, 10).await.is_ok());
+        // The zero node id is sent by the root of older binaries.
+        assert!(node.check_incarnation(0, 0).await.is_ok());
+        let r = node.check_incarnation(NODE_ID, 9).await;
+        assert!(matches!(r, Err(Error::NodeIncarnationMismatch(NODE_ID, 10, 9))), "{r:?}");
+        let r = node.check_incarnation(NODE_ID + 1, 10).await;
+        assert!(matches!(r, Err(Error::NodeIncarnationMismatch(..))), "{r:?}");
+    }
 }
@@ -14,21 +14,26 @@
 // limitations under the License.
 
 use std::sync::Arc;
+use std::time::Instant;
 
 use futures::channel::mpsc;
 use futures::StreamExt;
 use log::{debug, error, info, warn};
+use prost::Message;
 use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
 use sekas_client::MoveShardClient;
 use sekas_runtime::JoinHandle;
 
+use super::throttle::{MoveShardThrottle, PullBackoff};
+use crate::failpoint::fail_point;
 use crate::node::metrics::*;
+use crate::node::resource::{ResourceController, ResourceGroup};
 use crate::node::Replica;
 use crate::serverpb::v1::*;
 use crate::transport::TransportManager;
-use crate::{record_latency, NodeConfig, Result};
+use crate::{record_latency, Error, NodeConfig, Result};
 
 #[derive(Debug)]
 pub struct ForwardCtx {
@@ -44,6 +49,8 @@ struct MoveShardCoordinator {
     group_id: u64,
 
     replica: Arc<Replica>,
+    resource_ctrl: Arc<ResourceController>,
+    throttle: Arc<MoveShardThrottle>,
 
     client: MoveShardClient,
     desc: MoveShardDesc,
@@ -57,15 +64,32 @@ pub struct MoveShardController {
 struct MoveShardControllerShared {
     cfg: NodeConfig,
     transport_manager: TransportManager,
+    resource_ctrl: Arc<ResourceController>,
+    throttle: Arc<MoveShardThrottle>,
 }
 
 impl MoveShardController {
-    pub(crate) fn new(cfg: NodeConfig, transport_manager: TransportManager) -> Self {
+    pub(crate) fn new(
+        cfg: NodeConfig,
+        transport_manager: TransportManager,
+        resource_ctrl: Arc<ResourceController>,
+    ) -> Self {
         MoveShardController {
-            shared: Arc::new(MoveShardControllerShared { cfg, transport_manager }),
+            shared: Arc::new(MoveShardControllerShared {
+                cfg,
+                transport_manager,
+                resource_ctrl,
+                throttle: Arc::default(),
+            }),
         }
     }
 
+    /// Update the limits of copying data of moving shards, specified by root.
+    #[inline]
+    pub fn update_limit(&self, limit: MoveShardLimit) {
+        self.shared.throttle.update_limit(limit);
+    }
+
     /// Watch moving shard state and do the corresponding step.
     pub fn watch_state_changes(
         &self,
@@ -98,6 +122,8 @@ impl MoveShardController {
                         replica_id,
                         group_id,
                         replica: replica.clone(),
+                        resource_ctrl: ctrl.shared.resource_ctrl.clone(),
+                        throttle: ctrl.shared.throttle.clone(),
                         client,
                         desc: desc.clone(),
                     });
@@ -139,7 +165,7 @@ impl MoveShardCoordinator {
                     self.setup_source_group().await;
                 }
                 MoveShardStep::Moving => {
-                    self.pull(state.last_moved_key).await;
+                    self.pull(&state).await;
                 }
                 MoveShardStep::Moved => {
                     // Send finish moving request to source group.
@@ -165,12 +191,12 @@ impl MoveShardCoordinator {
         );
 
         match self.client.acquire_shard(&self.desc).await {
-            Ok(_) => {
+            Ok(resp) => {
                 info!(
-                    "setup source group moving shard success. replica={}, group={}, desc={}",
-                    self.replica_id, self.group_id, self.desc
+                    "setup source group moving shard success, total {} keys. replica={}, group={}, desc={}",
+                    resp.num_keys, self.replica_id, self.group_id, self.desc
                 );
-                self.enter_pulling_step().await;
+                self.enter_pulling_step(resp.num_keys).await;
             }
             Err(sekas_client::Error::EpochNotMatch(group_desc)) => {
                 // Since the epoch is not matched, this moving shard should be rollback.
@@ -250,8 +276,8 @@ impl MoveShardCoordinator {
         );
     }
 
-    async fn enter_pulling_step(&self) {
-        if let Err(e) = self.replica.enter_pulling_step(&self.desc).await {
+    async fn enter_pulling_step(&self, total_keys: u64) {
+        if let Err(e) = self.replica.enter_pulling_step(&self.desc, total_keys).await {
             error!(
                 "enter pulling step: {e:?}. replica={}, group={}, desc={}",
                 self.replica_id, self.group_id, self.desc
@@ -262,10 +288,16 @@ impl MoveShardCoordinator {
     async fn clean_orphan_shard(&self) {
         use super::gc::remove_shard;
 
+        let _permit = self.resource_ctrl.acquire_background_job().await;
         let group_engine = self.replica.group_engine();
-        if let Err(e) =
-            remove_shard(&self.cfg, self.replica.as_ref(), group_engine, self.desc.get_shard_id())
-                .await
+        if let Err(e) = remove_shard(
+            &self.cfg,
+            self.replica.as_ref(),
+            &self.resource_ctrl,
+            group_engine,
+            self.desc.get_shard_id(),
+        )
+        .await
         {
             error!(
                 "remove moved out shard from source group: {e:?}. replica={}, group={}, desc={}",
@@ -277,9 +309,17 @@ impl MoveShardCoordinator {
         self.clean_move_shard_state().await;
     }
 
-    async fn pull(&mut self, last_migrated_key: Option<Vec<u8>>) {
-        if let Err(e) =
-            pull_shard(&self.client, self.replica.as_ref(), &self.desc, last_migrated_key).await
+    async fn pull(&mut self, state: &MoveShardState) {
+        let permit = self.resource_ctrl.acquire_background_job().await;
+        if let Err(e) = pull_shard(
+            &self.client,
+            self.replica.as_ref(),
+            &self.resource_ctrl,
+            &self.throttle,
+            &self.desc,
+            state,
+        )
+        .await
         {
             error!(
                 "pull shard from source group: {e:?}. replica={}, group={}, desc={}",
@@ -287,6 +327,7 @@ impl MoveShardCoordinator {
             );
             return;
         }
+        drop(permit);
 
         self.commit_dest_group().await;
     }
@@ -297,32 +338,165 @@ impl MoveShardCoordinator {
     }
 }
 
+/// The bytes limit of each chunk pulled from the source group.
+const PULL_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// The progress of pulling a shard, which is saved along with the ingested
+/// data so that the moving could be resumed after leader changes.
+struct PullProgress {
+    last_key: Option<Vec<u8>>,
+    moved_keys: u64,
+    moved_bytes: u64,
+    backoff: PullBackoff,
+}
+
 pub async fn pull_shard(
     client: &MoveShardClient,
     replica: &Replica,
+    resource_ctrl: &ResourceController,
+    throttle: &MoveShardThrottle,
     desc: &MoveShardDesc,
-    last_migrated_key: Option<Vec<u8>>,
+    state: &MoveShardState,
 ) -> Result<()> {
     record_latency!(take_pull_shard_metrics());
     let shard_id = desc.get_shard_id();
-    let mut finished = false;
-    let mut last_key = last_migrated_key;
-    while !finished {
-        info!("pull shard chunk with last key {last_key:?}");
-        let shard_chunk = client.pull_shard_chunk(shard_id, last_key.clone()).await?;
-        info!("pull shard chunk with {} data", shard_chunk.len());
-        if let Some(value_set) = shard_chunk.last() {
-            last_key = Some(value_set.user_key.clone());
-        } else {
-            finished = true;
-        }
-        for value_set in &shard_chunk {
-            replica.ingest_value_set(shard_id, value_set).await?;
+    // Resume from the progress saved by the previous leader, if any.
+    let mut progress = PullProgress {
+        last_key: state.last_moved_key.clone(),
+        moved_keys: state.moved_keys,
+        moved_bytes: state.moved_bytes,
+        backoff: PullBackoff::default(),
+    };
+    if progress.last_key.as_ref().map(|k| !k.is_empty()).unwrap_or_default() {
+        info!(
+            "resume pulling shard {shard_id} from key {:?}, {} keys are moved",
+            progress.last_key, progress.moved_keys
+        );
+    }
+    loop {
+        info!("pull shard stream with last key {:?}", progress.last_key);
+        let last_key = progress.last_key.clone();
+        let mut stream = match client.pull_shard_stream(shard_id, last_key, PULL_CHUNK_BYTES).await
+        {
+            Ok(stream) => stream,
+            Err(sekas_client::Error::Rpc(status))
+                if status.code() == tonic::Code::Unimplemented =>
+            {
+                // The source node has not been upgraded yet, pull the chunks one by one.
+                info!("source group of shard {shard_id} does not support streaming: {status}");
+                return pull_shard_by_scan(
+                    client,
+                    replica,
+                    resource_ctrl,
+                    throttle,
+                    shard_id,
+                    progress,
+                )
+                .await;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        loop {
+            let start = Instant::now();
+            let chunk = match stream.message().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(status) => {
+                    // Resume the stream from the last ingested key.
+                    warn!("pull shard {shard_id} stream: {status}");
+                    break;
+                }
+            };
+            if !chunk.verify() {
+                return Err(Error::InvalidData(format!(
+                    "the checksum of the pulled chunk of shard {shard_id} is mismatched"
+                )));
+            }
+            let finished = chunk.data.is_empty();
+            fail_point!("move_shard_ingest_chunk");
+            ingest_chunk(
+                replica,
+                resource_ctrl,
+                throttle,
+                shard_id,
+                &chunk.data,
+                start,
+                &mut progress,
+            )
+            .await?;
+            if finished {
+                return Ok(());
+            }
         }
-        if let Some(value_set) = shard_chunk.last() {
-            replica.save_ingest_progress(shard_id, &value_set.user_key).await?
+    }
+}
+
+/// Pull the shard by issuing a scan request for each chunk, it is used when the
+/// source node does not support the streaming.
+async fn pull_shard_by_scan(
+    client: &MoveShardClient,
+    replica: &Replica,
+    resource_ctrl: &ResourceController,
+    throttle: &MoveShardThrottle,
+    shard_id: u64,
+    mut progress: PullProgress,
+) -> Result<()> {
+    loop {
+        info!("pull shard chunk with last key {:?}", progress.last_key);
+        let start = Instant::now();
+        let shard_chunk = client.pull_shard_chunk(shard_id, progress.last_key.clone()).await?;
+        let finished = shard_chunk.is_empty();
+        ingest_chunk(
+            replica,
+            resource_ctrl,
+            throttle,
+            shard_id,
+            &shard_chunk,
+            start,
+            &mut progress,
+        )
+        .await?;
+        if finished {
+            return Ok(());
         }
-        NODE_INGEST_CHUNK_TOTAL.inc();
     }
+}
+
+/// Ingest a pulled chunk into the dest group and save the progress.
+async fn ingest_chunk(
+    replica: &Replica,
+    resource_ctrl: &ResourceController,
+    throttle: &MoveShardThrottle,
+    shard_id: u64,
+    shard_chunk: &[ValueSet],
+    start: Instant,
+    progress: &mut PullProgress,
+) -> Result<()> {
+    let latency = start.elapsed();
+    info!("pull shard chunk with {} data", shard_chunk.len());
+    let num_bytes = shard_chunk.iter().map(|v| v.encoded_len()).sum();
+    resource_ctrl.consume(ResourceGroup::MoveShard, num_bytes).await;
+    throttle.consume(shard_chunk.len() as u64, num_bytes as u64).await;
+    // The latency of the source group rises once it is busy, back off so that the
+    // foreground requests take priority.
+    if let Some(duration) = progress.backoff.next(&throttle.limit(), latency) {
+        debug!("source group of shard {shard_id} is slow, back off {duration:?}");
+        tokio::time::sleep(duration).await;
+    }
+    replica.ingest_value_sets(shard_id, shard_chunk).await?;
+    if let Some(value_set) = shard_chunk.last() {
+        progress.last_key = Some(value_set.user_key.clone());
+        progress.moved_keys += shard_chunk.len() as u64;
+        progress.moved_bytes += num_bytes as u64;
+        replica
+            .save_ingest_progress(
+                shard_id,
+                &value_set.user_key,
+                progress.moved_keys,
+                progress.moved_bytes,
+            )
+            .await?
+    }
+    NODE_INGEST_CHUNK_TOTAL.inc();
     Ok(())
 }
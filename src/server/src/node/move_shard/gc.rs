@@ -13,12 +13,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use crate::engine::{GroupEngine, SnapshotMode};
+use crate::node::resource::{ResourceController, ResourceGroup};
 use crate::node::Replica;
 use crate::{NodeConfig, Result};
 
 pub(crate) async fn remove_shard(
     cfg: &NodeConfig,
     replica: &Replica,
+    resource_ctrl: &ResourceController,
     group_engine: GroupEngine,
     shard_id: u64,
 ) -> Result<()> {
@@ -29,6 +31,8 @@ pub(crate) async fn remove_shard(
             break;
         }
         latest_key = Some(chunk.last().unwrap().0.to_owned());
+        let num_bytes = chunk.iter().map(|(key, _)| key.len() + std::mem::size_of::<u64>()).sum();
+        resource_ctrl.consume(ResourceGroup::Gc, num_bytes).await;
         replica.delete_chunks(shard_id, &chunk).await?;
     }
     Ok(())
@@ -15,5 +15,6 @@
 
 mod ctrl;
 mod gc;
+mod throttle;
 
 pub(crate) use self::ctrl::{ForwardCtx, MoveShardController};
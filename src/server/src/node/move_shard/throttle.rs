@@ -0,0 +1,204 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::info;
+use sekas_api::server::v1::MoveShardLimit;
+
+use crate::node::metrics::*;
+
+/// The initial backoff once the source group is slow.
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+
+/// The max backoff between pulling two chunks.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Throttle the data copying of the shards moving into a node, the limits are
+/// specified by root and shared by all moving shards of the node.
+#[derive(Default)]
+pub struct MoveShardThrottle {
+    state: Mutex<ThrottleState>,
+}
+
+struct ThrottleState {
+    limit: MoveShardLimit,
+    /// The available tokens, they are negative if borrowed from the future.
+    keys: f64,
+    bytes: f64,
+    last_refill: Instant,
+}
+
+/// Back off the pulling of a moving shard once the latency of the source
+/// group rises, so that the foreground requests of the source group take
+/// priority.
+#[derive(Default)]
+pub struct PullBackoff {
+    backoff: Duration,
+}
+
+impl MoveShardThrottle {
+    pub fn update_limit(&self, limit: MoveShardLimit) {
+        let mut state = self.state.lock().unwrap();
+        if state.limit != limit {
+            info!(
+                "update move shard limit, keys per sec {}, bytes per sec {}, backoff latency {}ms",
+                limit.keys_per_sec, limit.bytes_per_sec, limit.backoff_latency_ms
+            );
+            state.limit = limit;
+        }
+    }
+
+    #[inline]
+    pub fn limit(&self) -> MoveShardLimit {
+        self.state.lock().unwrap().limit.clone()
+    }
+
+    /// Consume the keys and bytes of a pulled chunk, wait until they are
+    /// allowed by the limits.
+    pub async fn consume(&self, num_keys: u64, num_bytes: u64) {
+        if let Some(duration) = self.consume_at(num_keys, num_bytes, Instant::now()) {
+            NODE_MOVE_SHARD_THROTTLE_DURATION_SECONDS.observe(duration.as_secs_f64());
+            tokio::time::sleep(duration).await;
+        }
+    }
+
+    fn consume_at(&self, num_keys: u64, num_bytes: u64, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.last_refill).as_secs_f64();
+        state.last_refill = now;
+        let keys_wait = consume_tokens(
+            &mut state.keys,
+            state.limit.keys_per_sec as f64,
+            elapsed,
+            num_keys as f64,
+        );
+        let bytes_wait = consume_tokens(
+            &mut state.bytes,
+            state.limit.bytes_per_sec as f64,
+            elapsed,
+            num_bytes as f64,
+        );
+        let wait = f64::max(keys_wait, bytes_wait);
+        if wait > 0.0 {
+            Some(Duration::from_secs_f64(wait))
+        } else {
+            None
+        }
+    }
+}
+
+impl PullBackoff {
+    /// Record the latency of pulling a chunk from the source group, and return
+    /// the duration to wait before pulling the next chunk.
+    pub fn next(&mut self, limit: &MoveShardLimit, latency: Duration) -> Option<Duration> {
+        let threshold = Duration::from_millis(limit.backoff_latency_ms);
+        if limit.backoff_latency_ms > 0 && latency > threshold {
+            self.backoff = (self.backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+            NODE_MOVE_SHARD_BACKOFF_TOTAL.inc();
+        } else {
+            self.backoff /= 2;
+            if self.backoff < MIN_BACKOFF {
+                self.backoff = Duration::ZERO;
+            }
+        }
+        if self.backoff.is_zero() {
+            None
+        } else {
+            Some(self.backoff)
+        }
+    }
+}
+
+impl Default for ThrottleState {
+    fn default() -> Self {
+        ThrottleState {
+            limit: MoveShardLimit::default(),
+            keys: 0.0,
+            bytes: 0.0,
+            last_refill: Instant::now(),
+        }
+    }
+}
+
+/// Refill and consume the tokens, return the seconds to wait. The burst is
+/// limited to the tokens of one second.
+fn consume_tokens(tokens: &mut f64, rate: f64, elapsed: f64, amount: f64) -> f64 {
+    if rate <= 0.0 {
+        *tokens = 0.0;
+        return 0.0;
+    }
+    *tokens = f64::min(rate, *tokens + elapsed * rate) - amount;
+    if *tokens < 0.0 {
+        -*tokens / rate
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_of(throttle: &MoveShardThrottle) -> Instant {
+        throttle.state.lock().unwrap().last_refill
+    }
+
+    #[test]
+    fn throttle_keys_and_bytes() {
+        let throttle = MoveShardThrottle::default();
+        let now = start_of(&throttle);
+        assert_eq!(throttle.consume_at(100, 1000, now), None);
+
+        throttle.update_limit(MoveShardLimit {
+            keys_per_sec: 100,
+            bytes_per_sec: 1000,
+            ..Default::default()
+        });
+        assert_eq!(throttle.consume_at(50, 1000, now), Some(Duration::from_secs(1)));
+
+        // The keys limit dominates.
+        let now = now + Duration::from_secs(1);
+        assert_eq!(throttle.consume_at(300, 1000, now), Some(Duration::from_millis(2500)));
+
+        // Remove the limits.
+        throttle.update_limit(MoveShardLimit::default());
+        let now = now + Duration::from_secs(1);
+        assert_eq!(throttle.consume_at(1000, 1000, now), None);
+    }
+
+    #[test]
+    fn backoff_on_slow_source_group() {
+        let limit = MoveShardLimit { backoff_latency_ms: 100, ..Default::default() };
+        let mut backoff = PullBackoff::default();
+        assert_eq!(backoff.next(&limit, Duration::from_millis(10)), None);
+        assert_eq!(backoff.next(&limit, Duration::from_millis(200)), Some(MIN_BACKOFF));
+        assert_eq!(backoff.next(&limit, Duration::from_millis(200)), Some(MIN_BACKOFF * 2));
+        for _ in 0..32 {
+            backoff.next(&limit, Duration::from_secs(1));
+        }
+        assert_eq!(backoff.next(&limit, Duration::from_secs(1)), Some(MAX_BACKOFF));
+
+        // Recover once the source group is fast again.
+        for _ in 0..32 {
+            backoff.next(&limit, Duration::from_millis(10));
+        }
+        assert_eq!(backoff.next(&limit, Duration::from_millis(10)), None);
+
+        // Never back off if the threshold is not specified.
+        let limit = MoveShardLimit::default();
+        assert_eq!(backoff.next(&limit, Duration::from_secs(10)), None);
+    }
+}
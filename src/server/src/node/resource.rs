@@ -0,0 +1,226 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::metrics::*;
+use crate::ResourceConfig;
+
+/// The default ratio of bandwidth reserved for the background jobs.
+const DEFAULT_MIN_BACKGROUND_IO_RATIO: f64 = 0.1;
+
+/// The window to estimate the bandwidth used by the foreground requests.
+const FOREGROUND_WINDOW: Duration = Duration::from_secs(1);
+
+/// The traffic classes of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceGroup {
+    /// The requests issued by clients.
+    Foreground,
+    /// Pulling the data of a shard being moved into this node.
+    MoveShard,
+    /// Removing the data of a shard which has been moved out.
+    Gc,
+}
+
+/// Schedule the disk bandwidth and the concurrency of a node between the
+/// foreground requests and the background jobs.
+///
+/// The foreground requests are never throttled, but they consume the
+/// bandwidth first. The background jobs share the remaining bandwidth, and at
+/// least the reserved ratio, so that they can't starve the user requests and
+/// still make progress.
+pub struct ResourceController {
+    /// The total bandwidth of the node, 0 means no limit.
+    io_bytes_per_sec: u64,
+    min_background_io_ratio: f64,
+    background_jobs: Arc<Semaphore>,
+    io: Mutex<IoState>,
+}
+
+struct IoState {
+    /// The estimated bandwidth of the foreground requests.
+    foreground_rate: f64,
+    foreground_bytes: f64,
+    window_start: Instant,
+
+    /// The available bytes of the background jobs, it is negative if the
+    /// bytes are borrowed from the future.
+    background_bytes: f64,
+    last_refill: Instant,
+}
+
+impl ResourceGroup {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceGroup::Foreground => "foreground",
+            ResourceGroup::MoveShard => "move_shard",
+            ResourceGroup::Gc => "gc",
+        }
+    }
+}
+
+impl ResourceController {
+    pub fn new(cfg: &ResourceConfig) -> Self {
+        let max_background_jobs = if cfg.max_background_jobs == 0 {
+            Semaphore::MAX_PERMITS
+        } else {
+            cfg.max_background_jobs
+        };
+        let min_background_io_ratio = cfg
+            .min_background_io_ratio
+            .unwrap_or(DEFAULT_MIN_BACKGROUND_IO_RATIO)
+            .clamp(f64::MIN_POSITIVE, 1.0);
+        let now = Instant::now();
+        ResourceController {
+            io_bytes_per_sec: cfg.io_bytes_per_sec,
+            min_background_io_ratio,
+            background_jobs: Arc::new(Semaphore::new(max_background_jobs)),
+            io: Mutex::new(IoState {
+                foreground_rate: 0.0,
+                foreground_bytes: 0.0,
+                window_start: now,
+                background_bytes: 0.0,
+                last_refill: now,
+            }),
+        }
+    }
+
+    /// Acquire a slot to run a background job, the slot is released once the
+    /// permit is dropped.
+    pub async fn acquire_background_job(&self) -> OwnedSemaphorePermit {
+        self.background_jobs.clone().acquire_owned().await.expect("semaphore is never closed")
+    }
+
+    /// Consume the bytes read or written by the resource group. The background
+    /// jobs wait until the bandwidth is available.
+    pub async fn consume(&self, group: ResourceGroup, num_bytes: usize) {
+        NODE_RESOURCE_BYTES_TOTAL.with_label_values(&[group.as_str()]).inc_by(num_bytes as u64);
+        if let Some(duration) = self.consume_at(group, num_bytes, Instant::now()) {
+            NODE_RESOURCE_THROTTLE_DURATION_SECONDS
+                .with_label_values(&[group.as_str()])
+                .observe(duration.as_secs_f64());
+            tokio::time::sleep(duration).await;
+        }
+    }
+
+    fn consume_at(&self, group: ResourceGroup, num_bytes: usize, now: Instant) -> Option<Duration> {
+        if self.io_bytes_per_sec == 0 {
+            return None;
+        }
+
+        let mut io = self.io.lock().unwrap();
+        io.advance_foreground_window(now);
+        if group == ResourceGroup::Foreground {
+            io.foreground_bytes += num_bytes as f64;
+            return None;
+        }
+
+        let rate = self.background_rate(io.foreground_rate);
+        let elapsed = now.saturating_duration_since(io.last_refill).as_secs_f64();
+        io.background_bytes = f64::min(rate, io.background_bytes + elapsed * rate);
+        io.last_refill = now;
+        io.background_bytes -= num_bytes as f64;
+        if io.background_bytes < 0.0 {
+            Some(Duration::from_secs_f64(-io.background_bytes / rate))
+        } else {
+            None
+        }
+    }
+
+    /// Return the bandwidth of the background jobs.
+    fn background_rate(&self, foreground_rate: f64) -> f64 {
+        let total = self.io_bytes_per_sec as f64;
+        f64::max(total - foreground_rate, total * self.min_background_io_ratio)
+    }
+}
+
+impl IoState {
+    fn advance_foreground_window(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed >= FOREGROUND_WINDOW {
+            self.foreground_rate = self.foreground_bytes / elapsed.as_secs_f64();
+            self.foreground_bytes = 0.0;
+            self.window_start = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_of(ctrl: &ResourceController) -> Instant {
+        ctrl.io.lock().unwrap().window_start
+    }
+
+    #[test]
+    fn background_use_remaining_bandwidth() {
+        let cfg = ResourceConfig { io_bytes_per_sec: 1000, ..Default::default() };
+        let ctrl = ResourceController::new(&cfg);
+        let now = start_of(&ctrl);
+        assert_eq!(
+            ctrl.consume_at(ResourceGroup::MoveShard, 500, now),
+            Some(Duration::from_millis(500))
+        );
+
+        // The foreground requests are never throttled.
+        assert_eq!(ctrl.consume_at(ResourceGroup::Foreground, 800, now), None);
+
+        // The background jobs use the remaining bandwidth in the next window.
+        let now = now + FOREGROUND_WINDOW;
+        assert_eq!(ctrl.consume_at(ResourceGroup::Foreground, 800, now), None);
+        assert_eq!(
+            ctrl.consume_at(ResourceGroup::MoveShard, 100, now),
+            Some(Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn background_reserved_bandwidth() {
+        let cfg = ResourceConfig {
+            io_bytes_per_sec: 1000,
+            min_background_io_ratio: Some(0.5),
+            ..Default::default()
+        };
+        let ctrl = ResourceController::new(&cfg);
+        let now = start_of(&ctrl);
+        assert_eq!(ctrl.consume_at(ResourceGroup::Foreground, 100000, now), None);
+
+        let now = now + FOREGROUND_WINDOW;
+        assert_eq!(ctrl.consume_at(ResourceGroup::Gc, 500, now), None);
+        assert_eq!(ctrl.consume_at(ResourceGroup::Gc, 500, now), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn no_limits() {
+        let ctrl = ResourceController::new(&ResourceConfig::default());
+        let now = Instant::now();
+        assert_eq!(ctrl.consume_at(ResourceGroup::MoveShard, usize::MAX, now), None);
+    }
+
+    #[sekas_macro::test]
+    async fn limit_background_jobs() {
+        let cfg = ResourceConfig { max_background_jobs: 1, ..Default::default() };
+        let ctrl = ResourceController::new(&cfg);
+        let permit = ctrl.acquire_background_job().await;
+        assert!(ctrl.background_jobs.clone().try_acquire_owned().is_err());
+        drop(permit);
+        assert!(ctrl.background_jobs.clone().try_acquire_owned().is_ok());
+    }
+}
@@ -77,6 +77,12 @@ impl ReplicaRouteTable {
         let mut core = self.core.write().unwrap();
         core.replicas.remove(&group_id)
     }
+
+    /// Return all replicas in the route table.
+    pub fn replicas(&self) -> Vec<Arc<Replica>> {
+        let core = self.core.read().unwrap();
+        core.replicas.values().cloned().collect()
+    }
 }
 
 /// A structure support raft route table query.
@@ -0,0 +1,124 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::RaftConfig;
+
+/// The max backoff hint, in multiples of the raft tick interval.
+const MAX_BACKOFF_TICKS: u64 = 8;
+
+/// The backlog of a raft group, which is published by the raft worker after
+/// each round, and consulted before admitting new writes.
+#[derive(Default)]
+pub struct RaftBacklog {
+    max_uncommitted_entries: u64,
+    max_unapplied_entries: u64,
+    tick_interval_ms: u64,
+
+    uncommitted_entries: AtomicU64,
+    unapplied_entries: AtomicU64,
+}
+
+impl RaftBacklog {
+    pub fn new(cfg: &RaftConfig) -> Self {
+        RaftBacklog {
+            max_uncommitted_entries: cfg.max_uncommitted_entries,
+            max_unapplied_entries: cfg.max_unapplied_entries,
+            tick_interval_ms: cfg.tick_interval_ms,
+            ..Default::default()
+        }
+    }
+
+    /// Update the backlog with the raft log indexes.
+    pub(super) fn update(&self, last_index: u64, committed: u64, applied: u64) {
+        self.uncommitted_entries.store(last_index.saturating_sub(committed), Ordering::Relaxed);
+        self.unapplied_entries.store(committed.saturating_sub(applied), Ordering::Relaxed);
+    }
+
+    /// Return the number of raft entries proposed but not committed.
+    #[inline]
+    pub fn uncommitted_entries(&self) -> u64 {
+        self.uncommitted_entries.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of raft entries committed but not applied.
+    #[inline]
+    pub fn unapplied_entries(&self) -> u64 {
+        self.unapplied_entries.load(Ordering::Relaxed)
+    }
+
+    /// Return the suggested backoff if new writes should be rejected, the
+    /// backoff grows as the backlog exceeds the limits.
+    pub fn busy_backoff(&self) -> Option<Duration> {
+        let uncommitted = exceeded_ratio(self.uncommitted_entries(), self.max_uncommitted_entries);
+        let unapplied = exceeded_ratio(self.unapplied_entries(), self.max_unapplied_entries);
+        let ratio = std::cmp::max(uncommitted, unapplied);
+        if ratio == 0 {
+            return None;
+        }
+        let ticks = std::cmp::min(ratio, MAX_BACKOFF_TICKS);
+        Some(Duration::from_millis(self.tick_interval_ms * ticks))
+    }
+}
+
+/// Return how many times the value exceeds the limit, 0 if the limit is not
+/// exceeded or there is no limit.
+#[inline]
+fn exceeded_ratio(value: u64, limit: u64) -> u64 {
+    if limit == 0 || value <= limit {
+        0
+    } else {
+        value.div_ceil(limit) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_backoff() {
+        let cfg = RaftConfig {
+            max_uncommitted_entries: 10,
+            max_unapplied_entries: 100,
+            tick_interval_ms: 10,
+            ..Default::default()
+        };
+        let backlog = RaftBacklog::new(&cfg);
+        assert_eq!(backlog.busy_backoff(), None);
+
+        backlog.update(110, 100, 100);
+        assert_eq!(backlog.uncommitted_entries(), 10);
+        assert_eq!(backlog.unapplied_entries(), 0);
+        assert_eq!(backlog.busy_backoff(), None);
+
+        backlog.update(111, 100, 100);
+        assert_eq!(backlog.busy_backoff(), Some(Duration::from_millis(10)));
+
+        backlog.update(300, 300, 150);
+        assert_eq!(backlog.busy_backoff(), Some(Duration::from_millis(10)));
+
+        backlog.update(10000, 10000, 0);
+        assert_eq!(backlog.busy_backoff(), Some(Duration::from_millis(80)));
+    }
+
+    #[test]
+    fn no_limit() {
+        let backlog = RaftBacklog::new(&RaftConfig::default());
+        backlog.update(u64::MAX, 1, 0);
+        assert_eq!(backlog.busy_backoff(), None);
+    }
+}
@@ -14,6 +14,7 @@
 
 use std::collections::{HashMap, VecDeque};
 use std::path::Path;
+use std::time::Instant;
 
 use futures::channel::oneshot;
 use raft::prelude::{ConfChangeV2, Entry, EntryType};
@@ -21,9 +22,11 @@ use raft::{RawNode, ReadState};
 use sekas_api::server::v1::ReplicaDesc;
 
 use super::fsm::StateMachine;
+use super::group::ProposalTracker;
 use super::monitor::ApplierPerfContext;
 use super::storage::Storage;
 use super::ApplyEntry;
+use crate::failpoint::fail_point;
 use crate::raftgroup::metrics::*;
 use crate::raftgroup::monitor::record_perf_point;
 use crate::serverpb::v1::{EntryId, EvalResult};
@@ -32,7 +35,10 @@ use crate::{record_latency, Error, Result};
 struct ProposalContext {
     index: u64,
     term: u64,
-    sender: oneshot::Sender<Result<()>>,
+    /// The senders of the proposals merged into this entry.
+    senders: Vec<oneshot::Sender<Result<()>>>,
+    /// The trackers of the proposals merged into this entry.
+    trackers: Vec<ProposalTracker>,
 }
 
 /// Cache the descriptor of other replicas in the same group.
@@ -75,18 +81,20 @@ impl<M: StateMachine> Applier<M> {
         &mut self,
         index: u64,
         term: u64,
-        sender: oneshot::Sender<Result<()>>,
+        senders: Vec<oneshot::Sender<Result<()>>>,
+        trackers: Vec<ProposalTracker>,
     ) {
-        let ctx = ProposalContext { index, term, sender };
+        let ctx = ProposalContext { index, term, senders, trackers };
 
         // ensure the proposals are monotonic.
         if let Some(last_ctx) = self.proposal_queue.back() {
             if last_ctx.index >= ctx.index {
                 let last_ctx = self.proposal_queue.pop_back().unwrap();
-                last_ctx
-                    .sender
-                    .send(Err(Error::NotLeader(self.group_id, term, None)))
-                    .unwrap_or_default();
+                for sender in last_ctx.senders {
+                    sender
+                        .send(Err(Error::NotLeader(self.group_id, term, None)))
+                        .unwrap_or_default();
+                }
             }
         }
         self.proposal_queue.push_back(ctx);
@@ -148,6 +156,7 @@ impl<M: StateMachine> Applier<M> {
         committed_entries: Vec<Entry>,
     ) -> u64 {
         record_latency!(&RAFTGROUP_WORKER_APPLY_DURATION_SECONDS);
+        fail_point!("raft_apply_entries", panic);
         RAFTGROUP_WORKER_APPLY_ENTRIES_SIZE.observe(committed_entries.len() as f64);
 
         perf_ctx.num_committed = committed_entries.len();
@@ -219,23 +228,40 @@ impl<M: StateMachine> Applier<M> {
 
         assert!(matches!(entry.get_entry_type(), EntryType::EntryNormal));
 
+        let trackers = self.proposal_trackers(entry.index);
+        let span = apply_span(self.group_id, entry.index, &trackers);
+        let _entered = span.enter();
+        let start = Instant::now();
         let eval_result = EvalResult::decode(&*entry.data).expect("Entry::data is EvalResult");
         self.state_machine
             .apply(entry.index, entry.term, ApplyEntry::Proposal { eval_result })
             .expect("apply normal entry");
+        let elapsed = start.elapsed();
+        trackers.iter().for_each(|tracker| tracker.record_apply(elapsed));
+    }
+
+    /// Return the trackers of the proposals of the entry, if the entry is
+    /// proposed by this replica.
+    fn proposal_trackers(&self, index: u64) -> Vec<ProposalTracker> {
+        match self.proposal_queue.front() {
+            Some(ctx) if ctx.index == index => ctx.trackers.clone(),
+            _ => vec![],
+        }
     }
 
     #[inline]
     fn response_proposal(&mut self, index: u64, term: u64) {
         if self.proposal_queue.front().map(|ctx| ctx.index == index).unwrap_or_default() {
             let ctx = self.proposal_queue.pop_front().unwrap();
-            if ctx.term == term {
-                // TODO(walter) support user defined result.
-                ctx.sender.send(Ok(())).unwrap_or_default();
-            } else {
-                ctx.sender
-                    .send(Err(Error::NotLeader(self.group_id, term, None)))
-                    .unwrap_or_default();
+            for sender in ctx.senders {
+                if ctx.term == term {
+                    // TODO(walter) support user defined result.
+                    sender.send(Ok(())).unwrap_or_default();
+                } else {
+                    sender
+                        .send(Err(Error::NotLeader(self.group_id, term, None)))
+                        .unwrap_or_default();
+                }
             }
         }
     }
@@ -283,3 +309,17 @@ impl ReplicaCache {
         self.replicas.get(&replica_id).cloned()
     }
 }
+
+/// Return the span of applying the entry, which is a child of the proposal
+/// span.
+fn apply_span(group_id: u64, index: u64, trackers: &[ProposalTracker]) -> tracing::Span {
+    let Some((first, others)) = trackers.split_first() else {
+        return tracing::Span::none();
+    };
+    let span = tracing::info_span!(parent: &first.span, "raft.apply", group_id, index);
+    for other in others {
+        // The proposals merged into this entry.
+        span.follows_from(&other.span);
+    }
+    span
+}
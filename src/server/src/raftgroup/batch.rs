@@ -0,0 +1,285 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use sekas_runtime::time::Instant;
+
+use super::group::ProposalTracker;
+use crate::serverpb::v1::EvalResult;
+use crate::Result;
+
+/// Accumulate the write batches of independent proposals, and propose them in
+/// a single raft entry.
+///
+/// The proposals are evaluated with latches held, so the queued write batches
+/// never conflict with each other, and it is safe to merge them.
+#[derive(Default)]
+pub(super) struct ProposalBatch {
+    wb: Option<rocksdb::WriteBatch>,
+    senders: Vec<oneshot::Sender<Result<()>>>,
+    trackers: Vec<ProposalTracker>,
+    first_queued_at: Option<Instant>,
+}
+
+/// Accumulate the concurrent read index requests, and confirm them with a
+/// single read index round.
+#[derive(Default)]
+pub(super) struct ReadIndexBatch {
+    senders: Vec<oneshot::Sender<Result<()>>>,
+    first_queued_at: Option<Instant>,
+}
+
+struct Appender<'a> {
+    wb: &'a mut rocksdb::WriteBatch,
+}
+
+impl ProposalBatch {
+    /// Only the proposals which only contain a write batch could be merged.
+    #[inline]
+    pub fn is_batchable(eval_result: &EvalResult) -> bool {
+        eval_result.op.is_none() && eval_result.batch.is_some()
+    }
+
+    /// Append the write batch of a batchable proposal.
+    pub fn push(
+        &mut self,
+        eval_result: EvalResult,
+        sender: oneshot::Sender<Result<()>>,
+        tracker: ProposalTracker,
+    ) {
+        debug_assert!(Self::is_batchable(&eval_result));
+        let data = eval_result.batch.map(|b| b.data).unwrap_or_default();
+        match self.wb.as_mut() {
+            Some(wb) => rocksdb::WriteBatch::from_data(&data).iterate(&mut Appender { wb }),
+            None => {
+                self.wb = Some(rocksdb::WriteBatch::from_data(&data));
+                self.first_queued_at = Some(Instant::now());
+            }
+        }
+        self.senders.push(sender);
+        self.trackers.push(tracker);
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    /// The size of the merged write batch, in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.wb.as_ref().map(|wb| wb.size_in_bytes()).unwrap_or_default()
+    }
+
+    /// The instant that the batch should be proposed, in order to bound the
+    /// latency of the queued proposals.
+    #[inline]
+    pub fn deadline(&self, max_delay: Duration) -> Option<Instant> {
+        self.first_queued_at.map(|at| at + max_delay)
+    }
+
+    /// Whether the queued proposals have waited long enough, or the merged
+    /// write batch reaches `max_size` bytes.
+    pub fn is_ready(&self, max_delay: Duration, max_size: usize) -> bool {
+        match self.deadline(max_delay) {
+            Some(deadline) => deadline <= Instant::now() || self.size() >= max_size,
+            None => false,
+        }
+    }
+
+    /// Take the merged proposal and the senders and trackers of the queued
+    /// proposals.
+    #[allow(clippy::type_complexity)]
+    pub fn take(
+        &mut self,
+    ) -> Option<(EvalResult, Vec<oneshot::Sender<Result<()>>>, Vec<ProposalTracker>)> {
+        let wb = self.wb.take()?;
+        self.first_queued_at = None;
+        let senders = std::mem::take(&mut self.senders);
+        let trackers = std::mem::take(&mut self.trackers);
+        Some((EvalResult::with_batch(wb.data().to_vec()), senders, trackers))
+    }
+}
+
+impl ReadIndexBatch {
+    pub fn push(&mut self, sender: oneshot::Sender<Result<()>>) {
+        if self.senders.is_empty() {
+            self.first_queued_at = Some(Instant::now());
+        }
+        self.senders.push(sender);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// The instant that the read index round should be issued, in order to
+    /// bound the latency of the queued reads.
+    #[inline]
+    pub fn deadline(&self, max_delay: Duration) -> Option<Instant> {
+        self.first_queued_at.map(|at| at + max_delay)
+    }
+
+    /// Whether the queued reads have waited long enough, or the batch is full.
+    /// 0 `max_size` means no limit.
+    pub fn is_ready(&self, max_delay: Duration, max_size: usize) -> bool {
+        match self.deadline(max_delay) {
+            Some(deadline) => {
+                deadline <= Instant::now() || (max_size != 0 && self.len() >= max_size)
+            }
+            None => false,
+        }
+    }
+
+    /// Take the senders of the queued reads.
+    pub fn take(&mut self) -> Vec<oneshot::Sender<Result<()>>> {
+        self.first_queued_at = None;
+        std::mem::take(&mut self.senders)
+    }
+}
+
+impl<'a> rocksdb::WriteBatchIterator for Appender<'a> {
+    fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
+        self.wb.put(key, value);
+    }
+
+    fn delete(&mut self, key: Box<[u8]>) {
+        self.wb.delete(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverpb::v1::{SyncOp, WriteBatchRep};
+
+    struct Collector {
+        ops: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    }
+
+    impl rocksdb::WriteBatchIterator for Collector {
+        fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
+            self.ops.push((key.to_vec(), Some(value.to_vec())));
+        }
+
+        fn delete(&mut self, key: Box<[u8]>) {
+            self.ops.push((key.to_vec(), None));
+        }
+    }
+
+    fn eval_result(f: impl FnOnce(&mut rocksdb::WriteBatch)) -> EvalResult {
+        let mut wb = rocksdb::WriteBatch::default();
+        f(&mut wb);
+        EvalResult::with_batch(wb.data().to_vec())
+    }
+
+    #[test]
+    fn batchable_proposals() {
+        assert!(ProposalBatch::is_batchable(&eval_result(|wb| wb.put(b"a", b"1"))));
+        assert!(!ProposalBatch::is_batchable(&EvalResult::default()));
+        assert!(!ProposalBatch::is_batchable(&EvalResult {
+            batch: Some(WriteBatchRep::default()),
+            op: Some(SyncOp::default()),
+        }));
+    }
+
+    #[test]
+    fn merge_write_batches() {
+        let mut batch = ProposalBatch::default();
+        assert!(batch.is_empty());
+        assert!(batch.take().is_none());
+        assert!(batch.deadline(Duration::from_millis(1)).is_none());
+
+        let (tx1, _rx1) = oneshot::channel();
+        batch.push(eval_result(|wb| wb.put(b"a", b"1")), tx1, ProposalTracker::default());
+        let size = batch.size();
+        assert!(size > 0);
+        assert!(batch.deadline(Duration::from_millis(1)).is_some());
+
+        let (tx2, _rx2) = oneshot::channel();
+        batch.push(
+            eval_result(|wb| {
+                wb.put(b"b", b"2");
+                wb.delete(b"c");
+            }),
+            tx2,
+            ProposalTracker::default(),
+        );
+        assert!(batch.size() > size);
+
+        let (eval_result, senders, trackers) = batch.take().unwrap();
+        assert_eq!(senders.len(), 2);
+        assert_eq!(trackers.len(), 2);
+        assert!(batch.is_empty());
+        assert_eq!(batch.size(), 0);
+
+        let wb = rocksdb::WriteBatch::from_data(&eval_result.batch.unwrap().data);
+        assert_eq!(wb.len(), 3);
+        let mut collector = Collector { ops: vec![] };
+        wb.iterate(&mut collector);
+        assert_eq!(
+            collector.ops,
+            vec![
+                (b"a".to_vec(), Some(b"1".to_vec())),
+                (b"b".to_vec(), Some(b"2".to_vec())),
+                (b"c".to_vec(), None),
+            ]
+        );
+    }
+
+    #[sekas_macro::test(mock_time)]
+    async fn propose_batch_by_deadline_or_size() {
+        let delay = Duration::from_millis(10);
+        let mut batch = ProposalBatch::default();
+        assert!(!batch.is_ready(Duration::ZERO, 0));
+
+        let (tx, _rx) = oneshot::channel();
+        batch.push(eval_result(|wb| wb.put(b"a", b"1")), tx, ProposalTracker::default());
+        assert!(batch.is_ready(Duration::ZERO, usize::MAX));
+        assert!(!batch.is_ready(delay, usize::MAX));
+        sekas_runtime::time::advance(delay).await;
+        assert!(batch.is_ready(delay, usize::MAX));
+
+        // The large batch is proposed without waiting.
+        assert!(batch.is_ready(Duration::from_secs(3600), batch.size()));
+    }
+
+    #[sekas_macro::test(mock_time)]
+    async fn batch_read_index_requests() {
+        let delay = Duration::from_millis(10);
+        let mut batch = ReadIndexBatch::default();
+        assert!(batch.deadline(Duration::ZERO).is_none());
+        assert!(!batch.is_ready(Duration::ZERO, 0));
+
+        let (tx1, _rx1) = oneshot::channel();
+        batch.push(tx1);
+        assert!(batch.is_ready(Duration::ZERO, 0));
+        assert!(!batch.is_ready(delay, 0));
+        sekas_runtime::time::advance(delay).await;
+        assert!(batch.is_ready(delay, 0));
+        assert!(!batch.is_ready(Duration::from_secs(3600), 2));
+
+        // The full batch is issued without waiting.
+        let (tx2, _rx2) = oneshot::channel();
+        batch.push(tx2);
+        assert!(batch.is_ready(Duration::from_secs(3600), 2));
+
+        assert_eq!(batch.take().len(), 2);
+        assert_eq!(batch.len(), 0);
+        assert!(batch.deadline(Duration::ZERO).is_none());
+    }
+}
@@ -12,11 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Instant;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures::channel::{mpsc, oneshot};
 use sekas_api::server::v1::ChangeReplicas;
+use tracing::Instrument;
 
+use super::admission::RaftBacklog;
 use super::metrics::*;
 use super::worker::{RaftGroupState, Request};
 use super::{ReadPolicy, WorkerPerfContext};
@@ -31,12 +35,41 @@ where
     Self: Send,
 {
     request_sender: mpsc::Sender<Request>,
+    backlog: Arc<RaftBacklog>,
+}
+
+/// The durations of a proposal.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProposeStats {
+    /// From submitting the proposal to the entry is applied, excluding the
+    /// `apply` duration.
+    pub propose: Duration,
+    /// The duration of applying the entry to the state machine.
+    pub apply: Duration,
+}
+
+/// Follow a proposal through the raft worker, to trace and measure it.
+#[derive(Clone, Default)]
+pub struct ProposalTracker {
+    /// The span of the proposal.
+    pub span: tracing::Span,
+    apply_micros: Arc<AtomicU64>,
 }
 
 impl RaftGroup {
     /// Open the existed raft group.
-    pub fn open(sender: mpsc::Sender<Request>) -> Self {
-        RaftGroup { request_sender: sender }
+    pub fn open(sender: mpsc::Sender<Request>, backlog: Arc<RaftBacklog>) -> Self {
+        RaftGroup { request_sender: sender, backlog }
+    }
+
+    /// Return the suggested backoff if the backlog of raft group exceeds the
+    /// limits, and the new writes should be rejected.
+    pub fn busy_backoff(&self) -> Option<Duration> {
+        let backoff = self.backlog.busy_backoff();
+        if backoff.is_some() {
+            RAFTGROUP_BUSY_REJECTED_TOTAL.inc();
+        }
+        backoff
     }
 
     /// Submit a data to replicate, and returns corresponding future value.
@@ -47,13 +80,25 @@ impl RaftGroup {
     ///
     /// TODO(walter) support return user defined error.
     pub async fn propose(&self, eval_result: EvalResult) -> Result<()> {
+        self.propose_with_stats(eval_result).await.map(|_| ())
+    }
+
+    /// Like [`RaftGroup::propose`], and return the durations of the proposal.
+    pub async fn propose_with_stats(&self, eval_result: EvalResult) -> Result<ProposeStats> {
         let start_at = Instant::now();
         let (sender, receiver) = oneshot::channel();
 
-        let request = Request::Propose { eval_result, start: start_at, sender };
+        // The tracker is carried to the raft worker, so that the applying of the
+        // entry is traced as a child of the proposal span.
+        let tracker =
+            ProposalTracker { span: tracing::info_span!("raft.propose"), ..Default::default() };
+        let request =
+            Request::Propose { eval_result, start: start_at, sender, tracker: tracker.clone() };
 
         self.send(request)?;
-        take_propose_metrics(start_at, receiver.await?)
+        take_propose_metrics(start_at, receiver.instrument(tracker.span.clone()).await?)?;
+        let apply = tracker.apply_duration();
+        Ok(ProposeStats { propose: start_at.elapsed().saturating_sub(apply), apply })
     }
 
     /// Execute reading operations with the specified read policy.
@@ -130,3 +175,14 @@ impl RaftGroup {
         Ok(())
     }
 }
+
+impl ProposalTracker {
+    /// Record the duration of applying the entry of this proposal.
+    pub fn record_apply(&self, elapsed: Duration) {
+        self.apply_micros.store(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn apply_duration(&self) -> Duration {
+        Duration::from_micros(self.apply_micros.load(Ordering::Relaxed))
+    }
+}
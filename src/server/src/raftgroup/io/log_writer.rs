@@ -39,8 +39,8 @@ struct LogRequest {
 type LogResponse = Result<(), String>;
 
 impl LogWriter {
-    pub fn new(max_io_batch_size: u64, engine: Arc<raft_engine::Engine>) -> LogWriter {
-        let (join_handle, sender) = start_log_writer(max_io_batch_size, engine);
+    pub fn new(max_io_batch_size: u64, sync: bool, engine: Arc<raft_engine::Engine>) -> LogWriter {
+        let (join_handle, sender) = start_log_writer(max_io_batch_size, sync, engine);
         LogWriter { sender, _inner: Arc::new(WriterInner { handle: Some(join_handle) }) }
     }
 
@@ -76,6 +76,7 @@ impl Drop for WriterInner {
 
 fn start_log_writer(
     max_io_batch_size: u64,
+    sync: bool,
     engine: Arc<raft_engine::Engine>,
 ) -> (std::thread::JoinHandle<()>, mpsc::Sender<LogRequest>) {
     // Each worker sends at most one request, 1024 is large enough.
@@ -84,7 +85,7 @@ fn start_log_writer(
         .name("log:writer".to_owned())
         .spawn(move || {
             futures::executor::block_on(async move {
-                log_writer_main(max_io_batch_size as usize, engine, receiver).await;
+                log_writer_main(max_io_batch_size as usize, sync, engine, receiver).await;
             })
         })
         .unwrap();
@@ -93,6 +94,7 @@ fn start_log_writer(
 
 async fn log_writer_main(
     max_io_batch_size: usize,
+    sync: bool,
     engine: Arc<raft_engine::Engine>,
     receiver: mpsc::Receiver<LogRequest>,
 ) {
@@ -116,7 +118,7 @@ async fn log_writer_main(
             senders.push(req.sender);
         }
 
-        match engine.write(&mut log_batch, false) {
+        match engine.write(&mut log_batch, sync) {
             Ok(_) => {
                 for sender in senders {
                     sender.send(Ok(())).unwrap_or_default();
@@ -17,5 +17,5 @@ mod purge;
 mod transport;
 
 pub use self::log_writer::LogWriter;
-pub use self::purge::start_purging_expired_files;
+pub(crate) use self::purge::start_purging_expired_files;
 pub use self::transport::*;
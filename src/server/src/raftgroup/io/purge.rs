@@ -17,13 +17,17 @@ use std::time::Duration;
 use log::{debug, warn};
 use sekas_runtime::JoinHandle;
 
+use crate::engine::LogPurgeFence;
 use crate::Result;
 
-pub fn start_purging_expired_files(engine: Arc<raft_engine::Engine>) -> JoinHandle<()> {
+pub(crate) fn start_purging_expired_files(
+    engine: Arc<raft_engine::Engine>,
+    fence: LogPurgeFence,
+) -> JoinHandle<()> {
     sekas_runtime::spawn(async move {
         loop {
             sekas_runtime::time::sleep(Duration::from_secs(10)).await;
-            match purge_expired_files(engine.clone()).await {
+            match purge_expired_files(engine.clone(), fence.clone()).await {
                 Err(e) => {
                     warn!("raft engine purge expired files: {e:?}")
                 }
@@ -40,6 +44,16 @@ pub fn start_purging_expired_files(engine: Arc<raft_engine::Engine>) -> JoinHand
 #[inline]
 async fn purge_expired_files(
     engine: Arc<raft_engine::Engine>,
+    fence: LogPurgeFence,
 ) -> Result<Vec<u64>, raft_engine::Error> {
-    sekas_runtime::spawn_blocking(move || engine.purge_expired_files()).await.unwrap()
+    sekas_runtime::spawn_blocking(move || {
+        // The log files are rewritten or removed by purging, skip it while a
+        // checkpoint is being created.
+        let Some(_fence) = fence.try_enter() else {
+            return Ok(vec![]);
+        };
+        engine.purge_expired_files()
+    })
+    .await
+    .unwrap()
 }
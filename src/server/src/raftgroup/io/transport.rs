@@ -12,15 +12,20 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use futures::channel::mpsc;
 use futures::StreamExt;
 use log::{debug, warn};
 use sekas_api::server::v1::{NodeDesc, ReplicaDesc};
+use sekas_client::ConnManager;
 use sekas_runtime::{JoinHandle, TaskGroup};
+use serde::Serialize;
 
 use crate::node::route_table::RaftRouteTable;
+use crate::raftgroup::metrics::*;
 use crate::raftgroup::RaftGroup;
 use crate::serverpb::v1::raft_client::RaftClient;
 use crate::serverpb::v1::{RaftMessage, SnapshotChunk, SnapshotRequest};
@@ -35,10 +40,49 @@ struct StreamingRequest {
 
 struct StreamingTask {
     resolver: Arc<dyn AddressResolver>,
+    conn_manager: ConnManager,
+    peers: Arc<PeerTracker>,
     raft_node: RaftGroup,
     request: StreamingRequest,
 }
 
+/// The number of consecutive failures before quarantining a peer.
+const QUARANTINE_THRESHOLD: u32 = 3;
+const MIN_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The status of a peer node, for diagnosis.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerStatus {
+    pub node_id: u64,
+    pub num_connects: u64,
+    pub num_failures: u64,
+    pub consecutive_failures: u32,
+    pub quarantined: bool,
+    /// The milliseconds before the next probe, if the peer is quarantined.
+    pub next_probe_ms: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct PeerState {
+    num_connects: u64,
+    num_failures: u64,
+    consecutive_failures: u32,
+    probe_interval: Duration,
+    quarantined_until: Option<Instant>,
+    probing: bool,
+    last_error: Option<String>,
+}
+
+/// Track the send failures of peers. A peer which consistently fails is
+/// quarantined, the connections to it fail fast, except a probe issued with
+/// exponential backoff intervals.
+#[derive(Default)]
+struct PeerTracker {
+    peers: Mutex<HashMap<u64, PeerState>>,
+}
+
 /// An abstraction for resolving address by node id.
 #[crate::async_trait]
 pub trait AddressResolver: Send + Sync {
@@ -61,6 +105,8 @@ where
     Self: Send + Sync,
 {
     resolver: Arc<dyn AddressResolver>,
+    conn_manager: ConnManager,
+    peers: Arc<PeerTracker>,
     sender: mpsc::UnboundedSender<StreamingRequest>,
     _handle: JoinHandle<()>,
 }
@@ -96,13 +142,25 @@ impl Channel {
 }
 
 impl ChannelManager {
-    pub fn new(resolver: Arc<dyn AddressResolver>, route_table: RaftRouteTable) -> Self {
+    pub fn new(
+        resolver: Arc<dyn AddressResolver>,
+        route_table: RaftRouteTable,
+        conn_manager: ConnManager,
+    ) -> Self {
         let (sender, receiver) = mpsc::unbounded();
         let resolver_clone = resolver.clone();
+        let conn_manager_clone = conn_manager.clone();
+        let peers = Arc::new(PeerTracker::default());
+        let peers_clone = peers.clone();
         let handle = sekas_runtime::spawn(async move {
-            Self::run(resolver_clone, route_table, receiver).await;
+            Self::run(resolver_clone, conn_manager_clone, peers_clone, route_table, receiver).await;
         });
-        ChannelManager { resolver, sender, _handle: handle }
+        ChannelManager { resolver, conn_manager, peers, sender, _handle: handle }
+    }
+
+    /// Return the status of peers, sorted by node id.
+    pub fn peer_statuses(&self) -> Vec<PeerStatus> {
+        self.peers.statuses(Instant::now())
     }
 
     #[inline]
@@ -114,6 +172,8 @@ impl ChannelManager {
 
     async fn run(
         resolver: Arc<dyn AddressResolver>,
+        conn_manager: ConnManager,
+        peers: Arc<PeerTracker>,
         route_table: RaftRouteTable,
         mut receiver: mpsc::UnboundedReceiver<StreamingRequest>,
     ) {
@@ -130,7 +190,13 @@ impl ChannelManager {
                 }
             };
 
-            let task = StreamingTask { resolver: resolver.clone(), raft_node, request };
+            let task = StreamingTask {
+                resolver: resolver.clone(),
+                conn_manager: conn_manager.clone(),
+                peers: peers.clone(),
+                raft_node,
+                request,
+            };
             let handle = sekas_runtime::spawn(async move {
                 task.run().await;
             });
@@ -142,8 +208,17 @@ impl ChannelManager {
 impl StreamingTask {
     async fn run(self) {
         let target_id = self.request.to.id;
+        let node_id = self.request.to.node_id;
         let raft_node = self.raft_node.clone();
-        if self.serve_streaming_request().await.is_err() {
+        if !self.peers.try_connect(node_id, Instant::now()) {
+            debug!("node {node_id} is quarantined, skip sending messages to replica {target_id}");
+            raft_node.report_unreachable(target_id);
+            return;
+        }
+
+        let peers = self.peers.clone();
+        if let Err(err) = self.serve_streaming_request().await {
+            peers.on_failure(node_id, err.to_string(), Instant::now());
             raft_node.report_unreachable(target_id);
         }
     }
@@ -152,29 +227,139 @@ impl StreamingTask {
         let target_id = self.request.to.id;
         let from_id = self.request.from.id;
         let node_id = self.request.to.node_id;
+        let node_label = node_id.to_string();
         let node_desc = resolve_address(&*self.resolver, self.request.to.node_id).await?;
-        let address = format!("http://{}", node_desc.addr);
-        let mut client = RaftClient::connect(address).await?;
-        if let Err(e) = client.send_message(self.request.receiver).await {
+        let start = Instant::now();
+        let channel = self.conn_manager.connect(&node_desc.addr).await?;
+        let interceptor = self.conn_manager.interceptor().clone();
+        let mut client = RaftClient::with_interceptor(channel, interceptor);
+        RAFTGROUP_TRANSPORT_CONNECT_DURATION_SECONDS
+            .with_label_values(&[&node_label])
+            .observe(elapsed_seconds(start));
+        self.peers.on_connected(node_id);
+
+        let send_message_total =
+            RAFTGROUP_TRANSPORT_SEND_MESSAGE_TOTAL.with_label_values(&[&node_label]);
+        let receiver = self.request.receiver.inspect(move |_| send_message_total.inc());
+        #[cfg(feature = "failpoints")]
+        let receiver = inject_network_faults(self.request.from.node_id, node_id, receiver);
+        if let Err(e) = client.send_message(receiver).await {
             warn!("serve request to node {node_id} replica {target_id} from {from_id}: {e:?}");
+            self.peers.on_failure(node_id, e.to_string(), Instant::now());
         }
         Ok(())
     }
 }
 
+impl PeerTracker {
+    /// Return whether a connection to the peer is allowed. Only one probe is
+    /// allowed once the quarantine of the peer expired.
+    fn try_connect(&self, node_id: u64, now: Instant) -> bool {
+        let mut peers = self.peers.lock().unwrap();
+        let peer = peers.entry(node_id).or_default();
+        match peer.quarantined_until {
+            None => true,
+            Some(_) if peer.probing => false,
+            Some(until) if until <= now => {
+                peer.probing = true;
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    fn on_connected(&self, node_id: u64) {
+        let mut peers = self.peers.lock().unwrap();
+        let peer = peers.entry(node_id).or_default();
+        if peer.quarantined_until.is_some() {
+            debug!("node {node_id} is reachable again, lift the quarantine");
+        }
+        peer.num_connects += 1;
+        peer.consecutive_failures = 0;
+        peer.probe_interval = Duration::ZERO;
+        peer.quarantined_until = None;
+        peer.probing = false;
+    }
+
+    fn on_failure(&self, node_id: u64, err: String, now: Instant) {
+        RAFTGROUP_TRANSPORT_SEND_FAILURE_TOTAL.with_label_values(&[&node_id.to_string()]).inc();
+
+        let mut peers = self.peers.lock().unwrap();
+        let peer = peers.entry(node_id).or_default();
+        peer.num_failures += 1;
+        peer.consecutive_failures += 1;
+        peer.probing = false;
+        peer.last_error = Some(err);
+        if peer.consecutive_failures >= QUARANTINE_THRESHOLD {
+            peer.probe_interval = if peer.probe_interval.is_zero() {
+                RAFTGROUP_TRANSPORT_QUARANTINE_TOTAL
+                    .with_label_values(&[&node_id.to_string()])
+                    .inc();
+                warn!(
+                    "quarantine node {node_id} since it fails {} times consecutively: {:?}",
+                    peer.consecutive_failures, peer.last_error
+                );
+                MIN_PROBE_INTERVAL
+            } else {
+                std::cmp::min(peer.probe_interval * 2, MAX_PROBE_INTERVAL)
+            };
+            peer.quarantined_until = Some(now + peer.probe_interval);
+        }
+    }
+
+    fn statuses(&self, now: Instant) -> Vec<PeerStatus> {
+        let peers = self.peers.lock().unwrap();
+        let mut statuses = peers
+            .iter()
+            .map(|(node_id, peer)| PeerStatus {
+                node_id: *node_id,
+                num_connects: peer.num_connects,
+                num_failures: peer.num_failures,
+                consecutive_failures: peer.consecutive_failures,
+                quarantined: peer.quarantined_until.is_some(),
+                next_probe_ms: peer
+                    .quarantined_until
+                    .map(|until| until.saturating_duration_since(now).as_millis() as u64)
+                    .unwrap_or_default(),
+                last_error: peer.last_error.clone(),
+            })
+            .collect::<Vec<_>>();
+        statuses.sort_unstable_by_key(|s| s.node_id);
+        statuses
+    }
+}
+
 pub async fn retrive_snapshot(
     trans_mgr: &ChannelManager,
     target_replica: ReplicaDesc,
     snapshot_id: Vec<u8>,
 ) -> Result<impl futures::Stream<Item = Result<SnapshotChunk, tonic::Status>>> {
     let node_desc = resolve_address(&*trans_mgr.resolver, target_replica.node_id).await?;
-    let address = format!("http://{}", node_desc.addr);
-    let mut client = RaftClient::connect(address).await?;
+    let channel = trans_mgr.conn_manager.connect(&node_desc.addr).await?;
+    let interceptor = trans_mgr.conn_manager.interceptor().clone();
+    let mut client = RaftClient::with_interceptor(channel, interceptor);
     let request = SnapshotRequest { replica_id: target_replica.id, snapshot_id };
     let resp = client.retrieve_snapshot(request).await?;
     Ok(resp.into_inner())
 }
 
+/// Drop or delay the messages by the network faults between the nodes, see
+/// [`crate::failpoint::set_network_fault`].
+#[cfg(feature = "failpoints")]
+fn inject_network_faults(
+    from: u64,
+    to: u64,
+    receiver: impl futures::Stream<Item = RaftMessage> + Send + 'static,
+) -> impl futures::Stream<Item = RaftMessage> + Send + 'static {
+    receiver.filter_map(move |msg| async move {
+        let fault = crate::failpoint::network_fault(from, to);
+        if !fault.latency.is_zero() {
+            sekas_runtime::time::sleep(fault.latency).await;
+        }
+        (!fault.partitioned).then_some(msg)
+    })
+}
+
 async fn resolve_address(resolver: &dyn AddressResolver, node_id: u64) -> Result<NodeDesc> {
     let mut count = 0;
     loop {
@@ -190,3 +375,46 @@ async fn resolve_address(resolver: &dyn AddressResolver, node_id: u64) -> Result
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_quarantine_and_probe() {
+        let peers = PeerTracker::default();
+        let now = Instant::now();
+        assert!(peers.try_connect(1, now));
+        for _ in 0..QUARANTINE_THRESHOLD {
+            assert!(peers.try_connect(1, now));
+            peers.on_failure(1, "unreachable".to_owned(), now);
+        }
+
+        // The peer is quarantined.
+        assert!(!peers.try_connect(1, now));
+        let statuses = peers.statuses(now);
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].quarantined);
+        assert_eq!(statuses[0].num_failures, QUARANTINE_THRESHOLD as u64);
+        assert_eq!(statuses[0].next_probe_ms, MIN_PROBE_INTERVAL.as_millis() as u64);
+
+        // Only one probe is allowed after the quarantine expired.
+        let now = now + MIN_PROBE_INTERVAL;
+        assert!(peers.try_connect(1, now));
+        assert!(!peers.try_connect(1, now));
+
+        // The probe interval is doubled if the probe failed.
+        peers.on_failure(1, "unreachable".to_owned(), now);
+        assert!(!peers.try_connect(1, now + MIN_PROBE_INTERVAL));
+        let now = now + MIN_PROBE_INTERVAL * 2;
+        assert!(peers.try_connect(1, now));
+
+        // The quarantine is lifted once connected.
+        peers.on_connected(1);
+        assert!(peers.try_connect(1, now));
+        assert!(peers.try_connect(1, now));
+        let statuses = peers.statuses(now);
+        assert!(!statuses[0].quarantined);
+        assert_eq!(statuses[0].consecutive_failures, 0);
+    }
+}
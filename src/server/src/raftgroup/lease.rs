@@ -0,0 +1,121 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use sekas_runtime::time::Instant;
+
+use crate::RaftConfig;
+
+/// The lease of a leader, it is renewed once the read index requests are
+/// confirmed by the majority.
+///
+/// With `check_quorum`, a follower doesn't vote for others in an election
+/// timeout after it receives messages from the leader, so that no new leader
+/// could be elected before the lease expires. The clock skew between nodes is
+/// excluded from the lease duration.
+pub(super) struct LeaderLease {
+    /// The duration of lease, `None` means the lease reads are disabled.
+    duration: Option<Duration>,
+    term: u64,
+    expired_at: Option<Instant>,
+    /// The term and start time of the read index requests in flight.
+    pending: HashMap<Vec<u8>, (u64, Instant)>,
+}
+
+impl LeaderLease {
+    pub fn new(cfg: &RaftConfig) -> Self {
+        LeaderLease {
+            duration: cfg.lease_duration(),
+            term: 0,
+            expired_at: None,
+            pending: HashMap::default(),
+        }
+    }
+
+    /// Record a read index request sent by the leader of the term.
+    pub fn on_read_index(&mut self, ctx: &[u8], term: u64, now: Instant) {
+        if self.duration.is_some() {
+            self.pending.insert(ctx.to_owned(), (term, now));
+        }
+    }
+
+    /// Renew the lease since a read index request is confirmed in the term.
+    pub fn on_read_state(&mut self, ctx: &[u8], term: u64) {
+        let Some(duration) = self.duration else { return };
+        let Some((start_term, start)) = self.pending.remove(ctx) else { return };
+        if start_term != term {
+            return;
+        }
+        let expired_at = start + duration;
+        if self.term != term || self.expired_at.map(|e| e < expired_at).unwrap_or(true) {
+            self.term = term;
+            self.expired_at = Some(expired_at);
+        }
+    }
+
+    /// Return whether the lease is held in the term.
+    pub fn is_valid(&self, term: u64, now: Instant) -> bool {
+        self.term == term && self.expired_at.map(|e| now < e).unwrap_or_default()
+    }
+
+    /// Drop the lease, eg. the leadership is changed or is transferring.
+    pub fn reset(&mut self) {
+        self.expired_at = None;
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renew_leader_lease() {
+        let cfg = RaftConfig {
+            tick_interval_ms: 100,
+            election_tick: 10,
+            enable_lease_read: true,
+            lease_clock_skew_ms: Some(200),
+            ..Default::default()
+        };
+        let mut lease = LeaderLease::new(&cfg);
+        let now = Instant::now();
+        assert!(!lease.is_valid(1, now));
+
+        lease.on_read_index(b"1", 1, now);
+        lease.on_read_state(b"1", 1);
+        assert!(lease.is_valid(1, now + Duration::from_millis(799)));
+        assert!(!lease.is_valid(1, now + Duration::from_millis(800)));
+        assert!(!lease.is_valid(2, now));
+
+        // The requests of staled term don't renew the lease.
+        lease.on_read_index(b"2", 1, now + Duration::from_millis(500));
+        lease.on_read_state(b"2", 2);
+        assert!(!lease.is_valid(2, now + Duration::from_millis(500)));
+
+        lease.reset();
+        assert!(!lease.is_valid(1, now));
+    }
+
+    #[test]
+    fn lease_read_disabled() {
+        let mut lease = LeaderLease::new(&RaftConfig::default());
+        let now = Instant::now();
+        lease.on_read_index(b"1", 1, now);
+        lease.on_read_state(b"1", 1);
+        assert!(!lease.is_valid(1, now));
+    }
+}
@@ -83,6 +83,11 @@ lazy_static! {
     )
     .unwrap();
     pub static ref RAFTGROUP_READ_TOTAL: ReadTotal = ReadTotal::from(&RAFTGROUP_READ_TOTAL_VEC);
+    pub static ref RAFTGROUP_READ_BY_LEASE_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_read_by_lease_total",
+        "The total of read index of raftgroup served by the leader lease",
+    )
+    .unwrap();
 }
 
 lazy_static! {
@@ -113,6 +118,11 @@ lazy_static! {
         "The total of unreachable of raftgroup",
     )
     .unwrap();
+    pub static ref RAFTGROUP_BUSY_REJECTED_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_busy_rejected_total",
+        "The total of writes rejected by the admission control of raftgroup",
+    )
+    .unwrap();
 }
 
 lazy_static! {
@@ -126,6 +136,11 @@ lazy_static! {
         "The total bytes of send snapshot of raftgroup",
     )
     .unwrap();
+    pub static ref RAFTGROUP_SEND_SNAPSHOT_THROTTLED_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_send_snapshot_throttled_total",
+        "The total of send snapshot of raftgroup rejected by the concurrent limit",
+    )
+    .unwrap();
 }
 
 lazy_static! {
@@ -231,6 +246,50 @@ lazy_static! {
         exponential_buckets(1.0, 1.8, 22).unwrap(),
     )
     .unwrap();
+    pub static ref RAFTGROUP_WORKER_PROPOSAL_BATCH_SIZE: Histogram = register_histogram!(
+        "raftgroup_worker_proposal_batch_size",
+        "The number of proposals merged into a single raft entry",
+        exponential_buckets(1.0, 1.8, 22).unwrap(),
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_READ_INDEX_BATCH_SIZE: Histogram = register_histogram!(
+        "raftgroup_read_index_batch_size",
+        "The number of read index requests confirmed by a single read index round",
+        exponential_buckets(1.0, 1.8, 22).unwrap(),
+    )
+    .unwrap();
+}
+
+// For raft transport, labeled by the target node.
+lazy_static! {
+    pub static ref RAFTGROUP_TRANSPORT_SEND_MESSAGE_TOTAL: IntCounterVec =
+        register_int_counter_vec!(
+            "raftgroup_transport_send_message_total",
+            "The total of messages sent by raft transport",
+            &["node"]
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_TRANSPORT_SEND_FAILURE_TOTAL: IntCounterVec =
+        register_int_counter_vec!(
+            "raftgroup_transport_send_failure_total",
+            "The total of send failures of raft transport",
+            &["node"]
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_TRANSPORT_QUARANTINE_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "raftgroup_transport_quarantine_total",
+        "The total of peer quarantines of raft transport",
+        &["node"]
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_TRANSPORT_CONNECT_DURATION_SECONDS: HistogramVec =
+        register_histogram_vec!(
+            "raftgroup_transport_connect_duration_seconds",
+            "The intervals of connecting peers of raft transport",
+            &["node"],
+            exponential_buckets(0.00005, 1.8, 26).unwrap()
+        )
+        .unwrap();
 }
 
 pub fn take_read_metrics(read_policy: ReadPolicy) -> &'static Histogram {
@@ -12,10 +12,13 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+mod admission;
 mod applier;
+mod batch;
 mod fsm;
 mod group;
 mod io;
+mod lease;
 mod metrics;
 mod monitor;
 mod node;
@@ -31,15 +34,20 @@ use raft::prelude::{
 use sekas_api::server::v1::*;
 use sekas_runtime::{JoinHandle, TaskGroup};
 
+pub use self::admission::RaftBacklog;
 pub use self::fsm::{ApplyEntry, SnapshotBuilder, StateMachine};
-pub use self::group::RaftGroup;
+pub use self::group::{ProposeStats, RaftGroup};
 use self::io::LogWriter;
-pub use self::io::{retrive_snapshot, AddressResolver, ChannelManager};
+pub use self::io::{retrive_snapshot, AddressResolver, ChannelManager, PeerStatus};
 pub use self::monitor::*;
 pub use self::snap::SnapManager;
-pub use self::storage::{destory as destory_storage, write_initial_state};
+pub use self::storage::{
+    check_integrity as check_storage_integrity, destory as destory_storage,
+    reset_for_unsafe_recovery, write_initial_state,
+};
 use self::worker::RaftWorker;
 pub use self::worker::{RaftGroupState, StateObserver};
+use crate::engine::LogPurgeFence;
 use crate::raftgroup::io::start_purging_expired_files;
 use crate::{RaftConfig, Result};
 
@@ -59,6 +67,7 @@ pub struct RaftManager {
     pub cfg: RaftConfig,
     engine: Arc<raft_engine::Engine>,
     log_writer: LogWriter,
+    log_purge_fence: LogPurgeFence,
     transport_mgr: Arc<ChannelManager>,
     snap_mgr: SnapManager,
     _task_handle: Option<JoinHandle<()>>,
@@ -68,14 +77,16 @@ impl RaftManager {
     pub(crate) async fn open(
         cfg: RaftConfig,
         engine: Arc<raft_engine::Engine>,
+        log_purge_fence: LogPurgeFence,
         snap_mgr: SnapManager,
         transport_mgr: Arc<ChannelManager>,
     ) -> Result<Self> {
-        let task_handle = start_purging_expired_files(engine.clone());
-        let log_writer = LogWriter::new(cfg.max_io_batch_size, engine.clone());
+        let task_handle = start_purging_expired_files(engine.clone(), log_purge_fence.clone());
+        let log_writer = LogWriter::new(cfg.max_io_batch_size, cfg.sync_log, engine.clone());
         Ok(RaftManager {
             cfg,
             engine,
+            log_purge_fence,
             transport_mgr,
             snap_mgr,
             log_writer,
@@ -93,11 +104,20 @@ impl RaftManager {
         &self.snap_mgr
     }
 
+    #[inline]
+    pub fn transport_manager(&self) -> &ChannelManager {
+        &self.transport_mgr
+    }
+
     #[inline]
     pub async fn list_groups(&self) -> Vec<u64> {
         self.engine.raft_groups()
     }
 
+    /// Start the raft group of the replica. The replica recovered after the
+    /// node restarts should `catch_up` with the leader before it is eligible
+    /// to be the leader.
+    #[allow(clippy::too_many_arguments)]
     pub async fn start_raft_group<M: 'static + StateMachine>(
         &self,
         group_id: u64,
@@ -106,10 +126,19 @@ impl RaftManager {
         state_machine: M,
         observer: Box<dyn StateObserver>,
         task_group: &TaskGroup,
+        catch_up: bool,
     ) -> Result<RaftGroup> {
-        let worker =
-            RaftWorker::open(group_id, replica_id, node_id, state_machine, self, observer).await?;
-        let raft_group = RaftGroup::open(worker.request_sender());
+        let worker = RaftWorker::open(
+            group_id,
+            replica_id,
+            node_id,
+            state_machine,
+            self,
+            observer,
+            catch_up,
+        )
+        .await?;
+        let raft_group = RaftGroup::open(worker.request_sender(), worker.backlog());
         let log_writer = self.log_writer.clone();
         let task_handle = sekas_runtime::spawn(async move {
             if let Err(err) = worker.run(log_writer).await {
@@ -13,15 +13,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use futures::channel::oneshot;
 use log::{info, trace};
 use raft::prelude::*;
 use raft::{ConfChangeI, StateRole, Storage as RaftStorage};
 use raft_engine::LogBatch;
 use sekas_api::server::v1::RaftRole;
+use sekas_runtime::time::Instant;
 
 use super::applier::{Applier, ReplicaCache};
+use super::batch::ReadIndexBatch;
 use super::fsm::StateMachine;
+use super::group::ProposalTracker;
+use super::lease::LeaderLease;
+use super::metrics::*;
 use super::monitor::{record_perf_point, AdvancePerfContext};
 use super::snap::apply::apply_snapshot;
 use super::storage::Storage;
@@ -64,11 +71,28 @@ pub struct RaftNode<M: StateMachine> {
     group_id: u64,
 
     lease_read_requests: Vec<oneshot::Sender<Result<()>>>,
-    read_index_requests: Vec<oneshot::Sender<Result<()>>>,
+    read_index_batch: ReadIndexBatch,
+    read_index_batch_delay: Duration,
+    max_read_index_batch_size: usize,
     read_states: Vec<ReadState>,
+    lease: LeaderLease,
 
     raw_node: RawNode<Storage>,
     applier: Applier<M>,
+
+    catch_up: Option<CatchUp>,
+}
+
+/// The startup phase of a replica recovered after the node restarts. The
+/// replica does not campaign or accept the leadership until its applied index
+/// catches up with the leader, to prevent a stale leader right after restart.
+struct CatchUp {
+    max_lag: u64,
+    election_tick: usize,
+    /// The ticks elapsed since the last message of the leader.
+    idle_ticks: usize,
+    /// The committed index carried by the appending messages of the leader.
+    leader_committed: Option<u64>,
 }
 
 impl<M> RaftNode<M>
@@ -80,6 +104,7 @@ where
         replica_id: u64,
         mgr: &RaftManager,
         state_machine: M,
+        catch_up: bool,
     ) -> Result<Self> {
         let mut applier = Applier::new(group_id, state_machine);
         try_apply_fresh_snapshot(replica_id, &mgr.snap_mgr, &mut applier).await;
@@ -88,6 +113,13 @@ where
         let applied = applier.flushed_index();
         let conf_state =
             super::conf_state_from_group_descriptor(&applier.mut_state_machine().descriptor());
+        // There is no leader to catch up with if the replica is the only voter.
+        let catch_up = (catch_up && conf_state.voters.len() > 1).then(|| CatchUp {
+            max_lag: cfg.startup_catch_up_lag(),
+            election_tick: cfg.election_tick,
+            idle_ticks: 0,
+            leader_committed: None,
+        });
         let mut storage = Storage::open(
             cfg,
             replica_id,
@@ -103,38 +135,56 @@ where
         Ok(RaftNode {
             group_id,
             lease_read_requests: Vec::default(),
-            read_index_requests: Vec::default(),
+            read_index_batch: ReadIndexBatch::default(),
+            read_index_batch_delay: cfg.read_index_batch_delay(),
+            max_read_index_batch_size: cfg.max_read_index_batch_size as usize,
             read_states: Vec::default(),
+            lease: LeaderLease::new(cfg),
             raw_node: RawNode::with_default_logger(&config, storage)?,
             applier,
+            catch_up,
         })
     }
 
+    /// Propose an entry, the senders of all proposals merged into this entry
+    /// are notified once it is applied.
     pub fn propose(
         &mut self,
         data: Vec<u8>,
         context: Vec<u8>,
-        sender: oneshot::Sender<Result<()>>,
+        senders: Vec<oneshot::Sender<Result<()>>>,
+        trackers: Vec<ProposalTracker>,
     ) {
-        if let Err(err) = self.check_proposal_early(false) {
-            sender.send(Err(err)).unwrap_or_default();
+        if self.check_proposal_early(false).is_err() {
+            for sender in senders {
+                sender.send(self.check_proposal_early(false)).unwrap_or_default();
+            }
             return;
         }
 
         if let Err(err) = self.raw_node.propose(context, data) {
             if matches!(err, raft::Error::ProposalDropped) {
-                sender
-                    .send(Err(Error::ServiceIsBusy(BusyReason::ProposalDropped)))
-                    .unwrap_or_default();
-            } else {
+                for sender in senders {
+                    sender
+                        .send(Err(Error::ServiceIsBusy(BusyReason::ProposalDropped)))
+                        .unwrap_or_default();
+                }
+            } else if senders.len() == 1 {
+                let sender = senders.into_iter().next().unwrap();
                 sender.send(Err(err.into())).unwrap_or_default();
+            } else {
+                // The error is not cloneable, so share it in the form of rpc error.
+                let err = sekas_api::server::v1::Error::from(Error::from(err));
+                for sender in senders {
+                    sender.send(Err(err.clone().into())).unwrap_or_default();
+                }
             }
             return;
         }
 
         let index = self.raw_node.raft.raft_log.last_index();
         let term = self.raw_node.raft.term;
-        self.applier.delegate_proposal_context(index, term, sender);
+        self.applier.delegate_proposal_context(index, term, senders, trackers);
     }
 
     pub fn propose_conf_change(
@@ -161,7 +211,7 @@ where
 
         let index = self.raw_node.raft.raft_log.last_index();
         let term = self.raw_node.raft.term;
-        self.applier.delegate_proposal_context(index, term, sender);
+        self.applier.delegate_proposal_context(index, term, vec![sender], vec![]);
     }
 
     pub fn check_proposal_early(&self, check_config_change: bool) -> Result<()> {
@@ -182,28 +232,77 @@ where
         self.lease_read_requests.push(sender);
     }
 
+    /// Read with a read index round trip, it is skipped if the leader lease is
+    /// held. The concurrent reads are batched to share a single round trip.
     #[inline]
     pub fn read_index(&mut self, sender: oneshot::Sender<Result<()>>) {
-        self.read_index_requests.push(sender);
+        if self.is_lease_valid(Instant::now()) {
+            RAFTGROUP_READ_BY_LEASE_TOTAL.inc();
+            self.lease_read_requests.push(sender);
+        } else {
+            self.read_index_batch.push(sender);
+        }
+    }
+
+    /// The instant that the batched read index requests should be issued.
+    #[inline]
+    pub fn read_index_deadline(&self) -> Option<Instant> {
+        self.read_index_batch.deadline(self.read_index_batch_delay)
     }
 
     #[inline]
     pub fn transfer_leader(&mut self, transferee: u64) {
+        // The transferee could be elected before the lease expires.
+        self.lease.reset();
         self.raw_node.transfer_leader(transferee);
     }
 
+    fn is_lease_valid(&self, now: Instant) -> bool {
+        let raft = &self.raw_node.raft;
+        raft.state == StateRole::Leader
+            && raft.lead_transferee.is_none()
+            && self.lease.is_valid(raft.term, now)
+    }
+
     #[inline]
     pub fn report_unreachable(&mut self, target_id: u64) {
         self.raw_node.report_unreachable(target_id);
     }
 
-    #[inline]
     pub fn tick(&mut self) {
+        if self.catch_up.is_some()
+            && self.raw_node.raft.state != StateRole::Leader
+            && !self.try_finish_catch_up()
+        {
+            // The election timeout never elapses without ticks, so the replica won't
+            // campaign.
+            return;
+        }
         self.raw_node.tick();
     }
 
-    #[inline]
     pub fn step(&mut self, msg: Message) -> Result<(), raft::Error> {
+        if let Some(catch_up) = self.catch_up.as_mut() {
+            match msg.get_msg_type() {
+                MessageType::MsgAppend => {
+                    catch_up.idle_ticks = 0;
+                    let committed = catch_up.leader_committed.unwrap_or_default();
+                    catch_up.leader_committed = Some(std::cmp::max(committed, msg.commit));
+                }
+                MessageType::MsgHeartbeat | MessageType::MsgSnapshot => {
+                    catch_up.idle_ticks = 0;
+                }
+                MessageType::MsgTimeoutNow => {
+                    info!(
+                        "group {} replica {} reject transferring leader, it is catching up",
+                        self.group_id, self.raw_node.raft.id
+                    );
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
         if msg.get_msg_type() == MessageType::MsgSnapStatus {
             self.raw_node.report_snapshot(
                 msg.from,
@@ -234,9 +333,17 @@ where
             }
         }
 
-        if !self.read_index_requests.is_empty() {
-            let requests = std::mem::take(&mut self.read_index_requests);
+        if self
+            .read_index_batch
+            .is_ready(self.read_index_batch_delay, self.max_read_index_batch_size)
+        {
+            let requests = self.read_index_batch.take();
+            RAFTGROUP_READ_INDEX_BATCH_SIZE.observe(requests.len() as f64);
             let read_state_ctx = self.applier.delegate_read_requests(requests);
+            let raft = &self.raw_node.raft;
+            if raft.state == StateRole::Leader && raft.lead_transferee.is_none() {
+                self.lease.on_read_index(&read_state_ctx, raft.term, Instant::now());
+            }
             self.raw_node.read_index(read_state_ctx);
         }
     }
@@ -263,6 +370,9 @@ where
         record_perf_point(&mut perf_ctx.take_ready);
         let mut ready = self.raw_node.ready();
         if let Some(ss) = ready.ss() {
+            if ss.raft_state != StateRole::Leader {
+                self.lease.reset();
+            }
             let state = match ss.raft_state {
                 StateRole::Candidate => RaftRole::Candidate,
                 StateRole::Follower => RaftRole::Follower,
@@ -337,6 +447,36 @@ where
         self.raw_node.raft.raft_log.committed
     }
 
+    /// Return the committed index to catch up with, `None` if the replica is
+    /// not in the startup catch up phase.
+    pub fn catch_up_target(&self) -> Option<u64> {
+        let catch_up = self.catch_up.as_ref()?;
+        Some(std::cmp::max(catch_up.leader_committed.unwrap_or_default(), self.committed_index()))
+    }
+
+    /// Finish the startup catch up phase if the applied lag is under the
+    /// threshold, or no leader is heard during an election timeout.
+    fn try_finish_catch_up(&mut self) -> bool {
+        let Some(target) = self.catch_up_target() else {
+            return true;
+        };
+        let applied = self.raw_node.raft.raft_log.applied;
+        let catch_up = self.catch_up.as_mut().unwrap();
+        catch_up.idle_ticks += 1;
+        let lag = target.saturating_sub(applied);
+        let caught_up = catch_up.leader_committed.is_some() && lag <= catch_up.max_lag;
+        if !caught_up && catch_up.idle_ticks < catch_up.election_tick {
+            return false;
+        }
+
+        info!(
+            "group {} replica {} finish catching up, applied {applied} lag {lag}",
+            self.group_id, self.raw_node.raft.id,
+        );
+        self.catch_up = None;
+        true
+    }
+
     fn handle_apply(
         &mut self,
         perf_ctx: &mut AdvancePerfContext,
@@ -348,6 +488,12 @@ where
         }
 
         if !ready.read_states().is_empty() {
+            if self.raw_node.raft.state == StateRole::Leader {
+                let term = self.raw_node.raft.term;
+                for read_state in ready.read_states() {
+                    self.lease.on_read_state(&read_state.request_ctx, term);
+                }
+            }
             self.applier.apply_read_states(ready.take_read_states());
         }
 
@@ -501,6 +647,7 @@ mod tests {
     use sekas_runtime::ExecutorOwner;
 
     use super::*;
+    use crate::engine::LogPurgeFence;
     use crate::node::RaftRouteTable;
     use crate::raftgroup::io::LogWriter;
     use crate::raftgroup::{write_initial_state, AddressResolver, ChannelManager};
@@ -825,14 +972,19 @@ mod tests {
             let snap_dir = dir.path().join("snap");
             let snap_mgr = SnapManager::new(snap_dir.clone());
             let resolver = Arc::new(MockedAddressResolver {});
-            let transport_mgr = Arc::new(ChannelManager::new(resolver, RaftRouteTable::new()));
-            let log_writer = LogWriter::new(64 << 10, engine.clone());
+            let transport_mgr = Arc::new(ChannelManager::new(
+                resolver,
+                RaftRouteTable::new(),
+                sekas_client::ConnManager::new(),
+            ));
+            let log_writer = LogWriter::new(64 << 10, false, engine.clone());
             let raft_mgr = RaftManager {
                 cfg: RaftConfig::default(),
                 engine: engine.clone(),
                 transport_mgr,
                 snap_mgr: snap_mgr.clone(),
                 log_writer,
+                log_purge_fence: LogPurgeFence::default(),
                 _task_handle: None,
             };
 
@@ -853,7 +1005,7 @@ mod tests {
 
             // 3. recover node from snapshot. and apply all entries.
             let state_machine = CheckIndexStateMachine { flushed_index: 0 };
-            let mut node = RaftNode::new(1, 1, &raft_mgr, state_machine).await.unwrap();
+            let mut node = RaftNode::new(1, 1, &raft_mgr, state_machine, false).await.unwrap();
             assert_eq!(node.mut_state_machine().flushed_index(), 50);
             node.raw_node.campaign().unwrap();
 
@@ -896,4 +1048,134 @@ mod tests {
             assert!(node.mut_state_machine().flushed_index() >= 100);
         });
     }
+
+    #[test]
+    fn catch_up_after_restart() {
+        struct MockedAddressResolver {}
+
+        #[crate::async_trait]
+        impl AddressResolver for MockedAddressResolver {
+            async fn resolve(&self, _: u64) -> crate::Result<NodeDesc> {
+                todo!()
+            }
+        }
+
+        struct TwoVotersStateMachine {}
+
+        impl StateMachine for TwoVotersStateMachine {
+            fn start_plug(&mut self) -> crate::Result<()> {
+                Ok(())
+            }
+
+            fn apply(
+                &mut self,
+                _: u64,
+                _: u64,
+                _: crate::raftgroup::ApplyEntry,
+            ) -> crate::Result<()> {
+                Ok(())
+            }
+
+            fn finish_plug(&mut self) -> crate::Result<()> {
+                Ok(())
+            }
+
+            fn apply_snapshot(&mut self, _: &std::path::Path) -> crate::Result<()> {
+                Ok(())
+            }
+
+            fn snapshot_builder(&self) -> Box<dyn crate::raftgroup::SnapshotBuilder> {
+                todo!()
+            }
+
+            fn descriptor(&self) -> sekas_api::server::v1::GroupDesc {
+                let voter =
+                    |id| ReplicaDesc { id, role: ReplicaRole::Voter as i32, ..Default::default() };
+                GroupDesc { id: 1, epoch: 1, shards: vec![], replicas: vec![voter(1), voter(2)] }
+            }
+
+            fn flushed_index(&self) -> u64 {
+                0
+            }
+        }
+
+        fn leader_msg(
+            node: &RaftNode<TwoVotersStateMachine>,
+            msg_type: MessageType,
+            commit: u64,
+        ) -> Message {
+            let mut msg = Message::default();
+            msg.set_msg_type(msg_type);
+            msg.from = 2;
+            msg.to = 1;
+            msg.term = node.raft().term;
+            msg.commit = commit;
+            msg
+        }
+
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async {
+            use raft_engine::Config;
+
+            let dir = tempdir::TempDir::new("raftgroup-catch-up-after-restart").unwrap();
+            let cfg = Config {
+                dir: dir.path().join("db").to_str().unwrap().to_owned(),
+                ..Default::default()
+            };
+            let engine = Arc::new(Engine::open(cfg).unwrap());
+            let snap_mgr = SnapManager::new(dir.path().join("snap"));
+            let transport_mgr = Arc::new(ChannelManager::new(
+                Arc::new(MockedAddressResolver {}),
+                RaftRouteTable::new(),
+                sekas_client::ConnManager::new(),
+            ));
+            let log_writer = LogWriter::new(64 << 10, false, engine.clone());
+            let raft_mgr = RaftManager {
+                cfg: RaftConfig::default(),
+                engine: engine.clone(),
+                transport_mgr,
+                snap_mgr,
+                log_writer,
+                log_purge_fence: LogPurgeFence::default(),
+                _task_handle: None,
+            };
+            let replicas = vec![
+                ReplicaDesc { id: 1, ..Default::default() },
+                ReplicaDesc { id: 2, ..Default::default() },
+            ];
+            write_initial_state(&raft_mgr.cfg, engine.as_ref(), 1, replicas, vec![]).await.unwrap();
+
+            // 1. the replica neither campaigns nor accepts the leadership before catching
+            //    up.
+            let mut node =
+                RaftNode::new(1, 1, &raft_mgr, TwoVotersStateMachine {}, true).await.unwrap();
+            assert!(node.catch_up_target().is_some());
+            let far_ahead = node.committed_index() + raft_mgr.cfg.startup_catch_up_lag() + 1;
+            for _ in 0..raft_mgr.cfg.election_tick * 2 {
+                node.step(leader_msg(&node, MessageType::MsgAppend, far_ahead)).unwrap();
+                node.step(leader_msg(&node, MessageType::MsgTimeoutNow, 0)).unwrap();
+                node.tick();
+                assert_eq!(node.raft().state, StateRole::Follower);
+                assert_eq!(node.catch_up_target(), Some(far_ahead));
+            }
+
+            // 2. the catching up is finished if the leader is not heard in an election
+            //    timeout.
+            for _ in 1..raft_mgr.cfg.election_tick {
+                node.tick();
+                assert!(node.catch_up_target().is_some());
+            }
+            node.tick();
+            assert!(node.catch_up_target().is_none());
+            drop(node);
+
+            // 3. the catching up is finished once the lag is under the threshold.
+            let mut node =
+                RaftNode::new(1, 1, &raft_mgr, TwoVotersStateMachine {}, true).await.unwrap();
+            let committed = node.committed_index();
+            node.step(leader_msg(&node, MessageType::MsgAppend, committed)).unwrap();
+            node.tick();
+            assert!(node.catch_up_target().is_none());
+        });
+    }
 }
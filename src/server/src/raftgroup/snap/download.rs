@@ -192,6 +192,7 @@ async fn download_snap(
     assert!(msg.has_snapshot() && !msg.get_snapshot().is_empty());
     let snapshot = msg.get_snapshot();
     let snapshot_id = snapshot.data.clone();
+    let _permit = snap_mgr.throttle().acquire_receiving();
     let chunk_stream = retrive_snapshot(&tran_mgr, from_replica, snapshot_id).await?;
     save_snapshot(&snap_mgr, replica_id, chunk_stream).await
 }
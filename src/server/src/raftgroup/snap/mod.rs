@@ -17,6 +17,7 @@ pub mod apply;
 pub mod create;
 pub mod download;
 pub mod send;
+pub mod throttle;
 
 use std::collections::HashMap;
 use std::ffi::OsStr;
@@ -32,8 +33,9 @@ use sekas_runtime::JoinHandle;
 
 pub use self::create::dispatch_creating_snap_task;
 pub use self::download::dispatch_downloading_snap_task;
+pub use self::throttle::{SnapshotPermit, SnapshotThrottle};
 use crate::serverpb::v1::SnapshotMeta;
-use crate::Result;
+use crate::{RaftConfig, Result};
 
 const SNAP_DATA: &str = "DATA";
 const SNAP_TEMP: &str = "TEMP";
@@ -82,6 +84,7 @@ where
 struct SnapManagerShared {
     root_dir: PathBuf,
     min_keep_intervals: Duration,
+    throttle: Arc<SnapshotThrottle>,
     _recycler_handle: Option<JoinHandle<()>>,
     inner: Mutex<SnapManagerInner>,
 }
@@ -99,13 +102,14 @@ impl SnapManager {
             shared: Arc::new(SnapManagerShared {
                 root_dir: dir,
                 min_keep_intervals: Duration::from_secs(0),
+                throttle: Arc::new(SnapshotThrottle::new(&RaftConfig::default())),
                 _recycler_handle: None,
                 inner: Mutex::new(SnapManagerInner { sender, replicas: HashMap::default() }),
             }),
         }
     }
 
-    pub async fn recovery<P: AsRef<Path>>(root_dir: P) -> Result<SnapManager> {
+    pub async fn recovery<P: AsRef<Path>>(root_dir: P, cfg: &RaftConfig) -> Result<SnapManager> {
         use prost::Message;
 
         let (mut sender, receiver) = mpsc::unbounded();
@@ -161,12 +165,19 @@ impl SnapManager {
             shared: Arc::new(SnapManagerShared {
                 root_dir: root_dir.to_owned(),
                 min_keep_intervals: Duration::from_secs(180),
+                throttle: Arc::new(SnapshotThrottle::new(cfg)),
                 _recycler_handle: Some(recycler_handle),
                 inner: Mutex::new(SnapManagerInner { sender, replicas }),
             }),
         })
     }
 
+    /// Return the throttle of the snapshots transferring.
+    #[inline]
+    pub fn throttle(&self) -> &Arc<SnapshotThrottle> {
+        &self.shared.throttle
+    }
+
     /// Mark group as creating, and return a dir to save snapshot.
     pub fn create(&self, replica_id: u64) -> PathBuf {
         let mut inner = self.shared.inner.lock().unwrap();
@@ -451,7 +462,8 @@ mod tests {
 
             let replica_id_1: u64 = 1;
             let replica_id_2: u64 = 2;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, &RaftConfig::default()).await.unwrap();
 
             let snap_id_1 = build_snapshot(&snap_manager, replica_id_1, 1, vec![1]).await;
             let snap_id_2 = build_snapshot(&snap_manager, replica_id_1, 2, vec![2]).await;
@@ -460,7 +472,8 @@ mod tests {
 
             drop(snap_manager);
 
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, &RaftConfig::default()).await.unwrap();
             for snap_id in &replica_snaps_1 {
                 assert!(
                     snap_manager.lock_snap(replica_id_1, snap_id.as_slice()).is_some(),
@@ -485,7 +498,8 @@ mod tests {
             std::fs::create_dir_all(&root_dir).unwrap();
 
             let replica_id: u64 = 1;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, &RaftConfig::default()).await.unwrap();
 
             // Prepare snapshot
             let content = vec![1, 2, 3, 4, 5, 6, 7];
@@ -521,7 +535,8 @@ mod tests {
             std::fs::create_dir_all(&root_dir).unwrap();
 
             let replica_id: u64 = 1;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, &RaftConfig::default()).await.unwrap();
 
             // Prepare snapshot
             let content_1 = vec![1, 2, 3, 4, 5, 6, 7, 1];
@@ -13,14 +13,16 @@
 // limitations under the License.
 use std::ffi::OsStr;
 use std::fs::File;
+use std::future::Future;
 use std::io::Read;
 use std::os::unix::ffi::OsStrExt;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use log::debug;
 
-use super::{SnapManager, SnapshotGuard};
+use super::{SnapManager, SnapshotGuard, SnapshotPermit, SnapshotThrottle};
 use crate::raftgroup::metrics::*;
 use crate::serverpb::v1::{snapshot_chunk, SnapshotChunk};
 use crate::{Error, Result};
@@ -31,6 +33,10 @@ pub struct SnapshotChunkStream {
     info: SnapshotGuard,
     file: Option<File>,
     file_index: usize,
+    throttle: Arc<SnapshotThrottle>,
+    /// Delay the next chunk to limit the bandwidth.
+    delay: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    _permit: SnapshotPermit,
 }
 
 pub async fn send_snapshot(
@@ -38,6 +44,11 @@ pub async fn send_snapshot(
     replica_id: u64,
     snapshot_id: Vec<u8>,
 ) -> Result<SnapshotChunkStream> {
+    let throttle = snap_mgr.throttle().clone();
+    let Some(permit) = throttle.try_acquire_sending() else {
+        RAFTGROUP_SEND_SNAPSHOT_THROTTLED_TOTAL.inc();
+        return Err(Error::ResourceExhausted("too many outgoing snapshots".to_string()));
+    };
     let snapshot_info = match snap_mgr.lock_snap(replica_id, &snapshot_id) {
         Some(snap_info) => snap_info,
         None => {
@@ -46,12 +57,19 @@ pub async fn send_snapshot(
     };
 
     RAFTGROUP_SEND_SNAPSHOT_TOTAL.inc();
-    Ok(SnapshotChunkStream::new(snapshot_info))
+    Ok(SnapshotChunkStream::new(snapshot_info, throttle, permit))
 }
 
 impl SnapshotChunkStream {
-    fn new(info: SnapshotGuard) -> Self {
-        SnapshotChunkStream { info, file: None, file_index: 0 }
+    fn new(info: SnapshotGuard, throttle: Arc<SnapshotThrottle>, permit: SnapshotPermit) -> Self {
+        SnapshotChunkStream {
+            info,
+            file: None,
+            file_index: 0,
+            throttle,
+            delay: None,
+            _permit: permit,
+        }
     }
 
     fn next_chunk(&mut self) -> Option<SnapResult> {
@@ -81,6 +99,9 @@ impl SnapshotChunkStream {
                 }
                 chunk_data.truncate(num_read);
                 RAFTGROUP_SEND_SNAPSHOT_BYTES_TOTAL.inc_by(num_read as u64);
+                if let Some(wait) = self.throttle.consume(num_read) {
+                    self.delay = Some(Box::pin(sekas_runtime::time::sleep(wait)));
+                }
                 let value = snapshot_chunk::Value::ChunkData(chunk_data);
                 Some(Ok(SnapshotChunk { value: Some(value) }))
             }
@@ -116,7 +137,12 @@ impl SnapshotChunkStream {
 impl futures::Stream for SnapshotChunkStream {
     type Item = SnapResult;
 
-    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        Poll::Ready(self.get_mut().next_chunk())
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(delay) = this.delay.as_mut() {
+            futures::ready!(delay.as_mut().poll(cx));
+            this.delay = None;
+        }
+        Poll::Ready(this.next_chunk())
     }
 }
@@ -0,0 +1,160 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::RaftConfig;
+
+/// Limit the concurrent outgoing snapshots and the bandwidth used to send
+/// them, so that a node catching up doesn't saturate the network of peers.
+pub struct SnapshotThrottle {
+    /// The limit number of concurrent outgoing snapshots, 0 means no limit.
+    max_sending: usize,
+    /// The limit bytes per second of outgoing snapshots, 0 means no limit.
+    bytes_per_sec: u64,
+    num_sending: AtomicUsize,
+    num_receiving: AtomicUsize,
+    bucket: Mutex<ByteBucket>,
+}
+
+/// Hold a slot of the concurrent snapshots, it is released when dropping.
+pub struct SnapshotPermit {
+    throttle: Arc<SnapshotThrottle>,
+    sending: bool,
+}
+
+struct ByteBucket {
+    /// The available bytes, it is negative if the bytes are borrowed from the
+    /// future.
+    bytes: f64,
+    last_refill: Instant,
+}
+
+impl SnapshotThrottle {
+    pub fn new(cfg: &RaftConfig) -> Self {
+        SnapshotThrottle {
+            max_sending: cfg.max_sending_snapshots,
+            bytes_per_sec: cfg.snapshot_send_bytes_per_sec,
+            num_sending: AtomicUsize::new(0),
+            num_receiving: AtomicUsize::new(0),
+            bucket: Mutex::new(ByteBucket { bytes: 0.0, last_refill: Instant::now() }),
+        }
+    }
+
+    /// Acquire a permit to send a snapshot, return `None` if the number of
+    /// outgoing snapshots reaches the limit.
+    pub fn try_acquire_sending(self: &Arc<Self>) -> Option<SnapshotPermit> {
+        let acquired = self.num_sending.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            if self.max_sending == 0 || n < self.max_sending {
+                Some(n + 1)
+            } else {
+                None
+            }
+        });
+        acquired.ok().map(|_| SnapshotPermit { throttle: self.clone(), sending: true })
+    }
+
+    /// Acquire a permit to receive a snapshot, the incoming snapshots are only
+    /// throttled by the senders.
+    pub fn acquire_receiving(self: &Arc<Self>) -> SnapshotPermit {
+        self.num_receiving.fetch_add(1, Ordering::AcqRel);
+        SnapshotPermit { throttle: self.clone(), sending: false }
+    }
+
+    /// Return the number of snapshots in sending.
+    pub fn num_sending(&self) -> usize {
+        self.num_sending.load(Ordering::Acquire)
+    }
+
+    /// Return the number of snapshots in receiving.
+    pub fn num_receiving(&self) -> usize {
+        self.num_receiving.load(Ordering::Acquire)
+    }
+
+    /// Consume the bytes of an outgoing chunk, return the duration to wait
+    /// before sending it.
+    pub fn consume(&self, num_bytes: usize) -> Option<Duration> {
+        self.consume_at(num_bytes, Instant::now())
+    }
+
+    fn consume_at(&self, num_bytes: usize, now: Instant) -> Option<Duration> {
+        if self.bytes_per_sec == 0 {
+            return None;
+        }
+
+        let rate = self.bytes_per_sec as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+        bucket.bytes = f64::min(rate, bucket.bytes + elapsed * rate);
+        bucket.last_refill = now;
+        bucket.bytes -= num_bytes as f64;
+        if bucket.bytes < 0.0 {
+            Some(Duration::from_secs_f64(-bucket.bytes / rate))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for SnapshotPermit {
+    fn drop(&mut self) {
+        if self.sending {
+            self.throttle.num_sending.fetch_sub(1, Ordering::AcqRel);
+        } else {
+            self.throttle.num_receiving.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_concurrent_sending_snapshots() {
+        let cfg = RaftConfig { max_sending_snapshots: 2, ..Default::default() };
+        let throttle = Arc::new(SnapshotThrottle::new(&cfg));
+        let p1 = throttle.try_acquire_sending().unwrap();
+        let _p2 = throttle.try_acquire_sending().unwrap();
+        assert!(throttle.try_acquire_sending().is_none());
+        assert_eq!(throttle.num_sending(), 2);
+
+        let _r = throttle.acquire_receiving();
+        assert_eq!(throttle.num_receiving(), 1);
+        assert!(throttle.try_acquire_sending().is_none());
+
+        drop(p1);
+        assert_eq!(throttle.num_sending(), 1);
+        assert!(throttle.try_acquire_sending().is_some());
+    }
+
+    #[test]
+    fn limit_sending_bandwidth() {
+        let cfg = RaftConfig { snapshot_send_bytes_per_sec: 1000, ..Default::default() };
+        let throttle = SnapshotThrottle::new(&cfg);
+        let now = throttle.bucket.lock().unwrap().last_refill;
+        assert_eq!(throttle.consume_at(500, now), Some(Duration::from_millis(500)));
+        assert_eq!(throttle.consume_at(500, now), Some(Duration::from_secs(1)));
+
+        // The borrowed bytes are paid back.
+        let now = now + Duration::from_secs(2);
+        assert_eq!(throttle.consume_at(500, now), None);
+
+        // No limits.
+        let throttle = SnapshotThrottle::new(&RaftConfig::default());
+        assert_eq!(throttle.consume_at(usize::MAX, now), None);
+    }
+}
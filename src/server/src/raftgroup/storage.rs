@@ -546,6 +546,96 @@ pub async fn write_initial_state(
     Ok(())
 }
 
+/// Check the integrity of the raft states and log entries of the replica,
+/// without opening it. A description of the problem is returned if the
+/// storage is broken.
+pub fn check_integrity(
+    cfg: &RaftConfig,
+    engine: &Engine,
+    replica_id: u64,
+    mut applied_index: u64,
+) -> std::result::Result<(), String> {
+    let read_error = |e: raft_engine::Error| format!("read raft states: {e}");
+    if engine
+        .get_message::<HardState>(replica_id, keys::HARD_STATE_KEY)
+        .map_err(read_error)?
+        .is_none()
+    {
+        return Err("raft hard state not exists".to_owned());
+    }
+    let Some(local_state) = engine
+        .get_message::<RaftLocalState>(replica_id, keys::LOCAL_STATE_KEY)
+        .map_err(read_error)?
+    else {
+        return Err("raft local state not exists".to_owned());
+    };
+
+    let mut first_index = engine.first_index(replica_id).unwrap_or(1);
+    let mut last_index = engine.last_index(replica_id).unwrap_or(0);
+    if let Some(truncated) = local_state.last_truncated {
+        if first_index <= last_index {
+            if truncated.index + 1 != first_index {
+                return Err(format!(
+                    "some log entries are missing, truncated index {}, log range [{first_index}, {})",
+                    truncated.index,
+                    last_index + 1
+                ));
+            }
+        } else {
+            first_index = truncated.index + 1;
+            last_index = truncated.index;
+        }
+    }
+
+    if cfg.testing_knobs.force_new_peer_receiving_snapshot && applied_index == 0 {
+        applied_index = first_index.saturating_sub(1);
+    }
+    if last_index < applied_index {
+        return Err(format!(
+            "applied index {applied_index} is ahead of the last index {last_index} of raft log"
+        ));
+    }
+    if applied_index < last_index && applied_index + 1 < first_index {
+        return Err(format!(
+            "some unapplied log entries are missing, applied index {applied_index}, log range [{first_index}, {})",
+            last_index + 1
+        ));
+    }
+    Ok(())
+}
+
+/// Reset the raft states of the replica for unsafe recovery. All log entries
+/// are discarded, and the replica restarts from the applied entry with a
+/// higher term, so that it could campaign without receiving any messages from
+/// the lost replicas.
+pub async fn reset_for_unsafe_recovery(
+    engine: &Engine,
+    replica_id: u64,
+    applied: EntryId,
+) -> Result<()> {
+    let prev_term = engine
+        .get_message::<HardState>(replica_id, keys::HARD_STATE_KEY)?
+        .map(|hs| hs.term)
+        .unwrap_or_default();
+    let mut hard_state = HardState::default();
+    hard_state.term = std::cmp::max(prev_term, applied.term) + 1;
+    hard_state.commit = applied.index;
+    let local_state = RaftLocalState { replica_id, last_truncated: Some(applied.clone()) };
+
+    let mut batch = LogBatch::default();
+    batch.add_command(replica_id, Command::Clean);
+    batch.put_message(replica_id, keys::HARD_STATE_KEY.to_owned(), &hard_state).unwrap();
+    batch.put_message(replica_id, keys::LOCAL_STATE_KEY.to_owned(), &local_state).unwrap();
+    engine.write(&mut batch, true)?;
+
+    info!(
+        "reset raft states of {replica_id} for unsafe recovery, applied index {} term {}",
+        applied.index, applied.term
+    );
+
+    Ok(())
+}
+
 pub async fn destory(engine: &Engine, replica_id: u64) -> Result<()> {
     let mut batch = LogBatch::default();
     batch.add_command(replica_id, Command::Clean);
@@ -861,6 +951,29 @@ mod tests {
         });
     }
 
+    #[test]
+    fn check_raft_storage_integrity() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let dir = TempDir::new("check-raft-storage-integrity").unwrap();
+            let cfg = Config {
+                dir: dir.path().join("db").to_str().unwrap().to_owned(),
+                ..Default::default()
+            };
+            let engine = Engine::open(cfg).unwrap();
+            let raft_cfg = RaftConfig::default();
+            let replicas = vec![ReplicaDesc { id: 1, node_id: 1, ..Default::default() }];
+            write_initial_state(&raft_cfg, &engine, 1, replicas, vec![]).await.unwrap();
+
+            assert!(check_integrity(&raft_cfg, &engine, 1, 0).is_ok());
+            assert!(check_integrity(&raft_cfg, &engine, 1, 1).is_ok());
+            // The applied index is ahead of raft log.
+            assert!(check_integrity(&raft_cfg, &engine, 1, 2).is_err());
+            // The raft states are not exists.
+            assert!(check_integrity(&raft_cfg, &engine, 2, 0).is_err());
+        });
+    }
+
     #[test]
     fn raft_storage_snapshot() {
         let owner = ExecutorOwner::new(1);
@@ -30,8 +30,11 @@ use sekas_api::server::v1::{ChangeReplicas, RaftRole, ReplicaDesc};
 use sekas_runtime::TaskGroup;
 use tokio::time::{interval, Interval, MissedTickBehavior};
 
+use super::admission::RaftBacklog;
 use super::applier::{Applier, ReplicaCache};
+use super::batch::ProposalBatch;
 use super::fsm::StateMachine;
+use super::group::ProposalTracker;
 use super::io::{Channel, ChannelManager, LogWriter};
 use super::metrics::*;
 use super::monitor::WorkerPerfContext;
@@ -39,20 +42,40 @@ use super::node::RaftNode;
 use super::snap::apply::apply_snapshot;
 use super::snap::{RecycleSnapMode, SnapManager};
 use super::{RaftManager, ReadPolicy};
+use crate::engine::LogPurgeFence;
 use crate::raftgroup::monitor::record_perf_point;
 use crate::serverpb::v1::{EvalResult, RaftMessage};
 use crate::{record_latency, RaftConfig, Result};
 
 pub enum Request {
-    Read { policy: ReadPolicy, sender: oneshot::Sender<Result<()>> },
-    Propose { eval_result: EvalResult, start: Instant, sender: oneshot::Sender<Result<()>> },
+    Read {
+        policy: ReadPolicy,
+        sender: oneshot::Sender<Result<()>>,
+    },
+    Propose {
+        eval_result: EvalResult,
+        start: Instant,
+        sender: oneshot::Sender<Result<()>>,
+        tracker: ProposalTracker,
+    },
     CreateSnapshotFinished,
-    InstallSnapshot { msg: Message },
-    RejectSnapshot { msg: Message },
-    ChangeConfig { change: ChangeReplicas, sender: oneshot::Sender<Result<()>> },
-    Transfer { transferee: u64 },
+    InstallSnapshot {
+        msg: Message,
+    },
+    RejectSnapshot {
+        msg: Message,
+    },
+    ChangeConfig {
+        change: ChangeReplicas,
+        sender: oneshot::Sender<Result<()>>,
+    },
+    Transfer {
+        transferee: u64,
+    },
     Message(RaftMessage),
-    Unreachable { target_id: u64 },
+    Unreachable {
+        target_id: u64,
+    },
     State(oneshot::Sender<RaftGroupState>),
     Monitor(oneshot::Sender<Box<WorkerPerfContext>>),
     Start,
@@ -84,6 +107,9 @@ pub struct RaftGroupState {
     pub first_index: u64,
     /// The last index of log entries.
     pub last_index: u64,
+    /// The committed index to catch up with, if the replica is in the startup
+    /// catch up phase.
+    pub catch_up_target: Option<u64>,
 
     pub peers: HashMap<u64, PeerState>,
 }
@@ -169,8 +195,11 @@ where
     trans_mgr: Arc<ChannelManager>,
     snap_mgr: SnapManager,
     engine: Arc<Engine>,
+    log_purge_fence: LogPurgeFence,
     observer: Box<dyn StateObserver>,
     replica_cache: ReplicaCache,
+    proposal_batch: ProposalBatch,
+    backlog: Arc<RaftBacklog>,
 
     task_group: TaskGroup,
     marker: PhantomData<M>,
@@ -194,12 +223,14 @@ where
         state_machine: M,
         raft_mgr: &RaftManager,
         mut observer: Box<dyn StateObserver>,
+        catch_up: bool,
     ) -> Result<Self> {
         let desc = ReplicaDesc { id: replica_id, node_id, ..Default::default() };
         let mut replica_cache = ReplicaCache::default();
         replica_cache.insert(desc.clone());
         replica_cache.batch_insert(&state_machine.descriptor().replicas);
-        let raft_node = RaftNode::new(group_id, replica_id, raft_mgr, state_machine).await?;
+        let raft_node =
+            RaftNode::new(group_id, replica_id, raft_mgr, state_machine, catch_up).await?;
 
         let (mut request_sender, request_receiver) =
             mpsc::channel(raft_mgr.cfg.max_inflight_requests);
@@ -223,8 +254,11 @@ where
             trans_mgr: raft_mgr.transport_mgr.clone(),
             snap_mgr: raft_mgr.snap_mgr.clone(),
             engine: raft_mgr.engine.clone(),
+            log_purge_fence: raft_mgr.log_purge_fence.clone(),
             observer,
             replica_cache,
+            proposal_batch: ProposalBatch::default(),
+            backlog: Arc::new(RaftBacklog::new(&raft_mgr.cfg)),
             task_group: TaskGroup::default(),
             marker: PhantomData,
         })
@@ -235,6 +269,11 @@ where
         self.request_sender.clone()
     }
 
+    #[inline]
+    pub fn backlog(&self) -> Arc<RaftBacklog> {
+        self.backlog.clone()
+    }
+
     /// Poll requests and messages, forward both to `RaftNode`, and advance
     /// `RaftNode`.
     pub async fn run(mut self, log_writer: LogWriter) -> Result<()> {
@@ -263,6 +302,18 @@ where
         interval: &mut Interval,
     ) -> Result<()> {
         if !self.raft_node.has_ready() {
+            // Wake up to propose the batched proposals and issue the batched read index
+            // requests once they wait long enough.
+            // The batches are timed by the runtime clock, which is mocked in tests.
+            let now = sekas_runtime::time::Instant::now();
+            let batch_timeout = [
+                self.proposal_batch.deadline(self.cfg.proposal_batch_delay()),
+                self.raft_node.read_index_deadline(),
+            ]
+            .into_iter()
+            .flatten()
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(now));
             sekas_runtime::select! {
                 biased;
                 _ = interval.tick().fuse() => {
@@ -273,6 +324,8 @@ where
                         self.handle_request(ctx, req)?;
                     }
                 },
+                _ = sekas_runtime::time::sleep(batch_timeout.unwrap_or_default()).fuse(),
+                    if batch_timeout.is_some() => {},
             }
             record_perf_point(&mut ctx.perf_ctx.wake);
         } else {
@@ -302,6 +355,12 @@ where
                 break;
             }
         }
+        if self
+            .proposal_batch
+            .is_ready(self.cfg.proposal_batch_delay(), self.cfg.max_proposal_batch_bytes as usize)
+        {
+            self.flush_proposal_batch(ctx);
+        }
         Ok(())
     }
 
@@ -350,6 +409,9 @@ where
     }
 
     fn finish_round(&self, mut ctx: WorkerContext) {
+        let raft_log = &self.raft_node.raft().raft_log;
+        self.backlog.update(raft_log.last_index(), raft_log.committed, raft_log.applied);
+
         record_perf_point(&mut ctx.perf_ctx.finish);
         ctx.perf_ctx.accumulated_bytes = ctx.accumulated_bytes;
         for sender in ctx.monitors {
@@ -360,15 +422,22 @@ where
     fn handle_request(&mut self, ctx: &mut WorkerContext, request: Request) -> Result<()> {
         ctx.perf_ctx.num_requests += 1;
         match request {
-            Request::Propose { eval_result, start, sender } => {
-                self.handle_proposal(ctx, eval_result, start, sender)
+            Request::Propose { eval_result, start, sender, tracker } => {
+                self.handle_proposal(ctx, eval_result, start, sender, tracker)
+            }
+            Request::Read { policy, sender } => {
+                self.flush_proposal_batch(ctx);
+                self.handle_read(policy, sender)
+            }
+            Request::ChangeConfig { change, sender } => {
+                self.flush_proposal_batch(ctx);
+                self.handle_conf_change(change, sender)
             }
-            Request::Read { policy, sender } => self.handle_read(policy, sender),
-            Request::ChangeConfig { change, sender } => self.handle_conf_change(change, sender),
             Request::CreateSnapshotFinished => {
                 self.raft_node.mut_store().is_creating_snapshot.set(false);
             }
             Request::Transfer { transferee: target_id } => {
+                self.flush_proposal_batch(ctx);
                 self.raft_node.transfer_leader(target_id);
             }
             Request::Message(msg) => {
@@ -444,14 +513,40 @@ where
         eval_result: EvalResult,
         start: Instant,
         sender: oneshot::Sender<Result<()>>,
+        tracker: ProposalTracker,
     ) {
         use prost::Message;
 
-        let data = eval_result.encode_to_vec();
-        ctx.accumulated_bytes += data.len();
         ctx.perf_ctx.num_proposal += 1;
-        self.raft_node.propose(data, vec![], sender);
         RAFTGROUP_WORKER_REQUEST_IN_QUEUE_DURATION_SECONDS.observe(elapsed_seconds(start));
+        let max_batch_bytes = self.cfg.max_proposal_batch_bytes as usize;
+        if max_batch_bytes > 0 && ProposalBatch::is_batchable(&eval_result) {
+            let size = eval_result.batch.as_ref().map(|b| b.data.len()).unwrap_or_default();
+            if !self.proposal_batch.is_empty()
+                && self.proposal_batch.size() + size > max_batch_bytes
+            {
+                self.flush_proposal_batch(ctx);
+            }
+            self.proposal_batch.push(eval_result, sender, tracker);
+            return;
+        }
+
+        // Keep the order of proposals.
+        self.flush_proposal_batch(ctx);
+        let data = eval_result.encode_to_vec();
+        ctx.accumulated_bytes += data.len();
+        self.raft_node.propose(data, vec![], vec![sender], vec![tracker]);
+    }
+
+    fn flush_proposal_batch(&mut self, ctx: &mut WorkerContext) {
+        use prost::Message;
+
+        if let Some((eval_result, senders, trackers)) = self.proposal_batch.take() {
+            RAFTGROUP_WORKER_PROPOSAL_BATCH_SIZE.observe(senders.len() as f64);
+            let data = eval_result.encode_to_vec();
+            ctx.accumulated_bytes += data.len();
+            self.raft_node.propose(data, vec![], senders, trackers);
+        }
     }
 
     fn handle_conf_change(&mut self, change: ChangeReplicas, sender: oneshot::Sender<Result<()>>) {
@@ -488,6 +583,10 @@ where
             }
         }
 
+        let Some(_fence) = self.log_purge_fence.try_enter() else {
+            // A checkpoint is being created, compact the logs later.
+            return;
+        };
         let store = self.raft_node.mut_store();
         if store.first_index().unwrap() < to {
             let mut lb = store.compact_to(to);
@@ -520,6 +619,7 @@ where
             committed: self.raft_node.committed_index(),
             first_index,
             last_index,
+            catch_up_target: self.raft_node.catch_up_target(),
             peers: peer_states,
         }
     }
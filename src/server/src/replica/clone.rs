@@ -0,0 +1,40 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::Replica;
+use crate::serverpb::v1::*;
+use crate::Result;
+
+/// The number of user keys of the clone source copied in one proposal.
+const REALIZE_CLONE_CHUNK_KEYS: usize = 1024;
+
+impl Replica {
+    /// Copy a chunk of the clone source of the shard from `start_key` into the
+    /// shard. Return the start key of the next chunk, `None` is returned once
+    /// the clone source is realized and released.
+    pub async fn realize_clone(&self, shard_id: u64, start_key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let _acl_guard = self.take_read_acl_guard().await;
+        let next_key =
+            self.group_engine.next_clone_chunk(shard_id, start_key, REALIZE_CLONE_CHUNK_KEYS)?;
+        let op = SyncOp::realize_clone(
+            shard_id,
+            start_key.to_owned(),
+            next_key.clone().unwrap_or_default(),
+            next_key.is_none(),
+        );
+        let eval_result = EvalResult { op: Some(op), ..Default::default() };
+        self.raft_group.propose(eval_result).await?;
+        Ok(next_key)
+    }
+}
@@ -18,7 +18,7 @@ use prost::Message;
 use sekas_api::server::v1::*;
 use sekas_schema::system::txn::TXN_INTENT_VERSION;
 
-use super::LatchManager;
+use super::{resolve_intent_for_read, LatchManager};
 use crate::engine::{GroupEngine, SnapshotMode};
 use crate::node::move_shard::ForwardCtx;
 use crate::replica::ExecCtx;
@@ -47,7 +47,11 @@ pub(crate) async fn get<T: LatchManager>(
         req.shard_id,
         req.start_version
     );
-    read_key(engine, latch_mgr, req.shard_id, &req.user_key, req.start_version).await
+    let value = read_key(engine, latch_mgr, req.shard_id, &req.user_key, req.start_version).await?;
+    if req.key_only {
+        return Ok(value.map(|v| Value { content: v.content.map(|_| Vec::new()), ..v }));
+    }
+    Ok(value)
 }
 
 async fn read_key<T: LatchManager>(
@@ -71,15 +75,23 @@ async fn read_key<T: LatchManager>(
                     )));
                 };
                 let intent = TxnIntent::decode(value)?;
-                if intent.start_version <= start_version {
-                    if let Some(value) = latch_mgr
-                        .resolve_txn(shard_id, key, start_version, intent.start_version)
-                        .await?
-                    {
-                        if value.version <= start_version {
-                            trace!("get return resolve txn intent, shard_id {}, value version: {}, start version: {}",
-                                    shard_id, value.version, start_version);
-                            return Ok(Some(value));
+                match resolve_intent_for_read(latch_mgr, &intent, start_version) {
+                    Some(Some(value)) => {
+                        trace!("get return finished txn intent without resolving, shard_id {}, value version: {}, start version: {}",
+                                shard_id, value.version, start_version);
+                        return Ok(Some(value));
+                    }
+                    Some(None) => {}
+                    None => {
+                        if let Some(value) = latch_mgr
+                            .resolve_txn(shard_id, key, start_version, intent.start_version)
+                            .await?
+                        {
+                            if value.version <= start_version {
+                                trace!("get return resolve txn intent, shard_id {}, value version: {}, start version: {}",
+                                        shard_id, value.version, start_version);
+                                return Ok(Some(value));
+                            }
                         }
                     }
                 }
@@ -147,7 +159,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, 1, key, value, *version).unwrap();
             } else {
@@ -198,6 +210,29 @@ mod tests {
         }
     }
 
+    #[sekas_macro::test]
+    async fn get_key_only() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = NopLatchManager::default();
+        commit_values(&engine, b"live", &[Value::with_value(vec![b'1'; 64], 1)]);
+        commit_values(&engine, b"deleted", &[Value::tombstone(1)]);
+
+        let exec_ctx = ExecCtx::default();
+        let get = |key: &[u8]| ShardGetRequest {
+            shard_id: 1,
+            start_version: 3,
+            user_key: key.to_vec(),
+            key_only: true,
+        };
+        let got = super::get(&exec_ctx, &engine, &latch_mgr, &get(b"live")).await.unwrap();
+        assert_eq!(got, Some(Value::with_value(vec![], 1)));
+        let got = super::get(&exec_ctx, &engine, &latch_mgr, &get(b"deleted")).await.unwrap();
+        assert_eq!(got, Some(Value::tombstone(1)));
+        let got = super::get(&exec_ctx, &engine, &latch_mgr, &get(b"missing")).await.unwrap();
+        assert_eq!(got, None);
+    }
+
     #[sekas_macro::test]
     async fn read_key_with_version_but_without_intent() {
         // read_key should return the first value in the target version, including
@@ -279,6 +314,74 @@ mod tests {
         }
     }
 
+    struct FinishedLatchManager {
+        state: (TxnState, u64),
+    }
+
+    impl LatchManager for FinishedLatchManager {
+        type Guard = NopLatchGuard;
+
+        async fn resolve_txn(
+            &self,
+            _shard_id: u64,
+            _user_key: &[u8],
+            _start_version: u64,
+            _intent_version: u64,
+        ) -> Result<Option<Value>> {
+            unreachable!("the finished txn should not be resolved")
+        }
+
+        async fn acquire(&self, _shard_id: u64, _user_key: &[u8]) -> Result<Self::Guard> {
+            todo!()
+        }
+
+        fn finished_txn_state(&self, _intent_version: u64) -> Option<(TxnState, u64)> {
+            Some(self.state)
+        }
+    }
+
+    #[sekas_macro::test]
+    async fn read_key_with_finished_intent() {
+        struct TestCase {
+            state: (TxnState, u64),
+            expect: Option<Value>,
+        }
+
+        let values = vec![Value::with_value(b"123".to_vec(), 122)];
+        let intent = TxnIntent::with_put(122, Some(b"124".to_vec()));
+        let cases = vec![
+            // case 1. intent is aborted
+            TestCase {
+                state: (TxnState::Aborted, 0),
+                expect: Some(Value::with_value(b"123".to_vec(), 122)),
+            },
+            // case 2. intent is committed, but value is not visible
+            TestCase {
+                state: (TxnState::Committed, 125),
+                expect: Some(Value::with_value(b"123".to_vec(), 122)),
+            },
+            // case 3. intent is committed, value is visible
+            TestCase {
+                state: (TxnState::Committed, 123),
+                expect: Some(Value::with_value(b"124".to_vec(), 123)),
+            },
+        ];
+
+        let txn_version = 123;
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        for (idx, TestCase { state, expect }) in cases.into_iter().enumerate() {
+            let key = idx.to_string();
+            let mut values = values.clone();
+            values.push(Value::with_value(intent.encode_to_vec(), TXN_INTENT_VERSION));
+            commit_values(&engine, key.as_bytes(), &values);
+
+            let latch_mgr = FinishedLatchManager { state };
+            let got = read_key(&engine, &latch_mgr, 1, key.as_bytes(), txn_version).await.unwrap();
+            assert_eq!(got, expect, "idx = {idx}");
+        }
+    }
+
     #[sekas_macro::test]
     async fn read_key_with_intent() {
         struct TestCase {
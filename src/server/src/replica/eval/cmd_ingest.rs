@@ -11,11 +11,18 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use sekas_api::server::v1::ValueSet;
+use std::path::Path;
 
-use crate::engine::{GroupEngine, WriteBatch};
-use crate::serverpb::v1::{EvalResult, WriteBatchRep};
-use crate::Result;
+use sekas_api::server::v1::{IngestFile, ShardDesc, ShardIngestRequest, ValueSet};
+use sekas_schema::shard;
+
+use super::check_writable;
+use super::key_schema::check_key;
+use crate::engine::{verify_ingest_file, GroupEngine, IngestStore, WriteBatch};
+use crate::error::BusyReason;
+use crate::replica::ExecCtx;
+use crate::serverpb::v1::{EvalResult, SyncOp, WriteBatchRep};
+use crate::{Error, Result};
 
 pub async fn ingest_value_set(
     engine: &GroupEngine,
@@ -26,6 +33,7 @@ pub async fn ingest_value_set(
     if value_set.values.is_empty() {
         return Ok(None);
     }
+    check_key(&engine.shard_desc(shard_id)?, &value_set.user_key)?;
 
     if engine.get(shard_id, &value_set.user_key).await?.is_some() {
         return Ok(None);
@@ -33,12 +41,40 @@ pub async fn ingest_value_set(
 
     let mut wb = WriteBatch::default();
     for value in &value_set.values {
-        if let Some(content) = value.content.as_ref() {
-            engine.put(&mut wb, shard_id, &value_set.user_key, content, value.version)?;
-        } else {
-            engine.tombstone(&mut wb, shard_id, &value_set.user_key, value.version)?;
+        engine.put_value(&mut wb, shard_id, &value_set.user_key, value)?;
+    }
+
+    let eval_result = EvalResult {
+        batch: Some(WriteBatchRep { data: wb.data().to_vec() }),
+        ..Default::default()
+    };
+    Ok(Some(eval_result))
+}
+
+/// Ingest a chunk of value sets in one write batch, the keys already exist are
+/// skipped. The row latches of all keys should be held.
+pub async fn ingest_value_sets(
+    engine: &GroupEngine,
+    shard_id: u64,
+    value_sets: &[ValueSet],
+) -> Result<Option<EvalResult>> {
+    let shard = engine.shard_desc(shard_id)?;
+    let mut wb = WriteBatch::default();
+    for value_set in value_sets {
+        if value_set.values.is_empty() {
+            continue;
+        }
+        check_key(&shard, &value_set.user_key)?;
+        if engine.get(shard_id, &value_set.user_key).await?.is_some() {
+            continue;
+        }
+        for value in &value_set.values {
+            engine.put_value(&mut wb, shard_id, &value_set.user_key, value)?;
         }
     }
+    if wb.is_empty() {
+        return Ok(None);
+    }
 
     let eval_result = EvalResult {
         batch: Some(WriteBatchRep { data: wb.data().to_vec() }),
@@ -47,6 +83,73 @@ pub async fn ingest_value_set(
     Ok(Some(eval_result))
 }
 
+/// Ingest the uploaded key/value files into the shard. The files are sealed
+/// under the token and validated locally before proposing, and ingested by all
+/// replicas when applying, so the files must be uploaded to all replicas, which
+/// is confirmed by the leader before proposing.
+pub(crate) async fn ingest_files(
+    exec_ctx: &ExecCtx,
+    engine: &GroupEngine,
+    ingest_store: &IngestStore,
+    req: &ShardIngestRequest,
+    token: &str,
+) -> Result<Option<EvalResult>> {
+    if let Some(desc) = exec_ctx.move_shard_desc.as_ref() {
+        if desc.shard_desc.as_ref().map(|s| s.id) == Some(req.shard_id) {
+            return Err(Error::ServiceIsBusy(BusyReason::Moving));
+        }
+    }
+
+    // Make sure the shard exists and is writable.
+    let shard = engine.shard_desc(req.shard_id)?;
+    check_writable(&shard)?;
+    if req.files.is_empty() {
+        return Ok(None);
+    }
+    if req.version == 0 {
+        return Err(Error::InvalidArgument("the version of ingested values is required".into()));
+    }
+    let ingest_store = ingest_store.clone();
+    let files = req.files.clone();
+    let sealed_token = token.to_owned();
+    sekas_runtime::spawn_blocking(move || {
+        let paths = ingest_store.seal(shard.id, &sealed_token, &files)?;
+        for (path, file) in paths.iter().zip(&files) {
+            check_ingest_file(&shard, path, file)?;
+        }
+        Ok::<_, Error>(())
+    })
+    .await??;
+
+    let op = SyncOp::ingest_files(req.shard_id, req.version, req.files.clone(), token.to_owned());
+    Ok(Some(EvalResult { op: Some(op), ..Default::default() }))
+}
+
+/// Check that the fingerprint of the ingest file is matched, the keys are
+/// strictly increasing, and belong to the shard and match its key schema.
+fn check_ingest_file(desc: &ShardDesc, path: &Path, file: &IngestFile) -> Result<()> {
+    let mut last_key: Option<Vec<u8>> = None;
+    verify_ingest_file(path, file, |key| {
+        if key.is_empty() || last_key.as_deref().map(|last| last >= key).unwrap_or_default() {
+            return Err(Error::InvalidData(format!(
+                "the keys of ingest file {} are not strictly increasing",
+                file.name
+            )));
+        }
+        if !shard::belong_to(desc, key) {
+            return Err(Error::InvalidArgument(format!(
+                "the key {key:?} of ingest file {} does not belong to shard {}",
+                file.name, desc.id
+            )));
+        }
+        check_key(desc, key)?;
+        let last = last_key.get_or_insert_with(Vec::new);
+        last.clear();
+        last.extend_from_slice(key);
+        Ok(())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use sekas_api::server::v1::Value;
@@ -75,4 +178,49 @@ mod tests {
         let result = ingest_value_set(&engine, SHARD_ID, &value_set).await.unwrap();
         assert!(result.is_none());
     }
+
+    #[test]
+    fn check_ingest_file_keys() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let path = dir.path().join("1.kv");
+        let desc = ShardDesc::with_range(SHARD_ID, 1, b"a".to_vec(), b"c".to_vec());
+        // Encode the records directly, since the builder rejects the unordered keys.
+        let check = |keys: &[&[u8]]| {
+            let mut content = vec![];
+            for key in keys {
+                content.extend_from_slice(&(key.len() as u32).to_le_bytes());
+                content.extend_from_slice(key);
+                content.extend_from_slice(&0u32.to_le_bytes());
+            }
+            std::fs::write(&path, &content).unwrap();
+            check_ingest_file(&desc, &path, &IngestFile::fingerprint("1.kv".to_owned(), &content))
+        };
+
+        assert!(check(&[b"a", b"b"]).is_ok());
+        assert!(check(&[b"b", b"a"]).is_err());
+        assert!(check(&[b"a", b"a"]).is_err());
+        assert!(check(&[b"a", b"c"]).is_err());
+
+        std::fs::write(&path, b"corrupted").unwrap();
+        let file = IngestFile::fingerprint("1.kv".to_owned(), b"corrupted");
+        assert!(check_ingest_file(&desc, &path, &file).is_err());
+    }
+
+    #[sekas_macro::test]
+    async fn cmd_ingest_value_sets_skip_existing_keys() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+
+        let value_sets = vec![
+            ValueSet { user_key: vec![1], values: vec![Value::tombstone(1)] },
+            ValueSet { user_key: vec![2], values: vec![] },
+        ];
+        let eval_result = ingest_value_sets(&engine, SHARD_ID, &value_sets).await.unwrap().unwrap();
+        let wb = WriteBatch::new(&eval_result.batch.unwrap().data);
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        let value_sets = vec![ValueSet { user_key: vec![1], values: vec![Value::tombstone(2)] }];
+        let result = ingest_value_sets(&engine, SHARD_ID, &value_sets).await.unwrap();
+        assert!(result.is_none());
+    }
 }
@@ -16,7 +16,7 @@ use prost::Message;
 use sekas_api::server::v1::*;
 use sekas_schema::system::txn::TXN_INTENT_VERSION;
 
-use super::LatchManager;
+use super::{resolve_intent_for_read, LatchManager};
 use crate::engine::{GroupEngine, MvccIterator, Snapshot, SnapshotMode};
 use crate::node::move_shard::ForwardCtx;
 use crate::replica::ExecCtx;
@@ -160,6 +160,7 @@ async fn scan_value_set<T: LatchManager>(
         }
 
         let value;
+        let (mut expire_at, mut num_chunks) = (entry.expire_at(), entry.num_chunks());
         if version == TXN_INTENT_VERSION && !req.ignore_txn_intent {
             let intent_value = entry.value().ok_or_else(|| {
                 Error::InvalidData(format!("the value of intent key {user_key:?} is not exists",))
@@ -167,7 +168,10 @@ async fn scan_value_set<T: LatchManager>(
             match resolve_txn(latch_mgr, req.shard_id, req.start_version, user_key, intent_value)
                 .await?
             {
-                Some(v) => (value, version) = v,
+                Some(v) => {
+                    (value, version) = (v.content, v.version);
+                    (expire_at, num_chunks) = (v.expire_at, v.num_chunks);
+                }
                 None => continue,
             }
         } else if req.start_version < version {
@@ -177,11 +181,26 @@ async fn scan_value_set<T: LatchManager>(
             value = entry.value().map(ToOwned::to_owned);
         }
 
-        if let Some(value) = value {
+        if req.min_version > 0 && version < req.min_version {
+            if values.is_empty() {
+                // The key is not changed since the min version.
+                return Ok(None);
+            }
+            if value.is_none() {
+                // The versions before the tombstone belong to the previous lifetime of the key.
+                break;
+            }
+        }
+
+        if let Some(mut value) = value {
+            if req.keys_only {
+                value.clear();
+                num_chunks = 0;
+            }
             total_bytes += value.len();
-            values.push(Value { content: Some(value), version });
+            values.push(Value { content: Some(value), version, expire_at, num_chunks });
         } else if req.include_raw_data {
-            values.push(Value { content: None, version });
+            values.push(Value::tombstone(version));
         }
 
         if !req.include_raw_data {
@@ -228,11 +247,11 @@ async fn resolve_txn<T: LatchManager>(
     start_version: u64,
     user_key: &[u8],
     encoded_intent_value: &[u8],
-) -> Result<Option<(Option<Vec<u8>>, u64)>> {
+) -> Result<Option<Value>> {
     let intent = TxnIntent::decode(encoded_intent_value)?;
-    if intent.start_version > start_version {
-        // skip invisible versions.
-        return Ok(None);
+    if let Some(value) = resolve_intent_for_read(latch_mgr, &intent, start_version) {
+        // skip invisible versions, or read the finished txn without resolving.
+        return Ok(value);
     }
 
     let intent_value_opt =
@@ -245,7 +264,7 @@ async fn resolve_txn<T: LatchManager>(
         return Ok(None);
     }
 
-    Ok(Some((intent_value.content, intent_value.version)))
+    Ok(Some(intent_value))
 }
 
 #[cfg(test)]
@@ -262,7 +281,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, SHARD_ID, key, value, *version).unwrap();
             } else {
@@ -307,6 +326,58 @@ mod tests {
         assert_eq!(resp.data[0].values.len(), 100);
     }
 
+    #[sekas_macro::test]
+    async fn scan_with_min_version_returns_changed_keys() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = LocalLatchManager::default();
+
+        commit_values(&engine, b"a", &[Value::with_value(b"a".to_vec(), 10)]);
+        let values = [
+            Value::with_value(b"b1".to_vec(), 10),
+            Value::tombstone(20),
+            Value::with_value(b"b2".to_vec(), 30),
+            Value::with_value(b"b3".to_vec(), 40),
+            Value::with_value(b"b4".to_vec(), 50),
+        ];
+        commit_values(&engine, b"b", &values);
+
+        let scan_req = ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_version: 1000,
+            include_raw_data: true,
+            min_version: 45,
+            ..Default::default()
+        };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(resp.data.len(), 1);
+        assert_eq!(resp.data[0].user_key, b"b");
+        let versions = resp.data[0].values.iter().map(|v| v.version).collect::<Vec<_>>();
+        assert_eq!(versions, vec![50, 40, 30]);
+    }
+
+    #[sekas_macro::test]
+    async fn scan_keys_only() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = LocalLatchManager::default();
+
+        commit_values(&engine, b"a", &[Value::with_value(b"a".to_vec(), 10)]);
+        commit_values(&engine, b"b", &[Value::with_value(b"b".to_vec(), 10), Value::tombstone(20)]);
+
+        let scan_req = ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_version: 1000,
+            keys_only: true,
+            ..Default::default()
+        };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(resp.data.len(), 1);
+        assert_eq!(resp.data[0].user_key, b"a");
+        assert_eq!(resp.data[0].values[0].content, Some(vec![]));
+        assert_eq!(resp.data[0].values[0].version, 10);
+    }
+
     #[sekas_macro::test]
     async fn scan_with_limit_should_returns_more() {
         let dir = TempDir::new(fn_name!()).unwrap();
@@ -19,8 +19,9 @@ use sekas_rock::num::decode_i64;
 use sekas_schema::system::txn::TXN_INTENT_VERSION;
 
 use super::cas::eval_conditions;
+use super::key_schema::check_key;
 use super::latch::DeferSignalLatchGuard;
-use super::LatchGuard;
+use super::{check_writable, LatchGuard};
 use crate::engine::{GroupEngine, SnapshotMode, WriteBatch};
 use crate::node::move_shard::ForwardCtx;
 use crate::replica::ExecCtx;
@@ -40,6 +41,11 @@ pub(crate) async fn write_intent<T: LatchGuard>(
         .ok_or_else(|| Error::InvalidArgument("`write` is required".to_string()))?;
 
     let user_key = write.user_key();
+    let shard = group_engine.shard_desc(req.shard_id)?;
+    check_writable(&shard)?;
+    if matches!(write, WriteRequest::Put(_)) {
+        check_key(&shard, user_key)?;
+    }
     // Maybe we can extract the forwarding logic to a common place before writing.
     if let Some(desc) = exec_ctx.move_shard_desc.as_ref() {
         let shard_id = desc.shard_desc.as_ref().unwrap().id;
@@ -61,13 +67,17 @@ pub(crate) async fn write_intent<T: LatchGuard>(
     .await?;
 
     let mut wb = WriteBatch::default();
+    let mut add_result = None;
+    let prev_num_chunks = prev_value.as_ref().map(|v| v.num_chunks).unwrap_or_default();
     let prev_value = match write {
         WriteRequest::Delete(del) => {
             if !skip_write {
                 if let Some(cond_idx) = eval_conditions(prev_value.as_ref(), &del.conditions)? {
                     return Err(Error::CasFailed(0, cond_idx as u64, prev_value));
                 }
-                let txn_intent = TxnIntent::tombstone(req.start_version).encode_to_vec();
+                let txn_intent = TxnIntent::tombstone(req.start_version)
+                    .with_database(req.txn_database_id)
+                    .encode_to_vec();
                 group_engine.put(
                     &mut wb,
                     req.shard_id,
@@ -90,8 +100,11 @@ pub(crate) async fn write_intent<T: LatchGuard>(
                 }
                 let apply_value =
                     apply_put_op(put.put_type(), prev_value.as_ref(), put.value.clone())?;
-                let txn_intent =
-                    TxnIntent::with_put(req.start_version, apply_value).encode_to_vec();
+                let txn_intent = TxnIntent::with_put(req.start_version, apply_value)
+                    .with_expire_at(put.expire_at)
+                    .with_num_chunks(put.num_chunks)
+                    .with_database(req.txn_database_id)
+                    .encode_to_vec();
                 group_engine.put(
                     &mut wb,
                     req.shard_id,
@@ -100,6 +113,11 @@ pub(crate) async fn write_intent<T: LatchGuard>(
                     TXN_INTENT_VERSION,
                 )?;
             }
+            if put.put_type() == PutType::AddI64 {
+                // The result is recomputed if the intent already exists, it is deterministic.
+                let value = apply_put_op(PutType::AddI64, prev_value.as_ref(), put.value.clone())?;
+                add_result = value.as_deref().and_then(decode_i64);
+            }
             if put.take_prev_value {
                 prev_value
             } else {
@@ -108,7 +126,7 @@ pub(crate) async fn write_intent<T: LatchGuard>(
         }
     };
 
-    let resp = WriteResponse { prev_value };
+    let resp = WriteResponse { prev_value, add_result, prev_num_chunks };
     let eval_result =
         if !wb.is_empty() { Some(EvalResult::with_batch(wb.data().to_owned())) } else { None };
     Ok((eval_result, WriteIntentResponse { write: Some(resp) }))
@@ -148,8 +166,24 @@ pub(crate) async fn commit_intent<T: LatchGuard>(
     group_engine.delete(&mut wb, req.shard_id, &req.user_key, TXN_INTENT_VERSION)?;
     if intent.is_delete {
         group_engine.tombstone(&mut wb, req.shard_id, &req.user_key, req.commit_version)?;
+    } else if intent.num_chunks != 0 {
+        group_engine.put_chunked(
+            &mut wb,
+            req.shard_id,
+            &req.user_key,
+            intent.num_chunks,
+            req.commit_version,
+            intent.expire_at,
+        )?;
     } else if let Some(value) = intent.value {
-        group_engine.put(&mut wb, req.shard_id, &req.user_key, &value, req.commit_version)?;
+        group_engine.put_with_expire_at(
+            &mut wb,
+            req.shard_id,
+            &req.user_key,
+            &value,
+            req.commit_version,
+            intent.expire_at,
+        )?;
     }
 
     trace!(
@@ -362,7 +396,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, 1, key, value, *version).unwrap();
             } else {
@@ -450,6 +484,7 @@ mod tests {
                 take_prev_value: true,
                 ..Default::default()
             })),
+            ..Default::default()
         }
     }
 
@@ -492,6 +527,39 @@ mod tests {
         assert!(eval_result.is_none());
     }
 
+    #[sekas_macro::test]
+    async fn write_intent_returns_add_result() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let mut latch_guard = DeferSignalLatchGuard::<NotifyLatchGuard>::empty();
+
+        let key = b"counter".to_vec();
+        commit_values(&engine, &key, &[Value::with_value(10i64.to_be_bytes().to_vec(), 1)]);
+        let req = WriteIntentRequest {
+            start_version: 100,
+            shard_id: 1,
+            write: Some(WriteRequest::Put(WriteBuilder::new(key.clone()).ensure_add(5))),
+            ..Default::default()
+        };
+        let (eval_result, resp) =
+            write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+        assert_eq!(resp.write.unwrap().add_result, Some(15));
+        let wb = WriteBatch::new(&eval_result.unwrap().batch.unwrap().data);
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        // The retried request returns the same result.
+        let (eval_result, resp) =
+            write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+        assert!(eval_result.is_none());
+        assert_eq!(resp.write.unwrap().add_result, Some(15));
+
+        // The normal put has no add result.
+        let req = write_intent_request_with_value(101, b"other".to_vec(), b"value".to_vec());
+        let (_, resp) =
+            write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+        assert_eq!(resp.write.unwrap().add_result, None);
+    }
+
     #[sekas_macro::test]
     async fn write_and_clear_intent() {
         let dir = TempDir::new(fn_name!()).unwrap();
@@ -562,6 +630,7 @@ mod tests {
             write: Some(WriteRequest::Put(
                 WriteBuilder::new(key.clone()).expect_exists().ensure_put(b"value".to_vec()),
             )),
+            ..Default::default()
         };
         let r = write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await;
         assert!(matches!(r, Err(Error::CasFailed(0, 0, _))), "{r:?}");
@@ -573,6 +642,7 @@ mod tests {
             write: Some(WriteRequest::Delete(
                 WriteBuilder::new(key.clone()).expect_exists().ensure_delete(),
             )),
+            ..Default::default()
         };
         let r = write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await;
         assert!(matches!(r, Err(Error::CasFailed(0, 0, _))), "{r:?}");
@@ -589,6 +659,7 @@ mod tests {
                     .take_prev_value()
                     .ensure_put(b"value".to_vec()),
             )),
+            ..Default::default()
         };
         let r = write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await;
         assert!(r.is_ok());
@@ -694,6 +765,7 @@ mod tests {
                     write: Some(WriteRequest::Put(
                         WriteBuilder::new(key_clone.clone()).ensure_add(1),
                     )),
+                    ..Default::default()
                 };
                 let mut latch_guard = DeferSignalLatchGuard::with_single(
                     &ShardKey { shard_id, user_key: key_clone.to_vec() },
@@ -17,7 +17,10 @@ use sekas_api::server::v1::{PutType, ShardWriteRequest, ShardWriteResponse, Writ
 use sekas_rock::time::timestamp_nanos;
 
 use super::cas::eval_conditions;
+use super::check_writable;
+use super::key_schema::check_key;
 use crate::engine::{GroupEngine, WriteBatch};
+use crate::failpoint::fail_point;
 use crate::node::move_shard::ForwardCtx;
 use crate::replica::ExecCtx;
 use crate::serverpb::v1::EvalResult;
@@ -28,10 +31,16 @@ pub(crate) async fn batch_write(
     group_engine: &GroupEngine,
     req: &ShardWriteRequest,
 ) -> Result<(Option<EvalResult>, ShardWriteResponse)> {
+    fail_point!("replica_eval_write");
     // TODO(walter) only internal shards would write in batch.
     if req.deletes.is_empty() && req.puts.is_empty() {
         return Ok((None, ShardWriteResponse::default()));
     }
+    let shard = group_engine.shard_desc(req.shard_id)?;
+    check_writable(&shard)?;
+    for put in &req.puts {
+        check_key(&shard, &put.key)?;
+    }
 
     if let Some(desc) = exec_ctx.move_shard_desc.as_ref() {
         let shard_id = desc.shard_desc.as_ref().unwrap().id;
@@ -58,7 +67,9 @@ pub(crate) async fn batch_write(
         }
         let prev_version = prev_value.as_ref().map(|v| v.version).unwrap_or_default();
         resp.deletes.push(WriteResponse {
+            prev_num_chunks: prev_value.as_ref().map(|v| v.num_chunks).unwrap_or_default(),
             prev_value: if del.take_prev_value { prev_value } else { None },
+            ..Default::default()
         });
         let version = std::cmp::max(prev_version + 1, next_version());
         group_engine.tombstone(&mut wb, req.shard_id, &del.key, version)?;
@@ -75,10 +86,30 @@ pub(crate) async fn batch_write(
         }
         let prev_version = prev_value.as_ref().map(|v| v.version).unwrap_or_default();
         resp.puts.push(WriteResponse {
+            prev_num_chunks: prev_value.as_ref().map(|v| v.num_chunks).unwrap_or_default(),
             prev_value: if put.take_prev_value { prev_value } else { None },
+            ..Default::default()
         });
         let version = std::cmp::max(prev_version + 1, next_version());
-        group_engine.put(&mut wb, req.shard_id, &put.key, &put.value, version)?;
+        if put.num_chunks != 0 {
+            group_engine.put_chunked(
+                &mut wb,
+                req.shard_id,
+                &put.key,
+                put.num_chunks,
+                version,
+                put.expire_at,
+            )?;
+        } else {
+            group_engine.put_with_expire_at(
+                &mut wb,
+                req.shard_id,
+                &put.key,
+                &put.value,
+                version,
+                put.expire_at,
+            )?;
+        }
     }
     Ok((Some(EvalResult::with_batch(wb.data().to_owned())), resp))
 }
@@ -90,7 +121,7 @@ fn next_version() -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use sekas_api::server::v1::Value;
+    use sekas_api::server::v1::{GroupDesc, ShardDesc, Value};
     use sekas_client::WriteBuilder;
     use sekas_rock::fn_name;
     use tempdir::TempDir;
@@ -103,7 +134,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, SHARD_ID, key, value, *version).unwrap();
             } else {
@@ -162,4 +193,33 @@ mod tests {
         let r = batch_write(&exec_ctx, &engine, &req).await;
         assert!(r.is_ok());
     }
+    #[sekas_macro::test]
+    async fn batch_write_to_frozen_shard() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, SHARD_ID, 1).await;
+        let desc = GroupDesc {
+            id: 1,
+            shards: vec![ShardDesc { frozen: true, ..ShardDesc::whole(SHARD_ID, 1) }],
+            ..Default::default()
+        };
+        let states = WriteStates { descriptor: Some(desc), ..Default::default() };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        let exec_ctx = ExecCtx::default();
+        let req = ShardWriteRequest {
+            shard_id: SHARD_ID,
+            puts: vec![WriteBuilder::new(b"key".to_vec()).ensure_put(b"value".to_vec())],
+            ..Default::default()
+        };
+        let r = batch_write(&exec_ctx, &engine, &req).await;
+        assert!(matches!(r, Err(Error::CollectionFrozen(1))), "{r:?}");
+
+        let req = ShardWriteRequest {
+            shard_id: SHARD_ID,
+            deletes: vec![WriteBuilder::new(b"key".to_vec()).ensure_delete()],
+            ..Default::default()
+        };
+        let r = batch_write(&exec_ctx, &engine, &req).await;
+        assert!(matches!(r, Err(Error::CollectionFrozen(1))), "{r:?}");
+    }
 }
@@ -0,0 +1,88 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sekas_api::server::v1::*;
+
+use crate::{Error, Result};
+
+/// Check the key against the key schema of the shard, the deletes are not
+/// checked since they never introduce new keys.
+pub(super) fn check_key(shard: &ShardDesc, key: &[u8]) -> Result<()> {
+    let Some(schema) = shard.key_schema.as_ref() else {
+        return Ok(());
+    };
+    let Some(encoding) = KeyEncoding::from_i32(schema.encoding) else {
+        return Err(Error::InvalidArgument(format!("invalid key encoding {}", schema.encoding)));
+    };
+    let expect_len = match encoding {
+        KeyEncoding::Bytes => return Ok(()),
+        KeyEncoding::FixedLength => schema.length as usize,
+        KeyEncoding::U64Be => std::mem::size_of::<u64>(),
+        KeyEncoding::Utf8 => {
+            return std::str::from_utf8(key).map(|_| ()).map_err(|_| {
+                Error::InvalidArgument(format!(
+                    "key {} is not a valid UTF-8 string",
+                    key.escape_ascii()
+                ))
+            });
+        }
+    };
+    if key.len() != expect_len {
+        return Err(Error::InvalidArgument(format!(
+            "the length of key {} is {}, but {} is expected by {}",
+            key.escape_ascii(),
+            key.len(),
+            expect_len,
+            encoding.as_str_name()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard_with(encoding: KeyEncoding, length: u32) -> ShardDesc {
+        ShardDesc {
+            key_schema: Some(KeySchema { encoding: encoding as i32, length }),
+            ..ShardDesc::whole(1, 1)
+        }
+    }
+
+    #[test]
+    fn check_key_by_schema() {
+        assert!(check_key(&ShardDesc::whole(1, 1), b"\xff").is_ok());
+        assert!(check_key(&shard_with(KeyEncoding::Bytes, 0), b"").is_ok());
+
+        let shard = shard_with(KeyEncoding::FixedLength, 4);
+        assert!(check_key(&shard, b"abcd").is_ok());
+        assert!(check_key(&shard, b"abc").is_err());
+        assert!(check_key(&shard, b"abcde").is_err());
+
+        let shard = shard_with(KeyEncoding::U64Be, 0);
+        assert!(check_key(&shard, &42u64.to_be_bytes()).is_ok());
+        assert!(check_key(&shard, &42u32.to_be_bytes()).is_err());
+
+        let shard = shard_with(KeyEncoding::Utf8, 0);
+        assert!(check_key(&shard, "键".as_bytes()).is_ok());
+        assert!(check_key(&shard, b"\xff\xfe").is_err());
+
+        let shard = ShardDesc {
+            key_schema: Some(KeySchema { encoding: 100, length: 0 }),
+            ..ShardDesc::whole(1, 1)
+        };
+        assert!(check_key(&shard, b"key").is_err());
+    }
+}
@@ -49,6 +49,39 @@ pub trait LatchManager {
 
     /// Acquire row latch for the specified user key.
     async fn acquire(&self, shard_id: u64, user_key: &[u8]) -> Result<Self::Guard>;
+
+    /// Return the known state and commit version of a finished txn, readers
+    /// use it to skip resolving intents without acquiring the row latch.
+    fn finished_txn_state(&self, _intent_version: u64) -> Option<(TxnState, u64)> {
+        None
+    }
+}
+
+/// Try to resolve the txn intent for reading at `start_version` without
+/// acquiring the row latch. Return [`None`] if the intent must be resolved by
+/// [`LatchManager::resolve_txn`], otherwise the value of the intent visible to
+/// the reader is returned.
+///
+/// It is safe because an intent with a larger start version could never be
+/// committed at a version visible to the reader, and the state of a finished
+/// txn never changes.
+pub fn resolve_intent_for_read<T: LatchManager>(
+    latch_mgr: &T,
+    intent: &TxnIntent,
+    start_version: u64,
+) -> Option<Option<Value>> {
+    if intent.start_version > start_version {
+        // skip invisible versions.
+        return Some(None);
+    }
+
+    match latch_mgr.finished_txn_state(intent.start_version)? {
+        (TxnState::Committed, commit_version) if commit_version <= start_version => {
+            Some(Some(intent.committed_value(commit_version)))
+        }
+        (TxnState::Committed, _) | (TxnState::Aborted, _) => Some(None),
+        (TxnState::Running, _) => None,
+    }
 }
 
 pub struct DeferSignalLatchGuard<L: LatchGuard> {
@@ -125,10 +158,14 @@ where
         Request::Scan(_)
         | Request::Get(_)
         | Request::CreateShard(_)
+        | Request::TruncateShard(_)
+        | Request::FreezeShard(_)
+        | Request::SplitShard(_)
         | Request::ChangeReplicas(_)
         | Request::AcceptShard(_)
         | Request::Transfer(_)
-        | Request::MoveReplicas(_) => return Ok(None),
+        | Request::MoveReplicas(_)
+        | Request::Ingest(_) => return Ok(None),
     };
 
     if keys.is_empty() {
@@ -158,8 +195,8 @@ fn collect_shard_write_keys(req: &ShardWriteRequest) -> Result<Vec<Vec<u8>>> {
 }
 
 pub mod remote {
-    use std::collections::VecDeque;
-    use std::sync::Arc;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
     use dashmap::DashMap;
@@ -178,6 +215,9 @@ pub mod remote {
     use crate::serverpb::v1::EvalResult;
     use crate::{Error, Result};
 
+    /// The max number of finished txns to remember.
+    const MAX_FINISHED_TXNS: usize = 4096;
+
     #[derive(Default)]
     struct LatchBlock {
         hold: bool,
@@ -202,6 +242,14 @@ pub mod remote {
         group_engine: GroupEngine,
         raft_group: RaftGroup,
         latches: DashMap<ShardKey, LatchBlock>,
+        finished_txns: Mutex<FinishedTxns>,
+    }
+
+    /// A bounded cache of the state of finished txns, indexed by start version.
+    #[derive(Default)]
+    struct FinishedTxns {
+        states: HashMap<u64, (TxnState, u64)>,
+        order: VecDeque<u64>,
     }
 
     impl RemoteLatchManager {
@@ -216,6 +264,7 @@ pub mod remote {
                     group_engine,
                     raft_group,
                     latches: DashMap::with_shard_amount(16),
+                    finished_txns: Mutex::default(),
                 }),
             }
         }
@@ -390,28 +439,33 @@ pub mod remote {
                 Err(rx) => Ok(rx.await.expect("Will not be dropped without send()")),
             }
         }
+
+        fn finished_txn_state(&self, intent_version: u64) -> Option<(TxnState, u64)> {
+            let finished_txns = self.core.finished_txns.lock().expect("Poisoned");
+            finished_txns.states.get(&intent_version).cloned()
+        }
     }
 
     impl super::LatchGuard for RemoteLatchGuard {
         async fn resolve_txn(&mut self, txn_intent: TxnIntent) -> Result<Option<Value>> {
             let start_version = txn_intent.start_version;
             trace!("try resolve txn {start_version}, shard key {:?}", self.shard_key);
+            let txn_table =
+                self.latch_mgr.core.txn_table.clone().with_database(txn_intent.database_id);
             loop {
                 let txn_record =
-                    self.latch_mgr.core.txn_table.get_txn_record(start_version).await?.ok_or_else(
-                        || {
-                            Error::InvalidData(format!(
-                                "resolve txn {}, but txn record is not exists",
-                                start_version
-                            ))
-                        },
-                    )?;
+                    txn_table.get_txn_record(start_version).await?.ok_or_else(|| {
+                        Error::InvalidData(format!(
+                            "resolve txn {}, but txn record is not exists",
+                            start_version
+                        ))
+                    })?;
 
                 let mut delete_intent = false;
                 let (actual_txn_state, commit_version) = if txn_record.state == TxnState::Running {
                     if txn_record.heartbeat + 500 < timestamp_millis() {
                         debug!("abort txn {} because it was expired", start_version);
-                        match self.latch_mgr.core.txn_table.abort_txn(start_version).await {
+                        match txn_table.abort_txn(start_version).await {
                             Ok(()) => {
                                 delete_intent = true;
                                 (TxnState::Aborted, 0)
@@ -448,6 +502,11 @@ pub mod remote {
 
                 debug!("txn {} intent state {}, commit version {commit_version} delete intent {delete_intent}", start_version,
                     actual_txn_state.as_str_name());
+                self.latch_mgr.core.record_finished_txn(
+                    start_version,
+                    actual_txn_state,
+                    commit_version,
+                );
                 match actual_txn_state {
                     TxnState::Committed => {
                         if delete_intent {
@@ -455,14 +514,7 @@ pub mod remote {
                                 .commit_intent(&self.shard_key, &txn_intent, commit_version)
                                 .await?;
                         }
-                        if txn_intent.is_delete {
-                            return Ok(Some(Value::tombstone(commit_version)));
-                        } else {
-                            return Ok(Some(Value {
-                                content: txn_intent.value,
-                                version: commit_version,
-                            }));
-                        }
+                        return Ok(Some(txn_intent.committed_value(commit_version)));
                     }
                     TxnState::Aborted => {
                         if delete_intent {
@@ -512,6 +564,27 @@ pub mod remote {
             let shard_key = ShardKey { shard_id, user_key: user_key.to_owned() };
             self.latches.entry(shard_key).or_insert_with(default_latch_block)
         }
+
+        fn record_finished_txn(
+            &self,
+            start_version: u64,
+            txn_state: TxnState,
+            commit_version: u64,
+        ) {
+            if txn_state == TxnState::Running {
+                return;
+            }
+            let mut finished_txns = self.finished_txns.lock().expect("Poisoned");
+            if finished_txns.states.insert(start_version, (txn_state, commit_version)).is_some() {
+                return;
+            }
+            finished_txns.order.push_back(start_version);
+            while finished_txns.order.len() > MAX_FINISHED_TXNS {
+                if let Some(version) = finished_txns.order.pop_front() {
+                    finished_txns.states.remove(&version);
+                }
+            }
+        }
     }
 
     #[cfg(test)]
@@ -533,7 +606,7 @@ pub mod remote {
                     .unwrap();
             let engine = create_group_engine(dir.path(), 1, 1, 1).await;
             let (sender, _receiver) = mpsc::channel(1024);
-            let raft_group = RaftGroup::open(sender);
+            let raft_group = RaftGroup::open(sender, Arc::default());
             let latch_mgr = RemoteLatchManager::new(client, engine, raft_group);
 
             let shard_id = 1;
@@ -682,13 +755,7 @@ pub mod local {
                 self.latch_mgr.acquire(self.shard_key.shard_id, &self.shard_key.user_key).await?;
             match txn_state {
                 TxnState::Aborted => Ok(None),
-                TxnState::Committed => {
-                    if txn_intent.is_delete {
-                        Ok(Some(Value::tombstone(commit_version)))
-                    } else {
-                        Ok(Some(Value { content: txn_intent.value, version: commit_version }))
-                    }
-                }
+                TxnState::Committed => Ok(Some(txn_intent.committed_value(commit_version))),
                 _ => unreachable!(),
             }
         }
@@ -21,22 +21,113 @@ mod cmd_move_replicas;
 mod cmd_scan;
 mod cmd_txn;
 mod cmd_write;
+mod key_schema;
 mod latch;
 
-use sekas_api::server::v1::ShardDesc;
+use sekas_api::server::v1::{RangePartition, ShardDesc};
+use sekas_schema::shard;
 
 pub(crate) use self::cmd_accept_shard::accept_shard;
 pub(crate) use self::cmd_get::get;
-pub(crate) use self::cmd_ingest::ingest_value_set;
+pub(crate) use self::cmd_ingest::{ingest_files, ingest_value_set, ingest_value_sets};
 pub(crate) use self::cmd_move_replicas::move_replicas;
 pub(crate) use self::cmd_scan::{merge_scan_response, scan};
 pub(crate) use self::cmd_txn::{clear_intent, commit_intent, write_intent};
 pub(crate) use self::cmd_write::batch_write;
-pub(crate) use self::latch::{acquire_row_latches, remote, LatchGuard, LatchManager};
+pub(crate) use self::latch::{
+    acquire_row_latches, remote, resolve_intent_for_read, LatchGuard, LatchManager,
+};
+use crate::engine::GroupEngine;
 use crate::serverpb::v1::EvalResult;
+use crate::{Error, Result};
 
 pub fn add_shard(shard: ShardDesc) -> EvalResult {
     use crate::serverpb::v1::SyncOp;
 
     EvalResult { op: Some(SyncOp::add_shard(shard)), ..Default::default() }
 }
+
+/// Replace the shard with the fresh `new_shard`, the data of the shard is
+/// dropped once the result is applied. `None` is returned if the shard has
+/// already been truncated.
+pub fn truncate_shard(
+    engine: &GroupEngine,
+    shard_id: u64,
+    new_shard: ShardDesc,
+) -> Result<Option<EvalResult>> {
+    use crate::serverpb::v1::SyncOp;
+
+    if engine.shard_desc(new_shard.id).is_ok() {
+        return Ok(None);
+    }
+    let shard = engine.shard_desc(shard_id)?;
+    if shard.collection_id != new_shard.collection_id || shard.range != new_shard.range {
+        return Err(Error::InvalidArgument(format!(
+            "the new shard {} is not the same range of shard {shard_id}",
+            new_shard.id
+        )));
+    }
+    Ok(Some(EvalResult {
+        op: Some(SyncOp::truncate_shard(shard_id, new_shard)),
+        ..Default::default()
+    }))
+}
+
+/// Mark the shard as read-only or writable. `None` is returned if the shard is
+/// already in the state.
+pub fn freeze_shard(
+    engine: &GroupEngine,
+    shard_id: u64,
+    frozen: bool,
+) -> Result<Option<EvalResult>> {
+    use crate::serverpb::v1::SyncOp;
+
+    let shard = engine.shard_desc(shard_id)?;
+    if shard.frozen == frozen {
+        return Ok(None);
+    }
+    Ok(Some(EvalResult { op: Some(SyncOp::freeze_shard(shard_id, frozen)), ..Default::default() }))
+}
+
+/// Split the shard at `split_key`, the new shard inherits the properties of
+/// the shard and covers the range from `split_key` to the end of the shard.
+/// `None` is returned if the shard has already been split.
+pub fn split_shard(
+    engine: &GroupEngine,
+    shard_id: u64,
+    split_key: &[u8],
+    new_shard_id: u64,
+) -> Result<Option<EvalResult>> {
+    use crate::serverpb::v1::SyncOp;
+
+    if engine.shard_desc(new_shard_id).is_ok() {
+        return Ok(None);
+    }
+    let shard = engine.shard_desc(shard_id)?;
+    let Some(range) = shard.range.as_ref() else {
+        return Err(Error::InvalidArgument(format!("shard {shard_id} is not a range shard")));
+    };
+    if split_key <= range.start.as_slice() || !shard::belong_to(&shard, split_key) {
+        return Err(Error::InvalidArgument(format!(
+            "split key {} is not in the range of shard {shard_id}",
+            split_key.escape_ascii()
+        )));
+    }
+    let new_shard = ShardDesc {
+        id: new_shard_id,
+        range: Some(RangePartition { start: split_key.to_owned(), end: range.end.clone() }),
+        ..shard.clone()
+    };
+    Ok(Some(EvalResult {
+        op: Some(SyncOp::split_shard(shard_id, new_shard)),
+        ..Default::default()
+    }))
+}
+
+/// Reject the writes to a frozen shard.
+fn check_writable(shard: &ShardDesc) -> Result<()> {
+    if shard.frozen {
+        return Err(Error::CollectionFrozen(shard.collection_id));
+    }
+    Ok(())
+}
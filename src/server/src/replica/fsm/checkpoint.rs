@@ -161,7 +161,9 @@ mod tests {
         let db = Arc::new(db);
 
         let group_engine =
-            GroupEngine::create(&EngineConfig::default(), db.clone(), group_id, 1).await.unwrap();
+            GroupEngine::create(&EngineConfig::default(), db.clone(), Arc::default(), group_id, 1)
+                .await
+                .unwrap();
         let wb = WriteBatch::default();
         let states = WriteStates {
             descriptor: Some(GroupDesc {
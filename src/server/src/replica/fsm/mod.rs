@@ -22,14 +22,14 @@ use std::sync::Arc;
 use log::{info, trace, warn};
 use sekas_api::server::v1::{
     ChangeReplica, ChangeReplicaType, ChangeReplicas, GroupDesc, MoveShardDesc, ReplicaDesc,
-    ReplicaRole,
+    ReplicaRole, ShardDesc,
 };
 
 use super::ReplicaInfo;
-use crate::engine::{GroupEngine, WriteBatch, WriteStates};
+use crate::engine::{GroupEngine, IngestStore, WriteBatch, WriteStates};
 use crate::raftgroup::{ApplyEntry, SnapshotBuilder, StateMachine};
 use crate::serverpb::v1::*;
-use crate::{ReplicaConfig, Result};
+use crate::{Error, ReplicaConfig, Result};
 
 const SHARD_UPDATE_DELTA: u64 = 1 << 32;
 const CONFIG_CHANGE_DELTA: u64 = 1;
@@ -51,6 +51,10 @@ pub trait StateMachineObserver: Send + Sync {
 
     /// This function will be called once the move shard state changes.
     fn on_move_shard_state_updated(&mut self, state: Option<MoveShardState>);
+
+    /// This function will be called once only the progress of moving shard
+    /// changes, eg. the last moved key.
+    fn on_move_shard_progress_updated(&mut self, state: Option<MoveShardState>);
 }
 
 pub struct GroupStateMachine
@@ -61,6 +65,7 @@ where
     info: Arc<ReplicaInfo>,
 
     group_engine: GroupEngine,
+    ingest_store: IngestStore,
     observer: Box<dyn StateMachineObserver>,
 
     plugged_write_batches: Vec<WriteBatch>,
@@ -69,7 +74,13 @@ where
     /// Whether `GroupDesc` changes during apply.
     desc_updated: bool,
     move_shard_state_updated: bool,
+    move_shard_progress_updated: bool,
     last_applied_term: u64,
+    /// The ingested files and the index of their entries. The files are kept
+    /// until the apply state of the entry is flushed, so that the entry could
+    /// be applied again after restarting. It is persisted along with the apply
+    /// state, so the files are removed even if the replica is restarted.
+    ingest_state: IngestState,
 }
 
 impl GroupStateMachine {
@@ -77,19 +88,24 @@ impl GroupStateMachine {
         cfg: ReplicaConfig,
         info: Arc<ReplicaInfo>,
         group_engine: GroupEngine,
+        ingest_store: IngestStore,
         observer: Box<dyn StateMachineObserver>,
     ) -> Self {
         let apply_state = group_engine.flushed_apply_state().expect("access flushed index");
+        let ingest_state = group_engine.ingest_state().expect("access ingest state");
         GroupStateMachine {
             cfg,
             info,
             group_engine,
+            ingest_store,
             observer,
             plugged_write_batches: Vec::default(),
             plugged_write_states: WriteStates::default(),
             desc_updated: false,
             move_shard_state_updated: false,
+            move_shard_progress_updated: false,
             last_applied_term: apply_state.term,
+            ingest_state,
         }
     }
 }
@@ -114,7 +130,7 @@ impl GroupStateMachine {
         Ok(())
     }
 
-    fn apply_proposal(&mut self, eval_result: EvalResult) -> Result<()> {
+    fn apply_proposal(&mut self, index: u64, eval_result: EvalResult) -> Result<()> {
         if let Some(wb) = eval_result.batch {
             self.plugged_write_batches.push(WriteBatch::new(&wb.data));
         }
@@ -138,6 +154,22 @@ impl GroupStateMachine {
             if let Some(m) = op.move_shard {
                 self.apply_move_shard_event(m, &mut desc);
             }
+            if let Some(ingest_files) = op.ingest_files {
+                self.apply_ingest_files(index, ingest_files)?;
+            }
+            if let Some(TruncateShard { shard_id, new_shard: Some(new_shard) }) = op.truncate_shard
+            {
+                self.apply_truncate_shard(shard_id, new_shard, &mut desc)?;
+            }
+            if let Some(FreezeShard { shard_id, frozen }) = op.freeze_shard {
+                self.apply_freeze_shard(shard_id, frozen, &mut desc);
+            }
+            if let Some(SplitShard { shard_id, new_shard: Some(new_shard) }) = op.split_shard {
+                self.apply_split_shard(shard_id, new_shard, &mut desc);
+            }
+            if let Some(realize_clone) = op.realize_clone {
+                self.apply_realize_clone(realize_clone, &mut desc)?;
+            }
 
             // Any sync_op will update group desc.
             self.plugged_write_states.descriptor = Some(desc);
@@ -169,6 +201,7 @@ impl GroupStateMachine {
                     move_shard: move_shard.desc,
                     last_moved_key: None,
                     step: MoveShardStep::Prepare as i32,
+                    ..Default::default()
                 };
                 debug_assert!(state.move_shard.is_some());
                 self.plugged_write_states.move_shard_state = Some(state);
@@ -186,6 +219,12 @@ impl GroupStateMachine {
 
                 debug_assert!(state.step == MoveShardStep::Moving as i32);
                 state.last_moved_key = Some(move_shard.last_ingested_key);
+                state.moved_keys = move_shard.moved_keys;
+                state.moved_bytes = move_shard.moved_bytes;
+                if move_shard.total_keys > 0 {
+                    state.total_keys = move_shard.total_keys;
+                }
+                self.move_shard_progress_updated = true;
 
                 self.plugged_write_states.move_shard_state = Some(state);
             }
@@ -221,6 +260,172 @@ impl GroupStateMachine {
         }
     }
 
+    /// Ingest the files into the shard. The leader confirms that the files are
+    /// sealed by all replicas before proposing, and no replicas are added
+    /// until the entry is compacted, so every replica applying the entry holds
+    /// the same verified files, which are never changed by the later uploads.
+    fn apply_ingest_files(&mut self, index: u64, ingest_files: IngestFiles) -> Result<()> {
+        let IngestFiles { shard_id, version, files, token } = ingest_files;
+
+        // The ingested files are written into the engine directly, so the plugged write
+        // batches must be committed first to keep the order of writes.
+        if !self.plugged_write_batches.is_empty() {
+            self.group_engine.group_commit(
+                self.plugged_write_batches.as_slice(),
+                WriteStates::default(),
+                false,
+            )?;
+            self.plugged_write_batches.clear();
+        }
+
+        let paths = self.ingest_store.sealed_paths(&token, &files)?;
+        match self.group_engine.ingest_kv_files(shard_id, version, &paths) {
+            Ok(()) => {
+                info!(
+                    "ingest {} files into shard {shard_id} with version {version}. replica={}, \
+                     group={}",
+                    files.len(),
+                    self.info.replica_id,
+                    self.info.group_id
+                );
+            }
+            // The keys are rejected by the shard, which is the same on all replicas, so the
+            // entry is skipped by all replicas alike.
+            Err(Error::InvalidArgument(msg)) => {
+                warn!(
+                    "skip ingesting files into shard {shard_id}: {msg}. replica={}, group={}",
+                    self.info.replica_id, self.info.group_id
+                );
+            }
+            Err(err) => return Err(err),
+        }
+        self.ingest_state.records.push(IngestRecord { index, files, token });
+        self.ingest_state.last_index = index;
+        self.plugged_write_states.ingest_state = Some(self.ingest_state.clone());
+        Ok(())
+    }
+
+    /// Take the ingest records whose entries will not be applied again, since
+    /// their apply states are flushed. The updated ingest state is written
+    /// along with the plugged writes.
+    fn take_flushed_ingest_records(&mut self) -> Vec<IngestRecord> {
+        if self.ingest_state.records.is_empty() {
+            return vec![];
+        }
+        let flushed_index = self.flushed_index();
+        let num_flushed =
+            self.ingest_state.records.iter().take_while(|r| r.index <= flushed_index).count();
+        if num_flushed == 0 {
+            return vec![];
+        }
+        let records = self.ingest_state.records.drain(..num_flushed).collect();
+        self.plugged_write_states.ingest_state = Some(self.ingest_state.clone());
+        records
+    }
+
+    /// Remove the files of the ingest records.
+    fn remove_ingest_files(&self, records: Vec<IngestRecord>) {
+        for record in &records {
+            if let Err(err) = self.ingest_store.remove(&record.token) {
+                warn!("remove ingest files {}: {err}. group={}", record.token, self.info.group_id);
+            }
+        }
+    }
+
+    fn apply_truncate_shard(
+        &mut self,
+        shard_id: u64,
+        new_shard: ShardDesc,
+        group_desc: &mut GroupDesc,
+    ) -> Result<()> {
+        // The data is deleted from the engine directly, so the plugged write batches
+        // must be committed first to keep the order of writes.
+        if !self.plugged_write_batches.is_empty() {
+            self.group_engine.group_commit(
+                self.plugged_write_batches.as_slice(),
+                WriteStates::default(),
+                false,
+            )?;
+            self.plugged_write_batches.clear();
+        }
+
+        self.group_engine.delete_shard_data(&new_shard)?;
+        info!(
+            "group {} truncate shard {shard_id}, replaced by shard {} at epoch {}",
+            self.info.group_id, new_shard.id, group_desc.epoch
+        );
+        group_desc.shards.retain(|s| s.id != shard_id);
+        group_desc.shards.push(new_shard);
+        group_desc.epoch += SHARD_UPDATE_DELTA;
+        self.desc_updated = true;
+        Ok(())
+    }
+
+    fn apply_freeze_shard(&mut self, shard_id: u64, frozen: bool, group_desc: &mut GroupDesc) {
+        let Some(shard) = group_desc.shards.iter_mut().find(|s| s.id == shard_id) else {
+            warn!("group {} freeze shard {shard_id}, but it is not found", self.info.group_id);
+            return;
+        };
+        shard.frozen = frozen;
+        group_desc.epoch += SHARD_UPDATE_DELTA;
+        info!(
+            "group {} mark shard {shard_id} frozen={frozen} at epoch {}",
+            self.info.group_id, group_desc.epoch
+        );
+        self.desc_updated = true;
+    }
+
+    fn apply_split_shard(
+        &mut self,
+        shard_id: u64,
+        new_shard: ShardDesc,
+        group_desc: &mut GroupDesc,
+    ) {
+        let new_shard_id = new_shard.id;
+        if !split_shard_desc(group_desc, shard_id, new_shard) {
+            warn!("group {} split shard {shard_id}, but it is not found", self.info.group_id);
+            return;
+        }
+        group_desc.epoch += SHARD_UPDATE_DELTA;
+        info!(
+            "group {} split shard {shard_id}, the new shard is {new_shard_id} at epoch {}",
+            self.info.group_id, group_desc.epoch
+        );
+        self.desc_updated = true;
+    }
+
+    /// Copy a chunk of the clone source into the cloned shard, the data of
+    /// the clone source is the same on all replicas, since it is never
+    /// changed below the clone version.
+    fn apply_realize_clone(
+        &mut self,
+        realize_clone: RealizeClone,
+        group_desc: &mut GroupDesc,
+    ) -> Result<()> {
+        let RealizeClone { shard_id, start_key, end_key, finished } = realize_clone;
+        let Some(shard) = group_desc.shards.iter_mut().find(|s| s.id == shard_id) else {
+            warn!("group {} realize clone {shard_id}, but it is not found", self.info.group_id);
+            return Ok(());
+        };
+        if shard.clone_source.is_none() {
+            return Ok(());
+        }
+
+        let mut wb = WriteBatch::default();
+        self.group_engine.realize_clone(&mut wb, shard_id, &start_key, &end_key)?;
+        self.plugged_write_batches.push(wb);
+        if finished {
+            shard.clone_source = None;
+            group_desc.epoch += SHARD_UPDATE_DELTA;
+            info!(
+                "group {} realize the clone source of shard {shard_id} at epoch {}",
+                self.info.group_id, group_desc.epoch
+            );
+            self.desc_updated = true;
+        }
+        Ok(())
+    }
+
     fn apply_moving_shard(&mut self, group_desc: &mut GroupDesc, desc: &MoveShardDesc) {
         let shard_desc = desc.get_shard_desc();
 
@@ -232,7 +437,10 @@ impl GroupStateMachine {
             "shard migrated out"
         } else {
             debug_assert_eq!(desc.dest_group_id, group_desc.id);
-            group_desc.shards.push(shard_desc.clone());
+            // The data of the clone source has been moved in along with the shard.
+            let mut shard_desc = shard_desc.clone();
+            shard_desc.clone_source = None;
+            group_desc.shards.push(shard_desc);
             "shard migrated in"
         };
         info!(
@@ -255,7 +463,11 @@ impl GroupStateMachine {
 
         if self.move_shard_state_updated {
             self.move_shard_state_updated = false;
+            self.move_shard_progress_updated = false;
             self.observer.on_move_shard_state_updated(self.group_engine.move_shard_state());
+        } else if self.move_shard_progress_updated {
+            self.move_shard_progress_updated = false;
+            self.observer.on_move_shard_progress_updated(self.group_engine.move_shard_state());
         }
     }
 
@@ -289,7 +501,7 @@ impl StateMachine for GroupStateMachine {
                 self.apply_change_replicas(change_replicas)?;
             }
             ApplyEntry::Proposal { eval_result } => {
-                self.apply_proposal(eval_result)?;
+                self.apply_proposal(index, eval_result)?;
             }
         }
         self.plugged_write_states.apply_state = Some(ApplyState { index, term });
@@ -301,6 +513,7 @@ impl StateMachine for GroupStateMachine {
         let Some(ApplyState { term, .. }) = self.plugged_write_states.apply_state else {
             panic!("invoke GroupStateMachine::finish_plug but WriteStates::apply_states is None");
         };
+        let flushed_records = self.take_flushed_ingest_records();
         self.group_engine.group_commit(
             self.plugged_write_batches.as_slice(),
             std::mem::take(&mut self.plugged_write_states),
@@ -308,12 +521,17 @@ impl StateMachine for GroupStateMachine {
         )?;
         self.plugged_write_batches.clear();
         self.flush_updated_events(term);
+        self.remove_ingest_files(flushed_records);
 
         Ok(())
     }
 
     fn apply_snapshot(&mut self, snap_dir: &Path) -> Result<()> {
         checkpoint::apply_snapshot(&self.group_engine, self.info.replica_id, snap_dir)?;
+        // The entries ingesting the files are covered by the snapshot.
+        let records = std::mem::take(&mut self.ingest_state.records);
+        self.remove_ingest_files(records);
+        self.ingest_state = self.group_engine.ingest_state()?;
         self.observer.on_descriptor_updated(self.group_engine.descriptor());
         let apply_state = self.flushed_apply_state();
         self.observer.on_term_updated(apply_state.term);
@@ -349,6 +567,20 @@ impl ChangeReplicaKind {
     }
 }
 
+/// Shrink the range of the shard to the start of `new_shard` and add the new
+/// shard, return false if the shard is not found.
+fn split_shard_desc(desc: &mut GroupDesc, shard_id: u64, new_shard: ShardDesc) -> bool {
+    let Some(shard) = desc.shards.iter_mut().find(|s| s.id == shard_id) else {
+        return false;
+    };
+    let split_key = sekas_schema::shard::start_key(&new_shard);
+    if let Some(range) = shard.range.as_mut() {
+        range.end = split_key;
+    }
+    desc.shards.push(new_shard);
+    true
+}
+
 fn apply_simple_change(local_id: u64, desc: &mut GroupDesc, change: &ChangeReplica) {
     let group_id = desc.id;
     let replica_id = change.replica_id;
@@ -507,6 +739,22 @@ fn check_not_in_joint_state(exist: &Option<&mut ReplicaDesc>) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn split_shard() {
+        let mut desc = GroupDesc {
+            shards: vec![ShardDesc::with_range(1, 10, b"a".to_vec(), b"z".to_vec())],
+            ..Default::default()
+        };
+        let new_shard = ShardDesc::with_range(2, 10, b"m".to_vec(), b"z".to_vec());
+        assert!(split_shard_desc(&mut desc, 1, new_shard.clone()));
+        assert_eq!(desc.shards.len(), 2);
+        assert_eq!(desc.shards[0], ShardDesc::with_range(1, 10, b"a".to_vec(), b"m".to_vec()));
+        assert_eq!(desc.shards[1], new_shard);
+
+        assert!(!split_shard_desc(&mut desc, 3, new_shard));
+        assert_eq!(desc.shards.len(), 2);
+    }
+
     fn group_replicas(desc: &GroupDesc) -> Vec<(u64, ReplicaRole)> {
         let mut result: Vec<(u64, ReplicaRole)> =
             desc.replicas.iter().map(|r| (r.id, ReplicaRole::from_i32(r.role).unwrap())).collect();
@@ -0,0 +1,245 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sekas_api::server::v1::HotKey;
+
+/// The number of hot keys reported for each shard.
+pub const REPORT_HOT_KEYS: usize = 8;
+
+/// Only one of every `SAMPLE_INTERVAL` accesses is sampled, the reported
+/// counts are scaled up accordingly.
+const SAMPLE_INTERVAL: u64 = 4;
+
+/// The max number of keys tracked by a window of a shard. Once it is full, the
+/// least accessed key is replaced and its count is inherited by the new key,
+/// so a hot key is never missed but its count might be overestimated.
+const MAX_TRACKED_KEYS: usize = 128;
+
+/// The length of a window, the hot keys are counted over the current and the
+/// previous windows.
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct AccessCount {
+    reads: u64,
+    writes: u64,
+}
+
+struct ShardHotKeys {
+    current_start: Instant,
+    current: HashMap<Vec<u8>, AccessCount>,
+    previous: HashMap<Vec<u8>, AccessCount>,
+}
+
+/// Track the most accessed keys of shards in a group by sampling, to find the
+/// key causing a hotspot.
+#[derive(Default)]
+pub struct HotKeyTracker {
+    num_accesses: AtomicU64,
+    shards: Mutex<HashMap<u64, ShardHotKeys>>,
+}
+
+impl AccessCount {
+    #[inline]
+    fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+
+    fn add(&mut self, access: Access, count: u64) {
+        match access {
+            Access::Read => self.reads += count,
+            Access::Write => self.writes += count,
+        }
+    }
+}
+
+impl ShardHotKeys {
+    fn new(now: Instant) -> Self {
+        ShardHotKeys {
+            current_start: now,
+            current: HashMap::default(),
+            previous: HashMap::default(),
+        }
+    }
+
+    fn rotate(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.current_start);
+        if elapsed >= WINDOW * 2 {
+            self.previous.clear();
+            self.current.clear();
+            self.current_start = now;
+        } else if elapsed >= WINDOW {
+            self.previous = std::mem::take(&mut self.current);
+            self.current_start += WINDOW;
+        }
+    }
+
+    fn record(&mut self, key: &[u8], access: Access, now: Instant) {
+        self.rotate(now);
+        if let Some(count) = self.current.get_mut(key) {
+            count.add(access, 1);
+            return;
+        }
+
+        let mut count = AccessCount::default();
+        if self.current.len() >= MAX_TRACKED_KEYS {
+            let coldest = self
+                .current
+                .iter()
+                .min_by_key(|(_, count)| count.total())
+                .map(|(key, _)| key.clone())
+                .expect("the tracked keys is not empty");
+            let evicted = self.current.remove(&coldest).unwrap_or_default();
+            count.add(access, evicted.total());
+        }
+        count.add(access, 1);
+        self.current.insert(key.to_owned(), count);
+    }
+
+    fn hot_keys(&mut self, limit: usize, now: Instant) -> Vec<(Vec<u8>, AccessCount)> {
+        self.rotate(now);
+        let mut merged = self.previous.clone();
+        for (key, count) in &self.current {
+            let merged = merged.entry(key.clone()).or_default();
+            merged.reads += count.reads;
+            merged.writes += count.writes;
+        }
+        let mut hot_keys = merged.into_iter().collect::<Vec<_>>();
+        hot_keys.sort_unstable_by(|a, b| b.1.total().cmp(&a.1.total()).then_with(|| a.0.cmp(&b.0)));
+        hot_keys.truncate(limit);
+        hot_keys
+    }
+}
+
+impl HotKeyTracker {
+    /// Sample an access to the key of the shard.
+    pub fn record(&self, shard_id: u64, key: &[u8], access: Access) {
+        if self.num_accesses.fetch_add(1, Ordering::Relaxed) % SAMPLE_INTERVAL != 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut shards = self.shards.lock().unwrap();
+        shards.entry(shard_id).or_insert_with(|| ShardHotKeys::new(now)).record(key, access, now);
+    }
+
+    /// Return the most accessed keys of the shard in the recent windows,
+    /// ordered by the number of accesses.
+    pub fn hot_keys(&self, shard_id: u64, limit: usize) -> Vec<HotKey> {
+        let mut shards = self.shards.lock().unwrap();
+        let Some(shard) = shards.get_mut(&shard_id) else {
+            return vec![];
+        };
+        shard
+            .hot_keys(limit, Instant::now())
+            .into_iter()
+            .map(|(key, count)| HotKey {
+                key,
+                reads: count.reads * SAMPLE_INTERVAL,
+                writes: count.writes * SAMPLE_INTERVAL,
+            })
+            .collect()
+    }
+
+    /// Remove the tracked keys of shards not in `shard_ids`.
+    pub fn retain(&self, shard_ids: &[u64]) {
+        self.shards.lock().unwrap().retain(|id, _| shard_ids.contains(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(hot_keys: &[(Vec<u8>, AccessCount)]) -> Vec<&[u8]> {
+        hot_keys.iter().map(|(key, _)| key.as_slice()).collect()
+    }
+
+    #[test]
+    fn top_k_by_accesses() {
+        let now = Instant::now();
+        let mut shard = ShardHotKeys::new(now);
+        for i in 0..10u8 {
+            for _ in 0..i {
+                shard.record(&[i], Access::Read, now);
+            }
+            shard.record(&[i], Access::Write, now);
+        }
+        let hot_keys = shard.hot_keys(3, now);
+        assert_eq!(keys(&hot_keys), vec![&[9u8][..], &[8], &[7]]);
+        assert_eq!(hot_keys[0].1.reads, 9);
+        assert_eq!(hot_keys[0].1.writes, 1);
+    }
+
+    #[test]
+    fn hot_key_is_kept_when_full() {
+        let now = Instant::now();
+        let mut shard = ShardHotKeys::new(now);
+        for i in 0..(MAX_TRACKED_KEYS * 4) as u64 {
+            shard.record(&i.to_be_bytes(), Access::Write, now);
+            shard.record(b"hot", Access::Read, now);
+        }
+        assert!(shard.current.len() <= MAX_TRACKED_KEYS);
+        let hot_keys = shard.hot_keys(1, now);
+        assert_eq!(keys(&hot_keys), vec![b"hot"]);
+        assert_eq!(hot_keys[0].1.reads, (MAX_TRACKED_KEYS * 4) as u64);
+    }
+
+    #[test]
+    fn sliding_windows() {
+        let now = Instant::now();
+        let mut shard = ShardHotKeys::new(now);
+        shard.record(b"a", Access::Read, now);
+        shard.record(b"a", Access::Read, now);
+
+        // The previous window is still counted.
+        let now = now + WINDOW;
+        shard.record(b"b", Access::Read, now);
+        let hot_keys = shard.hot_keys(2, now);
+        assert_eq!(keys(&hot_keys), vec![&b"a"[..], b"b"]);
+
+        let now = now + WINDOW;
+        let hot_keys = shard.hot_keys(2, now);
+        assert_eq!(keys(&hot_keys), vec![b"b"]);
+
+        let now = now + WINDOW * 2;
+        assert!(shard.hot_keys(2, now).is_empty());
+    }
+
+    #[test]
+    fn tracker_samples_accesses() {
+        let tracker = HotKeyTracker::default();
+        for _ in 0..SAMPLE_INTERVAL * 10 {
+            tracker.record(1, b"key", Access::Write);
+        }
+        let hot_keys = tracker.hot_keys(1, REPORT_HOT_KEYS);
+        assert_eq!(hot_keys.len(), 1);
+        assert_eq!(hot_keys[0].key, b"key");
+        assert_eq!(hot_keys[0].writes, SAMPLE_INTERVAL * 10);
+        assert!(tracker.hot_keys(2, REPORT_HOT_KEYS).is_empty());
+
+        tracker.retain(&[2]);
+        assert!(tracker.hot_keys(1, REPORT_HOT_KEYS).is_empty());
+    }
+}
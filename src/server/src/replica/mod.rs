@@ -13,34 +13,53 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod clone;
 mod eval;
 pub mod fsm;
+mod hot_keys;
 mod move_shard;
 pub mod retry;
+mod slow_log;
 mod state;
+mod usage;
+mod write_stats;
 
 use std::sync::atomic::AtomicI32;
 use std::sync::{Arc, Mutex};
 use std::task::Poll;
+use std::time::{Duration, Instant};
 
 use log::{info, warn};
 use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
 use serde::Serialize;
+use tracing::Instrument;
 
 use self::eval::acquire_row_latches;
 pub(crate) use self::eval::merge_scan_response;
 use self::eval::remote::RemoteLatchManager;
+use self::hot_keys::{Access, HotKeyTracker, REPORT_HOT_KEYS};
+use self::slow_log::{record_slow_op, OpStats};
 pub use self::state::{LeaseState, LeaseStateObserver};
-use crate::engine::GroupEngine;
+pub use self::usage::ShardUsage;
+use self::usage::UsageCache;
+pub use self::write_stats::WritePattern;
+use self::write_stats::WriteStats;
+use crate::constants::ROOT_GROUP_ID;
+use crate::engine::{GroupEngine, IngestStore};
 use crate::error::BusyReason;
 use crate::raftgroup::{
     perf_point_micros, write_initial_state, RaftGroup, ReadPolicy, WorkerPerfContext,
 };
 use crate::schedule::MoveReplicasProvider;
 use crate::serverpb::v1::*;
-use crate::{Error, RaftConfig, Result};
+use crate::transport::TransportManager;
+use crate::{DynamicConfig, Error, RaftConfig, Result};
+
+/// The timeout of a follower sealing and verifying the ingest files, which are
+/// read entirely.
+const CONFIRM_INGEST_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct ReplicaPerfContext {
@@ -61,6 +80,19 @@ enum MetaAclGuard<'a> {
     Write(tokio::sync::RwLockWriteGuard<'a, ()>),
 }
 
+/// The components of an opened replica, see [`Replica::new`].
+pub(crate) struct ReplicaParts {
+    pub info: Arc<ReplicaInfo>,
+    pub lease_state: Arc<Mutex<LeaseState>>,
+    pub raft_group: RaftGroup,
+    pub group_engine: GroupEngine,
+    pub ingest_store: IngestStore,
+    pub sekas_client: sekas_client::SekasClient,
+    pub transport_manager: TransportManager,
+    pub move_replicas_provider: Arc<MoveReplicasProvider>,
+    pub dynamic_config: Arc<DynamicConfig>,
+}
+
 /// ExecCtx contains the required infos during request execution.
 #[derive(Default, Clone)]
 pub struct ExecCtx {
@@ -82,11 +114,17 @@ where
 {
     info: Arc<ReplicaInfo>,
     group_engine: GroupEngine,
+    ingest_store: IngestStore,
     raft_group: RaftGroup,
     lease_state: Arc<Mutex<LeaseState>>,
+    transport_manager: TransportManager,
     move_replicas_provider: Arc<MoveReplicasProvider>,
     meta_acl: Arc<tokio::sync::RwLock<()>>,
     latch_mgr: RemoteLatchManager,
+    write_stats: WriteStats,
+    hot_keys: HotKeyTracker,
+    usage_cache: UsageCache,
+    dynamic_config: Arc<DynamicConfig>,
 }
 
 impl Replica {
@@ -111,25 +149,35 @@ impl Replica {
     }
 
     /// Open the existed replica of raft group.
-    pub(crate) fn new(
-        info: Arc<ReplicaInfo>,
-        lease_state: Arc<Mutex<LeaseState>>,
-        raft_group: RaftGroup,
-        group_engine: GroupEngine,
-        sekas_client: sekas_client::SekasClient,
-        move_replicas_provider: Arc<MoveReplicasProvider>,
-    ) -> Self {
+    pub(crate) fn new(parts: ReplicaParts) -> Self {
+        let ReplicaParts {
+            info,
+            lease_state,
+            raft_group,
+            group_engine,
+            ingest_store,
+            sekas_client,
+            transport_manager,
+            move_replicas_provider,
+            dynamic_config,
+        } = parts;
         let latch_mgr =
             RemoteLatchManager::new(sekas_client, group_engine.clone(), raft_group.clone());
         Replica {
             info,
             group_engine,
+            ingest_store,
             raft_group,
             lease_state,
+            transport_manager,
             move_replicas_provider,
             meta_acl: Arc::default(),
             // FIXME(walter) create latch manager if epoch changed.
             latch_mgr,
+            write_stats: WriteStats::default(),
+            hot_keys: HotKeyTracker::default(),
+            usage_cache: UsageCache::default(),
+            dynamic_config,
         }
     }
 
@@ -163,7 +211,7 @@ impl Replica {
         let _acl_guard = self.take_acl_guard(request).await;
         self.check_request_early(exec_ctx, request)?;
         log::trace!("group {} eval command {request:?}", self.info.group_id);
-        self.evaluate_command(exec_ctx, request).await
+        self.evaluate_and_record(exec_ctx, request).await
     }
 
     /// Execute group request. instead of be blocked, it will returns
@@ -180,7 +228,39 @@ impl Replica {
         let _acl_guard =
             self.try_take_acl_guard(request).ok_or(Error::ServiceIsBusy(BusyReason::AclGuard))?;
         self.check_request_early(&mut exec_ctx, request)?;
-        self.evaluate_command(&exec_ctx, request).await
+        self.evaluate_and_record(&exec_ctx, request).await
+    }
+
+    /// Evaluate the request, and log it if it is slow.
+    async fn evaluate_and_record(&self, exec_ctx: &ExecCtx, request: &Request) -> Result<Response> {
+        let start = Instant::now();
+        let mut stats = OpStats::default();
+        let result = self
+            .evaluate_command(exec_ctx, request, &mut stats)
+            .instrument(self.eval_span(request))
+            .await;
+        if let Some(threshold) = self.dynamic_config.slow_op_threshold() {
+            record_slow_op(&self.info, request, &stats, start.elapsed(), threshold);
+        }
+        result
+    }
+
+    fn eval_span(&self, request: &Request) -> tracing::Span {
+        let span = tracing::info_span!(
+            "replica.eval",
+            group_id = self.info.group_id,
+            replica_id = self.info.replica_id,
+            request = request.name(),
+            shard_id = tracing::field::Empty,
+            txn = tracing::field::Empty,
+        );
+        if let Some(shard_id) = request.shard_id() {
+            span.record("shard_id", shard_id);
+        }
+        if let Some(start_version) = request.txn_version() {
+            span.record("txn", start_version);
+        }
+        span
     }
 
     pub async fn on_leader(&self, source: &'static str, immediate: bool) -> Result<Option<u64>> {
@@ -253,6 +333,60 @@ impl Replica {
         self.lease_state.lock().unwrap().schedule_state.clone()
     }
 
+    /// Return the write pattern of the shard, it is only tracked by the leader.
+    #[inline]
+    pub fn write_pattern(&self, shard_id: u64) -> WritePattern {
+        self.write_stats.write_pattern(shard_id)
+    }
+
+    /// Estimate the split key of the shard from the recent writes, so the
+    /// shard is not scanned. For append pattern shards, the split key is near
+    /// the tail rather than the median, so that the hot tail is kept in a small
+    /// shard.
+    pub fn estimate_split_key(&self, shard_id: u64) -> Result<Option<Vec<u8>>> {
+        let shard = self.group_engine.shard_desc(shard_id)?;
+        Ok(self.write_stats.split_key(&shard))
+    }
+
+    /// Return the number of live keys of the shard.
+    #[inline]
+    pub fn num_live_keys(&self, shard_id: u64) -> Result<u64> {
+        self.group_engine.num_live_keys(shard_id)
+    }
+
+    /// Return the usage of the shard, it is cached for a while since it
+    /// requires a full scan of the shard.
+    pub fn shard_usage(&self, shard_id: u64) -> Result<ShardUsage> {
+        self.usage_cache.get_or_refresh(shard_id, || {
+            let (num_keys, logical_bytes) = self.group_engine.shard_usage(shard_id)?;
+            Ok(ShardUsage { num_keys, logical_bytes })
+        })
+    }
+
+    /// Return the usage and the hot keys of all shards of this group.
+    pub fn all_shard_usage(&self) -> Result<Vec<ShardStats>> {
+        let descriptor = self.descriptor();
+        let shard_ids = descriptor.shards.iter().map(|s| s.id).collect::<Vec<_>>();
+        self.usage_cache.retain(&shard_ids);
+        self.hot_keys.retain(&shard_ids);
+        let mut shard_stats = Vec::with_capacity(descriptor.shards.len());
+        for shard in &descriptor.shards {
+            let usage = self.shard_usage(shard.id)?;
+            // The keys sampled before a split might belong to the new shard now.
+            let mut hot_keys = self.hot_keys.hot_keys(shard.id, REPORT_HOT_KEYS);
+            hot_keys.retain(|k| sekas_schema::shard::belong_to(shard, &k.key));
+            shard_stats.push(ShardStats {
+                shard_id: shard.id,
+                collection_id: shard.collection_id,
+                num_keys: usage.num_keys,
+                logical_bytes: usage.logical_bytes,
+                hot_keys,
+                append_writes: self.write_pattern(shard.id) == WritePattern::Append,
+            });
+        }
+        Ok(shard_stats)
+    }
+
     pub async fn monitor(&self) -> Result<ReplicaPerfContext> {
         let take_acl_guard = perf_point_micros();
         let _acl_guard = self.take_read_acl_guard().await;
@@ -295,16 +429,24 @@ impl Replica {
     }
 
     /// Delegates the eval method for the given `Request`.
-    async fn evaluate_command(&self, exec_ctx: &ExecCtx, request: &Request) -> Result<Response> {
+    async fn evaluate_command(
+        &self,
+        exec_ctx: &ExecCtx,
+        request: &Request,
+        stats: &mut OpStats,
+    ) -> Result<Response> {
         // Acquire row latches one by one. The implementation guarantees that there will
         // be no deadlock, so waiting while holding `read/write_acl_guard` will
         // not affect other requests.
         log::trace!("group {} before acquire row latches", self.info.group_id);
+        let latch_start = Instant::now();
         let mut latches = acquire_row_latches(&self.latch_mgr, request).await?;
+        stats.latch_wait = latch_start.elapsed();
         log::trace!("group {} acquire all row latches", self.info.group_id);
         let (eval_result_opt, resp) = match &request {
             Request::Get(req) => {
                 let value = eval::get(exec_ctx, &self.group_engine, &self.latch_mgr, req).await?;
+                self.hot_keys.record(req.shard_id, &req.user_key, Access::Read);
                 let resp = ShardGetResponse { value };
                 (None, Response::Get(resp))
             }
@@ -367,8 +509,41 @@ impl Replica {
                 let resp = CreateShardResponse {};
                 (eval_result, Response::CreateShard(resp))
             }
+            Request::TruncateShard(req) => {
+                let new_shard = req
+                    .new_shard
+                    .as_ref()
+                    .cloned()
+                    .ok_or_else(|| Error::InvalidArgument("TruncateShard::new_shard".into()))?;
+                let eval_result =
+                    eval::truncate_shard(&self.group_engine, req.shard_id, new_shard)?;
+                (eval_result, Response::TruncateShard(TruncateShardResponse {}))
+            }
+            Request::FreezeShard(req) => {
+                let eval_result = eval::freeze_shard(&self.group_engine, req.shard_id, req.frozen)?;
+                (eval_result, Response::FreezeShard(FreezeShardResponse {}))
+            }
+            Request::SplitShard(req) => {
+                let mut split_key = req.split_key.clone();
+                if split_key.is_empty() && self.group_engine.shard_desc(req.new_shard_id).is_err() {
+                    split_key = self.estimate_split_key(req.shard_id)?.ok_or_else(|| {
+                        Error::InvalidArgument(format!(
+                            "shard {} has too few keys written recently to split",
+                            req.shard_id
+                        ))
+                    })?;
+                }
+                let eval_result = eval::split_shard(
+                    &self.group_engine,
+                    req.shard_id,
+                    &split_key,
+                    req.new_shard_id,
+                )?;
+                (eval_result, Response::SplitShard(SplitShardResponse {}))
+            }
             Request::ChangeReplicas(req) => {
                 if let Some(change) = &req.change_replicas {
+                    self.check_ingest_compacted(change).await?;
                     self.raft_group.change_config(change.clone()).await?;
                 }
                 let resp = ChangeReplicasResponse {};
@@ -384,6 +559,22 @@ impl Replica {
                 let resp = AcceptShardResponse {};
                 (Some(eval_result), Response::AcceptShard(resp))
             }
+            Request::Ingest(req) => {
+                // The files are sealed under a token unique to this proposal.
+                let token = uuid::Uuid::new_v4().to_string();
+                let eval_result = eval::ingest_files(
+                    exec_ctx,
+                    &self.group_engine,
+                    &self.ingest_store,
+                    req,
+                    &token,
+                )
+                .await?;
+                if eval_result.is_some() {
+                    self.confirm_ingest_files(req.shard_id, &token, &req.files).await?;
+                }
+                (eval_result, Response::Ingest(ShardIngestResponse {}))
+            }
             Request::Transfer(req) => {
                 info!(
                     "transfer leadership to {}. replica={}, group={}",
@@ -395,12 +586,96 @@ impl Replica {
         };
 
         if let Some(eval_result) = eval_result_opt {
-            self.raft_group.propose(eval_result).await?;
+            let propose_stats = self.raft_group.propose_with_stats(eval_result).await?;
+            stats.propose = propose_stats.propose;
+            stats.apply = propose_stats.apply;
+            self.record_writes(request);
         }
 
         Ok(resp)
     }
 
+    fn record_writes(&self, request: &Request) {
+        match request {
+            Request::Write(req) => {
+                for put in &req.puts {
+                    self.write_stats.record(req.shard_id, &put.key);
+                    self.hot_keys.record(req.shard_id, &put.key, Access::Write);
+                }
+                for del in &req.deletes {
+                    self.hot_keys.record(req.shard_id, &del.key, Access::Write);
+                }
+            }
+            Request::WriteIntent(WriteIntentRequest { shard_id, write: Some(write), .. }) => {
+                if let write_intent_request::Write::Put(put) = write {
+                    self.write_stats.record(*shard_id, &put.key);
+                }
+                self.hot_keys.record(*shard_id, write.user_key(), Access::Write);
+            }
+            _ => {}
+        }
+    }
+
+    /// Confirm that the ingest files are uploaded to all replicas before
+    /// proposing, the followers seal the files under the token and verify
+    /// them concurrently, the local files are sealed and verified when
+    /// evaluating. The replicas are not changed until the ingestion is
+    /// applied, since the acl guard is held.
+    async fn confirm_ingest_files(
+        &self,
+        shard_id: u64,
+        token: &str,
+        files: &[IngestFile],
+    ) -> Result<()> {
+        let confirms = self
+            .descriptor()
+            .replicas
+            .into_iter()
+            .filter(|replica| replica.id != self.info.replica_id)
+            .map(|replica| async move {
+                let client = self.transport_manager.find_node_client(replica.node_id)?;
+                let verify = client.verify_ingest_files(shard_id, token.to_owned(), files.to_vec());
+                let result = sekas_runtime::time::timeout(CONFIRM_INGEST_TIMEOUT, verify)
+                    .await
+                    .unwrap_or_else(|_| Err(tonic::Status::deadline_exceeded("confirm timeout")));
+                result.map_err(|status| {
+                    Error::InvalidArgument(format!(
+                        "ingest files are not uploaded to replica {} on node {}: {}",
+                        replica.id,
+                        replica.node_id,
+                        status.message()
+                    ))
+                })
+            });
+        futures::future::try_join_all(confirms).await?;
+        Ok(())
+    }
+
+    /// The replicas added replay the log entries instead of receiving a
+    /// snapshot if the logs are not compacted, but the ingest files are only
+    /// uploaded to the existing replicas. So the replicas are not added until
+    /// the entries ingesting files are compacted.
+    async fn check_ingest_compacted(&self, change: &ChangeReplicas) -> Result<()> {
+        let desc = self.descriptor();
+        let adds_replica = change.changes.iter().any(|c| {
+            c.change_type != ChangeReplicaType::Remove as i32
+                && !desc.replicas.iter().any(|r| r.id == c.replica_id)
+        });
+        if !adds_replica {
+            return Ok(());
+        }
+        let last_index = self.group_engine.ingest_state()?.last_index;
+        if last_index == 0 {
+            return Ok(());
+        }
+        let first_index =
+            self.raft_group.raft_group_state().await.map(|s| s.first_index).unwrap_or_default();
+        if first_index <= last_index {
+            return Err(Error::ServiceIsBusy(BusyReason::PendingIngest));
+        }
+        Ok(())
+    }
+
     fn check_request_early(&self, exec_ctx: &mut ExecCtx, req: &Request) -> Result<()> {
         let group_id = self.info.group_id;
         exec_ctx.group_id = group_id;
@@ -421,9 +696,20 @@ impl Replica {
             Ok(())
         } else if exec_ctx.epoch < lease_state.descriptor.epoch {
             Err(Error::EpochNotMatch(lease_state.descriptor.clone()))
-        } else if lease_state.has_shard_moving() && matches!(req, Request::AcceptShard(_)) {
-            // At the same time, there can only be one moving shard task.
+        } else if lease_state.has_shard_moving()
+            && matches!(
+                req,
+                Request::AcceptShard(_)
+                    | Request::TruncateShard(_)
+                    | Request::FreezeShard(_)
+                    | Request::SplitShard(_)
+            )
+        {
+            // At the same time, there can only be one moving shard task, and the shards
+            // are not truncated, frozen or split until the moving is finished.
             Err(Error::ServiceIsBusy(BusyReason::Moving))
+        } else if let Some(backoff) = self.admission_backoff(req) {
+            Err(Error::GroupBusy(group_id, backoff))
         } else {
             // If the current replica is the leader and has applied data in the current
             // term, it is expected that the input epoch should not be larger
@@ -436,6 +722,18 @@ impl Replica {
         }
     }
 
+    /// Return the backoff if the new write should be rejected, because the
+    /// raft log of this group falls behind. The requests to complete the
+    /// existing transactions and the root group are always admitted.
+    fn admission_backoff(&self, req: &Request) -> Option<Duration> {
+        if self.info.group_id == ROOT_GROUP_ID
+            || !matches!(req, Request::Write(_) | Request::WriteIntent(_))
+        {
+            return None;
+        }
+        self.raft_group.busy_backoff()
+    }
+
     fn check_leader_early(&self) -> Result<()> {
         let lease_state = self.lease_state.lock().unwrap();
         if !lease_state.is_ready_for_serving() {
@@ -519,6 +817,9 @@ fn is_change_meta_request(request: &Request) -> bool {
     match request {
         Request::ChangeReplicas(_)
         | Request::CreateShard(_)
+        | Request::TruncateShard(_)
+        | Request::FreezeShard(_)
+        | Request::SplitShard(_)
         | Request::AcceptShard(_)
         | Request::MoveReplicas(_)
         | Request::Transfer(_) => true,
@@ -527,6 +828,7 @@ fn is_change_meta_request(request: &Request) -> bool {
         | Request::Scan(_)
         | Request::WriteIntent(_)
         | Request::CommitIntent(_)
-        | Request::ClearIntent(_) => false,
+        | Request::ClearIntent(_)
+        | Request::Ingest(_) => false,
     }
 }
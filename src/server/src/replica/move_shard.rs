@@ -16,9 +16,10 @@
 use log::{debug, info};
 use sekas_api::server::v1::*;
 
-use super::eval::{ingest_value_set, LatchManager};
+use super::eval::{ingest_value_set, ingest_value_sets, LatchManager};
 use super::{LeaseState, Replica, ReplicaInfo};
 use crate::engine::WriteBatch;
+use crate::failpoint::fail_point;
 use crate::serverpb::v1::*;
 use crate::{Error, Result};
 
@@ -38,12 +39,46 @@ impl Replica {
         Ok(())
     }
 
-    /// Save the ingestion progress to support fast recovery.
-    pub async fn save_ingest_progress(&self, shard_id: u64, user_key: &[u8]) -> Result<()> {
+    /// Ingest a chunk of value sets in one proposal, the keys already exist are
+    /// skipped.
+    pub async fn ingest_value_sets(&self, shard_id: u64, value_sets: &[ValueSet]) -> Result<()> {
+        if value_sets.is_empty() {
+            return Ok(());
+        }
+
         let _acl_guard = self.take_read_acl_guard().await;
         self.check_moving_shard_request_early(shard_id)?;
-        let eval_result =
-            EvalResult { op: Some(SyncOp::ingest(user_key.to_vec())), ..Default::default() };
+
+        // ATTN: Sort shard keys before acquiring any latch, to avoid deadlock.
+        let mut keys = value_sets.iter().map(|v| v.user_key.as_slice()).collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+        let mut latch_guards = Vec::with_capacity(keys.len());
+        for key in keys {
+            latch_guards.push(self.latch_mgr.acquire(shard_id, key).await?);
+        }
+        let eval_result = match ingest_value_sets(&self.group_engine, shard_id, value_sets).await? {
+            Some(eval_result) => eval_result,
+            None => return Ok(()),
+        };
+        self.raft_group.propose(eval_result).await?;
+
+        Ok(())
+    }
+
+    /// Save the ingestion progress to support fast recovery, the moving will
+    /// resume from the last ingested key after leader changes.
+    pub async fn save_ingest_progress(
+        &self,
+        shard_id: u64,
+        user_key: &[u8],
+        moved_keys: u64,
+        moved_bytes: u64,
+    ) -> Result<()> {
+        let _acl_guard = self.take_read_acl_guard().await;
+        self.check_moving_shard_request_early(shard_id)?;
+        let op = SyncOp::ingest(user_key.to_vec(), moved_keys, moved_bytes);
+        let eval_result = EvalResult { op: Some(op), ..Default::default() };
         self.raft_group.propose(eval_result).await?;
         Ok(())
     }
@@ -72,11 +107,18 @@ impl Replica {
         self.update_move_shard_state(desc, MoveShardEvent::Setup).await
     }
 
-    pub async fn enter_pulling_step(&self, desc: &MoveShardDesc) -> Result<()> {
-        self.update_move_shard_state(desc, MoveShardEvent::Ingest).await
+    /// Enter the pulling step, the `total_keys` is the number of keys of the
+    /// shard in the source group, it is used to report the moving progress.
+    pub async fn enter_pulling_step(&self, desc: &MoveShardDesc, total_keys: u64) -> Result<()> {
+        let mut op = SyncOp::move_shard(MoveShardEvent::Ingest, desc.clone());
+        if let Some(move_shard) = op.move_shard.as_mut() {
+            move_shard.total_keys = total_keys;
+        }
+        self.propose_move_shard_op(desc, MoveShardEvent::Ingest, op).await
     }
 
     pub async fn commit_shard_moving(&self, desc: &MoveShardDesc) -> Result<()> {
+        fail_point!("move_shard_commit");
         self.update_move_shard_state(desc, MoveShardEvent::Commit).await
     }
 
@@ -92,6 +134,16 @@ impl Replica {
         &self,
         desc: &MoveShardDesc,
         event: MoveShardEvent,
+    ) -> Result<()> {
+        let op = SyncOp::move_shard(event, desc.clone());
+        self.propose_move_shard_op(desc, event, op).await
+    }
+
+    async fn propose_move_shard_op(
+        &self,
+        desc: &MoveShardDesc,
+        event: MoveShardEvent,
+        op: Box<SyncOp>,
     ) -> Result<()> {
         debug!(
             "update moving shard state. replica={}, group={}, desc={}, event={:?}",
@@ -105,8 +157,7 @@ impl Replica {
             return Ok(());
         }
 
-        let eval_result =
-            EvalResult { op: Some(SyncOp::move_shard(event, desc.clone())), ..Default::default() };
+        let eval_result = EvalResult { op: Some(op), ..Default::default() };
         self.raft_group.propose(eval_result).await?;
 
         Ok(())
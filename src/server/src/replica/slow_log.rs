@@ -0,0 +1,136 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use log::warn;
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::write_intent_request::Write;
+
+use super::ReplicaInfo;
+use crate::node::metrics::NODE_SLOW_OP_TOTAL;
+
+/// Only the prefix of the key is hashed and logged, the user keys might be
+/// sensitive.
+const KEY_PREFIX_LEN: usize = 16;
+
+/// The durations of the steps of a shard request.
+#[derive(Debug, Default)]
+pub(crate) struct OpStats {
+    /// Wait for the row latches.
+    pub latch_wait: Duration,
+    /// Replicate the proposal, excluding `apply`.
+    pub propose: Duration,
+    /// Apply the proposal to the state machine.
+    pub apply: Duration,
+}
+
+/// Log a structured record of the shard request and increment the metric, if
+/// it exceeds the threshold.
+pub(crate) fn record_slow_op(
+    info: &ReplicaInfo,
+    request: &Request,
+    stats: &OpStats,
+    elapsed: Duration,
+    threshold: Duration,
+) {
+    if elapsed < threshold {
+        return;
+    }
+    let Some(key) = target_key(request) else {
+        // Only the shard reads, writes and txn steps are recorded.
+        return;
+    };
+
+    NODE_SLOW_OP_TOTAL.with_label_values(&[request.name()]).inc();
+    warn!(
+        "slow op {}: group={} replica={} shard={} txn={} key_prefix_hash={:08x} \
+         elapsed={elapsed:?} latch_wait={:?} propose={:?} apply={:?}",
+        request.name(),
+        info.group_id,
+        info.replica_id,
+        request.shard_id().unwrap_or_default(),
+        request.txn_version().unwrap_or_default(),
+        key_prefix_hash(key),
+        stats.latch_wait,
+        stats.propose,
+        stats.apply,
+    );
+}
+
+/// Return the (first) key accessed by the shard request.
+fn target_key(request: &Request) -> Option<&[u8]> {
+    match request {
+        Request::Get(req) => Some(&req.user_key),
+        Request::Scan(req) => {
+            Some(req.prefix.as_deref().or(req.start_key.as_deref()).unwrap_or_default())
+        }
+        Request::Write(req) => Some(
+            req.puts
+                .first()
+                .map(|put| put.key.as_slice())
+                .or_else(|| req.deletes.first().map(|del| del.key.as_slice()))
+                .unwrap_or_default(),
+        ),
+        Request::WriteIntent(req) => match req.write.as_ref() {
+            Some(Write::Put(put)) => Some(&put.key),
+            Some(Write::Delete(del)) => Some(&del.key),
+            None => Some(&[]),
+        },
+        Request::CommitIntent(req) => Some(&req.user_key),
+        Request::ClearIntent(req) => Some(&req.user_key),
+        _ => None,
+    }
+}
+
+fn key_prefix_hash(key: &[u8]) -> u32 {
+    crc32fast::hash(&key[..key.len().min(KEY_PREFIX_LEN)])
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::*;
+
+    use super::*;
+
+    #[test]
+    fn slow_op_target_key() {
+        let req = Request::Get(ShardGetRequest { user_key: b"key".to_vec(), ..Default::default() });
+        assert_eq!(target_key(&req), Some(b"key".as_slice()));
+
+        let req = Request::Write(ShardWriteRequest {
+            deletes: vec![DeleteRequest { key: b"b".to_vec(), ..Default::default() }],
+            puts: vec![PutRequest { key: b"a".to_vec(), ..Default::default() }],
+            ..Default::default()
+        });
+        assert_eq!(target_key(&req), Some(b"a".as_slice()));
+
+        let req = Request::Scan(ShardScanRequest {
+            prefix: Some(b"prefix".to_vec()),
+            start_key: Some(b"start".to_vec()),
+            ..Default::default()
+        });
+        assert_eq!(target_key(&req), Some(b"prefix".as_slice()));
+
+        let req = Request::Transfer(TransferRequest { transferee: 1 });
+        assert_eq!(target_key(&req), None);
+    }
+
+    #[test]
+    fn slow_op_key_prefix_hash() {
+        let key = [1u8; KEY_PREFIX_LEN];
+        assert_eq!(key_prefix_hash(&key), key_prefix_hash(&[key.as_slice(), b"suffix"].concat()));
+        assert_ne!(key_prefix_hash(b"a"), key_prefix_hash(b"b"));
+    }
+}
@@ -220,6 +220,14 @@ impl StateMachineObserver for LeaseStateObserver {
             }
         }
     }
+
+    fn on_move_shard_progress_updated(&mut self, move_shard_state: Option<MoveShardState>) {
+        // The move shard controller is not notified, it only cares about the step
+        // changes. The progress is saved so that the new leader resumes the
+        // moving from it.
+        let mut lease_state = self.lease_state.lock().unwrap();
+        lease_state.move_shard_state = move_shard_state;
+    }
 }
 
 impl ScheduleStateObserver for LeaseStateObserver {
@@ -0,0 +1,96 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::Result;
+
+/// The interval to refresh the usage of a shard, counting the usage requires
+/// a full scan of the shard, so it is cached between two stats collections.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShardUsage {
+    /// The number of live keys.
+    pub num_keys: u64,
+    /// The sum of the size of live keys and values.
+    pub logical_bytes: u64,
+}
+
+/// Cache the usage of shards in a group.
+#[derive(Default)]
+pub struct UsageCache {
+    shards: Mutex<HashMap<u64, (Instant, ShardUsage)>>,
+}
+
+impl UsageCache {
+    /// Return the cached usage of the shard, the usage is recomputed by
+    /// `count` if it is missing or expired.
+    pub fn get_or_refresh<F>(&self, shard_id: u64, count: F) -> Result<ShardUsage>
+    where
+        F: FnOnce() -> Result<ShardUsage>,
+    {
+        self.get_or_refresh_with_interval(shard_id, REFRESH_INTERVAL, count)
+    }
+
+    fn get_or_refresh_with_interval<F>(
+        &self,
+        shard_id: u64,
+        interval: Duration,
+        count: F,
+    ) -> Result<ShardUsage>
+    where
+        F: FnOnce() -> Result<ShardUsage>,
+    {
+        if let Some((updated_at, usage)) = self.shards.lock().unwrap().get(&shard_id) {
+            if updated_at.elapsed() < interval {
+                return Ok(*usage);
+            }
+        }
+
+        let usage = count()?;
+        self.shards.lock().unwrap().insert(shard_id, (Instant::now(), usage));
+        Ok(usage)
+    }
+
+    /// Remove the cached usage of shards not in `shard_ids`.
+    pub fn retain(&self, shard_ids: &[u64]) {
+        self.shards.lock().unwrap().retain(|id, _| shard_ids.contains(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_cache_refresh() {
+        let cache = UsageCache::default();
+        let usage = ShardUsage { num_keys: 1, logical_bytes: 10 };
+        assert_eq!(cache.get_or_refresh(1, || Ok(usage)).unwrap(), usage);
+
+        // The cached usage is returned before it expires.
+        let new_usage = ShardUsage { num_keys: 2, logical_bytes: 20 };
+        assert_eq!(cache.get_or_refresh(1, || Ok(new_usage)).unwrap(), usage);
+        assert_eq!(
+            cache.get_or_refresh_with_interval(1, Duration::ZERO, || Ok(new_usage)).unwrap(),
+            new_usage
+        );
+
+        cache.retain(&[2]);
+        assert_eq!(cache.get_or_refresh(1, || Ok(usage)).unwrap(), usage);
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use sekas_api::server::v1::ShardDesc;
+
+/// The minimum number of writes required before judging the write pattern.
+const MIN_SAMPLES: u64 = 128;
+
+/// The statistics are halved once the number of writes exceeds this window,
+/// so that the recent writes dominate the write pattern.
+const SAMPLE_WINDOW: u64 = 4096;
+
+/// The ratio of ascending writes required to treat a shard as append pattern.
+const APPEND_RATIO: f64 = 0.9;
+
+/// The number of the recent write keys kept for each shard, the split key is
+/// estimated from them instead of scanning the shard.
+const MAX_RECENT_KEYS: usize = 256;
+
+/// The relative position of the split key for append pattern shards, the hot
+/// tail is kept in a small shard so that it can be moved independently.
+pub const TAIL_SPLIT_RATIO: f64 = 0.9;
+
+/// The relative position of the split key for other shards.
+pub const MEDIAN_SPLIT_RATIO: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritePattern {
+    /// There are not enough writes to judge the pattern.
+    Unknown,
+    /// The write keys are monotonically increasing.
+    Append,
+    Random,
+}
+
+#[derive(Default)]
+struct ShardWriteStats {
+    /// The recent write keys, in the order of writes.
+    recent_keys: VecDeque<Vec<u8>>,
+    num_writes: u64,
+    num_ascending: u64,
+}
+
+/// Track the write keys of shards in a group, to predict the write pattern
+/// for split decisions.
+#[derive(Default)]
+pub struct WriteStats {
+    shards: Mutex<HashMap<u64, ShardWriteStats>>,
+}
+
+impl WritePattern {
+    /// Return the relative position of split key for this write pattern.
+    #[inline]
+    pub fn split_ratio(&self) -> f64 {
+        match self {
+            WritePattern::Append => TAIL_SPLIT_RATIO,
+            WritePattern::Unknown | WritePattern::Random => MEDIAN_SPLIT_RATIO,
+        }
+    }
+}
+
+impl ShardWriteStats {
+    fn record(&mut self, key: &[u8]) {
+        if self.recent_keys.back().map(|last_key| last_key.as_slice() < key).unwrap_or_default() {
+            self.num_ascending += 1;
+        }
+        // Reuse the buffer of the oldest key.
+        let mut buf = if self.recent_keys.len() >= MAX_RECENT_KEYS {
+            self.recent_keys.pop_front().unwrap_or_default()
+        } else {
+            Vec::with_capacity(key.len())
+        };
+        buf.clear();
+        buf.extend_from_slice(key);
+        self.recent_keys.push_back(buf);
+        self.num_writes += 1;
+        if self.num_writes >= SAMPLE_WINDOW {
+            self.num_writes /= 2;
+            self.num_ascending /= 2;
+        }
+    }
+
+    fn write_pattern(&self) -> WritePattern {
+        if self.num_writes < MIN_SAMPLES {
+            WritePattern::Unknown
+        } else if self.num_ascending as f64 >= self.num_writes as f64 * APPEND_RATIO {
+            WritePattern::Append
+        } else {
+            WritePattern::Random
+        }
+    }
+
+    /// Estimate the split key from the recent write keys in the shard, the
+    /// relative position of the split key depends on the write pattern.
+    fn split_key(&self, shard: &ShardDesc) -> Option<Vec<u8>> {
+        let mut keys = self
+            .recent_keys
+            .iter()
+            .filter(|key| sekas_schema::shard::belong_to(shard, key))
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+        if keys.len() < 2 {
+            return None;
+        }
+        // The split key is the first key of the right half, so it must be greater than
+        // the smallest key, which is not less than the start of shard.
+        let ratio = self.write_pattern().split_ratio();
+        let index = ((keys.len() as f64 * ratio) as usize).clamp(1, keys.len() - 1);
+        Some(keys[index].to_vec())
+    }
+}
+
+impl WriteStats {
+    /// Record a write of the shard.
+    pub fn record(&self, shard_id: u64, key: &[u8]) {
+        let mut shards = self.shards.lock().unwrap();
+        shards.entry(shard_id).or_default().record(key);
+    }
+
+    /// Return the write pattern of the shard.
+    pub fn write_pattern(&self, shard_id: u64) -> WritePattern {
+        let shards = self.shards.lock().unwrap();
+        shards.get(&shard_id).map(ShardWriteStats::write_pattern).unwrap_or(WritePattern::Unknown)
+    }
+
+    /// Estimate the split key of the shard from the recent writes, `None` is
+    /// returned if there are too few keys written recently.
+    pub fn split_key(&self, shard: &ShardDesc) -> Option<Vec<u8>> {
+        let shards = self.shards.lock().unwrap();
+        shards.get(&shard.id).and_then(|stats| stats.split_key(shard))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_pattern() {
+        let stats = WriteStats::default();
+        assert_eq!(stats.write_pattern(1), WritePattern::Unknown);
+
+        for i in 0..MIN_SAMPLES {
+            stats.record(1, &i.to_be_bytes());
+        }
+        assert_eq!(stats.write_pattern(1), WritePattern::Append);
+        assert_eq!(stats.write_pattern(1).split_ratio(), TAIL_SPLIT_RATIO);
+        assert_eq!(stats.write_pattern(2), WritePattern::Unknown);
+    }
+
+    #[test]
+    fn random_pattern() {
+        let stats = WriteStats::default();
+        for i in 0..MIN_SAMPLES {
+            // Alternate between ascending and descending keys.
+            let key = if i % 2 == 0 { i } else { u64::MAX - i };
+            stats.record(1, &key.to_be_bytes());
+        }
+        assert_eq!(stats.write_pattern(1), WritePattern::Random);
+        assert_eq!(stats.write_pattern(1).split_ratio(), MEDIAN_SPLIT_RATIO);
+    }
+
+    #[test]
+    fn recent_writes_dominate() {
+        let stats = WriteStats::default();
+        for i in 0..SAMPLE_WINDOW {
+            stats.record(1, &(u64::MAX - i).to_be_bytes());
+        }
+        assert_eq!(stats.write_pattern(1), WritePattern::Random);
+
+        for i in 0..(SAMPLE_WINDOW * 4) {
+            stats.record(1, &i.to_be_bytes());
+        }
+        assert_eq!(stats.write_pattern(1), WritePattern::Append);
+    }
+
+    #[test]
+    fn split_key_of_recent_writes() {
+        use sekas_api::server::v1::RangePartition;
+
+        let shard = ShardDesc {
+            id: 1,
+            range: Some(RangePartition { start: 0u64.to_be_bytes().to_vec(), end: vec![] }),
+            ..Default::default()
+        };
+        let stats = WriteStats::default();
+        assert!(stats.split_key(&shard).is_none());
+        stats.record(1, &0u64.to_be_bytes());
+        assert!(stats.split_key(&shard).is_none());
+
+        // The split key of random pattern shards is the median.
+        for i in 0..MIN_SAMPLES {
+            let key = if i % 2 == 0 { i } else { 1000 - i };
+            stats.record(1, &key.to_be_bytes());
+        }
+        assert_eq!(stats.write_pattern(1), WritePattern::Random);
+        let split_key = stats.split_key(&shard).unwrap();
+        let keys = stats.shards.lock().unwrap()[&1].recent_keys.len();
+        assert!(keys <= MAX_RECENT_KEYS);
+        assert!(split_key > 32u64.to_be_bytes().to_vec());
+        assert!(split_key < 968u64.to_be_bytes().to_vec());
+
+        // The split key of append pattern shards is near the tail.
+        for i in 1000..(1000 + MAX_RECENT_KEYS as u64 * 16) {
+            stats.record(1, &i.to_be_bytes());
+        }
+        assert_eq!(stats.write_pattern(1), WritePattern::Append);
+        let last_key = 1000 + MAX_RECENT_KEYS as u64 * 16 - 1;
+        let tail = (MAX_RECENT_KEYS as f64 * (1.0 - TAIL_SPLIT_RATIO)) as u64;
+        let split_key = stats.split_key(&shard).unwrap();
+        assert_eq!(split_key, (last_key - tail).to_be_bytes().to_vec());
+
+        // The keys out of the shard are ignored.
+        let shard = ShardDesc {
+            id: 1,
+            range: Some(RangePartition { start: vec![], end: 1000u64.to_be_bytes().to_vec() }),
+            ..Default::default()
+        };
+        assert!(stats.split_key(&shard).is_none());
+    }
+}
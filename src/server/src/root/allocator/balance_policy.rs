@@ -0,0 +1,165 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use sekas_api::server::v1::NodeDesc;
+
+use crate::root::OngoingStats;
+use crate::BalancePolicyKind;
+
+/// The policy to measure the load of nodes. The replicas are moved from the
+/// overfull nodes to the underfull nodes, until the load per weight of each
+/// node is close to the mean.
+pub trait BalancePolicy: Send + Sync {
+    /// The name of the policy, it is shown in the reasons of decisions.
+    fn name(&self) -> &'static str;
+
+    /// The load of the node.
+    fn node_load(&self, node: &NodeDesc) -> f64;
+
+    /// The load moved along with one replica of the node.
+    fn replica_load(&self, node: &NodeDesc) -> f64;
+}
+
+/// Create the balance policy selected by the config.
+pub fn new_balance_policy(
+    kind: BalancePolicyKind,
+    ongoing_stats: Arc<OngoingStats>,
+) -> Arc<dyn BalancePolicy> {
+    match kind {
+        BalancePolicyKind::ReplicaCount => Arc::new(CountBasedPolicy { ongoing_stats }),
+        BalancePolicyKind::Size => Arc::new(SizeBasedPolicy { ongoing_stats }),
+        BalancePolicyKind::Load => Arc::new(LoadBasedPolicy { ongoing_stats }),
+    }
+}
+
+/// Balance the number of replicas, including the replicas in moving.
+pub struct CountBasedPolicy {
+    ongoing_stats: Arc<OngoingStats>,
+}
+
+impl BalancePolicy for CountBasedPolicy {
+    fn name(&self) -> &'static str {
+        "replica count"
+    }
+
+    fn node_load(&self, node: &NodeDesc) -> f64 {
+        node_replica_count(node, &self.ongoing_stats) as f64
+    }
+
+    fn replica_load(&self, _node: &NodeDesc) -> f64 {
+        1.0
+    }
+}
+
+/// Balance the used disk space reported by heartbeats, the nodes not reported
+/// yet are considered as empty.
+pub struct SizeBasedPolicy {
+    ongoing_stats: Arc<OngoingStats>,
+}
+
+impl BalancePolicy for SizeBasedPolicy {
+    fn name(&self) -> &'static str {
+        "size"
+    }
+
+    fn node_load(&self, node: &NodeDesc) -> f64 {
+        self.ongoing_stats
+            .get_node_stats(node.id)
+            .map(|s| s.total_space.saturating_sub(s.available_space) as f64)
+            .unwrap_or_default()
+    }
+
+    fn replica_load(&self, node: &NodeDesc) -> f64 {
+        mean_replica_load(self.node_load(node), node, &self.ongoing_stats)
+    }
+}
+
+/// Balance the read and write QPS reported by heartbeats, the nodes not
+/// reported yet are considered as idle.
+pub struct LoadBasedPolicy {
+    ongoing_stats: Arc<OngoingStats>,
+}
+
+impl BalancePolicy for LoadBasedPolicy {
+    fn name(&self) -> &'static str {
+        "load"
+    }
+
+    fn node_load(&self, node: &NodeDesc) -> f64 {
+        self.ongoing_stats
+            .get_node_stats(node.id)
+            .map(|s| (s.read_qps + s.write_qps) as f64)
+            .unwrap_or_default()
+    }
+
+    fn replica_load(&self, node: &NodeDesc) -> f64 {
+        mean_replica_load(self.node_load(node), node, &self.ongoing_stats)
+    }
+}
+
+/// Return the replica count of the node, the replicas in moving are counted.
+pub(super) fn node_replica_count(n: &NodeDesc, ongoing_stats: &OngoingStats) -> u64 {
+    let cnt = n.capacity.as_ref().unwrap().replica_count as i64;
+    let delta = ongoing_stats.get_node_delta(n.id);
+    (cnt + delta.replica_count).max(0) as u64
+}
+
+/// Return the mean load of the replicas of the node.
+fn mean_replica_load(node_load: f64, n: &NodeDesc, ongoing_stats: &OngoingStats) -> f64 {
+    match node_replica_count(n, ongoing_stats) {
+        0 => 0.0,
+        cnt => node_load / cnt as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::{NodeCapacity, NodeStats};
+
+    use super::*;
+
+    #[test]
+    fn measure_node_load_by_policy() {
+        let node = NodeDesc {
+            id: 1,
+            capacity: Some(NodeCapacity { replica_count: 4, ..Default::default() }),
+            ..Default::default()
+        };
+        let ongoing_stats = Arc::new(OngoingStats::default());
+        let count = new_balance_policy(BalancePolicyKind::ReplicaCount, ongoing_stats.clone());
+        let size = new_balance_policy(BalancePolicyKind::Size, ongoing_stats.clone());
+        let load = new_balance_policy(BalancePolicyKind::Load, ongoing_stats.clone());
+        assert_eq!(count.node_load(&node), 4.0);
+        assert_eq!(count.replica_load(&node), 1.0);
+        assert_eq!(size.node_load(&node), 0.0);
+        assert_eq!(load.replica_load(&node), 0.0);
+
+        ongoing_stats.update_node_stats(
+            1,
+            NodeStats {
+                total_space: 1000,
+                available_space: 600,
+                read_qps: 10.0,
+                write_qps: 30.0,
+                ..Default::default()
+            },
+        );
+        assert_eq!(size.node_load(&node), 400.0);
+        assert_eq!(size.replica_load(&node), 100.0);
+        assert_eq!(load.node_load(&node), 40.0);
+        assert_eq!(load.replica_load(&node), 10.0);
+    }
+}
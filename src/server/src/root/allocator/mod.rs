@@ -16,20 +16,24 @@ use std::sync::Arc;
 
 use sekas_api::server::v1::{GroupDesc, NodeDesc};
 
+use self::balance_policy::{new_balance_policy, BalancePolicy};
 use self::policy_leader_cnt::LeaderCountPolicy;
 use self::policy_replica_cnt::ReplicaCountPolicy;
 use self::policy_shard_cnt::ShardCountPolicy;
 use self::source::NodeFilter;
 use super::{metrics, OngoingStats, RootShared};
-use crate::constants::REPLICA_PER_GROUP;
-use crate::{Result, RootConfig};
+use crate::constants::{REPLICA_PER_GROUP, SYSTEM_TIER_LABEL};
+use crate::{DynamicConfig, Result, RootConfig};
 
 #[cfg(test)]
 mod sim_test;
 
+mod balance_policy;
 mod policy_leader_cnt;
 mod policy_replica_cnt;
 mod policy_shard_cnt;
+#[cfg(feature = "sim")]
+pub mod sim;
 mod source;
 
 pub use source::{AllocSource, SysAllocSource};
@@ -70,6 +74,8 @@ pub struct TransferLeader {
     pub src_replica: u64,
     pub target_node: u64,
     pub target_replica: u64,
+    /// The scores compared to make this decision.
+    pub reason: String,
 }
 
 #[derive(Clone, Debug)]
@@ -78,6 +84,8 @@ pub struct ReallocateReplica {
     pub source_node: u64,
     pub source_replica: u64,
     pub target_node: NodeDesc,
+    /// The scores compared to make this decision.
+    pub reason: String,
 }
 
 #[derive(Clone, Debug)]
@@ -85,6 +93,8 @@ pub struct ReallocateShard {
     pub shard: u64,
     pub source_group: u64,
     pub target_group: u64,
+    /// The scores compared to make this decision.
+    pub reason: String,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -98,12 +108,20 @@ enum BalanceStatus {
 pub struct Allocator<T: AllocSource> {
     alloc_source: Arc<T>,
     ongoing_stats: Arc<OngoingStats>,
+    balance_policy: Arc<dyn BalancePolicy>,
     config: RootConfig,
+    dynamic_config: Arc<DynamicConfig>,
 }
 
 impl<T: AllocSource> Allocator<T> {
-    pub fn new(alloc_source: Arc<T>, ongoing_stats: Arc<OngoingStats>, config: RootConfig) -> Self {
-        Self { alloc_source, config, ongoing_stats }
+    pub fn new(
+        alloc_source: Arc<T>,
+        ongoing_stats: Arc<OngoingStats>,
+        config: RootConfig,
+        dynamic_config: Arc<DynamicConfig>,
+    ) -> Self {
+        let balance_policy = new_balance_policy(config.balance_policy, ongoing_stats.clone());
+        Self { alloc_source, config, dynamic_config, ongoing_stats, balance_policy }
     }
 
     pub fn replicas_per_group(&self) -> usize {
@@ -112,7 +130,7 @@ impl<T: AllocSource> Allocator<T> {
 
     /// Compute group change action.
     pub async fn compute_group_action(&self) -> Result<GroupAction> {
-        if !self.config.enable_group_balance {
+        if !self.dynamic_config.enable_group_balance() {
             return Ok(GroupAction::Noop);
         }
 
@@ -148,19 +166,31 @@ impl<T: AllocSource> Allocator<T> {
 
     /// Compute replica change action.
     pub async fn compute_replica_action(&self) -> Result<Vec<ReplicaAction>> {
-        if !self.config.enable_replica_balance {
+        if !self.dynamic_config.enable_replica_balance() {
             return Ok(vec![]);
         }
 
         // compute_group_action refreshed.
         // self.alloc_source.refresh_all().await?;
 
-        // TODO: try qps rebalance.
+        let policy = ReplicaCountPolicy::with(
+            self.alloc_source.to_owned(),
+            self.ongoing_stats.to_owned(),
+            self.balance_policy.clone(),
+        );
+
+        // try honor the system tier placement.
+        if let Some(action) = policy.compute_tier_placement() {
+            return Ok(vec![action]);
+        }
+
+        // try honor the placement constraints of collections.
+        if let Some(action) = policy.compute_constraint_placement() {
+            return Ok(vec![action]);
+        }
 
         // try replica-count rebalance.
-        let actions =
-            ReplicaCountPolicy::with(self.alloc_source.to_owned(), self.ongoing_stats.to_owned())
-                .compute_balance()?;
+        let actions = policy.compute_balance()?;
         if !actions.is_empty() {
             return Ok(actions);
         }
@@ -169,7 +199,7 @@ impl<T: AllocSource> Allocator<T> {
     }
 
     pub async fn compute_shard_action(&self) -> Result<Vec<ShardAction>> {
-        if !self.config.enable_shard_balance {
+        if !self.dynamic_config.enable_shard_balance() {
             return Ok(vec![]);
         }
 
@@ -187,31 +217,59 @@ impl<T: AllocSource> Allocator<T> {
         Ok(Vec::new())
     }
 
-    /// Allocate new replica in one group.
+    /// Allocate new replica in one group. The replicas of system group are
+    /// constrained to the system tier nodes, and the replicas are only placed
+    /// on the nodes satisfying the placement constraints of the group.
     pub async fn allocate_group_replica(
         &self,
         existing_replica_nodes: Vec<u64>,
         wanted_count: usize,
+        system_group: bool,
+        constraints: &[String],
     ) -> Result<Vec<NodeDesc>> {
         self.alloc_source.refresh_all().await?;
 
-        ReplicaCountPolicy::with(self.alloc_source.to_owned(), self.ongoing_stats.to_owned())
-            .allocate_group_replica(existing_replica_nodes, wanted_count)
+        ReplicaCountPolicy::with(
+            self.alloc_source.to_owned(),
+            self.ongoing_stats.to_owned(),
+            self.balance_policy.clone(),
+        )
+        .allocate_group_replica(
+            existing_replica_nodes,
+            wanted_count,
+            system_group,
+            constraints,
+        )
     }
 
-    /// Find a group to place shard.
-    pub async fn place_group_for_shard(&self, n: usize) -> Result<Vec<GroupDesc>> {
+    /// Find a group to place shard, the groups satisfying the placement
+    /// constraints of the shard are preferred.
+    pub async fn place_group_for_shard(
+        &self,
+        n: usize,
+        constraints: &[String],
+    ) -> Result<Vec<GroupDesc>> {
         self.alloc_source.refresh_all().await?;
 
-        ShardCountPolicy::with(self.alloc_source.to_owned()).allocate_shard(n)
+        ShardCountPolicy::with(self.alloc_source.to_owned()).allocate_shard(n, constraints)
     }
 
     pub async fn compute_leader_action(&self) -> Result<Vec<LeaderAction>> {
-        if !self.config.enable_leader_balance {
+        if !self.dynamic_config.enable_leader_balance() {
             return Ok(vec![]);
         }
         // self.alloc_source.refresh_all().await?;
-        match LeaderCountPolicy::with(self.alloc_source.to_owned()).compute_balance()? {
+        let mut policy = LeaderCountPolicy::with(self.alloc_source.to_owned());
+        if self.config.exclude_lagging_leader_targets {
+            let lagging = self
+                .ongoing_stats
+                .get_lagging_replicas(self.config.replica_apply_lag_persist())
+                .into_iter()
+                .map(|r| r.replica_id)
+                .collect();
+            policy = policy.exclude_replicas(lagging);
+        }
+        match policy.compute_balance()? {
             LeaderAction::Noop => {}
             e @ LeaderAction::Shed { .. } => return Ok(vec![e]),
         }
@@ -235,7 +293,12 @@ impl<T: AllocSource> Allocator<T> {
     }
 
     fn desired_groups(&self, replicas_per_group: usize) -> usize {
-        let nodes = self.alloc_source.nodes(NodeFilter::NotDecommissioned);
+        let mut nodes = self.alloc_source.nodes(NodeFilter::NotDecommissioned);
+        // The user groups are not placed on the system tier nodes, unless there are no
+        // enough user nodes.
+        if nodes.iter().filter(|n| !is_system_tier(n)).count() >= replicas_per_group {
+            nodes.retain(|n| !is_system_tier(n));
+        }
         let total_nodes = nodes.len();
         let total_cpus = nodes
             .iter()
@@ -252,6 +315,36 @@ impl<T: AllocSource> Allocator<T> {
     }
 }
 
+/// Return the capacity weight of the node, the cpu nums scaled by the
+/// configured weight factor, so the weights of nodes are on the same scale
+/// whether the factor is configured or not.
+fn node_weight(n: &NodeDesc) -> f64 {
+    let capacity = n.capacity.as_ref().unwrap();
+    let factor = if capacity.weight > 0.0 { capacity.weight } else { 1.0 };
+    f64::max(capacity.cpu_nums, 1.0) * factor
+}
+
+/// Return whether the node is labeled as the system tier.
+fn is_system_tier(n: &NodeDesc) -> bool {
+    n.labels.iter().any(|label| label == SYSTEM_TIER_LABEL)
+}
+
+/// Return whether the node has all labels required by the placement
+/// constraints.
+fn satisfy_constraints(n: &NodeDesc, constraints: &[String]) -> bool {
+    constraints.iter().all(|c| n.labels.contains(c))
+}
+
+/// Return the placement constraints of the group, which is the union of the
+/// constraints of its shards.
+pub(super) fn group_constraints(group: &GroupDesc) -> Vec<String> {
+    let mut constraints =
+        group.shards.iter().flat_map(|s| s.constraints.iter().cloned()).collect::<Vec<_>>();
+    constraints.sort_unstable();
+    constraints.dedup();
+    constraints
+}
+
 // Allocate Group's replica between nodes.
 impl<T: AllocSource> Allocator<T> {}
 
@@ -14,19 +14,21 @@
 // limitations under the License.
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use log::debug;
 use sekas_api::server::v1::{NodeDesc, RaftRole, ReplicaDesc, ReplicaRole};
 
 use super::source::NodeFilter;
-use super::{AllocSource, BalanceStatus, LeaderAction, TransferLeader};
+use super::{node_weight, AllocSource, BalanceStatus, LeaderAction, TransferLeader};
 use crate::constants::ROOT_GROUP_ID;
 use crate::Result;
 
 pub struct LeaderCountPolicy<T: AllocSource> {
     alloc_source: Arc<T>,
+    /// The replicas never chosen as the target of transferring leader.
+    excluded_replicas: HashSet<u64>,
 }
 
 enum TransferDescision {
@@ -36,21 +38,27 @@ enum TransferDescision {
         target_replica: u64,
         src_node: u64,
         target_node: u64,
+        reason: String,
     },
     // TODO: add create then transfer option?
 }
 
 impl<T: AllocSource> LeaderCountPolicy<T> {
     pub fn with(alloc_source: Arc<T>) -> Self {
-        Self { alloc_source }
+        Self { alloc_source, excluded_replicas: HashSet::default() }
+    }
+
+    pub fn exclude_replicas(mut self, replicas: HashSet<u64>) -> Self {
+        self.excluded_replicas = replicas;
+        self
     }
 
     pub fn compute_balance(&self) -> Result<LeaderAction> {
-        let mean = self.mean_leader_count(NodeFilter::Schedulable);
+        let mean = self.mean_leader_count_per_weight(NodeFilter::Schedulable);
         let candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
         let ranked_nodes = Self::rank_nodes_for_leader(candidate_nodes, mean);
         debug!(
-            "node ranked by leader count. mean={mean}, scored_nodes={:?}",
+            "node ranked by leader count. mean_per_weight={mean}, scored_nodes={:?}",
             ranked_nodes
                 .iter()
                 .map(|(n, s)| format!(
@@ -70,6 +78,7 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
                         target_replica,
                         src_node,
                         target_node,
+                        reason,
                     } => {
                         return Ok(LeaderAction::Shed(TransferLeader {
                             group,
@@ -77,6 +86,7 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
                             src_replica,
                             target_node,
                             target_replica,
+                            reason,
                         }));
                     }
                 }
@@ -118,7 +128,7 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
             let exist_replica_in_nodes = group
                 .replicas
                 .iter()
-                .filter(|r| r.id != replica.id)
+                .filter(|r| r.id != replica.id && !self.excluded_replicas.contains(&r.id))
                 .map(|r| (r.node_id, r.to_owned()))
                 .collect::<HashMap<u64, ReplicaDesc>>();
 
@@ -129,7 +139,8 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
                 .map(|e| &e.0)
             {
                 let sim_count = (target_node.capacity.as_ref().unwrap().leader_count + 1) as f64;
-                if Self::leader_balance_state(sim_count, mean) == BalanceStatus::Overfull {
+                let expect_count = mean * node_weight(target_node);
+                if Self::leader_balance_state(sim_count, expect_count) == BalanceStatus::Overfull {
                     continue;
                 }
                 let target_replica = exist_replica_in_nodes.get(&target_node.id);
@@ -137,12 +148,24 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
                     continue;
                 }
                 let target_replica = target_replica.unwrap();
+                let reason = format!(
+                    "node {} is overfull with {} leaders (load {:.2}), node {} is underfull with \
+                     {} leaders (load {:.2}), mean leaders per weight {:.2}",
+                    n.id,
+                    n.capacity.as_ref().unwrap().leader_count,
+                    Self::node_leader_load(n),
+                    target_node.id,
+                    target_node.capacity.as_ref().unwrap().leader_count,
+                    Self::node_leader_load(target_node),
+                    mean
+                );
                 return Ok(Some(TransferDescision::TransferOnly {
                     group: group_id.to_owned(),
                     src_replica: replica.id,
                     target_replica: target_replica.id,
                     src_node: replica.node_id,
                     target_node: target_replica.node_id,
+                    reason,
                 }));
             }
         }
@@ -154,7 +177,7 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
             .into_iter()
             .map(|n| {
                 let leader_num = n.capacity.as_ref().unwrap().leader_count as f64;
-                let s = Self::leader_balance_state(leader_num, mean_cnt);
+                let s = Self::leader_balance_state(leader_num, mean_cnt * node_weight(&n));
                 (n, s)
             })
             .collect::<Vec<(NodeDesc, BalanceStatus)>>();
@@ -165,13 +188,9 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
             if (n2.1 == BalanceStatus::Underfull) && (n1.1 != BalanceStatus::Underfull) {
                 return Ordering::Less;
             }
-            return n2
-                .0
-                .capacity
-                .as_ref()
-                .unwrap()
-                .leader_count
-                .cmp(&n1.0.capacity.as_ref().unwrap().leader_count);
+            return Self::node_leader_load(&n2.0)
+                .partial_cmp(&Self::node_leader_load(&n1.0))
+                .unwrap();
         });
         with_status
     }
@@ -187,10 +206,18 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
         BalanceStatus::Balanced
     }
 
-    fn mean_leader_count(&self, filter: NodeFilter) -> f64 {
+    /// The mean leader count of unit weight, the expected leader count of a
+    /// node is proportional to its weight.
+    fn mean_leader_count_per_weight(&self, filter: NodeFilter) -> f64 {
         let nodes = self.alloc_source.nodes(filter);
         let total_leaders =
             nodes.iter().map(|n| n.capacity.as_ref().unwrap().leader_count).sum::<u64>() as f64;
-        total_leaders / (nodes.len() as f64)
+        let total_weight = nodes.iter().map(node_weight).sum::<f64>();
+        total_leaders / total_weight
+    }
+
+    /// The leader count of unit weight.
+    fn node_leader_load(n: &NodeDesc) -> f64 {
+        n.capacity.as_ref().unwrap().leader_count as f64 / node_weight(n)
     }
 }
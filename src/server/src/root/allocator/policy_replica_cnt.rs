@@ -18,6 +18,7 @@ use std::sync::Arc;
 
 use sekas_api::server::v1::{NodeDesc, ReplicaDesc};
 
+use super::balance_policy::{self, BalancePolicy};
 use super::source::NodeFilter;
 use super::*;
 use crate::constants::ROOT_GROUP_ID;
@@ -27,46 +28,199 @@ use crate::Result;
 pub struct ReplicaCountPolicy<T: AllocSource> {
     alloc_source: Arc<T>,
     ongoing_stats: Arc<OngoingStats>,
+    balance: Arc<dyn BalancePolicy>,
 }
 
 impl<T: AllocSource> ReplicaCountPolicy<T> {
-    pub fn with(alloc_source: Arc<T>, ongoing_stats: Arc<OngoingStats>) -> Self {
-        Self { alloc_source, ongoing_stats }
+    pub fn with(
+        alloc_source: Arc<T>,
+        ongoing_stats: Arc<OngoingStats>,
+        balance: Arc<dyn BalancePolicy>,
+    ) -> Self {
+        Self { alloc_source, ongoing_stats, balance }
     }
 
     pub fn allocate_group_replica(
         &self,
         existing_replica_nodes: Vec<u64>,
         wanted_count: usize,
+        system_group: bool,
+        constraints: &[String],
     ) -> Result<Vec<NodeDesc>> {
         let mut candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
 
         // skip the nodes already have group replicas.
         candidate_nodes.retain(|n| !existing_replica_nodes.iter().any(|rn| *rn == n.id));
 
-        // sort by alloc score
+        // skip the nodes not satisfying the placement constraints.
+        candidate_nodes.retain(|n| satisfy_constraints(n, constraints));
+
+        // the replicas of system group are constrained to the system tier nodes.
+        if system_group && self.has_system_tier_nodes() {
+            candidate_nodes.retain(is_system_tier);
+        }
+
+        // sort by tier and alloc score, the user groups prefer the non system tier
+        // nodes.
         candidate_nodes.sort_by(|n1, n2| {
-            self.node_alloc_score(n2).partial_cmp(&self.node_alloc_score(n1)).unwrap()
+            let tier_ord = if system_group {
+                is_system_tier(n2).cmp(&is_system_tier(n1))
+            } else {
+                is_system_tier(n1).cmp(&is_system_tier(n2))
+            };
+            tier_ord.then_with(|| {
+                self.node_alloc_score(n2).partial_cmp(&self.node_alloc_score(n1)).unwrap()
+            })
         });
 
         Ok(candidate_nodes.into_iter().take(wanted_count).collect())
     }
 
+    /// Move a replica which violates the system tier placement, eg. a replica
+    /// of the root group outside the system tier nodes, or a replica of user
+    /// group on the system tier nodes.
+    pub fn compute_tier_placement(&self) -> Option<ReplicaAction> {
+        let candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
+        let system_nodes =
+            candidate_nodes.iter().filter(|n| is_system_tier(n)).cloned().collect::<Vec<_>>();
+        if system_nodes.is_empty() {
+            return None;
+        }
+        let user_nodes =
+            candidate_nodes.iter().filter(|n| !is_system_tier(n)).cloned().collect::<Vec<_>>();
+        let nodes = self
+            .alloc_source
+            .nodes(NodeFilter::All)
+            .into_iter()
+            .map(|n| (n.id, n))
+            .collect::<HashMap<_, _>>();
+
+        let mut groups = self.alloc_source.groups().into_values().collect::<Vec<_>>();
+        groups.sort_unstable_by_key(|g| g.id);
+        for group in groups {
+            let system_group = group.id == ROOT_GROUP_ID;
+            if !system_group && user_nodes.len() < REPLICA_PER_GROUP {
+                // There are no enough user nodes to hold the user groups.
+                continue;
+            }
+            let group_nodes = group.replicas.iter().map(|r| r.node_id).collect::<HashSet<_>>();
+            for replica in &group.replicas {
+                let Some(node) = nodes.get(&replica.node_id) else { continue };
+                if is_system_tier(node) == system_group {
+                    continue;
+                }
+                let targets = if system_group { &system_nodes } else { &user_nodes };
+                let Some(target) =
+                    targets.iter().filter(|n| !group_nodes.contains(&n.id)).max_by(|n1, n2| {
+                        self.node_alloc_score(n1).partial_cmp(&self.node_alloc_score(n2)).unwrap()
+                    })
+                else {
+                    continue;
+                };
+                let reason = format!(
+                    "replica {} of {} group {} is placed on {} node {}, node {} is a {} node with \
+                     {} replicas (load {:.2})",
+                    replica.id,
+                    if system_group { "system" } else { "user" },
+                    group.id,
+                    if system_group { "user" } else { "system tier" },
+                    node.id,
+                    target.id,
+                    if system_group { "system tier" } else { "user" },
+                    self.node_replica_count(target),
+                    self.node_replica_load(target),
+                );
+                return Some(ReplicaAction::Migrate(ReallocateReplica {
+                    group: group.id,
+                    source_node: node.id,
+                    source_replica: replica.id,
+                    target_node: target.to_owned(),
+                    reason,
+                }));
+            }
+        }
+        None
+    }
+
+    /// Move a replica which violates the placement constraints of its group,
+    /// to the node satisfying the constraints.
+    pub fn compute_constraint_placement(&self) -> Option<ReplicaAction> {
+        let has_system_tier_nodes = self.has_system_tier_nodes();
+        let candidate_nodes = self
+            .alloc_source
+            .nodes(NodeFilter::Schedulable)
+            .into_iter()
+            .filter(|n| !(has_system_tier_nodes && is_system_tier(n)))
+            .collect::<Vec<_>>();
+        let nodes = self
+            .alloc_source
+            .nodes(NodeFilter::All)
+            .into_iter()
+            .map(|n| (n.id, n))
+            .collect::<HashMap<_, _>>();
+
+        let mut groups = self.alloc_source.groups().into_values().collect::<Vec<_>>();
+        groups.sort_unstable_by_key(|g| g.id);
+        for group in groups {
+            let constraints = group_constraints(&group);
+            if group.id == ROOT_GROUP_ID || constraints.is_empty() {
+                continue;
+            }
+            let group_nodes = group.replicas.iter().map(|r| r.node_id).collect::<HashSet<_>>();
+            for replica in &group.replicas {
+                let Some(node) = nodes.get(&replica.node_id) else { continue };
+                if satisfy_constraints(node, &constraints) {
+                    continue;
+                }
+                let Some(target) = candidate_nodes
+                    .iter()
+                    .filter(|n| !group_nodes.contains(&n.id))
+                    .filter(|n| satisfy_constraints(n, &constraints))
+                    .max_by(|n1, n2| {
+                        self.node_alloc_score(n1).partial_cmp(&self.node_alloc_score(n2)).unwrap()
+                    })
+                else {
+                    continue;
+                };
+                let reason = format!(
+                    "replica {} of group {} is placed on node {} without labels {:?}, node {} \
+                     satisfies the constraints with {} replicas (load {:.2})",
+                    replica.id,
+                    group.id,
+                    node.id,
+                    constraints,
+                    target.id,
+                    self.node_replica_count(target),
+                    self.node_replica_load(target),
+                );
+                return Some(ReplicaAction::Migrate(ReallocateReplica {
+                    group: group.id,
+                    source_node: node.id,
+                    source_replica: replica.id,
+                    target_node: target.to_owned(),
+                    reason,
+                }));
+            }
+        }
+        None
+    }
+
     pub fn compute_balance(&self) -> Result<Vec<ReplicaAction>> {
-        let mean_cnt = self.mean_replica_count(NodeFilter::Schedulable);
+        let mean_load = self.mean_load_per_weight(NodeFilter::Schedulable);
         let candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
 
-        let ranked_candidates = self.rank_node_for_balance(candidate_nodes, mean_cnt);
+        let ranked_candidates = self.rank_node_for_balance(candidate_nodes, mean_load);
         tracing::debug!(
-            scored_nodes = ?ranked_candidates.iter().map(|(n, s)| format!("{}-{}({:?})", n.id, self.node_replica_count(n), s)).collect::<Vec<_>>(),
-            mean = mean_cnt,
-            "node ranked by replica count",
+            scored_nodes = ?ranked_candidates.iter().map(|(n, s)| format!("{}-{}({:?})", n.id, self.balance.node_load(n), s)).collect::<Vec<_>>(),
+            mean_per_weight = mean_load,
+            policy = self.balance.name(),
+            "node ranked by balance policy",
         );
         for (src_node, status) in &ranked_candidates {
             if *status != BalanceStatus::Overfull {
                 break;
             }
-            if let Some(action) = self.rebalance_target(src_node, &ranked_candidates, mean_cnt) {
+            if let Some(action) = self.rebalance_target(src_node, &ranked_candidates, mean_load) {
                 return Ok(vec![action]);
             }
         }
@@ -96,20 +250,41 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
             }
         }
 
+        let has_system_tier_nodes = self.has_system_tier_nodes();
         for (target, state) in ranked_nodes.iter().rev() {
             if *state != BalanceStatus::Underfull {
                 break;
             }
-            let sim_count = (self.node_replica_count(target) + 1) as f64;
-            if Self::node_balance_state(sim_count, mean) == BalanceStatus::Overfull {
+            if has_system_tier_nodes && is_system_tier(target) {
+                // The system tier nodes are dedicated to the root group.
+                continue;
+            }
+            // The load moved along with the replica is estimated by the source node.
+            let unit = self.balance.replica_load(src);
+            let sim_load = self.balance.node_load(target) + unit;
+            let expect_load = mean * node_weight(target);
+            if Self::node_balance_state(sim_load, expect_load, unit) == BalanceStatus::Overfull {
                 continue;
             }
             let (source_replica, group) = self.preferred_remove_replica(src, target, &groups)?;
+            let reason = format!(
+                "node {} is overfull with {} replicas (load {:.2}), node {} is underfull with {} \
+                 replicas (load {:.2}), mean {} per weight {:.2}",
+                src.id,
+                self.node_replica_count(src),
+                self.node_replica_load(src),
+                target.id,
+                self.node_replica_count(target),
+                self.node_replica_load(target),
+                self.balance.name(),
+                mean
+            );
             return Some(ReplicaAction::Migrate(ReallocateReplica {
                 group,
                 source_node: source_replica.node_id,
                 source_replica: source_replica.id,
                 target_node: target.to_owned(),
+                reason,
             }));
         }
         None
@@ -121,11 +296,20 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         target: &NodeDesc,
         group_nodes: &HashMap<u64, HashSet<u64>>,
     ) -> Option<(ReplicaDesc, u64)> {
+        let groups = self.alloc_source.groups();
         // TODO: sort & rank replica
         self.alloc_source.node_replicas(&src.id).into_iter().find(|(_, g)| {
             if *g == ROOT_GROUP_ID {
                 return false;
             }
+            // The target must satisfy the placement constraints of the group.
+            let satisfied = groups
+                .get(g)
+                .map(|g| satisfy_constraints(target, &group_constraints(g)))
+                .unwrap_or_default();
+            if !satisfied {
+                return false;
+            }
             if let Some(exist_nodes) = group_nodes.get(g) {
                 if exist_nodes.len() < REPLICA_PER_GROUP {
                     return false;
@@ -138,22 +322,26 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         })
     }
 
-    fn mean_replica_count(&self, filter: NodeFilter) -> f64 {
+    /// The mean load of unit weight measured by the balance policy, the
+    /// expected load of a node is proportional to its weight.
+    fn mean_load_per_weight(&self, filter: NodeFilter) -> f64 {
         let nodes = self.alloc_source.nodes(filter);
-        let total_replicas = nodes.iter().map(|n| self.node_replica_count(n)).sum::<u64>() as f64;
-        total_replicas / (nodes.len() as f64)
+        let total_load = nodes.iter().map(|n| self.balance.node_load(n)).sum::<f64>();
+        let total_weight = nodes.iter().map(node_weight).sum::<f64>();
+        total_load / total_weight
     }
 
     fn rank_node_for_balance(
         &self,
         ns: Vec<NodeDesc>,
-        mean_cnt: f64,
+        mean_load: f64,
     ) -> Vec<(NodeDesc, BalanceStatus)> {
         let mut with_status = ns
             .into_iter()
             .map(|n| {
-                let replica_num = self.node_replica_count(&n) as f64;
-                let s = Self::node_balance_state(replica_num, mean_cnt);
+                let load = self.balance.node_load(&n);
+                let unit = self.balance.replica_load(&n);
+                let s = Self::node_balance_state(load, mean_load * node_weight(&n), unit);
                 (n, s)
             })
             .collect::<Vec<(NodeDesc, BalanceStatus)>>();
@@ -164,38 +352,44 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
             if (n2.1 == BalanceStatus::Underfull) && (n1.1 != BalanceStatus::Underfull) {
                 return Ordering::Less;
             }
-            let n2_cnt = self.node_replica_count(&n2.0);
-            let n1_cnt = self.node_replica_count(&n1.0);
-            n2_cnt.cmp(&n1_cnt)
+            let n2_load = self.node_replica_load(&n2.0);
+            let n1_load = self.node_replica_load(&n1.0);
+            n2_load.partial_cmp(&n1_load).unwrap()
         });
         with_status
     }
 
-    fn node_balance_state(replica_num: f64, mean: f64) -> BalanceStatus {
+    /// Return the balance status of the node by its load, `unit` is the load of
+    /// one replica, the node is balanced if it is within two replicas from the
+    /// mean.
+    fn node_balance_state(load: f64, mean: f64, unit: f64) -> BalanceStatus {
         const THRESHOLD_FRACTION: f64 = 0.05;
         const MIN_RANGE_DELTA: f64 = 2.0;
-        let delta = f64::max(mean * THRESHOLD_FRACTION, MIN_RANGE_DELTA);
-        if replica_num > mean + delta {
+        let delta = f64::max(mean * THRESHOLD_FRACTION, MIN_RANGE_DELTA * unit);
+        if load > mean + delta {
             return BalanceStatus::Overfull;
         }
-        if replica_num < mean - delta {
+        if load < mean - delta {
             return BalanceStatus::Underfull;
         }
         BalanceStatus::Balanced
     }
 
+    fn has_system_tier_nodes(&self) -> bool {
+        self.alloc_source.nodes(NodeFilter::NotDecommissioned).iter().any(is_system_tier)
+    }
+
     fn node_alloc_score(&self, n: &NodeDesc) -> f64 {
         // TODO: add more rule to calculate score.
-        -(self.node_replica_count(n) as f64)
+        -self.node_replica_load(n)
+    }
+
+    /// The load of unit weight measured by the balance policy.
+    fn node_replica_load(&self, n: &NodeDesc) -> f64 {
+        self.balance.node_load(n) / node_weight(n)
     }
 
     fn node_replica_count(&self, n: &NodeDesc) -> u64 {
-        let mut cnt = n.capacity.as_ref().unwrap().replica_count as i64;
-        let delta = self.ongoing_stats.get_node_delta(n.id);
-        cnt += delta.replica_count;
-        if cnt < 0 {
-            cnt = 0;
-        }
-        cnt as u64
+        balance_policy::node_replica_count(n, &self.ongoing_stats)
     }
 }
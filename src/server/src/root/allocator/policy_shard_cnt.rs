@@ -14,12 +14,14 @@
 // limitations under the License.
 
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use log::debug;
-use sekas_api::server::v1::{GroupDesc, ShardDesc};
+use sekas_api::server::v1::{GroupDesc, NodeDesc, ShardDesc};
 
-use super::{AllocSource, ReallocateShard, ShardAction};
+use super::source::NodeFilter;
+use super::{group_constraints, satisfy_constraints, AllocSource, ReallocateShard, ShardAction};
 use crate::constants::ROOT_GROUP_ID;
 use crate::root::allocator::BalanceStatus;
 use crate::Result;
@@ -33,12 +35,26 @@ impl<T: AllocSource> ShardCountPolicy<T> {
         Self { alloc_source }
     }
 
-    pub fn allocate_shard(&self, n: usize) -> Result<Vec<GroupDesc>> {
+    /// Allocate groups for the shard with the placement constraints. The
+    /// groups with fewer replicas violating the constraints are preferred, the
+    /// violating replicas are moved by the replica placement later.
+    pub fn allocate_shard(&self, n: usize, constraints: &[String]) -> Result<Vec<GroupDesc>> {
         let mut groups = self.current_user_groups();
         if groups.is_empty() {
             return Ok(vec![]);
         }
-        groups.sort_by(|g1, g2| g1.shards.len().cmp(&g2.shards.len()));
+        let nodes = self.nodes();
+        let mut constraints = constraints.to_vec();
+        constraints.sort_unstable();
+        constraints.dedup();
+        groups.sort_by_key(|g| {
+            (
+                Self::violated_replicas(g, &constraints, &nodes),
+                // Keep the unconstrained shards away from the constrained groups.
+                group_constraints(g) != constraints,
+                g.shards.len(),
+            )
+        });
         Ok(groups.into_iter().take(n).collect())
     }
 
@@ -122,10 +138,20 @@ impl<T: AllocSource> ShardCountPolicy<T> {
                 continue;
             }
             let source_shard = self.preferred_remove_shard(source_group, target)?;
+            let reason = format!(
+                "group {} is overfull with {} shards, group {} is underfull with {} shards, mean \
+                 shards per group {:.2}",
+                source_group.id,
+                source_group.shards.len(),
+                target.id,
+                target.shards.len(),
+                mean
+            );
             return Some(ShardAction::Migrate(ReallocateShard {
                 shard: source_shard.id,
                 source_group: source_group.id,
                 target_group: target.id,
+                reason,
             }));
         }
         debug!("skip balance group:{} due to no suitable target", source_group.id);
@@ -135,15 +161,56 @@ impl<T: AllocSource> ShardCountPolicy<T> {
     fn preferred_remove_shard(
         &self,
         src_group: &GroupDesc,
-        _target_group: &GroupDesc,
+        target_group: &GroupDesc,
     ) -> Option<ShardDesc> {
-        let replicas = src_group.shards.to_owned();
+        // The target group must satisfy the placement constraints of the shard.
+        let nodes = self.nodes();
+        let satisfied = |shard: &ShardDesc| {
+            Self::violated_replicas(target_group, &shard.constraints, &nodes) == 0
+        };
         // TODO: ranking shards and choose the preferred one
-        replicas.first().map(ToOwned::to_owned)
+        src_group
+            .shards
+            .iter()
+            .find(|s| !sekas_schema::shard::is_clone_source(src_group, s) && satisfied(s))
+            .map(ToOwned::to_owned)
+    }
+
+    /// Return the number of the replicas of the group placed on the nodes not
+    /// satisfying the constraints.
+    fn violated_replicas(
+        group: &GroupDesc,
+        constraints: &[String],
+        nodes: &HashMap<u64, NodeDesc>,
+    ) -> usize {
+        if constraints.is_empty() {
+            return 0;
+        }
+        group
+            .replicas
+            .iter()
+            .filter(|r| {
+                !nodes
+                    .get(&r.node_id)
+                    .map(|n| satisfy_constraints(n, constraints))
+                    .unwrap_or_default()
+            })
+            .count()
+    }
+
+    fn nodes(&self) -> HashMap<u64, NodeDesc> {
+        self.alloc_source.nodes(NodeFilter::All).into_iter().map(|n| (n.id, n)).collect()
     }
 
+    /// Return the user groups accepting shards, the draining groups are
+    /// excluded.
     fn current_user_groups(&self) -> Vec<GroupDesc> {
         let groups = self.alloc_source.groups();
-        groups.values().filter(|g| g.id != ROOT_GROUP_ID).map(ToOwned::to_owned).collect()
+        let draining = self.alloc_source.draining_groups();
+        groups
+            .values()
+            .filter(|g| g.id != ROOT_GROUP_ID && !draining.contains(&g.id))
+            .map(ToOwned::to_owned)
+            .collect()
     }
 }
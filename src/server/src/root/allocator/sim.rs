@@ -0,0 +1,378 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A deterministic simulation of the allocator, without any real nodes.
+//!
+//! The synthetic cluster states are fed into the [`Allocator`], and the
+//! actions are applied to them instantly, in the same order as the reconcile
+//! scheduler checks them. It is used to validate the changes of the balance
+//! policies against many topologies.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use sekas_api::server::v1::*;
+
+use super::source::NodeFilter;
+use super::*;
+use crate::root::OngoingStats;
+use crate::{Config, DynamicConfig, Result, RootConfig};
+
+/// A violation of the invariants of the cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// More than one replica of the group are placed on the same node.
+    DuplicatedReplicas { group: u64, node: u64 },
+    /// The replica is placed on a node which doesn't exist or is
+    /// decommissioned.
+    UnavailableNode { group: u64, replica: u64, node: u64 },
+    /// The leader of the group is not one of its replicas.
+    MissingLeader { group: u64 },
+}
+
+/// The result of running the simulation.
+#[derive(Debug, Clone, Default)]
+pub struct SimReport {
+    /// Whether the allocator stops producing actions.
+    pub converged: bool,
+    /// The rounds run, the last round produces no actions if converged.
+    pub rounds: usize,
+    /// The actions applied during the simulation.
+    pub num_actions: usize,
+    pub violations: Vec<Violation>,
+}
+
+/// The synthetic state of a cluster. The replica and leader count of nodes
+/// are derived from the groups, so only the groups need to be maintained.
+pub struct SimCluster {
+    state: Mutex<SimState>,
+}
+
+#[derive(Default)]
+struct SimState {
+    nodes: BTreeMap<u64, NodeDesc>,
+    groups: BTreeMap<u64, GroupDesc>,
+    /// The replica id of the leader of each group.
+    leaders: HashMap<u64, u64>,
+    next_group_id: u64,
+    next_replica_id: u64,
+    next_shard_id: u64,
+}
+
+impl SimCluster {
+    pub fn new() -> Self {
+        let state = SimState {
+            next_group_id: 1,
+            next_replica_id: 1,
+            next_shard_id: 1,
+            ..Default::default()
+        };
+        SimCluster { state: Mutex::new(state) }
+    }
+
+    /// Add an active node with the cpu nums and labels, the weight is derived
+    /// from the cpu nums.
+    pub fn add_node(&self, id: u64, cpu_nums: f64, labels: Vec<String>) {
+        let node = NodeDesc {
+            id,
+            capacity: Some(NodeCapacity { cpu_nums, ..Default::default() }),
+            status: NodeStatus::Active as i32,
+            labels,
+            ..Default::default()
+        };
+        self.state.lock().unwrap().nodes.insert(id, node);
+    }
+
+    pub fn set_node_status(&self, id: u64, status: NodeStatus) {
+        if let Some(node) = self.state.lock().unwrap().nodes.get_mut(&id) {
+            node.status = status as i32;
+        }
+    }
+
+    /// Add a group with replicas on the nodes, the first replica is the
+    /// leader. Return the id of the group.
+    pub fn add_group(&self, nodes: &[u64]) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let group_id = state.next_group_id;
+        state.next_group_id += 1;
+        let replicas = nodes
+            .iter()
+            .map(|&node_id| {
+                let id = state.next_replica_id;
+                state.next_replica_id += 1;
+                ReplicaDesc { id, node_id, role: ReplicaRole::Voter as i32 }
+            })
+            .collect::<Vec<_>>();
+        if let Some(leader) = replicas.first() {
+            state.leaders.insert(group_id, leader.id);
+        }
+        state.groups.insert(group_id, GroupDesc { id: group_id, replicas, ..Default::default() });
+        group_id
+    }
+
+    /// Add a shard with the placement constraints into the group.
+    pub fn add_shard(&self, group_id: u64, constraints: Vec<String>) {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_shard_id;
+        state.next_shard_id += 1;
+        if let Some(group) = state.groups.get_mut(&group_id) {
+            group.shards.push(ShardDesc { id, constraints, ..Default::default() });
+        }
+    }
+
+    /// Return the replica count of each node.
+    pub fn node_replica_counts(&self) -> BTreeMap<u64, u64> {
+        self.state.lock().unwrap().nodes().into_iter().map(|n| (n.id, replica_count(&n))).collect()
+    }
+
+    /// Check the invariants of the cluster.
+    pub fn check_violations(&self) -> Vec<Violation> {
+        let state = self.state.lock().unwrap();
+        let mut violations = Vec::new();
+        for group in state.groups.values() {
+            let mut nodes = HashSet::new();
+            for replica in &group.replicas {
+                if !nodes.insert(replica.node_id) {
+                    violations.push(Violation::DuplicatedReplicas {
+                        group: group.id,
+                        node: replica.node_id,
+                    });
+                }
+                let available = state
+                    .nodes
+                    .get(&replica.node_id)
+                    .map(|n| n.status != NodeStatus::Decommissioned as i32)
+                    .unwrap_or_default();
+                if !available {
+                    violations.push(Violation::UnavailableNode {
+                        group: group.id,
+                        replica: replica.id,
+                        node: replica.node_id,
+                    });
+                }
+            }
+            let leader = state.leaders.get(&group.id);
+            if !group.replicas.iter().any(|r| Some(&r.id) == leader) {
+                violations.push(Violation::MissingLeader { group: group.id });
+            }
+        }
+        violations
+    }
+
+    fn create_group(&self, nodes: Vec<NodeDesc>) {
+        self.add_group(&nodes.iter().map(|n| n.id).collect::<Vec<_>>());
+    }
+
+    /// Move the replica to the target node, the leadership is shed to another
+    /// replica before the source replica is removed.
+    fn move_replica(&self, action: &ReallocateReplica) {
+        let mut state = self.state.lock().unwrap();
+        let replica_id = state.next_replica_id;
+        state.next_replica_id += 1;
+        let Some(group) = state.groups.get_mut(&action.group) else { return };
+        group.replicas.retain(|r| r.id != action.source_replica);
+        group.replicas.push(ReplicaDesc {
+            id: replica_id,
+            node_id: action.target_node.id,
+            role: ReplicaRole::Voter as i32,
+        });
+        group.epoch += 1;
+        let first = group.replicas.first().map(|r| r.id).unwrap_or_default();
+        if state.leaders.get(&action.group) == Some(&action.source_replica) {
+            state.leaders.insert(action.group, first);
+        }
+    }
+
+    fn transfer_leader(&self, action: &TransferLeader) {
+        self.state.lock().unwrap().leaders.insert(action.group, action.target_replica);
+    }
+
+    fn move_shard(&self, action: &ReallocateShard) {
+        let mut state = self.state.lock().unwrap();
+        let Some(source) = state.groups.get_mut(&action.source_group) else { return };
+        let Some(index) = source.shards.iter().position(|s| s.id == action.shard) else { return };
+        let shard = source.shards.remove(index);
+        source.epoch += 1;
+        if let Some(target) = state.groups.get_mut(&action.target_group) {
+            target.shards.push(shard);
+            target.epoch += 1;
+        }
+    }
+}
+
+impl Default for SimCluster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimState {
+    /// Return the nodes with the replica and leader count derived from groups.
+    fn nodes(&self) -> Vec<NodeDesc> {
+        let mut replicas = HashMap::<u64, u64>::new();
+        let mut leaders = HashMap::<u64, u64>::new();
+        for group in self.groups.values() {
+            let leader = self.leaders.get(&group.id);
+            for replica in &group.replicas {
+                *replicas.entry(replica.node_id).or_default() += 1;
+                if Some(&replica.id) == leader {
+                    *leaders.entry(replica.node_id).or_default() += 1;
+                }
+            }
+        }
+        let mut nodes = self.nodes.values().cloned().collect::<Vec<_>>();
+        for node in &mut nodes {
+            let capacity = node.capacity.get_or_insert_with(Default::default);
+            capacity.replica_count = replicas.get(&node.id).cloned().unwrap_or_default();
+            capacity.leader_count = leaders.get(&node.id).cloned().unwrap_or_default();
+        }
+        nodes
+    }
+}
+
+fn replica_count(n: &NodeDesc) -> u64 {
+    n.capacity.as_ref().map(|c| c.replica_count).unwrap_or_default()
+}
+
+#[crate::async_trait]
+impl AllocSource for SimCluster {
+    async fn refresh_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn nodes(&self, filter: NodeFilter) -> Vec<NodeDesc> {
+        let mut nodes = self.state.lock().unwrap().nodes();
+        match filter {
+            NodeFilter::All | NodeFilter::Alive => {}
+            NodeFilter::Schedulable => nodes.retain(|n| n.status == NodeStatus::Active as i32),
+            NodeFilter::NotDecommissioned => {
+                nodes.retain(|n| n.status != NodeStatus::Decommissioned as i32)
+            }
+        }
+        nodes
+    }
+
+    fn groups(&self) -> HashMap<u64, GroupDesc> {
+        let state = self.state.lock().unwrap();
+        state.groups.iter().map(|(id, g)| (*id, g.clone())).collect()
+    }
+
+    fn node_replicas(&self, node_id: &u64) -> Vec<(ReplicaDesc, u64)> {
+        let state = self.state.lock().unwrap();
+        state
+            .groups
+            .values()
+            .flat_map(|g| g.replicas.iter().map(|r| (r.clone(), g.id)))
+            .filter(|(r, _)| r.node_id == *node_id)
+            .collect()
+    }
+
+    fn replica_state(&self, replica_id: &u64) -> Option<ReplicaState> {
+        self.replica_states().into_iter().find(|r| r.replica_id == *replica_id)
+    }
+
+    fn replica_states(&self) -> Vec<ReplicaState> {
+        let state = self.state.lock().unwrap();
+        let mut states = Vec::new();
+        for group in state.groups.values() {
+            let leader = state.leaders.get(&group.id);
+            for replica in &group.replicas {
+                let role =
+                    if Some(&replica.id) == leader { RaftRole::Leader } else { RaftRole::Follower };
+                states.push(ReplicaState {
+                    replica_id: replica.id,
+                    group_id: group.id,
+                    role: role as i32,
+                    node_id: replica.node_id,
+                    ..Default::default()
+                });
+            }
+        }
+        states
+    }
+}
+
+/// Run the allocator against a [`SimCluster`].
+pub struct Simulator {
+    cluster: Arc<SimCluster>,
+    alloc: Allocator<SimCluster>,
+}
+
+impl Simulator {
+    pub fn new(cluster: SimCluster, cfg: RootConfig) -> Self {
+        let cluster = Arc::new(cluster);
+        let dynamic_config =
+            Arc::new(DynamicConfig::new(&Config { root: cfg.clone(), ..Default::default() }));
+        let alloc =
+            Allocator::new(cluster.clone(), Arc::new(OngoingStats::default()), cfg, dynamic_config);
+        Simulator { cluster, alloc }
+    }
+
+    pub fn cluster(&self) -> &SimCluster {
+        &self.cluster
+    }
+
+    /// Compute the actions of one round and apply them, return the number of
+    /// the applied actions.
+    pub async fn step(&self) -> Result<usize> {
+        let mut num_actions = 0;
+        if let GroupAction::Add(cnt) = self.alloc.compute_group_action().await? {
+            let wanted = self.alloc.replicas_per_group();
+            for _ in 0..cnt {
+                let nodes = self.alloc.allocate_group_replica(vec![], wanted, false, &[]).await?;
+                if nodes.len() == wanted {
+                    self.cluster.create_group(nodes);
+                    num_actions += 1;
+                }
+            }
+        }
+        for action in self.alloc.compute_replica_action().await? {
+            let ReplicaAction::Migrate(action) = action;
+            self.cluster.move_replica(&action);
+            num_actions += 1;
+        }
+        for action in self.alloc.compute_leader_action().await? {
+            if let LeaderAction::Shed(action) = action {
+                self.cluster.transfer_leader(&action);
+                num_actions += 1;
+            }
+        }
+        for action in self.alloc.compute_shard_action().await? {
+            let ShardAction::Migrate(action) = action;
+            self.cluster.move_shard(&action);
+            num_actions += 1;
+        }
+        Ok(num_actions)
+    }
+
+    /// Run rounds until the allocator converges or `max_rounds` is reached,
+    /// the invariants are checked after each round.
+    pub async fn run(&self, max_rounds: usize) -> Result<SimReport> {
+        let mut report = SimReport::default();
+        while report.rounds < max_rounds {
+            let num_actions = self.step().await?;
+            report.rounds += 1;
+            report.num_actions += num_actions;
+            report.violations = self.cluster.check_violations();
+            if !report.violations.is_empty() {
+                break;
+            }
+            if num_actions == 0 {
+                report.converged = true;
+                break;
+            }
+        }
+        Ok(report)
+    }
+}
@@ -22,7 +22,7 @@ use sekas_api::server::v1::*;
 use sekas_runtime::ExecutorOwner;
 
 use super::*;
-use crate::constants::REPLICA_PER_GROUP;
+use crate::constants::{REPLICA_PER_GROUP, ROOT_GROUP_ID, SYSTEM_TIER_LABEL};
 use crate::root::allocator::source::NodeFilter;
 
 #[test]
@@ -32,7 +32,7 @@ fn sim_boostrap_join_node_balance() {
     executor.block_on(async {
         let p = Arc::new(MockInfoProvider::new());
         let d = Arc::new(OngoingStats::default());
-        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default(), Arc::default());
 
         println!("1. boostrap and no need rebalance");
         p.set_groups(vec![GroupDesc {
@@ -44,8 +44,16 @@ fn sim_boostrap_join_node_balance() {
         p.set_nodes(vec![NodeDesc {
             id: 1,
             addr: "".into(),
-            capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 1, leader_count: 1 }),
+            capacity: Some(NodeCapacity {
+                cpu_nums: 2.0,
+                replica_count: 1,
+                leader_count: 1,
+                weight: 0.0,
+            }),
             status: NodeStatus::Active as i32,
+            binary_version: 0,
+            labels: vec![],
+            incarnation: 0,
         }]);
         p.set_replica_states(vec![ReplicaState {
             replica_id: 1,
@@ -66,14 +74,30 @@ fn sim_boostrap_join_node_balance() {
             NodeDesc {
                 id: 2,
                 addr: "".into(),
-                capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
+                capacity: Some(NodeCapacity {
+                    cpu_nums: 2.0,
+                    replica_count: 0,
+                    leader_count: 0,
+                    weight: 0.0,
+                }),
                 status: NodeStatus::Active as i32,
+                binary_version: 0,
+                labels: vec![],
+                incarnation: 0,
             },
             NodeDesc {
                 id: 3,
                 addr: "".into(),
-                capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
+                capacity: Some(NodeCapacity {
+                    cpu_nums: 2.0,
+                    replica_count: 0,
+                    leader_count: 0,
+                    weight: 0.0,
+                }),
                 status: NodeStatus::Active as i32,
+                binary_version: 0,
+                labels: vec![],
+                incarnation: 0,
             },
         ]);
         p.set_nodes(nodes);
@@ -125,7 +149,10 @@ fn sim_boostrap_join_node_balance() {
         match act {
             GroupAction::Add(n) => {
                 for _ in 0..n {
-                    let nodes = a.allocate_group_replica(vec![], REPLICA_PER_GROUP).await.unwrap();
+                    let nodes = a
+                        .allocate_group_replica(vec![], REPLICA_PER_GROUP, false, &[])
+                        .await
+                        .unwrap();
                     println!(
                         "alloc group {} in {:?}",
                         group_id_gen,
@@ -176,7 +203,7 @@ fn sim_boostrap_join_node_balance() {
         p.display();
 
         println!("5. assign shard in groups");
-        let cg = a.place_group_for_shard(9).await.unwrap();
+        let cg = a.place_group_for_shard(9, &[]).await.unwrap();
         for id in 0..9 {
             let group = cg.get(id % cg.len()).unwrap();
             p.assign_shard(group.id);
@@ -188,8 +215,16 @@ fn sim_boostrap_join_node_balance() {
         nodes.extend_from_slice(&[NodeDesc {
             id: 4,
             addr: "".into(),
-            capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
+            capacity: Some(NodeCapacity {
+                cpu_nums: 2.0,
+                replica_count: 0,
+                leader_count: 0,
+                weight: 0.0,
+            }),
             status: NodeStatus::Active as i32,
+            binary_version: 0,
+            labels: vec![],
+            incarnation: 0,
         }]);
         p.set_nodes(nodes);
         p.display();
@@ -199,7 +234,10 @@ fn sim_boostrap_join_node_balance() {
         match act {
             GroupAction::Add(n) => {
                 for _ in 0..n {
-                    let nodes = a.allocate_group_replica(vec![], REPLICA_PER_GROUP).await.unwrap();
+                    let nodes = a
+                        .allocate_group_replica(vec![], REPLICA_PER_GROUP, false, &[])
+                        .await
+                        .unwrap();
                     println!(
                         "alloc group {} in {:?}",
                         group_id_gen,
@@ -261,6 +299,7 @@ fn sim_boostrap_join_node_balance() {
                         source_node: _,
                         source_replica,
                         target_node,
+                        ..
                     }) => {
                         println!(
                             "move group {} replica {} to {}",
@@ -280,7 +319,9 @@ fn sim_boostrap_join_node_balance() {
         assert!(!sact.is_empty());
         for act in &sact {
             match act {
-                ShardAction::Migrate(ReallocateShard { shard, source_group, target_group }) => {
+                ShardAction::Migrate(ReallocateShard {
+                    shard, source_group, target_group, ..
+                }) => {
                     println!("move shard {} from {} to {}", shard, source_group, target_group);
                     p.move_shards(
                         source_group.to_owned(),
@@ -294,7 +335,9 @@ fn sim_boostrap_join_node_balance() {
         assert!(!sact.is_empty());
         for act in &sact {
             match act {
-                ShardAction::Migrate(ReallocateShard { shard, source_group, target_group }) => {
+                ShardAction::Migrate(ReallocateShard {
+                    shard, source_group, target_group, ..
+                }) => {
                     println!("move shard {} from {} to {}", shard, source_group, target_group);
                     p.move_shards(
                         source_group.to_owned(),
@@ -333,6 +376,174 @@ fn sim_boostrap_join_node_balance() {
     });
 }
 
+#[test]
+fn sim_heterogeneous_node_weight() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default(), Arc::default());
+
+        // Node 3 and 4 have double the weight of node 1 and 2. The weights of node 1
+        // and 4 are the cpu nums, and the weights of node 2 and 3 are scaled by the
+        // configured factors.
+        let capacities = [(2.0, 0.0), (4.0, 0.5), (2.0, 2.0), (4.0, 0.0)];
+        p.set_nodes(
+            capacities
+                .iter()
+                .enumerate()
+                .map(|(idx, (cpu_nums, weight))| NodeDesc {
+                    id: idx as u64 + 1,
+                    addr: "".into(),
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: *cpu_nums,
+                        weight: *weight,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    binary_version: 0,
+                    labels: vec![],
+                    incarnation: 0,
+                })
+                .collect(),
+        );
+
+        let mut groups = Vec::new();
+        let mut replica_id_gen = 1;
+        for group_id in 1..=6 {
+            let nodes =
+                a.allocate_group_replica(vec![], REPLICA_PER_GROUP, false, &[]).await.unwrap();
+            let replicas = nodes
+                .iter()
+                .map(|n| {
+                    replica_id_gen += 1;
+                    ReplicaDesc {
+                        id: replica_id_gen,
+                        node_id: n.id,
+                        role: ReplicaRole::Voter.into(),
+                    }
+                })
+                .collect();
+            groups.push(GroupDesc { id: group_id, epoch: 0, shards: vec![], replicas });
+            p.set_groups(groups.clone());
+        }
+        p.display();
+
+        let counts = p
+            .nodes(NodeFilter::All)
+            .iter()
+            .map(|n| n.capacity.as_ref().unwrap().replica_count)
+            .collect::<Vec<_>>();
+        assert!(counts[2].min(counts[3]) > counts[0].max(counts[1]), "{counts:?}");
+
+        let ract = a.compute_replica_action().await.unwrap();
+        assert!(ract.is_empty());
+    });
+}
+
+#[test]
+fn sim_system_tier_placement() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default(), Arc::default());
+
+        // Node 4, 5 and 6 are the system tier nodes.
+        p.set_nodes(
+            (1..=6)
+                .map(|id| NodeDesc {
+                    id,
+                    addr: "".into(),
+                    capacity: Some(NodeCapacity { cpu_nums: 2.0, ..Default::default() }),
+                    status: NodeStatus::Active as i32,
+                    binary_version: 0,
+                    labels: if id > 3 { vec![SYSTEM_TIER_LABEL.to_owned()] } else { vec![] },
+                    incarnation: 0,
+                })
+                .collect(),
+        );
+
+        let nodes = a.allocate_group_replica(vec![], REPLICA_PER_GROUP, true, &[]).await.unwrap();
+        assert!(nodes.iter().all(is_system_tier), "nodes: {nodes:?}");
+        let nodes = a.allocate_group_replica(vec![], REPLICA_PER_GROUP, false, &[]).await.unwrap();
+        assert!(!nodes.iter().any(is_system_tier), "nodes: {nodes:?}");
+
+        // The root group is placed on the user nodes, it should be moved to the system
+        // tier nodes.
+        p.set_groups(vec![GroupDesc {
+            id: ROOT_GROUP_ID,
+            epoch: 0,
+            shards: vec![],
+            replicas: (1..=3)
+                .map(|id| ReplicaDesc { id, node_id: id, role: ReplicaRole::Voter.into() })
+                .collect(),
+        }]);
+        let actions = a.compute_replica_action().await.unwrap();
+        assert_eq!(actions.len(), 1);
+        let ReplicaAction::Migrate(action) = &actions[0];
+        assert_eq!(action.group, ROOT_GROUP_ID);
+        assert!(is_system_tier(&action.target_node));
+    });
+}
+
+#[test]
+fn sim_constraint_placement() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default(), Arc::default());
+
+        // Node 4, 5 and 6 are labeled with `disk=ssd`.
+        let constraints = vec!["disk=ssd".to_owned()];
+        p.set_nodes(
+            (1..=6)
+                .map(|id| NodeDesc {
+                    id,
+                    addr: "".into(),
+                    capacity: Some(NodeCapacity { cpu_nums: 2.0, ..Default::default() }),
+                    status: NodeStatus::Active as i32,
+                    binary_version: 0,
+                    labels: if id > 3 { constraints.clone() } else { vec![] },
+                    incarnation: 0,
+                })
+                .collect(),
+        );
+
+        let nodes =
+            a.allocate_group_replica(vec![], REPLICA_PER_GROUP, false, &constraints).await.unwrap();
+        assert_eq!(nodes.len(), REPLICA_PER_GROUP);
+        assert!(nodes.iter().all(|n| n.labels == constraints), "nodes: {nodes:?}");
+
+        // Group 1 holds a constrained shard but it is placed on the nodes without the
+        // labels.
+        let replicas = |nodes: std::ops::RangeInclusive<u64>| {
+            nodes
+                .map(|id| ReplicaDesc { id, node_id: id, role: ReplicaRole::Voter.into() })
+                .collect::<Vec<_>>()
+        };
+        let shard = ShardDesc { id: 1, constraints: constraints.clone(), ..Default::default() };
+        p.set_groups(vec![
+            GroupDesc { id: 1, epoch: 0, shards: vec![shard], replicas: replicas(1..=3) },
+            GroupDesc { id: 2, epoch: 0, shards: vec![], replicas: replicas(4..=6) },
+        ]);
+
+        let groups = a.place_group_for_shard(1, &constraints).await.unwrap();
+        assert_eq!(groups[0].id, 2);
+
+        let actions = a.compute_replica_action().await.unwrap();
+        assert_eq!(actions.len(), 1);
+        let ReplicaAction::Migrate(action) = &actions[0];
+        assert_eq!(action.group, 1);
+        assert_eq!(action.target_node.labels, constraints);
+    });
+}
+
+#[derive(Default)]
 pub struct MockInfoProvider {
     nodes: Arc<Mutex<Vec<NodeDesc>>>,
     groups: Arc<Mutex<GroupInfo>>,
@@ -340,7 +551,6 @@ pub struct MockInfoProvider {
     shard_id_gen: AtomicU64,
 }
 
-#[derive(Default)]
 struct GroupInfo {
     descs: HashMap<u64, GroupDesc>,
     node_replicas: HashMap<u64, Vec<(ReplicaDesc, u64)>>,
@@ -415,7 +625,7 @@ impl MockInfoProvider {
         let mut nodes = self.nodes(NodeFilter::All);
         for n in nodes.iter_mut() {
             let mut cap = n.capacity.take().unwrap();
-            cap.replica_count = node_replicas.get(&n.id).unwrap().len() as u64;
+            cap.replica_count = node_replicas.get(&n.id).map(Vec::len).unwrap_or_default() as u64;
             n.capacity = Some(cap)
         }
         self.set_nodes(nodes);
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
 use sekas_api::server::v1::*;
@@ -38,6 +38,11 @@ pub trait AllocSource {
 
     fn groups(&self) -> HashMap<u64, GroupDesc>;
 
+    /// Return the groups being drained, which accept no shards.
+    fn draining_groups(&self) -> HashSet<u64> {
+        HashSet::default()
+    }
+
     fn node_replicas(&self, node_id: &u64) -> Vec<(ReplicaDesc, u64)>;
 
     fn replica_state(&self, replica_id: &u64) -> Option<ReplicaState>;
@@ -58,6 +63,7 @@ pub struct SysAllocSource {
 #[derive(Default)]
 struct GroupInfo {
     descs: HashMap<u64, GroupDesc>,
+    draining: HashSet<u64>,
     node_replicas: HashMap<u64, Vec<(ReplicaDesc, u64 /* group_id */)>>,
 }
 
@@ -116,6 +122,11 @@ impl AllocSource for SysAllocSource {
         groups.descs.to_owned()
     }
 
+    fn draining_groups(&self) -> HashSet<u64> {
+        let groups = self.groups.lock().unwrap();
+        groups.draining.to_owned()
+    }
+
     fn node_replicas(&self, node_id: &u64) -> Vec<(ReplicaDesc, u64)> {
         let groups = self.groups.lock().unwrap();
         groups.node_replicas.get(node_id).map(ToOwned::to_owned).unwrap_or_default()
@@ -148,11 +159,12 @@ impl SysAllocSource {
     async fn reload_groups(&self) -> Result<()> {
         let schema = self.root.schema()?;
         let cur_groups = schema.list_group().await?;
-        self.set_groups(cur_groups);
+        let draining = schema.draining_groups().await?;
+        self.set_groups(cur_groups, draining);
         Ok(())
     }
 
-    fn set_groups(&self, gs: Vec<GroupDesc>) {
+    fn set_groups(&self, gs: Vec<GroupDesc>, draining: HashSet<u64>) {
         let mut groups = self.groups.lock().unwrap();
         let mut node_replicas: HashMap<u64, Vec<(ReplicaDesc, u64)>> = HashMap::new();
         for group in gs.iter() {
@@ -168,7 +180,7 @@ impl SysAllocSource {
             }
         }
         let descs = gs.into_iter().map(|g| (g.id, g)).collect();
-        let _ = std::mem::replace(&mut *groups, GroupInfo { descs, node_replicas });
+        let _ = std::mem::replace(&mut *groups, GroupInfo { descs, draining, node_replicas });
     }
 
     async fn reload_replica_status(&self) -> Result<()> {
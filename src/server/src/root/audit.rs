@@ -0,0 +1,177 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use sekas_rock::time::timestamp_nanos;
+
+/// The prefix of the keys of audit records in the meta collection.
+pub(super) const AUDIT_KEY_PREFIX: &[u8] = b"audit_";
+
+/// The number of appended audit records between two purges of the expired
+/// records.
+pub(super) const PURGE_INTERVAL: u64 = 64;
+
+/// The admin and DDL operations recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    CreateDatabase,
+    DeleteDatabase,
+    RenameDatabase,
+    RecoverDatabase,
+    CreateCollection,
+    DeleteCollection,
+    RenameCollection,
+    TruncateCollection,
+    FreezeCollection,
+    UnfreezeCollection,
+    RecoverCollection,
+    CloneCollection,
+    ScatterCollection,
+    CordonNode,
+    UncordonNode,
+    DrainNode,
+    MoveReplicas,
+    MoveShard,
+    MergeGroup,
+    UnsafeRecover,
+    BumpClusterVersion,
+    RotateDataKey,
+    UpdateMoveShardLimit,
+    SetConfig,
+    CreateUser,
+    DeleteUser,
+    ResetUserToken,
+    GrantPermission,
+    CreateTenant,
+    DeleteTenant,
+    SetTenantQuota,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::CreateDatabase => "create_database",
+            AuditAction::DeleteDatabase => "delete_database",
+            AuditAction::RenameDatabase => "rename_database",
+            AuditAction::RecoverDatabase => "recover_database",
+            AuditAction::CreateCollection => "create_collection",
+            AuditAction::DeleteCollection => "delete_collection",
+            AuditAction::RenameCollection => "rename_collection",
+            AuditAction::TruncateCollection => "truncate_collection",
+            AuditAction::FreezeCollection => "freeze_collection",
+            AuditAction::UnfreezeCollection => "unfreeze_collection",
+            AuditAction::RecoverCollection => "recover_collection",
+            AuditAction::CloneCollection => "clone_collection",
+            AuditAction::ScatterCollection => "scatter_collection",
+            AuditAction::CordonNode => "cordon_node",
+            AuditAction::UncordonNode => "uncordon_node",
+            AuditAction::DrainNode => "drain_node",
+            AuditAction::MoveReplicas => "move_replicas",
+            AuditAction::MoveShard => "move_shard",
+            AuditAction::MergeGroup => "merge_group",
+            AuditAction::UnsafeRecover => "unsafe_recover",
+            AuditAction::BumpClusterVersion => "bump_cluster_version",
+            AuditAction::RotateDataKey => "rotate_data_key",
+            AuditAction::UpdateMoveShardLimit => "update_move_shard_limit",
+            AuditAction::SetConfig => "set_config",
+            AuditAction::CreateUser => "create_user",
+            AuditAction::DeleteUser => "delete_user",
+            AuditAction::ResetUserToken => "reset_user_token",
+            AuditAction::GrantPermission => "grant_permission",
+            AuditAction::CreateTenant => "create_tenant",
+            AuditAction::DeleteTenant => "delete_tenant",
+            AuditAction::SetTenantQuota => "set_tenant_quota",
+        }
+    }
+}
+
+/// Allocate the ids of audit records, the id is the unix timestamp in
+/// nanoseconds, and it is bumped if the clock goes backwards so that the
+/// records are ordered by their keys.
+#[derive(Default)]
+pub(super) struct AuditLog {
+    last_id: AtomicU64,
+    num_appended: AtomicU64,
+}
+
+impl AuditLog {
+    pub(super) fn next_id(&self) -> u64 {
+        self.next_id_at(timestamp_nanos())
+    }
+
+    fn next_id_at(&self, now: u64) -> u64 {
+        let mut last_id = self.last_id.load(Ordering::Relaxed);
+        loop {
+            let id = std::cmp::max(now, last_id + 1);
+            match self.last_id.compare_exchange_weak(
+                last_id,
+                id,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return id,
+                Err(actual) => last_id = actual,
+            }
+        }
+    }
+
+    /// Count an appended record, return whether the expired records should
+    /// be purged.
+    pub(super) fn on_appended(&self) -> bool {
+        let num_appended = self.num_appended.fetch_add(1, Ordering::Relaxed) + 1;
+        num_appended % PURGE_INTERVAL == 0
+    }
+}
+
+/// Return the key of the audit record in the meta collection, the id is
+/// encoded in big endian so that the records are ordered by time.
+pub(super) fn audit_key(id: u64) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(AUDIT_KEY_PREFIX.len() + core::mem::size_of::<u64>());
+    buf.extend_from_slice(AUDIT_KEY_PREFIX);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audit_id_is_monotonic() {
+        let audit_log = AuditLog::default();
+        assert_eq!(audit_log.next_id_at(100), 100);
+        // The clock goes backwards.
+        assert_eq!(audit_log.next_id_at(50), 101);
+        assert_eq!(audit_log.next_id_at(101), 102);
+        assert_eq!(audit_log.next_id_at(200), 200);
+    }
+
+    #[test]
+    fn audit_log_purge_interval() {
+        let log = AuditLog::default();
+        for _ in 1..PURGE_INTERVAL {
+            assert!(!log.on_appended());
+        }
+        assert!(log.on_appended());
+        assert!(!log.on_appended());
+    }
+
+    #[test]
+    fn audit_key_is_ordered() {
+        assert!(audit_key(1) < audit_key(2));
+        assert!(audit_key(255) < audit_key(256));
+        assert!(audit_key(u64::MAX).starts_with(AUDIT_KEY_PREFIX));
+    }
+}
@@ -13,7 +13,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::sync::{atomic, Arc, Mutex};
 use std::task::{Poll, Waker};
 use std::time::Duration;
@@ -21,20 +22,36 @@ use std::time::Duration;
 use futures::future::poll_fn;
 use log::{error, info, warn};
 use prometheus::HistogramTimer;
-use sekas_api::server::v1::{GroupDesc, ReplicaDesc, ReplicaRole, RootDesc, ShardDesc};
+use sekas_api::server::v1::{
+    CloneSource, GroupDesc, ReplicaDesc, ReplicaRole, RootDesc, ShardDesc,
+};
 use sekas_client::RetryState;
-use tokio::time::Instant;
+use sekas_rock::time::timestamp_millis;
+use sekas_runtime::time::Instant;
+use sekas_runtime::JoinHandle;
 
 use super::allocator::*;
 use super::{HeartbeatQueue, HeartbeatTask, RootShared, Schema};
 use crate::constants::INITIAL_EPOCH;
+use crate::failpoint::fail_point;
 use crate::root::metrics;
 use crate::serverpb::v1::background_job::Job;
 use crate::serverpb::v1::*;
 use crate::Result;
 
+/// The holder of the resource locks whose job is not appended yet.
+const PENDING_JOB_ID: u64 = u64::MAX;
+
+/// The max backoff before retrying a failed job.
+const MAX_JOB_BACKOFF: Duration = Duration::from_secs(60);
+
 pub struct Jobs {
     core: JobCore,
+    /// The max number of the jobs running concurrently.
+    parallelism: usize,
+    /// The handles of the running jobs, they are aborted once the leadership
+    /// is dropped.
+    handles: Mutex<HashMap<u64, JoinHandle<()>>>,
 }
 
 impl Jobs {
@@ -42,6 +59,7 @@ impl Jobs {
         root_shared: Arc<RootShared>,
         alloc: Arc<Allocator<SysAllocSource>>,
         heartbeat_queue: Arc<HeartbeatQueue>,
+        parallelism: usize,
     ) -> Self {
         Self {
             core: JobCore {
@@ -52,6 +70,8 @@ impl Jobs {
                 res_locks: Default::default(),
                 enable: Default::default(),
             },
+            parallelism,
+            handles: Default::default(),
         }
     }
 
@@ -68,10 +88,27 @@ impl Jobs {
         self.core.wait_more_jobs().await;
     }
 
-    pub async fn advance_jobs(&self) -> Result<()> {
-        let jobs = self.core.need_handle_jobs();
-        for job in &jobs {
-            self.handle_job(job).await?;
+    /// Start the runnable jobs until the parallelism is reached, then wait
+    /// until the jobs are changed or the backoff of a failed job is elapsed.
+    pub async fn advance_jobs(self: &Arc<Self>) -> Result<()> {
+        let now = timestamp_millis();
+        let (jobs, version, next_attempt_ms) = self.core.start_runnable_jobs(now, self.parallelism);
+        {
+            let mut handles = self.handles.lock().unwrap();
+            handles.retain(|_, handle| !handle.is_finished());
+            for job in jobs {
+                let id = job.id;
+                let jobs = self.clone();
+                handles.insert(id, sekas_runtime::spawn(async move { jobs.run_job(job).await }));
+            }
+        }
+        let wait = self.core.wait_changes(version);
+        match next_attempt_ms {
+            Some(ms) => {
+                let delay = Duration::from_millis(ms.saturating_sub(now));
+                let _ = sekas_runtime::time::timeout(delay, wait).await;
+            }
+            None => wait.await,
         }
         Ok(())
     }
@@ -84,10 +121,21 @@ impl Jobs {
 
     pub fn on_drop_leader(&self) {
         self.core.enable.store(false, atomic::Ordering::Relaxed);
+        self.handles.lock().unwrap().clear();
         self.core.on_drop_leader()
     }
 
+    async fn run_job(&self, job: BackgroundJob) {
+        if self.handle_job(&job).await.is_err() {
+            if let Err(err) = self.core.record_failure(job.id).await {
+                warn!("record the failure of background job {}: {err:?}", job.id);
+            }
+        }
+        self.core.on_job_exit(job.id);
+    }
+
     async fn handle_job(&self, job: &BackgroundJob) -> Result<()> {
+        fail_point!("root_job_handle");
         info!("start background job: {job:?}");
         let r = match job.job.as_ref().unwrap() {
             background_job::Job::CreateCollection(create_collection) => {
@@ -102,6 +150,15 @@ impl Jobs {
             background_job::Job::PurgeDatabase(purge_database) => {
                 self.handle_purge_database(job, purge_database).await
             }
+            background_job::Job::RotateDataKey(rotate_data_key) => {
+                self.handle_rotate_data_key(job, rotate_data_key).await
+            }
+            background_job::Job::TruncateCollection(truncate_collection) => {
+                self.handle_truncate_collection(job, truncate_collection).await
+            }
+            background_job::Job::MergeGroup(merge_group) => {
+                self.handle_merge_group(job, merge_group).await
+            }
         };
         info!("backgroud job: {job:?}, handle result: {r:?}");
         r
@@ -150,16 +207,23 @@ impl Jobs {
                 break;
             }
             let shard = shard.unwrap();
-            let groups = self.core.alloc.place_group_for_shard(1).await?;
-            if groups.is_empty() {
-                return Err(crate::Error::ResourceExhausted("no engouth groups".into()));
-            }
-            let group = groups.first().unwrap();
-            info!("try create shard at group {}, shards: {}", group.id, group.shards.len());
-            if let Err(err) = self.try_create_shard(group.id, &shard).await {
+            let group_id = match shard.clone_source.as_ref() {
+                Some(clone_source) => self.find_clone_source_group(clone_source, &shard).await?,
+                None => {
+                    let groups =
+                        self.core.alloc.place_group_for_shard(1, &shard.constraints).await?;
+                    if groups.is_empty() {
+                        return Err(crate::Error::ResourceExhausted("no engouth groups".into()));
+                    }
+                    let group = groups.first().unwrap();
+                    info!("try create shard at group {}, shards: {}", group.id, group.shards.len());
+                    group.id
+                }
+            };
+            if let Err(err) = self.try_create_shard(group_id, &shard).await {
                 error!(
                     "create collection shard error and try to rollback: {err:?}. group={}, shard={}",
-                    group.id, shard.id);
+                    group_id, shard.id);
                 create_collection.remark = format!("{err:?}");
                 create_collection.wait_cleanup.push(shard);
                 create_collection.status =
@@ -175,6 +239,27 @@ impl Jobs {
         Ok(())
     }
 
+    /// Find the group of the clone source, the cloned shard must be placed with
+    /// it.
+    async fn find_clone_source_group(
+        &self,
+        clone_source: &CloneSource,
+        shard: &ShardDesc,
+    ) -> Result<u64> {
+        let schema = self.core.root_shared.schema()?;
+        let source_shards = schema.get_collection_shards(clone_source.collection_id).await?;
+        source_shards
+            .into_iter()
+            .find(|(_, source_shard)| source_shard.range == shard.range)
+            .map(|(group_id, _)| group_id)
+            .ok_or_else(|| {
+                crate::Error::InvalidArgument(format!(
+                    "the clone source of shard {} is not found",
+                    shard.id
+                ))
+            })
+    }
+
     async fn handle_write_desc(
         &self,
         job_id: u64,
@@ -222,10 +307,7 @@ impl Jobs {
         create_collection: &CreateCollectionJob,
     ) -> Result<()> {
         self.core
-            .update(BackgroundJob {
-                id: job_id,
-                job: Some(background_job::Job::CreateCollection(create_collection.to_owned())),
-            })
+            .update(job_id, background_job::Job::CreateCollection(create_collection.to_owned()))
             .await?;
         Ok(())
     }
@@ -314,7 +396,7 @@ impl Jobs {
         let nodes = self
             .core
             .alloc
-            .allocate_group_replica(vec![], create_group.request_replica_cnt as usize)
+            .allocate_group_replica(vec![], create_group.request_replica_cnt as usize, false, &[])
             .await?;
         let group_id = schema.next_group_id().await?;
         let mut replicas = Vec::new();
@@ -411,10 +493,7 @@ impl Jobs {
 
     async fn save_create_group(&self, job_id: u64, create_group: &CreateOneGroupJob) -> Result<()> {
         self.core
-            .update(BackgroundJob {
-                id: job_id,
-                job: Some(background_job::Job::CreateOneGroup(create_group.to_owned())),
-            })
+            .update(job_id, background_job::Job::CreateOneGroup(create_group.to_owned()))
             .await?;
         Ok(())
     }
@@ -519,6 +598,134 @@ impl Jobs {
     }
 }
 
+impl Jobs {
+    async fn handle_truncate_collection(
+        &self,
+        job: &BackgroundJob,
+        truncate_collection: &TruncateCollectionJob,
+    ) -> Result<()> {
+        let schema = self.core.root_shared.schema()?;
+        let mut truncate_collection = truncate_collection.to_owned();
+        while let Some(task) = truncate_collection.wait_truncate.last() {
+            let new_shard = task.new_shard.as_ref().unwrap();
+            let group_shards =
+                schema.get_collection_shards(truncate_collection.collection_id).await?;
+            if let Some((group_id, _)) = group_shards.iter().find(|(_, s)| s.id == task.shard_id) {
+                self.try_truncate_shard(*group_id, task.shard_id, new_shard).await?;
+            } else if !group_shards.iter().any(|(_, s)| s.id == new_shard.id) {
+                // The shard might be moving between groups, retry later.
+                return Err(crate::Error::ShardNotFound(task.shard_id));
+            }
+            truncate_collection.wait_truncate.pop();
+            self.core
+                .update(
+                    job.id,
+                    background_job::Job::TruncateCollection(truncate_collection.to_owned()),
+                )
+                .await?;
+        }
+        self.core.finish(job.to_owned()).await?;
+        Ok(())
+    }
+}
+
+impl Jobs {
+    async fn handle_rotate_data_key(
+        &self,
+        job: &BackgroundJob,
+        rotate_data_key: &RotateDataKeyJob,
+    ) -> Result<()> {
+        let schema = self.core.root_shared.schema()?;
+        let key_id = rotate_data_key.key_id;
+        if !schema.data_keys().await?.iter().any(|k| k.id == key_id) {
+            let key_manager = &self.core.root_shared.key_manager;
+            let data_key = key_manager.generate_data_key(key_id, super::unix_timestamp())?;
+            schema.add_data_key(data_key).await?;
+            info!("generate data key {key_id}");
+        }
+
+        // Sync the new data key to nodes as soon as possible.
+        let nodes = schema.list_node().await?;
+        self.core
+            .heartbeat_queue
+            .try_schedule(
+                nodes.iter().map(|n| HeartbeatTask { node_id: n.id }).collect(),
+                Instant::now(),
+            )
+            .await;
+        self.core.finish(job.to_owned()).await?;
+        Ok(())
+    }
+}
+
+impl Jobs {
+    async fn handle_merge_group(
+        &self,
+        job: &BackgroundJob,
+        merge_group: &MergeGroupJob,
+    ) -> Result<()> {
+        let schema = self.core.root_shared.schema()?;
+        let mut merge_group = merge_group.to_owned();
+        let donor_id = merge_group.donor_group;
+        let target_id = merge_group.target_group;
+        // The new shards are no longer allocated to the donor group.
+        schema.mark_group_draining(donor_id).await?;
+        loop {
+            if merge_group.status == MergeGroupStatus::MergeGroupMovingShards as i32 {
+                while let Some(donor) = schema.get_group(donor_id).await? {
+                    if donor.shards.is_empty() {
+                        break;
+                    }
+                    // The clone sources are moved after the cloned shards, which carry the
+                    // data of the clone sources.
+                    let shard = donor
+                        .shards
+                        .iter()
+                        .find(|s| !sekas_schema::shard::is_clone_source(&donor, s))
+                        .ok_or_else(|| {
+                            crate::Error::InvalidData(format!(
+                                "all shards of group {donor_id} are clone sources"
+                            ))
+                        })?;
+                    self.try_move_shard(&donor, target_id, shard).await?;
+                }
+                merge_group.status = MergeGroupStatus::MergeGroupRemovingReplicas as i32;
+                self.core
+                    .update(job.id, background_job::Job::MergeGroup(merge_group.to_owned()))
+                    .await?;
+            }
+
+            let Some(donor) = schema.get_group(donor_id).await? else {
+                break;
+            };
+            // The shards might be added to the donor group after moving, eg. the clones of
+            // the shards, they must be moved before removing the replicas.
+            if !donor.shards.is_empty() {
+                warn!(
+                    "group {donor_id} has {} shards before removing replicas, move them again",
+                    donor.shards.len()
+                );
+                merge_group.status = MergeGroupStatus::MergeGroupMovingShards as i32;
+                self.core
+                    .update(job.id, background_job::Job::MergeGroup(merge_group.to_owned()))
+                    .await?;
+                continue;
+            }
+            for replica in &donor.replicas {
+                match self.try_remove_replica(donor_id, replica.id).await {
+                    Ok(()) | Err(crate::Error::AbortScheduleTask(_)) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            schema.delete_group(donor_id).await?;
+            info!("group {donor_id} is merged into group {target_id}");
+            break;
+        }
+        self.core.finish(job.to_owned()).await?;
+        Ok(())
+    }
+}
+
 impl Jobs {
     async fn try_create_shard(&self, group_id: u64, desc: &ShardDesc) -> Result<()> {
         let mut group_client = self.core.root_shared.transport_manager.lazy_group_client(group_id);
@@ -533,6 +740,24 @@ impl Jobs {
         }
     }
 
+    async fn try_truncate_shard(
+        &self,
+        group_id: u64,
+        shard_id: u64,
+        new_shard: &ShardDesc,
+    ) -> Result<()> {
+        let mut group_client = self.core.root_shared.transport_manager.lazy_group_client(group_id);
+        let mut retry_state = RetryState::new(Some(Duration::from_secs(10)));
+        loop {
+            match group_client.truncate_shard(shard_id, new_shard).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
     async fn try_create_replica(
         &self,
         addr: &str,
@@ -564,6 +789,61 @@ impl Jobs {
         Ok(())
     }
 
+    /// Move the shard to the target group, and wait until the descriptors of
+    /// both groups are updated.
+    async fn try_move_shard(
+        &self,
+        src_group: &GroupDesc,
+        target_group: u64,
+        shard: &ShardDesc,
+    ) -> Result<()> {
+        const MOVE_SHARD_TIMEOUT: Duration = Duration::from_secs(60);
+
+        let schema = self.core.root_shared.schema()?;
+        if sekas_schema::shard::is_clone_source(src_group, shard) {
+            return Err(crate::Error::InvalidArgument(format!(
+                "shard {} is the clone source of other shards in group {}",
+                shard.id, src_group.id
+            )));
+        }
+        let target = schema
+            .get_group(target_group)
+            .await?
+            .ok_or(crate::Error::GroupNotFound(target_group))?;
+        if !target.shards.iter().any(|s| s.id == shard.id) {
+            let transport_manager = &self.core.root_shared.transport_manager;
+            let mut group_client = transport_manager.lazy_group_client(target_group);
+            group_client.accept_shard(src_group.id, src_group.epoch, shard).await?;
+            info!("move shard {} from group {} to group {target_group}", shard.id, src_group.id);
+        }
+
+        let heartbeats = src_group
+            .replicas
+            .iter()
+            .chain(target.replicas.iter())
+            .map(|r| HeartbeatTask { node_id: r.node_id })
+            .collect::<Vec<_>>();
+        let deadline = Instant::now() + MOVE_SHARD_TIMEOUT;
+        loop {
+            let contains = |group: Option<GroupDesc>| {
+                group.map_or(false, |g| g.shards.iter().any(|s| s.id == shard.id))
+            };
+            if contains(schema.get_group(target_group).await?)
+                && !contains(schema.get_group(src_group.id).await?)
+            {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(crate::Error::DeadlineExceeded(format!(
+                    "moving shard {} to group {target_group}",
+                    shard.id
+                )));
+            }
+            self.core.heartbeat_queue.try_schedule(heartbeats.clone(), Instant::now()).await;
+            sekas_runtime::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     async fn try_remove_shard(&self, _group: u64, _shard: u64) -> Result<()> {
         // TODO: impl remove shard.
         Ok(())
@@ -573,7 +853,8 @@ impl Jobs {
 struct JobCore {
     root_shared: Arc<RootShared>,
     mem_jobs: Arc<Mutex<MemJobs>>,
-    res_locks: Arc<Mutex<HashSet<Vec<u8>>>>,
+    /// The resource keys and the ids of the jobs holding them.
+    res_locks: Arc<Mutex<HashMap<Vec<u8>, u64>>>,
     alloc: Arc<Allocator<SysAllocSource>>,
     heartbeat_queue: Arc<HeartbeatQueue>,
     enable: atomic::AtomicBool,
@@ -582,8 +863,21 @@ struct JobCore {
 #[derive(Default)]
 struct MemJobs {
     jobs: Vec<BackgroundJob>,
+    running: HashSet<u64>,
+    /// Increased once the jobs are changed.
+    version: u64,
     removed_wakers: Vec<Waker>,
     added_wakers: Vec<Waker>,
+    changed_wakers: Vec<Waker>,
+}
+
+impl MemJobs {
+    fn notify_changed(&mut self) {
+        self.version += 1;
+        for waker in std::mem::take(&mut self.changed_wakers) {
+            waker.wake();
+        }
+    }
 }
 
 impl JobCore {
@@ -600,6 +894,8 @@ impl JobCore {
         {
             let mut mem_jobs = self.mem_jobs.lock().unwrap();
             mem_jobs.jobs.clear();
+            mem_jobs.running.clear();
+            mem_jobs.notify_changed();
             let wakers = std::mem::take(&mut mem_jobs.removed_wakers);
             for waker in wakers {
                 waker.wake();
@@ -611,11 +907,14 @@ impl JobCore {
             }
         }
         {
+            // The dependent jobs are appended later, so they hold the locks.
+            let mut jobs = jobs;
+            jobs.sort_unstable_by_key(|job| job.id);
             let mut res_locks = self.res_locks.lock().unwrap();
             res_locks.clear();
             for job in &jobs {
                 if let Some(key) = res_key(job) {
-                    res_locks.insert(key);
+                    res_locks.insert(key, job.id);
                 }
             }
         }
@@ -633,6 +932,8 @@ impl JobCore {
             for waker in wakers {
                 waker.wake();
             }
+            mem_jobs.running.clear();
+            mem_jobs.notify_changed();
         }
         {
             let mut res_locks = self.res_locks.lock().unwrap();
@@ -640,19 +941,30 @@ impl JobCore {
         }
     }
 
-    pub async fn append(&self, job: BackgroundJob) -> Result<BackgroundJob> {
+    pub async fn append(&self, mut job: BackgroundJob) -> Result<BackgroundJob> {
         let schema = self.root_shared.schema()?;
-        if let Some(res_key) = res_key(&job) {
-            if !self.try_lock_res(res_key) {
-                return Err(crate::Error::AlreadyExists(
-                    "job for target resource already exist".into(),
-                ));
+        let res_key = res_key(&job);
+        let mut prev_holder = None;
+        if let Some(res_key) = &res_key {
+            prev_holder = self.try_lock_res(res_key.clone(), &job)?;
+            job.depends_on.extend(prev_holder);
+        }
+        let job = match schema.append_job(job).await {
+            Ok(job) => job,
+            Err(err) => {
+                if let Some(res_key) = &res_key {
+                    self.release_pending_res(res_key, prev_holder);
+                }
+                return Err(err);
             }
+        };
+        if let Some(res_key) = res_key {
+            self.res_locks.lock().unwrap().insert(res_key, job.id);
         }
-        let job = schema.append_job(job).await?;
         {
             let mut mem_jobs = self.mem_jobs.lock().unwrap();
             mem_jobs.jobs.push(job.to_owned());
+            mem_jobs.notify_changed();
             let wakers = std::mem::take(&mut mem_jobs.added_wakers);
             for waker in wakers {
                 waker.wake();
@@ -667,29 +979,113 @@ impl JobCore {
         {
             let mut mem_jobs = self.mem_jobs.lock().unwrap();
             mem_jobs.jobs.retain(|j| j.id != job.id);
+            mem_jobs.notify_changed();
             let wakers = std::mem::take(&mut mem_jobs.removed_wakers);
             for waker in wakers {
                 waker.wake();
             }
         }
         if let Some(res_key) = res_key(&job) {
-            self.unlock_res(&res_key);
+            self.unlock_res(&res_key, job.id);
         }
         Ok(())
     }
 
-    pub async fn update(&self, job: BackgroundJob) -> Result<()> {
+    /// Save the progress of the job, the dependencies and the retry states
+    /// of the job are kept.
+    pub async fn update(&self, id: u64, job: Job) -> Result<()> {
+        let Some(mut desc) = self.get_mem_job(id) else {
+            return Ok(());
+        };
+        desc.job = Some(job);
+        self.save(desc).await
+    }
+
+    /// Record the failed attempt of the job, it is retried after a backoff,
+    /// which is persisted so that it survives the failover of root.
+    pub async fn record_failure(&self, id: u64) -> Result<()> {
+        let Some(mut desc) = self.get_mem_job(id) else {
+            return Ok(());
+        };
+        desc.retried += 1;
+        desc.next_attempt_ms = timestamp_millis() + retry_backoff(desc.retried).as_millis() as u64;
+        metrics::RECONCILE_RETRY_TASK_TOTAL.background_job.inc();
+        self.save(desc).await
+    }
+
+    async fn save(&self, job: BackgroundJob) -> Result<()> {
         let schema = self.root_shared.schema()?;
         let updated = schema.update_job(job.to_owned()).await?;
         if updated {
             let mut mem_jobs = self.mem_jobs.lock().unwrap();
             if let Some(idx) = mem_jobs.jobs.iter().position(|j| j.id == job.id) {
                 let _ = std::mem::replace(&mut mem_jobs.jobs[idx], job);
+                mem_jobs.notify_changed();
             }
         }
         Ok(())
     }
 
+    fn get_mem_job(&self, id: u64) -> Option<BackgroundJob> {
+        let mem_jobs = self.mem_jobs.lock().unwrap();
+        mem_jobs.jobs.iter().find(|j| j.id == id).cloned()
+    }
+
+    /// Return the jobs which could be started and mark them as running, at most
+    /// `limit` jobs are running. A job could be started once the jobs it
+    /// depends on are finished and its backoff is elapsed.
+    ///
+    /// The version of the jobs and the earliest time to retry the jobs in
+    /// backoff are returned too.
+    pub fn start_runnable_jobs(
+        &self,
+        now_ms: u64,
+        limit: usize,
+    ) -> (Vec<BackgroundJob>, u64, Option<u64>) {
+        let mut mem_jobs = self.mem_jobs.lock().unwrap();
+        let ids = mem_jobs.jobs.iter().map(|j| j.id).collect::<HashSet<_>>();
+        let mut runnable = Vec::new();
+        let mut next_attempt_ms: Option<u64> = None;
+        for job in &mem_jobs.jobs {
+            if mem_jobs.running.len() + runnable.len() >= limit {
+                break;
+            }
+            if mem_jobs.running.contains(&job.id) || job.depends_on.iter().any(|d| ids.contains(d))
+            {
+                continue;
+            }
+            if job.next_attempt_ms > now_ms {
+                let ms =
+                    next_attempt_ms.map_or(job.next_attempt_ms, |v| v.min(job.next_attempt_ms));
+                next_attempt_ms = Some(ms);
+                continue;
+            }
+            runnable.push(job.clone());
+        }
+        mem_jobs.running.extend(runnable.iter().map(|j| j.id));
+        (runnable, mem_jobs.version, next_attempt_ms)
+    }
+
+    pub fn on_job_exit(&self, id: u64) {
+        let mut mem_jobs = self.mem_jobs.lock().unwrap();
+        mem_jobs.running.remove(&id);
+        mem_jobs.notify_changed();
+    }
+
+    /// Wait until the version of the jobs is changed.
+    pub async fn wait_changes(&self, version: u64) {
+        poll_fn(|ctx| {
+            let mut mem_jobs = self.mem_jobs.lock().unwrap();
+            if mem_jobs.version == version {
+                mem_jobs.changed_wakers.push(ctx.waker().clone());
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await;
+    }
+
     pub async fn wait_more_jobs(&self) {
         poll_fn(|ctx| {
             let mut mem_jobs = self.mem_jobs.lock().unwrap();
@@ -742,6 +1138,9 @@ impl JobCore {
                     _ => unreachable!(),
                 }
             }
+            background_job::Job::RotateDataKey(_)
+            | background_job::Job::TruncateCollection(_)
+            | background_job::Job::MergeGroup(_) => Ok(()),
             _ => unreachable!(),
         }
     }
@@ -751,22 +1150,66 @@ impl JobCore {
         schema.get_job_history(id).await
     }
 
-    pub fn need_handle_jobs(&self) -> Vec<BackgroundJob> {
-        let jobs = self.mem_jobs.lock().unwrap();
-        jobs.jobs.to_owned()
+    /// Lock the resource for the job. If the resource is locked by a job
+    /// which the new job could wait for, the lock is taken over and the id of
+    /// the previous holder is returned as the dependency of the new job.
+    fn try_lock_res(&self, res_key: Vec<u8>, job: &BackgroundJob) -> Result<Option<u64>> {
+        let mem_jobs = self.mem_jobs.lock().unwrap();
+        let mut res_locks = self.res_locks.lock().unwrap();
+        match res_locks.entry(res_key) {
+            Entry::Vacant(ent) => {
+                ent.insert(PENDING_JOB_ID);
+                Ok(None)
+            }
+            Entry::Occupied(mut ent) => {
+                let holder = *ent.get();
+                match mem_jobs.jobs.iter().find(|j| j.id == holder) {
+                    Some(prev) if waits_for(job, prev) => {
+                        ent.insert(PENDING_JOB_ID);
+                        Ok(Some(holder))
+                    }
+                    _ => Err(crate::Error::AlreadyExists(
+                        "job for target resource already exist".into(),
+                    )),
+                }
+            }
+        }
     }
 
-    fn try_lock_res(&self, res_key: Vec<u8>) -> bool {
+    /// Give back the resource locked by [`JobCore::try_lock_res`], since the
+    /// job is not appended.
+    fn release_pending_res(&self, res_key: &[u8], prev_holder: Option<u64>) {
         let mut res_locks = self.res_locks.lock().unwrap();
-        res_locks.insert(res_key)
+        if res_locks.get(res_key) != Some(&PENDING_JOB_ID) {
+            return;
+        }
+        match prev_holder {
+            Some(holder) => res_locks.insert(res_key.to_owned(), holder),
+            None => res_locks.remove(res_key),
+        };
     }
 
-    fn unlock_res(&self, res_key: &[u8]) {
+    fn unlock_res(&self, res_key: &[u8], job_id: u64) {
         let mut res_locks = self.res_locks.lock().unwrap();
-        res_locks.remove(res_key);
+        if res_locks.get(res_key) == Some(&job_id) {
+            res_locks.remove(res_key);
+        }
     }
 }
 
+/// Return whether the `job` could wait for the `prev` job on the same
+/// resource, instead of being rejected. The collection is purged once it is
+/// created or truncated.
+fn waits_for(job: &BackgroundJob, prev: &BackgroundJob) -> bool {
+    matches!(job.job, Some(Job::PurgeCollection(_)))
+        && matches!(prev.job, Some(Job::CreateCollection(_) | Job::TruncateCollection(_)))
+}
+
+/// The backoff before the next attempt of a job failed `retried` times.
+fn retry_backoff(retried: u64) -> Duration {
+    Duration::from_secs(1 << retried.min(6)).min(MAX_JOB_BACKOFF)
+}
+
 fn res_key(job: &BackgroundJob) -> Option<Vec<u8>> {
     match job.job.as_ref().unwrap() {
         background_job::Job::CreateCollection(job) => {
@@ -779,6 +1222,45 @@ fn res_key(job: &BackgroundJob) -> Option<Vec<u8>> {
             key.extend_from_slice(job.collection_name.as_bytes());
             Some(key)
         }
+        background_job::Job::TruncateCollection(job) => {
+            let mut key = job.database_id.to_le_bytes().to_vec();
+            key.extend_from_slice(job.collection_name.as_bytes());
+            Some(key)
+        }
+        background_job::Job::RotateDataKey(_) => Some(b"rotate_data_key".to_vec()),
+        background_job::Job::MergeGroup(job) => {
+            let mut key = b"merge_group_".to_vec();
+            key.extend_from_slice(&job.donor_group.to_le_bytes());
+            Some(key)
+        }
         background_job::Job::CreateOneGroup(_) | background_job::Job::PurgeDatabase(_) => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purge_collection_waits_for_creating() {
+        let create = BackgroundJob {
+            job: Some(Job::CreateCollection(CreateCollectionJob::default())),
+            ..Default::default()
+        };
+        let purge = BackgroundJob {
+            job: Some(Job::PurgeCollection(PurgeCollectionJob::default())),
+            ..Default::default()
+        };
+        assert_eq!(res_key(&create), res_key(&purge));
+        assert!(waits_for(&purge, &create));
+        assert!(!waits_for(&create, &purge));
+        assert!(!waits_for(&purge, &purge));
+    }
+
+    #[test]
+    fn backoff_of_failed_jobs() {
+        assert_eq!(retry_backoff(1), Duration::from_secs(2));
+        assert_eq!(retry_backoff(3), Duration::from_secs(8));
+        assert_eq!(retry_backoff(100), MAX_JOB_BACKOFF);
+    }
+}
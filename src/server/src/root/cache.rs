@@ -0,0 +1,207 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::lock::Mutex;
+use sekas_api::server::v1::{CollectionDesc, GroupDesc, NodeDesc};
+
+use super::schema::collection_key;
+use crate::{Error, Result};
+
+/// The in-memory cache of the descriptors read frequently by the root leader.
+///
+/// The cache is owned by the [`super::Schema`] created once the root steps up
+/// as leader, so it never outlives the leadership, and all writes to the
+/// cached descriptors of the leader are applied to it.
+pub(super) struct SchemaCache {
+    pub groups: DescCache<GroupDesc>,
+    pub nodes: DescCache<NodeDesc>,
+    pub collections: DescCache<CollectionDesc>,
+    /// The min snapshot versions of the clone sources, keyed by the source
+    /// collection ids, it is derived from the groups.
+    pub clone_sources: DerivedCache<Arc<HashMap<u64, u64>>>,
+}
+
+impl Default for SchemaCache {
+    fn default() -> Self {
+        SchemaCache {
+            groups: DescCache::new(|desc| desc.id.to_le_bytes().to_vec()),
+            nodes: DescCache::new(|desc| desc.id.to_le_bytes().to_vec()),
+            collections: DescCache::new(|desc| collection_key(desc.db, &desc.name)),
+            clone_sources: DerivedCache::default(),
+        }
+    }
+}
+
+/// The cached descriptors of a collection of the root store, keyed by the keys
+/// of the collection, so that they are listed in the same order as the store.
+pub(super) struct DescCache<T> {
+    key_of: fn(&T) -> Vec<u8>,
+    /// Serialize the writes, so that the cache is updated in the same order as
+    /// the store.
+    write_lock: Mutex<()>,
+    inner: std::sync::Mutex<CachedDescs<T>>,
+}
+
+/// A value derived from the cached descriptors, it is valid until the
+/// descriptors are written.
+pub(super) struct DerivedCache<T> {
+    /// The value and the generation of the descriptors it is derived from.
+    inner: std::sync::Mutex<Option<(u64, T)>>,
+}
+
+struct CachedDescs<T> {
+    /// `None` if the descriptors are not loaded.
+    descs: Option<BTreeMap<Vec<u8>, T>>,
+    /// Increased by each write, the descriptors read from the store before a
+    /// write are not filled into the cache.
+    generation: u64,
+}
+
+impl<T: Clone> DescCache<T> {
+    fn new(key_of: fn(&T) -> Vec<u8>) -> Self {
+        DescCache {
+            key_of,
+            write_lock: Mutex::new(()),
+            inner: std::sync::Mutex::new(CachedDescs { descs: None, generation: 0 }),
+        }
+    }
+
+    /// Return the descriptor of the key, `None` if the cache is not loaded.
+    pub fn get(&self, key: &[u8]) -> Option<Option<T>> {
+        let inner = self.inner.lock().expect("Poisoned");
+        inner.descs.as_ref().map(|descs| descs.get(key).cloned())
+    }
+
+    /// Return all descriptors, `None` if the cache is not loaded.
+    pub fn list(&self) -> Option<Vec<T>> {
+        let inner = self.inner.lock().expect("Poisoned");
+        inner.descs.as_ref().map(|descs| descs.values().cloned().collect())
+    }
+
+    /// Return the generation of the cache, it should be taken before reading
+    /// the descriptors from the store.
+    pub fn generation(&self) -> u64 {
+        self.inner.lock().expect("Poisoned").generation
+    }
+
+    /// Fill the cache with all descriptors read from the store, it is skipped
+    /// if there are any writes since the `generation` is taken.
+    pub fn fill(&self, generation: u64, descs: Vec<T>) {
+        let mut inner = self.inner.lock().expect("Poisoned");
+        if inner.generation == generation {
+            inner.descs =
+                Some(descs.into_iter().map(|desc| ((self.key_of)(&desc), desc)).collect());
+        }
+    }
+
+    /// Apply the `write` to the store, then apply the puts and deletes to the
+    /// cache. The cache is invalidated if the write fails, since it might
+    /// have been applied.
+    pub async fn write<F>(&self, write: F, puts: Vec<T>, deletes: Vec<Vec<u8>>) -> Result<()>
+    where
+        F: Future<Output = Result<()>>,
+    {
+        let _guard = self.write_lock.lock().await;
+        let result = write.await;
+        let mut inner = self.inner.lock().expect("Poisoned");
+        inner.generation += 1;
+        match &result {
+            Ok(()) => {
+                if let Some(descs) = inner.descs.as_mut() {
+                    for key in deletes {
+                        descs.remove(&key);
+                    }
+                    for desc in puts {
+                        descs.insert((self.key_of)(&desc), desc);
+                    }
+                }
+            }
+            // The conditional writes are not applied if the conditions are not met.
+            Err(Error::CasFailed(..)) => {}
+            Err(_) => inner.descs = None,
+        }
+        result
+    }
+}
+
+impl<T> Default for DerivedCache<T> {
+    fn default() -> Self {
+        DerivedCache { inner: std::sync::Mutex::new(None) }
+    }
+}
+
+impl<T: Clone> DerivedCache<T> {
+    /// Return the value derived from the descriptors of the `generation`.
+    pub fn get(&self, generation: u64) -> Option<T> {
+        let inner = self.inner.lock().expect("Poisoned");
+        inner.as_ref().filter(|(g, _)| *g == generation).map(|(_, value)| value.clone())
+    }
+
+    /// Fill the value derived from the descriptors read after the `generation`
+    /// is taken.
+    pub fn fill(&self, generation: u64, value: T) {
+        *self.inner.lock().expect("Poisoned") = Some((generation, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(id: u64, epoch: u64) -> GroupDesc {
+        GroupDesc { id, epoch, ..Default::default() }
+    }
+
+    #[sekas_macro::test]
+    async fn fill_and_write_cache() {
+        let cache = SchemaCache::default().groups;
+        assert!(cache.get(&1u64.to_le_bytes()).is_none());
+        assert!(cache.list().is_none());
+
+        // The descs read before a write are not filled.
+        let generation = cache.generation();
+        cache.write(async { Ok(()) }, vec![group(1, 1)], vec![]).await.unwrap();
+        cache.fill(generation, vec![group(1, 0)]);
+        assert!(cache.list().is_none());
+
+        cache.fill(cache.generation(), vec![group(1, 1), group(2, 1)]);
+        assert_eq!(cache.get(&1u64.to_le_bytes()), Some(Some(group(1, 1))));
+        assert_eq!(cache.get(&3u64.to_le_bytes()), Some(None));
+
+        let deletes = vec![2u64.to_le_bytes().to_vec()];
+        cache.write(async { Ok(()) }, vec![group(1, 2)], deletes).await.unwrap();
+        assert_eq!(cache.list(), Some(vec![group(1, 2)]));
+
+        // The cache is invalidated once a write fails.
+        let write = async { Err(Error::DeadlineExceeded("timeout".to_owned())) };
+        assert!(cache.write(write, vec![group(1, 3)], vec![]).await.is_err());
+        assert!(cache.list().is_none());
+    }
+
+    #[sekas_macro::test]
+    async fn derived_cache_invalidated_by_writes() {
+        let cache = SchemaCache::default();
+        let generation = cache.groups.generation();
+        assert!(cache.clone_sources.get(generation).is_none());
+        cache.clone_sources.fill(generation, Arc::new(HashMap::from([(1, 10)])));
+        assert_eq!(cache.clone_sources.get(generation).unwrap().get(&1), Some(&10));
+
+        cache.groups.write(async { Ok(()) }, vec![group(1, 1)], vec![]).await.unwrap();
+        assert!(cache.clone_sources.get(cache.groups.generation()).is_none());
+    }
+}
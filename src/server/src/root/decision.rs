@@ -0,0 +1,109 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+/// The max number of decisions kept in the log, the oldest decisions are
+/// evicted once the log is full.
+pub const MAX_DECISIONS: usize = 1024;
+
+/// A reconcile decision made by the root scheduler.
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    /// The sequence of this decision, it is increased monotonically.
+    pub seq: u64,
+    /// The unix timestamp in seconds.
+    pub time: u64,
+    pub kind: &'static str,
+    /// What the scheduler decides to do.
+    pub action: String,
+    /// Why the scheduler does that, including the compared scores.
+    pub reason: String,
+}
+
+struct DecisionLogCore {
+    next_seq: u64,
+    decisions: VecDeque<Decision>,
+}
+
+/// A bounded log of the reconcile decisions, to answer why the balancer moves
+/// a replica, a shard or a leader.
+pub struct DecisionLog {
+    capacity: usize,
+    core: Mutex<DecisionLogCore>,
+}
+
+impl DecisionLog {
+    pub fn new(capacity: usize) -> Self {
+        DecisionLog {
+            capacity,
+            core: Mutex::new(DecisionLogCore { next_seq: 1, decisions: VecDeque::default() }),
+        }
+    }
+
+    /// Record a decision, the oldest one is evicted if the log is full.
+    pub fn record(&self, kind: &'static str, action: String, reason: String) {
+        let mut core = self.core.lock().unwrap();
+        let seq = core.next_seq;
+        core.next_seq += 1;
+        if core.decisions.len() >= self.capacity {
+            core.decisions.pop_front();
+        }
+        core.decisions.push_back(Decision {
+            seq,
+            time: super::unix_timestamp(),
+            kind,
+            action,
+            reason,
+        });
+    }
+
+    /// Return the latest `limit` decisions, the newest one comes first.
+    pub fn recent(&self, limit: usize) -> Vec<Decision> {
+        let core = self.core.lock().unwrap();
+        core.decisions.iter().rev().take(limit).cloned().collect()
+    }
+}
+
+impl Default for DecisionLog {
+    fn default() -> Self {
+        DecisionLog::new(MAX_DECISIONS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decision_log_is_bounded() {
+        let log = DecisionLog::new(3);
+        assert!(log.recent(10).is_empty());
+
+        for i in 0..5 {
+            log.record("migrate shard", format!("move shard {i}"), "overfull".to_owned());
+        }
+        let decisions = log.recent(10);
+        assert_eq!(decisions.len(), 3);
+        assert_eq!(decisions.iter().map(|d| d.seq).collect::<Vec<_>>(), vec![5, 4, 3]);
+        assert_eq!(decisions[0].action, "move shard 4");
+
+        let decisions = log.recent(1);
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].seq, 5);
+    }
+}
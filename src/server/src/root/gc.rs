@@ -0,0 +1,224 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{Error, Result};
+
+/// The max TTL of a GC lease.
+pub const MAX_GC_LEASE_TTL: Duration = Duration::from_secs(600);
+
+struct GcLease {
+    collection_id: u64,
+    timestamp: u64,
+    expired_at: u64,
+}
+
+/// Coordinate the GC horizon of collections. The versions older than the
+/// retention are allowed to be collected, unless a lease holds them for
+/// long-running readers.
+///
+/// The leases are kept in memory of the root leader, so the horizon is held
+/// for `MAX_GC_LEASE_TTL` after stepping leader, to give the lease holders a
+/// chance to renew their leases. The lease ids are random, so they are unique
+/// across root leaders, and only known by their holders.
+pub struct GcLeases {
+    /// The retention in nanoseconds.
+    retention: u64,
+    /// The timestamp in nanoseconds of stepping leader.
+    started_at: u64,
+    leases: Mutex<HashMap<u64, GcLease>>,
+}
+
+impl GcLeases {
+    pub fn new(retention: Duration, now: u64) -> Self {
+        GcLeases {
+            retention: retention.as_nanos() as u64,
+            started_at: now,
+            leases: Mutex::default(),
+        }
+    }
+
+    /// Return the min GC timestamp of the collection, the AS-OF reads above
+    /// this timestamp are valid.
+    pub fn min_gc_timestamp(&self, collection_id: u64, now: u64) -> u64 {
+        let mut horizon = now.saturating_sub(self.retention);
+        if self.in_failover_window(now) {
+            horizon = std::cmp::min(horizon, self.started_at.saturating_sub(self.retention));
+        }
+
+        let mut leases = self.leases.lock().unwrap();
+        leases.retain(|_, lease| lease.expired_at > now);
+        leases
+            .values()
+            .filter(|lease| lease.collection_id == collection_id)
+            .map(|lease| lease.timestamp)
+            .fold(horizon, std::cmp::min)
+    }
+
+    /// Acquire a new lease if `lease_id` is zero or renew the lease, to hold
+    /// the GC horizon of the collection below `timestamp`. Return the lease id
+    /// and the expired timestamp.
+    pub fn hold(
+        &self,
+        lease_id: u64,
+        collection_id: u64,
+        timestamp: u64,
+        ttl: Duration,
+        now: u64,
+    ) -> Result<(u64, u64)> {
+        if ttl.is_zero() || ttl > MAX_GC_LEASE_TTL {
+            return Err(Error::InvalidArgument(format!(
+                "the ttl of gc lease should be in (0, {MAX_GC_LEASE_TTL:?}]"
+            )));
+        }
+
+        let expired_at = now + ttl.as_nanos() as u64;
+        let renewed = lease_id != 0 && {
+            let leases = self.leases.lock().unwrap();
+            leases.get(&lease_id).map(|lease| lease.expired_at > now).unwrap_or_default()
+        };
+        if !renewed {
+            // A lease unknown to this leader might be acquired from the former one, it is
+            // only allowed before the former leases expire, and if the versions are not
+            // collected yet.
+            if lease_id != 0 && !self.in_failover_window(now) {
+                return Err(Error::InvalidArgument(format!(
+                    "gc lease {lease_id} is not found or expired"
+                )));
+            }
+            let min_gc_timestamp = self.min_gc_timestamp(collection_id, now);
+            if timestamp < min_gc_timestamp {
+                return Err(Error::InvalidArgument(format!(
+                    "timestamp {timestamp} is less than the min gc timestamp {min_gc_timestamp}"
+                )));
+            }
+        }
+
+        let mut leases = self.leases.lock().unwrap();
+        let lease_id = if lease_id == 0 {
+            loop {
+                let lease_id = rand::random::<u64>();
+                if lease_id != 0 && !leases.contains_key(&lease_id) {
+                    break lease_id;
+                }
+            }
+        } else {
+            lease_id
+        };
+        let lease =
+            leases.entry(lease_id).or_insert(GcLease { collection_id, timestamp, expired_at });
+        if lease.collection_id != collection_id {
+            return Err(Error::InvalidArgument(format!(
+                "gc lease {lease_id} belongs to collection {}",
+                lease.collection_id
+            )));
+        }
+        // The horizon is never moved backward by renewing.
+        lease.timestamp = std::cmp::min(lease.timestamp, timestamp);
+        lease.expired_at = expired_at;
+        Ok((lease_id, expired_at))
+    }
+
+    /// Release the lease, the released or expired leases are ignored.
+    pub fn release(&self, lease_id: u64) {
+        self.leases.lock().unwrap().remove(&lease_id);
+    }
+
+    /// Whether the leases acquired from the former leader might not expire.
+    fn in_failover_window(&self, now: u64) -> bool {
+        now < self.started_at + MAX_GC_LEASE_TTL.as_nanos() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+    const RETENTION: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn gc_horizon_after_step_leader() {
+        let started_at = 1000 * SEC;
+        let leases = GcLeases::new(RETENTION, started_at);
+        assert_eq!(leases.min_gc_timestamp(1, started_at), started_at - 60 * SEC);
+
+        // The horizon is held until the former leases could be renewed.
+        let now = started_at + 10 * SEC;
+        assert_eq!(leases.min_gc_timestamp(1, now), started_at - 60 * SEC);
+        let now = started_at + MAX_GC_LEASE_TTL.as_nanos() as u64;
+        assert_eq!(leases.min_gc_timestamp(1, now), now - 60 * SEC);
+    }
+
+    #[test]
+    fn hold_and_release_gc_lease() {
+        let mut now = 1000 * SEC;
+        let leases = GcLeases::new(RETENTION, now);
+        now += MAX_GC_LEASE_TTL.as_nanos() as u64;
+
+        // The versions already collected could not be held.
+        let ttl = Duration::from_secs(10);
+        assert!(leases.hold(0, 1, now - 120 * SEC, ttl, now).is_err());
+        assert!(leases.hold(0, 1, now, Duration::ZERO, now).is_err());
+
+        let timestamp = now - 30 * SEC;
+        let (lease_id, expired_at) = leases.hold(0, 1, timestamp, ttl, now).unwrap();
+        assert_eq!(expired_at, now + 10 * SEC);
+        now += 60 * SEC - 1;
+        // The lease is expired.
+        assert_eq!(leases.min_gc_timestamp(1, now), now - 60 * SEC);
+
+        let (lease_id, _) = leases.hold(0, 1, now - 30 * SEC, ttl, now).unwrap();
+        let timestamp = now - 30 * SEC;
+        now += 5 * SEC;
+        let ttl = Duration::from_secs(120);
+        assert_eq!(leases.hold(lease_id, 1, now, ttl, now).unwrap().0, lease_id);
+        assert!(leases.hold(lease_id, 2, now, ttl, now).is_err());
+        now += 60 * SEC;
+        // The lease is renewed, and the horizon is not moved forward.
+        assert_eq!(leases.min_gc_timestamp(1, now), timestamp);
+        // Other collections are not affected.
+        assert_eq!(leases.min_gc_timestamp(2, now), now - 60 * SEC);
+
+        leases.release(lease_id);
+        assert_eq!(leases.min_gc_timestamp(1, now), now - 60 * SEC);
+
+        // The unknown or released leases could not be renewed after the failover
+        // window.
+        assert!(leases.hold(lease_id, 1, now, ttl, now).is_err());
+    }
+
+    #[test]
+    fn renew_gc_lease_after_step_leader() {
+        let mut now = 1000 * SEC;
+        let former = GcLeases::new(RETENTION, now);
+        let ttl = Duration::from_secs(120);
+        let (lease_id, _) = former.hold(0, 1, now - 30 * SEC, ttl, now).unwrap();
+
+        // The lease acquired from the former leader is renewed in the failover window.
+        now += 10 * SEC;
+        let leases = GcLeases::new(RETENTION, now);
+        assert_eq!(leases.hold(lease_id, 1, now - 30 * SEC, ttl, now).unwrap().0, lease_id);
+
+        // The new leases never reuse the ids of the former leader.
+        let (new_lease_id, _) = leases.hold(0, 1, now - 30 * SEC, ttl, now).unwrap();
+        assert_ne!(new_lease_id, lease_id);
+        // Releasing the new lease does not drop the renewed one.
+        leases.release(new_lease_id);
+        assert!(leases.leases.lock().unwrap().contains_key(&lease_id));
+    }
+}
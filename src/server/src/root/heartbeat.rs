@@ -16,14 +16,17 @@
 use std::collections::HashSet;
 use std::ops::Add;
 use std::sync::Arc;
+use std::time::Duration;
 use std::vec;
 
+use futures::StreamExt;
 use log::{info, trace, warn};
 use sekas_api::server::v1::watch_response::{update_event, UpdateEvent};
 use sekas_api::server::v1::*;
-use tokio::time::Instant;
+use sekas_client::NodeClient;
+use sekas_runtime::time::Instant;
 
-use super::{HeartbeatTask, Root, Schema};
+use super::{HeartbeatTask, Root, Schema, SnapshotStats};
 use crate::constants::ROOT_GROUP_ID;
 use crate::root::metrics;
 use crate::root::schema::ReplicaNodes;
@@ -74,29 +77,52 @@ impl Root {
             })
         }
 
+        let limit = self.move_shard_limit().await?;
+        piggybacks.push(PiggybackRequest {
+            info: Some(piggyback_request::Info::SyncMoveShardLimit(SyncMoveShardLimitRequest {
+                limit: Some(limit),
+            })),
+        });
+
+        let entries = schema.dynamic_config().await?;
+        if !entries.is_empty() {
+            piggybacks.push(PiggybackRequest {
+                info: Some(piggyback_request::Info::SyncConfig(SyncConfigRequest { entries })),
+            });
+        }
+
+        if self.shared.key_manager.is_enabled() {
+            let keys = schema.data_keys().await?;
+            if !keys.is_empty() {
+                piggybacks.push(PiggybackRequest {
+                    info: Some(piggyback_request::Info::SyncDataKeys(SyncDataKeysRequest { keys })),
+                });
+            }
+        }
+
         let resps = {
             let _timer = metrics::HEARTBEAT_NODES_RPC_DURATION_SECONDS.start_timer();
             metrics::HEARTBEAT_NODES_BATCH_SIZE.set(nodes.len() as i64);
-            let mut handles = Vec::new();
+            // The heartbeats are sent with bounded concurrency, and each one is bounded by
+            // the timeout, so a slow node doesn't delay the heartbeats of the
+            // others.
+            let timeout = self.shared.dynamic_config.heartbeat_timeout();
+            let mut requests = Vec::with_capacity(nodes.len());
             for n in &nodes {
                 trace!("attempt send heartbeat. node={}, target={}", n.id, n.addr);
-                let piggybacks = piggybacks.to_owned();
                 let client = self.shared.transport_manager.get_node_client(n.addr.to_owned())?;
-                let handle = sekas_runtime::spawn(async move {
-                    client
-                        .root_heartbeat(HeartbeatRequest {
-                            piggybacks,
-                            timestamp: 0, // TODO: use hlc
-                        })
-                        .await
-                });
-                handles.push(handle);
+                let req = HeartbeatRequest {
+                    piggybacks: piggybacks.to_owned(),
+                    timestamp: 0, // TODO: use hlc
+                    node_id: n.id,
+                    incarnation: n.incarnation,
+                };
+                requests.push(heartbeat_node(client, req, timeout));
             }
-            let mut resps = Vec::with_capacity(handles.len());
-            for handle in handles.into_iter() {
-                resps.push(handle.await?)
-            }
-            resps
+            futures::stream::iter(requests)
+                .buffered(self.cfg.heartbeat_parallelism())
+                .collect::<Vec<_>>()
+                .await
         };
 
         let last_heartbeat = Instant::now();
@@ -110,6 +136,9 @@ impl Root {
                     for resp in &res.piggybacks {
                         match resp.info.as_ref().unwrap() {
                             piggyback_response::Info::SyncRoot(_)
+                            | piggyback_response::Info::SyncDataKeys(_)
+                            | piggyback_response::Info::SyncMoveShardLimit(_)
+                            | piggyback_response::Info::SyncConfig(_)
                             | piggyback_response::Info::CollectMovingShardState(_) => {}
                             piggyback_response::Info::CollectStats(ref resp) => {
                                 self.handle_collect_stats(&schema, resp, n.to_owned()).await?
@@ -137,7 +166,10 @@ impl Root {
             }
         }
         self.heartbeat_queue
-            .try_schedule(heartbeat_tasks, last_heartbeat.add(self.cfg.heartbeat_interval()))
+            .try_schedule(
+                heartbeat_tasks,
+                last_heartbeat.add(self.shared.dynamic_config.heartbeat_interval()),
+            )
             .await;
 
         Ok(())
@@ -150,20 +182,48 @@ impl Root {
         node: &NodeDesc,
     ) -> Result<()> {
         if let Some(ns) = &resp.node_stats {
+            for r in &ns.catching_up_replicas {
+                info!(
+                    "node {} group {} replica {} is catching up after restart, applied {} committed {}",
+                    node.id, r.group_id, r.replica_id, r.applied_index, r.committed_index,
+                );
+            }
+            self.ongoing_stats.update_snapshot_stats(
+                node.id,
+                SnapshotStats { sending: ns.sending_snapshots, receiving: ns.receiving_snapshots },
+            );
+            self.ongoing_stats.update_moving_shards(node.id, resp.moving_shards.clone());
+            self.ongoing_stats.update_node_stats(node.id, ns.clone());
+            self.ongoing_stats.update_shard_stats(&resp.group_stats);
+            self.ongoing_stats.update_apply_lags(
+                node.id,
+                self.cfg.replica_apply_lag_threshold,
+                &resp.group_stats,
+                &resp.replica_stats,
+            );
+            let lagging =
+                self.ongoing_stats.get_lagging_replicas(self.cfg.replica_apply_lag_persist());
+            metrics::REPLICA_APPLY_LAG_MAX.set(self.ongoing_stats.get_max_apply_lag() as i64);
+            metrics::LAGGING_REPLICAS.set(lagging.len() as i64);
             let mut node = node.to_owned();
             let _timer = super::metrics::HEARTBEAT_HANDLE_NODE_STATS_DURATION_SECONDS.start_timer();
             let new_group_count = ns.group_count as u64;
             let new_leader_count = ns.leader_count as u64;
             let mut cap = node.capacity.take().unwrap();
-            if new_group_count != cap.replica_count || new_leader_count != cap.leader_count {
+            if new_group_count != cap.replica_count
+                || new_leader_count != cap.leader_count
+                || ns.binary_version != node.binary_version
+            {
                 super::metrics::HEARTBEAT_UPDATE_NODE_STATS_TOTAL.inc();
                 cap.replica_count = new_group_count;
                 cap.leader_count = new_leader_count;
+                node.binary_version = ns.binary_version;
                 info!(
-                    "update node stats by heartbeat response. node={}, replica_count={}, leader_count={}",
+                    "update node stats by heartbeat response. node={}, replica_count={}, leader_count={}, binary_version={}",
                     node.id,
                     cap.replica_count,
                     cap.leader_count,
+                    node.binary_version,
                 );
                 node.capacity = Some(cap);
                 schema.update_node(node).await?;
@@ -223,9 +283,8 @@ impl Root {
             changed_group_states.insert(state.group_id);
         }
 
-        let mut states = schema.list_group_state().await?; // TODO: fix poor performance.
-        states.retain(|s| changed_group_states.contains(&s.group_id));
-        for state in states {
+        for group_id in changed_group_states {
+            let state = schema.get_group_state(group_id).await?;
             update_events.push(UpdateEvent { event: Some(update_event::Event::GroupState(state)) })
         }
 
@@ -241,3 +300,17 @@ impl Root {
         Ok(())
     }
 }
+
+async fn heartbeat_node(
+    client: NodeClient,
+    req: HeartbeatRequest,
+    timeout: Option<Duration>,
+) -> std::result::Result<HeartbeatResponse, tonic::Status> {
+    let Some(timeout) = timeout else {
+        return client.root_heartbeat(req).await;
+    };
+    match sekas_runtime::time::timeout(timeout, client.root_heartbeat(req)).await {
+        Ok(resp) => resp,
+        Err(_) => Err(tonic::Status::deadline_exceeded(format!("heartbeat timeout {timeout:?}"))),
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sekas_api::server::v1::{GroupDesc, ReplicaRole};
+
+/// The prefix of the keys of group events in the meta collection.
+pub(super) const GROUP_EVENT_KEY_PREFIX: &[u8] = b"group_event_";
+
+/// The max number of events kept for each group, the oldest events are purged
+/// once it is exceeded.
+pub(super) const MAX_EVENTS_PER_GROUP: usize = 256;
+
+/// Return the prefix of the keys of the events of the group.
+pub(super) fn group_event_prefix(group_id: u64) -> Vec<u8> {
+    let mut buf =
+        Vec::with_capacity(GROUP_EVENT_KEY_PREFIX.len() + 2 * core::mem::size_of::<u64>());
+    buf.extend_from_slice(GROUP_EVENT_KEY_PREFIX);
+    buf.extend_from_slice(&group_id.to_be_bytes());
+    buf
+}
+
+/// Return the key of the group event in the meta collection, the ids are
+/// encoded in big endian so that the events of a group are ordered by time.
+pub(super) fn group_event_key(group_id: u64, id: u64) -> Vec<u8> {
+    let mut buf = group_event_prefix(group_id);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf
+}
+
+/// Compare the descriptors of a group, return the `(kind, detail)` of the
+/// changes. The epoch bumps without any membership or shard changes are
+/// reported as `bump_epoch`.
+pub(super) fn diff_group_desc(
+    prev: Option<&GroupDesc>,
+    desc: &GroupDesc,
+) -> Vec<(&'static str, String)> {
+    let Some(prev) = prev else {
+        let replicas = desc.replicas.iter().map(|r| r.id).collect::<Vec<_>>();
+        let shards = desc.shards.iter().map(|s| s.id).collect::<Vec<_>>();
+        return vec![("create_group", format!("replicas={replicas:?}, shards={shards:?}"))];
+    };
+
+    let mut events = Vec::new();
+    for r in &desc.replicas {
+        let role = ReplicaRole::from_i32(r.role).unwrap_or_default().as_str_name();
+        match prev.replicas.iter().find(|p| p.id == r.id) {
+            None => events.push((
+                "add_replica",
+                format!("replica={}, node={}, role={role}", r.id, r.node_id),
+            )),
+            Some(p) if p.role != r.role => {
+                let prev_role = ReplicaRole::from_i32(p.role).unwrap_or_default().as_str_name();
+                events.push(("change_role", format!("replica={}, role={prev_role}->{role}", r.id)));
+            }
+            Some(_) => {}
+        }
+    }
+    for p in &prev.replicas {
+        if !desc.replicas.iter().any(|r| r.id == p.id) {
+            events.push(("remove_replica", format!("replica={}, node={}", p.id, p.node_id)));
+        }
+    }
+    for s in &desc.shards {
+        if !prev.shards.iter().any(|p| p.id == s.id) {
+            events.push(("add_shard", format!("shard={}, collection={}", s.id, s.collection_id)));
+        }
+    }
+    for p in &prev.shards {
+        if !desc.shards.iter().any(|s| s.id == p.id) {
+            events
+                .push(("remove_shard", format!("shard={}, collection={}", p.id, p.collection_id)));
+        }
+    }
+    if events.is_empty() && prev.epoch != desc.epoch {
+        events.push(("bump_epoch", format!("epoch={}->{}", prev.epoch, desc.epoch)));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::{ReplicaDesc, ShardDesc};
+
+    use super::*;
+
+    fn group(epoch: u64, replicas: &[(u64, ReplicaRole)], shards: &[u64]) -> GroupDesc {
+        GroupDesc {
+            id: 1,
+            epoch,
+            replicas: replicas
+                .iter()
+                .map(|(id, role)| ReplicaDesc { id: *id, node_id: *id, role: *role as i32 })
+                .collect(),
+            shards: shards.iter().map(|id| ShardDesc::whole(*id, 1)).collect(),
+        }
+    }
+
+    #[test]
+    fn diff_group_changes() {
+        let prev = group(1, &[(1, ReplicaRole::Voter), (2, ReplicaRole::Voter)], &[1]);
+        assert_eq!(diff_group_desc(None, &prev)[0].0, "create_group");
+        assert!(diff_group_desc(Some(&prev), &prev).is_empty());
+
+        let desc = group(2, &[(1, ReplicaRole::Voter), (3, ReplicaRole::Learner)], &[1]);
+        let kinds =
+            diff_group_desc(Some(&prev), &desc).into_iter().map(|e| e.0).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["add_replica", "remove_replica"]);
+
+        let desc = group(3, &[(1, ReplicaRole::Voter), (2, ReplicaRole::Learner)], &[2]);
+        let kinds =
+            diff_group_desc(Some(&prev), &desc).into_iter().map(|e| e.0).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["change_role", "add_shard", "remove_shard"]);
+
+        let desc = group(4, &[(1, ReplicaRole::Voter), (2, ReplicaRole::Voter)], &[1]);
+        let events = diff_group_desc(Some(&prev), &desc);
+        assert_eq!(events, vec![("bump_epoch", "epoch=1->4".to_owned())]);
+    }
+
+    #[test]
+    fn group_event_key_is_ordered() {
+        assert!(group_event_key(1, 1) < group_event_key(1, 2));
+        assert!(group_event_key(1, u64::MAX) < group_event_key(2, 0));
+        assert!(group_event_key(1, 2).starts_with(&group_event_prefix(1)));
+        assert!(!group_event_key(2, 2).starts_with(&group_event_prefix(1)));
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use sekas_api::server::v1::*;
+use sekas_schema::shard;
+
+use crate::constants::ROOT_GROUP_ID;
+use crate::RootConfig;
+
+/// A shard whose sampled accesses dominate its group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotShard {
+    pub group_id: u64,
+    pub shard_id: u64,
+    /// The start key of the new shard, the hottest key is moved to the new
+    /// shard. It is empty for the append pattern shards, the leader splits
+    /// them near the tail instead.
+    pub split_key: Vec<u8>,
+    pub accesses: u64,
+    pub group_accesses: u64,
+}
+
+/// Find the hot shards to split. A shard is hot once its sampled accesses
+/// exceed `hot_shard_split_min_accesses`, and take at least
+/// `hot_shard_split_ratio` of the accesses of its group.
+pub fn find_hot_shards<F>(cfg: &RootConfig, groups: &[GroupDesc], shard_stats: F) -> Vec<HotShard>
+where
+    F: Fn(u64) -> Option<ShardStats>,
+{
+    if cfg.hot_shard_split_min_accesses == 0 {
+        return vec![];
+    }
+
+    let mut hot_shards = vec![];
+    for group in groups.iter().filter(|g| g.id != ROOT_GROUP_ID) {
+        let shards = group
+            .shards
+            .iter()
+            .filter_map(|s| shard_stats(s.id).map(|stats| (s, stats)))
+            .collect::<Vec<_>>();
+        let group_accesses = shards.iter().map(|(_, stats)| accesses(stats)).sum::<u64>();
+        for (shard, stats) in shards {
+            let accesses = accesses(&stats);
+            if accesses < cfg.hot_shard_split_min_accesses
+                || (accesses as f64) < group_accesses as f64 * cfg.hot_shard_split_ratio()
+            {
+                continue;
+            }
+            // The hottest key of an append pattern shard moves on with the
+            // writes, so the leader estimates the split key near the tail.
+            let split_key = if stats.append_writes {
+                vec![]
+            } else if let Some(split_key) = split_key(shard, &stats) {
+                split_key
+            } else {
+                continue;
+            };
+            hot_shards.push(HotShard {
+                group_id: group.id,
+                shard_id: shard.id,
+                split_key,
+                accesses,
+                group_accesses,
+            });
+        }
+    }
+    hot_shards
+}
+
+/// Pick the group to move the new shard to. The groups whose replicas share
+/// less nodes with the source group are preferred, then the groups led on
+/// another node, and then the groups serving less shards.
+pub fn pick_dest_group<F>(src: &GroupDesc, groups: &[GroupDesc], leader_node: F) -> Option<u64>
+where
+    F: Fn(u64) -> Option<u64>,
+{
+    let src_nodes = src.replicas.iter().map(|r| r.node_id).collect::<HashSet<_>>();
+    let src_leader = leader_node(src.id);
+    groups
+        .iter()
+        .filter(|g| g.id != src.id && g.id != ROOT_GROUP_ID && !g.replicas.is_empty())
+        .min_by_key(|g| {
+            let shared_nodes = g.replicas.iter().filter(|r| src_nodes.contains(&r.node_id)).count();
+            let same_leader = src_leader.is_some() && leader_node(g.id) == src_leader;
+            (shared_nodes, same_leader, g.shards.len(), g.id)
+        })
+        .map(|g| g.id)
+}
+
+fn accesses(stats: &ShardStats) -> u64 {
+    stats.hot_keys.iter().map(|k| k.reads + k.writes).sum()
+}
+
+/// Return the key to split the shard at, so that the hottest key starts the
+/// new shard. The hottest key is isolated by splitting at its successor if it
+/// is the start of the shard already.
+fn split_key(shard: &ShardDesc, stats: &ShardStats) -> Option<Vec<u8>> {
+    let hottest = stats
+        .hot_keys
+        .iter()
+        .filter(|k| shard::belong_to(shard, &k.key))
+        .max_by_key(|k| k.reads + k.writes)?;
+    let mut split_key = hottest.key.clone();
+    if split_key == shard::start_key(shard) {
+        split_key.push(0);
+    }
+    shard::belong_to(shard, &split_key).then_some(split_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard(id: u64, start: &[u8], end: &[u8]) -> ShardDesc {
+        ShardDesc {
+            id,
+            collection_id: 1,
+            range: Some(RangePartition { start: start.to_vec(), end: end.to_vec() }),
+            ..Default::default()
+        }
+    }
+
+    fn group(id: u64, nodes: &[u64], shards: Vec<ShardDesc>) -> GroupDesc {
+        GroupDesc {
+            id,
+            replicas: nodes
+                .iter()
+                .map(|&node_id| ReplicaDesc {
+                    id: id * 10 + node_id,
+                    node_id,
+                    ..Default::default()
+                })
+                .collect(),
+            shards,
+            ..Default::default()
+        }
+    }
+
+    fn stats(shard_id: u64, hot_keys: &[(&[u8], u64)]) -> ShardStats {
+        ShardStats {
+            shard_id,
+            collection_id: 1,
+            hot_keys: hot_keys
+                .iter()
+                .map(|(key, reads)| HotKey { key: key.to_vec(), reads: *reads, writes: 0 })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn find_dominating_shards() {
+        let groups = vec![
+            group(ROOT_GROUP_ID, &[1], vec![shard(1, b"", b"")]),
+            group(1, &[1], vec![shard(10, b"", b"m"), shard(11, b"m", b"")]),
+        ];
+        let all_stats = vec![
+            stats(1, &[(b"a", 1000)]),
+            stats(10, &[(b"b", 90), (b"c", 10)]),
+            stats(11, &[(b"m", 10)]),
+        ];
+        let shard_stats = |id| all_stats.iter().find(|s| s.shard_id == id).cloned();
+
+        let disabled = RootConfig::default();
+        assert!(find_hot_shards(&disabled, &groups, shard_stats).is_empty());
+
+        let cfg = RootConfig { hot_shard_split_min_accesses: 50, ..Default::default() };
+        let hot_shards = find_hot_shards(&cfg, &groups, shard_stats);
+        assert_eq!(
+            hot_shards,
+            vec![HotShard {
+                group_id: 1,
+                shard_id: 10,
+                split_key: b"b".to_vec(),
+                accesses: 100,
+                group_accesses: 110,
+            }]
+        );
+
+        let cfg = RootConfig { hot_shard_split_min_accesses: 200, ..Default::default() };
+        assert!(find_hot_shards(&cfg, &groups, shard_stats).is_empty());
+
+        // The split key of append pattern shards is left to the leader.
+        let append_stats =
+            |id| shard_stats(id).map(|stats| ShardStats { append_writes: id == 10, ..stats });
+        let cfg = RootConfig { hot_shard_split_min_accesses: 50, ..Default::default() };
+        let hot_shards = find_hot_shards(&cfg, &groups, append_stats);
+        assert_eq!(hot_shards.len(), 1);
+        assert!(hot_shards[0].split_key.is_empty());
+
+        let cfg = RootConfig {
+            hot_shard_split_min_accesses: 50,
+            hot_shard_split_ratio: Some(0.95),
+            ..Default::default()
+        };
+        assert!(find_hot_shards(&cfg, &groups, shard_stats).is_empty());
+    }
+
+    #[test]
+    fn split_at_hottest_key() {
+        let desc = shard(1, b"b", b"d");
+        let split = |hot_keys| split_key(&desc, &stats(1, hot_keys));
+        assert_eq!(split(&[(b"b", 1), (b"c", 5)]), Some(b"c".to_vec()));
+        assert_eq!(split(&[(b"b", 5), (b"c", 1)]), Some(b"b\0".to_vec()));
+        // The keys out of the shard are ignored.
+        assert_eq!(split(&[(b"a", 5), (b"c", 1)]), Some(b"c".to_vec()));
+        assert_eq!(split(&[(b"d", 5)]), None);
+        assert_eq!(split(&[]), None);
+
+        let desc = shard(1, b"b", b"b\0");
+        assert_eq!(split_key(&desc, &stats(1, &[(b"b", 5)])), None);
+    }
+
+    #[test]
+    fn pick_group_on_other_nodes() {
+        let src = group(1, &[1, 2, 3], vec![shard(10, b"", b"")]);
+        let groups = vec![
+            group(ROOT_GROUP_ID, &[4, 5, 6], vec![]),
+            src.clone(),
+            group(2, &[1, 2, 4], vec![]),
+            group(3, &[4, 5, 6], vec![shard(11, b"", b"")]),
+            group(4, &[3, 5, 6], vec![]),
+        ];
+        assert_eq!(pick_dest_group(&src, &groups, |_| None), Some(3));
+        assert_eq!(pick_dest_group(&src, &groups[..3], |_| None), Some(2));
+
+        // Prefer the group led on another node.
+        let src = group(1, &[1, 2, 3], vec![]);
+        let groups = vec![src.clone(), group(2, &[1, 2, 3], vec![]), group(3, &[1, 2, 3], vec![])];
+        let leader_node = |id| Some(if id == 3 { 2 } else { 1 });
+        assert_eq!(pick_dest_group(&src, &groups, leader_node), Some(3));
+        assert_eq!(pick_dest_group(&src, &groups[..1], leader_node), None);
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// The prefix of the keys of idempotency records in the meta collection.
+pub(super) const IDEMPOTENCY_KEY_PREFIX: &[u8] = b"idempotency_record_";
+
+/// The prefix of the index of idempotency records ordered by the creation
+/// time, so the expired records are purged without listing all of them.
+pub(super) const IDEMPOTENCY_INDEX_KEY_PREFIX: &[u8] = b"idempotency_index_";
+
+/// Return the key of the idempotency record in the meta collection.
+pub(super) fn idempotency_key(token: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(IDEMPOTENCY_KEY_PREFIX.len() + token.len());
+    buf.extend_from_slice(IDEMPOTENCY_KEY_PREFIX);
+    buf.extend_from_slice(token.as_bytes());
+    buf
+}
+
+/// Return the index key of the idempotency record, which is ordered by the
+/// creation time.
+pub(super) fn idempotency_index_key(created_at: u64, token: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(IDEMPOTENCY_INDEX_KEY_PREFIX.len() + 8 + token.len());
+    buf.extend_from_slice(IDEMPOTENCY_INDEX_KEY_PREFIX);
+    buf.extend_from_slice(&created_at.to_be_bytes());
+    buf.extend_from_slice(token.as_bytes());
+    buf
+}
+
+/// Parse the creation time and the token from the index key.
+pub(super) fn parse_idempotency_index_key(key: &[u8]) -> Option<(u64, String)> {
+    let key = key.strip_prefix(IDEMPOTENCY_INDEX_KEY_PREFIX)?;
+    if key.len() < 8 {
+        return None;
+    }
+    let (created_at, token) = key.split_at(8);
+    let created_at = u64::from_be_bytes(created_at.try_into().ok()?);
+    let token = String::from_utf8(token.to_owned()).ok()?;
+    Some((created_at, token))
+}
+
+/// Return whether the idempotency record created at `created_at` is expired at
+/// `now`, both of them are unix timestamps in seconds.
+pub(super) fn is_expired(created_at: u64, now: u64, retention_sec: u64) -> bool {
+    created_at.saturating_add(retention_sec) <= now
+}
+
+/// The locks of the ongoing operations carrying idempotency tokens, so that a
+/// retry waits for the original operation instead of executing concurrently.
+#[derive(Default)]
+pub(super) struct IdempotencyLocks {
+    locks: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+}
+
+pub(super) struct IdempotencyGuard<'a> {
+    locks: &'a IdempotencyLocks,
+    token: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl IdempotencyLocks {
+    pub(super) async fn lock(&self, token: &str) -> IdempotencyGuard<'_> {
+        let lock = {
+            let mut locks = self.locks.lock().expect("Poisoned");
+            locks.entry(token.to_owned()).or_default().clone()
+        };
+        let guard = lock.lock_owned().await;
+        IdempotencyGuard { locks: self, token: token.to_owned(), guard: Some(guard) }
+    }
+}
+
+impl<'a> Drop for IdempotencyGuard<'a> {
+    fn drop(&mut self) {
+        self.guard.take();
+        let mut locks = self.locks.locks.lock().expect("Poisoned");
+        // Only the map holds the lock if there is no waiter.
+        if locks.get(&self.token).map(|lock| Arc::strong_count(lock) == 1).unwrap_or_default() {
+            locks.remove(&self.token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idempotency_key_with_prefix() {
+        assert!(idempotency_key("token").starts_with(IDEMPOTENCY_KEY_PREFIX));
+        assert_ne!(idempotency_key("a"), idempotency_key("b"));
+        assert!(!IDEMPOTENCY_KEY_PREFIX.starts_with(IDEMPOTENCY_INDEX_KEY_PREFIX));
+        assert!(!IDEMPOTENCY_INDEX_KEY_PREFIX.starts_with(IDEMPOTENCY_KEY_PREFIX));
+    }
+
+    #[test]
+    fn idempotency_index_key_ordered_by_created_at() {
+        assert!(idempotency_index_key(9, "b") < idempotency_index_key(10, "a"));
+        assert!(idempotency_index_key(256, "a") < idempotency_index_key(256, "b"));
+        let key = idempotency_index_key(256, "token");
+        assert_eq!(parse_idempotency_index_key(&key), Some((256, "token".to_owned())));
+        assert_eq!(parse_idempotency_index_key(IDEMPOTENCY_INDEX_KEY_PREFIX), None);
+    }
+
+    #[test]
+    fn idempotency_record_expiration() {
+        assert!(!is_expired(100, 109, 10));
+        assert!(is_expired(100, 110, 10));
+    }
+
+    #[sekas_macro::test]
+    async fn idempotency_locks_are_released() {
+        let locks = IdempotencyLocks::default();
+        {
+            let _guard = locks.lock("a").await;
+            let _other = locks.lock("b").await;
+            assert_eq!(locks.locks.lock().unwrap().len(), 2);
+        }
+        assert!(locks.locks.lock().unwrap().is_empty());
+    }
+}
@@ -14,33 +14,37 @@
 
 use std::collections::{hash_map, HashMap};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+
+use sekas_runtime::time::Instant;
+
+use crate::DynamicConfig;
 
 #[derive(Clone)]
 pub struct NodeLiveness {
-    expiration: u128,
+    /// `None` means the node is expired.
+    expiration: Option<Instant>,
 }
 
 impl NodeLiveness {
     pub fn is_dead(&self) -> bool {
-        self.expiration < current_timestamp()
+        self.expiration.map(|e| e < Instant::now()).unwrap_or(true)
     }
 
     #[allow(dead_code)]
     pub fn is_alive(&self) -> bool {
-        self.expiration > current_timestamp()
+        self.expiration.map(|e| e > Instant::now()).unwrap_or_default()
     }
 }
 
 #[derive(Clone)]
 pub struct Liveness {
-    liveness_threshold: Duration,
+    dynamic_config: Arc<DynamicConfig>,
     nodes: Arc<Mutex<HashMap<u64, NodeLiveness>>>,
 }
 
 impl Liveness {
-    pub fn new(liveness_threshold: Duration) -> Self {
-        Self { liveness_threshold, nodes: Default::default() }
+    pub fn new(dynamic_config: Arc<DynamicConfig>) -> Self {
+        Self { dynamic_config, nodes: Default::default() }
     }
 
     pub fn get(&self, node: &u64) -> NodeLiveness {
@@ -68,6 +72,12 @@ impl Liveness {
         }
     }
 
+    /// Regard the node as dead until it is renewed by the next heartbeat.
+    pub fn expire(&self, node_id: u64) {
+        let mut nodes = self.nodes.lock().unwrap();
+        nodes.insert(node_id, NodeLiveness { expiration: None });
+    }
+
     pub fn init_node_if_first_seen(&self, node_id: u64) {
         // Give `liveness_threshold` time window to retry before mark as offline.
         let mut nodes = self.nodes.lock().unwrap();
@@ -80,14 +90,38 @@ impl Liveness {
         self.nodes.lock().unwrap().clear();
     }
 
-    fn new_expiration(&self) -> u128 {
-        current_timestamp() + self.liveness_threshold.as_millis()
+    fn new_expiration(&self) -> Option<Instant> {
+        Some(Instant::now() + self.dynamic_config.liveness_threshold())
     }
 }
 
-fn current_timestamp() -> u128 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let start = SystemTime::now();
-    let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap();
-    since_the_epoch.as_millis()
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sekas_runtime::time::advance;
+
+    use super::*;
+
+    #[sekas_macro::test(mock_time)]
+    async fn node_liveness_expires_after_threshold() {
+        let dynamic_config = Arc::new(DynamicConfig::default());
+        dynamic_config.set("root.liveness_threshold_sec", "30").unwrap();
+        let liveness = Liveness::new(dynamic_config);
+
+        liveness.init_node_if_first_seen(1);
+        advance(Duration::from_secs(20)).await;
+        assert!(liveness.get(&1).is_alive());
+
+        // The renewed node is kept alive in another threshold.
+        liveness.renew(1);
+        advance(Duration::from_secs(20)).await;
+        assert!(liveness.get(&1).is_alive());
+        advance(Duration::from_secs(11)).await;
+        assert!(liveness.get(&1).is_dead());
+
+        liveness.renew(1);
+        liveness.expire(1);
+        assert!(liveness.get(&1).is_dead());
+    }
 }
@@ -21,6 +21,11 @@ lazy_static! {
     pub static ref LEADER_STATE_INFO: IntGauge =
         register_int_gauge!("root_service_node_as_leader_info", "the node as root leader count")
             .unwrap();
+    pub static ref STEP_DOWN_TOTAL: IntCounter = register_int_counter!(
+        "root_service_step_down_total",
+        "the count of root leader step down by errors"
+    )
+    .unwrap();
 }
 
 // bootstrap root.
@@ -48,6 +53,9 @@ make_static_metric! {
             shed_group_leaders,
             shed_root_leader,
             create_group,
+            move_group_replicas,
+            split_hot_shard,
+            background_job,
         }
     }
     pub struct ReconcileScheduleHandleTaskDuration: Histogram {
@@ -59,6 +67,8 @@ make_static_metric! {
             create_collection_shards,
             shed_group_leaders,
             shed_root_leader,
+            move_group_replicas,
+            split_hot_shard,
         }
     }
     pub struct ReconcileScheduleCreateGroupStepDuration: Histogram {
@@ -245,6 +255,16 @@ lazy_static! {
         exponential_buckets(0.00005, 1.8, 26).unwrap(),
     )
     .unwrap();
+    pub static ref REPLICA_APPLY_LAG_MAX: IntGauge = register_int_gauge!(
+        "root_replica_apply_lag_max",
+        "the max distance between the applied index of replicas and the committed index of leaders"
+    )
+    .unwrap();
+    pub static ref LAGGING_REPLICAS: IntGauge = register_int_gauge!(
+        "root_lagging_replicas",
+        "the number of replicas whose apply lag exceeds the threshold persistently"
+    )
+    .unwrap();
     pub static ref HEARTBEAT_UPDATE_NODE_STATS_TOTAL: IntCounter = register_int_counter!(
         "root_heartbeat_update_node_stats_total",
         "the count of real update node stats after receive heartbeat response",
@@ -279,3 +299,31 @@ lazy_static! {
     )
     .unwrap();
 }
+
+// tenant
+lazy_static! {
+    pub static ref TENANT_DATABASES: IntGaugeVec = register_int_gauge_vec!(
+        "root_tenant_databases",
+        "the number of databases owned by each tenant",
+        &["tenant"]
+    )
+    .unwrap();
+    pub static ref TENANT_COLLECTIONS: IntGaugeVec = register_int_gauge_vec!(
+        "root_tenant_collections",
+        "the number of collections owned by each tenant",
+        &["tenant"]
+    )
+    .unwrap();
+    pub static ref TENANT_KEYS: IntGaugeVec = register_int_gauge_vec!(
+        "root_tenant_keys",
+        "the number of keys owned by each tenant, reported by the group leaders",
+        &["tenant"]
+    )
+    .unwrap();
+    pub static ref TENANT_LOGICAL_BYTES: IntGaugeVec = register_int_gauge_vec!(
+        "root_tenant_logical_bytes",
+        "the logical bytes owned by each tenant, reported by the group leaders",
+        &["tenant"]
+    )
+    .unwrap();
+}
@@ -14,47 +14,71 @@
 // limitations under the License.
 
 mod allocator;
+mod audit;
 mod bg_job;
+mod cache;
 mod collector;
+mod decision;
+mod gc;
 mod heartbeat;
+mod history;
+mod hot_shard;
+mod idempotency;
 mod liveness;
 mod metrics;
 mod schedule;
 mod schema;
+mod spec;
 mod store;
+mod tenant;
+mod trash;
 mod watch;
 
 use std::collections::*;
+use std::future::Future;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::*;
 use std::task::Poll;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use log::{error, info, trace, warn};
+use log::{debug, error, info, trace, warn};
 use sekas_api::server::v1::report_request::GroupUpdates;
 use sekas_api::server::v1::watch_response::*;
 use sekas_api::server::v1::*;
+use sekas_client::RetryState;
 use sekas_rock::time::timestamp_nanos;
+use sekas_runtime::time::Instant;
 use sekas_runtime::TaskGroup;
-use sekas_schema::shard::{SHARD_MAX, SHARD_MIN};
-use tokio::time::Instant;
 use tokio_util::time::delay_queue;
 
+#[cfg(feature = "sim")]
+pub use self::allocator::sim;
 use self::allocator::SysAllocSource;
+pub use self::audit::AuditAction;
+use self::audit::AuditLog;
 use self::bg_job::Jobs;
 pub use self::collector::RootCollector;
+pub use self::decision::Decision;
+use self::decision::DecisionLog;
 use self::diagnosis::Metadata;
+use self::gc::GcLeases;
+use self::idempotency::IdempotencyLocks;
 use self::schedule::ReconcileScheduler;
 use self::schema::ReplicaNodes;
 pub(crate) use self::schema::*;
 use self::store::RootStore;
-pub use self::watch::{WatchHub, Watcher};
-use crate::constants::ROOT_GROUP_ID;
+use self::tenant::DEFAULT_TENANT_ID;
+pub use self::watch::{WatchFilter, WatchHub, Watcher};
+use crate::constants::{
+    CLUSTER_VERSION_AUTH, CLUSTER_VERSION_ENCRYPTION, CLUSTER_VERSION_INITIAL, ROOT_GROUP_ID,
+    SYSTEM_TIER_LABEL,
+};
+use crate::engine::KeyManager;
 use crate::node::{Node, Replica, ReplicaRouteTable};
 use crate::serverpb::v1::background_job::Job;
 use crate::serverpb::v1::{reconcile_task, *};
 use crate::transport::TransportManager;
-use crate::{Config, Error, Result, RootConfig};
+use crate::{BootstrapSpec, Config, DynamicConfig, Error, Result, RootConfig};
 
 #[derive(Clone)]
 pub struct Root {
@@ -66,6 +90,9 @@ pub struct Root {
     heartbeat_queue: Arc<HeartbeatQueue>,
     ongoing_stats: Arc<OngoingStats>,
     jobs: Arc<Jobs>,
+    decisions: Arc<DecisionLog>,
+    audit_log: Arc<AuditLog>,
+    idempotency_locks: Arc<IdempotencyLocks>,
     task_group: TaskGroup,
 }
 
@@ -74,8 +101,12 @@ pub struct RootShared {
     node_ident: NodeIdent,
     local_addr: String,
     cfg_cpu_nums: u32,
+    cfg_capacity_weight: Option<f64>,
+    cfg_labels: Vec<String>,
     core: Mutex<Option<RootCore>>,
     watcher_hub: Arc<WatchHub>,
+    key_manager: Arc<KeyManager>,
+    dynamic_config: Arc<DynamicConfig>,
 }
 
 impl RootShared {
@@ -101,6 +132,7 @@ struct RootCore {
     schema: Arc<Schema>,
     next_txn_id: Arc<AtomicU64>,
     max_txn_id: Arc<AtomicU64>,
+    gc_leases: Arc<GcLeases>,
 }
 
 impl RootCore {
@@ -118,32 +150,49 @@ impl Root {
         transport_manager: TransportManager,
         node_ident: &NodeIdent,
         cfg: Config,
+        key_manager: Arc<KeyManager>,
+        dynamic_config: Arc<DynamicConfig>,
     ) -> Self {
         let local_addr = cfg.addr.clone();
         let cfg_cpu_nums = cfg.cpu_nums;
+        let cfg_capacity_weight = cfg.node.capacity_weight;
+        let cfg_labels = cfg.node.labels.clone();
         let ongoing_stats = Arc::new(OngoingStats::default());
         let shared = Arc::new(RootShared {
             transport_manager,
             local_addr,
             cfg_cpu_nums,
+            cfg_capacity_weight,
+            cfg_labels,
             core: Mutex::new(None),
             node_ident: node_ident.to_owned(),
             watcher_hub: Default::default(),
+            key_manager,
+            dynamic_config: dynamic_config.clone(),
         });
-        let liveness =
-            Arc::new(liveness::Liveness::new(Duration::from_secs(cfg.root.liveness_threshold_sec)));
+        let liveness = Arc::new(liveness::Liveness::new(dynamic_config.clone()));
         let info = Arc::new(SysAllocSource::new(shared.clone(), liveness.to_owned()));
-        let alloc =
-            Arc::new(allocator::Allocator::new(info, ongoing_stats.clone(), cfg.root.to_owned()));
+        let alloc = Arc::new(allocator::Allocator::new(
+            info,
+            ongoing_stats.clone(),
+            cfg.root.to_owned(),
+            dynamic_config,
+        ));
         let heartbeat_queue = Arc::new(HeartbeatQueue::default());
-        let jobs =
-            Arc::new(Jobs::new(shared.to_owned(), alloc.to_owned(), heartbeat_queue.to_owned()));
+        let jobs = Arc::new(Jobs::new(
+            shared.to_owned(),
+            alloc.to_owned(),
+            heartbeat_queue.to_owned(),
+            cfg.root.job_parallelism(),
+        ));
+        let decisions = Arc::new(DecisionLog::default());
         let sched_ctx = schedule::ScheduleContext::new(
             shared.clone(),
             alloc.clone(),
             heartbeat_queue.clone(),
             ongoing_stats.clone(),
             jobs.to_owned(),
+            decisions.to_owned(),
             cfg.root.to_owned(),
         );
         let scheduler = Arc::new(schedule::ReconcileScheduler::new(sched_ctx));
@@ -156,6 +205,9 @@ impl Root {
             heartbeat_queue,
             ongoing_stats,
             jobs,
+            decisions,
+            audit_log: Arc::default(),
+            idempotency_locks: Arc::default(),
             task_group: TaskGroup::default(),
         }
     }
@@ -204,7 +256,10 @@ impl Root {
     // - schedule group/replica/shard
     // - schedule heartbeat sending
     async fn run_schedule(&self, replica_table: ReplicaRouteTable) -> ! {
+        const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
         let mut bootstrapped = false;
+        let mut backoff = Duration::from_secs(1);
         loop {
             let root_replica = fetch_root_replica(&replica_table).await;
 
@@ -214,17 +269,24 @@ impl Root {
                     .step_leader(
                         &self.shared.local_addr,
                         self.shared.cfg_cpu_nums,
-                        root_replica,
+                        root_replica.clone(),
                         &mut bootstrapped,
                     )
                     .await
                 {
                     Ok(()) | Err(Error::NotLeader(..)) => {
                         // Step follower
+                        backoff = Duration::from_secs(1);
                         continue;
                     }
                     Err(err) => {
-                        todo!("handle error: {}", err)
+                        // The errors of the root store might be transient, so this node steps
+                        // down and backs off instead of exiting, then joins the election again.
+                        metrics::STEP_DOWN_TOTAL.inc();
+                        error!("root leader step down: {err:?}, back off {backoff:?}");
+                        step_down(&root_replica);
+                        sekas_runtime::time::sleep(backoff).await;
+                        backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
                     }
                 }
             }
@@ -277,9 +339,21 @@ impl Root {
         // Only when the program is initialized is it checked for bootstrap, after which
         // the leadership change does not need to check for whether bootstrap or
         // not.
-        if !*bootstrapped {
+        let first_step = !*bootstrapped;
+        if first_step {
             let cluster_id = self.shared.node_ident.cluster_id.clone();
-            if let Err(err) = schema.try_bootstrap_root(local_addr, cfg_cpu_nums, cluster_id).await
+            let cfg_capacity_weight = self.shared.cfg_capacity_weight;
+            let cfg_labels = self.shared.cfg_labels.clone();
+            if let Err(err) = schema
+                .try_bootstrap_root(
+                    local_addr,
+                    cfg_cpu_nums,
+                    cfg_capacity_weight,
+                    cfg_labels,
+                    cluster_id,
+                    self.shared.node_ident.incarnation,
+                )
+                .await
             {
                 metrics::BOOTSTRAP_FAIL_TOTAL.inc();
                 error!("boostrap: {err:?}");
@@ -288,11 +362,16 @@ impl Root {
             *bootstrapped = true;
         }
 
+        schema.load_cache().await?;
         let max_txn_id = schema.max_txn_id().await?;
         let root_core = RootCore {
             schema: Arc::new(schema.to_owned()),
             next_txn_id: Arc::new(AtomicU64::new(max_txn_id)),
             max_txn_id: Arc::new(AtomicU64::new(max_txn_id)),
+            gc_leases: Arc::new(GcLeases::new(
+                Duration::from_secs(self.cfg.gc_retention_sec),
+                timestamp_nanos(),
+            )),
         };
         root_core.bump_txn_id().await?;
 
@@ -314,15 +393,56 @@ impl Root {
         }
         self::metrics::LEADER_STATE_INFO.set(1);
 
+        let result = self.serve_leader(&schema, root_replica, first_step).await;
+
+        // After that, RootCore needs to be set to None before returning.
+        drop(txn_bumper_handle);
+        // Notify txn allocators to exit.
+        root_core.max_txn_id.store(0, Ordering::Release);
+        self.heartbeat_queue.enable(false).await;
+        self.jobs.on_drop_leader();
+        self.ongoing_stats.reset();
+        {
+            self.liveness.reset();
+
+            let mut core = self.shared.core.lock().unwrap();
+            *core = None;
+        }
+
+        self::metrics::LEADER_STATE_INFO.set(0);
+
+        result
+    }
+
+    /// Serve as the root leader until the leadership is lost, the states of
+    /// leader are cleared by the caller even if an error is returned.
+    async fn serve_leader(
+        &self,
+        schema: &Schema,
+        root_replica: Arc<Replica>,
+        first_step: bool,
+    ) -> Result<()> {
         self.ongoing_stats.reset();
         self.heartbeat_queue.enable(true).await;
         self.jobs.on_step_leader().await?;
+        self.scheduler.on_step_leader().await?;
+        self.shared.dynamic_config.apply(&schema.dynamic_config().await?);
+        // The collections are created by background jobs, so the spec is applied in
+        // another task to avoid blocking the scheduling.
+        let _spec_handle = self.cfg.bootstrap_spec.clone().filter(|_| first_step).map(|spec| {
+            let root = self.clone();
+            sekas_runtime::spawn(async move {
+                if let Err(err) = root.apply_bootstrap_spec(&spec).await {
+                    error!("apply bootstrap spec: {err:?}");
+                }
+            })
+        });
 
         let node_id = self.shared.node_ident.node_id;
         info!(
             "node {node_id} step root service leader, heartbeat_interval: {:?}, liveness_threshold: {:?}",
-            self.cfg.heartbeat_interval(),
-            Duration::from_secs(self.cfg.liveness_threshold_sec),
+            self.shared.dynamic_config.heartbeat_interval(),
+            self.shared.dynamic_config.liveness_threshold(),
         );
 
         // try schedule a full cluster heartbeat when current node become new root
@@ -336,41 +456,34 @@ impl Root {
             .await;
 
         while let Ok(Some(_)) = root_replica.to_owned().on_leader("root", true).await {
+            if let Err(err) = self.rotate_expired_data_key().await {
+                warn!("rotate data key: {err:?}");
+            }
+            if let Err(err) = self.purge_expired_trash().await {
+                warn!("purge expired trash: {err:?}");
+            }
+            if let Err(err) = self.purge_expired_idempotency_records().await {
+                warn!("purge expired idempotency records: {err:?}");
+            }
+            if let Err(err) = self.update_tenant_metrics().await {
+                warn!("update tenant metrics: {err:?}");
+            }
             let next_interval = self.scheduler.step_one().await;
             sekas_runtime::time::sleep(next_interval).await;
             self.scheduler.wait_one_heartbeat_tick().await;
         }
         info!("node {node_id} current root node drop leader");
-
-        // After that, RootCore needs to be set to None before returning.
-        drop(txn_bumper_handle);
-        // Notify txn allocators to exit.
-        root_core.max_txn_id.store(0, Ordering::Release);
-        self.heartbeat_queue.enable(false).await;
-        self.jobs.on_drop_leader();
-        self.ongoing_stats.reset();
-        {
-            self.liveness.reset();
-
-            let mut core = self.shared.core.lock().unwrap();
-            *core = None;
-        }
-
-        self::metrics::LEADER_STATE_INFO.set(0);
-
         Ok(())
     }
 
     pub async fn cordon_node(&self, node_id: u64) -> Result<()> {
         let schema = self.schema()?;
-        let mut node_desc = schema
-            .get_node(node_id)
-            .await?
-            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+        let mut node_desc =
+            schema.get_node(node_id).await?.ok_or(crate::Error::NodeNotFound(node_id))?;
 
         let current_status = NodeStatus::from_i32(node_desc.status).unwrap();
         if !matches!(current_status, NodeStatus::Active) {
-            return Err(crate::Error::InvalidArgument("node already cordoned".into()));
+            return Err(crate::Error::NodeStatusNotMatch(node_id, current_status));
         }
         node_desc.status = NodeStatus::Cordoned as i32;
         schema.update_node(node_desc).await?; // TODO: cas
@@ -379,17 +492,15 @@ impl Root {
 
     pub async fn uncordon_node(&self, node_id: u64) -> Result<()> {
         let schema = self.schema()?;
-        let mut node_desc = schema
-            .get_node(node_id)
-            .await?
-            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+        let mut node_desc =
+            schema.get_node(node_id).await?.ok_or(crate::Error::NodeNotFound(node_id))?;
 
         let current_status = NodeStatus::from_i32(node_desc.status).unwrap();
         if !matches!(
             current_status,
             NodeStatus::Cordoned | NodeStatus::Drained | NodeStatus::Decommissioned
         ) {
-            return Err(crate::Error::InvalidArgument("node status unsupport uncordon".into()));
+            return Err(crate::Error::NodeStatusNotMatch(node_id, current_status));
         }
 
         node_desc.status = NodeStatus::Active as i32;
@@ -402,52 +513,427 @@ impl Root {
 
         if self.current_node_id() == node_id {
             info!("try to drain root leader and move root leadership out first");
+            self.decisions.record(
+                "shed root leader",
+                format!("move root leadership out of node {node_id}"),
+                "the root leader node is requested to drain".to_owned(),
+            );
             self.scheduler
                 .setup_task(ReconcileTask {
                     task: Some(reconcile_task::Task::ShedRoot(ShedRootLeaderTask { node_id })),
                 })
-                .await;
+                .await?;
             return Err(crate::Error::InvalidArgument(
                 "node is root leader, try again later".into(),
             ));
         }
 
-        let mut node_desc = schema
-            .get_node(node_id)
-            .await?
-            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+        let mut node_desc =
+            schema.get_node(node_id).await?.ok_or(crate::Error::NodeNotFound(node_id))?;
 
         let current_status = NodeStatus::from_i32(node_desc.status).unwrap();
         if !matches!(current_status, NodeStatus::Cordoned) {
-            return Err(crate::Error::InvalidArgument(
-                "only in cordoned status node can be drain".into(),
-            ));
+            return Err(crate::Error::NodeStatusNotMatch(node_id, current_status));
         }
 
         node_desc.status = NodeStatus::Draining as i32;
         schema.update_node(node_desc).await?; // TODO: cas
 
+        self.decisions.record(
+            "shed leaders",
+            format!("move all group leaders out of node {node_id}"),
+            "the node is requested to drain".to_owned(),
+        );
         self.scheduler
             .setup_task(ReconcileTask {
                 task: Some(reconcile_task::Task::ShedLeader(ShedLeaderTask { node_id })),
             })
-            .await;
+            .await?;
 
         Ok(())
     }
 
     pub async fn node_status(&self, node_id: u64) -> Result<NodeStatus> {
         let schema = self.schema()?;
-        let node_desc = schema
-            .get_node(node_id)
-            .await?
-            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+        let node_desc =
+            schema.get_node(node_id).await?.ok_or(crate::Error::NodeNotFound(node_id))?;
 
         let current_status = NodeStatus::from_i32(node_desc.status).unwrap();
 
         Ok(current_status)
     }
 
+    /// Return the number of replicas and leaders remaining on the node, to
+    /// observe the progress of draining.
+    pub async fn node_replica_stats(&self, node_id: u64) -> Result<(usize, usize)> {
+        let schema = self.schema()?;
+        let replica_ids = schema
+            .list_group()
+            .await?
+            .into_iter()
+            .flat_map(|g| g.replicas)
+            .filter(|r| r.node_id == node_id)
+            .map(|r| r.id)
+            .collect::<HashSet<_>>();
+        // The states of the removed replicas might not be cleaned yet.
+        let num_leaders = schema
+            .list_replica_state()
+            .await?
+            .into_iter()
+            .filter(|s| s.role == RaftRole::Leader as i32 && replica_ids.contains(&s.replica_id))
+            .count();
+        Ok((replica_ids.len(), num_leaders))
+    }
+
+    /// Replace the source replicas of a group with new replicas on the dest
+    /// nodes, in a single joint consensus config change.
+    pub async fn move_group_replicas(
+        &self,
+        group_id: u64,
+        src_replicas: Vec<u64>,
+        dest_nodes: Vec<u64>,
+    ) -> Result<()> {
+        let schema = self.schema()?;
+        if src_replicas.is_empty() || src_replicas.len() != dest_nodes.len() {
+            return Err(crate::Error::InvalidArgument(
+                "the number of source replicas and dest nodes must be equal".into(),
+            ));
+        }
+        if src_replicas.iter().collect::<HashSet<_>>().len() != src_replicas.len()
+            || dest_nodes.iter().collect::<HashSet<_>>().len() != dest_nodes.len()
+        {
+            return Err(crate::Error::InvalidArgument("duplicated replicas or nodes".into()));
+        }
+
+        let group = schema
+            .get_group(group_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("group not found".into()))?;
+        if src_replicas.len() >= group.replicas.len() {
+            return Err(crate::Error::InvalidArgument(
+                "at least one replica of the group must be kept".into(),
+            ));
+        }
+        for replica_id in &src_replicas {
+            if !group.replicas.iter().any(|r| r.id == *replica_id) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "replica {replica_id} not belongs to group {group_id}"
+                )));
+            }
+        }
+        for node_id in &dest_nodes {
+            let node = schema.get_node(*node_id).await?.ok_or_else(|| {
+                crate::Error::InvalidArgument(format!("node {node_id} not found"))
+            })?;
+            if node.status != NodeStatus::Active as i32 {
+                return Err(crate::Error::InvalidArgument(format!("node {node_id} is not active")));
+            }
+            if group.replicas.iter().any(|r| r.node_id == *node_id) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "node {node_id} already has a replica of group {group_id}"
+                )));
+            }
+        }
+
+        self.decisions.record(
+            "move group replicas",
+            format!("move replicas {src_replicas:?} of group {group_id} to nodes {dest_nodes:?}"),
+            "the replicas are requested to move".to_owned(),
+        );
+        self.scheduler
+            .setup_task(ReconcileTask {
+                task: Some(reconcile_task::Task::MoveGroupReplicas(MoveGroupReplicasTask {
+                    group: group_id,
+                    src_replicas,
+                    dest_nodes,
+                })),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Move the shard to the group, and wait until the shard is served by the
+    /// group or the timeout is exceeded.
+    ///
+    /// The leader changes of the groups are handled by the group client. The
+    /// moving is issued again if the epoch of the source group is changed, or
+    /// the former moving is aborted without the shard moved.
+    pub async fn move_shard(
+        &self,
+        shard_id: u64,
+        dest_group_id: u64,
+        timeout: Duration,
+    ) -> Result<diagnosis::ShardMove> {
+        // The interval to refresh the descriptors and the moving shards of groups.
+        const POLL_INTERVAL: Duration = Duration::from_secs(1);
+        // The moving is treated as aborted if it isn't reported after this interval.
+        const REISSUE_INTERVAL: Duration = Duration::from_secs(10);
+
+        let schema = self.schema()?;
+        if dest_group_id == ROOT_GROUP_ID {
+            return Err(Error::InvalidArgument("the shards can't be moved to root group".into()));
+        }
+        let dest_group = schema
+            .get_group(dest_group_id)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("group {dest_group_id} not found")))?;
+        if schema.draining_groups().await?.contains(&dest_group_id) {
+            return Err(Error::InvalidArgument(format!("group {dest_group_id} is draining")));
+        }
+        let src_group = schema
+            .list_group()
+            .await?
+            .into_iter()
+            .find(|g| g.shards.iter().any(|s| s.id == shard_id))
+            .ok_or_else(|| Error::InvalidArgument(format!("shard {shard_id} not found")))?;
+        if src_group.id == ROOT_GROUP_ID {
+            return Err(Error::InvalidArgument("the shards of root group can't be moved".into()));
+        }
+        if src_group.id == dest_group_id {
+            return Err(Error::InvalidArgument(format!(
+                "shard {shard_id} is already in group {dest_group_id}"
+            )));
+        }
+        let shard = src_group.shards.iter().find(|s| s.id == shard_id).expect("shard exists");
+        if sekas_schema::shard::is_clone_source(&src_group, shard) {
+            return Err(Error::InvalidArgument(format!(
+                "shard {shard_id} is the clone source of other shards in group {}",
+                src_group.id
+            )));
+        }
+        for replica in &dest_group.replicas {
+            let node = schema.get_node(replica.node_id).await?;
+            if !node.map_or(false, |n| shard.constraints.iter().all(|c| n.labels.contains(c))) {
+                return Err(Error::InvalidArgument(format!(
+                    "the replica {} of group {dest_group_id} doesn't satisfy the constraints {:?}",
+                    replica.id, shard.constraints
+                )));
+            }
+        }
+
+        let nodes = src_group
+            .replicas
+            .iter()
+            .chain(dest_group.replicas.iter())
+            .map(|r| r.node_id)
+            .collect::<HashSet<_>>();
+        let deadline = Instant::now() + timeout;
+        let mut attempts = 0;
+        let mut last_issued: Option<Instant> = None;
+        loop {
+            let groups = schema.list_group().await?;
+            let find_shard = |group_id: u64| {
+                groups
+                    .iter()
+                    .find(|g| g.id == group_id)
+                    .filter(|g| g.shards.iter().any(|s| s.id == shard_id))
+            };
+            if find_shard(dest_group_id).is_some() {
+                info!(
+                    "shard {shard_id} is moved from group {} to group {dest_group_id}",
+                    src_group.id
+                );
+                return Ok(diagnosis::ShardMove {
+                    shard_id,
+                    src_group: src_group.id,
+                    dest_group: dest_group_id,
+                    attempts,
+                });
+            }
+            // The shard might be moved to another group by the balancer meanwhile.
+            if let Some(group) = groups
+                .iter()
+                .find(|g| g.id != src_group.id && g.shards.iter().any(|s| s.id == shard_id))
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "shard {shard_id} is moved to group {} instead of group {dest_group_id}",
+                    group.id
+                )));
+            }
+
+            let moving = nodes.iter().any(|node_id| {
+                self.ongoing_stats.get_moving_shards(*node_id).iter().any(|m| {
+                    m.desc.as_ref().and_then(|d| d.shard_desc.as_ref()).map(|s| s.id)
+                        == Some(shard_id)
+                })
+            });
+            let reissue = last_issued.map_or(true, |t| t.elapsed() >= REISSUE_INTERVAL);
+            if let (Some(src), false, true) = (find_shard(src_group.id), moving, reissue) {
+                let shard = src.shards.iter().find(|s| s.id == shard_id).expect("shard exists");
+                let mut group_client =
+                    self.shared.transport_manager.lazy_group_client(dest_group_id);
+                match group_client.accept_shard(src.id, src.epoch, shard).await {
+                    Ok(()) => {
+                        attempts += 1;
+                        last_issued = Some(Instant::now());
+                        info!(
+                            "issue moving shard {shard_id} from group {} epoch {} to group \
+                             {dest_group_id}, attempts {attempts}",
+                            src.id, src.epoch
+                        );
+                    }
+                    Err(sekas_client::Error::EpochNotMatch(_)) => {
+                        debug!("the epoch of group {} is changed, move shard later", src.id);
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::DeadlineExceeded(format!(
+                    "moving shard {shard_id} to group {dest_group_id}"
+                )));
+            }
+            let tasks = nodes.iter().map(|&node_id| HeartbeatTask { node_id }).collect();
+            self.heartbeat_queue.try_schedule(tasks, Instant::now()).await;
+            sekas_runtime::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Merge the donor group into the target group, to reduce the overhead of
+    /// the near-empty groups. All shards of the donor group are moved to the
+    /// target group, then the replicas of the donor group are removed. It is
+    /// coordinated by a background job, whose progress is shown in the jobs.
+    pub async fn merge_group(&self, donor_group_id: u64, target_group_id: u64) -> Result<()> {
+        let schema = self.schema()?;
+        if donor_group_id == ROOT_GROUP_ID || target_group_id == ROOT_GROUP_ID {
+            return Err(Error::InvalidArgument("the root group can't be merged".into()));
+        }
+        if donor_group_id == target_group_id {
+            return Err(Error::InvalidArgument("a group can't be merged into itself".into()));
+        }
+        let donor = schema
+            .get_group(donor_group_id)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("group {donor_group_id} not found")))?;
+        let target = schema
+            .get_group(target_group_id)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("group {target_group_id} not found")))?;
+        if schema.draining_groups().await?.contains(&target_group_id) {
+            return Err(Error::InvalidArgument(format!("group {target_group_id} is draining")));
+        }
+        for shard in donor.shards.iter().filter(|s| !s.constraints.is_empty()) {
+            for replica in &target.replicas {
+                let node = schema.get_node(replica.node_id).await?;
+                if !node.map_or(false, |n| shard.constraints.iter().all(|c| n.labels.contains(c))) {
+                    return Err(Error::InvalidArgument(format!(
+                        "the replica {} of group {target_group_id} doesn't satisfy the \
+                         constraints {:?} of shard {}",
+                        replica.id, shard.constraints, shard.id
+                    )));
+                }
+            }
+        }
+
+        self.decisions.record(
+            "merge group",
+            format!("merge group {donor_group_id} into group {target_group_id}"),
+            format!("the group is requested to merge, it serves {} shards", donor.shards.len()),
+        );
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::MergeGroup(MergeGroupJob {
+                        donor_group: donor_group_id,
+                        target_group: target_group_id,
+                        status: MergeGroupStatus::MergeGroupMovingShards as i32,
+                        created_time: format!("{:?}", Instant::now()),
+                    })),
+                    ..Default::default()
+                },
+                false,
+            )
+            .await
+    }
+
+    /// Force rebuild a group which has permanently lost its quorum from the
+    /// surviving replica, the other replicas are removed from the group.
+    ///
+    /// The operation is unsafe, the log entries not applied by the surviving
+    /// replica are lost, so the operator must confirm it by passing the group
+    /// id as `confirm`.
+    pub async fn unsafe_recover_group(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        confirm: u64,
+    ) -> Result<GroupDesc> {
+        let schema = self.schema()?;
+        if confirm != group_id {
+            return Err(crate::Error::InvalidArgument(
+                "the unsafe recovery must be confirmed with the group id".into(),
+            ));
+        }
+        if group_id == ROOT_GROUP_ID {
+            return Err(crate::Error::InvalidArgument(
+                "the root group can't be recovered by root".into(),
+            ));
+        }
+
+        let group = schema
+            .get_group(group_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("group not found".into()))?;
+        let replica = group.replicas.iter().find(|r| r.id == replica_id).ok_or_else(|| {
+            crate::Error::InvalidArgument(format!(
+                "replica {replica_id} not belongs to group {group_id}"
+            ))
+        })?;
+        let node = schema.get_node(replica.node_id).await?.ok_or_else(|| {
+            crate::Error::InvalidArgument(format!("node {} not found", replica.node_id))
+        })?;
+
+        let action = format!(
+            "rebuild group {group_id} from replica {replica_id}, drop replicas {:?}",
+            group.replicas.iter().filter(|r| r.id != replica_id).map(|r| r.id).collect::<Vec<_>>()
+        );
+        warn!("unsafe recovery: {action}");
+        self.decisions.record(
+            "unsafe recover group",
+            action,
+            "the group is requested to recover by operator".to_owned(),
+        );
+
+        let client = self.shared.transport_manager.get_node_client(node.addr.clone())?;
+        let desc = client.unsafe_recover_replica(group_id, replica_id).await?;
+        schema.update_group_replica(Some(desc.clone()), None).await?;
+        warn!("unsafe recovery: group {group_id} is recovered, epoch {}", desc.epoch);
+        Ok(desc)
+    }
+
+    /// Bump the cluster version to `version`, or to the max version supported
+    /// by all nodes if it is not specified. Return the new cluster version.
+    pub async fn bump_cluster_version(&self, version: Option<u64>) -> Result<u64> {
+        let schema = self.schema()?;
+        let current_version = schema.cluster_version().await?;
+        let nodes = schema.list_node().await?;
+        let supported_version = supported_cluster_version(&nodes).unwrap_or(current_version);
+        let version = version.unwrap_or(supported_version);
+        if version < current_version {
+            return Err(crate::Error::InvalidArgument(format!(
+                "downgrade cluster version from {current_version} to {version} is not allowed"
+            )));
+        }
+        if version > supported_version {
+            return Err(crate::Error::InvalidArgument(format!(
+                "cluster version {version} is not supported by all nodes, the max supported version is {supported_version}"
+            )));
+        }
+        if version != current_version {
+            schema.cas_cluster_version(current_version, version).await.map_err(
+                |err| match err {
+                    crate::Error::CasFailed(..) => crate::Error::InvalidArgument(format!(
+                        "cluster version is changed from {current_version} concurrently"
+                    )),
+                    err => err,
+                },
+            )?;
+            info!("bump cluster version from {current_version} to {version}");
+        }
+        Ok(version)
+    }
+
     pub async fn nodes(&self) -> Option<u64> {
         if let Ok(schema) = self.shared.schema() {
             if let Ok(nodes) = schema.list_node().await {
@@ -504,15 +990,143 @@ impl Root {
                         "database": p.database_id,
                     })
                 }
+                Job::RotateDataKey(r) => {
+                    json!({
+                        "type": "rotate data key",
+                        "key_id": r.key_id,
+                    })
+                }
+                Job::TruncateCollection(t) => {
+                    json!({
+                        "type": "truncate collection",
+                        "database": t.database_id,
+                        "collection": t.collection_id,
+                        "name": t.collection_name,
+                        "wait_truncate": t.wait_truncate.len(),
+                    })
+                }
+                Job::MergeGroup(m) => {
+                    let status = format!("{:?}", MergeGroupStatus::from_i32(m.status).unwrap());
+                    json!({
+                        "type": "merge group",
+                        "status": status,
+                        "donor_group": m.donor_group,
+                        "target_group": m.target_group,
+                    })
+                }
             }
         }
 
         let schema = self.schema()?;
         let ongoing_jobs = schema.list_job().await?;
         let history_jobs = schema.list_history_job().await?;
-        let ongoing = ongoing_jobs.iter().map(to_json).collect::<Vec<_>>();
+        let ongoing = ongoing_jobs
+            .iter()
+            .map(|j| {
+                let mut value = to_json(j);
+                value["id"] = json!(j.id);
+                value["depends_on"] = json!(j.depends_on);
+                value["retried"] = json!(j.retried);
+                value
+            })
+            .collect::<Vec<_>>();
         let history = history_jobs.iter().map(to_json).collect::<Vec<_>>();
-        Ok(json!({"ongoing": ongoing, "history": history}).to_string())
+        let trash = self.trash_state().await?;
+        Ok(json!({"ongoing": ongoing, "history": history, "trash": trash}).to_string())
+    }
+
+    /// Return the latest `limit` reconcile decisions, the newest one comes
+    /// first.
+    pub fn decisions(&self, limit: usize) -> Result<Vec<Decision>> {
+        // The decisions are only made by the root leader.
+        self.schema()?;
+        Ok(self.decisions.recent(limit))
+    }
+
+    /// Record an admin or DDL operation in the audit log. The failure of
+    /// persisting the record is logged rather than returned, since the
+    /// operation itself has finished.
+    pub async fn audit<T>(
+        &self,
+        operator: &str,
+        action: AuditAction,
+        target: String,
+        result: &Result<T>,
+    ) {
+        let record = AuditRecord {
+            id: self.audit_log.next_id(),
+            operator: operator.to_owned(),
+            action: action.as_str().to_owned(),
+            target,
+            error: result.as_ref().err().map(ToString::to_string).unwrap_or_default(),
+        };
+        info!(
+            "audit. operator={}, action={}, target={}, error={}",
+            record.operator, record.action, record.target, record.error
+        );
+        // The operations are only served by the root leader.
+        let Ok(schema) = self.schema() else {
+            return;
+        };
+        if let Err(err) = schema.append_audit_record(&record).await {
+            warn!("append audit record {}: {err:?}", record.id);
+            return;
+        }
+        if self.cfg.audit_retention_sec != 0 && self.audit_log.on_appended() {
+            let retention = Duration::from_secs(self.cfg.audit_retention_sec).as_nanos() as u64;
+            match schema.purge_audit_records(record.id.saturating_sub(retention)).await {
+                Ok(num_purged) => debug!("purge {num_purged} expired audit records"),
+                Err(err) => warn!("purge expired audit records: {err:?}"),
+            }
+        }
+    }
+
+    /// Return the latest `limit` audit records which are recorded since the
+    /// unix timestamp `since_ms`, the newest one comes first.
+    pub async fn audit_records(&self, since_ms: u64, limit: usize) -> Result<Vec<AuditRecord>> {
+        let records = self.schema()?.list_audit_records().await?;
+        let since_id = since_ms.saturating_mul(1_000_000);
+        Ok(records.into_iter().rev().take_while(|r| r.id >= since_id).take(limit).collect())
+    }
+
+    /// Execute the DDL operation carrying the idempotency token at most once,
+    /// the retries of it return the recorded response. The request reusing
+    /// the token of another request is rejected.
+    pub async fn idempotent<F>(
+        &self,
+        token: &str,
+        request: AdminRequestUnion,
+        operation: F,
+    ) -> Result<AdminResponseUnion>
+    where
+        F: Future<Output = Result<AdminResponseUnion>>,
+    {
+        // The retry waits for the original operation if it is still ongoing.
+        let _guard = self.idempotency_locks.lock(token).await;
+        let schema = self.schema()?;
+        if let Some(record) = schema.get_idempotency_record(token).await? {
+            if record.request.as_ref() != Some(&request) {
+                return Err(Error::InvalidArgument(format!(
+                    "idempotency token {token} is used by another request"
+                )));
+            }
+            debug!("replay the response of idempotency token {token}");
+            return record
+                .response
+                .ok_or_else(|| Error::InvalidData(format!("idempotency record: {token}")));
+        }
+
+        let response = operation.await?;
+        let record = IdempotencyRecord {
+            token: token.to_owned(),
+            created_at: unix_timestamp(),
+            request: Some(request),
+            response: Some(response.clone()),
+        };
+        // The caller must retry with the same token if the record is not persisted,
+        // otherwise a retry would execute the operation again silently.
+        schema.put_idempotency_record(&record).await?;
+        Ok(response)
     }
 
     pub async fn info(&self) -> Result<Metadata> {
@@ -555,7 +1169,35 @@ impl Root {
                         .filter(|r| r.raft_role == RaftRole::Leader as i32)
                         .cloned()
                         .collect::<Vec<_>>();
-                    Node { id: n.id, addr: n.addr.to_owned(), replicas, leaders, status: n.status }
+                    let snapshot_stats = self.ongoing_stats.get_snapshot_stats(n.id);
+                    let moving_shards = self
+                        .ongoing_stats
+                        .get_moving_shards(n.id)
+                        .into_iter()
+                        .filter_map(|ms| {
+                            let desc = ms.desc?;
+                            let progress = ms.progress.unwrap_or_default();
+                            Some(MovingShard {
+                                shard: desc.shard_desc.map(|s| s.id).unwrap_or_default(),
+                                src_group: desc.src_group_id,
+                                dest_group: desc.dest_group_id,
+                                moved_keys: progress.moved_keys,
+                                moved_bytes: progress.moved_bytes,
+                                total_keys: progress.total_keys,
+                                percent: progress.percent,
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    Node {
+                        id: n.id,
+                        addr: n.addr.to_owned(),
+                        replicas,
+                        leaders,
+                        status: n.status,
+                        sending_snapshots: snapshot_stats.sending,
+                        receiving_snapshots: snapshot_stats.receiving,
+                        moving_shards,
+                    }
                 })
                 .collect::<Vec<_>>(),
             databases: dbs
@@ -603,76 +1245,682 @@ impl Root {
             balanced,
         })
     }
-}
 
-impl Root {
-    pub async fn create_database(&self, name: String) -> Result<DatabaseDesc> {
-        let desc = self
-            .schema()?
-            .create_database(DatabaseDesc { name: name.to_owned(), ..Default::default() })
-            .await?;
-        self.watcher_hub()
-            .notify_updates(vec![UpdateEvent {
-                event: Some(update_event::Event::Database(desc.to_owned())),
-            }])
-            .await;
-        info!("create database. database_id={}, database={}", desc.id, name);
-        Ok(desc)
-    }
+    /// Return the topology and health of the cluster, including the usage of
+    /// nodes, the health of groups and the shards of collections.
+    pub async fn cluster_info(&self) -> Result<diagnosis::Cluster> {
+        use diagnosis::*;
 
-    pub async fn delete_database(&self, name: &str) -> Result<()> {
-        let db = self.get_database(name).await?;
-        if db.is_none() {
-            return Err(Error::DatabaseNotFound(name.to_owned()));
-        }
-        let db = db.unwrap();
-        if db.id == sekas_schema::system::db::ID {
-            return Err(Error::InvalidArgument("not support delete system database".into()));
-        }
-        self.jobs
-            .submit(
-                BackgroundJob {
-                    job: Some(Job::PurgeDatabase(PurgeDatabaseJob {
-                        database_id: db.id,
-                        database_name: db.name.to_owned(),
-                        created_time: format!("{:?}", Instant::now()),
-                    })),
-                    ..Default::default()
-                },
-                false,
-            )
-            .await?;
         let schema = self.schema()?;
-        let id = schema.delete_database(&db).await?;
-        self.watcher_hub()
-            .notify_deletes(vec![DeleteEvent { event: Some(delete_event::Event::Database(id)) }])
-            .await;
-        info!("delete database. database={name}");
-        Ok(())
+        let nodes = schema.list_node().await?;
+        let groups = schema.list_group().await?;
+        let states = schema.list_replica_state().await?;
+        let dbs = schema.list_database().await?;
+        let collections = schema.list_collection().await?;
+        let ongoing_jobs = schema.list_job().await?.len();
+        let balanced = !self.scheduler.need_reconcile().await?;
+        let pending_tasks = self.scheduler.num_pending_tasks().await;
+
+        // Both the source and dest groups report the moving shard.
+        let moving_shards = nodes
+            .iter()
+            .flat_map(|n| self.ongoing_stats.get_moving_shards(n.id))
+            .filter_map(|ms| ms.desc)
+            .map(|desc| (desc.shard_desc.as_ref().map(|s| s.id).unwrap_or_default(), desc))
+            .collect::<HashMap<_, _>>();
+        let node_usages = nodes
+            .iter()
+            .map(|n| {
+                let capacity = n.capacity.clone().unwrap_or_default();
+                let stats = self.ongoing_stats.get_node_stats(n.id).unwrap_or_default();
+                NodeUsage {
+                    id: n.id,
+                    addr: n.addr.clone(),
+                    status: n.status,
+                    alive: self.liveness.get(&n.id).is_alive(),
+                    cpu_nums: capacity.cpu_nums,
+                    load_average: stats.load_average,
+                    total_space: stats.total_space,
+                    available_space: stats.available_space,
+                    replicas: capacity.replica_count,
+                    leaders: capacity.leader_count,
+                    block_cache_usage: stats.block_cache_usage,
+                }
+            })
+            .collect::<Vec<_>>();
+        let group_healths = groups
+            .iter()
+            .map(|g| {
+                let is_alive = |node_id: u64| self.liveness.get(&node_id).is_alive();
+                let voters = g
+                    .replicas
+                    .iter()
+                    .filter(|r| {
+                        r.role == ReplicaRole::Voter as i32
+                            || r.role == ReplicaRole::IncomingVoter as i32
+                    })
+                    .collect::<Vec<_>>();
+                let alive_voters = voters.iter().filter(|r| is_alive(r.node_id)).count();
+                let leader = states
+                    .iter()
+                    .filter(|s| s.group_id == g.id && s.role == RaftRole::Leader as i32)
+                    .filter(|s| g.replicas.iter().any(|r| r.id == s.replica_id))
+                    .max_by_key(|s| s.term)
+                    .map(|s| s.node_id);
+                GroupHealth {
+                    id: g.id,
+                    epoch: g.epoch,
+                    voters: voters.len(),
+                    learners: g.replicas.len() - voters.len(),
+                    leader,
+                    under_replicated: alive_voters < self.cfg.replicas_per_group,
+                    no_leader: leader.is_none(),
+                    moving_shards: moving_shards
+                        .values()
+                        .filter(|ms| ms.src_group_id == g.id || ms.dest_group_id == g.id)
+                        .count(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let collection_shards = collections
+            .iter()
+            .map(|c| CollectionShards {
+                id: c.id,
+                database: dbs
+                    .iter()
+                    .find(|d| d.id == c.db)
+                    .map(|d| d.name.clone())
+                    .unwrap_or_default(),
+                collection: c.name.clone(),
+                shards: groups
+                    .iter()
+                    .flat_map(|g| g.shards.iter())
+                    .filter(|s| s.collection_id == c.id)
+                    .count(),
+            })
+            .collect::<Vec<_>>();
+        let lagging_replicas = self
+            .ongoing_stats
+            .get_lagging_replicas(self.cfg.replica_apply_lag_persist())
+            .into_iter()
+            .map(|r| LaggingReplica {
+                group_id: r.group_id,
+                replica_id: r.replica_id,
+                node_id: r.node_id,
+                applied_index: r.applied_index,
+                committed_index: r.committed_index,
+                lag: r.lag(),
+                lagging_sec: r.lagging_since.map(|s| s.elapsed().as_secs()).unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Cluster {
+            nodes: node_usages,
+            groups: group_healths,
+            collections: collection_shards,
+            lagging_replicas,
+            scheduler: SchedulerState { pending_tasks, ongoing_jobs, balanced },
+        })
+    }
+
+    /// Aggregate the usage of the shards of the collection, the usage is
+    /// reported by the group leaders so it might be stale.
+    pub async fn collection_stats(
+        &self,
+        database: &DatabaseDesc,
+        collection: &str,
+    ) -> Result<diagnosis::CollectionStats> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let co = schema
+            .get_collection(db.id, collection)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {collection} not found")))?;
+        let groups = schema.list_group().await?;
+        let shards = groups
+            .iter()
+            .flat_map(|g| g.shards.iter().map(move |s| (g.id, s)))
+            .filter(|(_, s)| s.collection_id == co.id)
+            .map(|(group_id, s)| {
+                let stats = self.ongoing_stats.get_shard_stats(s.id);
+                (group_id, s.id, stats)
+            })
+            .collect::<Vec<_>>();
+        Ok(aggregate_collection_stats(&self.cfg, &db.name, &co, &shards))
+    }
+
+    /// Describe the collection along with the layout and the usage of its
+    /// shards, return `None` if the collection is not found.
+    pub async fn describe_collection(
+        &self,
+        database: &DatabaseDesc,
+        collection: &str,
+    ) -> Result<Option<CollectionDescription>> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let Some(co) = schema.get_collection(db.id, collection).await? else {
+            return Ok(None);
+        };
+        let leaders = schema
+            .list_group_state()
+            .await?
+            .into_iter()
+            .map(|state| (state.group_id, state.leader_id))
+            .collect::<HashMap<_, _>>();
+        let mut shards = Vec::new();
+        for group in schema.list_group().await? {
+            for shard in group.shards.iter().filter(|s| s.collection_id == co.id) {
+                let desc = ShardDescription {
+                    shard: Some(shard.clone()),
+                    group_id: group.id,
+                    replicas: group.replicas.clone(),
+                    leader_id: leaders.get(&group.id).copied().flatten(),
+                    ..Default::default()
+                };
+                shards.push((desc, self.ongoing_stats.get_shard_stats(shard.id)));
+            }
+        }
+        Ok(Some(describe_collection(co, shards)))
+    }
+
+    /// Return the hottest keys of the cluster, or of the collection if it is
+    /// specified. The accesses are sampled and reported by the group leaders,
+    /// so they might be stale.
+    pub async fn hot_keys(
+        &self,
+        collection_id: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<diagnosis::HotKey>> {
+        let schema = self.schema()?;
+        let mut shards = Vec::new();
+        for group in schema.list_group().await? {
+            for shard in &group.shards {
+                if collection_id.map_or(false, |id| id != shard.collection_id) {
+                    continue;
+                }
+                if let Some(stats) = self.ongoing_stats.get_shard_stats(shard.id) {
+                    shards.push((group.id, stats));
+                }
+            }
+        }
+        Ok(aggregate_hot_keys(shards, limit))
+    }
+
+    /// Redistribute the shards of the collection evenly across the groups
+    /// satisfying its placement constraints, so that the leaders and the
+    /// replicas of the shards spread over the nodes. It is useful after a bulk
+    /// load that landed the shards on a few groups.
+    ///
+    /// The shards are moved by the reconcile scheduler in background, the
+    /// planned moves are returned.
+    pub async fn scatter_collection(
+        &self,
+        database: &DatabaseDesc,
+        collection: &str,
+    ) -> Result<Vec<diagnosis::ScatterMove>> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let co = schema
+            .get_collection(db.id, collection)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {collection} not found")))?;
+
+        let all_groups = schema.list_group().await?;
+        // The clone sources must stay with the cloned shards until the data is
+        // realized.
+        if all_groups
+            .iter()
+            .flat_map(|g| &g.shards)
+            .any(|s| s.clone_source.as_ref().map(|c| c.collection_id) == Some(co.id))
+        {
+            return Err(Error::InvalidArgument(format!(
+                "collection {collection} is the clone source of other collections"
+            )));
+        }
+
+        let nodes =
+            schema.list_node().await?.into_iter().map(|n| (n.id, n)).collect::<HashMap<_, _>>();
+        let draining = schema.draining_groups().await?;
+        let groups = all_groups
+            .into_iter()
+            .filter(|g| g.id != ROOT_GROUP_ID && !g.replicas.is_empty())
+            .filter(|g| !draining.contains(&g.id))
+            .filter(|g| {
+                g.replicas.iter().all(|r| {
+                    nodes
+                        .get(&r.node_id)
+                        .map_or(false, |n| co.constraints.iter().all(|c| n.labels.contains(c)))
+                })
+            })
+            .collect::<Vec<_>>();
+        if groups.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "no group satisfies the constraints {:?} of collection {collection}",
+                co.constraints
+            )));
+        }
+        let leader_nodes = schema
+            .list_group_state()
+            .await?
+            .into_iter()
+            .filter_map(|state| {
+                let group = groups.iter().find(|g| g.id == state.group_id)?;
+                let leader = group.replicas.iter().find(|r| Some(r.id) == state.leader_id)?;
+                Some((group.id, leader.node_id))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let moves = plan_scatter(co.id, &groups, &leader_nodes);
+        for mv in &moves {
+            self.decisions.record(
+                "scatter collection",
+                format!(
+                    "move shard {} from group {} to group {}",
+                    mv.shard_id, mv.src_group, mv.dest_group
+                ),
+                format!(
+                    "the collection {} of database {} is requested to scatter",
+                    co.name, db.name
+                ),
+            );
+            self.scheduler
+                .setup_task(ReconcileTask {
+                    task: Some(reconcile_task::Task::MigrateShard(MigrateShardTask {
+                        shard: mv.shard_id,
+                        src_group: mv.src_group,
+                        dest_group: mv.dest_group,
+                    })),
+                })
+                .await?;
+        }
+        Ok(moves)
+    }
+}
+
+/// Plan the moves to balance the shards of the collection across the groups.
+/// The shards are moved from the group serving the most shards to the one
+/// serving the least, preferring the group led by the node leading less
+/// shards of the collection, until the numbers differ by at most one.
+fn plan_scatter(
+    collection_id: u64,
+    groups: &[GroupDesc],
+    leader_nodes: &HashMap<u64 /* group */, u64 /* node */>,
+) -> Vec<diagnosis::ScatterMove> {
+    let mut group_shards = groups
+        .iter()
+        .map(|g| {
+            let shards = g
+                .shards
+                .iter()
+                .filter(|s| s.collection_id == collection_id)
+                .map(|s| s.id)
+                .collect::<Vec<_>>();
+            (g.id, shards)
+        })
+        .collect::<HashMap<_, _>>();
+    let mut node_shards = HashMap::<u64, usize>::new();
+    for (group_id, shards) in &group_shards {
+        if let Some(node_id) = leader_nodes.get(group_id) {
+            *node_shards.entry(*node_id).or_default() += shards.len();
+        }
+    }
+    let num_led_shards = |node_shards: &HashMap<u64, usize>, group_id: u64| {
+        leader_nodes.get(&group_id).and_then(|n| node_shards.get(n)).copied().unwrap_or_default()
+    };
+
+    let mut moves = Vec::new();
+    loop {
+        let Some(src) = groups
+            .iter()
+            .map(|g| g.id)
+            .max_by_key(|id| (group_shards[id].len(), std::cmp::Reverse(*id)))
+        else {
+            break;
+        };
+        let Some(dest) = groups
+            .iter()
+            .map(|g| g.id)
+            .min_by_key(|id| (group_shards[id].len(), num_led_shards(&node_shards, *id), *id))
+        else {
+            break;
+        };
+        if group_shards[&src].len() <= group_shards[&dest].len() + 1 {
+            break;
+        }
+
+        let shard_id = group_shards.get_mut(&src).unwrap().pop().unwrap();
+        group_shards.get_mut(&dest).unwrap().push(shard_id);
+        if let Some(node_id) = leader_nodes.get(&src) {
+            *node_shards.entry(*node_id).or_default() -= 1;
+        }
+        if let Some(node_id) = leader_nodes.get(&dest) {
+            *node_shards.entry(*node_id).or_default() += 1;
+        }
+        moves.push(diagnosis::ScatterMove { shard_id, src_group: src, dest_group: dest });
+    }
+    moves
+}
+
+/// Fill the usage of the shards into the description of the collection, the
+/// shards are ordered by their ranges.
+fn describe_collection(
+    co: CollectionDesc,
+    shards: Vec<(ShardDescription, Option<ShardStats>)>,
+) -> CollectionDescription {
+    let mut desc = CollectionDescription { collection: Some(co), ..Default::default() };
+    for (mut shard, shard_stats) in shards {
+        if let Some(ss) = shard_stats {
+            shard.reported = true;
+            shard.num_keys = ss.num_keys;
+            shard.logical_bytes = ss.logical_bytes;
+            desc.num_keys += ss.num_keys;
+            desc.logical_bytes += ss.logical_bytes;
+        }
+        desc.shards.push(shard);
+    }
+    desc.shards.sort_by_cached_key(|s| {
+        s.shard.as_ref().and_then(|s| s.range.as_ref()).map(|r| r.start.clone())
+    });
+    desc
+}
+
+/// Merge the hot keys of shards and keep the `limit` most accessed ones.
+fn aggregate_hot_keys(
+    shards: Vec<(u64 /* group */, ShardStats)>,
+    limit: usize,
+) -> Vec<diagnosis::HotKey> {
+    let mut hot_keys = shards
+        .into_iter()
+        .flat_map(|(group_id, ShardStats { shard_id, collection_id, hot_keys, .. })| {
+            hot_keys.into_iter().map(move |hot_key| diagnosis::HotKey {
+                collection_id,
+                shard_id,
+                group_id,
+                key: hot_key.key.escape_ascii().to_string(),
+                reads: hot_key.reads,
+                writes: hot_key.writes,
+            })
+        })
+        .collect::<Vec<_>>();
+    hot_keys.sort_by_key(|k| std::cmp::Reverse(k.reads + k.writes));
+    hot_keys.truncate(limit);
+    hot_keys
+}
+
+fn aggregate_collection_stats(
+    cfg: &RootConfig,
+    database: &str,
+    co: &CollectionDesc,
+    shards: &[(u64 /* group */, u64 /* shard */, Option<ShardStats>)],
+) -> diagnosis::CollectionStats {
+    let mut stats = diagnosis::CollectionStats {
+        id: co.id,
+        database: database.to_owned(),
+        collection: co.name.clone(),
+        ..Default::default()
+    };
+    for (group_id, shard_id, shard_stats) in shards {
+        let Some(ss) = shard_stats else {
+            stats.unreported_shards += 1;
+            continue;
+        };
+        stats.num_keys += ss.num_keys;
+        stats.logical_bytes += ss.logical_bytes;
+        stats.shards.push(diagnosis::ShardUsage {
+            id: *shard_id,
+            group_id: *group_id,
+            num_keys: ss.num_keys,
+            logical_bytes: ss.logical_bytes,
+            split_candidate: cfg.exceeds_split_threshold(ss.num_keys, ss.logical_bytes),
+        });
+    }
+    stats
+}
+
+impl Root {
+    /// Create the database owned by the tenant, the empty tenant means the
+    /// default tenant.
+    pub async fn create_database(&self, tenant: &str, name: String) -> Result<DatabaseDesc> {
+        let _tenant_guard = if tenant.is_empty() {
+            None
+        } else {
+            Some(ID_GEN_LOCKS.get(TENANT_LOCK_KEY).expect("tenant lock not found").lock().await)
+        };
+        let schema = self.schema()?;
+        let tenant_desc = self.get_tenant(&schema, tenant).await?;
+        let tenant_id = tenant_desc.as_ref().map(|t| t.id).unwrap_or(DEFAULT_TENANT_ID);
+        if let Some(tenant_desc) = tenant_desc.as_ref() {
+            let databases = schema.list_database().await?;
+            let num_databases = databases.iter().filter(|db| db.tenant_id == tenant_id).count();
+            tenant::check_database_quota(tenant_desc, num_databases)?;
+        }
+        let desc = schema
+            .create_database(DatabaseDesc {
+                name: name.to_owned(),
+                tenant_id,
+                ..Default::default()
+            })
+            .await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Database(desc.to_owned())),
+            }])
+            .await;
+        info!("create database. database_id={}, database={}, tenant_id={tenant_id}", desc.id, name);
+        Ok(desc)
+    }
+
+    pub async fn delete_database(&self, tenant: &str, name: &str) -> Result<()> {
+        let db = self.get_database(tenant, name).await?;
+        if db.is_none() {
+            return Err(Error::DatabaseNotFound(name.to_owned()));
+        }
+        let db = db.unwrap();
+        if db.id == sekas_schema::system::db::ID {
+            return Err(Error::InvalidArgument("not support delete system database".into()));
+        }
+        let schema = self.schema()?;
+        if self.cfg.trash_retention_sec == 0 {
+            self.submit_purge_database(&db).await?;
+        } else {
+            // The trash entry is put before deleting the descriptor, so the database is
+            // never lost.
+            let entry = TrashEntry {
+                dropped_at: unix_timestamp(),
+                database: Some(db.clone()),
+                ..Default::default()
+            };
+            schema.put_trash(&entry).await?;
+        }
+        let id = schema.delete_database(&db).await?;
+        self.watcher_hub()
+            .notify_deletes(vec![DeleteEvent { event: Some(delete_event::Event::Database(id)) }])
+            .await;
+        info!("delete database. database={name}");
+        Ok(())
+    }
+
+    /// Rename the database, the id of the database is kept so that the
+    /// clients could route the requests of its collections as before.
+    pub async fn rename_database(
+        &self,
+        tenant: &str,
+        name: &str,
+        new_name: String,
+    ) -> Result<DatabaseDesc> {
+        let schema = self.schema()?;
+        let tenant_id = self.tenant_id(&schema, tenant).await?;
+        let db = schema
+            .get_database(tenant_id, name)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(name.to_owned()))?;
+        if db.id == sekas_schema::system::db::ID {
+            return Err(Error::InvalidArgument("not support rename system database".into()));
+        }
+        if schema.get_database(tenant_id, &new_name).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("database {new_name}")));
+        }
+        let desc = schema.rename_database(&db, &new_name).await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Database(desc.to_owned())),
+            }])
+            .await;
+        info!("rename database. database_id={}, database={name}, new_name={new_name}", desc.id);
+        Ok(desc)
     }
 
     pub async fn create_collection(
         &self,
         name: String,
-        database: String,
+        database: &DatabaseDesc,
+        encrypted: bool,
+        compression: CompressionCodec,
+        key_schema: Option<KeySchema>,
+        constraints: Vec<String>,
+    ) -> Result<CollectionDesc> {
+        let desc = CollectionDesc {
+            name,
+            encrypted,
+            compression: compression as i32,
+            key_schema,
+            constraints,
+            ..Default::default()
+        };
+        self.create_collection_with_splits(desc, database, &[]).await
+    }
+
+    /// Create the collection whose shards are split at the keys in advance,
+    /// the options of the collection are taken from `desc`.
+    async fn create_collection_with_splits(
+        &self,
+        mut desc: CollectionDesc,
+        database: &DatabaseDesc,
+        split_keys: &[Vec<u8>],
     ) -> Result<CollectionDesc> {
         let schema = self.schema()?;
-        let db = schema
-            .get_database(&database)
+        let db = self
+            .find_database(database)
             .await?
-            .ok_or_else(|| Error::DatabaseNotFound(database.to_owned()))?;
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        if let Some(key_schema) = desc.key_schema.as_ref() {
+            check_key_schema(key_schema)?;
+        }
+        let ranges = spec::split_ranges(split_keys)?;
+        desc.constraints.sort_unstable();
+        desc.constraints.dedup();
+        self.check_constraints(&schema, &desc.constraints).await?;
+        let _tenant_guard = self.check_tenant_collection_quota(&schema, &db).await?;
+
+        desc.db = db.id;
+        desc.encrypted = desc.encrypted || self.cfg.encrypt_all_collections;
+        if desc.encrypted {
+            self.ensure_data_key().await?;
+        }
+        let collection = schema.prepare_create_collection(desc).await?;
+        info!(
+            "prepare create collection. database={}, collection={collection:?}, collection_id={}",
+            db.name, collection.id
+        );
+
+        let mut wait_create = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            wait_create.push(ShardDesc {
+                id: schema.next_shard_id().await?,
+                collection_id: collection.id.to_owned(),
+                range: Some(range),
+                clone_source: None,
+                encrypted: collection.encrypted,
+                compression: collection.compression,
+                key_schema: collection.key_schema.clone(),
+                constraints: collection.constraints.clone(),
+                frozen: false,
+            });
+        }
+        self.do_create_collection(collection.to_owned(), wait_create).await?;
+
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Collection(collection.to_owned())),
+            }])
+            .await;
+
+        Ok(collection)
+    }
+
+    /// Clone the source collection into a new collection, the data is shared
+    /// with the source collection at a snapshot, see [`CloneSource`] for
+    /// details.
+    pub async fn clone_collection(
+        &self,
+        source: &str,
+        target: String,
+        database: &DatabaseDesc,
+    ) -> Result<CollectionDesc> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let source = schema
+            .get_collection(db.id, source)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {source} not found")))?;
+        if source.id < sekas_schema::FIRST_USER_COLLECTION_ID {
+            return Err(Error::InvalidArgument("unsupported clone system collection".into()));
+        }
+
+        let source_shards = schema.get_collection_shards(source.id).await?;
+        if source_shards.iter().any(|(_, shard)| shard.clone_source.is_some()) {
+            return Err(Error::InvalidArgument(format!(
+                "collection {} is a clone whose data is not realized yet",
+                source.name
+            )));
+        }
+        let _tenant_guard = self.check_tenant_collection_quota(&schema, &db).await?;
 
         let collection = schema
             .prepare_create_collection(CollectionDesc {
-                name: name.to_owned(),
+                name: target.to_owned(),
                 db: db.id,
+                encrypted: source.encrypted,
+                compression: source.compression,
+                key_schema: source.key_schema.clone(),
+                // The cloned shards are placed with the source shards.
+                constraints: source.constraints.clone(),
                 ..Default::default()
             })
             .await?;
+        let version = self.close_clone_source(&source, &source_shards).await?;
+        let mut wait_create = Vec::with_capacity(source_shards.len());
+        for (_, shard) in source_shards {
+            wait_create.push(ShardDesc {
+                id: schema.next_shard_id().await?,
+                collection_id: collection.id,
+                range: shard.range,
+                clone_source: Some(CloneSource { collection_id: source.id, version }),
+                encrypted: collection.encrypted,
+                compression: collection.compression,
+                key_schema: collection.key_schema.clone(),
+                constraints: collection.constraints.clone(),
+                frozen: false,
+            });
+        }
         info!(
-            "prepare create collection. database={database}, collection={collection:?}, collection_id={}", collection.id);
+            "prepare clone collection. database={}, source={}, collection={collection:?}, version={version}",
+            db.name, source.name
+        );
 
-        self.do_create_collection(schema.to_owned(), collection.to_owned()).await?;
+        self.do_create_collection(collection.to_owned(), wait_create).await?;
 
         self.watcher_hub()
             .notify_updates(vec![UpdateEvent {
@@ -683,17 +1931,146 @@ impl Root {
         Ok(collection)
     }
 
+    /// Allocate the snapshot version of the clone source at a closed
+    /// timestamp. The source shards are frozen while the version is allocated,
+    /// so that no new intents are written, and the existing intents are
+    /// resolved before it, so no txn could commit at a version not greater
+    /// than the snapshot version afterwards.
+    async fn close_clone_source(
+        &self,
+        source: &CollectionDesc,
+        shards: &[(u64, ShardDesc)],
+    ) -> Result<u64> {
+        if !source.frozen {
+            self.freeze_shards(shards, true).await?;
+        }
+        let result = match self.wait_intents_resolved(shards).await {
+            Ok(()) => self.alloc_txn_id(1).await,
+            Err(err) => Err(err),
+        };
+        if !source.frozen {
+            self.freeze_shards(shards, false).await?;
+        }
+        result
+    }
+
+    /// Wait until the txn intents of the shards are resolved by their txns.
+    async fn wait_intents_resolved(&self, shards: &[(u64, ShardDesc)]) -> Result<()> {
+        use group_request_union::Request;
+        use group_response_union::Response;
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        const TIMEOUT: Duration = Duration::from_secs(30);
+        const INTERVAL: Duration = Duration::from_millis(100);
+
+        let deadline = Instant::now() + TIMEOUT;
+        for (group_id, shard) in shards {
+            // Only the keys having intents are returned.
+            let req = Request::Scan(ShardScanRequest {
+                shard_id: shard.id,
+                start_version: TXN_INTENT_VERSION,
+                limit: 1,
+                include_raw_data: true,
+                ignore_txn_intent: true,
+                min_version: TXN_INTENT_VERSION,
+                ..Default::default()
+            });
+            let mut group_client = self.shared.transport_manager.lazy_group_client(*group_id);
+            loop {
+                match group_client.request(&req).await {
+                    Ok(Response::Scan(resp)) if resp.data.is_empty() => break,
+                    Ok(Response::Scan(_)) => {}
+                    Ok(_) => {
+                        return Err(Error::InvalidData(
+                            "invalid response type, Scan is required".into(),
+                        ))
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+                if Instant::now() >= deadline {
+                    return Err(Error::DeadlineExceeded(format!(
+                        "wait the txn intents of shard {} to be resolved",
+                        shard.id
+                    )));
+                }
+                sekas_runtime::time::sleep(INTERVAL).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark the shards as read-only or writable.
+    async fn freeze_shards(&self, shards: &[(u64, ShardDesc)], frozen: bool) -> Result<()> {
+        for (group_id, shard) in shards {
+            let mut group_client = self.shared.transport_manager.lazy_group_client(*group_id);
+            let mut retry_state = RetryState::new(Some(Duration::from_secs(10)));
+            while let Err(err) = group_client.freeze_shard(shard.id, frozen).await {
+                // The shard might be moved away, it is reported as an internal error.
+                retry_state.retry(err).await.map_err(|err| Error::Rpc(err.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether there are enough nodes to satisfy the placement
+    /// constraints of a new collection.
+    async fn check_constraints(&self, schema: &Schema, constraints: &[String]) -> Result<()> {
+        if constraints.is_empty() {
+            return Ok(());
+        }
+        if let Some(c) = constraints.iter().find(|c| c.is_empty() || *c == SYSTEM_TIER_LABEL) {
+            return Err(Error::InvalidArgument(format!("illegal placement constraint {c:?}")));
+        }
+        let num_nodes = schema
+            .list_node()
+            .await?
+            .iter()
+            .filter(|n| n.status != NodeStatus::Decommissioned as i32)
+            .filter(|n| constraints.iter().all(|c| n.labels.contains(c)))
+            .count();
+        if num_nodes < self.cfg.replicas_per_group {
+            return Err(Error::InvalidArgument(format!(
+                "only {num_nodes} nodes satisfy the placement constraints {constraints:?}, but {} \
+                 replicas are required",
+                self.cfg.replicas_per_group
+            )));
+        }
+        Ok(())
+    }
+
+    /// Create the databases and collections declared by the spec, the existing
+    /// ones are skipped.
+    async fn apply_bootstrap_spec(&self, spec: &BootstrapSpec) -> Result<()> {
+        spec::check_replication_factor(spec, self.cfg.replicas_per_group)?;
+        for db_spec in &spec.databases {
+            let db = match self.get_database("", &db_spec.name).await? {
+                Some(db) => db,
+                None => self.create_database("", db_spec.name.clone()).await?,
+            };
+            for co_spec in &db_spec.collections {
+                if self.get_collection(&co_spec.name, &db).await?.is_some() {
+                    continue;
+                }
+                let desc = CollectionDesc { name: co_spec.name.clone(), ..Default::default() };
+                let split_keys =
+                    co_spec.split_keys.iter().map(|k| k.as_bytes().to_vec()).collect::<Vec<_>>();
+                let co = self.create_collection_with_splits(desc, &db, &split_keys).await?;
+                info!(
+                    "create collection by bootstrap spec. database={}, collection={}, shards={}",
+                    db.name,
+                    co.name,
+                    split_keys.len() + 1
+                );
+            }
+        }
+        Ok(())
+    }
+
     async fn do_create_collection(
         &self,
-        schema: Arc<Schema>,
         collection: CollectionDesc,
+        wait_create: Vec<ShardDesc>,
     ) -> Result<()> {
-        let wait_create = {
-            let range = RangePartition { start: SHARD_MIN.to_owned(), end: SHARD_MAX.to_owned() };
-            let id = schema.next_shard_id().await?;
-            vec![ShardDesc { id, collection_id: collection.id.to_owned(), range: Some(range) }]
-        };
-
         self.jobs
             .submit(
                 BackgroundJob {
@@ -714,58 +2091,401 @@ impl Root {
         Ok(())
     }
 
-    pub async fn delete_collection(&self, name: &str, database: &DatabaseDesc) -> Result<()> {
+    pub async fn delete_collection(&self, name: &str, database: &DatabaseDesc) -> Result<()> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let collection = schema.get_collection(db.id, name).await?;
+        if let Some(collection) = collection {
+            if collection.id < sekas_schema::FIRST_USER_COLLECTION_ID {
+                return Err(Error::InvalidArgument("unsupported delete system collection".into()));
+            }
+            let collection_id = collection.id;
+            let clones = schema
+                .list_group()
+                .await?
+                .into_iter()
+                .flat_map(|g| g.shards)
+                .filter(|s| s.clone_source.as_ref().map(|c| c.collection_id) == Some(collection_id))
+                .count();
+            if clones > 0 {
+                return Err(Error::InvalidArgument(format!(
+                    "collection {name} is the clone source of {clones} shards"
+                )));
+            }
+            if self.cfg.trash_retention_sec == 0 {
+                self.submit_purge_collection(&db.name, &collection).await?;
+            } else {
+                let entry = TrashEntry {
+                    dropped_at: unix_timestamp(),
+                    collection: Some(collection.clone()),
+                    ..Default::default()
+                };
+                schema.put_trash(&entry).await?;
+            }
+            schema.delete_collection(collection).await?;
+            self.watcher_hub()
+                .notify_deletes(vec![DeleteEvent {
+                    event: Some(delete_event::Event::Collection(collection_id)),
+                }])
+                .await;
+        }
+        info!("delete collection, database {}, collection={}", database.name, name);
+        Ok(())
+    }
+
+    async fn submit_purge_database(&self, db: &DatabaseDesc) -> Result<()> {
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::PurgeDatabase(PurgeDatabaseJob {
+                        database_id: db.id,
+                        database_name: db.name.to_owned(),
+                        created_time: format!("{:?}", Instant::now()),
+                    })),
+                    ..Default::default()
+                },
+                false,
+            )
+            .await
+    }
+
+    async fn submit_purge_collection(
+        &self,
+        database_name: &str,
+        collection: &CollectionDesc,
+    ) -> Result<()> {
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::PurgeCollection(PurgeCollectionJob {
+                        database_id: collection.db,
+                        collection_id: collection.id,
+                        database_name: database_name.to_owned(),
+                        collection_name: collection.name.to_owned(),
+                        created_time: format!("{:?}", Instant::now()),
+                    })),
+                    ..Default::default()
+                },
+                false,
+            )
+            .await
+    }
+
+    /// Return the dropped databases and collections which are not purged yet,
+    /// as a JSON array.
+    pub async fn trash_state(&self) -> Result<serde_json::Value> {
+        let retention_sec = self.cfg.trash_retention_sec;
+        let entries = self.schema()?.list_trash().await?;
+        Ok(entries.iter().map(|e| trash_to_json(e, retention_sec)).collect())
+    }
+
+    /// Recover the dropped database and its collections, the latest dropped
+    /// one is recovered if there are several databases with the same name.
+    pub async fn recover_database(&self, tenant: &str, name: &str) -> Result<DatabaseDesc> {
+        let schema = self.schema()?;
+        let tenant_id = self.tenant_id(&schema, tenant).await?;
+        let entry = schema
+            .list_trash()
+            .await?
+            .into_iter()
+            .filter(|e| {
+                e.database.as_ref().map(|db| (db.tenant_id, db.name.as_str()))
+                    == Some((tenant_id, name))
+            })
+            .max_by_key(|e| e.dropped_at)
+            .ok_or_else(|| Error::DatabaseNotFound(format!("{name} in trash")))?;
+        let db = entry.database.clone().unwrap();
+        schema.recover_database(&db).await?;
+        schema.delete_trash(&entry).await?;
+
+        let mut events =
+            vec![UpdateEvent { event: Some(update_event::Event::Database(db.clone())) }];
+        for co in schema.list_database_collections(db.id).await? {
+            events.push(UpdateEvent { event: Some(update_event::Event::Collection(co)) });
+        }
+        self.watcher_hub().notify_updates(events).await;
+        info!("recover database. database={name}, database_id={}", db.id);
+        Ok(db)
+    }
+
+    /// Recover the dropped collection, the database of it must exist.
+    pub async fn recover_collection(
+        &self,
+        database: &DatabaseDesc,
+        name: &str,
+    ) -> Result<CollectionDesc> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let entry = schema
+            .list_trash()
+            .await?
+            .into_iter()
+            .filter(|e| {
+                e.collection.as_ref().map(|co| (co.db, co.name.as_str())) == Some((db.id, name))
+            })
+            .max_by_key(|e| e.dropped_at)
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {name} not in trash")))?;
+        let collection = entry.collection.clone().unwrap();
+        schema.recover_collection(&collection).await?;
+        schema.delete_trash(&entry).await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Collection(collection.clone())),
+            }])
+            .await;
+        info!(
+            "recover collection. database={}, collection={name}, collection_id={}",
+            db.name, collection.id
+        );
+        Ok(collection)
+    }
+
+    /// Purge the dropped databases and collections whose trash retention is
+    /// expired.
+    async fn purge_expired_trash(&self) -> Result<()> {
+        let retention_sec = self.cfg.trash_retention_sec;
+        if retention_sec == 0 {
+            return Ok(());
+        }
+        let schema = self.schema()?;
+        let now = unix_timestamp();
+        for entry in schema.list_trash().await? {
+            if !trash::is_expired(&entry, now, retention_sec) {
+                continue;
+            }
+            let submitted = if let Some(db) = entry.database.as_ref() {
+                // The database is still alive if the trash entry is put but the descriptor
+                // is not deleted.
+                let alive =
+                    schema.get_database(db.tenant_id, &db.name).await?.map(|d| d.id) == Some(db.id);
+                if alive {
+                    Ok(())
+                } else {
+                    self.submit_purge_database(db).await
+                }
+            } else if let Some(co) = entry.collection.as_ref() {
+                let alive =
+                    schema.get_collection(co.db, &co.name).await?.map(|c| c.id) == Some(co.id);
+                if alive {
+                    Ok(())
+                } else {
+                    self.submit_purge_collection("", co).await
+                }
+            } else {
+                Ok(())
+            };
+            match submitted {
+                Ok(()) | Err(Error::AlreadyExists(_)) => {}
+                Err(err) => return Err(err),
+            }
+            schema.delete_trash(&entry).await?;
+            info!("purge expired trash entry: {entry:?}");
+        }
+        Ok(())
+    }
+
+    async fn purge_expired_idempotency_records(&self) -> Result<()> {
+        const IDEMPOTENCY_PURGE_BATCH: usize = 128;
+
+        let retention_sec = self.cfg.idempotency_retention_sec();
+        let schema = self.schema()?;
+        let now = unix_timestamp();
+        loop {
+            // The index is ordered by the creation time, so the purging stops at the first
+            // unexpired record.
+            let records = schema.oldest_idempotency_records(IDEMPOTENCY_PURGE_BATCH).await?;
+            let num_records = records.len();
+            for (created_at, token) in records {
+                if !idempotency::is_expired(created_at, now, retention_sec) {
+                    return Ok(());
+                }
+                let _guard = self.idempotency_locks.lock(&token).await;
+                schema.delete_idempotency_record(created_at, &token).await?;
+                debug!("purge expired idempotency record {token}");
+            }
+            if num_records < IDEMPOTENCY_PURGE_BATCH {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Drop all data of the collection but keep its descriptor. Each shard of
+    /// the collection is replaced with a fresh shard by a background job, which
+    /// is much faster than deleting the keys one by one.
+    pub async fn truncate_collection(
+        &self,
+        database: &DatabaseDesc,
+        name: &str,
+    ) -> Result<CollectionDesc> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let collection = schema
+            .get_collection(db.id, name)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {name} not found")))?;
+        if collection.id < sekas_schema::FIRST_USER_COLLECTION_ID {
+            return Err(Error::InvalidArgument("unsupported truncate system collection".into()));
+        }
+        if collection.frozen {
+            return Err(Error::CollectionFrozen(collection.id));
+        }
+        let collection_id = collection.id;
+        let clones = schema
+            .list_group()
+            .await?
+            .into_iter()
+            .flat_map(|g| g.shards)
+            .filter(|s| s.clone_source.as_ref().map(|c| c.collection_id) == Some(collection_id))
+            .count();
+        if clones > 0 {
+            return Err(Error::InvalidArgument(format!(
+                "collection {name} is the clone source of {clones} shards"
+            )));
+        }
+
+        let mut wait_truncate = Vec::new();
+        for (_, shard) in schema.get_collection_shards(collection_id).await? {
+            let new_shard = ShardDesc {
+                id: schema.next_shard_id().await?,
+                clone_source: None,
+                ..shard.clone()
+            };
+            wait_truncate
+                .push(TruncateShardTask { shard_id: shard.id, new_shard: Some(new_shard) });
+        }
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::TruncateCollection(TruncateCollectionJob {
+                        database_id: db.id,
+                        collection_id,
+                        database_name: db.name.to_owned(),
+                        collection_name: collection.name.to_owned(),
+                        wait_truncate,
+                        created_time: format!("{:?}", Instant::now()),
+                    })),
+                    ..Default::default()
+                },
+                true,
+            )
+            .await?;
+        info!(
+            "truncate collection. database={}, collection={name}, collection_id={collection_id}",
+            db.name
+        );
+        Ok(collection)
+    }
+
+    /// Rename the collection, the id of the collection is kept so that the
+    /// shards and the routing of clients are unaffected.
+    pub async fn rename_collection(
+        &self,
+        database: &DatabaseDesc,
+        name: &str,
+        new_name: String,
+    ) -> Result<CollectionDesc> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let collection = schema
+            .get_collection(db.id, name)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {name} not found")))?;
+        if collection.id < sekas_schema::FIRST_USER_COLLECTION_ID {
+            return Err(Error::InvalidArgument("unsupported rename system collection".into()));
+        }
+        if schema.get_collection(db.id, &new_name).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("collection {new_name}")));
+        }
+        let desc = schema.rename_collection(&collection, &new_name).await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Collection(desc.to_owned())),
+            }])
+            .await;
+        info!(
+            "rename collection. database={}, collection={name}, new_name={new_name}, \
+             collection_id={}",
+            db.name, desc.id
+        );
+        Ok(desc)
+    }
+
+    /// Mark the collection as read-only or writable. The shards are updated
+    /// before the descriptor, so the writes are always rejected once the
+    /// collection is shown as frozen.
+    pub async fn freeze_collection(
+        &self,
+        database: &DatabaseDesc,
+        name: &str,
+        frozen: bool,
+    ) -> Result<CollectionDesc> {
+        let schema = self.schema()?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let collection = schema
+            .get_collection(db.id, name)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {name} not found")))?;
+        if collection.id < sekas_schema::FIRST_USER_COLLECTION_ID {
+            return Err(Error::InvalidArgument("unsupported freeze system collection".into()));
+        }
+        let shards = schema.get_collection_shards(collection.id).await?;
+        self.freeze_shards(&shards, frozen).await?;
+        let desc = CollectionDesc { frozen, ..collection };
+        schema.update_collection(desc.clone()).await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Collection(desc.to_owned())),
+            }])
+            .await;
+        info!(
+            "freeze collection. database={}, collection={name}, collection_id={}, frozen={frozen}",
+            db.name, desc.id
+        );
+        Ok(desc)
+    }
+
+    /// List the databases owned by the tenant, the empty tenant means the
+    /// default tenant.
+    pub async fn list_database(&self, tenant: &str) -> Result<Vec<DatabaseDesc>> {
         let schema = self.schema()?;
-        let db = self
-            .get_database(&database.name)
-            .await?
-            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
-        let collection = schema.get_collection(db.id, name).await?;
-        if let Some(collection) = collection {
-            if collection.id < sekas_schema::FIRST_USER_COLLECTION_ID {
-                return Err(Error::InvalidArgument("unsupported delete system collection".into()));
-            }
-            let collection_id = collection.id;
-            let database_name = db.name.to_owned();
-            let collection_name = collection.name.to_owned();
-            self.jobs
-                .submit(
-                    BackgroundJob {
-                        job: Some(Job::PurgeCollection(PurgeCollectionJob {
-                            database_id: db.id,
-                            collection_id,
-                            database_name,
-                            collection_name,
-                            created_time: format!("{:?}", Instant::now()),
-                        })),
-                        ..Default::default()
-                    },
-                    false,
-                )
-                .await?;
-            schema.delete_collection(collection).await?;
-            self.watcher_hub()
-                .notify_deletes(vec![DeleteEvent {
-                    event: Some(delete_event::Event::Collection(collection_id)),
-                }])
-                .await;
-        }
-        info!("delete collection, database {}, collection={}", database.name, name);
-        Ok(())
+        let tenant_id = self.tenant_id(&schema, tenant).await?;
+        let databases = schema.list_database().await?;
+        Ok(databases.into_iter().filter(|db| db.tenant_id == tenant_id).collect())
     }
 
-    pub async fn list_database(&self) -> Result<Vec<DatabaseDesc>> {
-        self.schema()?.list_database().await
+    /// Get the database owned by the tenant, the empty tenant means the
+    /// default tenant.
+    pub async fn get_database(&self, tenant: &str, name: &str) -> Result<Option<DatabaseDesc>> {
+        let schema = self.schema()?;
+        let tenant_id = self.tenant_id(&schema, tenant).await?;
+        schema.get_database(tenant_id, name).await
     }
 
-    pub async fn get_database(&self, name: &str) -> Result<Option<DatabaseDesc>> {
-        self.schema()?.get_database(name).await
+    /// Find the latest descriptor of the database by the tenant and name of
+    /// the given one, which might be stale.
+    pub async fn find_database(&self, database: &DatabaseDesc) -> Result<Option<DatabaseDesc>> {
+        self.schema()?.get_database(database.tenant_id, &database.name).await
     }
 
     pub async fn list_collection(&self, database: &DatabaseDesc) -> Result<Vec<CollectionDesc>> {
         let schema = self.schema()?;
-        let db = schema
-            .get_database(&database.name)
+        let db = self
+            .find_database(database)
             .await?
             .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
         Ok(schema
@@ -783,18 +2503,25 @@ impl Root {
         database: &DatabaseDesc,
     ) -> Result<Option<CollectionDesc>> {
         let db = self
-            .get_database(&database.name)
+            .find_database(database)
             .await?
             .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
         self.schema()?.get_collection(db.id, name).await
     }
 
-    pub async fn watch(&self, cur_groups: HashMap<u64, u64>) -> Result<Watcher> {
+    /// Watch the events of the resources of `resource_types`, all types are
+    /// watched if it is empty.
+    pub async fn watch(
+        &self,
+        cur_groups: HashMap<u64, u64>,
+        resource_types: &[i32],
+    ) -> Result<Watcher> {
         let schema = self.schema()?;
+        let filter = WatchFilter::new(resource_types)?;
 
         let watcher = {
             let hub = self.watcher_hub();
-            let (watcher, mut initializer) = hub.create_watcher().await;
+            let (watcher, mut initializer) = hub.create_watcher(filter).await;
             let (updates, deletes) = schema.list_all_events(cur_groups).await?;
             initializer.set_init_resp(updates, deletes);
             watcher
@@ -806,10 +2533,27 @@ impl Root {
         &self,
         addr: String,
         capacity: NodeCapacity,
+        binary_version: u64,
+        labels: Vec<String>,
     ) -> Result<(Vec<u8>, NodeDesc, RootDesc)> {
         let schema = self.schema()?;
+        let binary_version = std::cmp::max(binary_version, CLUSTER_VERSION_INITIAL);
+        let cluster_version = schema.cluster_version().await?;
+        if binary_version < cluster_version {
+            return Err(Error::InvalidArgument(format!(
+                "the binary version {binary_version} of node {addr} is less than the cluster version {cluster_version}"
+            )));
+        }
         let node = schema
-            .add_node(NodeDesc { addr, capacity: Some(capacity), ..Default::default() })
+            .add_node(NodeDesc {
+                addr,
+                capacity: Some(capacity),
+                binary_version,
+                labels,
+                // The time of joining, so a wiped node re-joining always gets a new one.
+                incarnation: timestamp_nanos(),
+                ..Default::default()
+            })
             .await?;
         self.watcher_hub()
             .notify_updates(vec![UpdateEvent {
@@ -827,10 +2571,40 @@ impl Root {
         self.heartbeat_queue
             .try_schedule(vec![HeartbeatTask { node_id: node.id }], Instant::now())
             .await;
-        info!("new node join cluster. node={}, addr={}", node.id, node.addr);
+        info!(
+            "new node join cluster. node={}, addr={}, incarnation={}",
+            node.id, node.addr, node.incarnation
+        );
         Ok((cluster_id, node, root))
     }
 
+    /// Reject the requests from the stale incarnations of the node, the zero
+    /// node id means the node is unknown, eg. it runs an older binary.
+    pub async fn check_node_incarnation(&self, node_id: u64, incarnation: u64) -> Result<()> {
+        if node_id == 0 {
+            return Ok(());
+        }
+        let schema = self.schema()?;
+        let expected = schema.get_node(node_id).await?.map(|n| n.incarnation).unwrap_or_default();
+        if expected != incarnation {
+            warn!(
+                "reject stale node. node={node_id}, incarnation={incarnation}, expected={expected}"
+            );
+            return Err(Error::NodeIncarnationMismatch(node_id, expected, incarnation));
+        }
+        Ok(())
+    }
+
+    /// The node is shutting down gracefully, it is regarded as dead until the
+    /// heartbeat succeeds again.
+    pub fn shutdown_node(&self, node_id: u64) -> Result<()> {
+        // Only the root leader tracks the liveness of nodes.
+        self.schema()?;
+        info!("node {node_id} is shutting down");
+        self.liveness.expire(node_id);
+        Ok(())
+    }
+
     pub async fn report(&self, updates: Vec<GroupUpdates>) -> Result<()> {
         // mock report doesn't work.
         // return Ok(());
@@ -838,19 +2612,53 @@ impl Root {
         let ongoing_stats = self.ongoing_stats.clone();
         let schema = self.schema()?;
         let mut update_events = Vec::new();
-        let mut changed_group_states = Vec::new();
+        let mut changed_group_states = HashSet::new();
+        let mut group_events = Vec::new();
+
+        // The groups and replica states are read at most once for each group, and the
+        // accepted updates are applied to them, so that the later updates of the same
+        // group are compared with the latest ones.
+        let mut groups: HashMap<u64, Option<GroupDesc>> = HashMap::new();
+        let mut group_replica_states: HashMap<u64, Vec<ReplicaState>> = HashMap::new();
+        let mut group_writes = HashMap::new();
+        let mut replica_state_writes = HashMap::new();
         for u in updates {
             let group_desc = if let Some(update_group) = &u.group_desc {
-                match schema.get_group(u.group_id).await? {
-                    Some(pre_group) if pre_group.epoch >= update_group.epoch => None,
-                    _ => u.group_desc,
+                let pre_group = match groups.entry(u.group_id) {
+                    hash_map::Entry::Occupied(ent) => ent.into_mut(),
+                    hash_map::Entry::Vacant(ent) => ent.insert(schema.get_group(u.group_id).await?),
+                };
+                match pre_group {
+                    Some(pre) if pre.epoch >= update_group.epoch => None,
+                    _ => {
+                        for (kind, detail) in
+                            history::diff_group_desc(pre_group.as_ref(), update_group)
+                        {
+                            group_events.push(self.group_event(
+                                u.group_id,
+                                kind,
+                                update_group.epoch,
+                                detail,
+                            ));
+                        }
+                        *pre_group = Some(update_group.clone());
+                        group_writes.insert(update_group.id, update_group.clone());
+                        u.group_desc
+                    }
                 }
             } else {
                 None
             };
 
             let replica_state = if let Some(update_replica_state) = &u.replica_state {
-                match schema.get_replica_state(u.group_id, update_replica_state.replica_id).await? {
+                let replica_states = match group_replica_states.entry(u.group_id) {
+                    hash_map::Entry::Occupied(ent) => ent.into_mut(),
+                    hash_map::Entry::Vacant(ent) => {
+                        ent.insert(schema.group_replica_states(u.group_id).await?)
+                    }
+                };
+                let replica_id = update_replica_state.replica_id;
+                match replica_states.iter().find(|rs| rs.replica_id == replica_id) {
                     Some(pre_rs)
                         if pre_rs.term > update_replica_state.term
                             || (pre_rs.term == update_replica_state.term
@@ -858,12 +2666,27 @@ impl Root {
                     {
                         None
                     }
-                    _ => u.replica_state,
+                    _ => {
+                        let state = update_replica_state;
+                        if state.role == RaftRole::Leader as i32 {
+                            let detail =
+                                format!("replica={}, node={}", state.replica_id, state.node_id);
+                            group_events.push(self.group_event(
+                                u.group_id,
+                                "change_leader",
+                                state.term,
+                                detail,
+                            ));
+                        }
+                        replica_states.retain(|rs| rs.replica_id != replica_id);
+                        replica_states.push(state.clone());
+                        replica_state_writes.insert((state.group_id, replica_id), state.clone());
+                        u.replica_state
+                    }
                 }
             } else {
                 None
             };
-            schema.update_group_replica(group_desc.to_owned(), replica_state.to_owned()).await?;
 
             if let Some(sched_state) = u.schedule_state {
                 ongoing_stats.handle_update(&[sched_state], None);
@@ -888,95 +2711,612 @@ impl Root {
                     state.group_id, state.replica_id, state
                 );
                 metrics::ROOT_UPDATE_REPLICA_STATE_TOTAL.report.inc();
-                changed_group_states.push(state.group_id);
+                changed_group_states.insert(u.group_id);
+            }
+        }
+
+        schema
+            .update_group_replicas(
+                group_writes.into_values().collect(),
+                replica_state_writes.into_values().collect(),
+            )
+            .await?;
+
+        for group_id in changed_group_states {
+            let replicas = group_replica_states.remove(&group_id).unwrap_or_default();
+            let state = schema::group_state(group_id, replicas);
+            update_events.push(UpdateEvent { event: Some(update_event::Event::GroupState(state)) })
+        }
+
+        self.watcher_hub().notify_updates(update_events).await;
+        self.record_group_events(&schema, group_events).await;
+
+        Ok(())
+    }
+
+    fn group_event(&self, group_id: u64, kind: &str, epoch: u64, detail: String) -> GroupEvent {
+        // The ids are allocated by the audit log, which are unique and monotonic.
+        GroupEvent { id: self.audit_log.next_id(), group_id, kind: kind.to_owned(), epoch, detail }
+    }
+
+    /// Append the events to the history of groups, only the latest
+    /// [`history::MAX_EVENTS_PER_GROUP`] events of each group are kept.
+    async fn record_group_events(&self, schema: &Schema, events: Vec<GroupEvent>) {
+        let mut group_ids = HashSet::new();
+        for event in events {
+            info!(
+                "group event. group={}, kind={}, epoch={}, detail={}",
+                event.group_id, event.kind, event.epoch, event.detail
+            );
+            if let Err(err) = schema.append_group_event(&event).await {
+                warn!("append group event {} of group {}: {err:?}", event.id, event.group_id);
+                continue;
+            }
+            group_ids.insert(event.group_id);
+        }
+        for group_id in group_ids {
+            if let Err(err) =
+                schema.purge_group_events(group_id, history::MAX_EVENTS_PER_GROUP).await
+            {
+                warn!("purge the history of group {group_id}: {err:?}");
+            }
+        }
+    }
+
+    /// Return the latest `limit` events of the group which are observed since
+    /// the unix timestamp `since_ms`, the newest one comes first.
+    pub async fn group_history(
+        &self,
+        group_id: u64,
+        since_ms: u64,
+        limit: usize,
+    ) -> Result<Vec<GroupEvent>> {
+        let events = self.schema()?.list_group_events(group_id).await?;
+        let since_id = since_ms.saturating_mul(1_000_000);
+        Ok(events.into_iter().rev().take_while(|e| e.id >= since_id).take(limit).collect())
+    }
+
+    pub async fn alloc_replica(
+        &self,
+        group_id: u64,
+        epoch: u64,
+        requested_cnt: u64,
+    ) -> Result<Vec<ReplicaDesc>> {
+        let schema = self.schema()?;
+        let group_desc = schema.get_group(group_id).await?.ok_or(Error::GroupNotFound(group_id))?;
+        if group_desc.epoch != epoch {
+            return Err(Error::InvalidArgument("epoch not match".to_owned()));
+        }
+        let constraints = allocator::group_constraints(&group_desc);
+        let mut existing_replicas =
+            group_desc.replicas.into_iter().map(|r| r.node_id).collect::<HashSet<u64>>();
+        let replica_states = schema.group_replica_states(group_id).await?;
+        for replica in replica_states {
+            existing_replicas.insert(replica.node_id);
+        }
+        info!("attempt allocate {requested_cnt} replicas for exist group {group_id}");
+
+        let nodes = self
+            .alloc
+            .allocate_group_replica(
+                existing_replicas.into_iter().collect(),
+                requested_cnt as usize,
+                group_id == ROOT_GROUP_ID,
+                &constraints,
+            )
+            .await?;
+        if nodes.len() != requested_cnt as usize {
+            warn!("non enough nodes to allocate replicas, exist nodes: {}, requested: {requested_cnt}", nodes.len());
+            return Err(Error::ResourceExhausted("no enough nodes".to_owned()));
+        }
+
+        let mut replicas = Vec::with_capacity(nodes.len());
+        for n in &nodes {
+            let replica_id = schema.next_replica_id().await?;
+            replicas.push(ReplicaDesc {
+                id: replica_id,
+                node_id: n.id,
+                role: ReplicaRole::Voter.into(),
+            });
+        }
+        info!(
+            "advise allocate new group {group_id} replicas in nodes: {:?}",
+            replicas.iter().map(|r| r.node_id).collect::<Vec<_>>()
+        );
+        Ok(replicas)
+    }
+
+    pub async fn alloc_txn_id(&self, num_required: u64) -> Result<u64> {
+        let root_core = self.shared.root_core()?;
+        loop {
+            let next_txn_id = root_core.next_txn_id.load(Ordering::Relaxed);
+            let max_txn_id = root_core.max_txn_id.load(Ordering::Acquire);
+            if max_txn_id == 0 {
+                return Err(Error::NotLeader(0, 0, None));
+            }
+
+            if next_txn_id + num_required > max_txn_id {
+                sekas_runtime::yield_now().await;
+                continue;
+            }
+            if root_core
+                .next_txn_id
+                .compare_exchange(
+                    next_txn_id,
+                    next_txn_id + num_required,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                // TODO(walter) ensure leadership before return.
+                return Ok(next_txn_id);
+            }
+        }
+    }
+
+    /// Return the GC horizon of the collection, the versions shadowed by a
+    /// version not greater than it are collected by the replicas.
+    pub async fn min_gc_timestamp(&self, collection_id: u64) -> Result<u64> {
+        let root_core = self.shared.root_core()?;
+        // The versions are allocated from the txn ids, which might lag behind
+        // the wall clock.
+        let now = std::cmp::min(timestamp_nanos(), root_core.next_txn_id.load(Ordering::Acquire));
+        let horizon = root_core.gc_leases.min_gc_timestamp(collection_id, now);
+
+        // The snapshots of the clone sources are read until the clones are
+        // realized.
+        let schema = self.schema()?;
+        let clone_sources = schema.clone_source_versions().await?;
+        let horizon = match clone_sources.get(&collection_id) {
+            Some(version) => std::cmp::min(horizon, *version),
+            None => horizon,
+        };
+        Ok(horizon)
+    }
+
+    pub async fn hold_gc_lease(
+        &self,
+        lease_id: u64,
+        collection_id: u64,
+        timestamp: u64,
+        ttl: Duration,
+    ) -> Result<(u64, u64)> {
+        let root_core = self.shared.root_core()?;
+        root_core.gc_leases.hold(lease_id, collection_id, timestamp, ttl, timestamp_nanos())
+    }
+
+    pub async fn release_gc_lease(&self, lease_id: u64) -> Result<()> {
+        let root_core = self.shared.root_core()?;
+        root_core.gc_leases.release(lease_id);
+        Ok(())
+    }
+
+    /// Return the limits of copying data of moving shards, the one adjusted by
+    /// admin takes precedence over the config.
+    pub async fn move_shard_limit(&self) -> Result<MoveShardLimit> {
+        let schema = self.schema()?;
+        let limit = schema.move_shard_limit().await?.unwrap_or_else(|| MoveShardLimit {
+            keys_per_sec: self.cfg.move_shard_keys_per_sec,
+            bytes_per_sec: self.cfg.move_shard_bytes_per_sec,
+            backoff_latency_ms: self.cfg.move_shard_backoff_latency_ms,
+        });
+        Ok(limit)
+    }
+
+    /// Adjust the limits of copying data of moving shards, the unspecified
+    /// fields are kept. The nodes apply it once they receive it via heartbeat.
+    pub async fn update_move_shard_limit(
+        &self,
+        keys_per_sec: Option<u64>,
+        bytes_per_sec: Option<u64>,
+        backoff_latency_ms: Option<u64>,
+    ) -> Result<MoveShardLimit> {
+        let schema = self.schema()?;
+        let mut limit = self.move_shard_limit().await?;
+        limit.keys_per_sec = keys_per_sec.unwrap_or(limit.keys_per_sec);
+        limit.bytes_per_sec = bytes_per_sec.unwrap_or(limit.bytes_per_sec);
+        limit.backoff_latency_ms = backoff_latency_ms.unwrap_or(limit.backoff_latency_ms);
+        schema.set_move_shard_limit(&limit).await?;
+        info!(
+            "update move shard limit, keys per sec {}, bytes per sec {}, backoff latency {}ms",
+            limit.keys_per_sec, limit.bytes_per_sec, limit.backoff_latency_ms
+        );
+        Ok(limit)
+    }
+
+    /// Set a hot reloadable option, it takes effect on root immediately, and on
+    /// the nodes once they receive it via heartbeat.
+    pub async fn set_config(&self, key: &str, value: &str) -> Result<()> {
+        let schema = self.schema()?;
+        let dynamic_config = &self.shared.dynamic_config;
+        let prev_value = dynamic_config
+            .get(key)
+            .ok_or_else(|| Error::InvalidArgument(format!("{key} is not hot reloadable")))?;
+        dynamic_config.set(key, value)?;
+        if let Err(err) = schema.set_dynamic_config(key, value).await {
+            dynamic_config.set(key, &prev_value)?;
+            return Err(err);
+        }
+        info!("set config {key} from {prev_value} to {value}");
+
+        // Push the new value to nodes as soon as possible.
+        let nodes = schema.list_node().await?;
+        self.heartbeat_queue
+            .try_schedule(
+                nodes.iter().map(|n| HeartbeatTask { node_id: n.id }).collect::<Vec<_>>(),
+                Instant::now(),
+            )
+            .await;
+        Ok(())
+    }
+
+    /// Return the current values of the hot reloadable options.
+    pub fn list_config(&self) -> Result<Vec<(String, String)>> {
+        // The values set by admin are only known by the root leader.
+        self.schema()?;
+        Ok(self.shared.dynamic_config.list())
+    }
+
+    /// Generate a new data key, the new values are encrypted by it once the
+    /// nodes receive it via heartbeat.
+    pub async fn rotate_data_key(&self, wait_result: bool) -> Result<u64> {
+        if !self.shared.key_manager.is_enabled() {
+            return Err(Error::InvalidArgument("the encryption is not enabled".into()));
+        }
+        let schema = self.schema()?;
+        let key_id = schema.data_keys().await?.last().map(|k| k.id + 1).unwrap_or(1);
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::RotateDataKey(RotateDataKeyJob {
+                        key_id,
+                        created_time: format!("{:?}", Instant::now()),
+                    })),
+                    ..Default::default()
+                },
+                wait_result,
+            )
+            .await?;
+        Ok(key_id)
+    }
+
+    /// Make sure a data key exists before creating encrypted collections.
+    async fn ensure_data_key(&self) -> Result<()> {
+        let schema = self.schema()?;
+        let cluster_version = schema.cluster_version().await?;
+        if cluster_version < CLUSTER_VERSION_ENCRYPTION {
+            return Err(Error::InvalidArgument(format!(
+                "encryption requires cluster version {CLUSTER_VERSION_ENCRYPTION}, but the current cluster version is {cluster_version}"
+            )));
+        }
+        if schema.data_keys().await?.is_empty() {
+            match self.rotate_data_key(true).await {
+                Ok(_) | Err(Error::AlreadyExists(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    async fn rotate_expired_data_key(&self) -> Result<()> {
+        if !self.shared.key_manager.is_enabled() {
+            return Ok(());
+        }
+        let schema = self.schema()?;
+        let Some(current) = schema.data_keys().await?.pop() else {
+            // No collections are encrypted yet.
+            return Ok(());
+        };
+        if unix_timestamp() < current.created_at + self.cfg.data_key_rotation_sec {
+            return Ok(());
+        }
+        info!("data key {} is expired, rotate it", current.id);
+        match self.rotate_data_key(false).await {
+            Ok(_) | Err(Error::AlreadyExists(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Root {
+    /// Create a user and return its token. Only the digest of the token is
+    /// stored, so the token could not be retrieved later.
+    pub async fn create_user(&self, name: &str, superuser: bool) -> Result<String> {
+        crate::auth::validate_user_name(name)?;
+        let schema = self.user_schema().await?;
+        if schema.get_user(name).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("user {name}")));
+        }
+        let (token, token_digest) = crate::auth::generate_token(name)?;
+        let desc = UserDesc { name: name.to_owned(), token_digest, superuser, permissions: vec![] };
+        schema.put_user(&desc).await?;
+        info!("create user {name}, superuser {superuser}");
+        Ok(token)
+    }
+
+    pub async fn delete_user(&self, name: &str) -> Result<()> {
+        let schema = self.user_schema().await?;
+        self.get_user(&schema, name).await?;
+        schema.delete_user(name).await?;
+        info!("delete user {name}");
+        Ok(())
+    }
+
+    /// Generate a new token for the user, the previous one is revoked.
+    pub async fn reset_user_token(&self, name: &str) -> Result<String> {
+        let schema = self.user_schema().await?;
+        let mut desc = self.get_user(&schema, name).await?;
+        let (token, token_digest) = crate::auth::generate_token(name)?;
+        desc.token_digest = token_digest;
+        schema.put_user(&desc).await?;
+        info!("reset the token of user {name}");
+        Ok(token)
+    }
+
+    /// Grant the permission on the database to the user, the previous one is
+    /// replaced. [`Permission::None`] revokes the permission.
+    pub async fn grant_permission(
+        &self,
+        name: &str,
+        database: &DatabaseDesc,
+        permission: Permission,
+    ) -> Result<()> {
+        let schema = self.user_schema().await?;
+        let mut desc = self.get_user(&schema, name).await?;
+        let db = self
+            .find_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        desc.permissions.retain(|p| p.database_id != db.id);
+        if permission != Permission::None {
+            desc.permissions.push(DatabasePermission {
+                database_id: db.id,
+                database: db.name.clone(),
+                permission: permission as i32,
+            });
+        }
+        schema.put_user(&desc).await?;
+        info!(
+            "grant {} permission on database {} to user {name}",
+            permission.as_str_name(),
+            db.name
+        );
+        Ok(())
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<UserDesc>> {
+        self.user_schema().await?.list_user().await
+    }
+
+    async fn get_user(&self, schema: &Schema, name: &str) -> Result<UserDesc> {
+        schema
+            .get_user(name)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("user {name} not found")))
+    }
+
+    /// Return the schema once the user collection exists. The collection is
+    /// created on demand in the clusters bootstrapped before the users are
+    /// supported.
+    async fn user_schema(&self) -> Result<Arc<Schema>> {
+        let schema = self.schema()?;
+        let cluster_version = schema.cluster_version().await?;
+        if cluster_version < CLUSTER_VERSION_AUTH {
+            return Err(Error::InvalidArgument(format!(
+                "users require cluster version {CLUSTER_VERSION_AUTH}, but the current cluster version is {cluster_version}"
+            )));
+        }
+        if !schema.has_user_collection().await? {
+            let shard_desc = sekas_schema::system::col::user_shard_desc();
+            let mut group_client = self.shared.transport_manager.lazy_group_client(ROOT_GROUP_ID);
+            let mut retry_state = RetryState::new(Some(Duration::from_secs(10)));
+            while let Err(err) = group_client.create_shard(&shard_desc).await {
+                retry_state.retry(err).await?;
             }
+            schema.add_user_collection().await?;
+            info!("create the user collection");
+        }
+        Ok(schema)
+    }
+}
+
+impl Root {
+    pub async fn create_tenant(&self, name: &str, quota: TenantQuota) -> Result<TenantDesc> {
+        if name.is_empty() {
+            return Err(Error::InvalidArgument("tenant name is empty".into()));
         }
+        let desc = self
+            .schema()?
+            .create_tenant(TenantDesc {
+                name: name.to_owned(),
+                quota: Some(quota),
+                ..Default::default()
+            })
+            .await?;
+        info!("create tenant {name}, tenant_id={}", desc.id);
+        Ok(desc)
+    }
 
-        let mut states = schema.list_group_state().await?; // TODO: fix poor performance.
-        states.retain(|s| changed_group_states.contains(&s.group_id));
-        for state in states {
-            update_events.push(UpdateEvent { event: Some(update_event::Event::GroupState(state)) })
+    /// Delete the tenant, it must not own any databases, including the ones in
+    /// the trash.
+    pub async fn delete_tenant(&self, name: &str) -> Result<()> {
+        if name.is_empty() {
+            return Err(Error::InvalidArgument("tenant name is empty".into()));
         }
-
-        self.watcher_hub().notify_updates(update_events).await;
-
+        let _tenant_guard =
+            ID_GEN_LOCKS.get(TENANT_LOCK_KEY).expect("tenant lock not found").lock().await;
+        let schema = self.schema()?;
+        let tenant = self.get_tenant(&schema, name).await?.expect("tenant name is not empty");
+        let owned = schema.list_database().await?.iter().any(|db| db.tenant_id == tenant.id)
+            || schema
+                .list_trash()
+                .await?
+                .iter()
+                .any(|e| e.database.as_ref().map(|db| db.tenant_id) == Some(tenant.id));
+        if owned {
+            return Err(Error::InvalidArgument(format!("tenant {name} still owns databases")));
+        }
+        schema.delete_tenant(name).await?;
+        info!("delete tenant {name}, tenant_id={}", tenant.id);
         Ok(())
     }
 
-    pub async fn alloc_replica(
-        &self,
-        group_id: u64,
-        epoch: u64,
-        requested_cnt: u64,
-    ) -> Result<Vec<ReplicaDesc>> {
-        let schema = self.schema()?;
-        let group_desc = schema.get_group(group_id).await?.ok_or(Error::GroupNotFound(group_id))?;
-        if group_desc.epoch != epoch {
-            return Err(Error::InvalidArgument("epoch not match".to_owned()));
+    /// Replace the quota of the tenant, the resources already owned are kept
+    /// even if they exceed the new quota.
+    pub async fn set_tenant_quota(&self, name: &str, quota: TenantQuota) -> Result<TenantDesc> {
+        if name.is_empty() {
+            return Err(Error::InvalidArgument("tenant name is empty".into()));
         }
-        let mut existing_replicas =
-            group_desc.replicas.into_iter().map(|r| r.node_id).collect::<HashSet<u64>>();
-        let replica_states = schema.group_replica_states(group_id).await?;
-        for replica in replica_states {
-            existing_replicas.insert(replica.node_id);
+        let schema = self.schema()?;
+        let mut desc = self.get_tenant(&schema, name).await?.expect("tenant name is not empty");
+        info!("set the quota of tenant {name} to {quota:?}");
+        desc.quota = Some(quota);
+        schema.put_tenant(&desc).await?;
+        Ok(desc)
+    }
+
+    /// Aggregate the databases, collections and usage of each tenant, the
+    /// usage is reported by the group leaders so it might be stale.
+    pub async fn tenant_stats(&self) -> Result<Vec<diagnosis::TenantStats>> {
+        let schema = self.schema()?;
+        let tenants = schema.list_tenant().await?;
+        let databases = schema.list_database().await?;
+        let collections = schema.list_collection().await?;
+        let mut collection_usage = HashMap::<u64, (u64, u64)>::new();
+        for group in schema.list_group().await? {
+            for shard in &group.shards {
+                let Some(stats) = self.ongoing_stats.get_shard_stats(shard.id) else {
+                    continue;
+                };
+                let usage = collection_usage.entry(shard.collection_id).or_default();
+                usage.0 += stats.num_keys;
+                usage.1 += stats.logical_bytes;
+            }
         }
-        info!("attempt allocate {requested_cnt} replicas for exist group {group_id}");
+        Ok(tenant::aggregate_tenant_stats(&tenants, &databases, &collections, &collection_usage))
+    }
 
-        let nodes = self
-            .alloc
-            .allocate_group_replica(existing_replicas.into_iter().collect(), requested_cnt as usize)
-            .await?;
-        if nodes.len() != requested_cnt as usize {
-            warn!("non enough nodes to allocate replicas, exist nodes: {}, requested: {requested_cnt}", nodes.len());
-            return Err(Error::ResourceExhausted("no enough nodes".to_owned()));
+    async fn update_tenant_metrics(&self) -> Result<()> {
+        let stats = self.tenant_stats().await?;
+        metrics::TENANT_DATABASES.reset();
+        metrics::TENANT_COLLECTIONS.reset();
+        metrics::TENANT_KEYS.reset();
+        metrics::TENANT_LOGICAL_BYTES.reset();
+        for s in stats {
+            let labels = [s.name.as_str()];
+            metrics::TENANT_DATABASES.with_label_values(&labels).set(s.num_databases as i64);
+            metrics::TENANT_COLLECTIONS.with_label_values(&labels).set(s.num_collections as i64);
+            metrics::TENANT_KEYS.with_label_values(&labels).set(s.num_keys as i64);
+            metrics::TENANT_LOGICAL_BYTES.with_label_values(&labels).set(s.logical_bytes as i64);
         }
+        Ok(())
+    }
 
-        let mut replicas = Vec::with_capacity(nodes.len());
-        for n in &nodes {
-            let replica_id = schema.next_replica_id().await?;
-            replicas.push(ReplicaDesc {
-                id: replica_id,
-                node_id: n.id,
-                role: ReplicaRole::Voter.into(),
-            });
+    /// Get the tenant by name, the empty name means the default tenant, which
+    /// has no descriptor.
+    async fn get_tenant(&self, schema: &Schema, name: &str) -> Result<Option<TenantDesc>> {
+        if name.is_empty() {
+            return Ok(None);
         }
-        info!(
-            "advise allocate new group {group_id} replicas in nodes: {:?}",
-            replicas.iter().map(|r| r.node_id).collect::<Vec<_>>()
-        );
-        Ok(replicas)
+        let desc = schema
+            .get_tenant(name)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("tenant {name} not found")))?;
+        Ok(Some(desc))
     }
 
-    pub async fn alloc_txn_id(&self, num_required: u64) -> Result<u64> {
-        let root_core = self.shared.root_core()?;
-        loop {
-            let next_txn_id = root_core.next_txn_id.load(Ordering::Relaxed);
-            let max_txn_id = root_core.max_txn_id.load(Ordering::Acquire);
-            if max_txn_id == 0 {
-                return Err(Error::NotLeader(0, 0, None));
-            }
+    async fn tenant_id(&self, schema: &Schema, name: &str) -> Result<u64> {
+        Ok(self.get_tenant(schema, name).await?.map(|t| t.id).unwrap_or(DEFAULT_TENANT_ID))
+    }
 
-            if next_txn_id + num_required > max_txn_id {
-                sekas_runtime::yield_now().await;
-                continue;
-            }
-            if root_core
-                .next_txn_id
-                .compare_exchange(
-                    next_txn_id,
-                    next_txn_id + num_required,
-                    Ordering::AcqRel,
-                    Ordering::Relaxed,
-                )
-                .is_ok()
-            {
-                // TODO(walter) ensure leadership before return.
-                return Ok(next_txn_id);
-            }
+    /// Check whether the tenant owning the database could own another
+    /// collection. The returned tenant lock must be held until the collection
+    /// is created, so the concurrent creations could not exceed the quota.
+    async fn check_tenant_collection_quota(
+        &self,
+        schema: &Schema,
+        db: &DatabaseDesc,
+    ) -> Result<Option<futures::lock::MutexGuard<'static, ()>>> {
+        if db.tenant_id == DEFAULT_TENANT_ID {
+            return Ok(None);
+        }
+        let tenant_guard =
+            ID_GEN_LOCKS.get(TENANT_LOCK_KEY).expect("tenant lock not found").lock().await;
+        let Some(tenant) = schema.list_tenant().await?.into_iter().find(|t| t.id == db.tenant_id)
+        else {
+            return Ok(None);
+        };
+        let databases = schema
+            .list_database()
+            .await?
+            .into_iter()
+            .filter(|d| d.tenant_id == tenant.id)
+            .map(|d| d.id)
+            .collect::<HashSet<_>>();
+        let num_collections =
+            schema.list_collection().await?.iter().filter(|c| databases.contains(&c.db)).count();
+        tenant::check_collection_quota(&tenant, num_collections)?;
+        Ok(Some(tenant_guard))
+    }
+}
+
+/// Check whether the key schema of a new collection is valid.
+fn check_key_schema(key_schema: &KeySchema) -> Result<()> {
+    match KeyEncoding::from_i32(key_schema.encoding) {
+        None => {
+            Err(Error::InvalidArgument(format!("invalid key encoding {}", key_schema.encoding)))
+        }
+        Some(KeyEncoding::FixedLength) if key_schema.length == 0 => {
+            Err(Error::InvalidArgument("the length of FIXED_LENGTH keys is required".into()))
         }
+        Some(KeyEncoding::FixedLength) => Ok(()),
+        Some(_) if key_schema.length != 0 => {
+            Err(Error::InvalidArgument("the key length is only allowed by FIXED_LENGTH".into()))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+fn trash_to_json(entry: &TrashEntry, retention_sec: u64) -> serde_json::Value {
+    let expire_at = entry.dropped_at + retention_sec;
+    if let Some(db) = entry.database.as_ref() {
+        serde_json::json!({
+            "type": "database",
+            "name": db.name,
+            "id": db.id,
+            "dropped_at": entry.dropped_at,
+            "expire_at": expire_at,
+        })
+    } else {
+        let co = entry.collection.clone().unwrap_or_default();
+        serde_json::json!({
+            "type": "collection",
+            "database": co.db,
+            "name": co.name,
+            "id": co.id,
+            "dropped_at": entry.dropped_at,
+            "expire_at": expire_at,
+        })
     }
 }
 
+/// Return the seconds since unix epoch.
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Return the max cluster version supported by all serving nodes. The nodes
+/// which don't report the binary version are treated as
+/// [`CLUSTER_VERSION_INITIAL`].
+fn supported_cluster_version(nodes: &[NodeDesc]) -> Option<u64> {
+    nodes
+        .iter()
+        .filter(|n| n.status != NodeStatus::Decommissioned as i32)
+        .map(|n| std::cmp::max(n.binary_version, CLUSTER_VERSION_INITIAL))
+        .min()
+}
+
 pub async fn fetch_root_replica(replica_table: &ReplicaRouteTable) -> Arc<Replica> {
     use futures::future::poll_fn;
     poll_fn(|ctx| match replica_table.current_root_replica(Some(ctx.waker().clone())) {
@@ -986,6 +3326,26 @@ pub async fn fetch_root_replica(replica_table: &ReplicaRouteTable) -> Arc<Replic
     .await
 }
 
+/// Transfer the leadership of the root group to another voter, so that the root
+/// is served by the other nodes while this node backs off. The single replica
+/// root group keeps the leadership.
+fn step_down(root_replica: &Replica) {
+    let replica_id = root_replica.replica_info().replica_id;
+    let Some(transferee) = root_replica
+        .descriptor()
+        .replicas
+        .iter()
+        .find(|r| r.id != replica_id && r.role == ReplicaRole::Voter as i32)
+        .map(|r| r.id)
+    else {
+        return;
+    };
+    info!("root transfer leadership from {replica_id} to {transferee}");
+    if let Err(err) = root_replica.raft_node().transfer_leader(transferee) {
+        warn!("root transfer leadership to {transferee}: {err:?}");
+    }
+}
+
 #[derive(Debug)]
 pub enum QueueTask {
     Heartbeat(HeartbeatTask),
@@ -1107,10 +3467,48 @@ pub struct NodeDelta {
     // TODO: qps
 }
 
+/// The apply lag of a replica, the distance between the applied index of the
+/// replica and the committed index of its group leader.
+#[derive(Clone, Debug)]
+pub struct ReplicaApplyLag {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub node_id: u64,
+    pub applied_index: u64,
+    pub committed_index: u64,
+    /// Since when the lag exceeds the threshold.
+    pub lagging_since: Option<Instant>,
+}
+
+impl ReplicaApplyLag {
+    #[inline]
+    pub fn lag(&self) -> u64 {
+        self.committed_index.saturating_sub(self.applied_index)
+    }
+}
+
+#[derive(Default)]
+struct ApplyLagStats {
+    group_committed: HashMap<u64 /* group */, u64>,
+    replicas: HashMap<u64 /* replica */, ReplicaApplyLag>,
+}
+
 #[derive(Default, Clone)]
 pub struct OngoingStats {
     sched_stats: Arc<Mutex<SchedStats>>,
     job_stats: Arc<Mutex<JobStats>>,
+    snapshot_stats: Arc<Mutex<HashMap<u64 /* node */, SnapshotStats>>>,
+    moving_shards: Arc<Mutex<HashMap<u64 /* node */, Vec<MovingShardStats>>>>,
+    node_stats: Arc<Mutex<HashMap<u64 /* node */, NodeStats>>>,
+    shard_stats: Arc<Mutex<HashMap<u64 /* shard */, ShardStats>>>,
+    apply_lag_stats: Arc<Mutex<ApplyLagStats>>,
+}
+
+/// The snapshots in transferring of a node, reported by heartbeats.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SnapshotStats {
+    pub sending: u64,
+    pub receiving: u64,
 }
 
 #[derive(Default)]
@@ -1159,6 +3557,111 @@ impl OngoingStats {
         rs
     }
 
+    pub fn update_snapshot_stats(&self, node: u64, stats: SnapshotStats) {
+        self.snapshot_stats.lock().unwrap().insert(node, stats);
+    }
+
+    pub fn get_snapshot_stats(&self, node: u64) -> SnapshotStats {
+        self.snapshot_stats.lock().unwrap().get(&node).cloned().unwrap_or_default()
+    }
+
+    /// Replace the moving shards led by the node, reported by heartbeats.
+    pub fn update_moving_shards(&self, node: u64, moving_shards: Vec<MovingShardStats>) {
+        let mut inner = self.moving_shards.lock().unwrap();
+        if moving_shards.is_empty() {
+            inner.remove(&node);
+        } else {
+            inner.insert(node, moving_shards);
+        }
+    }
+
+    pub fn get_moving_shards(&self, node: u64) -> Vec<MovingShardStats> {
+        self.moving_shards.lock().unwrap().get(&node).cloned().unwrap_or_default()
+    }
+
+    /// Replace the latest stats of the node, reported by heartbeats.
+    pub fn update_node_stats(&self, node: u64, stats: NodeStats) {
+        self.node_stats.lock().unwrap().insert(node, stats);
+    }
+
+    pub fn get_node_stats(&self, node: u64) -> Option<NodeStats> {
+        self.node_stats.lock().unwrap().get(&node).cloned()
+    }
+
+    /// Update the usage of shards, reported by the group leaders.
+    pub fn update_shard_stats(&self, group_stats: &[GroupStats]) {
+        let mut inner = self.shard_stats.lock().unwrap();
+        for ss in group_stats.iter().flat_map(|gs| gs.shard_stats.iter()) {
+            inner.insert(ss.shard_id, ss.clone());
+        }
+    }
+
+    pub fn get_shard_stats(&self, shard: u64) -> Option<ShardStats> {
+        self.shard_stats.lock().unwrap().get(&shard).cloned()
+    }
+
+    /// Replace the applied index of the replicas on the node, reported by
+    /// heartbeats, and compare it with the latest committed index reported by
+    /// the group leaders. A replica starts lagging once its lag exceeds the
+    /// threshold, 0 means the lag is never checked.
+    pub fn update_apply_lags(
+        &self,
+        node: u64,
+        threshold: u64,
+        group_stats: &[GroupStats],
+        replica_stats: &[ReplicaStats],
+    ) {
+        let mut inner = self.apply_lag_stats.lock().unwrap();
+        for gs in group_stats {
+            let committed = inner.group_committed.entry(gs.group_id).or_default();
+            *committed = (*committed).max(gs.committed_index);
+        }
+        let mut prev = HashMap::new();
+        inner.replicas.retain(|id, r| {
+            if r.node_id == node {
+                prev.insert(*id, r.lagging_since);
+            }
+            r.node_id != node
+        });
+        for rs in replica_stats {
+            let Some(committed_index) = inner.group_committed.get(&rs.group_id).cloned() else {
+                continue;
+            };
+            let mut lag = ReplicaApplyLag {
+                group_id: rs.group_id,
+                replica_id: rs.replica_id,
+                node_id: node,
+                applied_index: rs.applied_index,
+                committed_index,
+                lagging_since: None,
+            };
+            if threshold != 0 && lag.lag() > threshold {
+                let since = prev.get(&rs.replica_id).cloned().flatten();
+                lag.lagging_since = since.or_else(|| Some(Instant::now()));
+            }
+            inner.replicas.insert(rs.replica_id, lag);
+        }
+    }
+
+    /// Return the replicas lagging longer than `persist`.
+    pub fn get_lagging_replicas(&self, persist: Duration) -> Vec<ReplicaApplyLag> {
+        let inner = self.apply_lag_stats.lock().unwrap();
+        let mut replicas = inner
+            .replicas
+            .values()
+            .filter(|r| r.lagging_since.map(|since| since.elapsed() >= persist).unwrap_or_default())
+            .cloned()
+            .collect::<Vec<_>>();
+        replicas.sort_unstable_by_key(|r| r.replica_id);
+        replicas
+    }
+
+    /// Return the max apply lag of the reported replicas.
+    pub fn get_max_apply_lag(&self) -> u64 {
+        let inner = self.apply_lag_stats.lock().unwrap();
+        inner.replicas.values().map(ReplicaApplyLag::lag).max().unwrap_or_default()
+    }
+
     pub fn reset(&self) {
         {
             let mut inner = self.sched_stats.lock().unwrap();
@@ -1169,6 +3672,11 @@ impl OngoingStats {
             let mut inner = self.job_stats.lock().unwrap();
             inner.node_delta.clear();
         }
+        self.snapshot_stats.lock().unwrap().clear();
+        self.moving_shards.lock().unwrap().clear();
+        self.node_stats.lock().unwrap().clear();
+        self.shard_stats.lock().unwrap().clear();
+        *self.apply_lag_stats.lock().unwrap() = ApplyLagStats::default();
     }
 }
 
@@ -1243,20 +3751,243 @@ mod root_test {
     use crate::transport::TransportManager;
 
     async fn create_root_and_node(config: &Config, node_ident: &NodeIdent) -> (Root, Node) {
-        let engines = Engines::open(&config.root_dir, &config.db).unwrap();
+        let engines = Engines::open(&config.root_dir, &config.db, &config.raft).unwrap();
         let root_list =
             if config.init { vec![config.addr.clone()] } else { config.join_list.clone() };
-        let transport_manager = TransportManager::new(root_list, engines.state()).await;
-        let root = Root::new(transport_manager.clone(), node_ident, config.clone());
-        let node = Node::new(config.clone(), engines, transport_manager).await.unwrap();
+        let transport_manager = TransportManager::new(
+            root_list,
+            engines.state(),
+            None,
+            sekas_client::TokenInterceptor::default(),
+        )
+        .await;
+        let node = Node::new(config.clone(), engines, transport_manager.clone()).await.unwrap();
+        let root = Root::new(
+            transport_manager.clone(),
+            node_ident,
+            config.clone(),
+            node.key_manager(),
+            node.dynamic_config(),
+        );
         (root, node)
     }
 
+    #[test]
+    fn supported_cluster_version() {
+        use sekas_api::server::v1::{NodeDesc, NodeStatus};
+
+        use crate::constants::CLUSTER_VERSION_INITIAL;
+
+        let node = |binary_version: u64, status: NodeStatus| NodeDesc {
+            binary_version,
+            status: status as i32,
+            ..Default::default()
+        };
+        assert_eq!(super::supported_cluster_version(&[]), None);
+        let nodes = vec![node(3, NodeStatus::Active), node(2, NodeStatus::Cordoned)];
+        assert_eq!(super::supported_cluster_version(&nodes), Some(2));
+        let nodes = vec![node(3, NodeStatus::Active), node(1, NodeStatus::Decommissioned)];
+        assert_eq!(super::supported_cluster_version(&nodes), Some(3));
+        let nodes = vec![node(3, NodeStatus::Active), node(0, NodeStatus::Active)];
+        assert_eq!(super::supported_cluster_version(&nodes), Some(CLUSTER_VERSION_INITIAL));
+    }
+
+    #[test]
+    fn aggregate_collection_stats() {
+        use sekas_api::server::v1::{CollectionDesc, ShardStats};
+
+        use crate::RootConfig;
+
+        let co = CollectionDesc { id: 10, name: "co".to_owned(), ..Default::default() };
+        let shard_stats = |shard_id: u64, num_keys: u64, logical_bytes: u64| ShardStats {
+            shard_id,
+            collection_id: 10,
+            num_keys,
+            logical_bytes,
+            ..Default::default()
+        };
+        let shards = vec![
+            (1, 100, Some(shard_stats(100, 10, 1000))),
+            (2, 101, Some(shard_stats(101, 20, 100))),
+            (2, 102, None),
+        ];
+
+        let cfg = RootConfig::default();
+        let stats = super::aggregate_collection_stats(&cfg, "db", &co, &shards);
+        assert_eq!(stats.id, 10);
+        assert_eq!(stats.num_keys, 30);
+        assert_eq!(stats.logical_bytes, 1100);
+        assert_eq!(stats.shards.len(), 2);
+        assert_eq!(stats.unreported_shards, 1);
+        assert!(stats.shards.iter().all(|s| !s.split_candidate));
+
+        let cfg = RootConfig { shard_split_threshold_bytes: 500, ..Default::default() };
+        let stats = super::aggregate_collection_stats(&cfg, "db", &co, &shards);
+        let candidates = stats.shards.iter().filter(|s| s.split_candidate).map(|s| s.id);
+        assert_eq!(candidates.collect::<Vec<_>>(), vec![100]);
+
+        let cfg = RootConfig { shard_split_threshold_keys: 15, ..Default::default() };
+        let stats = super::aggregate_collection_stats(&cfg, "db", &co, &shards);
+        let candidates = stats.shards.iter().filter(|s| s.split_candidate).map(|s| s.id);
+        assert_eq!(candidates.collect::<Vec<_>>(), vec![101]);
+    }
+
+    #[test]
+    fn aggregate_hot_keys() {
+        use sekas_api::server::v1::{HotKey, ShardStats};
+
+        let hot_key =
+            |key: &[u8], reads: u64, writes: u64| HotKey { key: key.to_owned(), reads, writes };
+        let shard_stats = |shard_id: u64, hot_keys: Vec<HotKey>| ShardStats {
+            shard_id,
+            collection_id: 10,
+            hot_keys,
+            ..Default::default()
+        };
+        let shards = vec![
+            (1, shard_stats(100, vec![hot_key(b"a", 10, 0), hot_key(b"b", 1, 1)])),
+            (2, shard_stats(101, vec![hot_key(b"\x01", 5, 20)])),
+            (2, shard_stats(102, vec![])),
+        ];
+        let hot_keys = super::aggregate_hot_keys(shards, 2);
+        let keys = hot_keys.iter().map(|k| (k.key.as_str(), k.group_id)).collect::<Vec<_>>();
+        assert_eq!(keys, vec![("\\x01", 2), ("a", 1)]);
+        assert_eq!(hot_keys[0].shard_id, 101);
+        assert_eq!(hot_keys[0].collection_id, 10);
+    }
+
+    #[test]
+    fn plan_scatter_shards() {
+        use sekas_api::server::v1::{GroupDesc, ShardDesc};
+
+        let group =
+            |id: u64, shards: Vec<ShardDesc>| GroupDesc { id, shards, ..Default::default() };
+        let shards = (100..106).map(|id| ShardDesc::whole(id, 10)).collect::<Vec<_>>();
+        let mut shards_1 = shards.clone();
+        shards_1.push(ShardDesc::whole(200, 20));
+        let groups = vec![group(1, shards_1), group(2, vec![]), group(3, vec![]), group(4, vec![])];
+        let leader_nodes = HashMap::from([(1, 1), (2, 1), (3, 2), (4, 3)]);
+
+        let moves = super::plan_scatter(10, &groups, &leader_nodes);
+        let moves =
+            moves.iter().map(|mv| (mv.shard_id, mv.src_group, mv.dest_group)).collect::<Vec<_>>();
+        // The groups led by other nodes are preferred.
+        assert_eq!(moves, vec![(105, 1, 3), (104, 1, 4), (103, 1, 2), (102, 1, 3)]);
+
+        // The balanced collection is kept as is.
+        let groups = vec![
+            group(1, shards[..2].to_vec()),
+            group(2, shards[2..4].to_vec()),
+            group(3, shards[4..].to_vec()),
+        ];
+        assert!(super::plan_scatter(10, &groups, &leader_nodes).is_empty());
+        assert!(super::plan_scatter(10, &[], &leader_nodes).is_empty());
+    }
+
+    #[test]
+    fn track_replica_apply_lags() {
+        use std::time::Duration;
+
+        use sekas_api::server::v1::{GroupStats, ReplicaStats};
+
+        use super::OngoingStats;
+
+        let group_stats = |group_id: u64, committed_index: u64| GroupStats {
+            group_id,
+            committed_index,
+            ..Default::default()
+        };
+        let replica_stats = |replica_id: u64, group_id: u64, applied_index: u64| ReplicaStats {
+            replica_id,
+            group_id,
+            applied_index,
+            ..Default::default()
+        };
+        let lagging = |stats: &OngoingStats| {
+            stats
+                .get_lagging_replicas(Duration::ZERO)
+                .iter()
+                .map(|r| (r.replica_id, r.lag()))
+                .collect::<Vec<_>>()
+        };
+
+        let stats = OngoingStats::default();
+        // The leader of group 1 is on node 1.
+        stats.update_apply_lags(
+            1,
+            100,
+            &[group_stats(1, 1000)],
+            &[replica_stats(11, 1, 1000), replica_stats(21, 2, 10)],
+        );
+        // The committed index of group 2 is unknown yet.
+        assert!(lagging(&stats).is_empty());
+
+        stats.update_apply_lags(
+            2,
+            100,
+            &[group_stats(2, 50)],
+            &[replica_stats(12, 1, 800), replica_stats(22, 2, 50)],
+        );
+        assert_eq!(lagging(&stats), vec![(12, 200)]);
+        assert_eq!(stats.get_max_apply_lag(), 200);
+        assert!(stats.get_lagging_replicas(Duration::from_secs(3600)).is_empty());
+
+        // The lagging replica catches up.
+        stats.update_apply_lags(2, 100, &[], &[replica_stats(12, 1, 950)]);
+        assert!(lagging(&stats).is_empty());
+
+        // The lag is never checked if the threshold is 0.
+        stats.update_apply_lags(2, 0, &[], &[replica_stats(12, 1, 0)]);
+        assert!(lagging(&stats).is_empty());
+        assert_eq!(stats.get_max_apply_lag(), 1000);
+    }
+
+    #[test]
+    fn describe_collection_shards() {
+        use sekas_api::server::v1::{
+            CollectionDesc, RangePartition, ShardDesc, ShardDescription, ShardStats,
+        };
+
+        let co = CollectionDesc { id: 10, name: "co".to_owned(), ..Default::default() };
+        let shard = |id: u64, start: &[u8], end: &[u8]| ShardDescription {
+            shard: Some(ShardDesc {
+                id,
+                collection_id: 10,
+                range: Some(RangePartition { start: start.to_vec(), end: end.to_vec() }),
+                ..Default::default()
+            }),
+            group_id: id,
+            ..Default::default()
+        };
+        let shard_stats = |shard_id: u64, num_keys: u64, logical_bytes: u64| ShardStats {
+            shard_id,
+            collection_id: 10,
+            num_keys,
+            logical_bytes,
+            ..Default::default()
+        };
+        let shards = vec![
+            (shard(100, b"m", b""), Some(shard_stats(100, 10, 1000))),
+            (shard(101, b"", b"g"), Some(shard_stats(101, 20, 100))),
+            (shard(102, b"g", b"m"), None),
+        ];
+
+        let desc = super::describe_collection(co, shards);
+        assert_eq!(desc.collection.as_ref().map(|c| c.id), Some(10));
+        assert_eq!(desc.num_keys, 30);
+        assert_eq!(desc.logical_bytes, 1100);
+        let ids = desc.shards.iter().map(|s| s.shard.as_ref().unwrap().id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![101, 102, 100]);
+        let reported = desc.shards.iter().map(|s| s.reported).collect::<Vec<_>>();
+        assert_eq!(reported, vec![true, false, true]);
+        assert_eq!(desc.shards[2].num_keys, 10);
+    }
+
     #[sekas_macro::test]
     async fn boostrap_root() {
         let tmp_dir = TempDir::new(fn_name!()).unwrap();
         let config = Config { root_dir: tmp_dir.path().to_owned(), ..Default::default() };
-        let ident = NodeIdent { cluster_id: vec![], node_id: 1 };
+        let ident = NodeIdent { cluster_id: vec![], node_id: 1, incarnation: 0 };
 
         let (root, node) = create_root_and_node(&config, &ident).await;
         bootstrap_cluster(&node, "0.0.0.0:8888").await.unwrap();
@@ -1269,7 +4000,7 @@ mod root_test {
     async fn bootstrap_pending_root_replica() {
         let tmp_dir = TempDir::new(fn_name!()).unwrap();
         let config = Config { root_dir: tmp_dir.path().to_owned(), ..Default::default() };
-        let ident = NodeIdent { cluster_id: vec![], node_id: 1 };
+        let ident = NodeIdent { cluster_id: vec![], node_id: 1, incarnation: 0 };
 
         let (root, node) = create_root_and_node(&config, &ident).await;
         node.bootstrap(&ident).await.unwrap();
@@ -1285,12 +4016,15 @@ mod root_test {
     #[sekas_macro::test]
     async fn watch_hub() {
         let tmp_dir = TempDir::new(fn_name!()).unwrap();
-        let ident = NodeIdent { cluster_id: vec![], node_id: 1 };
+        let ident = NodeIdent { cluster_id: vec![], node_id: 1, incarnation: 0 };
         let config = Config { root_dir: tmp_dir.path().to_owned(), ..Default::default() };
         let (root, _node) = create_root_and_node(&config, &ident).await;
         let hub = root.watcher_hub();
-        let _create_db1_event =
-            Some(update_event::Event::Database(DatabaseDesc { id: 1, name: "db1".into() }));
+        let _create_db1_event = Some(update_event::Event::Database(DatabaseDesc {
+            id: 1,
+            name: "db1".into(),
+            tenant_id: 0,
+        }));
         let mut w = {
             let (w, mut initializer) = hub.create_watcher().await;
             initializer.set_init_resp(vec![UpdateEvent { event: _create_db1_event }], vec![]);
@@ -1304,8 +4038,11 @@ mod root_test {
             w
         };
 
-        let _create_db2_event =
-            Some(update_event::Event::Database(DatabaseDesc { id: 2, name: "db2".into() }));
+        let _create_db2_event = Some(update_event::Event::Database(DatabaseDesc {
+            id: 2,
+            name: "db2".into(),
+            tenant_id: 0,
+        }));
         hub.notify_updates(vec![UpdateEvent { event: _create_db2_event }]).await;
         let resp2 = w.next().await.unwrap().unwrap();
         assert!(matches!(&resp2.updates[0].event, _create_db2_event));
@@ -1346,6 +4083,21 @@ pub mod diagnosis {
         pub replicas: Vec<NodeReplica>,
         pub leaders: Vec<NodeReplica>,
         pub status: i32,
+        pub sending_snapshots: u64,
+        pub receiving_snapshots: u64,
+        /// The shards being moved into the groups led by this node.
+        pub moving_shards: Vec<MovingShard>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct MovingShard {
+        pub shard: u64,
+        pub src_group: u64,
+        pub dest_group: u64,
+        pub moved_keys: u64,
+        pub moved_bytes: u64,
+        pub total_keys: u64,
+        pub percent: f32,
     }
 
     #[derive(Serialize, Deserialize, Clone)]
@@ -1379,4 +4131,171 @@ pub mod diagnosis {
         pub id: u64,
         pub range: String,
     }
+
+    /// The topology and health of the cluster, for the external dashboards.
+    #[derive(Serialize, Deserialize)]
+    pub struct Cluster {
+        pub nodes: Vec<NodeUsage>,
+        pub groups: Vec<GroupHealth>,
+        pub collections: Vec<CollectionShards>,
+        pub lagging_replicas: Vec<LaggingReplica>,
+        pub scheduler: SchedulerState,
+    }
+
+    /// The capacity and usage of a node, the stats are reported by heartbeats.
+    #[derive(Serialize, Deserialize)]
+    pub struct NodeUsage {
+        pub id: u64,
+        pub addr: String,
+        pub status: i32,
+        pub alive: bool,
+        pub cpu_nums: f64,
+        pub load_average: f64,
+        pub total_space: u64,
+        pub available_space: u64,
+        pub replicas: u64,
+        pub leaders: u64,
+        pub block_cache_usage: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct GroupHealth {
+        pub id: u64,
+        pub epoch: u64,
+        pub voters: usize,
+        pub learners: usize,
+        /// The node of the leader replica.
+        pub leader: Option<u64>,
+        /// The alive voters are less than the expected replicas of a group.
+        pub under_replicated: bool,
+        pub no_leader: bool,
+        /// The number of shards moving into or out of this group.
+        pub moving_shards: usize,
+    }
+
+    /// The replica whose applied index falls behind the committed index of the
+    /// group leader persistently.
+    #[derive(Serialize, Deserialize)]
+    pub struct LaggingReplica {
+        pub group_id: u64,
+        pub replica_id: u64,
+        pub node_id: u64,
+        pub applied_index: u64,
+        pub committed_index: u64,
+        pub lag: u64,
+        /// How long the replica has been lagging.
+        pub lagging_sec: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CollectionShards {
+        pub id: u64,
+        pub database: String,
+        pub collection: String,
+        pub shards: usize,
+    }
+
+    #[derive(Default, Serialize, Deserialize)]
+    pub struct CollectionStats {
+        pub id: u64,
+        pub database: String,
+        pub collection: String,
+        pub num_keys: u64,
+        pub logical_bytes: u64,
+        pub shards: Vec<ShardUsage>,
+        /// The number of shards whose usage is not reported yet, eg the
+        /// leader is just elected.
+        pub unreported_shards: usize,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ShardUsage {
+        pub id: u64,
+        pub group_id: u64,
+        pub num_keys: u64,
+        pub logical_bytes: u64,
+        /// Whether the shard exceeds the split thresholds.
+        pub split_candidate: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct HotKey {
+        pub collection_id: u64,
+        pub shard_id: u64,
+        pub group_id: u64,
+        /// The key escaped as ASCII.
+        pub key: String,
+        /// The estimated accesses in the recent windows.
+        pub reads: u64,
+        pub writes: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ShardMove {
+        pub shard_id: u64,
+        pub src_group: u64,
+        pub dest_group: u64,
+        /// The number of times the moving is issued.
+        pub attempts: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ScatterMove {
+        pub shard_id: u64,
+        pub src_group: u64,
+        pub dest_group: u64,
+    }
+
+    #[derive(Default, Serialize, Deserialize)]
+    pub struct TenantStats {
+        pub id: u64,
+        /// The name of the tenant, the default tenant is named as an empty
+        /// string.
+        pub name: String,
+        /// The quota of the tenant, zero means unlimited.
+        pub max_databases: u64,
+        pub max_collections: u64,
+        pub num_databases: u64,
+        pub num_collections: u64,
+        /// The usage is reported by the group leaders so it might be stale.
+        pub num_keys: u64,
+        pub logical_bytes: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct SchedulerState {
+        /// The number of reconcile tasks waiting to be advanced.
+        pub pending_tasks: usize,
+        pub ongoing_jobs: usize,
+        pub balanced: bool,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_runtime::time::advance;
+
+    use super::*;
+
+    #[sekas_macro::test(mock_time)]
+    async fn heartbeat_queue_schedule_by_mock_clock() {
+        let queue = HeartbeatQueue::default();
+        queue.enable(true).await;
+
+        let now = Instant::now();
+        let tasks = vec![HeartbeatTask { node_id: 1 }, HeartbeatTask { node_id: 2 }];
+        queue.try_schedule(tasks, now + Duration::from_secs(10)).await;
+        assert!(queue.try_poll().await.is_empty());
+
+        // The earlier schedule of a node overrides the later one.
+        queue.try_schedule(vec![HeartbeatTask { node_id: 2 }], now + Duration::from_secs(5)).await;
+        advance(Duration::from_secs(5)).await;
+        let nodes = queue.try_poll().await.into_iter().map(|t| t.node_id).collect::<Vec<_>>();
+        assert_eq!(nodes, vec![2]);
+
+        advance(Duration::from_secs(5)).await;
+        let nodes = queue.try_poll().await.into_iter().map(|t| t.node_id).collect::<Vec<_>>();
+        assert_eq!(nodes, vec![1]);
+        assert!(queue.try_poll().await.is_empty());
+    }
 }
@@ -13,22 +13,48 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::LinkedList;
+use std::collections::{HashSet, LinkedList};
 use std::sync::Arc;
 
 use log::{error, info, warn};
 use prometheus::HistogramTimer;
 use sekas_api::server::v1::*;
+use sekas_runtime::time::Instant;
 use tokio::sync::Mutex;
-use tokio::time::Instant;
 
 use super::allocator::*;
+use super::hot_shard::{self, HotShard};
 use super::{metrics, *};
 use crate::constants::ROOT_GROUP_ID;
 use crate::serverpb::v1::reconcile_task::Task;
 use crate::serverpb::v1::*;
 use crate::Result;
 
+/// The prefix of the keys of the persisted reconcile tasks in the meta
+/// collection.
+pub(super) const RECONCILE_TASK_KEY_PREFIX: &[u8] = b"reconcile_task_";
+
+/// Return the key of the reconcile task in the meta collection, or `None` if
+/// the task is not persisted.
+///
+/// Only the tasks requested by operators are persisted, so that they survive
+/// the root leader changes. The tasks for balancing are recomputed by the new
+/// leader. A task is keyed by its target, so that the same request replaces
+/// the former one instead of being queued twice.
+pub(super) fn reconcile_task_key(task: &ReconcileTask) -> Option<Vec<u8>> {
+    let (kind, id) = match task.task.as_ref()? {
+        Task::ShedLeader(task) => (b'l', task.node_id),
+        Task::ShedRoot(task) => (b'r', task.node_id),
+        Task::MoveGroupReplicas(task) => (b'm', task.group),
+        _ => return None,
+    };
+    let mut buf = Vec::with_capacity(RECONCILE_TASK_KEY_PREFIX.len() + 1 + 8);
+    buf.extend_from_slice(RECONCILE_TASK_KEY_PREFIX);
+    buf.push(kind);
+    buf.extend_from_slice(&id.to_be_bytes());
+    Some(buf)
+}
+
 pub struct ReconcileScheduler {
     ctx: ScheduleContext,
     tasks: Mutex<LinkedList<ReconcileTask>>,
@@ -40,6 +66,7 @@ pub struct ScheduleContext {
     heartbeat_queue: Arc<HeartbeatQueue>,
     ongoing_stats: Arc<OngoingStats>,
     jobs: Arc<Jobs>,
+    decisions: Arc<DecisionLog>,
     cfg: RootConfig,
 }
 
@@ -54,22 +81,57 @@ impl ReconcileScheduler {
             let _step_timer = metrics::RECONCILE_STEP_DURATION_SECONDS.start_timer();
             self.advance_tasks().await;
         }
-        Duration::from_secs(self.ctx.cfg.schedule_interval_sec)
+        self.ctx.shared.dynamic_config.schedule_interval()
     }
 
     pub async fn wait_one_heartbeat_tick(&self) {
         self.ctx.heartbeat_queue.wait_one_heartbeat_tick().await
     }
 
-    pub async fn setup_task(&self, task: ReconcileTask) {
+    pub async fn setup_task(&self, task: ReconcileTask) -> Result<()> {
         let mut tasks = self.tasks.lock().await;
+        if let Some(key) = reconcile_task_key(&task) {
+            self.ctx.shared.schema()?.put_reconcile_task(&key, &task).await?;
+            let mut cursor = tasks.cursor_front_mut();
+            while let Some(current) = cursor.current() {
+                if reconcile_task_key(current).as_ref() == Some(&key) {
+                    cursor.remove_current();
+                } else {
+                    cursor.move_next();
+                }
+            }
+        }
         tasks.push_back(task.to_owned());
         info!("setup new reconcile task. len={}, task={:?}", tasks.len(), task);
+        Ok(())
+    }
+
+    /// Reload the persisted reconcile tasks, it should be called after the
+    /// root leader is stepped up.
+    pub async fn on_step_leader(&self) -> Result<()> {
+        let persisted = self.ctx.shared.schema()?.list_reconcile_tasks().await?;
+        let mut tasks = self.tasks.lock().await;
+        let mut cursor = tasks.cursor_front_mut();
+        while let Some(current) = cursor.current() {
+            if reconcile_task_key(current).is_some() {
+                cursor.remove_current();
+            } else {
+                cursor.move_next();
+            }
+        }
+        info!("recover {} persisted reconcile tasks", persisted.len());
+        tasks.extend(persisted);
+        Ok(())
     }
 
     async fn is_empty(&self) -> bool {
         self.tasks.lock().await.is_empty()
     }
+
+    /// Return the number of reconcile tasks waiting to be advanced.
+    pub async fn num_pending_tasks(&self) -> usize {
+        self.tasks.lock().await.len()
+    }
 }
 
 impl ReconcileScheduler {
@@ -96,6 +158,11 @@ impl ReconcileScheduler {
         let group_action = self.ctx.alloc.compute_group_action().await?;
         if let GroupAction::Add(cnt) = group_action {
             metrics::RECONCILE_ALREADY_BALANCED_INFO.cluster_groups.set(0);
+            self.ctx.decisions.record(
+                "create group",
+                format!("create {cnt} groups"),
+                format!("the number of groups is {cnt} less than the desired"),
+            );
             for _ in 0..cnt {
                 self.ctx
                     .jobs
@@ -118,13 +185,25 @@ impl ReconcileScheduler {
 
         let ractions = self.comput_replica_role_action().await?;
         let sactions = self.ctx.alloc.compute_shard_action().await?;
-        if ractions.is_empty() && sactions.is_empty() {
+        let splits = self.compute_hot_shard_splits().await?;
+        if ractions.is_empty() && sactions.is_empty() && splits.is_empty() {
             return Ok(!self.is_empty().await);
         }
 
         for action in ractions {
             match action {
                 ReplicaRoleAction::Replica(ReplicaAction::Migrate(action)) => {
+                    self.ctx.decisions.record(
+                        "reallocate replica",
+                        format!(
+                            "move replica {} of group {} from node {} to node {}",
+                            action.source_replica,
+                            action.group,
+                            action.source_node,
+                            action.target_node.id
+                        ),
+                        action.reason,
+                    );
                     self.setup_task(ReconcileTask {
                         task: Some(reconcile_task::Task::ReallocateReplica(
                             ReallocateReplicaTask {
@@ -136,9 +215,22 @@ impl ReconcileScheduler {
                             },
                         )),
                     })
-                    .await;
+                    .await?;
                 }
                 ReplicaRoleAction::Leader(LeaderAction::Shed(action)) => {
+                    self.ctx.decisions.record(
+                        "transfer leader",
+                        format!(
+                            "transfer leader of group {} from replica {} on node {} to replica {} \
+                             on node {}",
+                            action.group,
+                            action.src_replica,
+                            action.src_node,
+                            action.target_replica,
+                            action.target_node
+                        ),
+                        action.reason,
+                    );
                     self.setup_task(ReconcileTask {
                         task: Some(reconcile_task::Task::TransferGroupLeader(
                             TransferGroupLeaderTask {
@@ -149,7 +241,7 @@ impl ReconcileScheduler {
                             },
                         )),
                     })
-                    .await;
+                    .await?;
                 }
                 _ => {}
             }
@@ -157,6 +249,14 @@ impl ReconcileScheduler {
 
         for action in sactions {
             let ShardAction::Migrate(action) = action;
+            self.ctx.decisions.record(
+                "migrate shard",
+                format!(
+                    "move shard {} from group {} to group {}",
+                    action.shard, action.source_group, action.target_group
+                ),
+                action.reason,
+            );
             self.setup_task(ReconcileTask {
                 task: Some(reconcile_task::Task::MigrateShard(MigrateShardTask {
                     shard: action.shard,
@@ -164,12 +264,100 @@ impl ReconcileScheduler {
                     dest_group: action.target_group,
                 })),
             })
-            .await;
+            .await?;
+        }
+
+        for (hot_shard, task) in splits {
+            let moving = if task.dest_group != 0 {
+                format!(" and move the new shard to group {}", task.dest_group)
+            } else {
+                String::default()
+            };
+            self.ctx.decisions.record(
+                "split hot shard",
+                format!(
+                    "split shard {} of group {} at key {} into shard {}{moving}",
+                    task.shard,
+                    task.group,
+                    task.split_key.escape_ascii(),
+                    task.new_shard
+                ),
+                format!(
+                    "the shard takes {} of the {} sampled accesses of the group",
+                    hot_shard.accesses, hot_shard.group_accesses
+                ),
+            );
+            self.setup_task(ReconcileTask { task: Some(Task::SplitHotShard(task)) }).await?;
         }
 
         Ok(!self.is_empty().await)
     }
 
+    /// Compute the tasks to split the hot shards, at most one shard of a group
+    /// is split at a time.
+    async fn compute_hot_shard_splits(&self) -> Result<Vec<(HotShard, SplitHotShardTask)>> {
+        let cfg = &self.ctx.cfg;
+        if cfg.hot_shard_split_min_accesses == 0 {
+            return Ok(vec![]);
+        }
+
+        let schema = self.ctx.shared.schema()?;
+        let groups = schema.list_group().await?;
+        let hot_shards = hot_shard::find_hot_shards(cfg, &groups, |shard_id| {
+            self.ctx.ongoing_stats.get_shard_stats(shard_id)
+        });
+        if hot_shards.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut splitting_groups = self
+            .tasks
+            .lock()
+            .await
+            .iter()
+            .filter_map(|task| match task.task.as_ref() {
+                Some(Task::SplitHotShard(task)) => Some(task.group),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        // The new shards are not moved to the draining groups.
+        let draining = schema.draining_groups().await?;
+        let dest_groups =
+            groups.iter().filter(|g| !draining.contains(&g.id)).cloned().collect::<Vec<_>>();
+        let mut splits = vec![];
+        for hot_shard in hot_shards {
+            if !splitting_groups.insert(hot_shard.group_id) {
+                continue;
+            }
+            let Some(src) = groups.iter().find(|g| g.id == hot_shard.group_id) else {
+                continue;
+            };
+            // The constrained shards are kept in place, the constraints are honored by the
+            // balancing of the shard count. The clone sources are kept in place too, since
+            // the cloned shards read the data of them.
+            let constrained = src.shards.iter().any(|s| {
+                s.id == hot_shard.shard_id
+                    && (!s.constraints.is_empty() || sekas_schema::shard::is_clone_source(src, s))
+            });
+            let dest_group = if constrained {
+                None
+            } else {
+                hot_shard::pick_dest_group(src, &dest_groups, |group_id| {
+                    self.ctx.find_leader_node(group_id).ok().flatten()
+                })
+            };
+            let task = SplitHotShardTask {
+                group: hot_shard.group_id,
+                shard: hot_shard.shard_id,
+                split_key: hot_shard.split_key.clone(),
+                new_shard: schema.next_shard_id().await?,
+                dest_group: dest_group.unwrap_or_default(),
+            };
+            splits.push((hot_shard, task));
+        }
+        Ok(splits)
+    }
+
     pub async fn comput_replica_role_action(&self) -> Result<Vec<ReplicaRoleAction>> {
         let mut actions = Vec::new();
         let replica_actions = self.ctx.alloc.compute_replica_action().await?;
@@ -202,9 +390,16 @@ impl ReconcileScheduler {
         let mut cursor = task.cursor_front_mut();
         while let Some(task) = cursor.current() {
             let _timer = Self::record_exec(task);
+            let key = reconcile_task_key(task);
+            let origin = key.as_ref().map(|_| task.clone());
             let rs = self.ctx.handle_task(task).await;
             match rs {
                 Ok((true /* ack */, immediately_next)) => {
+                    if let Some(key) = &key {
+                        if let Err(err) = self.delete_persisted_task(key).await {
+                            warn!("delete persisted reconcile task: {err:?}, task={task:?}");
+                        }
+                    }
                     cursor.remove_current();
                     if !immediately_next {
                         nowait_next = false
@@ -212,6 +407,14 @@ impl ReconcileScheduler {
                 }
                 _ => {
                     Self::record_retry(task);
+                    // The progress of the persisted task is saved, if it changes.
+                    if let (Some(key), Some(origin)) = (&key, &origin) {
+                        if origin != task {
+                            if let Err(err) = self.persist_task(key, task).await {
+                                warn!("persist reconcile task: {err:?}, task={task:?}");
+                            }
+                        }
+                    }
                     // ack == false or meet error, skip current task and retry later.
                     cursor.move_next();
                 }
@@ -220,6 +423,14 @@ impl ReconcileScheduler {
         nowait_next
     }
 
+    async fn persist_task(&self, key: &[u8], task: &ReconcileTask) -> Result<()> {
+        self.ctx.shared.schema()?.put_reconcile_task(key, task).await
+    }
+
+    async fn delete_persisted_task(&self, key: &[u8]) -> Result<()> {
+        self.ctx.shared.schema()?.delete_reconcile_task(key).await
+    }
+
     fn record_exec(task: &mut ReconcileTask) -> HistogramTimer {
         match task.task.as_ref().unwrap() {
             Task::ReallocateReplica(_) => {
@@ -242,6 +453,14 @@ impl ReconcileScheduler {
                 metrics::RECONCILE_HANDLE_TASK_TOTAL.shed_root_leader.inc();
                 metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS.shed_root_leader.start_timer()
             }
+            Task::MoveGroupReplicas(_) => {
+                metrics::RECONCILE_HANDLE_TASK_TOTAL.move_group_replicas.inc();
+                metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS.move_group_replicas.start_timer()
+            }
+            Task::SplitHotShard(_) => {
+                metrics::RECONCILE_HANDLE_TASK_TOTAL.split_hot_shard.inc();
+                metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS.split_hot_shard.start_timer()
+            }
         }
     }
 
@@ -256,6 +475,10 @@ impl ReconcileScheduler {
             }
             Task::ShedLeader(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.shed_group_leaders.inc(),
             Task::ShedRoot(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.shed_root_leader.inc(),
+            Task::MoveGroupReplicas(_) => {
+                metrics::RECONCILE_RETRY_TASK_TOTAL.move_group_replicas.inc()
+            }
+            Task::SplitHotShard(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.split_hot_shard.inc(),
         }
     }
 }
@@ -267,9 +490,10 @@ impl ScheduleContext {
         heartbeat_queue: Arc<HeartbeatQueue>,
         ongoing_stats: Arc<OngoingStats>,
         jobs: Arc<Jobs>,
+        decisions: Arc<DecisionLog>,
         cfg: RootConfig,
     ) -> Self {
-        Self { shared, alloc, heartbeat_queue, ongoing_stats, jobs, cfg }
+        Self { shared, alloc, heartbeat_queue, ongoing_stats, jobs, decisions, cfg }
     }
 
     pub async fn handle_task(
@@ -290,6 +514,10 @@ impl ScheduleContext {
             }
             Task::ShedLeader(shed_leader) => self.handle_shed_leader(shed_leader).await,
             Task::ShedRoot(shed_root) => self.handle_shed_root(shed_root).await,
+            Task::MoveGroupReplicas(move_replicas) => {
+                self.handle_move_group_replicas(move_replicas).await
+            }
+            Task::SplitHotShard(split) => self.handle_split_hot_shard(split).await,
         }
     }
 
@@ -345,14 +573,14 @@ impl ScheduleContext {
         );
         let next_replica = schema.next_replica_id().await?;
         match self
-            .try_move_replica(
+            .try_move_replicas(
                 group,
-                ReplicaDesc {
+                vec![ReplicaDesc {
                     id: next_replica,
                     node_id: task.dest_node.as_ref().unwrap().id,
                     role: ReplicaRole::Voter as i32,
-                },
-                src_replica.unwrap().to_owned(),
+                }],
+                vec![src_replica.unwrap().to_owned()],
             )
             .await
         {
@@ -380,6 +608,75 @@ impl ScheduleContext {
         }
     }
 
+    async fn handle_move_group_replicas(
+        &self,
+        task: &mut MoveGroupReplicasTask,
+    ) -> Result<(
+        bool, // ack current
+        bool, // immediately step next tick
+    )> {
+        let schema = self.shared.schema()?;
+        let group = task.group;
+        let Some(group_desc) = schema.get_group(group).await? else {
+            warn!("group not found abort move group replicas task. group={group}");
+            return Ok((true, false));
+        };
+
+        let mut outgoing_replicas = Vec::with_capacity(task.src_replicas.len());
+        for replica_id in &task.src_replicas {
+            let Some(replica) = group_desc.replicas.iter().find(|r| r.id == *replica_id) else {
+                warn!("source replica not found abort move group replicas task. group={group}, replica={replica_id}");
+                return Ok((true, false));
+            };
+            outgoing_replicas.push(replica.to_owned());
+        }
+
+        // The leader must be kept by one of the remaining replicas.
+        let leader_state = self.shared.transport_manager.find_group(group)?.leader_state;
+        if let Some((leader_replica, _)) = leader_state {
+            if task.src_replicas.contains(&leader_replica) {
+                if let Some(target) =
+                    group_desc.replicas.iter().find(|r| !task.src_replicas.contains(&r.id))
+                {
+                    info!(
+                        "transfer leader before moving group replicas. group={group}, target={}",
+                        target.id
+                    );
+                    self.try_transfer_leader(group, target.id).await?;
+                    return Ok((false, false));
+                }
+            }
+        }
+
+        let mut incoming_replicas = Vec::with_capacity(task.dest_nodes.len());
+        for node_id in &task.dest_nodes {
+            incoming_replicas.push(ReplicaDesc {
+                id: schema.next_replica_id().await?,
+                node_id: *node_id,
+                role: ReplicaRole::Voter as i32,
+            });
+        }
+
+        info!(
+            "start move group replicas. group={group}, src_replicas={:?}, dest_nodes={:?}",
+            task.src_replicas, task.dest_nodes
+        );
+        match self.try_move_replicas(group, incoming_replicas, outgoing_replicas).await {
+            Ok(schedule_state) => {
+                self.ongoing_stats.handle_update(&[schedule_state], None);
+                Ok((true, false))
+            }
+            Err(crate::Error::AlreadyExists(_)) | Err(crate::Error::EpochNotMatch(_)) => {
+                warn!("move group replicas task aborted due to replica already changed. group={group}");
+                Ok((true, false))
+            }
+            Err(err) => {
+                warn!("move group replicas meet error and retry later: {err:?}. group={group}");
+                Err(err)
+            }
+        }
+    }
+
     async fn handle_migrate_shard(
         &self,
         task: &mut MigrateShardTask,
@@ -411,6 +708,95 @@ impl ScheduleContext {
         }
     }
 
+    async fn handle_split_hot_shard(
+        &self,
+        task: &mut SplitHotShardTask,
+    ) -> Result<(
+        bool, // ack current
+        bool, // immediately step next tick
+    )> {
+        let schema = self.shared.schema()?;
+        let Some(group) = schema.get_group(task.group).await? else {
+            warn!("group not found, abort split hot shard task. group={}", task.group);
+            return Ok((true, false));
+        };
+
+        if !group.shards.iter().any(|s| s.id == task.new_shard) {
+            if !group.shards.iter().any(|s| s.id == task.shard) {
+                warn!(
+                    "shard has been moved out, abort split hot shard task. group={}, shard={}",
+                    task.group, task.shard
+                );
+                return Ok((true, false));
+            }
+            let mut group_client = self.shared.transport_manager.lazy_group_client(task.group);
+            match group_client.split_shard(task.shard, &task.split_key, task.new_shard).await {
+                Ok(()) => {}
+                Err(sekas_client::Error::InvalidArgument(msg)) => {
+                    warn!(
+                        "abort split hot shard. group={}, shard={}, reason={msg}",
+                        task.group, task.shard
+                    );
+                    return Ok((true, false));
+                }
+                Err(err) => {
+                    warn!(
+                        "split hot shard fail, retry later: {err:?}. group={}, shard={}",
+                        task.group, task.shard
+                    );
+                    return Err(err.into());
+                }
+            }
+            info!(
+                "split hot shard. group={}, shard={}, split_key={}, new_shard={}",
+                task.group,
+                task.shard,
+                task.split_key.escape_ascii(),
+                task.new_shard
+            );
+            if task.dest_group == 0 {
+                return Ok((true, false));
+            }
+            // Move the new shard once it is reported by the heartbeat.
+            if let Some(node_id) = self.find_leader_node(task.group)? {
+                self.heartbeat_queue
+                    .try_schedule(vec![HeartbeatTask { node_id }], Instant::now())
+                    .await;
+            }
+            return Ok((false, false));
+        }
+
+        if task.dest_group == 0 {
+            return Ok((true, false));
+        }
+        // The shard might become a clone source after the task is setup.
+        let new_shard = group.shards.iter().find(|s| s.id == task.new_shard).expect("checked");
+        if sekas_schema::shard::is_clone_source(&group, new_shard) {
+            warn!(
+                "the split shard is a clone source, keep it in place. shard={}, group={}",
+                task.new_shard, task.group
+            );
+            return Ok((true, false));
+        }
+        match self.try_migrate_shard(task.group, task.dest_group, task.new_shard).await {
+            Ok(_) => Ok((true, false)),
+            Err(crate::Error::AbortScheduleTask(reason)) => {
+                warn!(
+                    "abort moving the split shard. shard={}, src={}, dest={}, reason={reason}",
+                    task.new_shard, task.group, task.dest_group
+                );
+                Ok((true, false))
+            }
+            Err(err) => {
+                warn!(
+                    "move the split shard fail, retry later: {err:?}. shard={}, src={}, dest={}",
+                    task.new_shard, task.group, task.dest_group
+                );
+                Err(err)
+            }
+        }
+    }
+
     async fn handle_transfer_leader(
         &self,
         task: &mut TransferGroupLeaderTask,
@@ -586,15 +972,15 @@ impl ScheduleContext {
         Ok(())
     }
 
-    async fn try_move_replica(
+    async fn try_move_replicas(
         &self,
         group: u64,
-        incoming_replica: ReplicaDesc,
-        outgoing_replica: ReplicaDesc,
+        incoming_replicas: Vec<ReplicaDesc>,
+        outgoing_replicas: Vec<ReplicaDesc>,
     ) -> Result<ScheduleState> {
         let mut group_client = self.shared.transport_manager.lazy_group_client(group);
         let current_state =
-            group_client.move_replicas(vec![incoming_replica], vec![outgoing_replica]).await?;
+            group_client.move_replicas(incoming_replicas, outgoing_replicas).await?;
         Ok(current_state)
     }
 
@@ -646,3 +1032,36 @@ impl ScheduleContext {
         Ok(group_router.replicas.iter().find(|(_, r)| r.id == leader_repl).map(|(_, r)| r.node_id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(task: Task) -> ReconcileTask {
+        ReconcileTask { task: Some(task) }
+    }
+
+    #[test]
+    fn persist_only_requested_tasks() {
+        let shed_leader = task(Task::ShedLeader(ShedLeaderTask { node_id: 1 }));
+        let shed_root = task(Task::ShedRoot(ShedRootLeaderTask { node_id: 1 }));
+        let key = reconcile_task_key(&shed_leader).unwrap();
+        assert!(key.starts_with(RECONCILE_TASK_KEY_PREFIX));
+        assert_ne!(Some(key), reconcile_task_key(&shed_root));
+
+        let move_replicas = |src_replicas| {
+            task(Task::MoveGroupReplicas(MoveGroupReplicasTask {
+                group: 1,
+                src_replicas,
+                dest_nodes: vec![2],
+            }))
+        };
+        assert_eq!(
+            reconcile_task_key(&move_replicas(vec![1])),
+            reconcile_task_key(&move_replicas(vec![3]))
+        );
+
+        let migrate = task(Task::MigrateShard(MigrateShardTask::default()));
+        assert!(reconcile_task_key(&migrate).is_none());
+    }
+}
@@ -13,8 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use futures::lock::Mutex;
@@ -26,10 +25,23 @@ use sekas_api::server::v1::{CollectionDesc, DatabaseDesc, PutRequest, *};
 use sekas_rock::time::timestamp_nanos;
 use sekas_schema::system::col;
 
+use super::audit::{audit_key, AUDIT_KEY_PREFIX};
+use super::cache::SchemaCache;
+use super::history::{group_event_key, group_event_prefix};
+use super::idempotency::{
+    idempotency_index_key, idempotency_key, parse_idempotency_index_key,
+    IDEMPOTENCY_INDEX_KEY_PREFIX,
+};
+use super::schedule::RECONCILE_TASK_KEY_PREFIX;
 use super::store::RootStore;
+use super::tenant::{database_key, tenant_key, FIRST_TENANT_ID, TENANT_KEY_PREFIX};
+use super::trash::{trash_key, TRASH_KEY_PREFIX};
 use crate::constants::*;
 use crate::engine::{GroupEngine, SnapshotMode};
-use crate::serverpb::v1::BackgroundJob;
+use crate::serverpb::v1::{
+    AuditRecord, BackgroundJob, DataKeySet, DynamicConfigSet, GroupEvent, IdempotencyRecord,
+    ReconcileTask, TenantDesc, TrashEntry, UserDesc,
+};
 use crate::transport::TransportManager;
 use crate::{Error, Result};
 
@@ -42,6 +54,19 @@ const META_REPLICA_ID_KEY: &str = "replica_id";
 const META_SHARD_ID_KEY: &str = "shard_id";
 const META_JOB_ID_KEY: &str = "job_id";
 const META_TXN_ID_KEY: &str = "txn_id";
+const META_CLUSTER_VERSION_KEY: &str = "cluster_version";
+const META_DATA_KEYS_KEY: &str = "data_keys";
+const META_MOVE_SHARD_LIMIT_KEY: &str = "move_shard_limit";
+const META_DYNAMIC_CONFIG_KEY: &str = "dynamic_config";
+const META_TENANT_ID_KEY: &str = "tenant_id";
+
+/// The lock serializing the deletion of tenants with the creation of the
+/// databases and collections owned by them, it also serializes the checks of
+/// the tenant quotas.
+pub const TENANT_LOCK_KEY: &str = "tenant";
+
+/// The prefix of the keys marking the groups as draining.
+const DRAINING_GROUP_KEY_PREFIX: &[u8] = b"draining_group_";
 
 lazy_static! {
     pub static ref ID_GEN_LOCKS: HashMap<String, Mutex<()>> = HashMap::from([
@@ -53,18 +78,30 @@ lazy_static! {
         (META_REPLICA_ID_KEY.to_owned(), Mutex::new(())),
         (META_SHARD_ID_KEY.to_owned(), Mutex::new(())),
         (META_JOB_ID_KEY.to_owned(), Mutex::new(())),
+        (META_TENANT_ID_KEY.to_owned(), Mutex::new(())),
+        (TENANT_LOCK_KEY.to_owned(), Mutex::new(())),
     ]);
 }
 
 #[derive(Clone)]
 pub struct Schema {
     store: Arc<RootStore>,
+    cache: Arc<SchemaCache>,
 }
 
 // public interface.
 impl Schema {
     pub fn new(store: Arc<RootStore>) -> Self {
-        Self { store }
+        Self { store, cache: Arc::default() }
+    }
+
+    /// Load the groups, nodes and collections into the cache, so that the
+    /// following reads of them are served by the cache.
+    pub async fn load_cache(&self) -> Result<()> {
+        self.list_group().await?;
+        self.list_node().await?;
+        self.list_collection().await?;
+        Ok(())
     }
 
     pub async fn cluster_id(&self) -> Result<Option<Vec<u8>>> {
@@ -72,7 +109,7 @@ impl Schema {
     }
 
     pub async fn create_database(&self, desc: DatabaseDesc) -> Result<DatabaseDesc> {
-        if self.get_database(&desc.name).await?.is_some() {
+        if self.get_database(desc.tenant_id, &desc.name).await?.is_some() {
             warn!("create database but it already exists. database={}", desc.name);
             return Err(Error::AlreadyExists(format!("database {}", desc.name.to_owned())));
         }
@@ -83,8 +120,9 @@ impl Schema {
         Ok(desc)
     }
 
-    pub async fn get_database(&self, name: &str) -> Result<Option<DatabaseDesc>> {
-        let val = self.get(col::DATABASE_ID, name.as_bytes()).await?;
+    /// Get the database by name, the names are only unique within a tenant.
+    pub async fn get_database(&self, tenant_id: u64, name: &str) -> Result<Option<DatabaseDesc>> {
+        let val = self.get(col::DATABASE_ID, &database_key(tenant_id, name)).await?;
         if val.is_none() {
             return Ok(None);
         }
@@ -93,12 +131,30 @@ impl Schema {
         Ok(Some(desc))
     }
 
+    /// Rename the database, the id is kept so that the collections and the
+    /// permissions on it are unaffected.
+    pub async fn rename_database(&self, db: &DatabaseDesc, new_name: &str) -> Result<DatabaseDesc> {
+        let desc = DatabaseDesc { name: new_name.to_owned(), ..db.clone() };
+        self.rename(
+            col::DATABASE_ID,
+            database_key(db.tenant_id, &db.name),
+            database_key(db.tenant_id, new_name),
+            desc.encode_to_vec(),
+        )
+        .await
+        .map_err(|err| match err {
+            Error::CasFailed(..) => Error::AlreadyExists(format!("database {new_name}")),
+            err => err,
+        })?;
+        Ok(desc)
+    }
+
     pub async fn update_database(&self, _desc: DatabaseDesc) -> Result<()> {
         todo!()
     }
 
     pub async fn delete_database(&self, db: &DatabaseDesc) -> Result<u64> {
-        self.delete(col::DATABASE_ID, db.name.as_bytes()).await?;
+        self.delete(col::DATABASE_ID, &database_key(db.tenant_id, &db.name)).await?;
         Ok(db.id)
     }
 
@@ -134,7 +190,11 @@ impl Schema {
         database: u64,
         collection: &str,
     ) -> Result<Option<CollectionDesc>> {
-        let val = self.get(col::COLLECTION_ID, &collection_key(database, collection)).await?;
+        let key = collection_key(database, collection);
+        if let Some(desc) = self.cache.collections.get(&key) {
+            return Ok(desc);
+        }
+        let val = self.get(col::COLLECTION_ID, &key).await?;
         if val.is_none() {
             return Ok(None);
         }
@@ -158,15 +218,45 @@ impl Schema {
             .collect::<Vec<_>>())
     }
 
-    pub async fn update_collection(&self, _desc: CollectionDesc) -> Result<()> {
-        todo!()
+    /// Rename the collection, the id is kept so that the shards and the routing
+    /// of clients are unaffected.
+    pub async fn rename_collection(
+        &self,
+        collection: &CollectionDesc,
+        new_name: &str,
+    ) -> Result<CollectionDesc> {
+        let desc = CollectionDesc { name: new_name.to_owned(), ..collection.clone() };
+        let old_key = collection_key(collection.db, &collection.name);
+        let rename = self.rename(
+            col::COLLECTION_ID,
+            old_key.clone(),
+            collection_key(collection.db, new_name),
+            desc.encode_to_vec(),
+        );
+        self.cache.collections.write(rename, vec![desc.clone()], vec![old_key]).await.map_err(
+            |err| match err {
+                Error::CasFailed(..) => Error::AlreadyExists(format!("collection {new_name}")),
+                err => err,
+            },
+        )?;
+        Ok(desc)
+    }
+
+    pub async fn update_collection(&self, desc: CollectionDesc) -> Result<()> {
+        self.put_col(desc).await
     }
 
     pub async fn delete_collection(&self, collection: CollectionDesc) -> Result<()> {
-        self.delete(col::COLLECTION_ID, &collection_key(collection.db, &collection.name)).await
+        let key = collection_key(collection.db, &collection.name);
+        let delete = self.delete(col::COLLECTION_ID, &key);
+        self.cache.collections.write(delete, vec![], vec![key.clone()]).await
     }
 
     pub async fn list_collection(&self) -> Result<Vec<CollectionDesc>> {
+        if let Some(collections) = self.cache.collections.list() {
+            return Ok(collections);
+        }
+        let generation = self.cache.collections.generation();
         let values = self.list(col::COLLECTION_ID).await?;
         let mut collections = Vec::new();
         for val in values {
@@ -174,6 +264,7 @@ impl Schema {
                 .map_err(|_| Error::InvalidData("collection desc".into()))?;
             collections.push(c);
         }
+        self.cache.collections.fill(generation, collections.clone());
         Ok(collections)
     }
 
@@ -190,6 +281,9 @@ impl Schema {
     }
 
     pub async fn get_node(&self, id: u64) -> Result<Option<NodeDesc>> {
+        if let Some(desc) = self.cache.nodes.get(&id.to_le_bytes()) {
+            return Ok(desc);
+        }
         let val = self.get(col::NODE_ID, &id.to_le_bytes()).await?;
         if val.is_none() {
             return Ok(None);
@@ -200,7 +294,9 @@ impl Schema {
     }
 
     pub async fn delete_node(&self, id: u64) -> Result<()> {
-        self.delete(col::NODE_ID, &id.to_le_bytes()).await
+        let key = id.to_le_bytes();
+        let delete = self.delete(col::NODE_ID, &key);
+        self.cache.nodes.write(delete, vec![], vec![key.to_vec()]).await
     }
 
     pub async fn update_node(&self, desc: NodeDesc) -> Result<()> {
@@ -209,12 +305,17 @@ impl Schema {
     }
 
     pub async fn list_node(&self) -> Result<Vec<NodeDesc>> {
+        if let Some(nodes) = self.cache.nodes.list() {
+            return Ok(nodes);
+        }
+        let generation = self.cache.nodes.generation();
         let values = self.list(col::NODE_ID).await?;
         let mut nodes = Vec::new();
         for val in values {
             nodes
                 .push(NodeDesc::decode(&*val).map_err(|_| Error::InvalidData("node desc".into()))?);
         }
+        self.cache.nodes.fill(generation, nodes.clone());
         Ok(nodes)
     }
 
@@ -261,12 +362,52 @@ impl Schema {
         Ok(())
     }
 
+    /// Write the groups and replica states in batches. The groups and replica
+    /// states are placed in different shards of the root group, so they are
+    /// written by two batches, the replica states first.
+    pub async fn update_group_replicas(
+        &self,
+        groups: Vec<GroupDesc>,
+        replicas: Vec<ReplicaState>,
+    ) -> Result<()> {
+        if !replicas.is_empty() {
+            let mut batch = ShardWriteRequest {
+                shard_id: col::shard_id(col::REPLICA_STATE_ID),
+                ..Default::default()
+            };
+            for state in replicas {
+                batch.puts.push(PutRequest {
+                    key: replica_key(state.group_id, state.replica_id),
+                    value: state.encode_to_vec(),
+                    ..Default::default()
+                });
+            }
+            self.batch_write(batch).await?;
+        }
+        if !groups.is_empty() {
+            let mut batch =
+                ShardWriteRequest { shard_id: col::shard_id(col::GROUP_ID), ..Default::default() };
+            for desc in &groups {
+                batch.puts.push(PutRequest {
+                    key: group_key(desc.id),
+                    value: desc.encode_to_vec(),
+                    ..Default::default()
+                });
+            }
+            self.cache.groups.write(self.batch_write(batch), groups, vec![]).await?;
+        }
+        Ok(())
+    }
+
     pub async fn remove_replica_state(&self, group_id: u64, replica_id: u64) -> Result<()> {
         let key = replica_key(group_id, replica_id);
         self.delete(col::REPLICA_STATE_ID, &key).await
     }
 
     pub async fn get_group(&self, id: u64) -> Result<Option<GroupDesc>> {
+        if let Some(desc) = self.cache.groups.get(&group_key(id)) {
+            return Ok(desc);
+        }
         let val = self.get(col::GROUP_ID, &id.to_le_bytes()).await?;
         if val.is_none() {
             return Ok(None);
@@ -278,10 +419,54 @@ impl Schema {
 
     pub async fn delete_group(&self, id: u64) -> Result<()> {
         // TODO: prefix delete replica_state
-        self.delete(col::GROUP_ID, &id.to_le_bytes()).await
+        let key = group_key(id);
+        let delete = self.delete(col::GROUP_ID, &key);
+        self.cache.groups.write(delete, vec![], vec![key.clone()]).await?;
+        self.delete(col::META_ID, &draining_group_key(id)).await
+    }
+
+    /// Mark the group as draining, the shards are no longer allocated to it,
+    /// until the group is deleted.
+    pub async fn mark_group_draining(&self, id: u64) -> Result<()> {
+        self.put_meta(&draining_group_key(id), id.to_le_bytes().to_vec()).await
+    }
+
+    /// Return the ids of the groups marked as draining.
+    pub async fn draining_groups(&self) -> Result<HashSet<u64>> {
+        let values = self.list_prefix(col::META_ID, DRAINING_GROUP_KEY_PREFIX).await?;
+        values
+            .into_iter()
+            .map(|val| {
+                val.try_into()
+                    .map(u64::from_le_bytes)
+                    .map_err(|_| Error::InvalidData("draining group".into()))
+            })
+            .collect()
+    }
+
+    /// Return the min snapshot versions of the clone sources, keyed by the
+    /// source collection ids. It is rebuilt only if the groups are written.
+    pub async fn clone_source_versions(&self) -> Result<Arc<HashMap<u64, u64>>> {
+        let generation = self.cache.groups.generation();
+        if let Some(versions) = self.cache.clone_sources.get(generation) {
+            return Ok(versions);
+        }
+        let mut versions = HashMap::new();
+        for shard in self.list_group().await?.into_iter().flat_map(|g| g.shards) {
+            let Some(clone_source) = shard.clone_source else { continue };
+            let version = versions.entry(clone_source.collection_id).or_insert(u64::MAX);
+            *version = std::cmp::min(*version, clone_source.version);
+        }
+        let versions = Arc::new(versions);
+        self.cache.clone_sources.fill(generation, versions.clone());
+        Ok(versions)
     }
 
     pub async fn list_group(&self) -> Result<Vec<GroupDesc>> {
+        if let Some(groups) = self.cache.groups.list() {
+            return Ok(groups);
+        }
+        let generation = self.cache.groups.generation();
         let values = self.list(col::GROUP_ID).await?;
         let mut groups = Vec::new();
         for val in values {
@@ -289,6 +474,7 @@ impl Schema {
                 GroupDesc::decode(&*val).map_err(|_| Error::InvalidData("group desc".into()))?,
             );
         }
+        self.cache.groups.fill(generation, groups.clone());
         Ok(groups)
     }
 
@@ -335,34 +521,18 @@ impl Schema {
     }
 
     pub async fn list_group_state(&self) -> Result<Vec<GroupState>> {
-        let mut states: HashMap<u64, GroupState> = HashMap::new();
+        let mut states: HashMap<u64, Vec<ReplicaState>> = HashMap::new();
         for state in self.list_replica_state().await? {
-            match states.entry(state.group_id) {
-                Entry::Occupied(mut ent) => {
-                    let group = ent.get_mut();
-                    if state.role == RaftRole::Leader as i32 {
-                        group.leader_id = Some(state.replica_id);
-                    } else if group.leader_id == Some(state.replica_id) {
-                        group.leader_id = None;
-                    }
-                    group.replicas.retain(|desc| desc.replica_id != state.replica_id);
-                    group.replicas.push(state);
-                }
-                Entry::Vacant(ent) => {
-                    let leader_id = if state.role == RaftRole::Leader as i32 {
-                        Some(state.replica_id)
-                    } else {
-                        None
-                    };
-                    ent.insert(GroupState {
-                        group_id: state.group_id,
-                        leader_id,
-                        replicas: vec![state],
-                    });
-                }
-            }
+            states.entry(state.group_id).or_default().push(state);
         }
-        Ok(states.into_values().collect())
+        Ok(states.into_iter().map(|(group_id, replicas)| group_state(group_id, replicas)).collect())
+    }
+
+    /// Return the state of the group, only the replica states of the group are
+    /// scanned.
+    pub async fn get_group_state(&self, group_id: u64) -> Result<GroupState> {
+        let replicas = self.group_replica_states(group_id).await?;
+        Ok(group_state(group_id, replicas))
     }
 
     pub async fn get_root_desc(&self) -> Result<RootDesc> {
@@ -383,6 +553,7 @@ impl Schema {
         Ok(RootDesc {
             epoch: group_desc.epoch,
             root_nodes: nodes.into_values().collect::<Vec<_>>(),
+            cluster_version: self.cluster_version().await?,
         })
     }
 
@@ -494,7 +665,7 @@ impl Schema {
     }
 
     pub async fn update_job(&self, desc: BackgroundJob) -> Result<bool> {
-        if self.get(col::JOB_ID, &desc.id.to_be_bytes()).await?.is_none() {
+        if self.get(col::JOB_ID, &desc.id.to_le_bytes()).await?.is_none() {
             // TODO: replace this with storage put_condition operation.
             return Ok(false);
         }
@@ -549,6 +720,357 @@ impl Schema {
         self.put_meta(META_TXN_ID_KEY.as_bytes(), next_txn_id.to_le_bytes().to_vec()).await?;
         Ok(())
     }
+
+    /// Return the version of cluster. The clusters bootstrapped before
+    /// introducing cluster version are treated as [`CLUSTER_VERSION_INITIAL`].
+    pub async fn cluster_version(&self) -> Result<u64> {
+        let Some(version) = self.get_meta(META_CLUSTER_VERSION_KEY.as_bytes()).await? else {
+            return Ok(CLUSTER_VERSION_INITIAL);
+        };
+        Ok(u64::from_le_bytes(
+            version.try_into().map_err(|_| Error::InvalidData("cluster version".to_owned()))?,
+        ))
+    }
+
+    /// Update the version of cluster from `current` to `version`, it fails with
+    /// [`Error::CasFailed`] if the version has been changed by others.
+    pub async fn cas_cluster_version(&self, current: u64, version: u64) -> Result<()> {
+        let key = META_CLUSTER_VERSION_KEY.as_bytes();
+        let condition = match self.get_meta(key).await? {
+            None if current == CLUSTER_VERSION_INITIAL => WriteCondition {
+                r#type: WriteConditionType::ExpectNotExists.into(),
+                ..Default::default()
+            },
+            Some(value) if value == current.to_le_bytes() => WriteCondition {
+                r#type: WriteConditionType::ExpectValue.into(),
+                value,
+                ..Default::default()
+            },
+            _ => return Err(Error::CasFailed(0, 0, None)),
+        };
+        let batch = ShardWriteRequest {
+            shard_id: col::shard_id(col::META_ID),
+            puts: vec![PutRequest {
+                put_type: PutType::None.into(),
+                key: key.to_vec(),
+                value: version.to_le_bytes().to_vec(),
+                conditions: vec![condition],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        self.batch_write(batch).await
+    }
+
+    /// Return the wrapped data keys, ordered by id.
+    pub async fn data_keys(&self) -> Result<Vec<DataKey>> {
+        let Some(value) = self.get_meta(META_DATA_KEYS_KEY.as_bytes()).await? else {
+            return Ok(vec![]);
+        };
+        let data_keys =
+            DataKeySet::decode(&*value).map_err(|_| Error::InvalidData("data keys".to_owned()))?;
+        Ok(data_keys.keys)
+    }
+
+    pub async fn add_data_key(&self, data_key: DataKey) -> Result<()> {
+        let mut keys = self.data_keys().await?;
+        debug_assert!(keys.iter().all(|k| k.id < data_key.id));
+        keys.push(data_key);
+        let value = DataKeySet { keys }.encode_to_vec();
+        self.put_meta(META_DATA_KEYS_KEY.as_bytes(), value).await?;
+        Ok(())
+    }
+
+    /// Return the move shard limit adjusted by admin, `None` if it has never
+    /// been adjusted.
+    pub async fn move_shard_limit(&self) -> Result<Option<MoveShardLimit>> {
+        let Some(value) = self.get_meta(META_MOVE_SHARD_LIMIT_KEY.as_bytes()).await? else {
+            return Ok(None);
+        };
+        let limit = MoveShardLimit::decode(&*value)
+            .map_err(|_| Error::InvalidData("move shard limit".to_owned()))?;
+        Ok(Some(limit))
+    }
+
+    pub async fn set_move_shard_limit(&self, limit: &MoveShardLimit) -> Result<()> {
+        self.put_meta(META_MOVE_SHARD_LIMIT_KEY.as_bytes(), limit.encode_to_vec()).await?;
+        Ok(())
+    }
+
+    /// Return the options set by admin at runtime, ordered by key.
+    pub async fn dynamic_config(&self) -> Result<Vec<ConfigEntry>> {
+        let Some(value) = self.get_meta(META_DYNAMIC_CONFIG_KEY.as_bytes()).await? else {
+            return Ok(vec![]);
+        };
+        let set = DynamicConfigSet::decode(&*value)
+            .map_err(|_| Error::InvalidData("dynamic config".to_owned()))?;
+        Ok(set.entries)
+    }
+
+    /// Save the option, the existing value of the key is replaced.
+    pub async fn set_dynamic_config(&self, key: &str, value: &str) -> Result<()> {
+        let mut entries = self.dynamic_config().await?;
+        match entries.binary_search_by(|e| e.key.as_str().cmp(key)) {
+            Ok(idx) => entries[idx].value = value.to_owned(),
+            Err(idx) => {
+                entries.insert(idx, ConfigEntry { key: key.to_owned(), value: value.to_owned() })
+            }
+        }
+        let set = DynamicConfigSet { entries };
+        self.put_meta(META_DYNAMIC_CONFIG_KEY.as_bytes(), set.encode_to_vec()).await
+    }
+
+    pub async fn append_audit_record(&self, record: &AuditRecord) -> Result<()> {
+        self.put_meta(&audit_key(record.id), record.encode_to_vec()).await
+    }
+
+    /// Return the audit records, ordered by id.
+    pub async fn list_audit_records(&self) -> Result<Vec<AuditRecord>> {
+        let values = self.list_prefix(col::META_ID, AUDIT_KEY_PREFIX).await?;
+        let mut records = Vec::with_capacity(values.len());
+        for val in values {
+            let record = AuditRecord::decode(&*val)
+                .map_err(|_| Error::InvalidData("audit record".into()))?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Delete the audit records whose id is less than `before_id`.
+    pub async fn purge_audit_records(&self, before_id: u64) -> Result<usize> {
+        let records = self.list_audit_records().await?;
+        let mut num_purged = 0;
+        for record in records.iter().take_while(|r| r.id < before_id) {
+            self.delete(col::META_ID, &audit_key(record.id)).await?;
+            num_purged += 1;
+        }
+        Ok(num_purged)
+    }
+
+    pub async fn append_group_event(&self, event: &GroupEvent) -> Result<()> {
+        self.put_meta(&group_event_key(event.group_id, event.id), event.encode_to_vec()).await
+    }
+
+    /// Return the events of the group, ordered by id.
+    pub async fn list_group_events(&self, group_id: u64) -> Result<Vec<GroupEvent>> {
+        let values = self.list_prefix(col::META_ID, &group_event_prefix(group_id)).await?;
+        let mut events = Vec::with_capacity(values.len());
+        for val in values {
+            let event =
+                GroupEvent::decode(&*val).map_err(|_| Error::InvalidData("group event".into()))?;
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// Delete the oldest events of the group, only the latest `keep` events are
+    /// kept.
+    pub async fn purge_group_events(&self, group_id: u64, keep: usize) -> Result<usize> {
+        let events = self.list_group_events(group_id).await?;
+        let num_purged = events.len().saturating_sub(keep);
+        for event in &events[..num_purged] {
+            self.delete(col::META_ID, &group_event_key(group_id, event.id)).await?;
+        }
+        Ok(num_purged)
+    }
+
+    pub async fn put_trash(&self, entry: &TrashEntry) -> Result<()> {
+        self.put_meta(&trash_key(entry), entry.encode_to_vec()).await
+    }
+
+    pub async fn delete_trash(&self, entry: &TrashEntry) -> Result<()> {
+        self.delete(col::META_ID, &trash_key(entry)).await
+    }
+
+    pub async fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        let values = self.list_prefix(col::META_ID, TRASH_KEY_PREFIX).await?;
+        let mut entries = Vec::with_capacity(values.len());
+        for val in values {
+            let entry =
+                TrashEntry::decode(&*val).map_err(|_| Error::InvalidData("trash entry".into()))?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    pub async fn get_idempotency_record(&self, token: &str) -> Result<Option<IdempotencyRecord>> {
+        let Some(val) = self.get(col::META_ID, &idempotency_key(token)).await? else {
+            return Ok(None);
+        };
+        let record = IdempotencyRecord::decode(&*val)
+            .map_err(|_| Error::InvalidData(format!("idempotency record: {token}")))?;
+        Ok(Some(record))
+    }
+
+    /// Put the idempotency record and its creation time index in one write
+    /// batch.
+    pub async fn put_idempotency_record(&self, record: &IdempotencyRecord) -> Result<()> {
+        let index_key = idempotency_index_key(record.created_at, &record.token);
+        let batch = ShardWriteRequest {
+            shard_id: col::shard_id(col::META_ID),
+            puts: vec![
+                PutRequest {
+                    put_type: PutType::None.into(),
+                    key: idempotency_key(&record.token),
+                    value: record.encode_to_vec(),
+                    ..Default::default()
+                },
+                PutRequest {
+                    put_type: PutType::None.into(),
+                    key: index_key,
+                    value: vec![],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        self.batch_write(batch).await
+    }
+
+    /// Delete the idempotency record and its creation time index in one write
+    /// batch.
+    pub async fn delete_idempotency_record(&self, created_at: u64, token: &str) -> Result<()> {
+        let batch = ShardWriteRequest {
+            shard_id: col::shard_id(col::META_ID),
+            deletes: vec![
+                DeleteRequest { key: idempotency_key(token), ..Default::default() },
+                DeleteRequest {
+                    key: idempotency_index_key(created_at, token),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        self.batch_write(batch).await
+    }
+
+    /// Return the creation time and token of the oldest `limit` idempotency
+    /// records, the oldest one comes first.
+    pub async fn oldest_idempotency_records(&self, limit: usize) -> Result<Vec<(u64, String)>> {
+        let pairs = self
+            .store
+            .list_limit(col::shard_id(col::META_ID), IDEMPOTENCY_INDEX_KEY_PREFIX, limit)
+            .await?;
+        let mut records = Vec::with_capacity(pairs.len());
+        for (key, _) in pairs {
+            let record = parse_idempotency_index_key(&key)
+                .ok_or_else(|| Error::InvalidData("idempotency index".into()))?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    pub async fn put_reconcile_task(&self, key: &[u8], task: &ReconcileTask) -> Result<()> {
+        self.put_meta(key, task.encode_to_vec()).await
+    }
+
+    pub async fn delete_reconcile_task(&self, key: &[u8]) -> Result<()> {
+        self.delete(col::META_ID, key).await
+    }
+
+    pub async fn list_reconcile_tasks(&self) -> Result<Vec<ReconcileTask>> {
+        let values = self.list_prefix(col::META_ID, RECONCILE_TASK_KEY_PREFIX).await?;
+        let mut tasks = Vec::with_capacity(values.len());
+        for val in values {
+            let task = ReconcileTask::decode(&*val)
+                .map_err(|_| Error::InvalidData("reconcile task".into()))?;
+            tasks.push(task);
+        }
+        Ok(tasks)
+    }
+
+    /// Put the dropped database back, the name must not be used by others.
+    pub async fn recover_database(&self, desc: &DatabaseDesc) -> Result<()> {
+        if self.get_database(desc.tenant_id, &desc.name).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("database {}", desc.name)));
+        }
+        self.put_database(desc.clone()).await
+    }
+
+    /// Put the dropped collection back, the name must not be used by others.
+    pub async fn recover_collection(&self, desc: &CollectionDesc) -> Result<()> {
+        if self.get_collection(desc.db, &desc.name).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("collection {}", desc.name)));
+        }
+        self.put_col(desc.clone()).await
+    }
+
+    /// Return whether the user collection exists, it is missing in the
+    /// clusters bootstrapped before the authentication is supported.
+    pub async fn has_user_collection(&self) -> Result<bool> {
+        let desc = col::user_desc();
+        Ok(self.get_collection(desc.db, &desc.name).await?.is_some())
+    }
+
+    pub async fn add_user_collection(&self) -> Result<()> {
+        self.put_col(col::user_desc()).await
+    }
+
+    pub async fn get_user(&self, name: &str) -> Result<Option<UserDesc>> {
+        let Some(val) = self.get(col::USER_ID, name.as_bytes()).await? else {
+            return Ok(None);
+        };
+        let desc = UserDesc::decode(&*val)
+            .map_err(|_| Error::InvalidData(format!("user desc: {name}")))?;
+        Ok(Some(desc))
+    }
+
+    pub async fn put_user(&self, desc: &UserDesc) -> Result<()> {
+        self.put(col::USER_ID, desc.name.as_bytes(), desc.encode_to_vec()).await
+    }
+
+    pub async fn delete_user(&self, name: &str) -> Result<()> {
+        self.delete(col::USER_ID, name.as_bytes()).await
+    }
+
+    /// Create the tenant, the name of tenant must not be used by others.
+    pub async fn create_tenant(&self, desc: TenantDesc) -> Result<TenantDesc> {
+        if self.get_tenant(&desc.name).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("tenant {}", desc.name)));
+        }
+        let desc = TenantDesc { id: self.next_tenant_id().await?, ..desc };
+        self.put_tenant(&desc).await?;
+        Ok(desc)
+    }
+
+    pub async fn get_tenant(&self, name: &str) -> Result<Option<TenantDesc>> {
+        let Some(val) = self.get_meta(&tenant_key(name)).await? else {
+            return Ok(None);
+        };
+        let desc = TenantDesc::decode(&*val)
+            .map_err(|_| Error::InvalidData(format!("tenant desc: {name}")))?;
+        Ok(Some(desc))
+    }
+
+    pub async fn put_tenant(&self, desc: &TenantDesc) -> Result<()> {
+        self.put_meta(&tenant_key(&desc.name), desc.encode_to_vec()).await
+    }
+
+    pub async fn delete_tenant(&self, name: &str) -> Result<()> {
+        self.delete(col::META_ID, &tenant_key(name)).await
+    }
+
+    pub async fn list_tenant(&self) -> Result<Vec<TenantDesc>> {
+        let values = self.list_prefix(col::META_ID, TENANT_KEY_PREFIX).await?;
+        let mut tenants = Vec::with_capacity(values.len());
+        for val in values {
+            let tenant =
+                TenantDesc::decode(&*val).map_err(|_| Error::InvalidData("tenant desc".into()))?;
+            tenants.push(tenant);
+        }
+        Ok(tenants)
+    }
+
+    pub async fn list_user(&self) -> Result<Vec<UserDesc>> {
+        let values = self.list(col::USER_ID).await?;
+        let mut users = Vec::with_capacity(values.len());
+        for val in values {
+            let user =
+                UserDesc::decode(&*val).map_err(|_| Error::InvalidData("user desc".into()))?;
+            users.push(user);
+        }
+        Ok(users)
+    }
 }
 
 pub struct ReplicaNodes(pub Vec<NodeDesc>);
@@ -575,7 +1097,10 @@ impl Schema {
         &mut self,
         addr: &str,
         cfg_cpu_nums: u32,
+        cfg_capacity_weight: Option<f64>,
+        cfg_labels: Vec<String>,
         cluster_id: Vec<u8>,
+        incarnation: u64,
     ) -> Result<()> {
         debug_assert_ne!(cfg_cpu_nums, 0);
         let _timer = super::metrics::BOOTSTRAP_DURATION_SECONDS.start_timer();
@@ -606,8 +1131,12 @@ impl Schema {
                 cpu_nums: cfg_cpu_nums as f64,
                 replica_count: 1,
                 leader_count: 0,
+                weight: cfg_capacity_weight.unwrap_or_default(),
             }),
             status: NodeStatus::Active as i32,
+            binary_version: BINARY_VERSION,
+            labels: cfg_labels,
+            incarnation,
         };
         self.put_node(node_desc).await?;
 
@@ -639,14 +1168,15 @@ impl Schema {
 
         let mut batch =
             ShardWriteRequest { shard_id: col::shard_id(col::COLLECTION_ID), ..Default::default() };
-        for col in sekas_schema::system::collections() {
+        let collections = sekas_schema::system::collections();
+        for col in &collections {
             batch.puts.push(PutRequest {
                 key: collection_key(col.db, &col.name),
                 value: col.encode_to_vec(),
                 ..Default::default()
             });
         }
-        self.batch_write(batch).await?;
+        self.cache.collections.write(self.batch_write(batch), collections, vec![]).await?;
 
         // ATTN: init meta collection will setup cluster id, so it must be the last step
         // of bootstrap root.
@@ -692,6 +1222,7 @@ impl Schema {
         );
         put_meta(META_JOB_ID_KEY.into(), INITIAL_JOB_ID.to_le_bytes().to_vec());
         put_meta(META_TXN_ID_KEY.into(), timestamp_nanos().to_le_bytes().to_vec());
+        put_meta(META_CLUSTER_VERSION_KEY.into(), BINARY_VERSION.to_le_bytes().to_vec());
         self.batch_write(batch).await?;
         Ok(())
     }
@@ -724,6 +1255,33 @@ impl Schema {
         self.store.put(col::shard_id(collection_id), key.to_owned(), value).await
     }
 
+    /// Move the record from `old_key` to `new_key` in one write batch, it fails
+    /// with [`Error::CasFailed`] if `new_key` already exists.
+    async fn rename(
+        &self,
+        collection_id: u64,
+        old_key: Vec<u8>,
+        new_key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<()> {
+        let not_exists = WriteCondition {
+            r#type: WriteConditionType::ExpectNotExists.into(),
+            ..Default::default()
+        };
+        let batch = ShardWriteRequest {
+            shard_id: col::shard_id(collection_id),
+            deletes: vec![DeleteRequest { key: old_key, ..Default::default() }],
+            puts: vec![PutRequest {
+                put_type: PutType::None.into(),
+                key: new_key,
+                value,
+                conditions: vec![not_exists],
+                ..Default::default()
+            }],
+        };
+        self.batch_write(batch).await
+    }
+
     async fn list(&self, collection_id: u64) -> Result<Vec<Vec<u8>>> {
         let rs = self.list_prefix(collection_id, &[]).await;
         sekas_runtime::yield_now().await;
@@ -734,6 +1292,22 @@ impl Schema {
         self.store.list(col::shard_id(collection_id), prefix).await
     }
 
+    /// Allocate the id of a new tenant. The id generator is initialized on
+    /// demand, since it is missing in the clusters bootstrapped before the
+    /// tenants are supported.
+    async fn next_tenant_id(&self) -> Result<u64> {
+        let _mutex =
+            ID_GEN_LOCKS.get(META_TENANT_ID_KEY).expect("id gen lock not found").lock().await;
+        let id = match self.get_meta(META_TENANT_ID_KEY.as_bytes()).await? {
+            Some(id) => u64::from_le_bytes(
+                id.try_into().map_err(|_| Error::InvalidData("tenant id".into()))?,
+            ),
+            None => FIRST_TENANT_ID,
+        };
+        self.put_meta(META_TENANT_ID_KEY.as_bytes(), (id + 1).to_le_bytes().to_vec()).await?;
+        Ok(id)
+    }
+
     async fn next_id(&self, id_type: &str) -> Result<u64> {
         let _mutex = ID_GEN_LOCKS.get(id_type).expect("id gen lock not found").lock().await;
         let id = self
@@ -752,12 +1326,15 @@ impl Schema {
 impl Schema {
     #[inline]
     async fn put_database(&self, desc: DatabaseDesc) -> Result<()> {
-        self.put(col::DATABASE_ID, desc.name.as_bytes(), desc.encode_to_vec()).await
+        self.put(col::DATABASE_ID, &database_key(desc.tenant_id, &desc.name), desc.encode_to_vec())
+            .await
     }
 
     #[inline]
     async fn put_group(&self, desc: GroupDesc) -> Result<()> {
-        self.put(col::GROUP_ID, &desc.id.to_le_bytes(), desc.encode_to_vec()).await
+        let key = group_key(desc.id);
+        let put = self.put(col::GROUP_ID, &key, desc.encode_to_vec());
+        self.cache.groups.write(put, vec![desc], vec![]).await
     }
 
     #[inline]
@@ -777,7 +1354,9 @@ impl Schema {
 
     #[inline]
     async fn put_node(&self, desc: NodeDesc) -> Result<()> {
-        self.put(col::NODE_ID, &desc.id.to_le_bytes(), desc.encode_to_vec()).await
+        let key = desc.id.to_le_bytes();
+        let put = self.put(col::NODE_ID, &key, desc.encode_to_vec());
+        self.cache.nodes.write(put, vec![desc], vec![]).await
     }
 
     #[inline]
@@ -792,7 +1371,9 @@ impl Schema {
 
     #[inline]
     async fn put_col(&self, col: CollectionDesc) -> Result<()> {
-        self.put(col::COLLECTION_ID, &collection_key(col.db, &col.name), col.encode_to_vec()).await
+        let key = collection_key(col.db, &col.name);
+        let put = self.put(col::COLLECTION_ID, &key, col.encode_to_vec());
+        self.cache.collections.write(put, vec![col], vec![]).await
     }
 }
 
@@ -828,10 +1409,25 @@ impl RemoteStore {
         client.delete(&key).await?;
         Ok(())
     }
+
+    /// Read all users from the root group, so that they could be loaded by
+    /// any node.
+    pub async fn list_users(&self) -> Result<Vec<UserDesc>> {
+        let shard_id = col::shard_id(col::USER_ID);
+        let client = self.transport_manager.build_shard_client(ROOT_GROUP_ID, shard_id);
+        let values = client.prefix_list(&[]).await?;
+        let mut users = Vec::with_capacity(values.len());
+        for value in values {
+            let user = UserDesc::decode(value.as_slice())
+                .map_err(|_| Error::InvalidData("user desc".into()))?;
+            users.push(user);
+        }
+        Ok(users)
+    }
 }
 
 #[inline]
-fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
+pub(super) fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + collection_name.len());
     buf.extend_from_slice(database_id.to_le_bytes().as_slice());
     buf.extend_from_slice(collection_name.as_bytes());
@@ -845,6 +1441,21 @@ fn group_key(group_id: u64) -> Vec<u8> {
     buf
 }
 
+#[inline]
+fn draining_group_key(group_id: u64) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(DRAINING_GROUP_KEY_PREFIX.len() + core::mem::size_of::<u64>());
+    buf.extend_from_slice(DRAINING_GROUP_KEY_PREFIX);
+    buf.extend_from_slice(&group_id.to_be_bytes());
+    buf
+}
+
+/// Build the state of the group from the states of its replicas.
+pub(super) fn group_state(group_id: u64, replicas: Vec<ReplicaState>) -> GroupState {
+    let leader_id =
+        replicas.iter().filter(|r| r.role == RaftRole::Leader as i32).last().map(|r| r.replica_id);
+    GroupState { group_id, leader_id, replicas }
+}
+
 #[inline]
 fn replica_key(group_id: u64, replica_id: u64) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() * 2);
@@ -852,3 +1463,30 @@ fn replica_key(group_id: u64, replica_id: u64) -> Vec<u8> {
     buf.extend_from_slice(replica_id.to_le_bytes().as_slice());
     buf
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_group_state_from_replicas() {
+        let replica = |replica_id, role: RaftRole| ReplicaState {
+            replica_id,
+            group_id: 1,
+            role: role.into(),
+            ..Default::default()
+        };
+        let state = group_state(1, vec![]);
+        assert_eq!(state.leader_id, None);
+
+        let replicas = vec![
+            replica(1, RaftRole::Follower),
+            replica(2, RaftRole::Leader),
+            replica(3, RaftRole::Candidate),
+        ];
+        let state = group_state(1, replicas);
+        assert_eq!(state.group_id, 1);
+        assert_eq!(state.leader_id, Some(2));
+        assert_eq!(state.replicas.len(), 3);
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sekas_api::server::v1::RangePartition;
+use sekas_schema::shard::{SHARD_MAX, SHARD_MIN};
+
+use crate::{BootstrapSpec, Error, Result};
+
+/// Check the replication factors of the spec against the replicas of groups,
+/// the shards are replicated by the groups they belong to.
+pub(super) fn check_replication_factor(spec: &BootstrapSpec, replicas: usize) -> Result<()> {
+    let collections = spec.databases.iter().flat_map(|db| db.collections.iter());
+    let factors = std::iter::once(("cluster", spec.replication_factor))
+        .chain(collections.map(|co| (co.name.as_str(), co.replication_factor)));
+    for (target, factor) in factors {
+        match factor {
+            Some(factor) if factor != replicas => {
+                return Err(Error::InvalidArgument(format!(
+                    "the replication factor {factor} of {target} is not equal to the replicas \
+                     per group {replicas}"
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Return the ranges of the shards of a collection split at the keys, which
+/// must be in ascending order.
+pub(super) fn split_ranges(split_keys: &[Vec<u8>]) -> Result<Vec<RangePartition>> {
+    let mut ranges = Vec::with_capacity(split_keys.len() + 1);
+    let mut start = SHARD_MIN.to_owned();
+    for key in split_keys {
+        if key.is_empty() || *key <= start {
+            return Err(Error::InvalidArgument(format!(
+                "split key {} is empty or not in ascending order",
+                key.escape_ascii()
+            )));
+        }
+        ranges.push(RangePartition { start, end: key.clone() });
+        start = key.clone();
+    }
+    ranges.push(RangePartition { start, end: SHARD_MAX.to_owned() });
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CollectionSpec, DatabaseSpec};
+
+    #[test]
+    fn split_ranges_at_keys() {
+        let ranges = split_ranges(&[]).unwrap();
+        assert_eq!(ranges, vec![RangePartition { start: vec![], end: vec![] }]);
+
+        let ranges = split_ranges(&[b"g".to_vec(), b"p".to_vec()]).unwrap();
+        let bounds = ranges.into_iter().map(|r| (r.start, r.end)).collect::<Vec<_>>();
+        assert_eq!(
+            bounds,
+            vec![(vec![], b"g".to_vec()), (b"g".to_vec(), b"p".to_vec()), (b"p".to_vec(), vec![])]
+        );
+
+        assert!(split_ranges(&[b"p".to_vec(), b"g".to_vec()]).is_err());
+        assert!(split_ranges(&[b"g".to_vec(), b"g".to_vec()]).is_err());
+        assert!(split_ranges(&[vec![]]).is_err());
+    }
+
+    #[test]
+    fn check_spec_replication_factor() {
+        let mut spec = BootstrapSpec {
+            replication_factor: Some(3),
+            databases: vec![DatabaseSpec {
+                name: "db".to_owned(),
+                collections: vec![CollectionSpec { name: "co".to_owned(), ..Default::default() }],
+            }],
+        };
+        assert!(check_replication_factor(&spec, 3).is_ok());
+        assert!(check_replication_factor(&spec, 1).is_err());
+
+        spec.replication_factor = None;
+        spec.databases[0].collections[0].replication_factor = Some(5);
+        assert!(check_replication_factor(&spec, 3).is_err());
+        assert!(check_replication_factor(&BootstrapSpec::default(), 1).is_ok());
+    }
+}
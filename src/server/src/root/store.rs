@@ -56,6 +56,7 @@ impl RootStore {
             shard_id,
             start_version: sekas_schema::system::txn::TXN_MAX_VERSION,
             user_key: user_key.to_owned(),
+            ..Default::default()
         };
         let resp = self.submit_request(Request::Get(get)).await?;
         let resp = resp
@@ -106,6 +107,42 @@ impl RootStore {
         }
     }
 
+    /// List the first `limit` key-value pairs with the prefix in key order.
+    pub async fn list_limit(
+        &self,
+        shard_id: u64,
+        prefix: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let resp = self
+            .submit_request(Scan(ShardScanRequest {
+                shard_id,
+                prefix: Some(prefix.to_owned()),
+                start_version: sekas_schema::system::txn::TXN_MAX_VERSION,
+                limit: limit as u64,
+                ..Default::default()
+            }))
+            .await?;
+        let resp = resp
+            .response
+            .ok_or_else(|| Error::InvalidArgument("PrefixListResponse".into()))?
+            .response
+            .ok_or_else(|| Error::InvalidArgument("PrefixListUnionResponse".into()))?;
+
+        if let group_response_union::Response::Scan(resp) = resp {
+            Ok(resp
+                .data
+                .into_iter()
+                .filter_map(|v| {
+                    let value = v.values.last().and_then(|v| v.content.clone())?;
+                    Some((v.user_key, value))
+                })
+                .collect())
+        } else {
+            Err(Error::InvalidArgument("PrefixListResponse".into()))
+        }
+    }
+
     async fn submit_request(&self, req: Request) -> Result<GroupResponse> {
         use crate::replica::retry::execute;
         use crate::replica::ExecCtx;
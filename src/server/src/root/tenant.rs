@@ -0,0 +1,179 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use sekas_api::server::v1::{CollectionDesc, DatabaseDesc};
+
+use super::diagnosis::TenantStats;
+use crate::serverpb::v1::TenantDesc;
+use crate::{Error, Result};
+
+/// The id of the default tenant, it owns the system database and the
+/// databases created without a tenant.
+pub const DEFAULT_TENANT_ID: u64 = 0;
+
+/// The id of the first tenant created by users.
+pub(super) const FIRST_TENANT_ID: u64 = 1;
+
+/// The prefix of the keys of tenants in the meta collection.
+pub(super) const TENANT_KEY_PREFIX: &[u8] = b"tenant_";
+
+/// The first byte of the keys of the databases owned by tenants in the database
+/// collection. It never appears in the keys of the databases of the default
+/// tenant, which are the names of databases in UTF-8.
+const TENANT_DATABASE_KEY_PREFIX: u8 = 0xff;
+
+/// Return the key of the tenant in the meta collection.
+pub(super) fn tenant_key(name: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(TENANT_KEY_PREFIX.len() + name.len());
+    buf.extend_from_slice(TENANT_KEY_PREFIX);
+    buf.extend_from_slice(name.as_bytes());
+    buf
+}
+
+/// Return the key of the database in the database collection. The keys of
+/// the databases owned by a tenant are prefixed by the tenant id, so that the
+/// same name could be used by different tenants. The keys of the default
+/// tenant are kept as the names, which are compatible with the databases
+/// created before the tenants are supported.
+pub(super) fn database_key(tenant_id: u64, name: &str) -> Vec<u8> {
+    if tenant_id == DEFAULT_TENANT_ID {
+        return name.as_bytes().to_vec();
+    }
+    let mut buf = Vec::with_capacity(1 + core::mem::size_of::<u64>() + name.len());
+    buf.push(TENANT_DATABASE_KEY_PREFIX);
+    buf.extend_from_slice(&tenant_id.to_be_bytes());
+    buf.extend_from_slice(name.as_bytes());
+    buf
+}
+
+/// Check whether the tenant could own another database, `num_databases` is the
+/// number of the databases it owns.
+pub(super) fn check_database_quota(tenant: &TenantDesc, num_databases: usize) -> Result<()> {
+    let limit = tenant.quota.as_ref().map(|q| q.max_databases).unwrap_or_default();
+    if limit != 0 && num_databases as u64 >= limit {
+        return Err(Error::ResourceExhausted(format!(
+            "tenant {} exceeds the quota of {limit} databases",
+            tenant.name
+        )));
+    }
+    Ok(())
+}
+
+/// Check whether the tenant could own another collection, `num_collections` is
+/// the number of the collections in its databases.
+pub(super) fn check_collection_quota(tenant: &TenantDesc, num_collections: usize) -> Result<()> {
+    let limit = tenant.quota.as_ref().map(|q| q.max_collections).unwrap_or_default();
+    if limit != 0 && num_collections as u64 >= limit {
+        return Err(Error::ResourceExhausted(format!(
+            "tenant {} exceeds the quota of {limit} collections",
+            tenant.name
+        )));
+    }
+    Ok(())
+}
+
+/// Aggregate the databases, collections and usage of each tenant. The usage of
+/// collections is `(num_keys, logical_bytes)`, the default tenant is named as
+/// an empty string.
+pub(super) fn aggregate_tenant_stats(
+    tenants: &[TenantDesc],
+    databases: &[DatabaseDesc],
+    collections: &[CollectionDesc],
+    collection_usage: &HashMap<u64, (u64, u64)>,
+) -> Vec<TenantStats> {
+    let mut stats = vec![TenantStats::default()];
+    for tenant in tenants {
+        let quota = tenant.quota.clone().unwrap_or_default();
+        stats.push(TenantStats {
+            id: tenant.id,
+            name: tenant.name.clone(),
+            max_databases: quota.max_databases,
+            max_collections: quota.max_collections,
+            ..Default::default()
+        });
+    }
+    let index = stats.iter().enumerate().map(|(i, s)| (s.id, i)).collect::<HashMap<_, _>>();
+    let mut database_tenants = HashMap::with_capacity(databases.len());
+    for db in databases {
+        let Some(&i) = index.get(&db.tenant_id) else {
+            continue;
+        };
+        database_tenants.insert(db.id, i);
+        stats[i].num_databases += 1;
+    }
+    for co in collections {
+        let Some(&i) = database_tenants.get(&co.db) else {
+            continue;
+        };
+        let (num_keys, logical_bytes) = collection_usage.get(&co.id).cloned().unwrap_or_default();
+        stats[i].num_collections += 1;
+        stats[i].num_keys += num_keys;
+        stats[i].logical_bytes += logical_bytes;
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serverpb::v1::TenantQuota;
+
+    #[test]
+    fn isolate_database_keys_by_tenant() {
+        assert_eq!(database_key(DEFAULT_TENANT_ID, "db"), b"db".to_vec());
+        assert_ne!(database_key(1, "db"), database_key(2, "db"));
+        assert_ne!(database_key(1, "db"), database_key(DEFAULT_TENANT_ID, "db"));
+        assert!(database_key(1, "db").starts_with(&[TENANT_DATABASE_KEY_PREFIX]));
+        assert!(tenant_key("team").starts_with(TENANT_KEY_PREFIX));
+    }
+
+    #[test]
+    fn check_tenant_quota() {
+        let tenant = TenantDesc {
+            id: 1,
+            name: "team".to_owned(),
+            quota: Some(TenantQuota { max_databases: 2, max_collections: 0 }),
+        };
+        assert!(check_database_quota(&tenant, 1).is_ok());
+        assert!(check_database_quota(&tenant, 2).is_err());
+        // Zero means unlimited.
+        assert!(check_collection_quota(&tenant, 1000).is_ok());
+        let unlimited = TenantDesc { quota: None, ..tenant };
+        assert!(check_database_quota(&unlimited, 1000).is_ok());
+    }
+
+    #[test]
+    fn aggregate_stats_by_tenant() {
+        let tenants = vec![TenantDesc { id: 1, name: "team".to_owned(), quota: None }];
+        let databases = vec![
+            DatabaseDesc { id: 10, name: "db".to_owned(), tenant_id: 0 },
+            DatabaseDesc { id: 11, name: "db".to_owned(), tenant_id: 1 },
+            DatabaseDesc { id: 12, name: "db-1".to_owned(), tenant_id: 1 },
+        ];
+        let collections = vec![
+            CollectionDesc { id: 20, db: 10, ..Default::default() },
+            CollectionDesc { id: 21, db: 11, ..Default::default() },
+            CollectionDesc { id: 22, db: 12, ..Default::default() },
+        ];
+        let usage = HashMap::from([(20, (1, 10)), (21, (2, 20)), (22, (3, 30))]);
+        let stats = aggregate_tenant_stats(&tenants, &databases, &collections, &usage);
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].id, stats[0].num_databases, stats[0].num_keys), (0, 1, 1));
+        assert_eq!(stats[1].name, "team");
+        assert_eq!((stats[1].num_databases, stats[1].num_collections), (2, 2));
+        assert_eq!((stats[1].num_keys, stats[1].logical_bytes), (5, 50));
+    }
+}
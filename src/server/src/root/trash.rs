@@ -0,0 +1,71 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::serverpb::v1::TrashEntry;
+
+/// The prefix of the keys of trash entries in the meta collection.
+pub(super) const TRASH_KEY_PREFIX: &[u8] = b"trash_";
+
+/// Return the key of the trash entry in the meta collection, the dropped
+/// databases and collections are distinguished since their ids are allocated
+/// separately.
+pub(super) fn trash_key(entry: &TrashEntry) -> Vec<u8> {
+    let (kind, id) = match (&entry.database, &entry.collection) {
+        (Some(db), _) => (b'd', db.id),
+        (None, Some(co)) => (b'c', co.id),
+        (None, None) => (b'_', 0),
+    };
+    let mut buf = Vec::with_capacity(TRASH_KEY_PREFIX.len() + 1 + core::mem::size_of::<u64>());
+    buf.extend_from_slice(TRASH_KEY_PREFIX);
+    buf.push(kind);
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf
+}
+
+/// Return whether the trash entry is expired at `now`, both of them are unix
+/// timestamps in seconds.
+pub(super) fn is_expired(entry: &TrashEntry, now: u64, retention_sec: u64) -> bool {
+    entry.dropped_at.saturating_add(retention_sec) <= now
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::{CollectionDesc, DatabaseDesc};
+
+    use super::*;
+
+    #[test]
+    fn trash_key_of_databases_and_collections() {
+        let db = TrashEntry {
+            database: Some(DatabaseDesc { id: 1, ..Default::default() }),
+            ..Default::default()
+        };
+        let co = TrashEntry {
+            collection: Some(CollectionDesc { id: 1, ..Default::default() }),
+            ..Default::default()
+        };
+        assert_ne!(trash_key(&db), trash_key(&co));
+        assert!(trash_key(&db).starts_with(TRASH_KEY_PREFIX));
+        assert!(trash_key(&co).starts_with(TRASH_KEY_PREFIX));
+    }
+
+    #[test]
+    fn trash_entry_expiration() {
+        let entry = TrashEntry { dropped_at: 100, ..Default::default() };
+        assert!(!is_expired(&entry, 100, 10));
+        assert!(!is_expired(&entry, 109, 10));
+        assert!(is_expired(&entry, 110, 10));
+        assert!(is_expired(&entry, 100, 0));
+    }
+}
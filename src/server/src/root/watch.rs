@@ -18,7 +18,8 @@ use std::task::{Poll, Waker};
 use std::vec;
 
 use futures::Stream;
-use sekas_api::server::v1::watch_response::{DeleteEvent, UpdateEvent};
+use sekas_api::server::v1::watch_request::ResourceType;
+use sekas_api::server::v1::watch_response::{delete_event, update_event, DeleteEvent, UpdateEvent};
 use sekas_api::server::v1::WatchResponse;
 use tokio::sync::{RwLock, RwLockWriteGuard};
 
@@ -35,6 +36,69 @@ pub struct WatchHubInner {
     watchers: HashMap<u64, Watcher>,
 }
 
+/// The types of the resources watched by a watcher.
+#[derive(Clone, Copy, Debug)]
+pub struct WatchFilter {
+    mask: u32,
+}
+
+impl WatchFilter {
+    /// Build the filter from the types of the watch request, all types are
+    /// accepted if it is empty.
+    pub fn new(resource_types: &[i32]) -> Result<Self> {
+        if resource_types.is_empty() {
+            return Ok(WatchFilter::default());
+        }
+        let mut mask = 0;
+        for &value in resource_types {
+            let resource_type = ResourceType::from_i32(value).ok_or_else(|| {
+                Error::InvalidArgument(format!("unknown watch resource type {value}"))
+            })?;
+            mask |= 1 << resource_type as u32;
+        }
+        Ok(WatchFilter { mask })
+    }
+
+    #[inline]
+    fn accept(&self, resource_type: ResourceType) -> bool {
+        self.mask & (1 << resource_type as u32) != 0
+    }
+
+    fn accept_update(&self, event: &UpdateEvent) -> bool {
+        use update_event::Event;
+
+        let resource_type = match &event.event {
+            Some(Event::Node(_)) => ResourceType::Node,
+            Some(Event::Group(_)) => ResourceType::Group,
+            Some(Event::GroupState(_)) => ResourceType::GroupState,
+            Some(Event::Database(_)) => ResourceType::Database,
+            Some(Event::Collection(_)) => ResourceType::Collection,
+            None => return false,
+        };
+        self.accept(resource_type)
+    }
+
+    fn accept_delete(&self, event: &DeleteEvent) -> bool {
+        use delete_event::Event;
+
+        let resource_type = match &event.event {
+            Some(Event::Node(_)) => ResourceType::Node,
+            Some(Event::Group(_)) => ResourceType::Group,
+            Some(Event::GroupState(_)) => ResourceType::GroupState,
+            Some(Event::Database(_)) => ResourceType::Database,
+            Some(Event::Collection(_)) => ResourceType::Collection,
+            None => return false,
+        };
+        self.accept(resource_type)
+    }
+}
+
+impl Default for WatchFilter {
+    fn default() -> Self {
+        WatchFilter { mask: u32::MAX }
+    }
+}
+
 pub struct WatcherInitializer<'a> {
     _guard: RwLockWriteGuard<'a, WatchHubInner>,
     watcher_inner: Arc<Mutex<WatcherInner>>,
@@ -43,16 +107,17 @@ pub struct WatcherInitializer<'a> {
 impl<'a> WatcherInitializer<'a> {
     pub fn set_init_resp(&mut self, updates: Vec<UpdateEvent>, deletes: Vec<DeleteEvent>) {
         let mut inner = self.watcher_inner.lock().unwrap();
-        inner.updates.extend_from_slice(&updates);
-        inner.deletes.extend_from_slice(&deletes);
+        let filter = inner.filter;
+        inner.updates.extend(updates.into_iter().filter(|e| filter.accept_update(e)));
+        inner.deletes.extend(deletes.into_iter().filter(|e| filter.accept_delete(e)));
     }
 }
 
 impl WatchHub {
-    pub async fn create_watcher(&self) -> (Watcher, WatcherInitializer) {
+    pub async fn create_watcher(&self, filter: WatchFilter) -> (Watcher, WatcherInitializer) {
         let mut inner = self.inner.write().await;
         inner.next_watcher_id += 1;
-        let watcher_inner = Arc::new(Mutex::new(WatcherInner::default()));
+        let watcher_inner = Arc::new(Mutex::new(WatcherInner { filter, ..Default::default() }));
         let watcher = Watcher { id: inner.next_watcher_id, inner: watcher_inner.to_owned() };
         inner.watchers.insert(watcher.id, watcher.to_owned());
         super::metrics::WATCH_TABLE_SIZE.set(inner.watchers.len() as i64);
@@ -105,6 +170,7 @@ pub struct Watcher {
 
 #[derive(Default)]
 struct WatcherInner {
+    filter: WatchFilter,
     waker: Option<Waker>,
     updates: Vec<UpdateEvent>,
     deletes: Vec<DeleteEvent>,
@@ -119,8 +185,15 @@ impl Watcher {
         if inner.dropped {
             return;
         }
-        inner.updates.extend_from_slice(updates); // TODO: set capcity limit
-        inner.deletes.extend_from_slice(deletes);
+        let filter = inner.filter;
+        let num_events = inner.updates.len() + inner.deletes.len();
+        // TODO: set capcity limit
+        inner.updates.extend(updates.iter().filter(|e| filter.accept_update(e)).cloned());
+        inner.deletes.extend(deletes.iter().filter(|e| filter.accept_delete(e)).cloned());
+        if num_events == inner.updates.len() + inner.deletes.len() && err.is_none() {
+            // No wakeups for the filtered events.
+            return;
+        }
         if err.is_some() && inner.err.is_none() {
             inner.err = err
         }
@@ -162,3 +235,31 @@ impl Drop for Watcher {
         inner.dropped = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::{DatabaseDesc, NodeDesc};
+
+    use super::*;
+
+    #[test]
+    fn filter_events_by_resource_type() {
+        let node = UpdateEvent { event: Some(update_event::Event::Node(NodeDesc::default())) };
+        let database =
+            UpdateEvent { event: Some(update_event::Event::Database(DatabaseDesc::default())) };
+        let delete_node = DeleteEvent { event: Some(delete_event::Event::Node(1)) };
+        let delete_collection = DeleteEvent { event: Some(delete_event::Event::Collection(1)) };
+
+        let filter = WatchFilter::new(&[]).unwrap();
+        assert!(filter.accept_update(&node) && filter.accept_update(&database));
+        assert!(filter.accept_delete(&delete_node) && filter.accept_delete(&delete_collection));
+
+        let filter = WatchFilter::new(&[ResourceType::Node as i32]).unwrap();
+        assert!(filter.accept_update(&node));
+        assert!(!filter.accept_update(&database));
+        assert!(filter.accept_delete(&delete_node));
+        assert!(!filter.accept_delete(&delete_collection));
+
+        assert!(WatchFilter::new(&[100]).is_err());
+    }
+}
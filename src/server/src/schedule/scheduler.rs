@@ -17,9 +17,10 @@ use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use futures::Future;
+use sekas_runtime::time::Instant;
 
 use super::event_source::EventSource;
 use super::task::{Task, TaskState};
@@ -291,7 +292,7 @@ impl TaskTimer {
 
     async fn timeout<T: Future<Output = ()>>(&self, f: T) {
         if let Some(event) = self.timer_heap.peek() {
-            let _ = tokio::time::timeout_at(event.deadline.into(), f).await;
+            let _ = sekas_runtime::time::timeout_at(event.deadline, f).await;
         } else {
             f.await;
         }
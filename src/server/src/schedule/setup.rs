@@ -98,6 +98,7 @@ async fn allocate_group_tasks(scheduler: &mut Scheduler, providers: Arc<GroupPro
         Box::new(PromoteGroup::new(providers.clone())),
         Box::new(DurableGroup::new(providers.clone())),
         Box::new(RemoveOrphanReplica::new(providers.clone())),
+        Box::new(RealizeClone::new(providers.clone())),
         Box::new(ReplicaMigration::new(providers)),
     ];
     scheduler.install_tasks(tasks);
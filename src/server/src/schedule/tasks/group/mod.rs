@@ -16,6 +16,7 @@ mod durable;
 mod migration;
 mod orphan_replica;
 mod promote;
+mod realize_clone;
 mod watch_descriptor;
 mod watch_raft_state;
 mod watch_replica_states;
@@ -28,6 +29,7 @@ pub use self::durable::DurableGroup;
 pub use self::migration::ReplicaMigration;
 pub use self::orphan_replica::RemoveOrphanReplica;
 pub use self::promote::PromoteGroup;
+pub use self::realize_clone::RealizeClone;
 pub use self::watch_descriptor::WatchGroupDescriptor;
 pub use self::watch_raft_state::WatchRaftState;
 pub use self::watch_replica_states::WatchReplicaStates;
@@ -0,0 +1,87 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::schedule::event_source::EventSource;
+use crate::schedule::provider::GroupProviders;
+use crate::schedule::scheduler::ScheduleContext;
+use crate::schedule::task::{Task, TaskState};
+use crate::schedule::tasks::REALIZE_CLONE_TASK_ID;
+
+/// Copy the data of the clone sources into the cloned shards chunk by chunk,
+/// so that the clone sources could be released.
+pub struct RealizeClone {
+    providers: Arc<GroupProviders>,
+    /// The shard being realized and the start key of its next chunk.
+    cursor: Option<(u64, Vec<u8>)>,
+}
+
+impl RealizeClone {
+    pub fn new(providers: Arc<GroupProviders>) -> Self {
+        RealizeClone { providers, cursor: None }
+    }
+
+    /// Return the shard to realize and the start key of its next chunk.
+    fn next_cursor(&self, ctx: &ScheduleContext<'_>) -> Option<(u64, Vec<u8>)> {
+        let desc = ctx.replica.descriptor();
+        // The moving shard is realized by the migration.
+        let moving_shard = ctx.replica.move_shard_state().map(|m| m.get_shard_desc().id);
+        let shards = desc
+            .shards
+            .iter()
+            .filter(|s| s.clone_source.is_some() && Some(s.id) != moving_shard)
+            .collect::<Vec<_>>();
+        match &self.cursor {
+            Some((shard_id, key)) if shards.iter().any(|s| s.id == *shard_id) => {
+                Some((*shard_id, key.clone()))
+            }
+            _ => shards.first().map(|s| (s.id, sekas_schema::shard::start_key(s))),
+        }
+    }
+}
+
+#[crate::async_trait]
+impl Task for RealizeClone {
+    fn id(&self) -> u64 {
+        REALIZE_CLONE_TASK_ID
+    }
+
+    async fn poll(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let Some((shard_id, start_key)) = self.next_cursor(ctx) else {
+            self.cursor = None;
+            self.providers.descriptor.watch(self.id());
+            return TaskState::Pending(None);
+        };
+
+        match ctx.replica.realize_clone(shard_id, &start_key).await {
+            Ok(Some(next_key)) => {
+                self.cursor = Some((shard_id, next_key));
+                TaskState::Pending(Some(Duration::from_millis(10)))
+            }
+            Ok(None) => {
+                info!("group {} shard {shard_id} clone source is realized", ctx.group_id);
+                self.cursor = None;
+                TaskState::Pending(Some(Duration::from_millis(10)))
+            }
+            Err(err) => {
+                warn!("group {} realize clone of shard {shard_id}: {err:?}", ctx.group_id);
+                TaskState::Pending(Some(Duration::from_secs(10)))
+            }
+        }
+    }
+}
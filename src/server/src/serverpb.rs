@@ -15,7 +15,7 @@
 #![allow(clippy::all)]
 
 pub mod v1 {
-    use sekas_api::server::v1::{MoveShardDesc, ShardDesc};
+    use sekas_api::server::v1::{IngestFile, MoveShardDesc, MoveShardProgress, ShardDesc};
 
     tonic::include_proto!("serverpb.v1");
 
@@ -31,6 +31,43 @@ pub mod v1 {
             })
         }
 
+        #[inline]
+        pub fn truncate_shard(shard_id: u64, new_shard: ShardDesc) -> Box<Self> {
+            Box::new(SyncOp {
+                truncate_shard: Some(TruncateShard { shard_id, new_shard: Some(new_shard) }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn freeze_shard(shard_id: u64, frozen: bool) -> Box<Self> {
+            Box::new(SyncOp {
+                freeze_shard: Some(FreezeShard { shard_id, frozen }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn split_shard(shard_id: u64, new_shard: ShardDesc) -> Box<Self> {
+            Box::new(SyncOp {
+                split_shard: Some(SplitShard { shard_id, new_shard: Some(new_shard) }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn realize_clone(
+            shard_id: u64,
+            start_key: Vec<u8>,
+            end_key: Vec<u8>,
+            finished: bool,
+        ) -> Box<Self> {
+            Box::new(SyncOp {
+                realize_clone: Some(RealizeClone { shard_id, start_key, end_key, finished }),
+                ..Default::default()
+            })
+        }
+
         #[inline]
         pub fn purge_replica(orphan_replica_id: u64) -> Box<Self> {
             Box::new(SyncOp {
@@ -50,12 +87,28 @@ pub mod v1 {
                 ..Default::default()
             })
         }
+
+        #[inline]
+        pub fn ingest_files(
+            shard_id: u64,
+            version: u64,
+            files: Vec<IngestFile>,
+            token: String,
+        ) -> Box<Self> {
+            Box::new(SyncOp {
+                ingest_files: Some(IngestFiles { shard_id, version, files, token }),
+                ..Default::default()
+            })
+        }
+
         #[inline]
-        pub fn ingest(key: Vec<u8>) -> Box<Self> {
+        pub fn ingest(key: Vec<u8>, moved_keys: u64, moved_bytes: u64) -> Box<Self> {
             Box::new(SyncOp {
                 move_shard: Some(MoveShard {
                     event: MoveShardEvent::Ingest as i32,
                     last_ingested_key: key,
+                    moved_keys,
+                    moved_bytes,
                     ..Default::default()
                 }),
                 ..Default::default()
@@ -78,6 +131,26 @@ pub mod v1 {
         pub fn get_shard_id(&self) -> u64 {
             self.get_shard_desc().id
         }
+
+        /// Return the progress of the moving, it is only tracked by the dest
+        /// group.
+        pub fn progress(&self) -> MoveShardProgress {
+            let moved = self.step == MoveShardStep::Moved as i32
+                || self.step == MoveShardStep::Finished as i32;
+            let percent = if moved {
+                100.0
+            } else if self.total_keys == 0 {
+                0.0
+            } else {
+                f32::min(self.moved_keys as f32 * 100.0 / self.total_keys as f32, 100.0)
+            };
+            MoveShardProgress {
+                moved_keys: self.moved_keys,
+                moved_bytes: self.moved_bytes,
+                total_keys: self.total_keys,
+                percent,
+            }
+        }
     }
 
     impl From<&raft::eraftpb::Entry> for EntryId {
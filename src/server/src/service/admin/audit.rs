@@ -0,0 +1,102 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+/// The default number of audit records to show.
+const DEFAULT_LIMIT: usize = 100;
+
+/// Show the audit records of admin and DDL operations, the newest one comes
+/// first.
+///
+/// Params:
+/// - `since`: optional, the unix timestamp in milliseconds, only the records
+///   since then are shown.
+/// - `limit`: optional, the max number of records to show.
+pub(super) struct AuditHandle {
+    server: Server,
+}
+
+impl AuditHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for AuditHandle {
+    async fn call(
+        &self,
+        path: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let since = match params.get("since") {
+            Some(since) => {
+                since.parse::<u64>().map_err(|_| Error::InvalidArgument("invalid since".into()))?
+            }
+            None => 0,
+        };
+        let limit = match params.get("limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidArgument("invalid limit".into()))?,
+            None => DEFAULT_LIMIT,
+        };
+        let records = match self.server.root.audit_records(since, limit).await {
+            Ok(records) => records,
+            Err(e @ Error::NotRootLeader(..)) => {
+                let root_desc = self.server.node.get_root().await;
+                let Some(node) = root_desc.root_nodes.first() else {
+                    return Err(e);
+                };
+                if node.id == self.server.root.current_node_id() {
+                    return Err(e);
+                }
+                let resp = http::Response::builder()
+                    .status(http::StatusCode::PERMANENT_REDIRECT)
+                    .header(
+                        http::header::LOCATION,
+                        format!("http://{}{}?since={since}&limit={limit}", node.addr, path),
+                    )
+                    .body("".into())
+                    .unwrap();
+                return Ok(resp);
+            }
+            Err(e) => return Err(e),
+        };
+        let records = records
+            .iter()
+            .map(|r| {
+                json!({
+                    "id": r.id,
+                    "time_ms": r.id / 1_000_000,
+                    "operator": r.operator,
+                    "action": r.action,
+                    "target": r.target,
+                    "error": r.error,
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(json!({ "records": records }).to_string())
+            .unwrap())
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use tonic::async_trait;
+use tonic::codegen::http;
+
+use crate::{Error, Result, Server};
+
+/// Create a consistent checkpoint of the engines of this node, a node could be
+/// started with the checkpoint as its root dir.
+///
+/// Params:
+/// - `dir`: the absolute path of the checkpoint, it must not exist.
+pub(super) struct CheckpointHandle {
+    server: Server,
+}
+
+impl CheckpointHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for CheckpointHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let dir = params
+            .get("dir")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::InvalidArgument("dir is required".into()))?;
+        if !dir.is_absolute() {
+            return Err(Error::InvalidArgument("dir must be an absolute path".into()));
+        }
+        self.server.node.checkpoint(dir).await?;
+        Ok(http::Response::builder().status(http::StatusCode::OK).body("".to_owned()).unwrap())
+    }
+}
@@ -13,11 +13,14 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::time::Duration;
 
 use serde_json::json;
 use tonic::async_trait;
 use tonic::codegen::http;
 
+use super::operator;
+use crate::root::AuditAction;
 use crate::{Result, Server};
 
 pub(super) struct CordonHandle {
@@ -42,7 +45,10 @@ impl super::service::HttpHandle for CordonHandle {
             .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
             .parse::<u64>()
             .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
-        self.server.root.cordon_node(node_id).await?;
+        let result = self.server.root.cordon_node(node_id).await;
+        let target = format!("node_id={node_id}");
+        self.server.root.audit(operator(params), AuditAction::CordonNode, target, &result).await;
+        result?;
         Ok(http::Response::builder().status(http::StatusCode::OK).body("".to_owned()).unwrap())
     }
 }
@@ -69,7 +75,10 @@ impl super::service::HttpHandle for UncordonHandle {
             .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
             .parse::<u64>()
             .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
-        self.server.root.uncordon_node(node_id).await?;
+        let result = self.server.root.uncordon_node(node_id).await;
+        let target = format!("node_id={node_id}");
+        self.server.root.audit(operator(params), AuditAction::UncordonNode, target, &result).await;
+        result?;
         Ok(http::Response::builder().status(http::StatusCode::OK).body("".to_owned()).unwrap())
     }
 }
@@ -96,11 +105,16 @@ impl super::service::HttpHandle for DrainHandle {
             .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
             .parse::<u64>()
             .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
-        self.server.root.begin_drain(node_id).await?;
+        let result = self.server.root.begin_drain(node_id).await;
+        let target = format!("node_id={node_id}");
+        self.server.root.audit(operator(params), AuditAction::DrainNode, target, &result).await;
+        result?;
         Ok(http::Response::builder().status(http::StatusCode::OK).body("".to_owned()).unwrap())
     }
 }
 
+/// Show the status of a node, and the number of replicas and leaders remaining
+/// on it, so the progress of draining could be polled.
 pub(super) struct StatusHandle {
     server: Server,
 }
@@ -124,9 +138,327 @@ impl super::service::HttpHandle for StatusHandle {
             .parse::<u64>()
             .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
         let status = self.server.root.node_status(node_id).await?;
+        let (num_replicas, num_leaders) = self.server.root.node_replica_stats(node_id).await?;
+        let body = json!({
+            "node_id": node_id,
+            "node_status": format!("{:?}", status).to_uppercase(),
+            "replicas": num_replicas,
+            "leaders": num_leaders,
+        });
+        Ok(http::Response::builder().status(http::StatusCode::OK).body(body.to_string()).unwrap())
+    }
+}
+
+pub(super) struct BumpClusterVersionHandle {
+    server: Server,
+}
+
+impl BumpClusterVersionHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for BumpClusterVersionHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let version = params
+            .get("version")
+            .map(|v| v.parse::<u64>())
+            .transpose()
+            .map_err(|_| crate::Error::InvalidArgument("illegal version".into()))?;
+        let result = self.server.root.bump_cluster_version(version).await;
+        let target = format!("version={version:?}");
+        let root = &self.server.root;
+        root.audit(operator(params), AuditAction::BumpClusterVersion, target, &result).await;
+        let cluster_version = result?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(json!({ "cluster_version": cluster_version }).to_string())
+            .unwrap())
+    }
+}
+
+pub(super) struct RotateDataKeyHandle {
+    server: Server,
+}
+
+impl RotateDataKeyHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for RotateDataKeyHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let result = self.server.root.rotate_data_key(true).await;
+        let target = String::new();
+        self.server.root.audit(operator(params), AuditAction::RotateDataKey, target, &result).await;
+        let key_id = result?;
         Ok(http::Response::builder()
             .status(http::StatusCode::OK)
-            .body(json!({ "node_id": node_id, "node_status": format!("{:?}", status).to_uppercase() }).to_string())
+            .body(json!({ "key_id": key_id }).to_string())
             .unwrap())
     }
 }
+
+pub(super) struct MoveReplicasHandle {
+    server: Server,
+}
+
+impl MoveReplicasHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for MoveReplicasHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = params
+            .get("group_id")
+            .ok_or_else(|| crate::Error::InvalidArgument("group_id is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| crate::Error::InvalidArgument("illegal group_id".into()))?;
+        let src_replicas = parse_id_list(params, "src_replicas")?;
+        let dest_nodes = parse_id_list(params, "dest_nodes")?;
+        let target = format!(
+            "group_id={group_id}, src_replicas={src_replicas:?}, dest_nodes={dest_nodes:?}"
+        );
+        let result = self.server.root.move_group_replicas(group_id, src_replicas, dest_nodes).await;
+        self.server.root.audit(operator(params), AuditAction::MoveReplicas, target, &result).await;
+        result?;
+        Ok(http::Response::builder().status(http::StatusCode::OK).body("".to_owned()).unwrap())
+    }
+}
+
+/// Move a shard to the group, and respond once the shard is served by the
+/// group.
+///
+/// Params:
+/// - `shard_id`: the shard to move.
+/// - `dest_group`: the group to move the shard to.
+/// - `timeout_sec`: optional, how long to wait for the moving, default 600.
+pub(super) struct MoveShardHandle {
+    server: Server,
+}
+
+impl MoveShardHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for MoveShardHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let shard_id = parse_id(params, "shard_id")?;
+        let dest_group = parse_id(params, "dest_group")?;
+        let timeout_sec = parse_optional_id(params, "timeout_sec")?.unwrap_or(600);
+        let root = &self.server.root;
+        let result = root.move_shard(shard_id, dest_group, Duration::from_secs(timeout_sec)).await;
+        let target = format!("shard_id={shard_id}, dest_group={dest_group}");
+        root.audit(operator(params), AuditAction::MoveShard, target, &result).await;
+        let body = serde_json::to_string(&result?).expect("ShardMove is serializable");
+        Ok(http::Response::builder().status(http::StatusCode::OK).body(body).unwrap())
+    }
+}
+
+/// Merge the donor group into the target group by a background job.
+///
+/// Params:
+/// - `donor_group`: the group whose shards are moved out, it is removed then.
+/// - `target_group`: the group to move the shards to.
+pub(super) struct MergeGroupHandle {
+    server: Server,
+}
+
+impl MergeGroupHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for MergeGroupHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let donor_group = parse_id(params, "donor_group")?;
+        let target_group = parse_id(params, "target_group")?;
+        let root = &self.server.root;
+        let result = root.merge_group(donor_group, target_group).await;
+        let target = format!("donor_group={donor_group}, target_group={target_group}");
+        root.audit(operator(params), AuditAction::MergeGroup, target, &result).await;
+        result?;
+        Ok(http::Response::builder().status(http::StatusCode::OK).body("".to_owned()).unwrap())
+    }
+}
+
+pub(super) struct UnsafeRecoverHandle {
+    server: Server,
+}
+
+impl UnsafeRecoverHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for UnsafeRecoverHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = parse_id(params, "group_id")?;
+        let replica_id = parse_id(params, "replica_id")?;
+        let confirm = parse_id(params, "confirm")?;
+        let result = self.server.root.unsafe_recover_group(group_id, replica_id, confirm).await;
+        let target = format!("group_id={group_id}, replica_id={replica_id}");
+        let root = &self.server.root;
+        root.audit(operator(params), AuditAction::UnsafeRecover, target, &result).await;
+        let desc = result?;
+        let body = format!("group {group_id} is recovered, epoch {}", desc.epoch);
+        Ok(http::Response::builder().status(http::StatusCode::OK).body(body).unwrap())
+    }
+}
+
+pub(super) struct MoveShardLimitHandle {
+    server: Server,
+}
+
+impl MoveShardLimitHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for MoveShardLimitHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let keys_per_sec = parse_optional_id(params, "keys_per_sec")?;
+        let bytes_per_sec = parse_optional_id(params, "bytes_per_sec")?;
+        let backoff_latency_ms = parse_optional_id(params, "backoff_latency_ms")?;
+        let limit = if keys_per_sec.is_none()
+            && bytes_per_sec.is_none()
+            && backoff_latency_ms.is_none()
+        {
+            self.server.root.move_shard_limit().await?
+        } else {
+            let root = &self.server.root;
+            let result =
+                root.update_move_shard_limit(keys_per_sec, bytes_per_sec, backoff_latency_ms).await;
+            let target = format!(
+                "keys_per_sec={keys_per_sec:?}, bytes_per_sec={bytes_per_sec:?}, \
+                     backoff_latency_ms={backoff_latency_ms:?}"
+            );
+            root.audit(operator(params), AuditAction::UpdateMoveShardLimit, target, &result).await;
+            result?
+        };
+        let body = json!({
+            "keys_per_sec": limit.keys_per_sec,
+            "bytes_per_sec": limit.bytes_per_sec,
+            "backoff_latency_ms": limit.backoff_latency_ms,
+        });
+        Ok(http::Response::builder().status(http::StatusCode::OK).body(body.to_string()).unwrap())
+    }
+}
+
+/// Show the hot reloadable options, or set one of them if both `key` and
+/// `value` are specified.
+pub(super) struct ConfigHandle {
+    server: Server,
+}
+
+impl ConfigHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for ConfigHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let root = &self.server.root;
+        match (params.get("key"), params.get("value")) {
+            (Some(key), Some(value)) => {
+                let result = root.set_config(key, value).await;
+                let target = format!("{key}={value}");
+                root.audit(operator(params), AuditAction::SetConfig, target, &result).await;
+                result?;
+            }
+            (None, None) => {}
+            _ => {
+                return Err(crate::Error::InvalidArgument("both key and value are required".into()))
+            }
+        }
+        let options = root
+            .list_config()?
+            .into_iter()
+            .map(|(key, value)| (key, serde_json::Value::String(value)))
+            .collect::<serde_json::Map<_, _>>();
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::Value::Object(options).to_string())
+            .unwrap())
+    }
+}
+
+fn parse_id(params: &HashMap<String, String>, name: &str) -> Result<u64> {
+    params
+        .get(name)
+        .ok_or_else(|| crate::Error::InvalidArgument(format!("{name} is required")))?
+        .parse::<u64>()
+        .map_err(|_| crate::Error::InvalidArgument(format!("illegal {name}")))
+}
+
+fn parse_optional_id(params: &HashMap<String, String>, name: &str) -> Result<Option<u64>> {
+    params
+        .get(name)
+        .map(|v| v.parse::<u64>())
+        .transpose()
+        .map_err(|_| crate::Error::InvalidArgument(format!("illegal {name}")))
+}
+
+/// Parse a comma separated id list, eg. `1,2,3`.
+fn parse_id_list(params: &HashMap<String, String>, name: &str) -> Result<Vec<u64>> {
+    params
+        .get(name)
+        .ok_or_else(|| crate::Error::InvalidArgument(format!("{name} is required")))?
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<u64>()
+                .map_err(|_| crate::Error::InvalidArgument(format!("illegal {name}")))
+        })
+        .collect()
+}
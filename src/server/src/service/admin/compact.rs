@@ -0,0 +1,68 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use sekas_api::server::v1::RangePartition;
+use tonic::async_trait;
+use tonic::codegen::http;
+
+use crate::{Error, Result, Server};
+
+/// Compact the group engine of the local replica manually.
+///
+/// Params:
+/// - `group_id`: the group to compact.
+/// - `shard_id`: optional, only compact the keys of the shard.
+/// - `start`, `end`: optional, only compact the keys of the shard in the range.
+pub(super) struct CompactHandle {
+    server: Server,
+}
+
+impl CompactHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for CompactHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = params
+            .get("group_id")
+            .ok_or_else(|| Error::InvalidArgument("group_id is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| Error::InvalidArgument("illegal group_id".into()))?;
+        let range = match params.get("shard_id") {
+            Some(shard_id) => {
+                let shard_id = shard_id
+                    .parse::<u64>()
+                    .map_err(|_| Error::InvalidArgument("illegal shard_id".into()))?;
+                let start = params.get("start").map(|v| v.as_bytes().to_vec()).unwrap_or_default();
+                let end = params.get("end").map(|v| v.as_bytes().to_vec()).unwrap_or_default();
+                Some((shard_id, RangePartition { start, end }))
+            }
+            None if params.contains_key("start") || params.contains_key("end") => {
+                return Err(Error::InvalidArgument("shard_id is required for range".into()));
+            }
+            None => None,
+        };
+        self.server.node.compact_group(group_id, range).await?;
+        Ok(http::Response::builder().status(http::StatusCode::OK).body("".to_owned()).unwrap())
+    }
+}
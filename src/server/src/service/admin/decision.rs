@@ -0,0 +1,77 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+/// The default number of decisions to show.
+const DEFAULT_LIMIT: usize = 100;
+
+/// Show the latest reconcile decisions of the root scheduler, with the reasons
+/// and the compared scores.
+pub(super) struct DecisionHandle {
+    server: Server,
+}
+
+impl DecisionHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for DecisionHandle {
+    async fn call(
+        &self,
+        path: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let limit = match params.get("limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidArgument("invalid limit".into()))?,
+            None => DEFAULT_LIMIT,
+        };
+        let decisions = match self.server.root.decisions(limit) {
+            Ok(decisions) => decisions,
+            Err(e @ Error::NotRootLeader(..)) => {
+                let root_desc = self.server.node.get_root().await;
+                let node = root_desc.root_nodes.first();
+                if node.is_none() {
+                    return Err(e);
+                }
+                if node.as_ref().unwrap().id == self.server.root.current_node_id() {
+                    return Err(e);
+                }
+                let resp = http::Response::builder()
+                    .status(http::StatusCode::PERMANENT_REDIRECT)
+                    .header(
+                        http::header::LOCATION,
+                        format!("http://{}{}?limit={}", node.unwrap().addr, path, limit),
+                    )
+                    .body("".into())
+                    .unwrap();
+                return Ok(resp);
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&decisions).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use tonic::async_trait;
+use tonic::codegen::http;
+
+use crate::{Error, Result, Server};
+
+/// Dump the data and raft state of a local replica into a directory as JSON
+/// files, to debug the corruption or divergence of replicas offline.
+///
+/// Params:
+/// - `group_id`, `replica_id`: the replica to dump.
+/// - `path`: the absolute path of the dump directory, it must not exist.
+pub(super) struct DumpReplicaHandle {
+    server: Server,
+}
+
+impl DumpReplicaHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for DumpReplicaHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = parse_u64(params, "group_id")?;
+        let replica_id = parse_u64(params, "replica_id")?;
+        let path = params
+            .get("path")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::InvalidArgument("path is required".into()))?;
+        if !path.is_absolute() {
+            return Err(Error::InvalidArgument("path must be an absolute path".into()));
+        }
+        let summary = self.server.node.dump_replica(group_id, replica_id, path).await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&summary).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
+
+fn parse_u64(params: &HashMap<String, String>, name: &str) -> Result<u64> {
+    params
+        .get(name)
+        .ok_or_else(|| Error::InvalidArgument(format!("{name} is required")))?
+        .parse::<u64>()
+        .map_err(|_| Error::InvalidArgument(format!("illegal {name}")))
+}
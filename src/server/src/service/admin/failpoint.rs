@@ -0,0 +1,53 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{failpoint, Error, Result};
+
+/// Configure the failpoints, the failpoints are shared by all nodes in the
+/// same process.
+///
+/// Params:
+/// - `name`, `actions`: configure the actions of the failpoint, the `off`
+///   action removes it.
+/// - `clear`: remove all failpoints.
+///
+/// The configured failpoints are returned.
+pub(super) struct FailpointHandle;
+
+#[async_trait]
+impl super::service::HttpHandle for FailpointHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        if params.contains_key("clear") {
+            failpoint::clear();
+        } else if let Some(name) = params.get("name") {
+            let actions = params
+                .get("actions")
+                .ok_or_else(|| Error::InvalidArgument("actions is required".into()))?;
+            failpoint::configure(name, actions)?;
+        }
+        let failpoints = failpoint::list().into_iter().collect::<HashMap<_, _>>();
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&failpoints).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+/// The default number of group events to show.
+const DEFAULT_LIMIT: usize = 100;
+
+/// Show the history of a group observed by the root, including the membership
+/// changes, leader transfers, shard moves and epoch bumps. The newest event
+/// comes first.
+///
+/// Params:
+/// - `group`: the id of the group.
+/// - `since`: optional, the unix timestamp in milliseconds, only the events
+///   since then are shown.
+/// - `limit`: optional, the max number of events to show.
+pub(super) struct GroupHistoryHandle {
+    server: Server,
+}
+
+impl GroupHistoryHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for GroupHistoryHandle {
+    async fn call(
+        &self,
+        path: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = params
+            .get("group")
+            .ok_or_else(|| Error::InvalidArgument("group is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| Error::InvalidArgument("invalid group".into()))?;
+        let since = match params.get("since") {
+            Some(since) => {
+                since.parse::<u64>().map_err(|_| Error::InvalidArgument("invalid since".into()))?
+            }
+            None => 0,
+        };
+        let limit = match params.get("limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidArgument("invalid limit".into()))?,
+            None => DEFAULT_LIMIT,
+        };
+        let events = match self.server.root.group_history(group_id, since, limit).await {
+            Ok(events) => events,
+            Err(e @ Error::NotRootLeader(..)) => {
+                let root_desc = self.server.node.get_root().await;
+                let Some(node) = root_desc.root_nodes.first() else {
+                    return Err(e);
+                };
+                if node.id == self.server.root.current_node_id() {
+                    return Err(e);
+                }
+                let resp = http::Response::builder()
+                    .status(http::StatusCode::PERMANENT_REDIRECT)
+                    .header(
+                        http::header::LOCATION,
+                        format!(
+                            "http://{}{}?group={group_id}&since={since}&limit={limit}",
+                            node.addr, path
+                        ),
+                    )
+                    .body("".into())
+                    .unwrap();
+                return Ok(resp);
+            }
+            Err(e) => return Err(e),
+        };
+        let events = events
+            .iter()
+            .map(|e| {
+                json!({
+                    "id": e.id,
+                    "time_ms": e.id / 1_000_000,
+                    "kind": e.kind,
+                    "epoch": e.epoch,
+                    "detail": e.detail,
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(json!({ "group_id": group_id, "events": events }).to_string())
+            .unwrap())
+    }
+}
@@ -23,12 +23,22 @@ pub(super) struct MetadataHandle {
     server: Server,
 }
 
+pub(super) struct ClusterInfoHandle {
+    server: Server,
+}
+
 impl MetadataHandle {
     pub fn new(server: Server) -> Self {
         Self { server }
     }
 }
 
+impl ClusterInfoHandle {
+    pub fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
 #[crate::async_trait]
 impl super::service::HttpHandle for MetadataHandle {
     async fn call(
@@ -39,26 +49,47 @@ impl super::service::HttpHandle for MetadataHandle {
         let info = match self.server.root.info().await {
             Ok(info) => serde_json::to_string(&info).unwrap(),
             Err(e @ crate::Error::NotRootLeader(..)) => {
-                let root_desc = self.server.node.get_root().await;
-                let node = root_desc.root_nodes.first();
-                if node.is_none() {
-                    return Err(e);
-                }
-                if node.as_ref().unwrap().id == self.server.root.current_node_id() {
-                    return Err(e);
-                }
-                let resp = http::Response::builder()
-                    .status(http::StatusCode::PERMANENT_REDIRECT)
-                    .header(
-                        http::header::LOCATION,
-                        format!("http://{}{}", node.unwrap().addr, path),
-                    )
-                    .body("".into())
-                    .unwrap();
-                return Ok(resp);
+                return redirect_to_root(&self.server, path, e).await;
             }
             Err(e) => return Err(e),
         };
         Ok(http::Response::builder().status(http::StatusCode::OK).body(info).unwrap())
     }
 }
+
+#[crate::async_trait]
+impl super::service::HttpHandle for ClusterInfoHandle {
+    async fn call(
+        &self,
+        path: &str,
+        _: &HashMap<String, String>,
+    ) -> crate::Result<http::Response<String>> {
+        let info = match self.server.root.cluster_info().await {
+            Ok(info) => serde_json::to_string(&info).unwrap(),
+            Err(e @ crate::Error::NotRootLeader(..)) => {
+                return redirect_to_root(&self.server, path, e).await;
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(http::Response::builder().status(http::StatusCode::OK).body(info).unwrap())
+    }
+}
+
+/// Redirect the request to the first root node, if it is not the current node.
+async fn redirect_to_root(
+    server: &Server,
+    path: &str,
+    err: crate::Error,
+) -> crate::Result<http::Response<String>> {
+    let root_desc = server.node.get_root().await;
+    let node = match root_desc.root_nodes.first() {
+        Some(node) if node.id != server.root.current_node_id() => node,
+        _ => return Err(err),
+    };
+    let resp = http::Response::builder()
+        .status(http::StatusCode::PERMANENT_REDIRECT)
+        .header(http::header::LOCATION, format!("http://{}{}", node.addr, path))
+        .body("".into())
+        .unwrap();
+    Ok(resp)
+}
@@ -12,16 +12,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod audit;
+mod checkpoint;
 mod cluster;
+mod compact;
+mod decision;
+mod dump_replica;
+#[cfg(feature = "failpoints")]
+mod failpoint;
+mod group_history;
 mod health;
 mod job;
 mod metadata;
 mod metrics;
 mod monitor;
+mod raft_peers;
+mod schema;
 mod service;
+mod startup_report;
+mod tenant;
+mod user;
 
+use std::collections::HashMap;
+
+use self::schema::{SchemaHandle, SchemaOp};
 pub use self::service::AdminService;
 use self::service::Router;
+use self::tenant::{TenantHandle, TenantOp};
+use self::user::{UserHandle, UserOp};
 use crate::Server;
 
 pub fn make_admin_service(server: Server) -> AdminService {
@@ -29,12 +47,89 @@ pub fn make_admin_service(server: Server) -> AdminService {
         .route("/metrics", self::metrics::MetricsHandle::new(server.to_owned()))
         .route("/job", self::job::JobHandle::new(server.to_owned()))
         .route("/metadata", self::metadata::MetadataHandle::new(server.to_owned()))
+        .route("/cluster", self::metadata::ClusterInfoHandle::new(server.to_owned()))
         .route("/health", self::health::HealthHandle)
         .route("/cordon", self::cluster::CordonHandle::new(server.to_owned()))
         .route("/uncordon", self::cluster::UncordonHandle::new(server.to_owned()))
         .route("/drain", self::cluster::DrainHandle::new(server.to_owned()))
         .route("/node_status", self::cluster::StatusHandle::new(server.to_owned()))
+        .route(
+            "/bump_cluster_version",
+            self::cluster::BumpClusterVersionHandle::new(server.to_owned()),
+        )
+        .route("/rotate_data_key", self::cluster::RotateDataKeyHandle::new(server.to_owned()))
+        .route("/move_replicas", self::cluster::MoveReplicasHandle::new(server.to_owned()))
+        .route("/move_shard", self::cluster::MoveShardHandle::new(server.to_owned()))
+        .route("/merge_group", self::cluster::MergeGroupHandle::new(server.to_owned()))
+        .route("/unsafe_recover", self::cluster::UnsafeRecoverHandle::new(server.to_owned()))
+        .route("/move_shard_limit", self::cluster::MoveShardLimitHandle::new(server.to_owned()))
+        .route("/config", self::cluster::ConfigHandle::new(server.to_owned()))
+        .route("/compact", self::compact::CompactHandle::new(server.to_owned()))
+        .route("/checkpoint", self::checkpoint::CheckpointHandle::new(server.to_owned()))
+        .route("/dump_replica", self::dump_replica::DumpReplicaHandle::new(server.to_owned()))
+        .route("/decisions", self::decision::DecisionHandle::new(server.to_owned()))
+        .route("/audit", self::audit::AuditHandle::new(server.to_owned()))
+        .route("/group_history", self::group_history::GroupHistoryHandle::new(server.to_owned()))
+        .route("/raft_peers", self::raft_peers::RaftPeersHandle::new(server.to_owned()))
+        .route("/startup_report", self::startup_report::StartupReportHandle::new(server.to_owned()))
+        .route("/create_database", SchemaHandle::new(server.to_owned(), SchemaOp::CreateDatabase))
+        .route("/list_databases", SchemaHandle::new(server.to_owned(), SchemaOp::ListDatabases))
+        .route("/delete_database", SchemaHandle::new(server.to_owned(), SchemaOp::DeleteDatabase))
+        .route("/rename_database", SchemaHandle::new(server.to_owned(), SchemaOp::RenameDatabase))
+        .route("/recover_database", SchemaHandle::new(server.to_owned(), SchemaOp::RecoverDatabase))
+        .route(
+            "/create_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::CreateCollection),
+        )
+        .route("/list_collections", SchemaHandle::new(server.to_owned(), SchemaOp::ListCollections))
+        .route(
+            "/delete_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::DeleteCollection),
+        )
+        .route(
+            "/rename_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::RenameCollection),
+        )
+        .route(
+            "/truncate_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::TruncateCollection),
+        )
+        .route(
+            "/freeze_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::FreezeCollection),
+        )
+        .route(
+            "/unfreeze_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::UnfreezeCollection),
+        )
+        .route(
+            "/recover_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::RecoverCollection),
+        )
+        .route("/collection_stats", SchemaHandle::new(server.to_owned(), SchemaOp::CollectionStats))
+        .route(
+            "/scatter_collection",
+            SchemaHandle::new(server.to_owned(), SchemaOp::ScatterCollection),
+        )
+        .route("/hot_keys", SchemaHandle::new(server.to_owned(), SchemaOp::HotKeys))
+        .route("/trash", SchemaHandle::new(server.to_owned(), SchemaOp::ListTrash))
+        .route("/create_user", UserHandle::new(server.to_owned(), UserOp::CreateUser))
+        .route("/delete_user", UserHandle::new(server.to_owned(), UserOp::DeleteUser))
+        .route("/list_users", UserHandle::new(server.to_owned(), UserOp::ListUsers))
+        .route("/reset_token", UserHandle::new(server.to_owned(), UserOp::ResetToken))
+        .route("/grant", UserHandle::new(server.to_owned(), UserOp::Grant))
+        .route("/create_tenant", TenantHandle::new(server.to_owned(), TenantOp::CreateTenant))
+        .route("/delete_tenant", TenantHandle::new(server.to_owned(), TenantOp::DeleteTenant))
+        .route("/set_tenant_quota", TenantHandle::new(server.to_owned(), TenantOp::SetQuota))
+        .route("/tenants", TenantHandle::new(server.to_owned(), TenantOp::ListTenants))
         .route("/monitor", self::monitor::MonitorHandle::new(server));
+    #[cfg(feature = "failpoints")]
+    let router = router.route("/failpoints", self::failpoint::FailpointHandle);
     let api = Router::nest("/admin", router);
     AdminService::new(api)
 }
+
+/// Return who issues the request, it is filled by [`AdminService`].
+fn operator(params: &HashMap<String, String>) -> &str {
+    params.get("operator").map(String::as_str).unwrap_or("unknown")
+}
@@ -0,0 +1,393 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use sekas_api::server::v1::{
+    CollectionDesc, CompressionCodec, DatabaseDesc, KeyEncoding, KeySchema,
+};
+use serde_json::json;
+use tonic::async_trait;
+use tonic::codegen::http;
+
+use super::operator;
+use crate::root::{AuditAction, Root};
+use crate::{Error, Result, Server};
+
+/// The max length of the names of databases and collections.
+const MAX_NAME_LEN: usize = 255;
+
+/// The default number of hot keys to show.
+const DEFAULT_HOT_KEYS_LIMIT: usize = 20;
+
+#[derive(Clone, Copy)]
+pub(super) enum SchemaOp {
+    CreateDatabase,
+    ListDatabases,
+    DeleteDatabase,
+    RenameDatabase,
+    RecoverDatabase,
+    CreateCollection,
+    ListCollections,
+    DeleteCollection,
+    RenameCollection,
+    TruncateCollection,
+    FreezeCollection,
+    UnfreezeCollection,
+    RecoverCollection,
+    CollectionStats,
+    ScatterCollection,
+    HotKeys,
+    ListTrash,
+}
+
+/// Manage the databases and collections.
+///
+/// Params:
+/// - `tenant`: optional, the name of the tenant owning the databases, the
+///   default tenant is used if it is absent.
+/// - `database`: the name of database, required except for listing databases,
+///   the trash and the hot keys.
+/// - `collection`: the name of collection, required for creating, deleting,
+///   renaming, truncating, freezing, recovering, scattering collections and the
+///   stats of collection. It is optional for the hot keys, the hot keys of the
+///   whole cluster are shown if both `database` and `collection` are absent.
+/// - `limit`: optional, the number of hot keys to show, default 20.
+/// - `new_name`: the new name of the renamed database or collection.
+/// - `encrypted`: optional, whether to encrypt the created collection.
+/// - `compression`: optional, the compression codec of the created collection,
+///   `uncompressed`, `lz4` or `zstd`.
+/// - `key_encoding`: optional, the encoding of the keys of the created
+///   collection, `bytes`, `fixed_length`, `u64_be` or `utf8`.
+/// - `key_length`: the length of keys, required by `fixed_length`.
+/// - `constraints`: optional, the comma separated node labels required by the
+///   replicas of the created collection, eg `disk=ssd,region=us-east`.
+///
+/// The errors are responded as JSON: `{"error": {"code": .., "message": ..}}`.
+pub(super) struct SchemaHandle {
+    server: Server,
+    op: SchemaOp,
+}
+
+impl SchemaHandle {
+    pub(crate) fn new(server: Server, op: SchemaOp) -> Self {
+        Self { server, op }
+    }
+
+    async fn execute(&self, params: &HashMap<String, String>) -> Result<serde_json::Value> {
+        let root = &self.server.root;
+        match self.op {
+            SchemaOp::CreateDatabase => {
+                let tenant = optional_tenant(params)?;
+                let name = required_name(params, "database")?;
+                let result = root.create_database(tenant, name.to_owned()).await;
+                let target = format!("tenant={tenant}, database={name}");
+                root.audit(operator(params), AuditAction::CreateDatabase, target, &result).await;
+                Ok(database_json(&result?))
+            }
+            SchemaOp::ListDatabases => {
+                let databases = root.list_database(optional_tenant(params)?).await?;
+                Ok(json!({ "databases": databases.iter().map(database_json).collect::<Vec<_>>() }))
+            }
+            SchemaOp::DeleteDatabase => {
+                let tenant = optional_tenant(params)?;
+                let name = required_name(params, "database")?;
+                let result = root.delete_database(tenant, name).await;
+                let target = format!("tenant={tenant}, database={name}");
+                root.audit(operator(params), AuditAction::DeleteDatabase, target, &result).await;
+                result?;
+                Ok(json!({}))
+            }
+            SchemaOp::RenameDatabase => {
+                let tenant = optional_tenant(params)?;
+                let name = required_name(params, "database")?;
+                let new_name = required_name(params, "new_name")?;
+                let result = root.rename_database(tenant, name, new_name.to_owned()).await;
+                let target = format!("tenant={tenant}, database={name}, new_name={new_name}");
+                root.audit(operator(params), AuditAction::RenameDatabase, target, &result).await;
+                Ok(database_json(&result?))
+            }
+            SchemaOp::RecoverDatabase => {
+                let tenant = optional_tenant(params)?;
+                let name = required_name(params, "database")?;
+                let result = root.recover_database(tenant, name).await;
+                let target = format!("tenant={tenant}, database={name}");
+                root.audit(operator(params), AuditAction::RecoverDatabase, target, &result).await;
+                Ok(database_json(&result?))
+            }
+            SchemaOp::CreateCollection => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let encrypted = match params.get("encrypted") {
+                    Some(v) => v
+                        .parse::<bool>()
+                        .map_err(|_| Error::InvalidArgument("illegal encrypted".into()))?,
+                    None => false,
+                };
+                let compression = match params.get("compression") {
+                    Some(v) => CompressionCodec::from_str_name(&v.to_uppercase())
+                        .ok_or_else(|| Error::InvalidArgument("illegal compression".into()))?,
+                    None => CompressionCodec::default(),
+                };
+                let key_schema = match params.get("key_encoding") {
+                    Some(v) => {
+                        let encoding = KeyEncoding::from_str_name(&v.to_uppercase())
+                            .ok_or_else(|| Error::InvalidArgument("illegal key_encoding".into()))?;
+                        let length = match params.get("key_length") {
+                            Some(v) => v
+                                .parse::<u32>()
+                                .map_err(|_| Error::InvalidArgument("illegal key_length".into()))?,
+                            None => 0,
+                        };
+                        Some(KeySchema { encoding: encoding as i32, length })
+                    }
+                    None => None,
+                };
+                let constraints = params
+                    .get("constraints")
+                    .map(|v| v.split(',').map(|c| c.trim().to_owned()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let result = root
+                    .create_collection(
+                        name.to_owned(),
+                        &database,
+                        encrypted,
+                        compression,
+                        key_schema,
+                        constraints,
+                    )
+                    .await;
+                let target = format!("database={}, collection={name}", database.name);
+                root.audit(operator(params), AuditAction::CreateCollection, target, &result).await;
+                Ok(collection_json(&database.name, &result?))
+            }
+            SchemaOp::ListCollections => {
+                let database = get_database(root, params).await?;
+                let collections = root.list_collection(&database).await?;
+                let collections = collections
+                    .iter()
+                    .map(|c| collection_json(&database.name, c))
+                    .collect::<Vec<_>>();
+                Ok(json!({ "collections": collections }))
+            }
+            SchemaOp::DeleteCollection => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let result = root.delete_collection(name, &database).await;
+                let target = format!("database={}, collection={name}", database.name);
+                root.audit(operator(params), AuditAction::DeleteCollection, target, &result).await;
+                result?;
+                Ok(json!({}))
+            }
+            SchemaOp::RenameCollection => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let new_name = required_name(params, "new_name")?;
+                let result = root.rename_collection(&database, name, new_name.to_owned()).await;
+                let target =
+                    format!("database={}, collection={name}, new_name={new_name}", database.name);
+                root.audit(operator(params), AuditAction::RenameCollection, target, &result).await;
+                Ok(collection_json(&database.name, &result?))
+            }
+            SchemaOp::TruncateCollection => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let result = root.truncate_collection(&database, name).await;
+                let target = format!("database={}, collection={name}", database.name);
+                root.audit(operator(params), AuditAction::TruncateCollection, target, &result)
+                    .await;
+                Ok(collection_json(&database.name, &result?))
+            }
+            SchemaOp::FreezeCollection | SchemaOp::UnfreezeCollection => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let (frozen, action) = match self.op {
+                    SchemaOp::FreezeCollection => (true, AuditAction::FreezeCollection),
+                    _ => (false, AuditAction::UnfreezeCollection),
+                };
+                let result = root.freeze_collection(&database, name, frozen).await;
+                let target = format!("database={}, collection={name}", database.name);
+                root.audit(operator(params), action, target, &result).await;
+                Ok(collection_json(&database.name, &result?))
+            }
+            SchemaOp::RecoverCollection => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let result = root.recover_collection(&database, name).await;
+                let target = format!("database={}, collection={name}", database.name);
+                root.audit(operator(params), AuditAction::RecoverCollection, target, &result).await;
+                Ok(collection_json(&database.name, &result?))
+            }
+            SchemaOp::CollectionStats => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let stats = root.collection_stats(&database, name).await?;
+                Ok(serde_json::to_value(stats).expect("CollectionStats is serializable"))
+            }
+            SchemaOp::ScatterCollection => {
+                let database = get_database(root, params).await?;
+                let name = required_name(params, "collection")?;
+                let result = root.scatter_collection(&database, name).await;
+                let target = format!("database={}, collection={name}", database.name);
+                root.audit(operator(params), AuditAction::ScatterCollection, target, &result).await;
+                Ok(json!({ "moves": result? }))
+            }
+            SchemaOp::HotKeys => {
+                let limit = match params.get("limit") {
+                    Some(limit) => limit
+                        .parse::<usize>()
+                        .map_err(|_| Error::InvalidArgument("invalid limit".into()))?,
+                    None => DEFAULT_HOT_KEYS_LIMIT,
+                };
+                let collection_id = if params.contains_key("database") {
+                    let database = get_database(root, params).await?;
+                    let name = required_name(params, "collection")?;
+                    let co = root.get_collection(name, &database).await?.ok_or_else(|| {
+                        Error::InvalidArgument(format!("collection {name} not found"))
+                    })?;
+                    Some(co.id)
+                } else {
+                    None
+                };
+                let hot_keys = root.hot_keys(collection_id, limit).await?;
+                Ok(json!({ "hot_keys": hot_keys }))
+            }
+            SchemaOp::ListTrash => Ok(json!({ "trash": root.trash_state().await? })),
+        }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for SchemaHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let (status, body) = match self.execute(params).await {
+            Ok(body) => (http::StatusCode::OK, body),
+            Err(err) => error_json(err)?,
+        };
+        Ok(http::Response::builder()
+            .status(status)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .unwrap())
+    }
+}
+
+pub(super) fn required_name<'a>(params: &'a HashMap<String, String>, key: &str) -> Result<&'a str> {
+    let name =
+        params.get(key).ok_or_else(|| Error::InvalidArgument(format!("{key} is required")))?;
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(Error::InvalidArgument(format!(
+            "the length of {key} should be in [1, {MAX_NAME_LEN}]"
+        )));
+    }
+    if name.chars().any(|c| c.is_control() || c == '/') {
+        return Err(Error::InvalidArgument(format!("{key} contains illegal characters")));
+    }
+    Ok(name)
+}
+
+/// Return the name of the tenant in params, the empty name means the default
+/// tenant.
+pub(super) fn optional_tenant(params: &HashMap<String, String>) -> Result<&str> {
+    match params.get("tenant") {
+        Some(_) => required_name(params, "tenant"),
+        None => Ok(""),
+    }
+}
+
+/// Get the database by the `tenant` and `database` params.
+pub(super) async fn get_database(
+    root: &Root,
+    params: &HashMap<String, String>,
+) -> Result<DatabaseDesc> {
+    let tenant = optional_tenant(params)?;
+    let name = required_name(params, "database")?;
+    root.get_database(tenant, name).await?.ok_or_else(|| Error::DatabaseNotFound(name.to_owned()))
+}
+
+fn database_json(desc: &DatabaseDesc) -> serde_json::Value {
+    json!({ "id": desc.id, "name": desc.name, "tenant_id": desc.tenant_id })
+}
+
+fn collection_json(database: &str, desc: &CollectionDesc) -> serde_json::Value {
+    let compression = CompressionCodec::from_i32(desc.compression).unwrap_or_default();
+    let key_schema = desc.key_schema.clone().unwrap_or_default();
+    let key_encoding = KeyEncoding::from_i32(key_schema.encoding).unwrap_or_default();
+    json!({
+        "id": desc.id,
+        "name": desc.name,
+        "database": database,
+        "encrypted": desc.encrypted,
+        "compression": compression.as_str_name().to_lowercase(),
+        "key_encoding": key_encoding.as_str_name().to_lowercase(),
+        "key_length": key_schema.length,
+        "constraints": desc.constraints,
+        "frozen": desc.frozen,
+    })
+}
+
+/// Convert the business errors to the structured JSON, the other errors are
+/// returned as is.
+pub(super) fn error_json(err: Error) -> Result<(http::StatusCode, serde_json::Value)> {
+    let (status, code) = match &err {
+        Error::InvalidArgument(_) => (http::StatusCode::BAD_REQUEST, "invalid_argument"),
+        Error::AlreadyExists(_) => (http::StatusCode::CONFLICT, "already_exists"),
+        Error::DatabaseNotFound(_) => (http::StatusCode::NOT_FOUND, "not_found"),
+        Error::ResourceExhausted(_) => (http::StatusCode::TOO_MANY_REQUESTS, "resource_exhausted"),
+        Error::CollectionFrozen(_) => (http::StatusCode::CONFLICT, "collection_frozen"),
+        Error::NodeNotFound(_) => (http::StatusCode::NOT_FOUND, "not_found"),
+        Error::NodeStatusNotMatch(..) => (http::StatusCode::CONFLICT, "node_status_not_match"),
+        Error::NotRootLeader(..) => (http::StatusCode::SERVICE_UNAVAILABLE, "not_root_leader"),
+        _ => return Err(err),
+    };
+    Ok((status, json!({ "error": { "code": code, "message": err.to_string() } })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_name() {
+        let params = HashMap::from([
+            ("database".to_owned(), "db1".to_owned()),
+            ("empty".to_owned(), String::new()),
+            ("slash".to_owned(), "a/b".to_owned()),
+            ("long".to_owned(), "a".repeat(MAX_NAME_LEN + 1)),
+        ]);
+        assert_eq!(required_name(&params, "database").unwrap(), "db1");
+        assert!(required_name(&params, "collection").is_err());
+        assert!(required_name(&params, "empty").is_err());
+        assert!(required_name(&params, "slash").is_err());
+        assert!(required_name(&params, "long").is_err());
+
+        assert_eq!(optional_tenant(&params).unwrap(), "");
+        let params = HashMap::from([("tenant".to_owned(), "a/b".to_owned())]);
+        assert!(optional_tenant(&params).is_err());
+    }
+
+    #[test]
+    fn business_errors_to_json() {
+        let (status, body) = error_json(Error::AlreadyExists("database db1".into())).unwrap();
+        assert_eq!(status, http::StatusCode::CONFLICT);
+        assert_eq!(body["error"]["code"], "already_exists");
+        assert_eq!(body["error"]["message"], "database db1 already exists");
+
+        assert!(error_json(Error::Canceled).is_err());
+    }
+}
@@ -18,6 +18,7 @@ use std::task::{Context, Poll};
 
 use tonic::body::BoxBody;
 use tonic::codegen::{empty_body, http, BoxFuture, Service};
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
 use tonic::transport::NamedService;
 
 #[crate::async_trait]
@@ -60,11 +61,29 @@ where
 
     fn call(&mut self, req: http::Request<B>) -> Self::Future {
         let inner = self.inner.clone();
-        let query_params = req
+        let mut query_params: HashMap<String, String> = req
             .uri()
             .query()
             .map(|q| url::form_urlencoded::parse(q.as_bytes()).into_owned().collect())
             .unwrap_or_default();
+        // Who issues the request, it is recorded in the audit log.
+        let operator = req
+            .headers()
+            .get(crate::service::OPERATOR_KEY)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| query_params.get("operator").map(String::as_str));
+        let extensions = req.extensions();
+        let peer = extensions
+            .get::<TcpConnectInfo>()
+            .or_else(|| {
+                extensions.get::<TlsConnectInfo<TcpConnectInfo>>().map(|info| info.get_ref())
+            })
+            .and_then(|info| info.remote_addr());
+        let operator = match extensions.get::<crate::auth::Principal>() {
+            Some(principal) => principal.name().to_owned(),
+            None => crate::service::operator_or_peer(operator, peer),
+        };
+        query_params.insert("operator".to_owned(), operator);
         let path = req.uri().path().to_owned();
         Box::pin(async move { inner.call(&path, query_params).await })
     }
@@ -0,0 +1,42 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{Result, Server};
+
+/// Show the result of the integrity check when the node starts, including the
+/// broken replicas refused to serve.
+pub(super) struct StartupReportHandle {
+    server: Server,
+}
+
+impl StartupReportHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for StartupReportHandle {
+    async fn call(&self, _: &str, _: &HashMap<String, String>) -> Result<http::Response<String>> {
+        let report = self.server.node.startup_report().await;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&report).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
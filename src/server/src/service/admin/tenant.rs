@@ -0,0 +1,148 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use tonic::async_trait;
+use tonic::codegen::http;
+
+use super::operator;
+use super::schema::{error_json, required_name};
+use crate::root::AuditAction;
+use crate::serverpb::v1::{TenantDesc, TenantQuota};
+use crate::{Error, Result, Server};
+
+#[derive(Clone, Copy)]
+pub(super) enum TenantOp {
+    CreateTenant,
+    DeleteTenant,
+    SetQuota,
+    ListTenants,
+}
+
+/// Manage the tenants and their quotas.
+///
+/// Params:
+/// - `tenant`: the name of tenant, required except for listing tenants.
+/// - `max_databases`, `max_collections`: optional, the quota of the tenant, 0
+///   or absent means unlimited.
+///
+/// Listing tenants responds the databases, collections and usage owned by
+/// each tenant, the default tenant is named as an empty string.
+pub(super) struct TenantHandle {
+    server: Server,
+    op: TenantOp,
+}
+
+impl TenantHandle {
+    pub(crate) fn new(server: Server, op: TenantOp) -> Self {
+        Self { server, op }
+    }
+
+    async fn execute(&self, params: &HashMap<String, String>) -> Result<serde_json::Value> {
+        let root = &self.server.root;
+        match self.op {
+            TenantOp::CreateTenant => {
+                let name = required_name(params, "tenant")?;
+                let quota = parse_quota(params)?;
+                let result = root.create_tenant(name, quota).await;
+                let target = format!("tenant={name}, {}", quota_str(&quota));
+                root.audit(operator(params), AuditAction::CreateTenant, target, &result).await;
+                Ok(tenant_json(&result?))
+            }
+            TenantOp::DeleteTenant => {
+                let name = required_name(params, "tenant")?;
+                let result = root.delete_tenant(name).await;
+                let target = format!("tenant={name}");
+                root.audit(operator(params), AuditAction::DeleteTenant, target, &result).await;
+                result?;
+                Ok(json!({}))
+            }
+            TenantOp::SetQuota => {
+                let name = required_name(params, "tenant")?;
+                let quota = parse_quota(params)?;
+                let target = format!("tenant={name}, {}", quota_str(&quota));
+                let result = root.set_tenant_quota(name, quota).await;
+                root.audit(operator(params), AuditAction::SetTenantQuota, target, &result).await;
+                Ok(tenant_json(&result?))
+            }
+            TenantOp::ListTenants => {
+                let stats = root.tenant_stats().await?;
+                Ok(json!({
+                    "tenants": serde_json::to_value(stats).expect("TenantStats is serializable")
+                }))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for TenantHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let (status, body) = match self.execute(params).await {
+            Ok(body) => (http::StatusCode::OK, body),
+            Err(err) => error_json(err)?,
+        };
+        Ok(http::Response::builder()
+            .status(status)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .unwrap())
+    }
+}
+
+fn parse_quota(params: &HashMap<String, String>) -> Result<TenantQuota> {
+    let parse = |key: &str| match params.get(key) {
+        Some(v) => v.parse::<u64>().map_err(|_| Error::InvalidArgument(format!("illegal {key}"))),
+        None => Ok(0),
+    };
+    Ok(TenantQuota {
+        max_databases: parse("max_databases")?,
+        max_collections: parse("max_collections")?,
+    })
+}
+
+fn quota_str(quota: &TenantQuota) -> String {
+    format!("max_databases={}, max_collections={}", quota.max_databases, quota.max_collections)
+}
+
+fn tenant_json(desc: &TenantDesc) -> serde_json::Value {
+    let quota = desc.quota.clone().unwrap_or_default();
+    json!({
+        "id": desc.id,
+        "name": desc.name,
+        "max_databases": quota.max_databases,
+        "max_collections": quota.max_collections,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tenant_quota() {
+        let params = HashMap::from([("max_databases".to_owned(), "2".to_owned())]);
+        let quota = parse_quota(&params).unwrap();
+        assert_eq!((quota.max_databases, quota.max_collections), (2, 0));
+
+        let params = HashMap::from([("max_collections".to_owned(), "-1".to_owned())]);
+        assert!(parse_quota(&params).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use tonic::async_trait;
+use tonic::codegen::http;
+
+use super::operator;
+use super::schema::{error_json, get_database, required_name};
+use crate::auth::Permission;
+use crate::root::AuditAction;
+use crate::serverpb::v1::UserDesc;
+use crate::{Error, Result, Server};
+
+#[derive(Clone, Copy)]
+pub(super) enum UserOp {
+    CreateUser,
+    DeleteUser,
+    ListUsers,
+    ResetToken,
+    Grant,
+}
+
+/// Manage the users and their permissions.
+///
+/// Params:
+/// - `user`: the name of user, required except for listing users.
+/// - `superuser`: optional, whether the created user is a superuser.
+/// - `database`, `permission`: required for granting, the permission is one of
+///   `none`, `read`, `write` and `admin`. `none` revokes the permission.
+/// - `tenant`: optional, the tenant owning the granted database.
+///
+/// The token is only responded when the user is created or its token is reset.
+pub(super) struct UserHandle {
+    server: Server,
+    op: UserOp,
+}
+
+impl UserHandle {
+    pub(crate) fn new(server: Server, op: UserOp) -> Self {
+        Self { server, op }
+    }
+
+    async fn execute(&self, params: &HashMap<String, String>) -> Result<serde_json::Value> {
+        let root = &self.server.root;
+        match self.op {
+            UserOp::CreateUser => {
+                let name = required_name(params, "user")?;
+                let superuser = match params.get("superuser") {
+                    Some(v) => v
+                        .parse::<bool>()
+                        .map_err(|_| Error::InvalidArgument("illegal superuser".into()))?,
+                    None => false,
+                };
+                let result = root.create_user(name, superuser).await;
+                let target = format!("user={name}, superuser={superuser}");
+                root.audit(operator(params), AuditAction::CreateUser, target, &result).await;
+                Ok(json!({ "name": name, "token": result? }))
+            }
+            UserOp::DeleteUser => {
+                let name = required_name(params, "user")?;
+                let result = root.delete_user(name).await;
+                let target = format!("user={name}");
+                root.audit(operator(params), AuditAction::DeleteUser, target, &result).await;
+                result?;
+                Ok(json!({}))
+            }
+            UserOp::ListUsers => {
+                let users = root.list_users().await?;
+                Ok(json!({ "users": users.iter().map(user_json).collect::<Vec<_>>() }))
+            }
+            UserOp::ResetToken => {
+                let name = required_name(params, "user")?;
+                let result = root.reset_user_token(name).await;
+                let target = format!("user={name}");
+                root.audit(operator(params), AuditAction::ResetUserToken, target, &result).await;
+                Ok(json!({ "name": name, "token": result? }))
+            }
+            UserOp::Grant => {
+                let name = required_name(params, "user")?;
+                let database = get_database(root, params).await?;
+                let permission = params
+                    .get("permission")
+                    .and_then(|v| {
+                        Permission::from_str_name(&format!("PERMISSION_{}", v.to_uppercase()))
+                    })
+                    .ok_or_else(|| Error::InvalidArgument("illegal permission".into()))?;
+                let result = root.grant_permission(name, &database, permission).await;
+                let target = format!(
+                    "user={name}, database={}, permission={}",
+                    database.name,
+                    permission_str(permission)
+                );
+                root.audit(operator(params), AuditAction::GrantPermission, target, &result).await;
+                result?;
+                Ok(json!({}))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for UserHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let (status, body) = match self.execute(params).await {
+            Ok(body) => (http::StatusCode::OK, body),
+            Err(err) => error_json(err)?,
+        };
+        Ok(http::Response::builder()
+            .status(status)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .unwrap())
+    }
+}
+
+fn user_json(desc: &UserDesc) -> serde_json::Value {
+    let permissions = desc
+        .permissions
+        .iter()
+        .map(|p| json!({ "database": p.database, "permission": permission_str(p.permission()) }))
+        .collect::<Vec<_>>();
+    json!({ "name": desc.name, "superuser": desc.superuser, "permissions": permissions })
+}
+
+fn permission_str(permission: Permission) -> String {
+    permission.as_str_name().trim_start_matches("PERMISSION_").to_lowercase()
+}
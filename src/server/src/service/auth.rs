@@ -0,0 +1,182 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use sekas_client::AUTHORIZATION_KEY;
+use tonic::body::BoxBody;
+use tonic::codegen::{empty_body, http, BoxFuture, Service};
+use tonic::Status;
+use tower_layer::Layer;
+
+use super::HEALTH_PATH_PREFIX;
+use crate::auth::{Authenticator, Principal};
+
+/// The methods could be called by the users which are not superusers, the
+/// permissions on databases are checked by the handlers. The others, such as
+/// the raft messages and the admin service, require superuser.
+const USER_METHODS: &[&str] = &[
+    "/sekas.server.v1.Node/Batch",
+    "/sekas.server.v1.Node/Admin",
+    "/sekas.server.v1.Root/Admin",
+    "/sekas.server.v1.Root/Watch",
+    "/sekas.server.v1.Root/AllocTxnId",
+];
+
+/// The prefixes of the paths of the proxy services, see [`USER_METHODS`].
+const USER_PATH_PREFIXES: &[&str] = &["/sekas.gateway.v1.Gateway/", "/v1/"];
+
+/// The prefix of the paths of the etcd services, the requests without sekas
+/// tokens are authenticated by the etcd auth service with the etcd tokens.
+const ETCD_PATH_PREFIX: &str = "/etcdserverpb.";
+
+/// Verify the token of requests and attach the [`Principal`] to the
+/// extensions, the requests are passed through if the authentication is
+/// disabled. The health checks are always passed through, for the probes
+/// without tokens.
+#[derive(Clone)]
+pub(crate) struct AuthLayer {
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl AuthLayer {
+    pub(crate) fn new(authenticator: Option<Arc<Authenticator>>) -> Self {
+        AuthLayer { authenticator }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService { authenticator: self.authenticator.clone(), inner }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AuthService<S> {
+    authenticator: Option<Arc<Authenticator>>,
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for AuthService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        let Some(authenticator) = self.authenticator.clone() else {
+            return Box::pin(self.inner.call(req));
+        };
+
+        // The inner service is ready, see `tower::Service` for details.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            if req.uri().path().starts_with(HEALTH_PATH_PREFIX) {
+                return inner.call(req).await;
+            }
+            let is_etcd = req.uri().path().starts_with(ETCD_PATH_PREFIX);
+            if is_etcd && !req.headers().contains_key(AUTHORIZATION_KEY) {
+                return inner.call(req).await;
+            }
+            let is_grpc = is_grpc_request(&req);
+            let principal = match authenticate(&authenticator, &req).await {
+                Ok(principal) => principal,
+                Err(status) => return Ok(reject(status, is_grpc)),
+            };
+            if !principal.is_superuser() && !is_user_path(req.uri().path()) {
+                let status = Status::permission_denied(format!(
+                    "user {} is not a superuser",
+                    principal.name()
+                ));
+                return Ok(reject(status, is_grpc));
+            }
+            req.extensions_mut().insert(principal);
+            #[cfg(feature = "layer_etcd")]
+            if is_etcd {
+                // Only the superusers are allowed to call the etcd services with sekas tokens.
+                req.extensions_mut().insert(sekas_etcd_proxy::Superuser);
+            }
+            inner.call(req).await
+        })
+    }
+}
+
+async fn authenticate<B>(
+    authenticator: &Authenticator,
+    req: &http::Request<B>,
+) -> Result<Principal, Status> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION_KEY)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| Status::unauthenticated("the token is required"))?;
+    authenticator.authenticate(token.trim()).await
+}
+
+fn is_user_path(path: &str) -> bool {
+    USER_METHODS.contains(&path) || USER_PATH_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+fn is_grpc_request<B>(req: &http::Request<B>) -> bool {
+    req.headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("application/grpc"))
+        .unwrap_or_default()
+}
+
+/// Reply the gRPC status to the gRPC requests, and the HTTP status code to the
+/// others, eg. the admin and rest services.
+fn reject(status: Status, is_grpc: bool) -> http::Response<BoxBody> {
+    if is_grpc {
+        return status.to_http();
+    }
+    let code = match status.code() {
+        tonic::Code::PermissionDenied => http::StatusCode::FORBIDDEN,
+        tonic::Code::Unauthenticated => http::StatusCode::UNAUTHORIZED,
+        _ => http::StatusCode::SERVICE_UNAVAILABLE,
+    };
+    http::Response::builder().status(code).body(empty_body()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_paths() {
+        assert!(is_user_path("/sekas.server.v1.Node/Batch"));
+        assert!(is_user_path("/sekas.server.v1.Root/Watch"));
+        assert!(is_user_path("/sekas.gateway.v1.Gateway/Get"));
+        assert!(is_user_path("/v1/db/a/co/b/key/c"));
+        assert!(!is_user_path("/sekas.server.v1.Node/MoveShard"));
+        assert!(!is_user_path("/sekas.server.v1.Node/PullShard"));
+        assert!(!is_user_path("/sekas.server.v1.Root/Join"));
+        assert!(!is_user_path("/serverpb.v1.Raft/SendMessage"));
+        assert!(!is_user_path("/admin/create_user"));
+    }
+}
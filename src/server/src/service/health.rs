@@ -0,0 +1,55 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The standard gRPC health checking service `grpc.health.v1.Health`, for the
+//! load balancers and probes. The status of the whole server, named by the
+//! empty string, and the node service are serving until the node starts
+//! shutting down. The root service is serving only if the node is the leader
+//! of root.
+
+use std::time::Duration;
+
+use sekas_api::server::v1::node_server::NodeServer;
+use sekas_api::server::v1::root_server::RootServer;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+use crate::Server;
+
+/// The prefix of the paths of the health service, the requests are allowed
+/// without tokens.
+pub(crate) const HEALTH_PATH_PREFIX: &str = "/grpc.health.v1.Health/";
+
+/// The interval to refresh the status of services.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Refresh the status of services periodically.
+pub(crate) async fn health_main(mut reporter: HealthReporter, server: Server) {
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if server.node.is_shutting_down() {
+            reporter.set_service_status("", ServingStatus::NotServing).await;
+            reporter.set_not_serving::<NodeServer<Server>>().await;
+        } else {
+            reporter.set_service_status("", ServingStatus::Serving).await;
+            reporter.set_serving::<NodeServer<Server>>().await;
+        }
+        if server.root.is_root() && !server.node.is_shutting_down() {
+            reporter.set_serving::<RootServer<Server>>().await;
+        } else {
+            reporter.set_not_serving::<RootServer<Server>>().await;
+        }
+    }
+}
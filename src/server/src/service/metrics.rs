@@ -29,8 +29,12 @@ make_static_metric! {
             transfer,
             accept_shard,
             create_shard,
+            truncate_shard,
+            freeze_shard,
+            split_shard,
             move_replicas,
             change_replicas,
+            ingest,
         }
     }
     pub struct GroupRequestDuration: Histogram {
@@ -44,8 +48,12 @@ make_static_metric! {
             transfer,
             accept_shard,
             create_shard,
+            truncate_shard,
+            freeze_shard,
+            split_shard,
             move_replicas,
             change_replicas,
+            ingest,
         }
     }
 }
@@ -96,6 +104,18 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.create_shard.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.create_shard)
         }
+        Some(Request::TruncateShard(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.truncate_shard.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.truncate_shard)
+        }
+        Some(Request::FreezeShard(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.freeze_shard.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.freeze_shard)
+        }
+        Some(Request::SplitShard(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.split_shard.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.split_shard)
+        }
         Some(Request::ChangeReplicas(_)) => {
             NODE_SERVICE_GROUP_REQUEST_TOTAL.change_replicas.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.change_replicas)
@@ -120,10 +140,60 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.clear_intent.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.clear_intent)
         }
+        Some(Request::Ingest(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.ingest.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.ingest)
+        }
         None => None,
     }
 }
 
+// For workload tags.
+lazy_static! {
+    pub static ref NODE_SERVICE_APP_REQUEST_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_service_app_request_total",
+        "The total group requests of node service by workload tags",
+        &["app"]
+    )
+    .unwrap();
+    pub static ref NODE_SERVICE_APP_REQUEST_THROTTLED_TOTAL: IntCounterVec =
+        register_int_counter_vec!(
+            "node_service_app_request_throttled_total",
+            "The total throttled group requests of node service by workload tags",
+            &["app"]
+        )
+        .unwrap();
+    pub static ref NODE_SERVICE_CLIENT_QUOTA_EXCEEDED_TOTAL: IntCounter = register_int_counter!(
+        "node_service_client_quota_exceeded_total",
+        "The total batch requests of node service rejected by the client quotas"
+    )
+    .unwrap();
+    pub static ref NODE_SERVICE_APP_REQUEST_DURATION_SECONDS: HistogramVec =
+        register_histogram_vec!(
+            "node_service_app_request_duration_seconds",
+            "The intervals of batch requests of node service by workload tags",
+            &["app"],
+            exponential_buckets(0.00005, 1.8, 26).unwrap(),
+        )
+        .unwrap();
+}
+
+pub fn take_app_request_metrics(app_tag: &str, num_requests: usize) -> Histogram {
+    NODE_SERVICE_APP_REQUEST_TOTAL.with_label_values(&[app_tag]).inc_by(num_requests as u64);
+    NODE_SERVICE_APP_REQUEST_DURATION_SECONDS.with_label_values(&[app_tag])
+}
+
+pub fn take_app_request_throttled_metrics(app_tag: &str, num_requests: usize) {
+    NODE_SERVICE_APP_REQUEST_THROTTLED_TOTAL
+        .with_label_values(&[app_tag])
+        .inc_by(num_requests as u64);
+}
+
+#[inline]
+pub fn take_client_quota_exceeded_metrics() {
+    NODE_SERVICE_CLIENT_QUOTA_EXCEEDED_TOTAL.inc();
+}
+
 // For batch request.
 lazy_static! {
     pub static ref NODE_SERVICE_BATCH_REQUEST_TOTAL: IntCounter = register_int_counter!(
@@ -179,6 +249,9 @@ macro_rules! simple_node_method {
 simple_node_method!(get_root);
 simple_node_method!(create_replica);
 simple_node_method!(remove_replica);
+simple_node_method!(upload_ingest_file);
+simple_node_method!(verify_ingest_files);
+simple_node_method!(unsafe_recover_replica);
 simple_node_method!(root_heartbeat);
 simple_node_method!(migrate);
 simple_node_method!(forward);
@@ -240,6 +313,7 @@ make_static_metric! {
             get,
             put,
             delete,
+            scan,
             batch,
         }
     }
@@ -248,6 +322,7 @@ make_static_metric! {
             get,
             put,
             delete,
+            scan,
             batch,
         }
     }
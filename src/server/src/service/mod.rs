@@ -12,36 +12,105 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 pub mod admin;
+mod auth;
+mod health;
 mod metrics;
 pub mod node;
+mod proxy;
 pub mod raft;
+mod rest;
 pub mod root;
+mod tls;
+mod workload;
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use sekas_client::{ClientOptions, SekasClient};
+use sekas_client::{ClientOptions, Router, SekasClient};
 
+pub(crate) use self::auth::AuthLayer;
+pub(crate) use self::health::{health_main, HEALTH_PATH_PREFIX};
+pub(crate) use self::rest::RestService;
+pub(crate) use self::tls::{tls_incoming, tls_reload_main, TlsAcceptor};
+pub(crate) use self::workload::WorkloadController;
+use crate::auth::Principal;
 use crate::node::Node;
 use crate::root::Root;
 use crate::transport::{AddressResolver, TransportManager};
 
+/// The header carrying who issues the admin and DDL requests, it is recorded in
+/// the audit log.
+pub const OPERATOR_KEY: &str = "x-sekas-operator";
+
+/// The header carrying the workload tag of the requests issued through the
+/// proxy, the proxy attaches it to the requests forwarded to groups.
+pub const APP_TAG_KEY: &str = "x-sekas-app-tag";
+
 #[derive(Clone)]
 pub struct Server {
     pub node: Arc<Node>,
     pub root: Root,
     pub address_resolver: Arc<AddressResolver>,
+    pub(crate) workload: Arc<WorkloadController>,
 }
 
 #[derive(Clone)]
 pub struct ProxyServer {
     pub client: SekasClient,
+    router: Router,
 }
 
 impl ProxyServer {
     pub(crate) fn new(transport_manager: &TransportManager) -> Self {
-        let opts =
-            ClientOptions { connect_timeout: Some(Duration::from_millis(250)), timeout: None };
-        ProxyServer { client: transport_manager.build_client(opts) }
+        let opts = ClientOptions {
+            connect_timeout: Some(Duration::from_millis(250)),
+            timeout: None,
+            app_tag: None,
+            chunk_size: None,
+            retry_policy: Default::default(),
+            conn_pool: Default::default(),
+            instrument: None,
+            tls: None,
+            tenant: None,
+            token: None,
+        };
+        ProxyServer {
+            client: transport_manager.build_client(opts),
+            router: transport_manager.router().clone(),
+        }
+    }
+
+    /// Return the client to forward the requests of the principal with the
+    /// workload tag, the requests are counted against the principal by the
+    /// client quotas.
+    pub(crate) fn client_for(
+        &self,
+        principal: Option<&Principal>,
+        app_tag: Option<&str>,
+    ) -> SekasClient {
+        let client = match app_tag.filter(|tag| !tag.is_empty()) {
+            Some(app_tag) => self.client.with_app_tag(Some(app_tag.to_owned())),
+            None => self.client.clone(),
+        };
+        match principal {
+            Some(Principal::User(user)) => client.with_end_user(Some(user.name.clone())),
+            _ => client,
+        }
+    }
+}
+
+/// Return the workload tag carried by the request metadata.
+pub(crate) fn app_tag_of<T>(request: &tonic::Request<T>) -> Option<String> {
+    request.metadata().get(APP_TAG_KEY).and_then(|v| v.to_str().ok()).map(ToOwned::to_owned)
+}
+
+/// Return who issues the request, the peer address is used if the operator is
+/// not specified.
+pub(crate) fn operator_or_peer(operator: Option<&str>, peer: Option<SocketAddr>) -> String {
+    match (operator, peer) {
+        (Some(operator), _) if !operator.is_empty() => operator.to_owned(),
+        (_, Some(addr)) => addr.to_string(),
+        _ => "unknown".to_owned(),
     }
 }
@@ -13,47 +13,118 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::SocketAddr;
+
+use futures::channel::mpsc;
+use futures::SinkExt;
+use prost::Message;
 use sekas_api::server::v1::*;
+use sekas_client::error::NODE_SHUTTING_DOWN;
 use sekas_runtime::JoinHandle;
 use tonic::{Request, Response, Status};
+use tracing::Instrument;
 
 use super::metrics::*;
+use super::workload::app_tag_or_default;
+use crate::auth::{check_database, check_superuser, Permission, Principal};
+use crate::constants::ROOT_GROUP_ID;
+use crate::node::resource::ResourceGroup;
 use crate::serverpb::v1::MoveShardEvent;
 use crate::{record_latency, record_latency_opt, Error, Server};
 
 #[crate::async_trait]
 impl node_server::Node for Server {
+    type PullShardStream = mpsc::Receiver<Result<PullShardChunk, Status>>;
+
     async fn batch(
         &self,
         request: Request<BatchRequest>,
     ) -> Result<Response<BatchResponse>, Status> {
+        let span = tracing::info_span!(
+            "node.batch",
+            app_tag = tracing::field::Empty,
+            num_requests = tracing::field::Empty,
+        );
+        crate::trace::set_remote_parent(&span, request.metadata());
+        if self.node.is_shutting_down() {
+            // The clients retry the other replicas once the leaderships are transferred.
+            return Err(Status::unavailable(NODE_SHUTTING_DOWN));
+        }
+        let principal = request.extensions().get::<Principal>().cloned();
+        let remote_addr = request.remote_addr();
         let batch_request = request.into_inner();
+        let client = quota_client(principal.as_ref(), &batch_request.end_user, remote_addr);
+        for request in &batch_request.requests {
+            check_metadata_view_write(request)?;
+        }
+        if let Some(principal) = principal.as_ref().filter(|p| !p.is_superuser()) {
+            for request in &batch_request.requests {
+                self.check_group_request(principal, request)?;
+            }
+        }
         record_latency!(take_batch_request_metrics(&batch_request));
-        if batch_request.requests.len() == 1 {
-            let request = batch_request.requests.into_iter().next().expect("already checked");
-            let server = self.clone();
-            let response =
-                Box::pin(async move { server.submit_group_request(&request).await }).await;
-            Ok(Response::new(BatchResponse { responses: vec![response] }))
-        } else {
-            let handles = self.submit_group_requests(batch_request.requests);
-            let mut responses = Vec::with_capacity(handles.len());
-            for handle in handles {
-                responses.push(handle.await.map_err(Error::from)?);
+        let app_tag = app_tag_or_default(&batch_request.app_tag).to_owned();
+        let num_requests = batch_request.requests.len();
+        span.record("app_tag", app_tag.as_str());
+        span.record("num_requests", num_requests);
+        let request_bytes = batch_request.encoded_len();
+        if let Err(err) =
+            self.workload.check_client_quota(client.as_deref(), num_requests, request_bytes)
+        {
+            take_client_quota_exceeded_metrics();
+            return Err(err.into());
+        }
+        let metric_app_tag = self.workload.metric_app_tag(&app_tag);
+        if !self.workload.try_acquire(&app_tag, num_requests) {
+            take_app_request_throttled_metrics(metric_app_tag, num_requests);
+            return Err(Error::ResourceExhausted(format!(
+                "the requests of app {app_tag} exceed the rate limit"
+            ))
+            .into());
+        }
+        record_latency!(take_app_request_metrics(metric_app_tag, num_requests));
+        let _slow_request_guard = self.workload.slow_request_guard(&app_tag, &batch_request);
+        let batch_response = async {
+            if batch_request.requests.len() == 1 {
+                let request = batch_request.requests.into_iter().next().expect("already checked");
+                let server = self.clone();
+                let response =
+                    Box::pin(async move { server.submit_group_request(&request).await }).await;
+                Ok::<_, Error>(BatchResponse { responses: vec![response] })
+            } else {
+                let handles = self.submit_group_requests(batch_request.requests);
+                let mut responses = Vec::with_capacity(handles.len());
+                for handle in handles {
+                    responses.push(handle.await?);
+                }
+                Ok(BatchResponse { responses })
             }
-
-            Ok(Response::new(BatchResponse { responses }))
         }
+        .instrument(span)
+        .await?;
+
+        let num_bytes = request_bytes + batch_response.encoded_len();
+        self.node.resource_controller().consume(ResourceGroup::Foreground, num_bytes).await;
+        Ok(Response::new(batch_response))
     }
 
     async fn admin(
         &self,
         request: Request<NodeAdminRequest>,
     ) -> Result<Response<NodeAdminResponse>, Status> {
+        let principal = request.extensions().get::<Principal>().cloned();
         let request = request.into_inner();
         let Some(request) = request.request else {
             return Err(Status::invalid_argument("AdminRequest::request is empty".to_owned()));
         };
+        // The users need to locate root and upload the files to ingest.
+        match &request {
+            node_admin_request::Request::GetRoot(_) => {}
+            node_admin_request::Request::UploadIngestFile(req) => {
+                self.check_upload_ingest_file(principal.as_ref(), req)?
+            }
+            _ => check_superuser(principal.as_ref())?,
+        }
         let resp = match request {
             node_admin_request::Request::GetRoot(_) => {
                 node_admin_response::Response::GetRoot(self.get_root().await?)
@@ -67,6 +138,19 @@ impl node_server::Node for Server {
             node_admin_request::Request::Heartbeat(req) => {
                 node_admin_response::Response::Heartbeat(self.root_heartbeat(req).await?)
             }
+            node_admin_request::Request::UploadIngestFile(req) => {
+                node_admin_response::Response::UploadIngestFile(self.upload_ingest_file(req).await?)
+            }
+            node_admin_request::Request::UnsafeRecoverReplica(req) => {
+                node_admin_response::Response::UnsafeRecoverReplica(
+                    self.unsafe_recover_replica(req).await?,
+                )
+            }
+            node_admin_request::Request::VerifyIngestFiles(req) => {
+                node_admin_response::Response::VerifyIngestFiles(
+                    self.verify_ingest_files(req).await?,
+                )
+            }
         };
         Ok(Response::new(NodeAdminResponse { response: Some(resp) }))
     }
@@ -90,8 +174,7 @@ impl node_server::Node for Server {
                     ));
                 };
                 record_latency!(take_migrate_request_metrics());
-                self.node.move_shard(MoveShardEvent::Setup, desc).await?;
-                move_shard_response::Response::AcquireShard(AcquireShardResponse::default())
+                move_shard_response::Response::AcquireShard(self.node.acquire_shard(desc).await?)
             }
             move_shard_request::Request::MoveOut(req) => {
                 let Some(desc) = req.desc else {
@@ -106,9 +189,130 @@ impl node_server::Node for Server {
         };
         Ok(Response::new(MoveShardResponse { response: Some(resp) }))
     }
+
+    async fn pull_shard(
+        &self,
+        request: Request<PullShardRequest>,
+    ) -> Result<Response<Self::PullShardStream>, Status> {
+        if self.node.is_shutting_down() {
+            return Err(Status::unavailable(NODE_SHUTTING_DOWN));
+        }
+        let mut req = request.into_inner();
+        record_latency!(take_migrate_request_metrics());
+        // Read the first chunk eagerly, so that the client could be redirected to the
+        // leader by the returned status.
+        let data = self.node.pull_shard_chunk(&req).await?;
+        let (mut sender, receiver) = mpsc::channel(1);
+        let node = self.node.clone();
+        sekas_runtime::spawn(async move {
+            let mut data = data;
+            loop {
+                let last_key = data.last().map(|v| v.user_key.clone());
+                if sender.send(Ok(PullShardChunk::new(data))).await.is_err() {
+                    // The receiver is dropped.
+                    return;
+                }
+                let Some(last_key) = last_key else {
+                    return;
+                };
+                req.last_key = last_key;
+                data = match node.pull_shard_chunk(&req).await {
+                    Ok(data) => data,
+                    Err(err) => {
+                        let _ = sender.send(Err(err.into())).await;
+                        return;
+                    }
+                };
+            }
+        });
+        Ok(Response::new(receiver))
+    }
 }
 
 impl Server {
+    /// Check the permission of the user on the database of the requested
+    /// collection.
+    fn check_group_request(
+        &self,
+        principal: &Principal,
+        request: &GroupRequest,
+    ) -> Result<(), Status> {
+        use group_request_union::Request;
+
+        let Some(union) = request.request.as_ref().and_then(|r| r.request.as_ref()) else {
+            return Ok(());
+        };
+        let (shard_id, required) = match union {
+            Request::Get(req) => (req.shard_id, Permission::Read),
+            Request::Scan(req) => (req.shard_id, Permission::Read),
+            Request::Write(req) => (req.shard_id, Permission::Write),
+            Request::WriteIntent(req) => (req.shard_id, Permission::Write),
+            Request::CommitIntent(req) => (req.shard_id, Permission::Write),
+            Request::ClearIntent(req) => (req.shard_id, Permission::Write),
+            Request::Ingest(req) => (req.shard_id, Permission::Write),
+            Request::CreateShard(_)
+            | Request::TruncateShard(_)
+            | Request::FreezeShard(_)
+            | Request::SplitShard(_)
+            | Request::ChangeReplicas(_)
+            | Request::AcceptShard(_)
+            | Request::Transfer(_)
+            | Request::MoveReplicas(_) => return check_superuser(Some(principal)),
+        };
+
+        // The group is not served by this node, the request will be rejected later.
+        let Some(replica) = self.node.replica_table().find(request.group_id) else {
+            return Ok(());
+        };
+        let Some(shard) = replica.descriptor().shards.into_iter().find(|s| s.id == shard_id) else {
+            return Ok(());
+        };
+        let collection_id = shard.collection_id;
+        if collection_id == sekas_schema::system::col::txn_col_id() {
+            return check_txn_request(principal, union);
+        }
+        if sekas_schema::system::col::is_metadata_view(collection_id)
+            && required == Permission::Read
+        {
+            return Ok(());
+        }
+        if collection_id < sekas_schema::FIRST_USER_COLLECTION_ID {
+            return check_superuser(Some(principal));
+        }
+        let database_id = self.node.collection_database(collection_id).ok_or_else(|| {
+            Status::unavailable(format!("the database of collection {collection_id} is unknown"))
+        })?;
+        check_database(Some(principal), database_id, required)
+    }
+
+    /// The ingest files are staged per shard, uploading them requires the write
+    /// permission of the database of the shard, which must be served by this
+    /// node.
+    fn check_upload_ingest_file(
+        &self,
+        principal: Option<&Principal>,
+        request: &UploadIngestFileRequest,
+    ) -> Result<(), Status> {
+        let Some(replica) = self.node.replica_table().find(request.group_id) else {
+            return Err(Error::GroupNotFound(request.group_id).into());
+        };
+        let shards = replica.descriptor().shards;
+        let Some(shard) = shards.into_iter().find(|s| s.id == request.shard_id) else {
+            return Err(Error::ShardNotFound(request.shard_id).into());
+        };
+        let Some(principal) = principal.filter(|p| !p.is_superuser()) else {
+            return Ok(());
+        };
+        let collection_id = shard.collection_id;
+        if collection_id < sekas_schema::FIRST_USER_COLLECTION_ID {
+            return check_superuser(Some(principal));
+        }
+        let database_id = self.node.collection_database(collection_id).ok_or_else(|| {
+            Status::unavailable(format!("the database of collection {collection_id} is unknown"))
+        })?;
+        check_database(Some(principal), database_id, Permission::Write)
+    }
+
     async fn forward(&self, request: ForwardRequest) -> Result<ForwardResponse, Status> {
         record_latency!(take_forward_request_metrics());
         Ok(self.node.forward(request).await?)
@@ -144,8 +348,43 @@ impl Server {
         Ok(RemoveReplicaResponse {})
     }
 
+    async fn upload_ingest_file(
+        &self,
+        request: UploadIngestFileRequest,
+    ) -> Result<UploadIngestFileResponse, Status> {
+        record_latency!(take_upload_ingest_file_request_metrics());
+        let size = self
+            .node
+            .upload_ingest_file(request.shard_id, request.name, request.offset, request.data)
+            .await?;
+        Ok(UploadIngestFileResponse { size })
+    }
+
+    async fn verify_ingest_files(
+        &self,
+        request: VerifyIngestFilesRequest,
+    ) -> Result<VerifyIngestFilesResponse, Status> {
+        record_latency!(take_verify_ingest_files_request_metrics());
+        self.node.verify_ingest_files(request.shard_id, request.token, request.files).await?;
+        Ok(VerifyIngestFilesResponse {})
+    }
+
+    async fn unsafe_recover_replica(
+        &self,
+        request: UnsafeRecoverReplicaRequest,
+    ) -> Result<UnsafeRecoverReplicaResponse, Status> {
+        record_latency!(take_unsafe_recover_replica_request_metrics());
+        let group = self.node.unsafe_recover_replica(request.group_id, request.replica_id).await?;
+        Ok(UnsafeRecoverReplicaResponse { group: Some(group) })
+    }
+
     async fn root_heartbeat(&self, request: HeartbeatRequest) -> Result<HeartbeatResponse, Status> {
         record_latency!(take_root_heartbeat_request_metrics());
+        if self.node.is_shutting_down() {
+            // Keep the node dead in root, see `Node::notify_shutdown`.
+            return Err(Status::unavailable(NODE_SHUTTING_DOWN));
+        }
+        self.node.check_incarnation(request.node_id, request.incarnation).await?;
         let mut piggybacks_resps = Vec::with_capacity(request.piggybacks.len());
 
         for req in request.piggybacks {
@@ -171,6 +410,18 @@ impl Server {
                         self.node.collect_schedule_state(&req).await,
                     )
                 }
+                piggyback_request::Info::SyncDataKeys(req) => {
+                    self.node.update_data_keys(&req.keys).await;
+                    piggyback_response::Info::SyncDataKeys(SyncDataKeysResponse {})
+                }
+                piggyback_request::Info::SyncMoveShardLimit(req) => {
+                    self.node.update_move_shard_limit(req.limit.unwrap_or_default());
+                    piggyback_response::Info::SyncMoveShardLimit(SyncMoveShardLimitResponse {})
+                }
+                piggyback_request::Info::SyncConfig(req) => {
+                    self.node.update_dynamic_config(&req.entries);
+                    piggyback_response::Info::SyncConfig(SyncConfigResponse {})
+                }
             };
             piggybacks_resps.push(PiggybackResponse { info: Some(info) });
         }
@@ -192,21 +443,184 @@ impl Server {
 
     async fn submit_group_request(&self, request: &GroupRequest) -> GroupResponse {
         record_latency_opt!(take_group_request_metrics(request));
-        self.node.execute_request(request).await.unwrap_or_else(error_to_response)
+        let span = group_request_span(request);
+        match self.node.execute_request(request).instrument(span).await {
+            Ok(resp) => resp,
+            Err(Error::NotLeader(group_id, term, leader)) => {
+                // Carry the latest descriptor, so that the client could refresh its routing
+                // table without waiting for the watch stream.
+                let descriptor = self.node.replica_table().find(group_id).map(|r| r.descriptor());
+                let err = sekas_api::server::v1::Error::not_leader_with_descriptor(
+                    group_id, term, leader, descriptor,
+                );
+                GroupResponse { response: None, error: Some(err) }
+            }
+            Err(err) => error_to_response(err),
+        }
     }
 
     fn submit_group_requests(&self, requests: Vec<GroupRequest>) -> Vec<JoinHandle<GroupResponse>> {
         let mut handles = Vec::with_capacity(requests.len());
         for request in requests.into_iter() {
             let server = self.clone();
-            let handle =
-                sekas_runtime::spawn(async move { server.submit_group_request(&request).await });
+            let handle = sekas_runtime::spawn(
+                async move { server.submit_group_request(&request).await }.in_current_span(),
+            );
             handles.push(handle);
         }
         handles
     }
 }
 
+/// Return the client whose requests are limited by the client quotas. The
+/// users are identified by their names, the requests forwarded by the proxies
+/// with the cluster token are counted against the end users, and the other
+/// requests of the cluster are not limited. If the authentication is disabled,
+/// the clients are identified by the remote addresses.
+fn quota_client(
+    principal: Option<&Principal>,
+    end_user: &str,
+    remote_addr: Option<SocketAddr>,
+) -> Option<String> {
+    match principal {
+        Some(Principal::User(user)) => Some(user.name.clone()),
+        Some(Principal::Cluster) if !end_user.is_empty() => Some(end_user.to_owned()),
+        Some(Principal::Cluster) => None,
+        None => remote_addr.map(|addr| addr.to_string()),
+    }
+}
+
+/// The txn records are written by the clients, a principal could only write
+/// the records of the txns owned by its databases, see
+/// [`sekas_schema::system::keys::txn_prefix`].
+fn check_txn_request(
+    principal: &Principal,
+    request: &group_request_union::Request,
+) -> Result<(), Status> {
+    use group_request_union::Request;
+    use sekas_schema::system::keys::txn_key_database;
+
+    match request {
+        Request::Get(_) | Request::Scan(_) => Ok(()),
+        Request::Write(req) => {
+            let keys = req.deletes.iter().map(|d| &d.key).chain(req.puts.iter().map(|p| &p.key));
+            for key in keys {
+                match txn_key_database(key) {
+                    Some(database_id) => {
+                        check_database(Some(principal), database_id, Permission::Write)?
+                    }
+                    None => check_superuser(Some(principal))?,
+                }
+            }
+            Ok(())
+        }
+        _ => check_superuser(Some(principal)),
+    }
+}
+
+/// The views of root metadata are read-only to the clients, root writes them
+/// through the local replica.
+fn check_metadata_view_write(request: &GroupRequest) -> Result<(), Status> {
+    use group_request_union::Request;
+
+    if request.group_id != ROOT_GROUP_ID {
+        return Ok(());
+    }
+    let Some(union) = request.request.as_ref().and_then(|r| r.request.as_ref()) else {
+        return Ok(());
+    };
+    let shard_id = match union {
+        Request::Write(req) => req.shard_id,
+        Request::WriteIntent(req) => req.shard_id,
+        Request::CommitIntent(req) => req.shard_id,
+        Request::ClearIntent(req) => req.shard_id,
+        Request::Ingest(req) => req.shard_id,
+        _ => return Ok(()),
+    };
+    // The shard id of the collections in the root group equals to the collection
+    // id.
+    if sekas_schema::system::col::is_metadata_view(shard_id) {
+        return Err(Status::invalid_argument(format!("system collection {shard_id} is read-only")));
+    }
+    Ok(())
+}
+
+fn group_request_span(request: &GroupRequest) -> tracing::Span {
+    let span = tracing::info_span!(
+        "node.group_request",
+        group_id = request.group_id,
+        epoch = request.epoch,
+        request = tracing::field::Empty,
+        shard_id = tracing::field::Empty,
+        txn = tracing::field::Empty,
+    );
+    if let Some(request) = request.request.as_ref().and_then(|r| r.request.as_ref()) {
+        span.record("request", request.name());
+        if let Some(shard_id) = request.shard_id() {
+            span.record("shard_id", shard_id);
+        }
+        if let Some(start_version) = request.txn_version() {
+            span.record("txn", start_version);
+        }
+    }
+    span
+}
+
 fn error_to_response(err: Error) -> GroupResponse {
     GroupResponse { response: None, error: Some(err.into()) }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use group_request_union::Request;
+    use sekas_schema::system::keys::txn_state_key;
+
+    use super::*;
+    use crate::serverpb::v1::{DatabasePermission, UserDesc};
+
+    fn txn_write(key: Vec<u8>) -> Request {
+        Request::Write(ShardWriteRequest {
+            puts: vec![PutRequest { key, ..Default::default() }],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn txn_records_are_written_by_database_owners() {
+        let user = UserDesc {
+            name: "alice".to_owned(),
+            permissions: vec![DatabasePermission {
+                database_id: 1,
+                database: "db".to_owned(),
+                permission: Permission::Write as i32,
+            }],
+            ..Default::default()
+        };
+        let principal = Principal::User(Arc::new(user));
+        assert!(check_txn_request(&principal, &txn_write(txn_state_key(0, 1, 100))).is_ok());
+        assert!(check_txn_request(&principal, &txn_write(txn_state_key(0, 2, 100))).is_err());
+        assert!(check_txn_request(&principal, &txn_write(b"txn_".to_vec())).is_err());
+        let scan = Request::Scan(ShardScanRequest::default());
+        assert!(check_txn_request(&principal, &scan).is_ok());
+
+        let cluster = Principal::Cluster;
+        assert!(check_txn_request(&cluster, &txn_write(txn_state_key(0, 2, 100))).is_ok());
+        assert!(check_txn_request(&cluster, &txn_write(b"txn_".to_vec())).is_ok());
+    }
+
+    #[test]
+    fn client_quotas_identify_clients() {
+        let addr: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let user =
+            Principal::User(Arc::new(UserDesc { name: "alice".into(), ..Default::default() }));
+        assert_eq!(quota_client(Some(&user), "", Some(addr)), Some("alice".to_owned()));
+        // The end users are only trusted from the cluster.
+        assert_eq!(quota_client(Some(&user), "bob", Some(addr)), Some("alice".to_owned()));
+        assert_eq!(quota_client(Some(&Principal::Cluster), "bob", None), Some("bob".to_owned()));
+        assert_eq!(quota_client(Some(&Principal::Cluster), "", Some(addr)), None);
+        // The authentication is disabled.
+        assert_eq!(quota_client(None, "bob", Some(addr)), Some(addr.to_string()));
+    }
+}
@@ -12,231 +12,234 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ::sekas_client::{Collection, Database};
-use sekas_api::v1::*;
+use sekas_api::gateway::v1::gateway_server::Gateway;
+use sekas_api::gateway::v1::*;
+use sekas_api::server::v1::{DatabaseDesc, WriteResponse};
+use sekas_client::{Database, WriteBatchRequest as ClientWriteBatchRequest};
 use tonic::{Request, Response, Status};
 
-use super::ProxyServer;
-use crate::service::metrics::take_database_request_metrics;
-use crate::{record_latency, Error};
+use super::metrics::*;
+use super::{app_tag_of, ProxyServer};
+use crate::auth::{check_database, check_superuser, Permission, Principal};
+use crate::record_latency;
 
 #[tonic::async_trait]
-impl sekas_server::Sekas for ProxyServer {
-    async fn admin(
-        &self,
-        request: Request<AdminRequest>,
-    ) -> Result<Response<AdminResponse>, Status> {
-        use sekas_api::v1::admin_request_union::Request;
-        use sekas_api::v1::admin_response_union::Response;
-        let req = request.into_inner().request.and_then(|r| r.request).ok_or_else(|| {
-            Error::InvalidArgument(
-                "AdminRequest::request or AdminRequestUnion::request is required".to_owned(),
-            )
-        })?;
-        let resp = match req {
-            Request::GetDatabase(req) => Response::GetDatabase(self.get_database(req).await?),
-            Request::ListDatabases(req) => Response::ListDatabases(self.list_database(req).await?),
-            Request::CreateDatabase(req) => {
-                Response::CreateDatabase(self.create_database(req).await?)
-            }
-            Request::UpdateDatabase(req) => {
-                Response::UpdateDatabase(self.update_database(req).await?)
-            }
-            Request::DeleteDatabase(req) => {
-                Response::DeleteDatabase(self.delete_database(req).await?)
-            }
-            Request::GetCollection(req) => Response::GetCollection(self.get_collection(req).await?),
-            Request::ListCollections(req) => {
-                Response::ListCollections(self.list_collections(req).await?)
-            }
-            Request::CreateCollection(req) => {
-                Response::CreateCollection(self.create_collection(req).await?)
-            }
-            Request::UpdateCollection(req) => {
-                Response::UpdateCollection(self.update_collection(req).await?)
-            }
-            Request::DeleteCollection(req) => {
-                Response::DeleteCollection(self.delete_collection(req).await?)
-            }
-        };
-
-        Ok(tonic::Response::new(AdminResponse {
-            response: Some(AdminResponseUnion { response: Some(resp) }),
-        }))
-    }
-
-    async fn database(
+impl Gateway for ProxyServer {
+    async fn create_database(
         &self,
-        request: Request<DatabaseRequest>,
-    ) -> Result<Response<DatabaseResponse>, Status> {
-        use sekas_api::v1::collection_request_union::Request;
-        use sekas_api::v1::collection_response_union::Response;
-
+        request: Request<CreateDatabaseRequest>,
+    ) -> Result<Response<CreateDatabaseResponse>, Status> {
+        check_superuser(request.extensions().get::<Principal>())?;
         let request = request.into_inner();
-        let request = request.request.ok_or_else(|| {
-            Error::InvalidArgument("DatabaseRequest::request is required".to_owned())
-        })?;
-        let collection = request.collection.ok_or_else(|| {
-            Error::InvalidArgument("CollectionRequest::collection is required".to_owned())
-        })?;
-        let request = request.request.and_then(|r| r.request).ok_or_else(|| {
-            Error::InvalidArgument(
-                "CollectionRequest::request or CollectionRequestUnion is required".to_owned(),
-            )
-        })?;
-        record_latency!(take_database_request_metrics(&request));
-        let resp = match request {
-            Request::Get(req) => Response::Get(self.handle_get(collection, req).await?),
-            Request::Put(req) => Response::Put(self.handle_put(collection, req).await?),
-            Request::Delete(req) => Response::Delete(self.handle_delete(collection, req).await?),
-            Request::Batch(req) => Response::Batch(self.handle_batch(collection, req).await?),
-        };
-        Ok(tonic::Response::new(DatabaseResponse {
-            response: Some(CollectionResponse {
-                response: Some(CollectionResponseUnion { response: Some(resp) }),
-            }),
-        }))
-    }
-}
-
-impl ProxyServer {
-    async fn get_database(&self, req: GetDatabaseRequest) -> Result<GetDatabaseResponse, Status> {
-        let database = self.client.open_database(req.name).await?;
-        Ok(GetDatabaseResponse { database: Some(database.desc()) })
+        let database = self.client.create_database(request.name).await?;
+        Ok(Response::new(CreateDatabaseResponse { database: Some(database.desc()) }))
     }
 
-    async fn list_database(
+    async fn get_database(
         &self,
-        _req: ListDatabasesRequest,
-    ) -> Result<ListDatabasesResponse, Status> {
-        let databases = self.client.list_database().await?.into_iter().map(|d| d.desc()).collect();
-        Ok(ListDatabasesResponse { databases })
+        request: Request<GetDatabaseRequest>,
+    ) -> Result<Response<GetDatabaseResponse>, Status> {
+        let principal = request.extensions().get::<Principal>().cloned();
+        let request = request.into_inner();
+        let database = self.client.open_database(request.name).await?;
+        check_database(principal.as_ref(), database.desc().id, Permission::Read)?;
+        Ok(Response::new(GetDatabaseResponse { database: Some(database.desc()) }))
     }
 
-    async fn create_database(
+    async fn delete_database(
         &self,
-        req: CreateDatabaseRequest,
-    ) -> Result<CreateDatabaseResponse, Status> {
-        let database = self.client.create_database(req.name).await?;
-        Ok(CreateDatabaseResponse { database: Some(database.desc()) })
+        request: Request<DeleteDatabaseRequest>,
+    ) -> Result<Response<DeleteDatabaseResponse>, Status> {
+        let principal = request.extensions().get::<Principal>().cloned();
+        let request = request.into_inner();
+        if principal.is_some() {
+            let database = self.client.open_database(request.name.clone()).await?;
+            check_database(principal.as_ref(), database.desc().id, Permission::Admin)?;
+        }
+        self.client.delete_database(request.name).await?;
+        Ok(Response::new(DeleteDatabaseResponse {}))
     }
 
-    async fn update_database(
+    async fn create_collection(
         &self,
-        _req: UpdateDatabaseRequest,
-    ) -> Result<UpdateDatabaseResponse, Status> {
-        Err(Status::unimplemented("ProxyServer::update_database"))
+        request: Request<CreateCollectionRequest>,
+    ) -> Result<Response<CreateCollectionResponse>, Status> {
+        let principal = request.extensions().get::<Principal>().cloned();
+        let request = request.into_inner();
+        let database = self.client.open_database(request.database).await?;
+        check_database(principal.as_ref(), database.desc().id, Permission::Admin)?;
+        let collection = database.create_collection(request.name).await?;
+        Ok(Response::new(CreateCollectionResponse { collection: Some(collection) }))
     }
 
-    async fn delete_database(
+    async fn get_collection(
         &self,
-        req: DeleteDatabaseRequest,
-    ) -> Result<DeleteDatabaseResponse, Status> {
-        self.client.delete_database(req.name).await?;
-        Ok(DeleteDatabaseResponse {})
+        request: Request<GetCollectionRequest>,
+    ) -> Result<Response<GetCollectionResponse>, Status> {
+        let principal = request.extensions().get::<Principal>().cloned();
+        let request = request.into_inner();
+        let database = self.client.open_database(request.database).await?;
+        check_database(principal.as_ref(), database.desc().id, Permission::Read)?;
+        let collection = database.open_collection(request.name).await?;
+        Ok(Response::new(GetCollectionResponse { collection: Some(collection) }))
     }
 
-    async fn get_collection(
+    async fn delete_collection(
         &self,
-        req: GetCollectionRequest,
-    ) -> Result<GetCollectionResponse, Status> {
-        let desc = req.database.ok_or_else(|| {
-            Error::InvalidArgument("GetCollectionRequest::database is required".to_owned())
-        })?;
-        let name = req.name;
-        let database = Database::new(self.client.clone(), desc, None);
-        let collection = database.open_collection(name).await?;
-        Ok(GetCollectionResponse { collection: Some(collection.desc()) })
+        request: Request<DeleteCollectionRequest>,
+    ) -> Result<Response<DeleteCollectionResponse>, Status> {
+        let principal = request.extensions().get::<Principal>().cloned();
+        let request = request.into_inner();
+        let database = self.client.open_database(request.database).await?;
+        check_database(principal.as_ref(), database.desc().id, Permission::Admin)?;
+        database.delete_collection(request.name).await?;
+        Ok(Response::new(DeleteCollectionResponse {}))
     }
 
-    async fn list_collections(
-        &self,
-        req: ListCollectionsRequest,
-    ) -> Result<ListCollectionsResponse, Status> {
-        let desc = req.database.ok_or_else(|| {
-            Error::InvalidArgument("ListCollectionRequest::database is required".to_owned())
-        })?;
-        let database = Database::new(self.client.clone(), desc, None);
-        let collections = database.list_collection().await?.into_iter().map(|c| c.desc()).collect();
-        Ok(ListCollectionsResponse { collections })
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.get.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.get);
+        let principal = request.extensions().get::<Principal>().cloned();
+        let app_tag = app_tag_of(&request);
+        let request = request.into_inner();
+        self.check_collection(principal.as_ref(), request.collection_id, Permission::Read)?;
+        let database = self.database(principal.as_ref(), app_tag.as_deref(), request.database_id);
+        let value = database.get(request.collection_id, request.key).await.map_err(Status::from)?;
+        Ok(Response::new(GetResponse { value }))
     }
 
-    async fn create_collection(
-        &self,
-        req: CreateCollectionRequest,
-    ) -> Result<CreateCollectionResponse, Status> {
-        let desc = req.database.ok_or_else(|| {
-            Error::InvalidArgument("CreateCollectionRequest::database is required".to_owned())
-        })?;
-        let partition = req.partition.ok_or_else(|| {
-            Error::InvalidArgument("CreateCollectionRequest::partition is required".to_owned())
-        })?;
-        let name = req.name;
-        let database = Database::new(self.client.clone(), desc, None);
-        let collection = database.create_collection(name, Some(partition.into())).await?;
-        Ok(CreateCollectionResponse { collection: Some(collection.desc()) })
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutResponse>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.put.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.put);
+        let principal = request.extensions().get::<Principal>().cloned();
+        let app_tag = app_tag_of(&request);
+        let request = request.into_inner();
+        self.check_collection(principal.as_ref(), request.collection_id, Permission::Write)?;
+        let put = request.put.ok_or_else(|| Status::invalid_argument("`put` is required"))?;
+        let batch = ClientWriteBatchRequest {
+            puts: vec![(request.collection_id, put)],
+            ..Default::default()
+        };
+        let database = self.database(principal.as_ref(), app_tag.as_deref(), request.database_id);
+        let resp = database.write_batch(batch).await.map_err(Status::from)?;
+        let prev_value = resp.puts.into_iter().next().flatten();
+        Ok(Response::new(PutResponse { prev_value }))
     }
 
-    async fn update_collection(
+    async fn delete(
         &self,
-        _req: UpdateCollectionRequest,
-    ) -> Result<UpdateCollectionResponse, Status> {
-        Err(Status::unimplemented("ProxyServer::update_collection"))
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.delete.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.delete);
+        let principal = request.extensions().get::<Principal>().cloned();
+        let app_tag = app_tag_of(&request);
+        let request = request.into_inner();
+        self.check_collection(principal.as_ref(), request.collection_id, Permission::Write)?;
+        let delete =
+            request.delete.ok_or_else(|| Status::invalid_argument("`delete` is required"))?;
+        let batch = ClientWriteBatchRequest {
+            deletes: vec![(request.collection_id, delete)],
+            ..Default::default()
+        };
+        let database = self.database(principal.as_ref(), app_tag.as_deref(), request.database_id);
+        let resp = database.write_batch(batch).await.map_err(Status::from)?;
+        let prev_value = resp.deletes.into_iter().next().flatten();
+        Ok(Response::new(DeleteResponse { prev_value }))
     }
 
-    async fn delete_collection(
+    async fn scan(&self, request: Request<ScanRequest>) -> Result<Response<ScanResponse>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.scan.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.scan);
+        let principal = request.extensions().get::<Principal>().cloned();
+        let app_tag = app_tag_of(&request);
+        let request = request.into_inner();
+        self.check_collection(principal.as_ref(), request.collection_id, Permission::Read)?;
+        let database = self.database(principal.as_ref(), app_tag.as_deref(), request.database_id);
+        let key_values = database
+            .scan(request.collection_id, request.start_key, request.end_key, request.limit as usize)
+            .await
+            .map_err(Status::from)?
+            .into_iter()
+            .map(|(key, value)| KeyValue { key, value })
+            .collect();
+        Ok(Response::new(ScanResponse { key_values }))
+    }
+
+    async fn write_batch(
         &self,
-        req: DeleteCollectionRequest,
-    ) -> Result<DeleteCollectionResponse, Status> {
-        let desc = req.database.ok_or_else(|| {
-            Error::InvalidArgument("DeleteCollectionRequest::database is required".to_owned())
-        })?;
-        let name = req.name;
-        let database = Database::new(self.client.clone(), desc, None);
-        database.delete_collection(name).await?;
-        Ok(DeleteCollectionResponse {})
+        request: Request<WriteBatchRequest>,
+    ) -> Result<Response<WriteBatchResponse>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.batch.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.batch);
+        let principal = request.extensions().get::<Principal>().cloned();
+        let app_tag = app_tag_of(&request);
+        let request = request.into_inner();
+        let mut batch = ClientWriteBatchRequest::default();
+        for CollectionDelete { collection_id, delete } in request.deletes {
+            let delete = delete.ok_or_else(|| Status::invalid_argument("`delete` is required"))?;
+            self.check_collection(principal.as_ref(), collection_id, Permission::Write)?;
+            batch.deletes.push((collection_id, delete));
+        }
+        for CollectionPut { collection_id, put } in request.puts {
+            let put = put.ok_or_else(|| Status::invalid_argument("`put` is required"))?;
+            self.check_collection(principal.as_ref(), collection_id, Permission::Write)?;
+            batch.puts.push((collection_id, put));
+        }
+        let database = self.database(principal.as_ref(), app_tag.as_deref(), request.database_id);
+        let resp = database.write_batch(batch).await.map_err(Status::from)?;
+        let deletes = resp
+            .deletes
+            .into_iter()
+            .map(|prev_value| WriteResponse { prev_value, ..Default::default() })
+            .collect();
+        let puts = resp
+            .puts
+            .into_iter()
+            .zip(resp.add_results)
+            .map(|(prev_value, add_result)| WriteResponse {
+                prev_value,
+                add_result,
+                ..Default::default()
+            })
+            .collect();
+        Ok(Response::new(WriteBatchResponse { version: resp.version, deletes, puts }))
     }
 }
 
 impl ProxyServer {
-    async fn handle_get(
+    /// Build the database handle for data requests, only the id is required.
+    /// The requests are forwarded for the caller with its workload tag.
+    fn database(
         &self,
-        desc: CollectionDesc,
-        req: GetRequest,
-    ) -> Result<GetResponse, Status> {
-        let collection = Collection::new(self.client.clone(), desc, None);
-        let resp = collection.get(req.key).await?;
-        Ok(GetResponse { value: resp })
-    }
-
-    async fn handle_put(
+        principal: Option<&Principal>,
+        app_tag: Option<&str>,
+        database_id: u64,
+    ) -> Database {
+        let desc = DatabaseDesc { id: database_id, ..Default::default() };
+        Database::new(self.client_for(principal, app_tag), desc, None)
+    }
+
+    /// Check the permission on the database of the collection. The data
+    /// requests specify the database by id, which might not own the
+    /// collection, so the database is resolved from the collection.
+    fn check_collection(
         &self,
-        desc: CollectionDesc,
-        req: PutRequest,
-    ) -> Result<PutResponse, Status> {
-        let collection = Collection::new(self.client.clone(), desc, None);
-        collection
-            .put(req.key, req.value, Some(req.ttl), PutOperation::from_i32(req.op), req.conditions)
-            .await?;
-        Ok(PutResponse {})
-    }
-
-    async fn handle_delete(
-        &self,
-        desc: CollectionDesc,
-        req: DeleteRequest,
-    ) -> Result<DeleteResponse, Status> {
-        let collection = Collection::new(self.client.clone(), desc, None);
-        collection.delete(req.key, req.conditions).await?;
-        Ok(DeleteResponse {})
-    }
-
-    async fn handle_batch(
-        &self,
-        desc: CollectionDesc,
-        req: WriteBatchRequest,
-    ) -> Result<WriteBatchResponse, Status> {
-        let collection = Collection::new(self.client.clone(), desc, None);
-        Ok(collection.write_batch(req).await?)
+        principal: Option<&Principal>,
+        collection_id: u64,
+        required: Permission,
+    ) -> Result<(), Status> {
+        let Some(principal) = principal.filter(|p| !p.is_superuser()) else {
+            return Ok(());
+        };
+        let database_id =
+            self.router.find_collection_by_id(collection_id).map(|desc| desc.db).ok_or_else(
+                || {
+                    Status::unavailable(format!(
+                        "the database of collection {collection_id} is unknown"
+                    ))
+                },
+            )?;
+        check_database(Some(principal), database_id, required)
     }
 }
@@ -0,0 +1,391 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A HTTP/JSON data API served by the proxy, so that the scripts and browsers
+//! could read and write data without gRPC tooling.
+//!
+//! - `GET /v1/db/{db}/co/{co}/key/{key}`: read a key, the version of the value
+//!   is returned in the `ETag` header.
+//! - `PUT /v1/db/{db}/co/{co}/key/{key}`: write a key, the body is a json
+//!   object like `{"value": "<base64>", "ttl": 60}`, the value expires after
+//!   the optional `ttl` seconds.
+//! - `DELETE /v1/db/{db}/co/{co}/key/{key}`: delete a key.
+//! - `GET /v1/db/{db}/co/{co}/scan?start={key}&end={key}&limit={n}`: scan the
+//!   key values in range `[start, end)`.
+//!
+//! The keys in path and query are encoded in url safe base64 without padding,
+//! the keys and values in json are encoded in standard base64. The writes
+//! accept the conditional headers: `If-Match: *` expects the key exists,
+//! `If-Match: "<version>"` expects the version of key, and `If-None-Match: *`
+//! expects the key not exists. The requests are forwarded with the workload
+//! tag in the `x-sekas-app-tag` header.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use sekas_client::{Database, WriteBatchRequest, WriteBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use tonic::body::BoxBody;
+use tonic::codegen::{http, BoxFuture, Service};
+use tonic::transport::NamedService;
+use tonic::{Code, Status};
+
+use super::metrics::*;
+use super::{ProxyServer, APP_TAG_KEY};
+use crate::auth::{check_database, Permission, Principal};
+use crate::record_latency;
+
+#[derive(Deserialize)]
+struct PutBody {
+    value: String,
+    #[serde(default)]
+    ttl: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct RestService {
+    proxy: ProxyServer,
+}
+
+/// Who issues the request, and the workload tag the request is forwarded with.
+struct Caller<'a> {
+    principal: Option<&'a Principal>,
+    app_tag: Option<&'a str>,
+}
+
+impl RestService {
+    pub fn new(proxy: ProxyServer) -> Self {
+        RestService { proxy }
+    }
+
+    async fn handle(
+        &self,
+        req: http::Request<hyper::Body>,
+    ) -> Result<http::Response<String>, Status> {
+        let (parts, body) = req.into_parts();
+        let path = parts.uri.path().trim_matches('/').to_owned();
+        let segments = path.split('/').collect::<Vec<_>>();
+        let principal = parts.extensions.get::<Principal>();
+        let app_tag = parts.headers.get(APP_TAG_KEY).and_then(|v| v.to_str().ok());
+        let caller = Caller { principal, app_tag };
+        match (&parts.method, segments.as_slice()) {
+            (&http::Method::GET, ["v1", "db", db, "co", co, "key", key]) => {
+                self.get(&caller, db, co, decode_key(key)?).await
+            }
+            (&http::Method::PUT, ["v1", "db", db, "co", co, "key", key]) => {
+                let body = hyper::body::to_bytes(body)
+                    .await
+                    .map_err(|e| Status::invalid_argument(format!("read body: {e}")))?;
+                let body: PutBody = serde_json::from_slice(&body)
+                    .map_err(|e| Status::invalid_argument(format!("parse body: {e}")))?;
+                let value = STANDARD
+                    .decode(body.value)
+                    .map_err(|e| Status::invalid_argument(format!("decode value: {e}")))?;
+                let mut builder = WriteBuilder::new(decode_key(key)?);
+                match body.ttl {
+                    Some(0) => return Err(Status::invalid_argument("ttl must be positive")),
+                    Some(ttl) => {
+                        builder = builder.with_expire_at(sekas_rock::time::timestamp() + ttl);
+                    }
+                    None => {}
+                }
+                let put = with_conditions(builder, &parts.headers)?.put(value)?;
+                self.put(&caller, db, co, put).await
+            }
+            (&http::Method::DELETE, ["v1", "db", db, "co", co, "key", key]) => {
+                let builder = WriteBuilder::new(decode_key(key)?);
+                let delete = with_conditions(builder, &parts.headers)?.delete()?;
+                self.delete(&caller, db, co, delete).await
+            }
+            (&http::Method::GET, ["v1", "db", db, "co", co, "scan"]) => {
+                let params: HashMap<String, String> = parts
+                    .uri
+                    .query()
+                    .map(|q| url::form_urlencoded::parse(q.as_bytes()).into_owned().collect())
+                    .unwrap_or_default();
+                self.scan(&caller, db, co, &params).await
+            }
+            (_, [_, _, _, _, _, "key", _]) | (_, [_, _, _, _, _, "scan"]) => Ok(response(
+                http::StatusCode::METHOD_NOT_ALLOWED,
+                json!({ "error": "method not allowed" }),
+            )),
+            _ => Ok(response(http::StatusCode::NOT_FOUND, json!({ "error": "not found" }))),
+        }
+    }
+
+    async fn get(
+        &self,
+        caller: &Caller<'_>,
+        db: &str,
+        co: &str,
+        key: Vec<u8>,
+    ) -> Result<http::Response<String>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.get.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.get);
+        let (database, collection_id) =
+            self.open_collection(caller, db, co, Permission::Read).await?;
+        let value =
+            database.get_raw_value(collection_id, key.clone()).await.map_err(Status::from)?;
+        match value {
+            Some(sekas_api::server::v1::Value { content: Some(content), version, .. }) => {
+                let body = json!({
+                    "key": STANDARD.encode(key),
+                    "value": STANDARD.encode(content),
+                    "version": version,
+                });
+                let mut resp = response(http::StatusCode::OK, body);
+                resp.headers_mut().insert(
+                    http::header::ETAG,
+                    http::HeaderValue::from_str(&format!("\"{version}\"")).unwrap(),
+                );
+                Ok(resp)
+            }
+            _ => Ok(response(http::StatusCode::NOT_FOUND, json!({ "error": "key not found" }))),
+        }
+    }
+
+    async fn put(
+        &self,
+        caller: &Caller<'_>,
+        db: &str,
+        co: &str,
+        put: sekas_api::server::v1::PutRequest,
+    ) -> Result<http::Response<String>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.put.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.put);
+        let (database, collection_id) =
+            self.open_collection(caller, db, co, Permission::Write).await?;
+        let batch = WriteBatchRequest::default().add_put(collection_id, put);
+        let resp = database.write_batch(batch).await.map_err(Status::from)?;
+        Ok(response(http::StatusCode::OK, json!({ "version": resp.version })))
+    }
+
+    async fn delete(
+        &self,
+        caller: &Caller<'_>,
+        db: &str,
+        co: &str,
+        delete: sekas_api::server::v1::DeleteRequest,
+    ) -> Result<http::Response<String>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.delete.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.delete);
+        let (database, collection_id) =
+            self.open_collection(caller, db, co, Permission::Write).await?;
+        let batch = WriteBatchRequest::default().add_delete(collection_id, delete);
+        let resp = database.write_batch(batch).await.map_err(Status::from)?;
+        Ok(response(http::StatusCode::OK, json!({ "version": resp.version })))
+    }
+
+    async fn scan(
+        &self,
+        caller: &Caller<'_>,
+        db: &str,
+        co: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>, Status> {
+        PROXY_SERVICE_DATABASE_REQUEST_TOTAL.scan.inc();
+        record_latency!(&PROXY_SERVICE_DATABASE_REQUEST_DURATION_SECONDS.scan);
+        let start_key = params.get("start").map(|k| decode_key(k)).transpose()?.unwrap_or_default();
+        let end_key = params.get("end").map(|k| decode_key(k)).transpose()?;
+        let limit = match params.get("limit") {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|e| Status::invalid_argument(format!("parse limit: {e}")))?,
+            None => 0,
+        };
+        let (database, collection_id) =
+            self.open_collection(caller, db, co, Permission::Read).await?;
+        let key_values = database
+            .scan(collection_id, start_key, end_key, limit)
+            .await
+            .map_err(Status::from)?
+            .into_iter()
+            .map(|(key, value)| {
+                json!({ "key": STANDARD.encode(key), "value": STANDARD.encode(value) })
+            })
+            .collect::<Vec<_>>();
+        Ok(response(http::StatusCode::OK, json!({ "key_values": key_values })))
+    }
+
+    async fn open_collection(
+        &self,
+        caller: &Caller<'_>,
+        db: &str,
+        co: &str,
+        required: Permission,
+    ) -> Result<(Database, u64), Status> {
+        let client = self.proxy.client_for(caller.principal, caller.app_tag);
+        let database = client.open_database(db.to_owned()).await?;
+        check_database(caller.principal, database.desc().id, required)?;
+        let collection = database.open_collection(co.to_owned()).await?;
+        Ok((database, collection.id))
+    }
+}
+
+impl Service<http::Request<hyper::Body>> for RestService {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<hyper::Body>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let resp = match service.handle(req).await {
+                Ok(resp) => resp,
+                Err(status) => {
+                    let mut resp = response(
+                        to_http_status(status.code()),
+                        json!({ "error": status.message() }),
+                    );
+                    if let Some(secs) = retry_after_secs(&status) {
+                        resp.headers_mut().insert(http::header::RETRY_AFTER, secs.into());
+                    }
+                    resp
+                }
+            };
+            Ok(resp.map(boxed))
+        })
+    }
+}
+
+impl NamedService for RestService {
+    const NAME: &'static str = "v1";
+}
+
+fn with_conditions(
+    mut builder: WriteBuilder,
+    headers: &http::HeaderMap,
+) -> Result<WriteBuilder, Status> {
+    if let Some(value) = headers.get(http::header::IF_MATCH) {
+        let value =
+            value.to_str().map_err(|_| Status::invalid_argument("invalid If-Match header"))?.trim();
+        builder = if value == "*" {
+            builder.expect_exists()
+        } else {
+            let version = value
+                .trim_matches('"')
+                .parse::<u64>()
+                .map_err(|_| Status::invalid_argument("invalid If-Match header"))?;
+            builder.expect_version(version)
+        };
+    }
+    if let Some(value) = headers.get(http::header::IF_NONE_MATCH) {
+        if value.as_bytes() != b"*" {
+            return Err(Status::invalid_argument("only `If-None-Match: *` is supported"));
+        }
+        builder = builder.expect_not_exists();
+    }
+    Ok(builder)
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>, Status> {
+    URL_SAFE_NO_PAD.decode(key).map_err(|e| Status::invalid_argument(format!("decode key: {e}")))
+}
+
+fn to_http_status(code: Code) -> http::StatusCode {
+    match code {
+        Code::InvalidArgument => http::StatusCode::BAD_REQUEST,
+        Code::NotFound => http::StatusCode::NOT_FOUND,
+        Code::AlreadyExists => http::StatusCode::CONFLICT,
+        Code::FailedPrecondition => http::StatusCode::PRECONDITION_FAILED,
+        Code::ResourceExhausted => http::StatusCode::TOO_MANY_REQUESTS,
+        Code::DeadlineExceeded => http::StatusCode::GATEWAY_TIMEOUT,
+        Code::Unavailable => http::StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unauthenticated => http::StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => http::StatusCode::FORBIDDEN,
+        _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Return the `Retry-After` seconds of the requests rejected by the quotas,
+/// rounded up.
+fn retry_after_secs(status: &Status) -> Option<u64> {
+    use prost::Message;
+    use sekas_api::server::v1;
+
+    if status.code() != Code::ResourceExhausted || status.details().is_empty() {
+        return None;
+    }
+    let err = v1::Error::decode(status.details()).ok()?;
+    match sekas_client::Error::from(err) {
+        sekas_client::Error::QuotaExceeded(_, retry_after) if !retry_after.is_zero() => {
+            Some((retry_after.as_millis() as u64 + 999) / 1000)
+        }
+        _ => None,
+    }
+}
+
+fn response(status: http::StatusCode, body: serde_json::Value) -> http::Response<String> {
+    http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .unwrap()
+}
+
+fn boxed(body: String) -> BoxBody {
+    use http_body::Body;
+
+    body.map_err(|_| panic!("")).boxed_unsync()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_conditional_headers() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::IF_MATCH, "\"10\"".parse().unwrap());
+        let put = with_conditions(WriteBuilder::new(vec![]), &headers).unwrap().ensure_put(vec![]);
+        assert_eq!(put.conditions.len(), 1);
+        assert_eq!(put.conditions[0].version, 10);
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::IF_NONE_MATCH, "*".parse().unwrap());
+        let put = with_conditions(WriteBuilder::new(vec![]), &headers).unwrap().ensure_put(vec![]);
+        assert_eq!(put.conditions.len(), 1);
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::IF_NONE_MATCH, "\"10\"".parse().unwrap());
+        assert!(with_conditions(WriteBuilder::new(vec![]), &headers).is_err());
+    }
+
+    #[test]
+    fn decode_url_safe_key() {
+        assert_eq!(decode_key(&URL_SAFE_NO_PAD.encode(b"\xff\xfe")).unwrap(), b"\xff\xfe");
+        assert!(decode_key("a+b/").is_err());
+    }
+
+    #[test]
+    fn retry_after_of_quota_exceeded() {
+        use std::time::Duration;
+
+        let status = |retry_after| {
+            sekas_client::error::quota_exceeded_status("quota".to_owned(), retry_after)
+        };
+        assert_eq!(retry_after_secs(&status(Duration::from_millis(1500))), Some(2));
+        assert_eq!(retry_after_secs(&status(Duration::from_millis(1))), Some(1));
+        assert_eq!(retry_after_secs(&status(Duration::ZERO)), None);
+        assert_eq!(retry_after_secs(&Status::resource_exhausted("quota")), None);
+    }
+}
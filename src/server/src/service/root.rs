@@ -12,11 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use sekas_api::server::v1::*;
 use tonic::{Request, Response, Status};
 
 use super::metrics::*;
-use crate::root::Watcher;
+use super::{operator_or_peer, OPERATOR_KEY};
+use crate::auth::{check_database, check_superuser, Permission, Principal};
+use crate::root::{AuditAction, Watcher};
 use crate::{record_latency, Error, Result, Server};
 
 #[tonic::async_trait]
@@ -25,8 +29,30 @@ impl root_server::Root for Server {
 
     async fn admin(&self, req: Request<AdminRequest>) -> Result<Response<AdminResponse>, Status> {
         record_latency!(take_admin_request_metrics());
+        let principal = req.extensions().get::<Principal>().cloned();
+        let operator = match &principal {
+            Some(principal) => principal.name().to_owned(),
+            None => operator_or_peer(
+                req.metadata().get(OPERATOR_KEY).and_then(|v| v.to_str().ok()),
+                req.remote_addr(),
+            ),
+        };
         let req = req.into_inner();
-        let res = self.handle_admin(req).await?;
+        let principal = principal.filter(|p| !p.is_superuser());
+        if let Some(principal) = &principal {
+            self.check_admin_request(principal, &req).await?;
+        }
+        let mut res = self.handle_admin(&operator, req).await?;
+        if let (
+            Some(principal),
+            Some(AdminResponseUnion {
+                response: Some(admin_response_union::Response::ListDatabases(list)),
+            }),
+        ) = (&principal, &mut res.response)
+        {
+            // Only the databases readable by the user are visible.
+            list.databases.retain(|db| principal.permission(db.id) >= Permission::Read);
+        }
         Ok(Response::new(res))
     }
 
@@ -36,7 +62,8 @@ impl root_server::Root for Server {
     ) -> Result<Response<Self::WatchStream>, Status> {
         record_latency!(take_watch_request_metrics());
         let req = req.into_inner();
-        let watcher = self.wrap(self.root.watch(req.cur_group_epochs).await).await?;
+        let watcher =
+            self.wrap(self.root.watch(req.cur_group_epochs, &req.resource_types).await).await?;
         Ok(Response::new(watcher))
     }
 
@@ -49,12 +76,18 @@ impl root_server::Root for Server {
         let capacity = request
             .capacity
             .ok_or_else(|| Error::InvalidArgument("capacity is required".into()))?;
-        let (cluster_id, node, root) =
-            self.wrap(self.root.join(request.addr, capacity).await).await?;
+        let (cluster_id, node, root) = self
+            .wrap(
+                self.root
+                    .join(request.addr, capacity, request.binary_version, request.labels)
+                    .await,
+            )
+            .await?;
         Ok::<Response<JoinNodeResponse>, Status>(Response::new(JoinNodeResponse {
             cluster_id,
             node_id: node.id,
             root: Some(root),
+            incarnation: node.incarnation,
         }))
     }
 
@@ -64,6 +97,11 @@ impl root_server::Root for Server {
     ) -> Result<Response<ReportResponse>, Status> {
         record_latency!(take_report_request_metrics());
         let request = request.into_inner();
+        self.wrap(self.root.check_node_incarnation(request.node_id, request.incarnation).await)
+            .await?;
+        if let Some(node_id) = request.shutdown_node_id {
+            self.wrap(self.root.shutdown_node(node_id)).await?;
+        }
         self.wrap(self.root.report(request.updates).await).await?;
         Ok(Response::new(ReportResponse {}))
     }
@@ -92,25 +130,89 @@ impl root_server::Root for Server {
 }
 
 impl Server {
-    async fn handle_admin(&self, req: AdminRequest) -> Result<AdminResponse> {
+    /// Check the permission of the user which is not a superuser.
+    async fn check_admin_request(
+        &self,
+        principal: &Principal,
+        req: &AdminRequest,
+    ) -> Result<(), Status> {
+        use admin_request_union::Request;
+
+        let Some(req) = req.request.as_ref().and_then(|r| r.request.as_ref()) else {
+            return Ok(());
+        };
+        let (database, required) = match req {
+            Request::GetDatabase(req) => {
+                (self.root.get_database(&req.tenant, &req.name).await, Permission::Read)
+            }
+            Request::DeleteDatabase(req) => {
+                (self.root.get_database(&req.tenant, &req.name).await, Permission::Admin)
+            }
+            Request::CreateCollection(req) => {
+                (self.find_database(&req.database).await, Permission::Admin)
+            }
+            Request::DeleteCollection(req) => {
+                (self.find_database(&req.database).await, Permission::Admin)
+            }
+            Request::CloneCollection(req) => {
+                (self.find_database(&req.database).await, Permission::Admin)
+            }
+            Request::GetCollection(req) => {
+                (self.find_database(&req.database).await, Permission::Read)
+            }
+            Request::ListCollections(req) => {
+                (self.find_database(&req.database).await, Permission::Read)
+            }
+            Request::DescribeCollection(req) => {
+                (self.find_database(&req.database).await, Permission::Read)
+            }
+            Request::ListDatabases(_) | Request::GetGcTimestamp(_) => return Ok(()),
+            Request::CreateDatabase(_)
+            | Request::UpdateDatabase(_)
+            | Request::UpdateCollection(_) => return check_superuser(Some(principal)),
+            // The GC leases hold back the GC horizon of the cluster.
+            Request::HoldGcLease(_) | Request::ReleaseGcLease(_) => {
+                return check_superuser(Some(principal))
+            }
+        };
+        match self.wrap(database).await? {
+            Some(desc) => check_database(Some(principal), desc.id, required),
+            // Do not reveal whether the database exists.
+            None => check_superuser(Some(principal)),
+        }
+    }
+
+    async fn handle_admin(&self, operator: &str, req: AdminRequest) -> Result<AdminResponse> {
         let mut res = AdminResponse::default();
         let req = req.request.ok_or_else(|| Error::InvalidArgument("AdminRequest".into()))?;
-        res.response = Some(self.wrap(self.handle_admin_union(req).await).await?);
+        let result = match idempotency_token(&req) {
+            Some(token) => {
+                let token = token.to_owned();
+                let operation = self.handle_admin_union(operator, req.clone());
+                self.root.idempotent(&token, req, operation).await
+            }
+            None => self.handle_admin_union(operator, req).await,
+        };
+        res.response = Some(self.wrap(result).await?);
         Ok(res)
     }
 
-    async fn handle_admin_union(&self, req: AdminRequestUnion) -> Result<AdminResponseUnion> {
+    async fn handle_admin_union(
+        &self,
+        operator: &str,
+        req: AdminRequestUnion,
+    ) -> Result<AdminResponseUnion> {
         let req = req.request.ok_or_else(|| Error::InvalidArgument("AdminRequestUnion".into()))?;
         let res = match req {
             admin_request_union::Request::CreateDatabase(req) => {
-                let res = self.handle_create_database(req).await?;
+                let res = self.handle_create_database(operator, req).await?;
                 admin_response_union::Response::CreateDatabase(res)
             }
             admin_request_union::Request::UpdateDatabase(_req) => {
                 todo!()
             }
             admin_request_union::Request::DeleteDatabase(req) => {
-                let res = self.handle_delete_database(req).await?;
+                let res = self.handle_delete_database(operator, req).await?;
                 admin_response_union::Response::DeleteDatabase(res)
             }
             admin_request_union::Request::GetDatabase(req) => {
@@ -122,14 +224,14 @@ impl Server {
                 admin_response_union::Response::ListDatabases(res)
             }
             admin_request_union::Request::CreateCollection(req) => {
-                let res = self.handle_create_collection(req).await?;
+                let res = self.handle_create_collection(operator, req).await?;
                 admin_response_union::Response::CreateCollection(res)
             }
             admin_request_union::Request::UpdateCollection(_req) => {
                 todo!()
             }
             admin_request_union::Request::DeleteCollection(req) => {
-                let res = self.handle_delete_collection(req).await?;
+                let res = self.handle_delete_collection(operator, req).await?;
                 admin_response_union::Response::DeleteCollection(res)
             }
             admin_request_union::Request::GetCollection(req) => {
@@ -140,58 +242,122 @@ impl Server {
                 let res = self.handle_list_collection(req).await?;
                 admin_response_union::Response::ListCollections(res)
             }
+            admin_request_union::Request::GetGcTimestamp(req) => {
+                let res = self.handle_get_gc_timestamp(req).await?;
+                admin_response_union::Response::GetGcTimestamp(res)
+            }
+            admin_request_union::Request::HoldGcLease(req) => {
+                let res = self.handle_hold_gc_lease(req).await?;
+                admin_response_union::Response::HoldGcLease(res)
+            }
+            admin_request_union::Request::ReleaseGcLease(req) => {
+                let res = self.handle_release_gc_lease(req).await?;
+                admin_response_union::Response::ReleaseGcLease(res)
+            }
+            admin_request_union::Request::CloneCollection(req) => {
+                let res = self.handle_clone_collection(operator, req).await?;
+                admin_response_union::Response::CloneCollection(res)
+            }
+            admin_request_union::Request::DescribeCollection(req) => {
+                let res = self.handle_describe_collection(req).await?;
+                admin_response_union::Response::DescribeCollection(res)
+            }
         };
         Ok(AdminResponseUnion { response: Some(res) })
     }
 
     async fn handle_create_database(
         &self,
+        operator: &str,
         req: CreateDatabaseRequest,
     ) -> Result<CreateDatabaseResponse> {
-        let desc = self.root.create_database(req.name).await?;
-        Ok(CreateDatabaseResponse { database: Some(desc) })
+        let target = format!("tenant={}, database={}", req.tenant, req.name);
+        let result = self.root.create_database(&req.tenant, req.name).await;
+        self.root.audit(operator, AuditAction::CreateDatabase, target, &result).await;
+        Ok(CreateDatabaseResponse { database: Some(result?) })
     }
 
     async fn handle_delete_database(
         &self,
+        operator: &str,
         req: DeleteDatabaseRequest,
     ) -> Result<DeleteDatabaseResponse> {
-        self.root.delete_database(&req.name).await?;
+        let result = self.root.delete_database(&req.tenant, &req.name).await;
+        let target = format!("tenant={}, database={}", req.tenant, req.name);
+        self.root.audit(operator, AuditAction::DeleteDatabase, target, &result).await;
+        result?;
         Ok(DeleteDatabaseResponse {})
     }
 
     async fn handle_get_database(&self, req: GetDatabaseRequest) -> Result<GetDatabaseResponse> {
-        let database = self.root.get_database(&req.name).await?;
+        let database = self.root.get_database(&req.tenant, &req.name).await?;
         Ok(GetDatabaseResponse { database })
     }
 
     async fn handle_list_database(
         &self,
-        _req: ListDatabasesRequest,
+        req: ListDatabasesRequest,
     ) -> Result<ListDatabasesResponse> {
-        let databases = self.root.list_database().await?;
+        let databases = self.root.list_database(&req.tenant).await?;
         Ok(ListDatabasesResponse { databases })
     }
 
     async fn handle_create_collection(
         &self,
+        operator: &str,
         req: CreateCollectionRequest,
     ) -> Result<CreateCollectionResponse> {
         let database = req.database.ok_or_else(|| {
             Error::InvalidArgument("CreateCollectionRequest::database".to_owned())
         })?;
-        let desc = self.root.create_collection(req.name, database.name).await?;
-        Ok(CreateCollectionResponse { collection: Some(desc) })
+        let compression = CompressionCodec::from_i32(req.compression).ok_or_else(|| {
+            Error::InvalidArgument("CreateCollectionRequest::compression".to_owned())
+        })?;
+        let target = format!("database={}, collection={}", database.name, req.name);
+        let result = self
+            .root
+            .create_collection(
+                req.name,
+                &database,
+                req.encrypted,
+                compression,
+                req.key_schema,
+                req.constraints,
+            )
+            .await;
+        self.root.audit(operator, AuditAction::CreateCollection, target, &result).await;
+        Ok(CreateCollectionResponse { collection: Some(result?) })
+    }
+
+    async fn handle_clone_collection(
+        &self,
+        operator: &str,
+        req: CloneCollectionRequest,
+    ) -> Result<CloneCollectionResponse> {
+        let database = req.database.ok_or_else(|| {
+            Error::InvalidArgument("CloneCollectionRequest::database is required".to_owned())
+        })?;
+        let target = format!(
+            "database={}, source={}, target={}",
+            database.name, req.source_name, req.target_name
+        );
+        let result = self.root.clone_collection(&req.source_name, req.target_name, &database).await;
+        self.root.audit(operator, AuditAction::CloneCollection, target, &result).await;
+        Ok(CloneCollectionResponse { collection: Some(result?) })
     }
 
     async fn handle_delete_collection(
         &self,
+        operator: &str,
         req: DeleteCollectionRequest,
     ) -> Result<DeleteCollectionResponse> {
         let database = req.database.ok_or_else(|| {
             Error::InvalidArgument("DeleteCollectionRequest::database is required".to_owned())
         })?;
-        self.root.delete_collection(&req.name, &database).await?;
+        let result = self.root.delete_collection(&req.name, &database).await;
+        let target = format!("database={}, collection={}", database.name, req.name);
+        self.root.audit(operator, AuditAction::DeleteCollection, target, &result).await;
+        result?;
         Ok(DeleteCollectionResponse {})
     }
 
@@ -217,6 +383,48 @@ impl Server {
         Ok(ListCollectionsResponse { collections })
     }
 
+    async fn handle_describe_collection(
+        &self,
+        req: DescribeCollectionRequest,
+    ) -> Result<DescribeCollectionResponse> {
+        let database = req.database.ok_or_else(|| {
+            Error::InvalidArgument("DescribeCollectionRequest::database is required".to_owned())
+        })?;
+        let description = self.root.describe_collection(&database, &req.name).await?;
+        Ok(DescribeCollectionResponse { description })
+    }
+
+    async fn handle_get_gc_timestamp(
+        &self,
+        req: GetGcTimestampRequest,
+    ) -> Result<GetGcTimestampResponse> {
+        let min_gc_timestamp = self.root.min_gc_timestamp(req.collection_id).await?;
+        Ok(GetGcTimestampResponse { min_gc_timestamp })
+    }
+
+    async fn handle_hold_gc_lease(&self, req: HoldGcLeaseRequest) -> Result<HoldGcLeaseResponse> {
+        let ttl = Duration::from_millis(req.ttl_ms);
+        let (lease_id, expired_at) =
+            self.root.hold_gc_lease(req.lease_id, req.collection_id, req.timestamp, ttl).await?;
+        Ok(HoldGcLeaseResponse { lease_id, expired_at })
+    }
+
+    async fn handle_release_gc_lease(
+        &self,
+        req: ReleaseGcLeaseRequest,
+    ) -> Result<ReleaseGcLeaseResponse> {
+        self.root.release_gc_lease(req.lease_id).await?;
+        Ok(ReleaseGcLeaseResponse {})
+    }
+
+    /// Find the latest descriptor of the database carried by the request.
+    async fn find_database(&self, desc: &Option<DatabaseDesc>) -> Result<Option<DatabaseDesc>> {
+        match desc {
+            Some(desc) => self.root.find_database(desc).await,
+            None => Ok(None),
+        }
+    }
+
     async fn wrap<T>(&self, result: Result<T>) -> Result<T> {
         match result {
             Err(Error::NotRootLeader(..) | Error::GroupNotFound(_)) => {
@@ -239,3 +447,18 @@ impl Server {
         }
     }
 }
+
+/// Return the idempotency token carried by the DDL request, if any.
+fn idempotency_token(req: &AdminRequestUnion) -> Option<&str> {
+    use admin_request_union::Request;
+
+    let token = match req.request.as_ref()? {
+        Request::CreateDatabase(req) => &req.idempotency_token,
+        Request::DeleteDatabase(req) => &req.idempotency_token,
+        Request::CreateCollection(req) => &req.idempotency_token,
+        Request::DeleteCollection(req) => &req.idempotency_token,
+        Request::CloneCollection(req) => &req.idempotency_token,
+        _ => return None,
+    };
+    Some(token.as_str()).filter(|token| !token.is_empty())
+}
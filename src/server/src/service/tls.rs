@@ -0,0 +1,235 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use hyper::server::conn::AddrStream;
+use log::{debug, info, warn};
+use sekas_runtime::TcpIncoming;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+
+use crate::{Error, Result, TlsConfig};
+
+/// The handshakes not finished after the duration are aborted.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accept the TLS connections, the server config is rebuilt once the
+/// certificate files are changed, and applied to the new connections.
+pub(crate) struct TlsAcceptor {
+    cfg: TlsConfig,
+    core: RwLock<Core>,
+}
+
+struct Core {
+    /// The contents of the files that the config is built from.
+    contents: Vec<Vec<u8>>,
+    config: Arc<ServerConfig>,
+}
+
+impl TlsAcceptor {
+    pub(crate) fn new(cfg: &TlsConfig) -> Result<Self> {
+        let contents = read_files(cfg)?;
+        let config = Arc::new(build_server_config(cfg, &contents)?);
+        Ok(TlsAcceptor { cfg: cfg.clone(), core: RwLock::new(Core { contents, config }) })
+    }
+
+    /// Reload the certificates if the files are changed, return whether the
+    /// config is reloaded. The config in use is kept if the new one is
+    /// invalid.
+    pub(crate) fn reload_if_changed(&self) -> Result<bool> {
+        let contents = read_files(&self.cfg)?;
+        if self.core.read().unwrap().contents == contents {
+            return Ok(false);
+        }
+        let config = Arc::new(build_server_config(&self.cfg, &contents)?);
+        *self.core.write().unwrap() = Core { contents, config };
+        Ok(true)
+    }
+
+    async fn accept(&self, stream: AddrStream) -> std::io::Result<TlsStream<AddrStream>> {
+        let config = self.core.read().unwrap().config.clone();
+        tokio_rustls::TlsAcceptor::from(config).accept(stream).await
+    }
+}
+
+/// Wrap the incoming tcp connections with TLS. The handshakes are performed
+/// in the background, so that a slow client does not block the others.
+pub(crate) fn tls_incoming(
+    mut incoming: TcpIncoming,
+    acceptor: Arc<TlsAcceptor>,
+) -> impl Stream<Item = std::io::Result<TlsStream<AddrStream>>> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(128);
+    let accept_handle = sekas_runtime::spawn(async move {
+        while let Some(stream) = incoming.next().await {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("accept tcp connection: {err}");
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let sender = sender.clone();
+            // The handshakes are detached, they are bounded by the timeout.
+            tokio::spawn(async move {
+                let remote_addr = stream.remote_addr();
+                let handshake = acceptor.accept(stream);
+                match sekas_runtime::time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+                    Ok(Ok(stream)) => {
+                        // The server is shutdown if the receiver is dropped.
+                        let _ = sender.send(Ok(stream)).await;
+                    }
+                    Ok(Err(err)) => debug!("tls handshake with {remote_addr}: {err}"),
+                    Err(_) => debug!("tls handshake with {remote_addr}: timeout"),
+                }
+            });
+        }
+    });
+    async_stream::stream! {
+        // The accepting is stopped once the stream is dropped.
+        let _accept_handle = accept_handle;
+        while let Some(stream) = receiver.recv().await {
+            yield stream;
+        }
+    }
+}
+
+/// Check the certificate files periodically and reload the changed ones.
+pub(crate) async fn tls_reload_main(acceptor: Arc<TlsAcceptor>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        match acceptor.reload_if_changed() {
+            Ok(true) => info!("tls certificates are reloaded"),
+            Ok(false) => {}
+            Err(err) => warn!("reload tls certificates: {err}"),
+        }
+    }
+}
+
+/// Read the certificate, private key and CA certificates.
+fn read_files(cfg: &TlsConfig) -> Result<Vec<Vec<u8>>> {
+    [&cfg.cert, &cfg.key, &cfg.ca].into_iter().map(|path| Ok(std::fs::read(path)?)).collect()
+}
+
+fn build_server_config(cfg: &TlsConfig, contents: &[Vec<u8>]) -> Result<ServerConfig> {
+    let certs = parse_certs(&cfg.cert, &contents[0])?;
+    let key = parse_private_key(&cfg.key, &contents[1])?;
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = if cfg.mutual {
+        let mut roots = RootCertStore::empty();
+        for cert in parse_certs(&cfg.ca, &contents[2])? {
+            roots.add(&cert).map_err(|err| {
+                Error::InvalidArgument(format!("ca {}: {err:?}", cfg.ca.display()))
+            })?;
+        }
+        builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+    } else {
+        builder.with_no_client_auth()
+    };
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|err| Error::InvalidArgument(format!("tls cert: {err}")))?;
+    // The admin service is served over http1.
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn parse_certs(path: &Path, content: &[u8]) -> Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(content))?;
+    if certs.is_empty() {
+        return Err(Error::InvalidArgument(format!("no certificate in {}", path.display())));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn parse_private_key(path: &Path, content: &[u8]) -> Result<PrivateKey> {
+    use rustls_pemfile::Item;
+
+    let mut reader = BufReader::new(content);
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        match item {
+            Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => {
+                return Ok(PrivateKey(key))
+            }
+            _ => {}
+        }
+    }
+    Err(Error::InvalidArgument(format!("no private key in {}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509NameBuilder, X509};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    /// Generate a self-signed certificate and its private key in PEM.
+    fn self_signed_cert(name: &str) -> (Vec<u8>, Vec<u8>) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_issuer_name(&subject).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = builder.build();
+        (cert.to_pem().unwrap(), key.private_key_to_pem_pkcs8().unwrap())
+    }
+
+    #[test]
+    fn reload_changed_certificates() {
+        let dir = TempDir::new("tls").unwrap();
+        let cfg = TlsConfig {
+            cert: dir.path().join("node.crt"),
+            key: dir.path().join("node.key"),
+            ca: dir.path().join("node.crt"),
+            mutual: true,
+            domain_name: None,
+            reload_interval_sec: 0,
+        };
+        let (cert, key) = self_signed_cert("node-1");
+        std::fs::write(&cfg.cert, cert).unwrap();
+        std::fs::write(&cfg.key, key).unwrap();
+
+        let acceptor = TlsAcceptor::new(&cfg).unwrap();
+        assert!(!acceptor.reload_if_changed().unwrap());
+
+        // The invalid certificate is rejected, and the config in use is kept.
+        std::fs::write(&cfg.cert, b"invalid").unwrap();
+        assert!(acceptor.reload_if_changed().is_err());
+
+        let (cert, key) = self_signed_cert("node-2");
+        std::fs::write(&cfg.cert, cert).unwrap();
+        std::fs::write(&cfg.key, key).unwrap();
+        assert!(acceptor.reload_if_changed().unwrap());
+        assert!(!acceptor.reload_if_changed().unwrap());
+    }
+}
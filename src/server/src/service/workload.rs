@@ -0,0 +1,451 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::warn;
+use sekas_api::server::v1::BatchRequest;
+
+use crate::{DynamicConfig, Error, Result, WorkloadConfig};
+
+/// The workload tag of requests without tag.
+pub const DEFAULT_APP_TAG: &str = "default";
+
+/// The workload tag reported in metrics once the number of tags exceeds the
+/// limit.
+pub const OTHER_APP_TAG: &str = "other";
+
+/// The default max number of distinct workload tags reported in metrics.
+const DEFAULT_MAX_METRIC_APP_TAGS: usize = 64;
+
+/// The full and idle buckets of clients are dropped once the number of clients
+/// exceeds it, the least recently used one is dropped if none of them is idle.
+const MAX_CLIENT_BUCKETS: usize = 1024;
+
+/// The buckets of clients without requests in the duration are idle.
+const CLIENT_BUCKET_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Account and throttle the requests by workload tags, and enforce the quotas
+/// of clients.
+pub struct WorkloadController {
+    slow_request_threshold: Option<Duration>,
+    rate_limits: HashMap<String, u64>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+    dynamic_config: Arc<DynamicConfig>,
+    client_buckets: Mutex<HashMap<String, ClientBuckets>>,
+    max_metric_app_tags: usize,
+    metric_app_tags: Mutex<HashSet<String>>,
+}
+
+/// Log the batch request if it exceeds the threshold when dropping.
+pub struct SlowRequestGuard {
+    app_tag: String,
+    group_ids: Vec<u64>,
+    threshold: Duration,
+    start: Instant,
+}
+
+struct TokenBucket {
+    /// The number of tokens refilled per second, it is also the capacity.
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// The buckets of a client, `None` means no limit.
+struct ClientBuckets {
+    requests: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    last_used: Instant,
+}
+
+impl WorkloadController {
+    pub fn new(cfg: &WorkloadConfig, dynamic_config: Arc<DynamicConfig>) -> Self {
+        // Zero means no limit.
+        let rate_limits =
+            cfg.rate_limits.iter().filter(|(_, v)| **v > 0).map(|(k, v)| (k.clone(), *v)).collect();
+        WorkloadController {
+            slow_request_threshold: cfg.slow_request_threshold_ms.map(Duration::from_millis),
+            rate_limits,
+            buckets: Mutex::default(),
+            dynamic_config,
+            client_buckets: Mutex::default(),
+            max_metric_app_tags: cfg.max_metric_app_tags.unwrap_or(DEFAULT_MAX_METRIC_APP_TAGS),
+            metric_app_tags: Mutex::default(),
+        }
+    }
+
+    /// Return the label of the workload tag used in metrics. The tags are
+    /// supplied by clients, so only the tags with rate limits and the first
+    /// `max_metric_app_tags` tags are reported, the others are reported as
+    /// [`OTHER_APP_TAG`] to bound the cardinality of metrics.
+    pub fn metric_app_tag<'a>(&self, app_tag: &'a str) -> &'a str {
+        if app_tag == DEFAULT_APP_TAG || self.rate_limits.contains_key(app_tag) {
+            return app_tag;
+        }
+
+        let mut metric_app_tags = self.metric_app_tags.lock().unwrap();
+        if metric_app_tags.contains(app_tag) {
+            app_tag
+        } else if metric_app_tags.len() < self.max_metric_app_tags {
+            metric_app_tags.insert(app_tag.to_owned());
+            app_tag
+        } else {
+            OTHER_APP_TAG
+        }
+    }
+
+    /// Check the size of the batch request and the rate limits of the client.
+    /// The size is checked for all requests, and the client is `None` if it is
+    /// exempt from the rate limits, eg. the other nodes. The limits are read
+    /// from the dynamic config, so they could be adjusted at runtime.
+    pub fn check_client_quota(
+        &self,
+        client: Option<&str>,
+        num_requests: usize,
+        num_bytes: usize,
+    ) -> Result<()> {
+        self.check_client_quota_at(client, num_requests, num_bytes, Instant::now())
+    }
+
+    fn check_client_quota_at(
+        &self,
+        client: Option<&str>,
+        num_requests: usize,
+        num_bytes: usize,
+        now: Instant,
+    ) -> Result<()> {
+        if let Some(max_request_bytes) = self.dynamic_config.max_request_bytes() {
+            if num_bytes as u64 > max_request_bytes {
+                return Err(Error::QuotaExceeded(
+                    format!("the request size {num_bytes} exceeds the limit {max_request_bytes}"),
+                    Duration::ZERO,
+                ));
+            }
+        }
+        let Some(client) = client else {
+            return Ok(());
+        };
+
+        let requests_per_sec = self.dynamic_config.client_requests_per_sec();
+        let bytes_per_sec = self.dynamic_config.client_bytes_per_sec();
+        if requests_per_sec.is_none() && bytes_per_sec.is_none() {
+            return Ok(());
+        }
+
+        let mut client_buckets = self.client_buckets.lock().unwrap();
+        if client_buckets.len() >= MAX_CLIENT_BUCKETS && !client_buckets.contains_key(client) {
+            evict_client_buckets(&mut client_buckets, now);
+        }
+        let buckets = client_buckets.entry(client.to_owned()).or_insert_with(|| ClientBuckets {
+            requests: None,
+            bytes: None,
+            last_used: now,
+        });
+        buckets.last_used = now;
+        update_rate(&mut buckets.requests, requests_per_sec, now);
+        update_rate(&mut buckets.bytes, bytes_per_sec, now);
+
+        // Both limits are checked before consuming, so the rejected requests take no
+        // tokens.
+        let wait_time = |bucket: &mut Option<TokenBucket>, num_tokens: usize| {
+            bucket.as_mut().map(|b| b.wait_time(num_tokens as f64, now)).unwrap_or_default()
+        };
+        let retry_after = std::cmp::max(
+            wait_time(&mut buckets.requests, num_requests),
+            wait_time(&mut buckets.bytes, num_bytes),
+        );
+        if !retry_after.is_zero() {
+            return Err(Error::QuotaExceeded(
+                format!("the requests of client {client} exceed the rate limit"),
+                retry_after,
+            ));
+        }
+        if let Some(bucket) = buckets.requests.as_mut() {
+            bucket.consume(num_requests as f64);
+        }
+        if let Some(bucket) = buckets.bytes.as_mut() {
+            bucket.consume(num_bytes as f64);
+        }
+        Ok(())
+    }
+
+    /// Acquire permits for requests of the workload tag, return false if the
+    /// rate limit is exceeded.
+    pub fn try_acquire(&self, app_tag: &str, num_requests: usize) -> bool {
+        self.try_acquire_at(app_tag, num_requests, Instant::now())
+    }
+
+    fn try_acquire_at(&self, app_tag: &str, num_requests: usize, now: Instant) -> bool {
+        let Some(rate) = self.rate_limits.get(app_tag) else {
+            return true;
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(app_tag.to_owned())
+            .or_insert_with(|| TokenBucket::new(*rate as f64, now));
+        bucket.try_acquire(num_requests as f64, now)
+    }
+
+    /// Return a guard to log the request if it is slow.
+    pub fn slow_request_guard(
+        &self,
+        app_tag: &str,
+        request: &BatchRequest,
+    ) -> Option<SlowRequestGuard> {
+        self.slow_request_threshold.map(|threshold| SlowRequestGuard {
+            app_tag: app_tag.to_owned(),
+            group_ids: request.requests.iter().map(|r| r.group_id).collect(),
+            threshold,
+            start: Instant::now(),
+        })
+    }
+}
+
+/// Return the workload tag used for accounting.
+#[inline]
+pub fn app_tag_or_default(app_tag: &str) -> &str {
+    if app_tag.is_empty() {
+        DEFAULT_APP_TAG
+    } else {
+        app_tag
+    }
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        TokenBucket { rate, tokens: rate, last_refill: now }
+    }
+
+    fn try_acquire(&mut self, num_tokens: f64, now: Instant) -> bool {
+        if self.wait_time(num_tokens, now).is_zero() {
+            self.consume(num_tokens);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = f64::min(self.rate, self.tokens + elapsed * self.rate);
+        self.last_refill = now;
+    }
+
+    /// Return the duration to wait until the tokens are enough, zero if they
+    /// are enough now.
+    fn wait_time(&mut self, num_tokens: f64, now: Instant) -> Duration {
+        self.refill(now);
+        // A batch larger than the capacity is allowed once the bucket is full.
+        let num_tokens = f64::min(num_tokens, self.rate);
+        if self.tokens >= num_tokens {
+            return Duration::ZERO;
+        }
+        let wait_time = Duration::from_secs_f64((num_tokens - self.tokens) / self.rate);
+        // At least 1ms, since zero means never admitted in the retry hints.
+        std::cmp::max(wait_time, Duration::from_millis(1))
+    }
+
+    fn consume(&mut self, num_tokens: f64) {
+        self.tokens = f64::max(0.0, self.tokens - f64::min(num_tokens, self.rate));
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.rate
+    }
+}
+
+impl ClientBuckets {
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.requests.as_mut().map(|b| b.is_full(now)).unwrap_or(true)
+            && self.bytes.as_mut().map(|b| b.is_full(now)).unwrap_or(true)
+    }
+
+    /// The full buckets behave the same as the new buckets, and the idle ones
+    /// are unlikely to be used soon, so both are safe to drop.
+    fn is_evictable(&mut self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_used) >= CLIENT_BUCKET_IDLE_TIMEOUT
+            || self.is_full(now)
+    }
+}
+
+/// Drop the full and idle buckets, and the least recently used one if none of
+/// them could be dropped, so that the number of buckets is bounded.
+fn evict_client_buckets(client_buckets: &mut HashMap<String, ClientBuckets>, now: Instant) {
+    client_buckets.retain(|_, buckets| !buckets.is_evictable(now));
+    if client_buckets.len() >= MAX_CLIENT_BUCKETS {
+        let lru = client_buckets
+            .iter()
+            .min_by_key(|(_, buckets)| buckets.last_used)
+            .map(|(client, _)| client.clone());
+        if let Some(client) = lru {
+            client_buckets.remove(&client);
+        }
+    }
+}
+
+/// Reset the bucket if the limit is changed by the dynamic config.
+fn update_rate(bucket: &mut Option<TokenBucket>, rate: Option<u64>, now: Instant) {
+    match rate {
+        None => *bucket = None,
+        Some(rate) if bucket.as_ref().map(|b| b.rate) != Some(rate as f64) => {
+            *bucket = Some(TokenBucket::new(rate as f64, now));
+        }
+        Some(_) => {}
+    }
+}
+
+impl Drop for SlowRequestGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        if elapsed >= self.threshold {
+            warn!(
+                "slow group requests of app {}: groups {:?}, takes {elapsed:?}",
+                self.app_tag, self.group_ids
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workload_rate_limit() {
+        let cfg = WorkloadConfig {
+            rate_limits: HashMap::from([("app".to_owned(), 10), ("zero".to_owned(), 0)]),
+            ..Default::default()
+        };
+        let controller = WorkloadController::new(&cfg, Arc::default());
+        let now = Instant::now();
+
+        // No limits.
+        assert!(controller.try_acquire_at(DEFAULT_APP_TAG, 100, now));
+        assert!(controller.try_acquire_at("zero", 100, now));
+
+        assert!(controller.try_acquire_at("app", 6, now));
+        assert!(!controller.try_acquire_at("app", 6, now));
+        assert!(controller.try_acquire_at("app", 4, now));
+        assert!(!controller.try_acquire_at("app", 1, now));
+
+        // Refill 5 tokens.
+        let now = now + Duration::from_millis(500);
+        assert!(!controller.try_acquire_at("app", 6, now));
+        assert!(controller.try_acquire_at("app", 5, now));
+
+        // The batch larger than the capacity is allowed if the bucket is full.
+        let now = now + Duration::from_secs(1);
+        assert!(controller.try_acquire_at("app", 100, now));
+        assert!(!controller.try_acquire_at("app", 1, now));
+    }
+
+    #[test]
+    fn workload_client_quota() {
+        let dynamic_config = Arc::new(DynamicConfig::default());
+        let controller =
+            WorkloadController::new(&WorkloadConfig::default(), dynamic_config.clone());
+        let now = Instant::now();
+
+        // No limits.
+        assert!(controller.check_client_quota_at(Some("alice"), 100, 1 << 20, now).is_ok());
+
+        dynamic_config.set("workload.max_request_bytes", "1024").unwrap();
+        dynamic_config.set("workload.client_requests_per_sec", "10").unwrap();
+        dynamic_config.set("workload.client_bytes_per_sec", "1000").unwrap();
+
+        // The large requests are never admitted, even from the exempt clients, which
+        // are not rate limited.
+        let err = controller.check_client_quota_at(Some("alice"), 1, 2048, now).unwrap_err();
+        assert!(matches!(err, Error::QuotaExceeded(_, retry_after) if retry_after.is_zero()));
+        let err = controller.check_client_quota_at(None, 1, 2048, now).unwrap_err();
+        assert!(matches!(err, Error::QuotaExceeded(_, retry_after) if retry_after.is_zero()));
+        assert!(controller.check_client_quota_at(None, 100, 1000, now).is_ok());
+
+        assert!(controller.check_client_quota_at(Some("alice"), 5, 500, now).is_ok());
+        let err = controller.check_client_quota_at(Some("alice"), 1, 600, now).unwrap_err();
+        let Error::QuotaExceeded(_, retry_after) = err else { panic!("{err:?}") };
+        assert_eq!(retry_after, Duration::from_millis(100));
+        // The rejected requests take no tokens.
+        assert!(controller.check_client_quota_at(Some("alice"), 5, 500, now).is_ok());
+        assert!(controller.check_client_quota_at(Some("alice"), 1, 1, now).is_err());
+
+        // The clients are limited separately.
+        assert!(controller.check_client_quota_at(Some("bob"), 10, 10, now).is_ok());
+
+        let now = now + Duration::from_millis(100);
+        assert!(controller.check_client_quota_at(Some("alice"), 1, 100, now).is_ok());
+
+        // The limits are disabled at runtime.
+        dynamic_config.set("workload.client_requests_per_sec", "0").unwrap();
+        dynamic_config.set("workload.client_bytes_per_sec", "0").unwrap();
+        assert!(controller.check_client_quota_at(Some("alice"), 100, 1000, now).is_ok());
+    }
+
+    #[test]
+    fn workload_client_buckets_bounded() {
+        let dynamic_config = Arc::new(DynamicConfig::default());
+        dynamic_config.set("workload.client_requests_per_sec", "10").unwrap();
+        let controller =
+            WorkloadController::new(&WorkloadConfig::default(), dynamic_config.clone());
+        let now = Instant::now();
+
+        // None of the buckets is full or idle.
+        for i in 0..MAX_CLIENT_BUCKETS + 10 {
+            let client = format!("client-{i}");
+            let now = now + Duration::from_micros(i as u64);
+            assert!(controller.check_client_quota_at(Some(&client), 5, 0, now).is_ok());
+        }
+        let client_buckets = controller.client_buckets.lock().unwrap();
+        assert_eq!(client_buckets.len(), MAX_CLIENT_BUCKETS);
+        // The least recently used ones are dropped.
+        assert!(!client_buckets.contains_key("client-0"));
+        assert!(client_buckets.contains_key(&format!("client-{}", MAX_CLIENT_BUCKETS + 9)));
+        drop(client_buckets);
+
+        // The idle buckets are dropped.
+        let now = now + CLIENT_BUCKET_IDLE_TIMEOUT + Duration::from_secs(1);
+        assert!(controller.check_client_quota_at(Some("new"), 5, 0, now).is_ok());
+        assert_eq!(controller.client_buckets.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn workload_metric_app_tag() {
+        let cfg = WorkloadConfig {
+            rate_limits: HashMap::from([("limited".to_owned(), 10)]),
+            max_metric_app_tags: Some(2),
+            ..Default::default()
+        };
+        let controller = WorkloadController::new(&cfg, Arc::default());
+
+        assert_eq!(controller.metric_app_tag("a"), "a");
+        assert_eq!(controller.metric_app_tag("b"), "b");
+        assert_eq!(controller.metric_app_tag("c"), OTHER_APP_TAG);
+        assert_eq!(controller.metric_app_tag("a"), "a");
+
+        // The default tag and the tags with rate limits are always reported.
+        assert_eq!(controller.metric_app_tag(DEFAULT_APP_TAG), DEFAULT_APP_TAG);
+        assert_eq!(controller.metric_app_tag("limited"), "limited");
+    }
+
+    #[test]
+    fn workload_app_tag_or_default() {
+        assert_eq!(app_tag_or_default(""), DEFAULT_APP_TAG);
+        assert_eq!(app_tag_or_default("app"), "app");
+    }
+}
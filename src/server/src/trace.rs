@@ -0,0 +1,109 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use opentelemetry::propagation::Extractor;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self, Sampler, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use tonic::metadata::{KeyRef, MetadataMap};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{Error, Result, TraceConfig};
+
+/// Install the OTLP exporter and return the tracer, which is used to build the
+/// `tracing_opentelemetry` layer. `None` is returned if tracing is disabled.
+///
+/// It must be called within a tokio runtime, the spans are exported by a
+/// background task.
+pub fn init_tracer(cfg: &TraceConfig) -> Result<Option<Tracer>> {
+    let Some(endpoint) = cfg.otlp_endpoint.as_ref() else {
+        return Ok(None);
+    };
+    if !(0.0..=1.0).contains(&cfg.sample_ratio) {
+        return Err(Error::InvalidArgument(format!(
+            "trace.sample_ratio {} is out of range [0, 1]",
+            cfg.sample_ratio
+        )));
+    }
+
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(cfg.sample_ratio)));
+    let resource = Resource::new(vec![KeyValue::new("service.name", cfg.service_name.clone())]);
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(trace::config().with_sampler(sampler).with_resource(resource))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .map_err(|e| Error::InvalidArgument(format!("install OTLP exporter: {e}")))?;
+    Ok(Some(tracer))
+}
+
+/// Flush the pending spans and shutdown the exporter.
+pub fn shutdown_tracer() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Set the trace context propagated by the client as the parent of the span.
+pub(crate) fn set_remote_parent(span: &tracing::Span, metadata: &MetadataMap) {
+    let cx = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&MetadataExtractor(metadata))
+    });
+    span.set_parent(cx);
+}
+
+struct MetadataExtractor<'a>(&'a MetadataMap);
+
+impl<'a> Extractor for MetadataExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .filter_map(|key| match key {
+                KeyRef::Ascii(key) => Some(key.as_str()),
+                KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::trace::TraceContextExt;
+
+    use super::*;
+
+    #[test]
+    fn extract_trace_context() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap(),
+        );
+        let extractor = MetadataExtractor(&metadata);
+        assert_eq!(extractor.keys(), vec!["traceparent"]);
+
+        let cx = TraceContextPropagator::new().extract(&extractor);
+        let span_context = cx.span().span_context().clone();
+        assert!(span_context.is_remote());
+        assert!(span_context.is_sampled());
+        assert_eq!(format!("{:032x}", span_context.trace_id()), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(format!("{:016x}", span_context.span_id()), "b7ad6b7169203331");
+    }
+}
@@ -35,16 +35,21 @@ pub(crate) struct TransportManager {
 }
 
 impl TransportManager {
-    pub(crate) async fn new(root_list: Vec<String>, state_engine: StateEngine) -> Self {
+    pub(crate) async fn new(
+        root_list: Vec<String>,
+        state_engine: StateEngine,
+        tls: Option<TlsOptions>,
+        interceptor: TokenInterceptor,
+    ) -> Self {
         let discovery = Arc::new(RootDiscovery::new(root_list, state_engine));
-        let conn_manager = ConnManager::new();
+        let conn_manager =
+            ConnManager::with_options(None, ConnPoolOptions::default(), tls, interceptor);
         let root_client = RootClient::new(discovery, conn_manager.clone());
         let router = Router::new(root_client.clone()).await;
         let address_resolver = Arc::new(AddressResolver::new(router.clone()));
         TransportManager { address_resolver, conn_manager, root_client, router }
     }
 
-    #[allow(dead_code)]
     #[inline]
     pub(crate) fn conn_manager(&self) -> &ConnManager {
         &self.conn_manager
@@ -11,6 +11,8 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+#![allow(deprecated)]
+
 mod helper;
 
 use std::time::Duration;
@@ -18,10 +20,11 @@ use std::time::Duration;
 use log::info;
 use prost::Message;
 use sekas_api::server::v1::*;
-use sekas_client::{ClientOptions, NodeClient, SekasClient};
+use sekas_client::{AppError, ClientOptions, NodeClient, SekasClient};
 use sekas_rock::fn_name;
 use sekas_server::diagnosis;
 
+use crate::helper::client::ClusterClient;
 use crate::helper::context::*;
 use crate::helper::init::setup_panic_hook;
 
@@ -160,6 +163,194 @@ async fn admin_basic() {
     assert!(m.nodes.len() == node_count);
 }
 
+#[sekas_macro::test]
+async fn admin_move_group_replicas() {
+    let node_count = 5;
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(node_count).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes.clone()).await;
+    c.assert_root_group_has_promoted().await;
+
+    // Move two followers of the root group to the free nodes in one task.
+    let leader = c.assert_group_leader(0).await;
+    let state = c.get_router_group_state(0).await.unwrap();
+    let followers = state.replicas.values().filter(|r| r.id != leader).collect::<Vec<_>>();
+    assert_eq!(followers.len(), 2);
+    let src_replicas = followers.iter().map(|r| r.id.to_string()).collect::<Vec<_>>();
+    let dest_nodes = nodes
+        .keys()
+        .filter(|id| !state.replicas.values().any(|r| r.node_id == **id))
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(dest_nodes.len(), 2);
+
+    let root_addr = find_root(addrs).await;
+    let resp = reqwest::get(format!(
+        "http://{root_addr}/admin/move_replicas?group_id=0&src_replicas={}&dest_nodes={}",
+        src_replicas.join(","),
+        dest_nodes.join(",")
+    ))
+    .await
+    .unwrap();
+    assert!(resp.status().is_success());
+
+    for follower in followers {
+        c.assert_group_not_contains_node(0, follower.node_id).await;
+    }
+    c.assert_num_group_voters(0, 3).await;
+    let state = c.get_router_group_state(0).await.unwrap();
+    for node_id in dest_nodes {
+        assert!(state.replicas.values().any(|r| r.node_id.to_string() == node_id));
+    }
+}
+
+#[sekas_macro::test]
+async fn admin_move_shard() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    c.assert_root_group_has_promoted().await;
+
+    let (src_group, dest_group, shard_id) = (100000, 100001, 10000000);
+    let shard_desc = ShardDesc::whole(shard_id, shard_id);
+    for (group_id, shards) in [(src_group, vec![shard_desc]), (dest_group, vec![])] {
+        let replicas = node_ids
+            .iter()
+            .map(|&node_id| ReplicaDesc {
+                id: group_id * 10 + node_id,
+                node_id,
+                role: ReplicaRole::Voter as i32,
+            })
+            .collect::<Vec<_>>();
+        let desc =
+            GroupDesc { id: group_id, shards, replicas: replicas.clone(), ..Default::default() };
+        for replica in replicas {
+            c.create_replica(replica.node_id, replica.id, desc.clone()).await;
+        }
+        c.assert_group_leader(group_id).await;
+    }
+
+    let root_addr = find_root(addrs).await;
+    let url = format!("http://{root_addr}/admin/move_shard");
+    let path = format!("{url}?shard_id={shard_id}&dest_group={dest_group}&timeout_sec=30");
+    // Wait until the groups are reported to root.
+    let mut moved = None;
+    for _ in 0..100 {
+        let resp = reqwest::get(&path).await.unwrap();
+        if resp.status().is_success() {
+            moved = Some(resp.json::<diagnosis::ShardMove>().await.unwrap());
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let moved = moved.expect("move shard by admin api");
+    assert_eq!(moved.src_group, src_group);
+    assert_eq!(moved.dest_group, dest_group);
+    assert!(moved.attempts >= 1);
+    c.assert_group_contains_shard(dest_group, shard_id).await;
+
+    // The shard is already in the dest group.
+    let resp = reqwest::get(&path).await.unwrap();
+    assert!(!resp.status().is_success());
+    let resp = reqwest::get(format!("{url}?shard_id={shard_id}&dest_group=0")).await.unwrap();
+    assert!(!resp.status().is_success());
+    let resp = reqwest::get(format!("{url}?shard_id=abc&dest_group=1")).await.unwrap();
+    assert!(!resp.status().is_success());
+}
+
+#[sekas_macro::test]
+async fn admin_merge_group() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    c.assert_root_group_has_promoted().await;
+
+    let (donor_group, target_group) = (100000, 100001);
+    let shards = vec![ShardDesc::whole(10000000, 10000000), ShardDesc::whole(10000001, 10000001)];
+    create_group(&c, donor_group, &node_ids, shards.clone()).await;
+    create_group(&c, target_group, &node_ids, vec![]).await;
+
+    let root_addr = find_root(addrs.clone()).await;
+    let url = format!("http://{root_addr}/admin/merge_group");
+    let resp = reqwest::get(format!("{url}?donor_group={donor_group}&target_group=0")).await;
+    assert!(!resp.unwrap().status().is_success());
+    let path = format!("{url}?donor_group={donor_group}&target_group={target_group}");
+    // Wait until the groups are reported to root.
+    let mut merged = false;
+    for _ in 0..100 {
+        if reqwest::get(&path).await.unwrap().status().is_success() {
+            merged = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(merged, "merge group by admin api");
+
+    for shard in &shards {
+        c.assert_group_contains_shard(target_group, shard.id).await;
+    }
+    for _ in 0..600 {
+        let m = current_metadata(addrs.clone()).await;
+        if !m.groups.iter().any(|g| g.id == donor_group) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("group {donor_group} is not removed after merging");
+}
+
+async fn create_group(c: &ClusterClient, group_id: u64, node_ids: &[u64], shards: Vec<ShardDesc>) {
+    let replicas = node_ids
+        .iter()
+        .map(|&node_id| ReplicaDesc {
+            id: group_id * 10 + node_id,
+            node_id,
+            role: ReplicaRole::Voter as i32,
+        })
+        .collect::<Vec<_>>();
+    let desc = GroupDesc { id: group_id, shards, replicas: replicas.clone(), ..Default::default() };
+    for replica in replicas {
+        c.create_replica(replica.node_id, replica.id, desc.clone()).await;
+    }
+    c.assert_group_leader(group_id).await;
+}
+
+#[sekas_macro::test]
+async fn admin_adjust_move_shard_limit() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    c.assert_root_group_has_promoted().await;
+
+    let root_addr = find_root(addrs).await;
+    let url = format!("http://{root_addr}/admin/move_shard_limit");
+    let limit: serde_json::Value = reqwest::get(&url).await.unwrap().json().await.unwrap();
+    assert_eq!(limit["keys_per_sec"], 0);
+
+    // The unspecified fields are kept.
+    let resp = reqwest::get(format!("{url}?keys_per_sec=100&backoff_latency_ms=50")).await.unwrap();
+    assert!(resp.status().is_success());
+    let resp = reqwest::get(format!("{url}?bytes_per_sec=1024")).await.unwrap();
+    assert!(resp.status().is_success());
+    let limit: serde_json::Value = reqwest::get(&url).await.unwrap().json().await.unwrap();
+    assert_eq!(limit["keys_per_sec"], 100);
+    assert_eq!(limit["bytes_per_sec"], 1024);
+    assert_eq!(limit["backoff_latency_ms"], 50);
+
+    let resp = reqwest::get(format!("{url}?keys_per_sec=abc")).await.unwrap();
+    assert!(!resp.status().is_success());
+}
+
 fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + collection_name.len());
     buf.extend_from_slice(database_id.to_le_bytes().as_slice());
@@ -167,6 +358,545 @@ fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
     buf
 }
 
+#[sekas_macro::test]
+async fn admin_schema_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs).await;
+    let root_addr = root_addr.as_str();
+    let call = |path: String| async move {
+        let resp = reqwest::get(format!("http://{root_addr}/admin/{path}")).await.unwrap();
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await.unwrap();
+        (status, body)
+    };
+
+    let (status, body) = call("create_database?database=db1".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["name"], "db1");
+    let (status, body) = call("create_database?database=db1".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "already_exists");
+    let (status, body) = call("create_database".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_argument");
+    let (_, body) = call("list_databases".to_owned()).await;
+    let databases = body["databases"].as_array().unwrap();
+    assert!(databases.iter().any(|db| db["name"] == "db1"));
+
+    let path = "create_collection?database=db1&collection=co1&compression=lz4".to_owned();
+    let (status, body) = call(path).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["compression"], "lz4");
+    let (status, body) = call("create_collection?database=db2&collection=co1".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+    // No nodes satisfy the placement constraints.
+    let path = "create_collection?database=db1&collection=co9&constraints=disk%3Dssd".to_owned();
+    let (status, _) = call(path).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    let (_, body) = call("list_collections?database=db1".to_owned()).await;
+    assert_eq!(body["collections"].as_array().unwrap().len(), 1);
+
+    let (status, body) = call("collection_stats?database=db1&collection=co1".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["collection"], "co1");
+    assert!(body["num_keys"].is_u64());
+    assert!(body["logical_bytes"].is_u64());
+    let (status, body) = call("collection_stats?database=db1&collection=co2".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_argument");
+
+    let (status, body) = call("scatter_collection?database=db1&collection=co1".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert!(body["moves"].is_array());
+    let (status, body) = call("scatter_collection?database=db1&collection=co2".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_argument");
+
+    let (status, body) = call("hot_keys".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert!(body["hot_keys"].is_array());
+    let (status, body) = call("hot_keys?database=db1&collection=co1&limit=5".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert!(body["hot_keys"].as_array().unwrap().len() <= 5);
+    let (status, _) = call("hot_keys?database=db1&collection=co2".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    let (status, _) = call("hot_keys?limit=abc".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    let path = "rename_collection?database=db1&collection=co1&new_name=co2".to_owned();
+    let (status, body) = call(path).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["name"], "co2");
+    let (_, renamed) = call("list_collections?database=db1".to_owned()).await;
+    assert_eq!(renamed["collections"][0]["name"], "co2");
+    assert_eq!(renamed["collections"][0]["id"], body["id"]);
+    let path = "rename_collection?database=db1&collection=co1&new_name=co3".to_owned();
+    let (status, _) = call(path).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    let path = "rename_collection?database=db1&collection=co2&new_name=co2".to_owned();
+    let (status, _) = call(path).await;
+    assert_eq!(status, reqwest::StatusCode::CONFLICT);
+    let path = "rename_collection?database=__system__&collection=node&new_name=nodes".to_owned();
+    let (status, _) = call(path).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    let (status, _) = call("delete_collection?database=db1&collection=co2".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let (_, body) = call("list_collections?database=db1".to_owned()).await;
+    assert!(body["collections"].as_array().unwrap().is_empty());
+    let (status, body) = call("rename_database?database=db1&new_name=db3".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["name"], "db3");
+    let (status, _) = call("list_collections?database=db1".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+    let (status, _) = call("rename_database?database=__system__&new_name=db4".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    let (status, _) = call("delete_database?database=db3".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let (status, _) = call("list_collections?database=db3".to_owned()).await;
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+}
+
+#[sekas_macro::test]
+async fn admin_truncate_collection_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs.clone()).await;
+    let c = SekasClient::new(ClientOptions::default(), addrs).await.unwrap();
+    let db = c.create_database("db1".into()).await.unwrap();
+    let co = db.create_collection("co1".into()).await.unwrap();
+    for i in 0..10 {
+        db.put(co.id, format!("k{i}").into(), "v".into()).await.unwrap();
+    }
+
+    let url = format!("http://{root_addr}/admin/truncate_collection?database=db1&collection=co1");
+    let resp = reqwest::get(url).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["id"], co.id);
+
+    // The client will observe the fresh shards once the group descriptor is
+    // updated.
+    let mut truncated = false;
+    for _ in 0..100 {
+        if let Ok(None) = db.get(co.id, "k1".into()).await {
+            truncated = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(truncated);
+    for i in 0..10 {
+        assert!(db.get(co.id, format!("k{i}").into()).await.unwrap().is_none());
+    }
+    db.put(co.id, "k1".into(), "v2".into()).await.unwrap();
+    assert_eq!(db.get(co.id, "k1".into()).await.unwrap(), Some(b"v2".to_vec()));
+    assert_eq!(db.open_collection("co1".into()).await.unwrap().id, co.id);
+
+    let url = format!("http://{root_addr}/admin/truncate_collection?database=db1&collection=co2");
+    let resp = reqwest::get(url).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[sekas_macro::test]
+async fn admin_freeze_collection_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs.clone()).await;
+    let c = SekasClient::new(ClientOptions::default(), addrs).await.unwrap();
+    let db = c.create_database("db1".into()).await.unwrap();
+    let co = db.create_collection("co1".into()).await.unwrap();
+    db.put(co.id, "k1".into(), "v1".into()).await.unwrap();
+    let call = |path: &'static str| async move {
+        let resp = reqwest::get(format!("http://{root_addr}/admin/{path}")).await.unwrap();
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await.unwrap();
+        (status, body)
+    };
+
+    let (status, body) = call("freeze_collection?database=db1&collection=co1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["frozen"], true);
+    let r = db.put(co.id, "k1".into(), "v2".into()).await;
+    assert!(matches!(r, Err(AppError::CollectionFrozen(id)) if id == co.id), "{r:?}");
+    let r = db.delete(co.id, "k1".into()).await;
+    assert!(matches!(r, Err(AppError::CollectionFrozen(_))), "{r:?}");
+    // The reads are still served.
+    assert_eq!(db.get(co.id, "k1".into()).await.unwrap(), Some(b"v1".to_vec()));
+    let (status, body) = call("truncate_collection?database=db1&collection=co1").await;
+    assert_eq!(status, reqwest::StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "collection_frozen");
+
+    let (status, body) = call("unfreeze_collection?database=db1&collection=co1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["frozen"], false);
+    db.put(co.id, "k1".into(), "v2".into()).await.unwrap();
+    assert_eq!(db.get(co.id, "k1".into()).await.unwrap(), Some(b"v2".to_vec()));
+
+    let (status, _) = call("freeze_collection?database=db1&collection=co2").await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[sekas_macro::test]
+async fn admin_trash_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_trash_retention(3600);
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs.clone()).await;
+    let c = SekasClient::new(ClientOptions::default(), addrs).await.unwrap();
+    let db = c.create_database("db1".into()).await.unwrap();
+    let co = db.create_collection("co1".into()).await.unwrap();
+    db.put(co.id, "k1".into(), "v1".into()).await.unwrap();
+    let call = |path: &'static str| async move {
+        let resp = reqwest::get(format!("http://{root_addr}/admin/{path}")).await.unwrap();
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await.unwrap();
+        (status, body)
+    };
+
+    let (status, _) = call("delete_database?database=db1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let (status, body) = call("trash").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let trash = body["trash"].as_array().unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0]["type"], "database");
+    assert_eq!(trash[0]["name"], "db1");
+    assert_eq!(trash[0]["id"], db.desc().id);
+
+    let (status, body) = call("recover_database?database=db1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["id"], db.desc().id);
+    let (_, body) = call("trash").await;
+    assert!(body["trash"].as_array().unwrap().is_empty());
+    let (status, _) = call("recover_database?database=db1").await;
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+
+    // The data of the recovered database is kept.
+    let db = c.open_database("db1".into()).await.unwrap();
+    let co = db.open_collection("co1".into()).await.unwrap();
+    assert_eq!(db.get(co.id, "k1".into()).await.unwrap(), Some(b"v1".to_vec()));
+
+    let (status, _) = call("delete_collection?database=db1&collection=co1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let (status, body) = call("recover_collection?database=db1&collection=co1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["id"], co.id);
+    assert_eq!(db.get(co.id, "k1".into()).await.unwrap(), Some(b"v1".to_vec()));
+}
+
+#[sekas_macro::test]
+async fn admin_user_http_api_with_auth() {
+    const CLUSTER_TOKEN: &str = "cluster-token";
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.enable_auth(CLUSTER_TOKEN);
+    let nodes = ctx.bootstrap_servers(1).await;
+    let root_addr = nodes.get(&0).unwrap().clone();
+    let root_addr = root_addr.as_str();
+    let call = |path: &'static str, token: Option<String>| async move {
+        let mut req = reqwest::Client::new().get(format!("http://{root_addr}/admin/{path}"));
+        if let Some(token) = token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().await.unwrap();
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await.unwrap_or_default();
+        (status, body)
+    };
+    let cluster_token = || Some(CLUSTER_TOKEN.to_owned());
+
+    let (status, _) = call("list_users", None).await;
+    assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+    let (status, _) = call("list_users", Some("alice:invalid".to_owned())).await;
+    assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+
+    let (status, body) = call("create_user?user=alice", cluster_token()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let alice_token = body["token"].as_str().unwrap().to_owned();
+    let (status, _) = call("create_database?database=db1", cluster_token()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let (status, _) = call("create_collection?database=db1&collection=co1", cluster_token()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+
+    // The admin service requires superuser.
+    let (status, _) = call("list_users", Some(alice_token.clone())).await;
+    assert_eq!(status, reqwest::StatusCode::FORBIDDEN);
+
+    let opts = ClientOptions { token: Some(alice_token.clone()), ..Default::default() };
+    let client = SekasClient::new(opts, vec![root_addr.to_owned()]).await.unwrap();
+    assert!(client.open_database("db1".to_owned()).await.is_err());
+
+    let path = "grant?user=alice&database=db1&permission=write";
+    let (status, _) = call(path, cluster_token()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let (_, body) = call("list_users", cluster_token()).await;
+    let users = body["users"].as_array().unwrap();
+    let alice = users.iter().find(|user| user["name"] == "alice").unwrap();
+    assert_eq!(alice["permissions"][0]["database"], "db1");
+    assert_eq!(alice["permissions"][0]["permission"], "write");
+
+    // Wait until the cached users are expired.
+    sekas_runtime::time::sleep(Duration::from_secs(2)).await;
+    let db = client.open_database("db1".to_owned()).await.unwrap();
+    let co = db.open_collection("co1".to_owned()).await.unwrap();
+    let mut put_result = None;
+    for _ in 0..100 {
+        put_result = Some(db.put(co.id, b"key".to_vec(), b"value".to_vec()).await);
+        if matches!(put_result, Some(Ok(_))) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    put_result.unwrap().unwrap();
+    let value = db.get(co.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(value, Some(b"value".to_vec()));
+
+    // The write permission is not enough to manage collections.
+    assert!(db.create_collection("co2".to_owned()).await.is_err());
+    assert!(client.create_database("db2".to_owned()).await.is_err());
+
+    let (status, _) = call("delete_user?user=alice", cluster_token()).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+}
+
+#[sekas_macro::test]
+async fn admin_tenant_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs).await;
+    let root_addr = root_addr.as_str();
+    let call = |path: &str| {
+        let url = format!("http://{root_addr}/admin/{path}");
+        async move {
+            let resp = reqwest::get(url).await.unwrap();
+            let status = resp.status();
+            let body: serde_json::Value = resp.json().await.unwrap();
+            (status, body)
+        }
+    };
+
+    let (status, body) = call("create_tenant?tenant=team&max_databases=1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["max_databases"], 1);
+    let (status, _) = call("create_tenant?tenant=team").await;
+    assert_eq!(status, reqwest::StatusCode::CONFLICT);
+
+    // The same name could be used by different tenants.
+    let (status, _) = call("create_database?database=db1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let (status, body) = call("create_database?tenant=team&database=db1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_ne!(body["tenant_id"], 0);
+    let (status, body) = call("create_database?tenant=team&database=db2").await;
+    assert_eq!(status, reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["error"]["code"], "resource_exhausted");
+    let (status, _) = call("create_database?tenant=unknown&database=db1").await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    // The databases are isolated by tenants.
+    let opts = ClientOptions { tenant: Some("team".to_owned()), ..Default::default() };
+    let client = SekasClient::new(opts, vec![root_addr.to_owned()]).await.unwrap();
+    let databases = client.list_database().await.unwrap();
+    assert_eq!(databases.len(), 1);
+    let db = client.open_database("db1".to_owned()).await.unwrap();
+    assert_eq!(db.desc().id, databases[0].id);
+    let co = db.create_collection("co1".to_owned()).await.unwrap();
+    db.put(co.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert!(client.create_database("db2".to_owned()).await.is_err());
+
+    let path = "set_tenant_quota?tenant=team&max_databases=2&max_collections=1";
+    let (status, _) = call(path).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    client.create_database("db2".to_owned()).await.unwrap();
+    assert!(db.create_collection("co2".to_owned()).await.is_err());
+
+    let (status, body) = call("tenants").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let tenants = body["tenants"].as_array().unwrap();
+    let team = tenants.iter().find(|t| t["name"] == "team").unwrap();
+    assert_eq!(team["num_databases"], 2);
+    assert_eq!(team["num_collections"], 1);
+
+    // The tenant owning databases could not be deleted.
+    let (status, _) = call("delete_tenant?tenant=team").await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[sekas_macro::test]
+async fn admin_drain_node_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs).await;
+    let node_id = *nodes.iter().find(|(_, addr)| **addr != root_addr).unwrap().0;
+    let root_addr = root_addr.as_str();
+    let call = |path: String| async move {
+        reqwest::get(format!("http://{root_addr}/admin/{path}")).await.unwrap()
+    };
+
+    assert!(call(format!("cordon?node_id={node_id}")).await.status().is_success());
+    assert!(call(format!("drain?node_id={node_id}")).await.status().is_success());
+    loop {
+        let resp = call(format!("node_status?node_id={node_id}")).await;
+        let body: serde_json::Value = resp.json().await.unwrap();
+        info!("drain progress {body}");
+        assert!(body["replicas"].is_u64());
+        assert!(body["leaders"].is_u64());
+        if body["node_status"] == "DRAINED" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    assert!(call(format!("uncordon?node_id={node_id}")).await.status().is_success());
+}
+
+#[sekas_macro::test]
+async fn admin_cluster_info_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs).await;
+
+    let resp = reqwest::get(format!("http://{root_addr}/admin/cluster")).await.unwrap();
+    assert!(resp.status().is_success());
+    let cluster: diagnosis::Cluster = resp.json().await.unwrap();
+    assert_eq!(cluster.nodes.len(), 3);
+    assert!(cluster.nodes.iter().all(|n| nodes.contains_key(&n.id)));
+    assert!(cluster.groups.iter().any(|g| g.id == sekas_schema::ROOT_GROUP_ID));
+    assert!(!cluster.collections.is_empty());
+}
+
+#[sekas_macro::test]
+async fn admin_audit_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!("http://{root_addr}/admin/create_database?database=db1"))
+        .header("x-sekas-operator", "alice")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let resp = reqwest::get(format!("http://{root_addr}/admin/delete_database?database=db2"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let resp = reqwest::get(format!("http://{root_addr}/admin/audit")).await.unwrap();
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    let records = body["records"].as_array().unwrap();
+    assert_eq!(records.len(), 2);
+    // The newest record comes first.
+    assert_eq!(records[0]["action"], "delete_database");
+    assert_eq!(records[0]["target"], "database=db2");
+    assert!(!records[0]["error"].as_str().unwrap().is_empty());
+    assert_eq!(records[1]["action"], "create_database");
+    assert_eq!(records[1]["operator"], "alice");
+    assert_eq!(records[1]["error"], "");
+
+    let resp = reqwest::get(format!("http://{root_addr}/admin/audit?limit=1")).await.unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["records"].as_array().unwrap().len(), 1);
+}
+
+#[sekas_macro::test]
+async fn admin_group_history_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db1".into()).await.unwrap();
+    let co = db.create_collection("co1".into()).await.unwrap();
+    db.put(co.id, "k1".into(), "v1".into()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+    let group_id = c.find_router_group_state_by_key(co.id, b"k1").await.unwrap().id;
+
+    // The shard of the collection is reported by the node later.
+    let shard_added = format!("collection={}", co.id);
+    let mut found = false;
+    for _ in 0..100 {
+        let url = format!("http://{root_addr}/admin/group_history?group={group_id}");
+        let resp = reqwest::get(url).await.unwrap();
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["group_id"], group_id);
+        let events = body["events"].as_array().unwrap();
+        if events.iter().any(|e| {
+            e["kind"] == "add_shard" && e["detail"].as_str().unwrap().contains(&shard_added)
+        }) {
+            // The newest event comes first.
+            let ids = events.iter().map(|e| e["id"].as_u64().unwrap()).collect::<Vec<_>>();
+            assert!(ids.windows(2).all(|w| w[0] > w[1]));
+            found = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(found);
+
+    let resp = reqwest::get(format!("http://{root_addr}/admin/group_history")).await.unwrap();
+    assert!(!resp.status().is_success());
+}
+
+#[sekas_macro::test]
+async fn admin_config_http_api() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let root_addr = find_root(addrs).await;
+    let root_addr = root_addr.as_str();
+    let call = |path: String| async move {
+        reqwest::get(format!("http://{root_addr}/admin/{path}")).await.unwrap()
+    };
+
+    let resp = call("config".to_owned()).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["root.enable_shard_balance"], "false");
+
+    let resp = call("config?key=root.enable_shard_balance&value=true".to_owned()).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["root.enable_shard_balance"], "true");
+
+    // Neither the unknown options nor the illegal values are accepted.
+    let resp = call("config?key=root.replicas_per_group&value=5".to_owned()).await;
+    assert!(!resp.status().is_success());
+    let resp = call("config?key=root.enable_shard_balance&value=yes".to_owned()).await;
+    assert!(!resp.status().is_success());
+    let resp = call("config?key=root.enable_shard_balance".to_owned()).await;
+    assert!(!resp.status().is_success());
+
+    let body: serde_json::Value = call("config".to_owned()).await.json().await.unwrap();
+    assert_eq!(body["root.enable_shard_balance"], "true");
+}
+
 async fn current_metadata(nodes: Vec<String>) -> diagnosis::Metadata {
     let root_addr = find_root(nodes).await;
     let resp = reqwest::get(format!("http://{root_addr}/admin/metadata")).await.unwrap();
@@ -187,3 +917,25 @@ async fn find_root(nodes: Vec<String>) -> String {
     }
     panic!("no avaliable root")
 }
+
+#[sekas_macro::test]
+async fn read_cluster_metadata_from_system_collections() {
+    let node_count = 3;
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(node_count).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+
+    let c = SekasClient::new(ClientOptions::default(), addrs).await.unwrap();
+    let sys = c.open_system_collections().await.unwrap();
+    assert_eq!(sys.nodes().await.unwrap().len(), node_count);
+    let groups = sys.groups().await.unwrap();
+    assert!(groups.iter().any(|g| g.id == sekas_schema::ROOT_GROUP_ID));
+    assert!(!sys.replica_states().await.unwrap().is_empty());
+    assert!(sys.list::<NodeDesc>(sekas_schema::system::col::USER_ID).await.is_err());
+
+    // The views of the cluster metadata are read-only.
+    let sys_db = c.open_database("__system__".to_owned()).await.unwrap();
+    let node_col = sys_db.open_collection("node".to_owned()).await.unwrap();
+    assert!(sys_db.put(node_col.id, b"key".to_vec(), b"value".to_vec()).await.is_err());
+}
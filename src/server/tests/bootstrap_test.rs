@@ -14,8 +14,14 @@
 // limitations under the License.
 mod helper;
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use log::info;
+use sekas_api::server::v1::ReportRequest;
+use sekas_client::{ConnManager, Error, RootClient, StaticServiceDiscovery};
 use sekas_rock::fn_name;
+use sekas_server::{BootstrapSpec, CollectionSpec, DatabaseSpec};
 
 use crate::helper::client::*;
 use crate::helper::context::*;
@@ -37,6 +43,34 @@ async fn bootstrap_simple_cluster() {
     node_client_with_retry(&node_1_addr).await;
 }
 
+#[sekas_macro::test]
+async fn bootstrap_health_check() {
+    use tonic_health::proto::health_check_response::ServingStatus;
+    use tonic_health::proto::health_client::HealthClient;
+    use tonic_health::proto::HealthCheckRequest;
+
+    let mut ctx = TestContext::new(fn_name!());
+    let node_1_addr = ctx.next_listen_address();
+    ctx.spawn_server(1, &node_1_addr, true, vec![]);
+    node_client_with_retry(&node_1_addr).await;
+
+    let mut client = HealthClient::connect(format!("http://{node_1_addr}")).await.unwrap();
+    for service in ["", "sekas.server.v1.Node", "sekas.server.v1.Root"] {
+        let mut status = ServingStatus::Unknown;
+        for _ in 0..100 {
+            let req = HealthCheckRequest { service: service.to_owned() };
+            if let Ok(resp) = client.check(req).await {
+                status = resp.into_inner().status();
+                if status == ServingStatus::Serving {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(status, ServingStatus::Serving, "service {service:?}");
+    }
+}
+
 #[sekas_macro::test]
 async fn bootstrap_cluster_join_node() {
     let mut ctx = TestContext::new(fn_name!());
@@ -67,3 +101,69 @@ async fn bootstrap_restart_cluster() {
     let app = c.app_client().await;
     app.create_database("db".into()).await.unwrap();
 }
+
+#[sekas_macro::test]
+async fn bootstrap_cluster_with_spec() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_bootstrap_spec(BootstrapSpec {
+        replication_factor: None,
+        databases: vec![DatabaseSpec {
+            name: "db".to_owned(),
+            collections: vec![CollectionSpec {
+                name: "co".to_owned(),
+                split_keys: vec!["g".to_owned(), "p".to_owned()],
+                replication_factor: None,
+            }],
+        }],
+    });
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    // The spec is applied by the root in background.
+    let mut collection = None;
+    for _ in 0..100 {
+        if let Ok(db) = app.open_database("db".into()).await {
+            if let Ok(co) = db.open_collection("co".into()).await {
+                collection = Some(co);
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let co = collection.expect("the collection of spec is created");
+    c.assert_collection_ready(co.id).await;
+
+    let shard = c.get_shard_desc(co.id, b"h").await.unwrap();
+    let range = shard.range.unwrap();
+    assert_eq!((range.start, range.end), (b"g".to_vec(), b"p".to_vec()));
+    let first = c.get_shard_desc(co.id, b"a").await.unwrap();
+    let last = c.get_shard_desc(co.id, b"z").await.unwrap();
+    assert_ne!(first.id, shard.id);
+    assert_ne!(last.id, shard.id);
+}
+
+#[sekas_macro::test]
+async fn bootstrap_reject_report_of_stale_node() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    // Wait until the root is bootstrapped.
+    c.app_client().await.create_database("db".into()).await.unwrap();
+
+    let discovery = Arc::new(StaticServiceDiscovery::new(addrs));
+    let root_client = RootClient::new(discovery, ConnManager::new());
+    // The zero node id means unknown, it is not fenced.
+    root_client.report(&ReportRequest::default()).await.unwrap();
+
+    // The incarnation of the first node is the time of bootstrapping.
+    let req = ReportRequest { node_id: 1, incarnation: 1, ..Default::default() };
+    let r = root_client.report(&req).await;
+    assert!(
+        matches!(r, Err(Error::NodeIncarnationMismatch(1, expected, 1)) if expected > 1),
+        "{r:?}"
+    );
+}
@@ -14,10 +14,20 @@
 // limitations under the License.
 mod helper;
 
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use log::info;
-use sekas_client::{AppError, ClientOptions};
+use sekas_api::server::v1::{
+    admin_request_union, AdminRequest, AdminRequestUnion, CreateDatabaseRequest, KeyEncoding,
+    KeySchema,
+};
+use sekas_client::{
+    AppError, ClientInstrument, ClientOptions, CollectionOptions, ConnManager, Error, Operation,
+    RootClient, StaticServiceDiscovery, StringCodec, U64Codec, WriteBatchRequest, WriteBuilder,
+    WriteCoalescerOptions,
+};
 use sekas_rock::fn_name;
 
 use crate::helper::client::*;
@@ -39,6 +49,7 @@ async fn client_to_unreachable_peers() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
     let db = client.create_database("test_db".to_string()).await.unwrap();
@@ -102,6 +113,67 @@ async fn client_create_duplicated_database_or_collection() {
     assert!(matches!(r, Some(Ok(v)) if v == "value"));
 }
 
+#[sekas_macro::test]
+async fn client_retry_ddl_with_idempotency_token() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    // Wait until the root is bootstrapped.
+    c.app_client().await.create_database("db".into()).await.unwrap();
+
+    let discovery = Arc::new(StaticServiceDiscovery::new(addrs));
+    let root_client = RootClient::new(discovery, ConnManager::new());
+    let create_database = |name: &str, token: &str| AdminRequest {
+        request: Some(AdminRequestUnion {
+            request: Some(admin_request_union::Request::CreateDatabase(CreateDatabaseRequest {
+                name: name.to_owned(),
+                idempotency_token: token.to_owned(),
+                ..Default::default()
+            })),
+        }),
+    };
+    let first = root_client.admin(create_database("test_db", "token")).await.unwrap();
+    // The retry returns the original result instead of `AlreadyExists`.
+    let retry = root_client.admin(create_database("test_db", "token")).await.unwrap();
+    assert_eq!(first, retry);
+    let r = root_client.admin(create_database("test_db", "another_token")).await;
+    assert!(matches!(r, Err(Error::AlreadyExists(_))), "{r:?}");
+    // The token is reused by another request.
+    let r = root_client.admin(create_database("another_db", "token")).await;
+    assert!(matches!(r, Err(Error::InvalidArgument(_))), "{r:?}");
+}
+
+#[sekas_macro::test]
+async fn client_list_and_describe_collections() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    let databases = client.list_databases().await.unwrap();
+    assert!(databases.iter().any(|d| d.desc().id == db.desc().id));
+    let collections = db.list_collections().await.unwrap();
+    assert_eq!(collections.iter().map(|c| c.id).collect::<Vec<_>>(), vec![co.id]);
+
+    let description = db.describe_collection("test_co".to_string()).await.unwrap();
+    assert_eq!(description.collection.map(|c| c.id), Some(co.id));
+    assert!(!description.shards.is_empty());
+    for shard in &description.shards {
+        assert_eq!(shard.shard.as_ref().map(|s| s.collection_id), Some(co.id));
+        assert!(!shard.replicas.is_empty());
+    }
+    assert!(matches!(
+        db.describe_collection("not_exists".to_string()).await,
+        Err(AppError::NotFound(_))
+    ));
+}
+
 #[sekas_macro::test]
 async fn client_access_not_exists_database_or_collection() {
     let mut ctx = TestContext::new(fn_name!());
@@ -159,3 +231,358 @@ async fn client_request_to_offline_leader() {
         }
     }
 }
+
+#[sekas_macro::test]
+async fn client_read_and_write_chunked_values() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let opts = ClientOptions { chunk_size: Some(1024), ..Default::default() };
+    let client = c.app_client_with_options(opts).await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    let large_value = (0..10000u32).map(|v| v as u8).collect::<Vec<_>>();
+    db.put(co.id, b"a".to_vec(), b"small".to_vec()).await.unwrap();
+    db.put(co.id, b"b".to_vec(), large_value.clone()).await.unwrap();
+    db.put(co.id, b"c".to_vec(), b"small".to_vec()).await.unwrap();
+    assert_eq!(db.get(co.id, b"b".to_vec()).await.unwrap(), Some(large_value.clone()));
+
+    // The chunk keys are invisible to scans.
+    let key_values = db.scan(co.id, vec![], None, 0).await.unwrap();
+    assert_eq!(
+        key_values,
+        vec![
+            (b"a".to_vec(), b"small".to_vec()),
+            (b"b".to_vec(), large_value.clone()),
+            (b"c".to_vec(), b"small".to_vec()),
+        ]
+    );
+    let key_values = db.scan(co.id, b"b".to_vec(), None, 1).await.unwrap();
+    assert_eq!(key_values, vec![(b"b".to_vec(), large_value.clone())]);
+
+    // Overwrite with a shorter value.
+    let shorter_value = large_value[..3000].to_vec();
+    db.put(co.id, b"b".to_vec(), shorter_value.clone()).await.unwrap();
+    assert_eq!(db.get(co.id, b"b".to_vec()).await.unwrap(), Some(shorter_value));
+
+    // The small values are never read as chunked values, whatever the content.
+    let small_value = b"\xffsekas-chunked\xff\x00\x00\x00\x03".to_vec();
+    db.put(co.id, b"b".to_vec(), small_value.clone()).await.unwrap();
+    assert_eq!(db.get(co.id, b"b".to_vec()).await.unwrap(), Some(small_value));
+
+    db.delete(co.id, b"b".to_vec()).await.unwrap();
+    assert_eq!(db.get(co.id, b"b".to_vec()).await.unwrap(), None);
+    let key_values = db.scan(co.id, vec![], None, 0).await.unwrap();
+    assert_eq!(key_values.len(), 2);
+}
+
+#[sekas_macro::test]
+async fn client_delete_with_prev_and_exists() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let opts = ClientOptions { chunk_size: Some(1024), ..Default::default() };
+    let client = c.app_client_with_options(opts).await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    let large_value = (0..10000u32).map(|v| v as u8).collect::<Vec<_>>();
+    db.put(co.id, b"a".to_vec(), b"small".to_vec()).await.unwrap();
+    db.put(co.id, b"b".to_vec(), large_value.clone()).await.unwrap();
+    assert!(db.exists(co.id, b"a".to_vec()).await.unwrap());
+    assert!(db.exists(co.id, b"b".to_vec()).await.unwrap());
+    assert!(!db.exists(co.id, b"c".to_vec()).await.unwrap());
+
+    let prev = db.delete_with_prev(co.id, b"a".to_vec()).await.unwrap();
+    assert_eq!(prev, Some(b"small".to_vec()));
+    assert!(!db.exists(co.id, b"a".to_vec()).await.unwrap());
+    assert_eq!(db.delete_with_prev(co.id, b"a".to_vec()).await.unwrap(), None);
+
+    // The chunked value is reassembled.
+    let prev = db.delete_with_prev(co.id, b"b".to_vec()).await.unwrap();
+    assert_eq!(prev, Some(large_value));
+    assert!(!db.exists(co.id, b"b".to_vec()).await.unwrap());
+    assert!(db.scan(co.id, vec![], None, 0).await.unwrap().is_empty());
+}
+
+#[sekas_macro::test]
+async fn client_incr() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    assert_eq!(db.incr(co.id, b"counter".to_vec(), 3).await.unwrap(), 3);
+    assert_eq!(db.incr(co.id, b"counter".to_vec(), 4).await.unwrap(), 7);
+    assert_eq!(db.incr(co.id, b"counter".to_vec(), -10).await.unwrap(), -3);
+    let value = db.get(co.id, b"counter".to_vec()).await.unwrap();
+    assert_eq!(value, Some((-3i64).to_be_bytes().to_vec()));
+
+    // An existing value must be a i64.
+    db.put(co.id, b"other".to_vec(), b"abc".to_vec()).await.unwrap();
+    assert!(db.incr(co.id, b"other".to_vec(), 1).await.is_err());
+}
+
+#[derive(Default)]
+struct CountingInstrument {
+    num_operations: AtomicU64,
+    num_failed_operations: AtomicU64,
+    sent_bytes: AtomicU64,
+    received_bytes: AtomicU64,
+    num_router_hits: AtomicU64,
+}
+
+impl ClientInstrument for CountingInstrument {
+    fn on_operation(&self, _op: Operation, _latency: Duration, succeed: bool) {
+        self.num_operations.fetch_add(1, Ordering::Relaxed);
+        if !succeed {
+            self.num_failed_operations.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn on_bytes(&self, _op: Operation, sent: u64, received: u64) {
+        self.sent_bytes.fetch_add(sent, Ordering::Relaxed);
+        self.received_bytes.fetch_add(received, Ordering::Relaxed);
+    }
+
+    fn on_router_lookup(&self, hit: bool) {
+        if hit {
+            self.num_router_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[sekas_macro::test]
+async fn client_report_operations_to_instrument() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let instrument = Arc::new(CountingInstrument::default());
+    let opts = ClientOptions { instrument: Some(instrument.clone()), ..Default::default() };
+    let client = c.app_client_with_options(opts).await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    db.put(co.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(co.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    db.scan(co.id, vec![], None, 0).await.unwrap();
+    db.delete(co.id, b"key".to_vec()).await.unwrap();
+
+    assert_eq!(instrument.num_operations.load(Ordering::Relaxed), 4);
+    assert_eq!(instrument.num_failed_operations.load(Ordering::Relaxed), 0);
+    // put: key + value, get: key, delete: key.
+    assert_eq!(instrument.sent_bytes.load(Ordering::Relaxed), 8 + 3 + 3);
+    // get: value, scan: key + value.
+    assert_eq!(instrument.received_bytes.load(Ordering::Relaxed), 5 + 8);
+    assert!(instrument.num_router_hits.load(Ordering::Relaxed) >= 4);
+}
+
+#[sekas_macro::test]
+async fn client_coalesce_concurrent_writes() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    let opts = WriteCoalescerOptions { window: Duration::from_millis(10), ..Default::default() };
+    let coalescer = db.write_coalescer(opts);
+    let mut futures = Vec::new();
+    for i in 0..16u8 {
+        let put = WriteBuilder::new(vec![i]).ensure_put(vec![i]);
+        futures.push(coalescer.put(co.id, put).unwrap());
+    }
+    for future in futures {
+        future.await.unwrap();
+    }
+
+    // The failed condition of a write doesn't affect the others.
+    let put = WriteBuilder::new(vec![0]).expect_not_exists().ensure_put(vec![0]);
+    let failed = coalescer.put(co.id, put).unwrap();
+    let put = WriteBuilder::new(vec![1]).take_prev_value().ensure_put(vec![2]);
+    let succeed = coalescer.put(co.id, put).unwrap();
+    coalescer.flush();
+    assert!(matches!(failed.await, Err(Error::CasFailed(..))));
+    let resp = succeed.await.unwrap();
+    assert_eq!(resp.prev_value.and_then(|v| v.content), Some(vec![1]));
+
+    let delete = WriteBuilder::new(vec![1]).take_prev_value().ensure_delete();
+    let resp = coalescer.delete(co.id, delete).unwrap().await.unwrap();
+    assert_eq!(resp.prev_value.and_then(|v| v.content), Some(vec![2]));
+}
+
+#[sekas_macro::test]
+async fn client_typed_collection() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    let co = db.open_typed_collection("test_co".to_string(), U64Codec, StringCodec).await.unwrap();
+    for i in [3u64, 1, 256, 2] {
+        co.put(&i, &format!("value-{i}")).await.unwrap();
+    }
+    assert_eq!(co.get(&256).await.unwrap(), Some("value-256".to_owned()));
+    assert_eq!(co.get(&4).await.unwrap(), None);
+
+    co.delete(&3).await.unwrap();
+    let key_values = co.scan(&0, Some(&256), 0).await.unwrap();
+    assert_eq!(key_values, vec![(1, "value-1".to_owned()), (2, "value-2".to_owned())]);
+}
+
+#[sekas_macro::test]
+async fn client_reject_keys_violating_key_schema() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+
+    let invalid = KeySchema { encoding: KeyEncoding::FixedLength as i32, length: 0 };
+    let options = CollectionOptions::default().key_schema(invalid);
+    let r = db.create_collection_with_options("test_co".to_string(), options).await;
+    assert!(matches!(r, Err(AppError::InvalidArgument(_))), "{r:?}");
+
+    let key_schema = KeySchema { encoding: KeyEncoding::U64Be as i32, length: 0 };
+    let options = CollectionOptions::default().key_schema(key_schema.clone());
+    let co = db.create_collection_with_options("test_co".to_string(), options).await;
+    let co = co.unwrap();
+    assert_eq!(co.key_schema, Some(key_schema));
+    c.assert_collection_ready(co.id).await;
+
+    db.put(co.id, 1u64.to_be_bytes().to_vec(), b"v1".to_vec()).await.unwrap();
+    let r = db.put(co.id, b"k1".to_vec(), b"v1".to_vec()).await;
+    assert!(matches!(r, Err(AppError::InvalidArgument(_))), "{r:?}");
+    assert_eq!(db.get(co.id, b"k1".to_vec()).await.unwrap(), None);
+    // Deleting keys never violates the key schema.
+    db.delete(co.id, b"k1".to_vec()).await.unwrap();
+}
+
+#[sekas_macro::test]
+async fn client_read_expired_values_as_deleted() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let short =
+        WriteBuilder::new(b"short".to_vec()).with_expire_at(now + 2).ensure_put(b"v".to_vec());
+    let long =
+        WriteBuilder::new(b"long".to_vec()).with_expire_at(now + 3600).ensure_put(b"v".to_vec());
+    let batch = WriteBatchRequest::default().add_put(co.id, short).add_put(co.id, long);
+    db.write_batch(batch).await.unwrap();
+    assert_eq!(db.get(co.id, b"short".to_vec()).await.unwrap(), Some(b"v".to_vec()));
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(db.get(co.id, b"short".to_vec()).await.unwrap(), None);
+    let value = db.get_raw_value(co.id, b"long".to_vec()).await.unwrap().unwrap();
+    assert_eq!(value.content, Some(b"v".to_vec()));
+    assert_eq!(value.expire_at, now + 3600);
+}
+
+#[sekas_macro::test]
+async fn client_refresh_cached_metadata() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let other_client = c.app_client().await;
+
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    db.create_collection("test_co".to_string()).await.unwrap();
+    let other_db = other_client.open_database("test_db".to_string()).await.unwrap();
+    other_db.open_collection("test_co".to_string()).await.unwrap();
+
+    // The deleting is visible to the other client after refreshing metadata.
+    db.delete_collection("test_co".to_string()).await.unwrap();
+    assert!(db.open_collection("test_co".to_string()).await.is_err());
+    other_client.refresh_metadata().await.unwrap();
+    assert!(other_db.open_collection("test_co".to_string()).await.is_err());
+
+    client.delete_database("test_db".to_string()).await.unwrap();
+    assert!(client.open_database("test_db".to_string()).await.is_err());
+    other_client.refresh_metadata().await.unwrap();
+    assert!(other_client.open_database("test_db".to_string()).await.is_err());
+}
+
+#[sekas_macro::test]
+async fn client_clone_collection() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let src = db.create_collection("src".to_string()).await.unwrap();
+    c.assert_collection_ready(src.id).await;
+
+    db.put(src.id, b"k1".to_vec(), b"v1".to_vec()).await.unwrap();
+    db.put(src.id, b"k2".to_vec(), b"v2".to_vec()).await.unwrap();
+    let dst = db.clone_collection("src".to_string(), "dst".to_string()).await.unwrap();
+    c.assert_collection_ready(dst.id).await;
+
+    // The snapshot of the source is held from GC until the clone is realized.
+    let clone_source = c.get_shard_desc(dst.id, b"k1").await.and_then(|s| s.clone_source);
+    if let Some(clone_source) = clone_source {
+        assert_eq!(clone_source.collection_id, src.id);
+        assert!(db.min_gc_timestamp(src.id).await.unwrap() <= clone_source.version);
+    }
+
+    // The writes after cloning are not visible to the clone, and vice versa.
+    db.put(src.id, b"k1".to_vec(), b"v1-new".to_vec()).await.unwrap();
+    db.delete(src.id, b"k2".to_vec()).await.unwrap();
+    db.put(src.id, b"k3".to_vec(), b"v3".to_vec()).await.unwrap();
+    db.put(dst.id, b"k4".to_vec(), b"v4".to_vec()).await.unwrap();
+    let rewritten = db.get_raw_value(src.id, b"k1".to_vec()).await.unwrap().unwrap();
+    let expect = vec![
+        (b"k1".to_vec(), b"v1".to_vec()),
+        (b"k2".to_vec(), b"v2".to_vec()),
+        (b"k4".to_vec(), b"v4".to_vec()),
+    ];
+    assert_eq!(db.scan(dst.id, vec![], None, 0).await.unwrap(), expect);
+    assert_eq!(db.get(src.id, b"k4".to_vec()).await.unwrap(), None);
+
+    // The clone is realized in background, then the snapshot of the source is
+    // released to GC.
+    let mut realized = false;
+    for _ in 0..200 {
+        let shard = c.get_shard_desc(dst.id, b"k1").await;
+        if shard.map(|s| s.clone_source.is_none()).unwrap_or_default() {
+            realized = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(realized, "the clone is not realized");
+    assert!(db.min_gc_timestamp(src.id).await.unwrap() >= rewritten.version);
+
+    // The realized clone is intact after the source is rewritten and dropped.
+    db.put(src.id, b"k1".to_vec(), b"v1-newer".to_vec()).await.unwrap();
+    db.delete_collection("src".to_string()).await.unwrap();
+    assert_eq!(db.scan(dst.id, vec![], None, 0).await.unwrap(), expect);
+}
@@ -13,6 +13,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 mod helper;
+// The chaos controller is only used by the cluster tests, so it is not shared
+// with the other tests by `helper`.
+#[path = "helper/chaos.rs"]
+mod chaos;
 
 use log::info;
 use rand::prelude::SmallRng;
@@ -21,6 +25,7 @@ use sekas_api::server::v1::ReplicaRole;
 use sekas_client::{ClientOptions, Error, SekasClient, WriteBatchRequest, WriteBuilder};
 use sekas_rock::fn_name;
 
+use crate::chaos::ChaosController;
 use crate::helper::client::*;
 use crate::helper::context::*;
 use crate::helper::init::setup_panic_hook;
@@ -159,6 +164,37 @@ async fn cluster_rw_with_leader_transfer() {
     }
 }
 
+#[sekas_macro::test]
+async fn cluster_rw_with_graceful_shutdown() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.enable_graceful_shutdown(5);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+    c.assert_root_group_has_promoted().await;
+
+    for i in 0..100 {
+        let k = format!("key-{i}").as_bytes().to_vec();
+        let v = format!("value-{i}").as_bytes().to_vec();
+        db.put(co.id, k.clone(), v).await.unwrap();
+        let r = db.get(co.id, k.clone()).await.unwrap();
+        let r = r.map(String::from_utf8);
+        assert!(matches!(r, Some(Ok(v)) if v == format!("value-{i}")));
+
+        if i == 20 {
+            let state = c.find_router_group_state_by_key(co.id, k.as_slice()).await.unwrap();
+            let node_id = c.get_group_leader_node_id(state.id).await.unwrap();
+            info!("shutdown node {node_id} gracefully, which leads group {}", state.id);
+            ctx.stop_server(node_id).await;
+        }
+    }
+}
+
 #[sekas_macro::test]
 async fn cluster_rw_with_shard_moving() {
     let mut ctx = TestContext::new(fn_name!());
@@ -202,6 +238,163 @@ async fn cluster_rw_with_shard_moving() {
     assert_ne!(source_state.id, prev_group_id);
 }
 
+/// The shard moving is interrupted by the failpoints while pulling and
+/// committing, it should be resumed and all keys are kept.
+#[cfg(feature = "failpoints")]
+#[sekas_macro::test]
+async fn cluster_rw_with_shard_moving_failpoints() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let admin_addr = nodes.values().next().cloned().unwrap();
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+    for i in 0..100 {
+        let k = format!("key-{i}").as_bytes().to_vec();
+        let v = format!("value-{i}").as_bytes().to_vec();
+        db.put(co.id, k, v).await.unwrap();
+    }
+
+    for (name, actions) in
+        [("move_shard_ingest_chunk", "2*return"), ("move_shard_commit", "1*return")]
+    {
+        let url = format!("http://{admin_addr}/admin/failpoints?name={name}&actions={actions}");
+        let resp = reqwest::get(url).await.unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let source_state = c.find_router_group_state_by_key(co.id, &[0]).await.unwrap();
+    let prev_group_id = source_state.id;
+    let target_group_id = 0;
+    let shard_desc = c.get_shard_desc(co.id, &[0]).await.unwrap();
+    let mut client = c.group(target_group_id);
+    client.accept_shard(source_state.id, source_state.epoch, &shard_desc).await.unwrap();
+
+    for _ in 0..100 {
+        let state = c.find_router_group_state_by_key(co.id, &[0]).await.unwrap();
+        if state.id != prev_group_id {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let state = c.find_router_group_state_by_key(co.id, &[0]).await.unwrap();
+    assert_ne!(state.id, prev_group_id);
+
+    for i in 0..100 {
+        let k = format!("key-{i}").as_bytes().to_vec();
+        let r = db.get(co.id, k).await.unwrap().map(String::from_utf8);
+        assert!(matches!(&r, Some(Ok(v)) if v == &format!("value-{i}")), "index {i}: {r:?}");
+    }
+}
+
+/// The servers are killed and restarted randomly while writing, the
+/// acknowledged writes should be kept after the cluster is recovered.
+#[sekas_macro::test]
+#[ignore]
+async fn cluster_rw_with_chaos() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(5).await;
+    let node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    // At most two of the five servers are killed at the same time.
+    let mut chaos = ChaosController::new(0, node_ids).with_max_killed(2);
+    let mut acked = vec![];
+    for round in 0..20 {
+        let event = chaos.step(&mut ctx).await;
+        info!("round {round} chaos event {event:?}");
+        for i in 0..10 {
+            let k = format!("key-{round}-{i}").as_bytes().to_vec();
+            let v = format!("value-{round}-{i}").as_bytes().to_vec();
+            if db.put(co.id, k.clone(), v.clone()).await.is_ok() {
+                acked.push((k, v));
+            }
+        }
+    }
+    chaos.recover(&mut ctx).await;
+
+    for (k, v) in acked {
+        let r = db.get(co.id, k.clone()).await.unwrap();
+        assert_eq!(r, Some(v), "seed {} key {:?}", chaos.seed(), String::from_utf8(k));
+    }
+}
+
+/// The raft messages are dropped or delayed between the servers while writing,
+/// the writes should be served by the majority, and all of them are kept after
+/// the network is healed.
+#[cfg(feature = "failpoints")]
+#[sekas_macro::test]
+#[ignore]
+async fn cluster_rw_with_network_faults() {
+    use std::time::Duration;
+
+    use crate::chaos::ChaosEvent;
+
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let mut node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    node_ids.sort_unstable();
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_collection("test_co".to_string()).await.unwrap();
+    c.assert_collection_ready(co.id).await;
+
+    let mut chaos = ChaosController::new(0, node_ids.clone())
+        .with_max_killed(0)
+        .with_max_latency(Duration::from_millis(50));
+    let mut events = vec![
+        ChaosEvent::Latency(node_ids[0], node_ids[1], Duration::from_millis(20)),
+        ChaosEvent::Partition(node_ids[1], node_ids[2]),
+        ChaosEvent::Heal,
+    ];
+    for _ in 0..10 {
+        events.push(chaos.next_event());
+    }
+
+    let mut acked = vec![];
+    let write = |round: usize| {
+        let k = format!("key-{round}").as_bytes().to_vec();
+        let v = format!("value-{round}").as_bytes().to_vec();
+        (k, v)
+    };
+    for (round, event) in events.into_iter().enumerate() {
+        chaos.apply(&mut ctx, event).await;
+        let (k, v) = write(round);
+        if db.put(co.id, k.clone(), v.clone()).await.is_ok() {
+            acked.push((k, v));
+        }
+    }
+
+    // A single isolated follower doesn't block the writes of the majority.
+    chaos.recover(&mut ctx).await;
+    let state = c.find_router_group_state_by_key(co.id, &[0]).await.unwrap();
+    let leader_id = state.leader_state.unwrap().0;
+    let follower = state.replicas.values().find(|r| r.id != leader_id).unwrap();
+    chaos.isolate(follower.node_id);
+    let (k, v) = write(usize::MAX);
+    db.put(co.id, k.clone(), v.clone()).await.unwrap();
+    acked.push((k, v));
+
+    chaos.recover(&mut ctx).await;
+    for (k, v) in acked {
+        let r = db.get(co.id, k.clone()).await.unwrap();
+        assert_eq!(r, Some(v), "seed {} key {:?}", chaos.seed(), String::from_utf8(k));
+    }
+}
+
 #[test]
 #[ignore]
 fn cluster_rw_single_server_large_read_write() {
@@ -0,0 +1,133 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod helper;
+
+use sekas_api::gateway::v1::gateway_client::GatewayClient;
+use sekas_api::gateway::v1::*;
+use sekas_api::server::v1::{PutRequest as ShardPutRequest, PutType};
+use sekas_rock::fn_name;
+
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn put_request(key: &[u8], value: &[u8]) -> ShardPutRequest {
+    ShardPutRequest {
+        put_type: PutType::None.into(),
+        key: key.to_owned(),
+        value: value.to_owned(),
+        take_prev_value: true,
+        ..Default::default()
+    }
+}
+
+#[sekas_macro::test]
+async fn gateway_basic_operations() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.enable_proxy_service();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addr = nodes.values().next().unwrap().clone();
+    let mut gateway = GatewayClient::connect(format!("http://{addr}")).await.unwrap();
+
+    let database = gateway
+        .create_database(CreateDatabaseRequest { name: "db".into() })
+        .await
+        .unwrap()
+        .into_inner()
+        .database
+        .unwrap();
+    let collection = gateway
+        .create_collection(CreateCollectionRequest { database: "db".into(), name: "co".into() })
+        .await
+        .unwrap()
+        .into_inner()
+        .collection
+        .unwrap();
+    let (database_id, collection_id) = (database.id, collection.id);
+
+    let resp = gateway
+        .put(PutRequest { database_id, collection_id, put: Some(put_request(b"k1", b"v1")) })
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(resp.prev_value.is_none());
+
+    let resp = gateway
+        .get(GetRequest { database_id, collection_id, key: b"k1".to_vec() })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(resp.value, Some(b"v1".to_vec()));
+
+    let resp = gateway
+        .write_batch(WriteBatchRequest {
+            database_id,
+            puts: vec![
+                CollectionPut { collection_id, put: Some(put_request(b"k1", b"v2")) },
+                CollectionPut { collection_id, put: Some(put_request(b"k2", b"v2")) },
+            ],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(resp.puts.len(), 2);
+    let prev_value = resp.puts[0].prev_value.as_ref().and_then(|v| v.content.clone());
+    assert_eq!(prev_value, Some(b"v1".to_vec()));
+
+    let resp = gateway
+        .scan(ScanRequest {
+            database_id,
+            collection_id,
+            start_key: vec![],
+            end_key: None,
+            limit: 0,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let keys = resp.key_values.into_iter().map(|kv| kv.key).collect::<Vec<_>>();
+    assert_eq!(keys, vec![b"k1".to_vec(), b"k2".to_vec()]);
+
+    gateway
+        .delete(DeleteRequest {
+            database_id,
+            collection_id,
+            delete: Some(sekas_api::server::v1::DeleteRequest {
+                key: b"k1".to_vec(),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap();
+    let resp = gateway
+        .get(GetRequest { database_id, collection_id, key: b"k1".to_vec() })
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(resp.value.is_none());
+
+    let status = gateway
+        .get_collection(GetCollectionRequest { database: "db".into(), name: "missing".into() })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
@@ -203,3 +203,45 @@ async fn group_move_replica() {
     c.assert_group_not_contains_member(group_id, follower_id).await;
     c.assert_group_contains_member(group_id, 123123).await;
 }
+
+/// A group which has permanently lost its quorum could be rebuilt from the
+/// surviving replica.
+#[sekas_macro::test]
+async fn group_unsafe_recover_from_single_replica() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.disable_all_node_scheduler();
+    let nodes = ctx.bootstrap_servers(5).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    c.assert_root_group_has_promoted().await;
+
+    // Place the group on one of the root nodes and the other two nodes, so that
+    // the root group is still available after the other two nodes are stopped.
+    let root_state = c.get_router_group_state(0).await.unwrap();
+    let root_nodes = root_state.replicas.values().map(|r| r.node_id).collect::<Vec<_>>();
+    let survivor = root_nodes[0];
+    let mut group_nodes =
+        nodes.keys().cloned().filter(|id| !root_nodes.contains(id)).collect::<Vec<_>>();
+    assert_eq!(group_nodes.len(), 2);
+    let lost_nodes = group_nodes.clone();
+    group_nodes.push(survivor);
+
+    let group_id = 10;
+    create_group(&c, group_id, group_nodes).await;
+    c.assert_group_leader(group_id).await;
+
+    info!("stop nodes {lost_nodes:?}, group {group_id} lost its quorum");
+    for node_id in lost_nodes {
+        ctx.stop_server(node_id).await;
+    }
+    ctx.wait_election_timeout().await;
+
+    let replica_id = group_id * 10 + survivor;
+    let client = node_client_with_retry(&nodes[&survivor]).await;
+    let desc = client.unsafe_recover_replica(group_id, replica_id).await.unwrap();
+    assert_eq!(desc.replicas.len(), 1);
+    assert_eq!(desc.replicas[0].id, replica_id);
+
+    c.assert_group_leader(group_id).await;
+    c.assert_group_members(group_id, vec![replica_id]).await;
+}
@@ -0,0 +1,232 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+#[cfg(feature = "failpoints")]
+use std::time::Duration;
+
+use log::info;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+#[cfg(feature = "failpoints")]
+use rand::Rng;
+use rand::SeedableRng;
+
+use crate::helper::context::TestContext;
+
+/// A fault injected into the cluster by [`ChaosController`].
+#[derive(Debug, Clone)]
+pub enum ChaosEvent {
+    Kill(u64),
+    Restart(u64),
+    /// Drop the raft messages between the two nodes.
+    #[cfg(feature = "failpoints")]
+    Partition(u64, u64),
+    /// Delay the raft messages between the two nodes.
+    #[cfg(feature = "failpoints")]
+    Latency(u64, u64, Duration),
+    /// Remove all network faults.
+    Heal,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ChaosKind {
+    Kill,
+    Restart,
+    #[cfg(feature = "failpoints")]
+    Partition,
+    #[cfg(feature = "failpoints")]
+    Latency,
+    Heal,
+}
+
+/// Inject faults into the servers of a [`TestContext`], by a schedule which is
+/// determined by the seed, so that a failed run could be reproduced.
+///
+/// The servers are killed and restarted, at most a minority of them are killed
+/// at the same time by default. The network faults are injected into the raft
+/// transport, they require the `failpoints` feature, and they are shared by
+/// all servers in the same process.
+pub struct ChaosController {
+    seed: u64,
+    rng: SmallRng,
+    node_ids: Vec<u64>,
+    killed: HashSet<u64>,
+    max_killed: usize,
+    #[cfg(feature = "failpoints")]
+    max_latency: Duration,
+}
+
+impl ChaosController {
+    pub fn new(seed: u64, mut node_ids: Vec<u64>) -> Self {
+        node_ids.sort_unstable();
+        let max_killed = node_ids.len().saturating_sub(1) / 2;
+        info!("chaos controller with seed {seed}, nodes {node_ids:?}");
+        ChaosController {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+            node_ids,
+            killed: HashSet::default(),
+            max_killed,
+            #[cfg(feature = "failpoints")]
+            max_latency: Duration::from_millis(200),
+        }
+    }
+
+    /// Set the max number of servers killed at the same time.
+    pub fn with_max_killed(mut self, max_killed: usize) -> Self {
+        self.max_killed = max_killed;
+        self
+    }
+
+    /// Set the max latency injected between nodes.
+    #[cfg(feature = "failpoints")]
+    pub fn with_max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = max_latency;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Generate the next event by the schedule.
+    pub fn next_event(&mut self) -> ChaosEvent {
+        let alive = self.alive_nodes();
+        let mut killed = self.killed.iter().cloned().collect::<Vec<_>>();
+        killed.sort_unstable();
+
+        let mut kinds = vec![];
+        if self.killed.len() < self.max_killed && !alive.is_empty() {
+            kinds.push(ChaosKind::Kill);
+        }
+        if !killed.is_empty() {
+            kinds.push(ChaosKind::Restart);
+        }
+        #[cfg(feature = "failpoints")]
+        {
+            if alive.len() >= 2 {
+                kinds.push(ChaosKind::Partition);
+                kinds.push(ChaosKind::Latency);
+            }
+            kinds.push(ChaosKind::Heal);
+        }
+
+        match kinds.choose(&mut self.rng) {
+            Some(ChaosKind::Kill) => ChaosEvent::Kill(*alive.choose(&mut self.rng).unwrap()),
+            Some(ChaosKind::Restart) => ChaosEvent::Restart(*killed.choose(&mut self.rng).unwrap()),
+            #[cfg(feature = "failpoints")]
+            Some(ChaosKind::Partition) => {
+                let (a, b) = self.choose_pair(&alive);
+                ChaosEvent::Partition(a, b)
+            }
+            #[cfg(feature = "failpoints")]
+            Some(ChaosKind::Latency) => {
+                let (a, b) = self.choose_pair(&alive);
+                let max_ms = (self.max_latency.as_millis() as u64).max(1);
+                let latency = Duration::from_millis(self.rng.gen_range(1..=max_ms));
+                ChaosEvent::Latency(a, b, latency)
+            }
+            Some(ChaosKind::Heal) | None => ChaosEvent::Heal,
+        }
+    }
+
+    /// Generate and apply the next event, return the applied event.
+    pub async fn step(&mut self, ctx: &mut TestContext) -> ChaosEvent {
+        let event = self.next_event();
+        self.apply(ctx, event.clone()).await;
+        event
+    }
+
+    pub async fn apply(&mut self, ctx: &mut TestContext, event: ChaosEvent) {
+        info!("chaos (seed {}) apply event {event:?}", self.seed);
+        match event {
+            ChaosEvent::Kill(id) => self.kill(ctx, id).await,
+            ChaosEvent::Restart(id) => self.restart(ctx, id).await,
+            #[cfg(feature = "failpoints")]
+            ChaosEvent::Partition(a, b) => self.partition(a, b),
+            #[cfg(feature = "failpoints")]
+            ChaosEvent::Latency(a, b, latency) => self.add_latency(a, b, latency),
+            ChaosEvent::Heal => self.heal(),
+        }
+    }
+
+    pub async fn kill(&mut self, ctx: &mut TestContext, id: u64) {
+        if self.killed.insert(id) {
+            ctx.stop_server(id).await;
+        }
+    }
+
+    pub async fn restart(&mut self, ctx: &mut TestContext, id: u64) {
+        if self.killed.remove(&id) {
+            ctx.restart_server(id).await;
+        }
+    }
+
+    /// Drop the raft messages between the two nodes in both directions.
+    #[cfg(feature = "failpoints")]
+    pub fn partition(&self, a: u64, b: u64) {
+        self.set_network_fault(a, b, true, Duration::ZERO);
+    }
+
+    /// Drop the raft messages between the node and all other nodes.
+    #[cfg(feature = "failpoints")]
+    pub fn isolate(&self, id: u64) {
+        for &other in self.node_ids.iter().filter(|&&other| other != id) {
+            self.partition(id, other);
+        }
+    }
+
+    /// Delay the raft messages between the two nodes in both directions.
+    #[cfg(feature = "failpoints")]
+    pub fn add_latency(&self, a: u64, b: u64, latency: Duration) {
+        self.set_network_fault(a, b, false, latency);
+    }
+
+    /// Remove all network faults, it is a no-op without the `failpoints`
+    /// feature.
+    pub fn heal(&self) {
+        #[cfg(feature = "failpoints")]
+        sekas_server::failpoint::clear_network_faults();
+    }
+
+    /// Restart the killed servers and remove all network faults, so that the
+    /// cluster could be checked after chaos.
+    pub async fn recover(&mut self, ctx: &mut TestContext) {
+        self.heal();
+        let mut killed = self.killed.drain().collect::<Vec<_>>();
+        killed.sort_unstable();
+        for id in killed {
+            ctx.restart_server(id).await;
+        }
+    }
+
+    #[cfg(feature = "failpoints")]
+    fn choose_pair(&mut self, nodes: &[u64]) -> (u64, u64) {
+        let pair = nodes.choose_multiple(&mut self.rng, 2).cloned().collect::<Vec<_>>();
+        (pair[0], pair[1])
+    }
+
+    fn alive_nodes(&self) -> Vec<u64> {
+        self.node_ids.iter().filter(|id| !self.killed.contains(id)).cloned().collect()
+    }
+
+    #[cfg(feature = "failpoints")]
+    fn set_network_fault(&self, a: u64, b: u64, partitioned: bool, latency: Duration) {
+        use sekas_server::failpoint::{set_network_fault, NetworkFault};
+        let fault = NetworkFault { partitioned, latency };
+        set_network_fault(a, b, fault);
+        set_network_fault(b, a, fault);
+    }
+}
@@ -40,9 +40,12 @@ async fn is_not_in_shard_moving(c: &ClusterClient, dest_group_id: u64) -> bool {
         debug!("group {dest_group_id} node {leader_node_id} collect moving shard state",);
         if let Ok(resp) = c.collect_moving_shard_state(dest_group_id, leader_node_id).await {
             debug!(
-                "group {dest_group_id} node {leader_node_id} collect moving shard state: {:?}",
-                resp.state
+                "group {dest_group_id} node {leader_node_id} collect moving shard state: {:?}, progress: {:?}",
+                resp.state, resp.progress
             );
+            if let Some(progress) = resp.progress.as_ref() {
+                assert!((0.0..=100.0).contains(&progress.percent), "{progress:?}");
+            }
             if resp.state == State::None as i32 {
                 // moving shard is finished or aborted.
                 return true;
@@ -99,6 +102,7 @@ async fn validate(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::o
             shard_id,
             start_version: u64::MAX,
             user_key: key.as_bytes().to_vec(),
+            ..Default::default()
         });
 
         let mut retry_state = RetryState::default();
@@ -106,7 +110,7 @@ async fn validate(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::o
             match c.request(&req).await {
                 Ok(resp) => {
                     let Response::Get(resp) = resp else { panic!("Invalid response type") };
-                    assert!(matches!(resp.value, Some(Value { content: Some(content), version: _})
+                    assert!(matches!(resp.value, Some(Value { content: Some(content), .. })
                             if content == expected_value));
                     break;
                 }
@@ -488,7 +492,7 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
         shard_id,
         forward_data: vec![ValueSet {
             user_key: b"a".to_vec(),
-            values: vec![Value { content: Some(b"b".to_vec()), version: 1 }],
+            values: vec![Value { content: Some(b"b".to_vec()), version: 1, ..Default::default() }],
         }],
         request: Some(GroupRequestUnion {
             request: Some(Request::Write(ShardWriteRequest {
@@ -508,6 +512,7 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
             shard_id,
             start_version: u64::MAX,
             user_key: b"a".to_vec(),
+            ..Default::default()
         }))
         .await
         .unwrap();
@@ -523,6 +528,7 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
             shard_id,
             start_version: u64::MAX,
             user_key: b"b".to_vec(),
+            ..Default::default()
         }))
         .await
         .unwrap();
@@ -530,7 +536,47 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
         Response::Get(ShardGetResponse { value }) => value,
         _ => panic!("invalid response type, Get is required"),
     };
-    assert!(
-        matches!(value, Some(Value { content: Some(v), version: _ }) if v == b"value".to_vec())
-    );
+    assert!(matches!(value, Some(Value { content: Some(v), .. }) if v == b"value".to_vec()));
+}
+
+/// Split a shard and move the new shard to another group.
+#[sekas_macro::test]
+async fn split_shard_and_move_new_shard() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    let (group_id_1, group_id_2, shard_desc) = create_two_groups(&c, node_ids, 100).await;
+    let shard_id = shard_desc.id;
+    let new_shard_id = shard_id + 1;
+    let split_key = b"key-5".to_vec();
+
+    info!("split shard {shard_id} of group {group_id_1} into shard {new_shard_id}");
+    let mut g = c.group(group_id_1);
+    g.split_shard(shard_id, &split_key, new_shard_id).await.unwrap();
+    // Split is idempotent.
+    g.split_shard(shard_id, &split_key, new_shard_id).await.unwrap();
+    c.assert_group_contains_shard(group_id_1, new_shard_id).await;
+
+    let owner = |i: u64| {
+        if format!("key-{i}").as_bytes() < split_key.as_slice() {
+            shard_id
+        } else {
+            new_shard_id
+        }
+    };
+    for i in 0..100 {
+        validate(&c, group_id_1, owner(i), i..i + 1).await;
+    }
+
+    let new_shard_desc = ShardDesc {
+        id: new_shard_id,
+        range: Some(RangePartition { start: split_key.clone(), end: vec![] }),
+        ..shard_desc
+    };
+    move_shard(&c, &new_shard_desc, group_id_2, group_id_1).await;
+    for i in (0..100).filter(|&i| owner(i) == new_shard_id) {
+        validate(&c, group_id_2, new_shard_id, i..i + 1).await;
+    }
 }
@@ -86,6 +86,12 @@ async fn snapshot_send() {
         id: shard_id,
         collection_id: shard_id,
         range: Some(RangePartition { start: vec![], end: vec![] }),
+        clone_source: None,
+        encrypted: false,
+        compression: CompressionCodec::Uncompressed as i32,
+        key_schema: None,
+        constraints: vec![],
+        frozen: false,
     };
     create_group(&c, group_id, node_ids.clone(), vec![shard_desc]).await;
     insert(&c, group_id, shard_id, 1..100).await;
@@ -39,6 +39,7 @@ async fn txn_table_begin_txn_idempotent() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
@@ -66,6 +67,7 @@ async fn txn_table_commit_txn_idempotent() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
@@ -99,6 +101,7 @@ async fn txn_table_abort_txn_idempotent() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
@@ -131,6 +134,7 @@ async fn txn_table_normal_case() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
@@ -0,0 +1,147 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The test utilities to validate the allocator against random topologies.
+//!
+//! The topologies are generated from seeds, so a failed topology could be
+//! reproduced by its seed.
+
+use std::ops::Range;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use sekas_runtime::ExecutorOwner;
+pub use sekas_server::sim::{SimCluster, SimReport, Simulator, Violation};
+use sekas_server::RootConfig;
+
+/// The label of the nodes which satisfy the constrained shards.
+pub const CONSTRAINT_LABEL: &str = "ssd";
+
+/// The options of generating random topologies.
+#[derive(Debug, Clone)]
+pub struct TopologyOptions {
+    /// The range of the number of nodes.
+    pub num_nodes: Range<usize>,
+    /// The cpu nums of nodes are chosen from it.
+    pub cpu_nums: Vec<f64>,
+    /// The max shards of each group.
+    pub max_shards_per_group: usize,
+    /// The probability of a node labeled with [`CONSTRAINT_LABEL`], and a
+    /// shard constrained by it.
+    pub constraint_ratio: f64,
+}
+
+impl Default for TopologyOptions {
+    fn default() -> Self {
+        TopologyOptions {
+            num_nodes: 3..16,
+            cpu_nums: vec![1.0, 2.0, 4.0, 8.0],
+            max_shards_per_group: 4,
+            constraint_ratio: 0.1,
+        }
+    }
+}
+
+/// Generate a random cluster by the seed. The groups are placed on random
+/// nodes, so the cluster is usually unbalanced.
+pub fn random_cluster(seed: u64, opts: &TopologyOptions, replicas_per_group: usize) -> SimCluster {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let cluster = SimCluster::new();
+    let num_nodes = rng.gen_range(opts.num_nodes.clone()).max(replicas_per_group);
+    let mut has_labeled_nodes = false;
+    for id in 1..=num_nodes as u64 {
+        let cpu_nums = *opts.cpu_nums.choose(&mut rng).unwrap_or(&1.0);
+        let mut labels = vec![];
+        if rng.gen_bool(opts.constraint_ratio) {
+            labels.push(CONSTRAINT_LABEL.to_owned());
+            has_labeled_nodes = true;
+        }
+        cluster.add_node(id, cpu_nums, labels);
+    }
+
+    let node_ids = (1..=num_nodes as u64).collect::<Vec<_>>();
+    let num_groups = rng.gen_range(1..=num_nodes);
+    for _ in 0..num_groups {
+        let nodes =
+            node_ids.choose_multiple(&mut rng, replicas_per_group).cloned().collect::<Vec<_>>();
+        let group_id = cluster.add_group(&nodes);
+        for _ in 0..rng.gen_range(0..=opts.max_shards_per_group) {
+            let mut constraints = vec![];
+            if has_labeled_nodes && rng.gen_bool(opts.constraint_ratio) {
+                constraints.push(CONSTRAINT_LABEL.to_owned());
+            }
+            cluster.add_shard(group_id, constraints);
+        }
+    }
+    cluster
+}
+
+/// Run the simulation for the topologies generated from `seeds`, return the
+/// seeds and reports of the topologies which don't converge within
+/// `max_rounds` or violate the invariants.
+pub fn check_topologies(
+    seeds: Range<u64>,
+    opts: &TopologyOptions,
+    cfg: &RootConfig,
+    max_rounds: usize,
+) -> Vec<(u64, SimReport)> {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    let mut failures = Vec::new();
+    for seed in seeds {
+        let cluster = random_cluster(seed, opts, cfg.replicas_per_group);
+        let sim = Simulator::new(cluster, cfg.clone());
+        let report = executor.block_on(sim.run(max_rounds)).expect("simulation never fails");
+        if !report.converged || !report.violations.is_empty() {
+            failures.push((seed, report));
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_server::BalancePolicyKind;
+
+    use super::*;
+
+    const MAX_ROUNDS: usize = 10000;
+
+    #[test]
+    fn deterministic_topology() {
+        let opts = TopologyOptions::default();
+        let c1 = random_cluster(42, &opts, 3);
+        let c2 = random_cluster(42, &opts, 3);
+        assert_eq!(c1.node_replica_counts(), c2.node_replica_counts());
+        assert!(c1.check_violations().is_empty());
+    }
+
+    #[test]
+    fn converge_random_topologies() {
+        let opts = TopologyOptions::default();
+        let failures = check_topologies(0..1000, &opts, &RootConfig::default(), MAX_ROUNDS);
+        assert!(failures.is_empty(), "{failures:?}");
+    }
+
+    #[test]
+    fn converge_random_topologies_by_policies() {
+        let opts = TopologyOptions::default();
+        for balance_policy in [BalancePolicyKind::Size, BalancePolicyKind::Load] {
+            let cfg = RootConfig { balance_policy, ..Default::default() };
+            let failures = check_topologies(0..100, &opts, &cfg, MAX_ROUNDS);
+            assert!(failures.is_empty(), "{balance_policy:?}: {failures:?}");
+        }
+    }
+}
@@ -23,7 +23,7 @@ use sekas_client::{
     ClientOptions, ConnManager, GroupClient, NodeClient, RootClient, Router, RouterGroupState,
     SekasClient, StaticServiceDiscovery,
 };
-use sekas_server::Result;
+use sekas_server::{diagnosis, Result};
 
 pub async fn node_client_with_retry(addr: &str) -> NodeClient {
     for _ in 0..10000 {
@@ -45,7 +45,6 @@ pub struct ClusterClient {
     client: SekasClient,
 }
 
-#[allow(unused)]
 impl ClusterClient {
     pub async fn new(nodes: HashMap<u64, String>) -> Self {
         let conn_manager = ConnManager::new();
@@ -281,12 +280,16 @@ impl ClusterClient {
                         CollectMovingShardStateRequest { group: group_id },
                     )),
                 }],
+                ..Default::default()
             })
             .await?;
         for resp in &resp.piggybacks {
             match resp.info.as_ref().unwrap() {
                 piggyback_response::Info::SyncRoot(_)
                 | piggyback_response::Info::CollectStats(_)
+                | piggyback_response::Info::SyncDataKeys(_)
+                | piggyback_response::Info::SyncMoveShardLimit(_)
+                | piggyback_response::Info::SyncConfig(_)
                 | piggyback_response::Info::CollectScheduleState(_)
                 | piggyback_response::Info::CollectGroupDetail(_) => {}
                 piggyback_response::Info::CollectMovingShardState(resp) => {
@@ -312,6 +315,7 @@ impl ClusterClient {
                         CollectGroupDetailRequest { groups: vec![group_id] },
                     )),
                 }],
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -319,6 +323,9 @@ impl ClusterClient {
             match resp.info.as_ref().unwrap() {
                 piggyback_response::Info::SyncRoot(_)
                 | piggyback_response::Info::CollectStats(_)
+                | piggyback_response::Info::SyncDataKeys(_)
+                | piggyback_response::Info::SyncMoveShardLimit(_)
+                | piggyback_response::Info::SyncConfig(_)
                 | piggyback_response::Info::CollectScheduleState(_)
                 | piggyback_response::Info::CollectMovingShardState(_) => {}
                 piggyback_response::Info::CollectGroupDetail(resp) => {
@@ -397,4 +404,37 @@ impl ClusterClient {
     pub async fn assert_root_group_has_promoted(&self) {
         self.assert_num_group_voters(0, 3).await;
     }
+
+    /// Read the metadata of the cluster from the admin service of the root.
+    pub async fn metadata(&self) -> diagnosis::Metadata {
+        let root_addr = self.root_addr().await;
+        let resp = reqwest::get(format!("http://{root_addr}/admin/metadata")).await.unwrap();
+        let content = resp.bytes().await.unwrap();
+        serde_json::from_slice(&content).unwrap_or_else(|_| panic!("decode json fail: {content:?}"))
+    }
+
+    /// Wait until the root has nothing to reconcile, eg. the replicas, leaders
+    /// and shards are balanced between nodes.
+    pub async fn wait_balanced(&self) {
+        for _ in 0..1000 {
+            if self.metadata().await.balanced {
+                info!("cluster is balanced");
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("cluster is not balanced in 100 seconds");
+    }
+
+    async fn root_addr(&self) -> String {
+        for addr in self.nodes.values() {
+            let Ok(client) = NodeClient::connect(addr.clone()).await else { continue };
+            if let Ok(root) = client.get_root().await {
+                if let Some(node) = root.root_nodes.first() {
+                    return node.addr.clone();
+                }
+            }
+        }
+        panic!("no available root");
+    }
 }
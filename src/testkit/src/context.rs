@@ -21,9 +21,8 @@ use sekas_runtime::{ExecutorConfig, ExecutorOwner, ShutdownNotifier};
 use sekas_server::{Config, DbConfig, NodeConfig, RaftConfig, RootConfig, *};
 use tempdir::TempDir;
 
-use super::client::node_client_with_retry;
-use super::socket::next_n_avail_port;
-use crate::helper::socket::next_avail_port;
+use crate::client::node_client_with_retry;
+use crate::socket::{next_avail_port, next_n_avail_port};
 
 #[allow(dead_code)]
 pub struct TestContext {
@@ -33,14 +32,21 @@ pub struct TestContext {
     replica_knobs: ReplicaTestingKnobs,
     raft_knobs: RaftTestingKnobs,
     disable_group_promoting: bool,
+    enable_proxy_service: bool,
+    auth: AuthConfig,
+    /// The servers are stopped immediately by default, see
+    /// `enable_graceful_shutdown`.
+    shutdown_timeout_sec: u64,
 
     tick_interval_ms: u64,
 
     notifiers: HashMap<u64, ShutdownNotifier>,
     handles: HashMap<u64, std::thread::JoinHandle<()>>,
+    /// The address, init flag and join list of the spawned servers, to restart
+    /// them.
+    spawned: HashMap<u64, (String, bool, Vec<String>)>,
 }
 
-#[allow(dead_code)]
 impl TestContext {
     pub fn new(prefix: &str) -> Self {
         let root_dir = TempDir::new(prefix).unwrap();
@@ -48,12 +54,16 @@ impl TestContext {
             name: prefix.to_owned(),
             root_dir,
             disable_group_promoting: false,
+            enable_proxy_service: false,
+            auth: AuthConfig::default(),
+            shutdown_timeout_sec: 0,
             replica_knobs: ReplicaTestingKnobs::default(),
             raft_knobs: RaftTestingKnobs::default(),
             root_cfg: RootConfig::default(),
             tick_interval_ms: 500,
             notifiers: HashMap::default(),
             handles: HashMap::default(),
+            spawned: HashMap::default(),
         };
         // Disable all balance by default.
         ctx.disable_all_balance();
@@ -85,6 +95,10 @@ impl TestContext {
         &mut self.raft_knobs
     }
 
+    pub fn enable_proxy_service(&mut self) {
+        self.enable_proxy_service = true;
+    }
+
     pub fn disable_replica_balance(&mut self) {
         self.root_cfg.enable_replica_balance = false;
     }
@@ -108,17 +122,35 @@ impl TestContext {
         self.disable_group_balance();
     }
 
+    pub fn enable_auth(&mut self, cluster_token: &str) {
+        self.auth = AuthConfig {
+            enable: true,
+            cluster_token: cluster_token.to_owned(),
+            user_cache_ttl_sec: 1,
+        };
+    }
+
+    pub fn set_trash_retention(&mut self, retention_sec: u64) {
+        self.root_cfg.trash_retention_sec = retention_sec;
+    }
+
+    pub fn set_bootstrap_spec(&mut self, spec: BootstrapSpec) {
+        self.root_cfg.bootstrap_spec = Some(spec);
+    }
+
+    pub fn enable_graceful_shutdown(&mut self, timeout_sec: u64) {
+        self.shutdown_timeout_sec = timeout_sec;
+    }
+
     pub fn disable_all_node_scheduler(&mut self) {
         self.replica_knobs.disable_scheduler_durable_task = true;
         self.replica_knobs.disable_scheduler_remove_orphan_replica_task = true;
     }
 
-    #[allow(dead_code)]
     pub fn spawn_server(&mut self, idx: usize, addr: &str, init: bool, join_list: Vec<String>) {
         self.spawn_server_with_cfg(idx, addr, 2, init, join_list, self.root_cfg.clone());
     }
 
-    #[allow(dead_code)]
     pub fn spawn_server_with_cfg(
         &mut self,
         idx: usize,
@@ -129,6 +161,7 @@ impl TestContext {
         root: RootConfig,
     ) {
         let addr = addr.to_owned();
+        self.spawned.insert(idx as u64, (addr.clone(), init, join_list.clone()));
         let name = idx.to_string();
         let root_dir = self.root_dir.path().join(name);
         let cfg = Config {
@@ -136,9 +169,10 @@ impl TestContext {
             addr,
             cpu_nums,
             init,
-            enable_proxy_service: false,
+            enable_proxy_service: self.enable_proxy_service,
             join_list,
             node: NodeConfig {
+                shutdown_timeout_sec: Some(self.shutdown_timeout_sec),
                 replica: ReplicaConfig {
                     testing_knobs: self.replica_knobs.clone(),
                     ..Default::default()
@@ -153,6 +187,10 @@ impl TestContext {
             root,
             executor: ExecutorConfig::default(),
             db: DbConfig { max_background_jobs: 2, max_sub_compactions: 1, ..DbConfig::default() },
+            redis: RedisConfig::default(),
+            trace: TraceConfig::default(),
+            tls: None,
+            auth: self.auth.clone(),
         };
         let notifier = ShutdownNotifier::new();
         let shutdown = notifier.subscribe();
@@ -167,7 +205,6 @@ impl TestContext {
     }
 
     /// Create a set of servers and bootstrap all of them.
-    #[allow(dead_code)]
     pub async fn bootstrap_servers(&mut self, num_server: usize) -> HashMap<u64, String> {
         let nodes = self
             .next_n_listen_addrs(num_server)
@@ -214,6 +251,14 @@ impl TestContext {
         }
     }
 
+    /// Restart the stopped server with the same address and data.
+    pub async fn restart_server(&mut self, id: u64) {
+        info!("{} restart server {id}", self.name);
+        let (addr, init, join_list) = self.spawned.get(&id).cloned().expect("server is spawned");
+        self.spawn_server(id as usize, &addr, init, join_list);
+        node_client_with_retry(&addr).await;
+    }
+
     pub async fn wait_election_timeout(&self) {
         tokio::time::sleep(Duration::from_millis(self.tick_interval_ms * 6)).await;
     }
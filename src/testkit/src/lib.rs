@@ -0,0 +1,40 @@
+// Copyright 2023-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Run a Sekas cluster in a process, for the integration tests of Sekas and
+//! the applications built on it.
+//!
+//! ```no_run
+//! use sekas_testkit::{ClusterClient, TestContext};
+//!
+//! # async fn example() {
+//! let mut ctx = TestContext::new("example");
+//! let nodes = ctx.bootstrap_servers(3).await;
+//! let c = ClusterClient::new(nodes).await;
+//! let client = c.app_client().await;
+//! let db = client.create_database("db".to_owned()).await.unwrap();
+//! # }
+//! ```
+//!
+//! The servers are run in the threads of the current process, and they are
+//! stopped once the [`TestContext`] is dropped.
+
+pub mod client;
+pub mod context;
+pub mod init;
+pub mod runtime;
+pub mod socket;
+
+pub use self::client::ClusterClient;
+pub use self::context::TestContext;